@@ -18,5 +18,7 @@ pub struct Args {
 
 fn main() -> anyhow::Result<()> {
     let Args { global, profile } = Args::from_args();
-    rad_profile::cli::main(profile, global.rad_ssh_auth_sock)
+    let format = global.format();
+    let passphrases = global.passphrases()?;
+    rad_profile::cli::main(profile, global.ssh_auth_sock(), passphrases, format)
 }
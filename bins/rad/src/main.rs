@@ -3,6 +3,6 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-fn main() -> anyhow::Result<()> {
-    rad_exe::cli::main()
+fn main() {
+    rad_exe::exit::exit(rad_exe::cli::main())
 }
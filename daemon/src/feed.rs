@@ -0,0 +1,418 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! A WebSocket feed of peer activity.
+//!
+//! Clients connecting to the feed receive a text frame for every [`Message`],
+//! encoded as JSON. Every message is an object with a `type` field naming the
+//! kind of message, e.g.:
+//!
+//! ```json
+//! {"type":"projectReplicated","urn":"rad:git:hnrk...","peer":"hyn..."}
+//! ```
+//!
+//! The feed is send-only, anything clients send is ignored. A client which
+//! can't keep up receives a [`Message::Lagged`] telling how many messages it
+//! missed.
+//!
+//! Clients have to present the [`Token`] the feed was started with in an
+//! `Authorization: Bearer <token>` header of the WebSocket handshake,
+//! otherwise the handshake is rejected with `401 Unauthorized`.
+
+use std::{fmt, net::SocketAddr, sync::Arc};
+
+use futures::{SinkExt as _, StreamExt as _};
+use serde::Serialize;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast::{self, error::RecvError},
+};
+use tokio_tungstenite::tungstenite::{
+    self,
+    handshake::server::{ErrorResponse, Request, Response},
+    http::{header, StatusCode},
+};
+
+use librad::{
+    git::Urn,
+    net::{
+        peer::ProtocolEvent,
+        protocol::{broadcast::PutResult, membership},
+    },
+    PeerId,
+};
+
+use crate::{
+    convert::MaybeFrom,
+    peer::{Event, Status, WaitingRoomEvent, RECEIVER_CAPACITY},
+};
+
+/// Feed errors.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Failure to accept connections.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Failure to serialise a [`Message`].
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// Failure to talk to a client.
+    #[error(transparent)]
+    WebSocket(#[from] Box<tungstenite::Error>),
+}
+
+/// The secret clients of the feed have to present as a bearer token.
+#[derive(Clone)]
+pub struct Token(Arc<str>);
+
+impl Token {
+    /// Create a token from the shared `secret`.
+    pub fn new(secret: impl Into<Arc<str>>) -> Self {
+        Self(secret.into())
+    }
+
+    /// Whether the `Authorization` header of `req` carries this token.
+    fn authorizes(&self, req: &Request) -> bool {
+        req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map_or(false, |given| {
+                // Compare in constant time, so the token can't be guessed byte
+                // by byte
+                given.len() == self.0.len()
+                    && given
+                        .bytes()
+                        .zip(self.0.bytes())
+                        .fold(0, |acc, (a, b)| acc | (a ^ b))
+                        == 0
+            })
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Token(..)")
+    }
+}
+
+/// A message sent to the clients of the feed.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Message {
+    /// The status of the local peer changed.
+    StatusChanged {
+        /// The previous status.
+        old: Status,
+        /// The current status.
+        new: Status,
+    },
+    /// A peer joined the set of peers the local peer is connected to.
+    PeerConnected {
+        /// The connected peer.
+        peer: PeerId,
+    },
+    /// A peer left the set of peers the local peer is connected to.
+    PeerDisconnected {
+        /// The disconnected peer.
+        peer: PeerId,
+    },
+    /// A project was cloned or updated from the network.
+    ProjectReplicated {
+        /// The project.
+        urn: Urn,
+        /// The peer the project was replicated from.
+        peer: PeerId,
+    },
+    /// A request for a project made progress.
+    Request {
+        /// What happened to the request.
+        event: WaitingRoomEvent,
+    },
+    /// The client missed messages because it didn't keep up.
+    Lagged {
+        /// The number of messages missed.
+        skipped: u64,
+    },
+}
+
+impl MaybeFrom<&Event> for Message {
+    fn maybe_from(event: &Event) -> Option<Self> {
+        match event {
+            Event::StatusChanged { old, new } => Some(Self::StatusChanged {
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            Event::Protocol(ProtocolEvent::Membership(transition)) => match transition {
+                membership::Transition::Promoted(info) => {
+                    Some(Self::PeerConnected { peer: info.peer_id })
+                },
+                membership::Transition::Demoted(info) => {
+                    Some(Self::PeerDisconnected { peer: info.peer_id })
+                },
+                membership::Transition::Evicted(info) => {
+                    Some(Self::PeerDisconnected { peer: info.peer_id })
+                },
+            },
+            Event::GossipFetched {
+                provider,
+                gossip,
+                result: PutResult::Applied(_),
+            } => Some(Self::ProjectReplicated {
+                urn: gossip.urn.clone(),
+                peer: provider.peer_id,
+            }),
+            Event::RequestCloned(urn, peer) => Some(Self::ProjectReplicated {
+                urn: urn.clone(),
+                peer: *peer,
+            }),
+            Event::WaitingRoomTransition(transition) => Some(Self::Request {
+                event: transition.event.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Serve the feed of `events` on `addr` until `events` is closed.
+///
+/// Failing to bind to `addr` is only logged, as the feed is auxiliary to
+/// running the peer.
+pub async fn run(addr: SocketAddr, token: Token, events: broadcast::Receiver<Event>) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!(%addr, ?err, "failed to bind event feed");
+            return;
+        },
+    };
+    tracing::info!(%addr, "serving event feed");
+    if let Err(err) = serve(listener, token, events).await {
+        tracing::error!(?err, "event feed failed");
+    }
+}
+
+/// Accept feed clients presenting `token` on `listener` until `events` is
+/// closed.
+///
+/// # Errors
+///
+/// * if accepting a connection fails
+pub async fn serve(
+    listener: TcpListener,
+    token: Token,
+    mut events: broadcast::Receiver<Event>,
+) -> Result<(), Error> {
+    // Serialise each message only once, regardless of the number of clients.
+    let (messages, _) = broadcast::channel::<Arc<String>>(RECEIVER_CAPACITY);
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if let Some(message) = Message::maybe_from(&event) {
+                        // Ignore if there are no clients.
+                        messages.send(Arc::new(serde_json::to_string(&message)?)).ok();
+                    }
+                },
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "event feed lagging behind");
+                    let message = Message::Lagged { skipped };
+                    messages.send(Arc::new(serde_json::to_string(&message)?)).ok();
+                },
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            conn = listener.accept() => {
+                let (stream, remote) = conn?;
+                tokio::spawn({
+                    let token = token.clone();
+                    let messages = messages.subscribe();
+                    async move {
+                        if let Err(err) = client(stream, token, messages).await {
+                            tracing::debug!(%remote, ?err, "event feed client error");
+                        }
+                    }
+                });
+            },
+        }
+    }
+}
+
+/// Forward `messages` to the client on `stream`, until either side goes away.
+///
+/// The handshake is rejected if the client doesn't present `token`.
+async fn client(
+    stream: TcpStream,
+    token: Token,
+    mut messages: broadcast::Receiver<Arc<String>>,
+) -> Result<(), Error> {
+    let authorize = |req: &Request, response: Response| {
+        if token.authorizes(req) {
+            Ok(response)
+        } else {
+            let mut response = ErrorResponse::new(Some("unauthorized".to_owned()));
+            *response.status_mut() = StatusCode::UNAUTHORIZED;
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                header::HeaderValue::from_static("Bearer"),
+            );
+            Err(response)
+        }
+    };
+    let mut ws = tokio_tungstenite::accept_hdr_async(stream, authorize)
+        .await
+        .map_err(Box::new)?;
+    loop {
+        tokio::select! {
+            message = messages.recv() => {
+                let text = match message {
+                    Ok(text) => text.as_ref().clone(),
+                    Err(RecvError::Lagged(skipped)) => {
+                        serde_json::to_string(&Message::Lagged { skipped })?
+                    },
+                    Err(RecvError::Closed) => break,
+                };
+                ws.send(tungstenite::Message::Text(text))
+                    .await
+                    .map_err(Box::new)?;
+            },
+            // Incoming frames need to be polled for control frames to be
+            // answered.
+            incoming = ws.next() => match incoming {
+                None | Some(Ok(tungstenite::Message::Close(_))) => return Ok(()),
+                Some(Ok(_)) => {},
+                Some(Err(err)) => return Err(Box::new(err).into()),
+            },
+        }
+    }
+    ws.close(None).await.map_err(Box::new)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::SocketAddr, str::FromStr as _};
+
+    use assert_matches::assert_matches;
+    use futures::StreamExt as _;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use tokio::{
+        net::{TcpListener, TcpStream},
+        sync::broadcast,
+    };
+    use tokio_tungstenite::{
+        tungstenite::{self, client::IntoClientRequest as _, http::header},
+        MaybeTlsStream,
+        WebSocketStream,
+    };
+
+    use librad::{git::Urn, git_ext::Oid, PeerId, SecretKey};
+
+    use super::{
+        serve,
+        Event,
+        MaybeFrom as _,
+        Message,
+        StatusCode,
+        Token,
+        WaitingRoomEvent,
+        RECEIVER_CAPACITY,
+    };
+
+    /// Connect to the feed at `addr`, presenting `token` if given.
+    async fn connect(
+        addr: SocketAddr,
+        token: Option<&str>,
+    ) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::Error> {
+        let mut req = format!("ws://{}", addr).into_client_request()?;
+        if let Some(token) = token {
+            req.headers_mut().insert(
+                header::AUTHORIZATION,
+                header::HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+            );
+        }
+        let (ws, _) = tokio_tungstenite::connect_async(req).await?;
+        Ok(ws)
+    }
+
+    #[tokio::test]
+    async fn streams_messages_to_authorized_clients() -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (events, receiver) = broadcast::channel(RECEIVER_CAPACITY);
+        let feed = tokio::spawn(serve(listener, Token::new("s3cr3t"), receiver));
+
+        for token in &[None, Some("guess")] {
+            assert_matches!(
+                connect(addr, *token).await,
+                Err(tungstenite::Error::Http(response))
+                    if response.status() == StatusCode::UNAUTHORIZED
+            );
+        }
+        let mut ws = connect(addr, Some("s3cr3t")).await?;
+
+        let urn = Urn::new(Oid::from_str("7ab8629dd6da14dcacde7f65b3d58cd291d7e235")?);
+        let peer = PeerId::from(SecretKey::new());
+        // Not forwarded, so the next frame is the replication
+        events.send(Event::RequestTick)?;
+        events.send(Event::RequestCloned(urn.clone(), peer))?;
+        let frame = ws.next().await.expect("feed closed")?;
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(frame.to_text()?)?,
+            json!({
+                "type": "projectReplicated",
+                "urn": urn.to_string(),
+                "peer": peer.to_string(),
+            })
+        );
+
+        // Closing the events stops the feed, and hangs up on the clients
+        drop(events);
+        feed.await??;
+        assert_matches!(
+            ws.next().await,
+            None | Some(Ok(tungstenite::Message::Close(_)))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn messages_have_a_stable_encoding() -> Result<(), Box<dyn std::error::Error>> {
+        let urn = Urn::new(Oid::from_str("7ab8629dd6da14dcacde7f65b3d58cd291d7e235")?);
+        let peer = PeerId::from(SecretKey::new());
+
+        let message = Message::maybe_from(&Event::RequestCloned(urn.clone(), peer));
+        assert_eq!(
+            serde_json::to_value(&message)?,
+            json!({
+                "type": "projectReplicated",
+                "urn": urn.to_string(),
+                "peer": peer.to_string(),
+            })
+        );
+
+        let message = Message::Request {
+            event: WaitingRoomEvent::Queried { urn: urn.clone() },
+        };
+        assert_eq!(
+            serde_json::to_value(&message)?,
+            json!({
+                "type": "request",
+                "event": {
+                    "type": "queried",
+                    "urn": urn.to_string(),
+                },
+            })
+        );
+
+        assert_eq!(Message::maybe_from(&Event::RequestTick), None);
+
+        Ok(())
+    }
+}
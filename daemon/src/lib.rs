@@ -48,10 +48,13 @@ pub use radicle_git_helpers::remote_helper;
 
 pub mod config;
 pub mod convert;
+pub mod feed;
+pub mod mirror;
 pub mod peer;
 pub use peer::{Control as PeerControl, Event as PeerEvent, Peer, RunConfig, Status as PeerStatus};
 pub mod project;
 pub mod request;
 pub mod state;
+pub mod webhook;
 
 pub mod seed;
@@ -0,0 +1,395 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Mirroring of replicated projects to external git hosts.
+//!
+//! Each project can be configured with a set of external [`Remote`]s, e.g. a
+//! repository on GitHub or GitLab. After a successful replication, the heads
+//! of the project's delegates, as attested by their signed refs, are pushed
+//! there, so that the external repositories serve as read-only mirrors.
+
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use either::Either;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use librad::{
+    git::{identities, refs::Refs, types::Namespace, Urn},
+    net::peer::Peer,
+    PeerId,
+    Signer,
+};
+
+use crate::state;
+
+/// Name for the bucket used in [`kv::Store`].
+const BUCKET_NAME: &str = "mirrors";
+
+/// Mirroring errors.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Failures from [`kv`].
+    #[error(transparent)]
+    Kv(#[from] kv::Error),
+
+    /// Failures from [`git2`], e.g. when pushing.
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    /// Failures verifying the project.
+    #[error(transparent)]
+    Identities(#[from] Box<identities::Error>),
+
+    /// Failures loading the signed refs of a delegate.
+    #[error(transparent)]
+    Refs(#[from] librad::git::refs::stored::Error),
+
+    /// Error occurred when interacting with [`Peer`].
+    #[error(transparent)]
+    State(#[from] Box<state::Error>),
+
+    /// Error in spawned task.
+    #[error(transparent)]
+    Task(#[from] tokio::task::JoinError),
+}
+
+impl From<identities::Error> for Error {
+    fn from(e: identities::Error) -> Self {
+        Self::from(Box::new(e))
+    }
+}
+
+impl From<state::Error> for Error {
+    fn from(e: state::Error) -> Self {
+        Self::from(Box::new(e))
+    }
+}
+
+impl From<librad::net::peer::error::Storage> for Error {
+    fn from(e: librad::net::peer::error::Storage) -> Self {
+        Self::from(state::Error::from(e))
+    }
+}
+
+/// An external repository to mirror a project to.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Remote {
+    /// The URL to push to, e.g. `https://github.com/radicle-dev/radicle-link.git`.
+    pub url: String,
+}
+
+/// Callback to obtain the credentials for pushing to a [`Remote`].
+///
+/// The arguments are the URL, the username if it is part of the URL, and the
+/// kinds of credentials the remote accepts, as for
+/// [`git2::RemoteCallbacks::credentials`].
+#[derive(Clone)]
+pub struct Credentials(
+    Arc<
+        dyn Fn(&str, Option<&str>, git2::CredentialType) -> Result<git2::Cred, git2::Error>
+            + Send
+            + Sync,
+    >,
+);
+
+impl Credentials {
+    /// Constructs [`Credentials`] from a callback.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&str, Option<&str>, git2::CredentialType) -> Result<git2::Cred, git2::Error>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Credentials")
+    }
+}
+
+/// List the [`Remote`]s configured for the project at `urn`.
+///
+/// # Errors
+///
+/// * if the [`kv::Bucket`] can't be accessed
+/// * if the access of the key in the [`kv::Bucket`] fails
+pub fn list(store: &kv::Store, urn: &Urn) -> Result<Vec<Remote>, Error> {
+    let bucket = store.bucket::<&str, kv::Json<Vec<Remote>>>(Some(BUCKET_NAME))?;
+    let key = urn.to_string();
+    Ok(bucket
+        .get(key.as_str())?
+        .map_or_else(Vec::new, |json| json.0))
+}
+
+/// Add a [`Remote`] to mirror the project at `urn` to.
+///
+/// Returns `false` if the [`Remote`] was already configured.
+///
+/// # Errors
+///
+/// * if the [`kv::Bucket`] can't be accessed
+/// * if the access of the key in the [`kv::Bucket`] fails
+pub fn add(store: &kv::Store, urn: &Urn, remote: Remote) -> Result<bool, Error> {
+    let mut remotes = list(store, urn)?;
+    if remotes.contains(&remote) {
+        return Ok(false);
+    }
+    remotes.push(remote);
+    save(store, urn, remotes)?;
+
+    Ok(true)
+}
+
+/// Stop mirroring the project at `urn` to the [`Remote`] with the given `url`.
+///
+/// Returns `false` if no such [`Remote`] was configured.
+///
+/// # Errors
+///
+/// * if the [`kv::Bucket`] can't be accessed
+/// * if the access of the key in the [`kv::Bucket`] fails
+pub fn remove(store: &kv::Store, urn: &Urn, url: &str) -> Result<bool, Error> {
+    let mut remotes = list(store, urn)?;
+    let before = remotes.len();
+    remotes.retain(|remote| remote.url != url);
+    if remotes.len() == before {
+        return Ok(false);
+    }
+    save(store, urn, remotes)?;
+
+    Ok(true)
+}
+
+fn save(store: &kv::Store, urn: &Urn, remotes: Vec<Remote>) -> Result<(), Error> {
+    let bucket = store.bucket::<&str, kv::Json<Vec<Remote>>>(Some(BUCKET_NAME))?;
+    let key = urn.to_string();
+    if remotes.is_empty() {
+        bucket.remove(key.as_str())?;
+    } else {
+        bucket.set(key.as_str(), kv::Json(remotes))?;
+    }
+
+    Ok(())
+}
+
+/// A ref in the monorepo to push to a mirror.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Update {
+    /// The fully qualified name of the ref in the monorepo.
+    src: String,
+    /// The name of the ref on the mirror.
+    dst: String,
+    /// The target of `src`, as attested by the signed refs.
+    oid: git2::Oid,
+}
+
+/// Push the heads of the delegates of the project at `urn` to all [`Remote`]s
+/// configured for it.
+///
+/// The heads of each delegate are pushed to `refs/remotes/<peer id>/heads/*`.
+/// If all delegates agree on the tip of the project's default branch, it is
+/// also pushed to `refs/heads/<default branch>`. Refs on the mirrors are
+/// overwritten.
+///
+/// Failing to push to one [`Remote`] does not prevent pushing to the others,
+/// the failure is only logged.
+///
+/// # Errors
+///
+/// * if the configured [`Remote`]s can't be loaded
+/// * if the project can't be verified
+/// * if the signed refs of the delegates can't be loaded
+pub async fn mirror<S>(
+    peer: &Peer<S>,
+    store: kv::Store,
+    urn: Urn,
+    credentials: Option<Credentials>,
+) -> Result<(), Error>
+where
+    S: Clone + Signer,
+{
+    let remotes = {
+        let urn = urn.clone();
+        spawn_blocking(move || list(&store, &urn)).await??
+    };
+    if remotes.is_empty() {
+        return Ok(());
+    }
+
+    let updates = updates(peer, urn.clone()).await?;
+    if updates.is_empty() {
+        tracing::debug!(%urn, "nothing to mirror");
+        return Ok(());
+    }
+
+    let monorepo = state::monorepo(peer);
+    spawn_blocking(move || {
+        let repo = git2::Repository::open(monorepo)?;
+        let refspecs = updates
+            .iter()
+            .filter_map(|update| match repo.refname_to_id(&update.src) {
+                Ok(oid) if oid == update.oid => Some(format!("+{}:{}", update.src, update.dst)),
+                _ => {
+                    tracing::warn!(src = %update.src, "ref does not match signed refs, skipping");
+                    None
+                },
+            })
+            .collect::<Vec<_>>();
+
+        for remote in remotes {
+            if let Err(err) = push(&repo, &remote, &refspecs, credentials.as_ref()) {
+                tracing::warn!(%urn, url = %remote.url, ?err, "failed to push to mirror");
+            }
+        }
+
+        Ok::<_, Error>(())
+    })
+    .await?
+}
+
+/// Compute the refs to push for the project at `urn`.
+async fn updates<S>(peer: &Peer<S>, urn: Urn) -> Result<Vec<Update>, Error>
+where
+    S: Clone + Signer,
+{
+    let local = peer.peer_id();
+    peer.using_storage(move |storage| {
+        let project = identities::project::verify(storage, &urn)?
+            .ok_or_else(|| state::Error::ProjectNotFound(urn.clone()))?;
+        let namespace = Namespace::from(&urn);
+        let delegates = project
+            .delegations()
+            .iter()
+            .flat_map(|either| match either {
+                Either::Left(pk) => Either::Left(std::iter::once(PeerId::from(*pk))),
+                Either::Right(indirect) => {
+                    Either::Right(indirect.delegations().iter().map(|pk| PeerId::from(*pk)))
+                },
+            })
+            .collect::<Vec<_>>();
+
+        let mut updates = Vec::new();
+        let mut default_tips = BTreeMap::new();
+        let default_branch = project.subject().default_branch.clone();
+        for delegate in delegates {
+            let remote = (delegate != local).then(|| delegate);
+            let refs = match Refs::load(storage, &urn, remote)? {
+                Some(refs) => refs,
+                None => continue,
+            };
+            for (name, oid) in refs.heads() {
+                let oid = git2::Oid::from(oid);
+                let src = match remote {
+                    None => format!("refs/namespaces/{}/refs/heads/{}", namespace, name),
+                    Some(remote) => format!(
+                        "refs/namespaces/{}/refs/remotes/{}/heads/{}",
+                        namespace, remote, name
+                    ),
+                };
+                if default_branch.as_ref().map(|b| b.as_str()) == Some(name.as_str()) {
+                    default_tips.insert(delegate, (src.clone(), oid));
+                }
+                updates.push(Update {
+                    src,
+                    dst: format!("refs/remotes/{}/heads/{}", delegate, name),
+                    oid,
+                });
+            }
+        }
+
+        let mut tips = default_tips.values();
+        if let (Some(branch), Some((src, oid))) = (default_branch, tips.next()) {
+            if tips.all(|(_, other)| other == oid) {
+                updates.push(Update {
+                    src: src.clone(),
+                    dst: format!("refs/heads/{}", branch),
+                    oid: *oid,
+                });
+            } else {
+                tracing::warn!(%urn, "delegates disagree on the default branch, not mirroring it");
+            }
+        }
+
+        Ok(updates)
+    })
+    .await?
+}
+
+/// Push `refspecs` to `remote`.
+fn push(
+    repo: &git2::Repository,
+    remote: &Remote,
+    refspecs: &[String],
+    credentials: Option<&Credentials>,
+) -> Result<(), git2::Error> {
+    let mut callbacks = git2::RemoteCallbacks::new();
+    if let Some(Credentials(credentials)) = credentials {
+        callbacks.credentials(move |url, username, allowed| credentials(url, username, allowed));
+    }
+    let mut rejected = None;
+    callbacks.push_update_reference(|refname, status| {
+        if let Some(msg) = status {
+            rejected = Some(format!("{}: {}", refname, msg));
+        }
+        Ok(())
+    });
+
+    let mut opts = git2::PushOptions::new();
+    opts.remote_callbacks(callbacks);
+    repo.remote_anonymous(&remote.url)?
+        .push(refspecs, Some(&mut opts))?;
+    drop(opts);
+
+    match rejected {
+        None => Ok(()),
+        Some(msg) => Err(git2::Error::from_str(&msg)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom as _;
+
+    use pretty_assertions::assert_eq;
+
+    use librad::{git::Urn, git_ext::RefLike};
+
+    use super::{add, list, remove, Remote};
+
+    #[test]
+    fn add_list_remove() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(dir.path().join("store")))?;
+        let urn = Urn {
+            id: "7ab8629dd6da14dcacde7f65b3d58cd291d7e235"
+                .parse::<radicle_git_ext::Oid>()
+                .expect("oid parse failed"),
+            path: Some(RefLike::try_from("upstream").expect("head was not reflike")),
+        };
+        let github = Remote {
+            url: "https://github.com/radicle-dev/radicle-link.git".to_owned(),
+        };
+        let gitlab = Remote {
+            url: "https://gitlab.com/radicle-dev/radicle-link.git".to_owned(),
+        };
+
+        assert_eq!(list(&store, &urn)?, vec![]);
+        assert!(add(&store, &urn, github.clone())?);
+        assert!(!add(&store, &urn, github.clone())?);
+        assert!(add(&store, &urn, gitlab.clone())?);
+        assert_eq!(list(&store, &urn)?, vec![github.clone(), gitlab.clone()]);
+
+        assert!(remove(&store, &urn, &github.url)?);
+        assert!(!remove(&store, &urn, &github.url)?);
+        assert_eq!(list(&store, &urn)?, vec![gitlab]);
+
+        Ok(())
+    }
+}
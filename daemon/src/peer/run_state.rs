@@ -31,6 +31,7 @@ use crate::{
     convert::MaybeFrom,
     peer::{announcement, control},
     request::waiting_room::{self, WaitingRoom},
+    webhook,
 };
 
 pub mod command;
@@ -94,26 +95,34 @@ impl From<WaitingRoomTransition<SystemTime>> for Event {
     }
 }
 
+impl Event {
+    /// The event to report for an event of the underlying protocol, if any.
+    pub fn from_protocol(event: &ProtocolEvent) -> Option<Self> {
+        match event {
+            ProtocolEvent::Gossip(gossip) => match &**gossip {
+                upstream::Gossip::Put {
+                    provider,
+                    payload,
+                    result,
+                } => Some(Self::GossipFetched {
+                    provider: provider.clone(),
+                    gossip: payload.clone(),
+                    result: result.clone(),
+                }),
+                upstream::Gossip::Want { .. } => None,
+            },
+            event => Some(Self::Protocol(event.clone())),
+        }
+    }
+}
+
 impl MaybeFrom<&Input> for Event {
     fn maybe_from(input: &Input) -> Option<Self> {
         match input {
             Input::Announce(input::Announce::Succeeded(updates)) => {
                 Some(Self::Announced(updates.clone()))
             },
-            Input::Protocol(protocol_event) => match protocol_event {
-                ProtocolEvent::Gossip(gossip) => match &**gossip {
-                    upstream::Gossip::Put {
-                        provider,
-                        payload,
-                        result,
-                    } => Some(Self::GossipFetched {
-                        provider: provider.clone(),
-                        gossip: payload.clone(),
-                        result: result.clone(),
-                    }),
-                },
-                event => Some(Self::Protocol(event.clone())),
-            },
+            Input::Protocol(protocol_event) => Self::from_protocol(protocol_event),
             Input::Request(input::Request::Cloned(urn, remote_peer)) => {
                 Some(Self::RequestCloned(urn.clone(), *remote_peer))
             },
@@ -286,9 +295,16 @@ impl RunState {
                         }
 
                         if let PutResult::Applied(_) = result {
+                            cmds.push(Command::Notify(
+                                webhook::Trigger::Fetched,
+                                urn.clone(),
+                                peer_id,
+                            ));
+                            cmds.push(Command::Mirror(urn.clone()));
                             cmds.push(Command::Include(urn));
                         }
                     },
+                    upstream::Gossip::Want { .. } => {},
                 }
 
                 cmds
@@ -310,8 +326,15 @@ impl RunState {
                     .cloning(&urn, remote_peer, SystemTime::now())
             },
             (_, input::Request::Cloned(urn, remote_peer)) => {
-                self.waiting_room
-                    .cloned(&urn, remote_peer, SystemTime::now())
+                let mut cmds = vec![
+                    Command::Notify(webhook::Trigger::Cloned, urn.clone(), remote_peer),
+                    Command::Mirror(urn.clone()),
+                ];
+                cmds.extend(
+                    self.waiting_room
+                        .cloned(&urn, remote_peer, SystemTime::now()),
+                );
+                cmds
             },
             (_, input::Request::Queried(urn)) => self.waiting_room.queried(&urn, SystemTime::now()),
             (
@@ -7,7 +7,7 @@ use std::time::{Duration, SystemTime};
 
 use librad::{git::Urn, PeerId};
 
-use crate::{peer::control, request::waiting_room::WaitingRoom};
+use crate::{peer::control, request::waiting_room::WaitingRoom, webhook};
 
 /// Instructions to issue side-effectful operations which are the results from
 /// state transitions.
@@ -20,6 +20,11 @@ pub enum Command {
     Control(Control),
     /// Update the include file for the provided [`Urn`].
     Include(Urn),
+    /// Push the provided [`Urn`] to its configured mirrors.
+    Mirror(Urn),
+    /// Notify the configured webhooks about updates to the provided [`Urn`],
+    /// replicated from the provided [`PeerId`].
+    Notify(webhook::Trigger, Urn, PeerId),
     /// Tell the subroutine to persist the [`WaitingRoom`].
     PersistWaitingRoom(WaitingRoom<SystemTime, Duration>),
     /// Fulfill request commands.
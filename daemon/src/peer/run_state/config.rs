@@ -5,7 +5,7 @@
 
 //! Configuration types for configuring the `RunState`.
 
-use std::time::Duration;
+use std::{net::SocketAddr, time::Duration};
 
 /// Default time to wait between announcement subroutine runs.
 const DEFAULT_ANNOUNCE_INTERVAL: Duration = std::time::Duration::from_secs(1);
@@ -27,6 +27,12 @@ pub struct Config {
     pub stats: Stats,
     /// Set of knobs to alter [`WaitingRoom`] behaviour.
     pub waiting_room: WaitingRoom,
+    /// Set of knobs to alter mirroring behaviour.
+    pub mirror: Mirror,
+    /// Set of knobs to alter webhook notifications.
+    pub webhooks: Webhooks,
+    /// Set of knobs to alter the event feed.
+    pub feed: Feed,
 }
 
 /// Set of knobs to alter announce behaviour.
@@ -75,3 +81,31 @@ impl Default for WaitingRoom {
         }
     }
 }
+
+/// Set of knobs to alter mirroring to external git hosts, see
+/// [`crate::mirror`].
+#[derive(Clone, Debug, Default)]
+pub struct Mirror {
+    /// Callback to obtain the credentials for pushing to a mirror. If `None`,
+    /// only mirrors not requiring authentication can be pushed to.
+    pub credentials: Option<crate::mirror::Credentials>,
+}
+
+/// Set of knobs to alter notifying external services about replication events,
+/// see [`crate::webhook`].
+#[derive(Clone, Debug, Default)]
+pub struct Webhooks {
+    /// The URLs to notify. If empty, no notifications are sent.
+    pub endpoints: Vec<crate::webhook::Endpoint>,
+    /// How to retry failed deliveries.
+    pub retry: crate::webhook::Retry,
+}
+
+/// Set of knobs to alter the WebSocket feed of peer events, see
+/// [`crate::feed`].
+#[derive(Clone, Debug, Default)]
+pub struct Feed {
+    /// The address to serve the feed on, and the token clients have to
+    /// present. If `None`, the feed is disabled.
+    pub listen: Option<(SocketAddr, crate::feed::Token)>,
+}
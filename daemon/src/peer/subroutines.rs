@@ -28,8 +28,11 @@ use librad::{
 
 use crate::{
     convert::MaybeFrom as _,
+    feed,
+    mirror,
     request::{self, waiting_room::WaitingRoom},
     state,
+    webhook,
 };
 
 use super::{
@@ -54,6 +57,10 @@ pub struct Subroutines<S> {
     peer: net::peer::Peer<S>,
     /// [`kv::Store`] for suborutine task fulfillment.
     store: kv::Store,
+    /// Credentials for pushing to mirrors.
+    mirror_credentials: Option<mirror::Credentials>,
+    /// Endpoints and retry policy for webhook notifications.
+    webhooks: config::Webhooks,
 
     /// Main peer state machine.
     run_state: RunState,
@@ -199,12 +206,19 @@ where
             coalesced
         };
 
+        let pending_tasks = FuturesUnordered::new();
+        if let Some((addr, token)) = run_config.feed.listen.clone() {
+            pending_tasks.push(tokio::spawn(feed::run(addr, token, subscriber.subscribe())));
+        }
+
         Self {
-            pending_tasks: FuturesUnordered::new(),
+            pending_tasks,
             inputs,
 
             peer,
             store,
+            mirror_credentials: run_config.mirror.credentials.clone(),
+            webhooks: run_config.webhooks.clone(),
             run_state,
 
             subscriber,
@@ -226,6 +240,20 @@ where
                 },
             },
             Command::Include(urn) => tokio::spawn(include::update(self.peer.clone(), urn)),
+            Command::Mirror(urn) => tokio::spawn(update_mirrors(
+                self.peer.clone(),
+                self.store.clone(),
+                urn,
+                self.mirror_credentials.clone(),
+            )),
+            Command::Notify(trigger, urn, provider) => tokio::spawn(notify_webhooks(
+                self.peer.clone(),
+                self.store.clone(),
+                self.webhooks.clone(),
+                trigger,
+                urn,
+                provider,
+            )),
             Command::PersistWaitingRoom(waiting_room) => {
                 tokio::spawn(persist_waiting_room(waiting_room, self.store.clone()))
             },
@@ -333,6 +361,47 @@ where
     }
 }
 
+/// Push the project at `urn` to its configured mirrors, logging any failure.
+async fn update_mirrors<S>(
+    peer: net::peer::Peer<S>,
+    store: kv::Store,
+    urn: Urn,
+    credentials: Option<mirror::Credentials>,
+) where
+    S: Clone + Signer,
+{
+    if let Err(err) = mirror::mirror(&peer, store, urn.clone(), credentials).await {
+        tracing::error!(%urn, ?err, "failed to mirror");
+    }
+}
+
+/// Notify the configured webhooks about updates to the project at `urn`,
+/// logging any failure.
+async fn notify_webhooks<S>(
+    peer: net::peer::Peer<S>,
+    store: kv::Store,
+    webhooks: config::Webhooks,
+    trigger: webhook::Trigger,
+    urn: Urn,
+    provider: PeerId,
+) where
+    S: Clone + Signer,
+{
+    if let Err(err) = webhook::notify(
+        &peer,
+        store,
+        &webhooks.endpoints,
+        webhooks.retry,
+        trigger,
+        urn.clone(),
+        provider,
+    )
+    .await
+    {
+        tracing::error!(%urn, ?err, "failed to notify webhooks");
+    }
+}
+
 /// Fulfill control requests by sending the scheduled responses.
 #[allow(clippy::unused_async)]
 async fn control_respond(cmd: control::Response) {
@@ -0,0 +1,463 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Notification of external services about replication events.
+//!
+//! After a project was cloned or fetched from the network, a JSON [`Payload`]
+//! describing the refs which changed is `POST`ed to each configured
+//! [`Endpoint`]. The body is signed with the key of the local peer: the
+//! `X-Radicle-Peer-Id` header carries the signing peer, and the
+//! `X-Radicle-Signature` header the signature over the exact bytes of the
+//! body, so that receivers can verify the notification originates from a node
+//! they trust.
+//!
+//! Which refs changed is determined by comparing the refs of the project
+//! against a snapshot taken when the previous notification was delivered to
+//! the same [`Endpoint`]. Hence, the first notification for a project reports
+//! all of its refs, and the changes of a notification which could not be
+//! delivered are reported again by the next one.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use futures::future;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use librad::{
+    git::{types::Namespace, Urn},
+    keystore::sign::Signer as _,
+    net::peer::Peer,
+    PeerId,
+    Signature,
+    Signer,
+};
+
+use crate::state;
+
+/// Name for the bucket used in [`kv::Store`].
+const BUCKET_NAME: &str = "webhooks";
+
+/// The ref pointing to the local view of the project identity.
+const RAD_ID: &str = "refs/rad/id";
+
+/// Webhook errors.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Failures from [`kv`].
+    #[error(transparent)]
+    Kv(#[from] kv::Error),
+
+    /// Failures from [`git2`] when reading the refs of a project.
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    /// Failure to serialise the [`Payload`].
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// Failure to sign the [`Payload`].
+    #[error("failed to sign payload")]
+    Sign(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    /// Failure to deliver the [`Payload`] to an [`Endpoint`].
+    #[error(transparent)]
+    Http(#[from] Box<ureq::Error>),
+
+    /// Error in spawned task.
+    #[error(transparent)]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// A URL to `POST` notifications to.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Endpoint {
+    /// The URL, e.g. `https://ci.example.com/hooks/radicle`.
+    pub url: String,
+}
+
+/// How to retry delivering a notification.
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    /// The number of delivery attempts before giving up.
+    pub max_attempts: u32,
+    /// The time to wait before the first retry, doubling with each further
+    /// retry.
+    pub backoff: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The replication event a notification is sent for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Trigger {
+    /// The project was cloned from the network.
+    Cloned,
+    /// Updates to the project were fetched from the network.
+    Fetched,
+}
+
+/// A ref which was created, updated or removed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RefUpdate {
+    /// The name of the ref, relative to the namespace of the project, e.g.
+    /// `refs/remotes/<peer id>/heads/main`.
+    pub name: String,
+    /// The previous target, if the ref existed before.
+    pub old: Option<String>,
+    /// The new target, if the ref was not removed.
+    pub new: Option<String>,
+}
+
+/// A change to the local view of the project identity.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct IdentityUpdate {
+    /// The previous identity commit, if any.
+    pub old: Option<String>,
+    /// The new identity commit.
+    pub new: String,
+}
+
+/// The body of a notification.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payload {
+    /// The event which caused the notification.
+    pub event: Trigger,
+    /// The project.
+    pub urn: Urn,
+    /// The peer the updates were replicated from.
+    pub peer: PeerId,
+    /// The refs which were created, updated or removed.
+    pub refs: Vec<RefUpdate>,
+    /// The change to the project identity, if any.
+    pub identity: Option<IdentityUpdate>,
+}
+
+/// Notify all `endpoints` about the refs of the project at `urn` which changed
+/// since the last notification delivered to them.
+///
+/// Nothing is sent to an [`Endpoint`] if no ref changed for it. Failing to
+/// notify one [`Endpoint`] does not prevent notifying the others, the failure
+/// is only logged.
+///
+/// # Errors
+///
+/// * if the refs of the project can't be read
+pub async fn notify<S>(
+    peer: &Peer<S>,
+    store: kv::Store,
+    endpoints: &[Endpoint],
+    retry: Retry,
+    trigger: Trigger,
+    urn: Urn,
+    provider: PeerId,
+) -> Result<(), Error>
+where
+    S: Clone + Signer,
+{
+    if endpoints.is_empty() {
+        return Ok(());
+    }
+
+    let monorepo = state::monorepo(peer);
+    let current = {
+        let urn = urn.clone();
+        spawn_blocking(move || snapshot(&git2::Repository::open(monorepo)?, &urn)).await??
+    };
+
+    future::join_all(endpoints.iter().map(|endpoint| {
+        let store = store.clone();
+        let urn = urn.clone();
+        let current = current.clone();
+        async move {
+            let res = notify_endpoint(
+                peer, store, endpoint, retry, trigger, urn, provider, current,
+            )
+            .await;
+            if let Err(err) = res {
+                tracing::warn!(url = %endpoint.url, ?err, "failed to deliver webhook");
+            }
+        }
+    }))
+    .await;
+
+    Ok(())
+}
+
+/// Notify `endpoint` about the changes from its snapshot to `current`, and
+/// make `current` its snapshot once the notification was delivered.
+#[allow(clippy::too_many_arguments)]
+async fn notify_endpoint<S>(
+    peer: &Peer<S>,
+    store: kv::Store,
+    endpoint: &Endpoint,
+    retry: Retry,
+    trigger: Trigger,
+    urn: Urn,
+    provider: PeerId,
+    current: BTreeMap<String, String>,
+) -> Result<(), Error>
+where
+    S: Clone + Signer,
+{
+    let previous = {
+        let store = store.clone();
+        let endpoint = endpoint.clone();
+        let urn = urn.clone();
+        spawn_blocking(move || load(&store, &endpoint, &urn)).await??
+    };
+    let (refs, identity) = diff(&previous, &current);
+    if refs.is_empty() {
+        tracing::debug!(%urn, url = %endpoint.url, "no refs changed, not notifying");
+        return Ok(());
+    }
+
+    let body = serde_json::to_vec(&Payload {
+        event: trigger,
+        urn: urn.clone(),
+        peer: provider,
+        refs,
+        identity,
+    })?;
+    let signature = Signature::from(
+        peer.signer()
+            .sign(&body)
+            .await
+            .map_err(|err| Error::Sign(Box::new(err)))?,
+    );
+    deliver(
+        endpoint,
+        peer.peer_id(),
+        &signature.to_string(),
+        body,
+        retry,
+    )
+    .await?;
+
+    let endpoint = endpoint.clone();
+    spawn_blocking(move || save(&store, &endpoint, &urn, &current)).await?
+}
+
+/// `POST` `body` to `endpoint`, retrying with exponential backoff on transport
+/// errors and server-side failures.
+async fn deliver(
+    endpoint: &Endpoint,
+    local: PeerId,
+    signature: &str,
+    body: Vec<u8>,
+    retry: Retry,
+) -> Result<(), Error> {
+    let mut attempt = 0;
+    loop {
+        let res = spawn_blocking({
+            let url = endpoint.url.clone();
+            let signature = signature.to_owned();
+            let body = body.clone();
+            move || {
+                ureq::post(&url)
+                    .set("Content-Type", "application/json")
+                    .set("X-Radicle-Peer-Id", &local.default_encoding())
+                    .set("X-Radicle-Signature", &signature)
+                    .send_bytes(&body)
+            }
+        })
+        .await?;
+
+        attempt += 1;
+        match res {
+            Ok(_) => return Ok(()),
+            Err(err) if attempt < retry.max_attempts && is_transient(&err) => {
+                let delay = retry.backoff.saturating_mul(1_u32 << (attempt - 1).min(16));
+                tracing::debug!(url = %endpoint.url, ?err, ?delay, "retrying webhook");
+                tokio::time::sleep(delay).await;
+            },
+            Err(err) => return Err(Box::new(err).into()),
+        }
+    }
+}
+
+/// Whether delivery may succeed when retried.
+fn is_transient(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::Status(code, _) => *code == 429 || *code >= 500,
+        ureq::Error::Transport(_) => true,
+    }
+}
+
+/// The direct refs of the project at `urn`, relative to its namespace.
+fn snapshot(repo: &git2::Repository, urn: &Urn) -> Result<BTreeMap<String, String>, Error> {
+    let prefix = format!("refs/namespaces/{}/", Namespace::from(urn));
+    let mut refs = BTreeMap::new();
+    for reference in repo.references_glob(&format!("{}*", prefix))? {
+        let reference = reference?;
+        if let (Some(name), Some(target)) = (reference.name(), reference.target()) {
+            if let Some(name) = name.strip_prefix(&prefix) {
+                refs.insert(name.to_owned(), target.to_string());
+            }
+        }
+    }
+
+    Ok(refs)
+}
+
+/// The refs which differ between `previous` and `current`, and the change to
+/// [`RAD_ID`].
+fn diff(
+    previous: &BTreeMap<String, String>,
+    current: &BTreeMap<String, String>,
+) -> (Vec<RefUpdate>, Option<IdentityUpdate>) {
+    let refs = previous
+        .keys()
+        .chain(current.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|name| previous.get(*name) != current.get(*name))
+        .map(|name| RefUpdate {
+            name: name.clone(),
+            old: previous.get(name).cloned(),
+            new: current.get(name).cloned(),
+        })
+        .collect::<Vec<_>>();
+    let identity = refs
+        .iter()
+        .find(|update| update.name == RAD_ID)
+        .and_then(|update| {
+            update.new.as_ref().map(|new| IdentityUpdate {
+                old: update.old.clone(),
+                new: new.clone(),
+            })
+        });
+
+    (refs, identity)
+}
+
+/// The key of the snapshot of the project at `urn` for `endpoint`.
+fn key(endpoint: &Endpoint, urn: &Urn) -> String {
+    format!("{} {}", endpoint.url, urn)
+}
+
+fn load(
+    store: &kv::Store,
+    endpoint: &Endpoint,
+    urn: &Urn,
+) -> Result<BTreeMap<String, String>, Error> {
+    let bucket = store.bucket::<&str, kv::Json<BTreeMap<String, String>>>(Some(BUCKET_NAME))?;
+    let key = key(endpoint, urn);
+    Ok(bucket
+        .get(key.as_str())?
+        .map_or_else(BTreeMap::new, |json| json.0))
+}
+
+fn save(
+    store: &kv::Store,
+    endpoint: &Endpoint,
+    urn: &Urn,
+    refs: &BTreeMap<String, String>,
+) -> Result<(), Error> {
+    let bucket = store.bucket::<&str, kv::Json<BTreeMap<String, String>>>(Some(BUCKET_NAME))?;
+    let key = key(endpoint, urn);
+    bucket.set(key.as_str(), kv::Json(refs.clone()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use pretty_assertions::assert_eq;
+
+    use librad::git::Urn;
+
+    use super::{diff, load, save, Endpoint, IdentityUpdate, RefUpdate};
+
+    #[test]
+    fn diff_reports_new_changed_and_removed_refs() {
+        let previous = vec![
+            ("refs/heads/main", "a"),
+            ("refs/heads/stale", "b"),
+            ("refs/rad/id", "c"),
+        ]
+        .into_iter()
+        .map(|(name, oid)| (name.to_owned(), oid.to_owned()))
+        .collect::<BTreeMap<_, _>>();
+        let current = vec![
+            ("refs/heads/main", "a"),
+            ("refs/heads/next", "d"),
+            ("refs/rad/id", "e"),
+        ]
+        .into_iter()
+        .map(|(name, oid)| (name.to_owned(), oid.to_owned()))
+        .collect::<BTreeMap<_, _>>();
+
+        let (refs, identity) = diff(&previous, &current);
+        assert_eq!(
+            refs,
+            vec![
+                RefUpdate {
+                    name: "refs/heads/next".to_owned(),
+                    old: None,
+                    new: Some("d".to_owned()),
+                },
+                RefUpdate {
+                    name: "refs/heads/stale".to_owned(),
+                    old: Some("b".to_owned()),
+                    new: None,
+                },
+                RefUpdate {
+                    name: "refs/rad/id".to_owned(),
+                    old: Some("c".to_owned()),
+                    new: Some("e".to_owned()),
+                },
+            ]
+        );
+        assert_eq!(
+            identity,
+            Some(IdentityUpdate {
+                old: Some("c".to_owned()),
+                new: "e".to_owned(),
+            })
+        );
+
+        assert_eq!(diff(&current, &current), (vec![], None));
+    }
+
+    #[test]
+    fn snapshots_are_kept_per_endpoint() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(dir.path().join("store")))?;
+        let urn = Urn::new(
+            "7ab8629dd6da14dcacde7f65b3d58cd291d7e235"
+                .parse::<radicle_git_ext::Oid>()
+                .expect("oid parse failed"),
+        );
+        let delivered = Endpoint {
+            url: "https://ci.example.com/hooks/radicle".to_owned(),
+        };
+        let failed = Endpoint {
+            url: "https://down.example.com/hooks/radicle".to_owned(),
+        };
+        let refs = vec![("refs/heads/main".to_owned(), "a".to_owned())]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+
+        save(&store, &delivered, &urn, &refs)?;
+        assert_eq!(load(&store, &delivered, &urn)?, refs);
+        assert_eq!(load(&store, &failed, &urn)?, BTreeMap::new());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,8 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+#![no_main]
+
+libfuzzer_sys::fuzz_target!(|data: &[u8]| link_git::fuzz::pkt_line(data));
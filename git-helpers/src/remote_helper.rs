@@ -7,6 +7,7 @@ use std::{
     env,
     io,
     path::{Path, PathBuf},
+    process::{Command, Stdio},
 };
 
 use librad::{
@@ -19,17 +20,29 @@ use librad::{
         BoxedSigner,
         SomeSigner,
     },
-    git::local::{
-        transport::{CanOpenStorage, LocalTransport, Localio, Mode::Stateful, Settings},
-        url::LocalUrl,
+    git::{
+        identities,
+        local::{
+            hooks::{self, Hook},
+            transport::{CanOpenStorage, LocalTransport, Localio, Mode::Stateful, Settings},
+            url::LocalUrl,
+        },
+        storage::Storage,
+        tracking,
+        types::Namespace,
+        Urn,
     },
     profile::Profile,
+    PeerId,
     PublicKey,
     SecretKey,
 };
 
 use crate::credential;
 
+pub mod url;
+use self::url::Url;
+
 #[derive(Default)]
 pub struct Config {
     /// Signer for radicle artifacts created by pushes.
@@ -40,7 +53,7 @@ pub struct Config {
 const SECRET_KEY_FILE: &str = "librad.key";
 
 pub fn run(config: Config) -> anyhow::Result<()> {
-    let url = {
+    let url: Url = {
         let args = env::args().skip(1).take(2).collect::<Vec<_>>();
         if args.is_empty() {
             return Err(anyhow::anyhow!(
@@ -58,24 +71,30 @@ See https://git-scm.com/docs/git-remote-ext for more detail."#
 
     let git_dir = env::var("GIT_DIR").map(PathBuf::from)?;
 
+    let profile = Profile::load()?;
+    let paths = profile.paths().to_owned();
+    let signer = match config.signer {
+        Some(signer) => signer,
+        None => get_signer(&git_dir, paths.keys_dir(), &url.local)?,
+    };
+    let storage = Storage::open(&paths, signer.clone())?;
+    let remote = url.peer.filter(|peer| peer != storage.peer_id());
+
     let mut transport = {
-        let profile = Profile::load()?;
-        let paths = profile.paths().to_owned();
-        let signer = match config.signer {
-            Some(signer) => signer,
-            None => get_signer(&git_dir, paths.keys_dir(), &url)?,
-        };
         let settings: Box<dyn CanOpenStorage> = Box::new(Settings { paths, signer });
-        Ok::<_, anyhow::Error>(LocalTransport::from(settings))
-    }?;
+        LocalTransport::from(settings).with_policy(profile.settings().load()?.push)
+    };
 
+    let mut wants = Vec::new();
     loop {
         let mut buf = String::with_capacity(32);
-        io::stdin().read_line(&mut buf)?;
+        if io::stdin().read_line(&mut buf)? == 0 {
+            break;
+        }
         let line = buf.trim();
 
         if line == "capabilities" {
-            println!("connect\n\n");
+            println!("connect\nlist\nfetch\n");
             continue;
         }
 
@@ -86,21 +105,186 @@ See https://git-scm.com/docs/git-remote-ext for more detail."#
                 unknown => Err(anyhow::anyhow!("unknown service: {}", unknown)),
             }?;
 
+            if let Some(peer) = remote {
+                if let git2::transport::Service::ReceivePack = service {
+                    return Err(anyhow::anyhow!(
+                        "cannot push to the view of {}, push to {} instead",
+                        peer,
+                        url.local
+                    ));
+                }
+                // Serve the view of `peer` via `list` and `fetch`
+                replicate(&storage, &url.local.urn, peer)?;
+                println!("fallback");
+                continue;
+            }
+
             println!();
 
+            let push = matches!(service, git2::transport::Service::ReceivePack);
             transport
-                .connect(url, service, Stateful, Localio::inherit())?
+                .connect(url.local, service, Stateful, Localio::inherit())?
                 .wait()?;
 
+            if push {
+                post_push(&git_dir);
+            }
+
             break;
         }
 
+        if let Some(peer) = remote {
+            if line == "list" || line == "list for-push" {
+                for (name, oid) in list(&storage, &url.local.urn, peer)? {
+                    println!("{} {}", oid, name);
+                }
+                println!();
+                continue;
+            }
+
+            if let Some(want) = line.strip_prefix("fetch ") {
+                let (_, name) = want
+                    .split_once(' ')
+                    .ok_or_else(|| anyhow::anyhow!("malformed fetch: {}", line))?;
+                wants.push(name.to_owned());
+                continue;
+            }
+
+            if line.is_empty() {
+                if wants.is_empty() {
+                    break;
+                }
+                fetch(&storage, &url.local.urn, peer, &git_dir, wants.drain(..))?;
+                println!();
+                continue;
+            }
+        }
+
         return Err(anyhow::anyhow!("unexpected command: {}", line));
     }
 
     Ok(())
 }
 
+/// Run the [`Hook::PostPush`] of the working copy at `git_dir`, if it is
+/// installed.
+///
+/// The push went through already, so failures are only reported.
+fn post_push(git_dir: &Path) {
+    let res = git2::Repository::open(git_dir)
+        .map_err(hooks::Error::from)
+        .and_then(|repo| hooks::run(&repo, Hook::PostPush));
+    match res {
+        Ok(None) => {},
+        Ok(Some(status)) if status.success() => {},
+        Ok(Some(status)) => eprintln!("warning: post-push hook failed: {}", status),
+        Err(e) => eprintln!("warning: failed to run post-push hook: {}", e),
+    }
+}
+
+/// Track `peer` for `urn` if it isn't already, and replicate `urn` using `rad
+/// sync`, which connects to the seeds of the profile.
+///
+/// If replication fails, but the view of `peer` is already present in the
+/// monorepo, it is served as is.
+fn replicate(storage: &Storage, urn: &Urn, peer: PeerId) -> anyhow::Result<()> {
+    // If `peer` is already tracked, its tracking configuration is kept
+    let _ = tracking::track(
+        storage,
+        urn,
+        Some(peer),
+        tracking::Config::default(),
+        tracking::policy::Track::MustNotExist,
+    )?;
+
+    let status = Command::new("rad")
+        .args(&["sync", "--fetch-only", "--rad-quiet"])
+        .arg(urn.to_string())
+        .stdout(Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => return Ok(()),
+        Ok(status) => eprintln!("warning: replicating {} failed: {}", urn, status),
+        Err(e) => eprintln!("warning: failed to run `rad sync`: {}", e),
+    }
+
+    if list(storage, urn, peer)?.is_empty() {
+        Err(anyhow::anyhow!(
+            "{} has no refs of {} in the monorepo",
+            peer,
+            urn
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The branches and tags of `peer`, named as they are in the repository of
+/// `peer`, and the `HEAD` pointing to the default branch of the project.
+fn list(storage: &Storage, urn: &Urn, peer: PeerId) -> anyhow::Result<Vec<(String, String)>> {
+    let repo = git2::Repository::open(storage.path())?;
+    let prefix = remote_prefix(urn, peer);
+    let mut refs = Vec::new();
+    for category in &["heads", "tags"] {
+        for reference in repo.references_glob(&format!("{}/{}/*", prefix, category))? {
+            let reference = reference?;
+            if let (Some(name), Some(oid)) = (reference.name(), reference.target()) {
+                let name = name.strip_prefix(&format!("{}/", prefix)).unwrap_or(name);
+                refs.push((format!("refs/{}", name), oid.to_string()));
+            }
+        }
+    }
+
+    let head = identities::project::get(storage, urn)?
+        .and_then(|project| project.payload().subject.default_branch.clone())
+        .map(|branch| format!("refs/heads/{}", branch))
+        .filter(|branch| refs.iter().any(|(name, _)| name == branch));
+    if let Some(branch) = head {
+        refs.push(("HEAD".to_owned(), format!("@{}", branch)));
+    }
+
+    Ok(refs)
+}
+
+/// Fetch the objects of the refs `wants`, as named by [`list`], into `git_dir`.
+fn fetch(
+    storage: &Storage,
+    urn: &Urn,
+    peer: PeerId,
+    git_dir: &Path,
+    wants: impl Iterator<Item = String>,
+) -> anyhow::Result<()> {
+    let status = Command::new("git")
+        .env("GIT_DIR", git_dir)
+        .envs(env::vars().filter(|(key, _)| key.starts_with("GIT_TRACE")))
+        .args(&["fetch-pack", "--quiet"])
+        .arg(format!(
+            "--upload-pack=git --namespace={} upload-pack",
+            Namespace::from(urn)
+        ))
+        .arg(storage.path())
+        .args(wants.map(|name| {
+            name.strip_prefix("refs/")
+                .map(|name| format!("refs/remotes/{}/{}", peer, name))
+                .unwrap_or(name)
+        }))
+        .stdout(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("fetch-pack failed: {}", status));
+    }
+
+    Ok(())
+}
+
+fn remote_prefix(urn: &Urn, peer: PeerId) -> String {
+    format!(
+        "refs/namespaces/{}/refs/remotes/{}",
+        Namespace::from(urn),
+        peer
+    )
+}
+
 fn get_signer(git_dir: &Path, keys_dir: &Path, url: &LocalUrl) -> anyhow::Result<BoxedSigner> {
     let mut cred = credential::Git::new(git_dir);
     let pass = cred.get(url)?;
@@ -0,0 +1,79 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use librad::{
+    crypto::peer,
+    git::local::{
+        url::{self, LocalUrl},
+        URL_SCHEME,
+    },
+    PeerId,
+};
+
+/// A URL understood by `git-remote-rad`.
+///
+/// In addition to the [`LocalUrl`] form `rad://<urn id>.git`, which refers to
+/// the local peer's view of a URN, a peer may be given in the user part of the
+/// URL, as in `rad://<peer id>@<urn id>.git`. This refers to the view of that
+/// peer, as replicated into the monorepo.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Url {
+    pub peer: Option<PeerId>,
+    pub local: LocalUrl,
+}
+
+impl From<LocalUrl> for Url {
+    fn from(local: LocalUrl) -> Self {
+        Self { peer: None, local }
+    }
+}
+
+impl Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let local = self.local.to_string();
+        match self.peer {
+            None => f.write_str(&local),
+            Some(peer) => {
+                let prefix = format!("{}://", URL_SCHEME);
+                let rest = local.strip_prefix(&prefix).unwrap_or(&local);
+                write!(f, "{}{}@{}", prefix, peer, rest)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error(transparent)]
+    Local(#[from] url::ParseError),
+
+    #[error("invalid peer id in URL")]
+    Peer(#[from] peer::conversion::Error),
+}
+
+impl FromStr for Url {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let prefix = format!("{}://", URL_SCHEME);
+        match s
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.split_once('@'))
+        {
+            Some((peer, rest)) => Ok(Self {
+                peer: Some(peer.parse()?),
+                local: format!("{}{}", prefix, rest).parse()?,
+            }),
+            None => Ok(Self::from(s.parse::<LocalUrl>()?)),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! [`proptest`] generators for the core types of `librad`.
+//!
+//! Only available with the `arbitrary` feature enabled.
+
+use std::{borrow::Cow, convert::TryFrom as _};
+
+use bstr::BString;
+use link_replication::{refs::Namespaced, Policy, SymrefTarget, Update};
+use proptest::{collection, option, prelude::*};
+use url::Url;
+
+use crate::{
+    canonical::Cstring,
+    git::tracking,
+    git_ext::{Oid, RefLike},
+    identities::{
+        payload::{
+            HasNamespace,
+            Person,
+            PersonPayload,
+            Project,
+            ProjectPayload,
+            SomePayload,
+        },
+        urn::Urn,
+    },
+    PeerId,
+    SecretKey,
+};
+
+pub fn gen_peer_id() -> impl Strategy<Value = PeerId> {
+    any::<[u8; 32]>().prop_map(|seed| PeerId::from(SecretKey::from_seed(seed)))
+}
+
+pub fn gen_oid(kind: git2::ObjectType) -> impl Strategy<Value = Oid> {
+    any::<Vec<u8>>()
+        .prop_map(move |bytes| git2::Oid::hash_object(kind, &bytes).map(Oid::from).unwrap())
+}
+
+/// A valid refname of one to three components below `prefix`.
+pub fn gen_reflike(prefix: &'static str) -> impl Strategy<Value = RefLike> {
+    collection::vec("[a-z0-9]+", 1..=3).prop_map(move |components| {
+        RefLike::try_from(format!("{}/{}", prefix, components.join("/")))
+            .expect("generated refname is valid")
+    })
+}
+
+pub fn gen_urn() -> impl Strategy<Value = Urn<Oid>> {
+    (
+        gen_oid(git2::ObjectType::Tree),
+        option::of(collection::vec("[a-z0-9]+", 1..3)),
+    )
+        .prop_map(|(id, path)| Urn {
+            id,
+            path: path.map(|components| {
+                RefLike::try_from(components.join("/")).expect("generated path is valid")
+            }),
+        })
+}
+
+/// The name of a tracking entry, for either the default or a specific peer.
+pub fn gen_tracking_refname() -> impl Strategy<Value = tracking::reference::RefName<'static, Oid>> {
+    (gen_urn(), option::of(gen_peer_id()))
+        .prop_map(|(urn, peer)| tracking::reference::RefName::new(urn, peer).into_owned())
+}
+
+pub fn gen_cstring() -> impl Strategy<Value = Cstring> {
+    ".*".prop_map(Cstring::from)
+}
+
+lazy_static! {
+    static ref UPSTREAM_USER_NAMESPACE: Url =
+        Url::parse("https://radicle.xyz/upstream/user/v1").unwrap();
+    static ref UPSTREAM_PROJECT_NAMESPACE: Url =
+        Url::parse("https://radicle.xyz/upstream/project/v1").unwrap();
+}
+
+/// A payload extension, so that generated [`PersonPayload`]s exercise the
+/// handling of namespaced extensions.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct UpstreamUser {
+    #[serde(rename = "radicle-registry-name")]
+    pub registered_as: Cstring,
+}
+
+impl HasNamespace for UpstreamUser {
+    fn namespace() -> &'static Url {
+        &UPSTREAM_USER_NAMESPACE
+    }
+}
+
+/// A payload extension, so that generated [`ProjectPayload`]s exercise the
+/// handling of namespaced extensions.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct UpstreamProject {
+    #[serde(rename = "radicle-registry-name")]
+    pub registered_as: Cstring,
+}
+
+impl HasNamespace for UpstreamProject {
+    fn namespace() -> &'static Url {
+        &UPSTREAM_PROJECT_NAMESPACE
+    }
+}
+
+pub fn gen_person() -> impl Strategy<Value = Person> {
+    gen_cstring().prop_map(|name| Person { name })
+}
+
+pub fn gen_upstream_user() -> impl Strategy<Value = UpstreamUser> {
+    gen_cstring().prop_map(|registered_as| UpstreamUser { registered_as })
+}
+
+pub fn gen_project() -> impl Strategy<Value = Project> {
+    (
+        gen_cstring(),
+        option::of(gen_cstring()),
+        option::of(gen_cstring()),
+    )
+        .prop_map(|(name, description, default_branch)| Project {
+            name,
+            description,
+            default_branch,
+        })
+}
+
+pub fn gen_upstream_project() -> impl Strategy<Value = UpstreamProject> {
+    gen_cstring().prop_map(|registered_as| UpstreamProject { registered_as })
+}
+
+pub fn gen_person_payload() -> impl Strategy<Value = PersonPayload> {
+    (gen_person(), option::of(gen_upstream_user())).prop_map(|(person, up)| {
+        let mut p = PersonPayload::new(person);
+        if let Some(up) = up {
+            p.set_ext(up).unwrap();
+        }
+        p
+    })
+}
+
+pub fn gen_project_payload() -> impl Strategy<Value = ProjectPayload> {
+    (gen_project(), option::of(gen_upstream_project())).prop_map(|(project, up)| {
+        let mut p = ProjectPayload::new(project);
+        if let Some(up) = up {
+            p.set_ext(up).unwrap();
+        }
+        p
+    })
+}
+
+pub fn gen_payload() -> impl Strategy<Value = SomePayload> {
+    prop_oneof![
+        gen_person_payload().prop_map(SomePayload::Person),
+        gen_project_payload().prop_map(SomePayload::Project)
+    ]
+}
+
+pub fn gen_policy() -> impl Strategy<Value = Policy> {
+    prop_oneof![
+        Just(Policy::Abort),
+        Just(Policy::Reject),
+        Just(Policy::Allow)
+    ]
+}
+
+/// A replication [`Update`] of a remote tracking head, or of a symbolic ref to
+/// the `rad/id` of another identity.
+pub fn gen_update() -> impl Strategy<Value = Update<'static>> {
+    let direct = (
+        gen_peer_id(),
+        gen_reflike("heads"),
+        gen_oid(git2::ObjectType::Commit),
+        gen_policy(),
+    )
+        .prop_map(|(peer, name, target, no_ff)| Update::Direct {
+            name: Cow::Owned(BString::from(format!("refs/remotes/{}/{}", peer, name))),
+            target: target.into(),
+            no_ff,
+        });
+    let symbolic = (
+        gen_peer_id(),
+        gen_urn(),
+        gen_oid(git2::ObjectType::Commit),
+        gen_policy(),
+    )
+        .prop_map(|(peer, urn, target, type_change)| {
+            let id = urn.encode_id();
+            Update::Symbolic {
+                name: Cow::Owned(BString::from(format!(
+                    "refs/remotes/{}/rad/ids/{}",
+                    peer, id
+                ))),
+                target: SymrefTarget {
+                    name: Namespaced {
+                        namespace: Some(Cow::Owned(BString::from(id))),
+                        refname: Cow::Owned(BString::from("refs/rad/id")),
+                    },
+                    target: target.into(),
+                },
+                type_change,
+            }
+        });
+
+    prop_oneof![direct, symbolic]
+}
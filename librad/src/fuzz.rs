@@ -0,0 +1,19 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fuzzing entry points, see `fuzz/` in the repository root.
+//!
+//! The functions in this module must not panic for any input.
+
+use once_cell::sync::Lazy;
+
+use crate::{git::refs::Signed, PeerId, SecretKey};
+
+static SIGNER: Lazy<PeerId> = Lazy::new(|| PeerId::from(SecretKey::from_seed([42; 32])));
+
+/// Decode and verify `data` as the blob of a peer's signed refs.
+pub fn sigrefs_blob(data: &[u8]) {
+    let _ = Signed::from_json(data, &SIGNER);
+}
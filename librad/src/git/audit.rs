@@ -0,0 +1,418 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! An append-only log of the changes to what the local peer trusts.
+//!
+//! Every local identity revision, change of delegations, and change of
+//! tracking entries is recorded as an [`Entry`] signed by the local peer. The
+//! log is stored as a chain of commits at [`REF`] in a repository of its own
+//! in the profile, see [`crate::paths::Paths::audit_dir`], so it is never
+//! replicated. Each entry names the commit of its predecessor, so that the
+//! signatures cover the order of the log, too.
+//!
+//! Changes are recorded after they were applied, and recording them is
+//! best-effort: a change is not undone if it can't be recorded, but a warning
+//! is logged.
+//!
+//! [`entries`] and [`export`] verify the complete log before handing it out,
+//! so that it can be presented as evidence of what the peer trusted and when.
+
+use std::{
+    collections::BTreeSet,
+    io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use link_canonical::{Cjson, CjsonError};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+use super::storage::{ReadOnly, Storage};
+use crate::{
+    git_ext as ext,
+    identities::git::{Person, Project, Urn},
+    PeerId,
+    Signature,
+    Signer,
+};
+
+/// The ref the log is stored at, in the repository at
+/// [`crate::paths::Paths::audit_dir`].
+pub const REF: &str = "refs/rad/audit";
+
+/// The name of the blob containing the signed [`Entry`] in each commit's tree.
+const BLOB_PATH: &str = "entry";
+
+/// How often to retry appending an entry if the log was modified
+/// concurrently.
+const MAX_RETRIES: usize = 3;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("audit log entry {0} is not a valid record")]
+    Malformed(ext::Oid),
+
+    #[error("invalid signature on audit log entry {0}")]
+    InvalidSignature(ext::Oid),
+
+    #[error("audit log entry {0} is out of order")]
+    Chain(ext::Oid),
+
+    #[error("the audit log was modified concurrently")]
+    ConcurrentlyModified,
+
+    #[error("failed to sign audit log entry")]
+    Sign(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error(transparent)]
+    Cjson(#[from] CjsonError),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// A party an identity delegates to.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Delegate {
+    /// A key, given as the [`PeerId`] it corresponds to.
+    Key(PeerId),
+    /// A person identity.
+    Person(Urn),
+}
+
+/// A change to what the local peer trusts.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Event {
+    /// The local view of the identity at `urn` moved to `revision`, the
+    /// commit of the identity document.
+    #[serde(rename_all = "camelCase")]
+    IdentityRevision {
+        urn: Urn,
+        revision: ext::Oid,
+        /// `None` if the identity was created.
+        previous: Option<ext::Oid>,
+    },
+    /// The delegations of the identity at `urn` changed as of `revision`.
+    #[serde(rename_all = "camelCase")]
+    Delegations {
+        urn: Urn,
+        revision: ext::Oid,
+        added: BTreeSet<Delegate>,
+        removed: BTreeSet<Delegate>,
+    },
+    /// The tracking entry for `peer` was written. A `peer` of `None` denotes
+    /// the default entry for `urn`.
+    #[serde(rename_all = "camelCase")]
+    Tracked {
+        urn: Urn,
+        peer: Option<PeerId>,
+        /// The blob holding the tracking configuration.
+        config: ext::Oid,
+    },
+    /// The tracking entry for `peer` was removed.
+    #[serde(rename_all = "camelCase")]
+    Untracked { urn: Urn, peer: Option<PeerId> },
+}
+
+/// An [`Event`] as recorded in the log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    /// The position in the log, starting at `0`.
+    pub seq: u64,
+    /// Seconds since the Unix epoch at which the entry was recorded.
+    pub timestamp: u64,
+    /// The peer which signed the entry.
+    pub peer: PeerId,
+    /// The commit of the preceding entry.
+    pub previous: Option<ext::Oid>,
+    pub event: Event,
+}
+
+/// A signed [`Entry`], and the commit it is stored at.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Record {
+    pub commit: ext::Oid,
+    pub entry: Entry,
+    pub signature: Signature,
+}
+
+/// The blob stored in the tree of each commit of the log.
+#[derive(Serialize, Deserialize)]
+struct Signed {
+    entry: Entry,
+    signature: Signature,
+}
+
+/// The state of an identity as far as the log is concerned.
+pub(crate) struct Snapshot {
+    urn: Urn,
+    revision: ext::Oid,
+    delegations: BTreeSet<Delegate>,
+}
+
+impl From<&Person> for Snapshot {
+    fn from(person: &Person) -> Self {
+        Self {
+            urn: person.urn(),
+            revision: person.content_id,
+            delegations: person
+                .delegations()
+                .iter()
+                .map(|key| Delegate::Key(PeerId::from(*key)))
+                .collect(),
+        }
+    }
+}
+
+impl From<&Project> for Snapshot {
+    fn from(project: &Project) -> Self {
+        Self {
+            urn: project.urn(),
+            revision: project.content_id,
+            delegations: project
+                .delegations()
+                .iter()
+                .map(|delegate| {
+                    delegate.either(
+                        |key| Delegate::Key(PeerId::from(*key)),
+                        |person| Delegate::Person(person.urn()),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Snapshot {
+    /// The events leading from `previous` to `self`.
+    pub(crate) fn events(self, previous: Option<Snapshot>) -> Vec<Event> {
+        let (prev_revision, prev_delegations) = match previous {
+            Some(prev) => (Some(prev.revision), prev.delegations),
+            None => (None, BTreeSet::new()),
+        };
+        let added = self
+            .delegations
+            .difference(&prev_delegations)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let removed = prev_delegations
+            .difference(&self.delegations)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        let mut events = vec![Event::IdentityRevision {
+            urn: self.urn.clone(),
+            revision: self.revision,
+            previous: prev_revision,
+        }];
+        if !added.is_empty() || !removed.is_empty() {
+            events.push(Event::Delegations {
+                urn: self.urn,
+                revision: self.revision,
+                added,
+                removed,
+            });
+        }
+        events
+    }
+}
+
+/// Append `events` to the log, signed by the [`Storage`]'s key.
+pub fn record<I>(storage: &Storage, events: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Event>,
+{
+    for event in events {
+        let mut retries = 0;
+        loop {
+            match append(storage, event.clone()) {
+                Err(Error::ConcurrentlyModified) if retries < MAX_RETRIES => retries += 1,
+                res => break res,
+            }
+        }?;
+    }
+
+    Ok(())
+}
+
+/// [`record`] `events`, logging a warning if that fails.
+pub(crate) fn try_record<I>(storage: &Storage, events: I)
+where
+    I: IntoIterator<Item = Event>,
+{
+    if let Err(err) = record(storage, events) {
+        warn!(err = %err, "failed to record change in the audit log")
+    }
+}
+
+fn append(storage: &Storage, event: Event) -> Result<ext::Oid, Error> {
+    let raw = &open_or_init(storage.read_only().audit_dir())?;
+    let parent = tip(raw)?;
+    let seq = match &parent {
+        None => 0,
+        Some(parent) => read(raw, parent)?.entry.seq + 1,
+    };
+    let entry = Entry {
+        seq,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_secs())
+            .unwrap_or_default(),
+        peer: *storage.peer_id(),
+        previous: parent.as_ref().map(|parent| parent.id().into()),
+        event,
+    };
+    let signature =
+        futures::executor::block_on(storage.signer().sign(&Cjson(&entry).canonical_form()?))
+            .map_err(|err| Error::Sign(Box::new(err)))?;
+
+    let tree = {
+        let json = serde_json::to_vec(&Signed {
+            entry,
+            signature: signature.into(),
+        })?;
+        let mut builder = raw.treebuilder(None)?;
+        builder.insert(BLOB_PATH, raw.blob(&json)?, 0o100_644)?;
+        raw.find_tree(builder.write()?)?
+    };
+    // The audit repository has no identity configured, unlike the monorepo
+    let author = storage.as_raw().signature()?;
+    let commit = raw.commit(
+        Some(REF),
+        &author,
+        &author,
+        &format!("Record audit log entry {}", seq),
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+    );
+    match commit {
+        Ok(oid) => Ok(oid.into()),
+        Err(e) => match (e.class(), e.code()) {
+            (git2::ErrorClass::Object, git2::ErrorCode::Modified) => {
+                Err(Error::ConcurrentlyModified)
+            },
+            _ => Err(e.into()),
+        },
+    }
+}
+
+/// All entries of the log, oldest first.
+///
+/// The signature of every entry is verified against the [`PeerId`] it names,
+/// and the entries are checked to form an unbroken chain.
+pub fn entries<S>(storage: &S) -> Result<Vec<Record>, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    let raw = match open(storage.as_ref().audit_dir())? {
+        None => return Ok(Vec::new()),
+        Some(raw) => raw,
+    };
+    let mut commit = tip(&raw)?;
+    let mut records = Vec::new();
+    while let Some(current) = commit {
+        let record = read(&raw, &current)?;
+        let parent = match current.parent_count() {
+            0 => None,
+            1 => Some(current.parent(0)?),
+            _ => return Err(Error::Chain(record.commit)),
+        };
+        if record.entry.previous != parent.as_ref().map(|parent| parent.id().into()) {
+            return Err(Error::Chain(record.commit));
+        }
+        let canonical = Cjson(&record.entry).canonical_form()?;
+        if !record.signature.verify(&canonical, &*record.entry.peer) {
+            return Err(Error::InvalidSignature(record.commit));
+        }
+        records.push(record);
+        commit = parent;
+    }
+    records.reverse();
+
+    for (seq, record) in records.iter().enumerate() {
+        if record.entry.seq != seq as u64 {
+            return Err(Error::Chain(record.commit));
+        }
+    }
+
+    Ok(records)
+}
+
+/// Write the verified log to `out`, one JSON-encoded [`Record`] per line.
+///
+/// Returns the number of records written.
+pub fn export<S, W>(storage: &S, mut out: W) -> Result<usize, Error>
+where
+    S: AsRef<ReadOnly>,
+    W: io::Write,
+{
+    let records = entries(storage)?;
+    for record in &records {
+        serde_json::to_writer(&mut out, record)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+
+    Ok(records.len())
+}
+
+fn open(dir: &Path) -> Result<Option<git2::Repository>, Error> {
+    match git2::Repository::open_bare(dir) {
+        Ok(raw) => Ok(Some(raw)),
+        Err(e) if ext::is_not_found_err(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn open_or_init(dir: &Path) -> Result<git2::Repository, Error> {
+    match open(dir)? {
+        Some(raw) => Ok(raw),
+        None => Ok(git2::Repository::init_opts(
+            dir,
+            git2::RepositoryInitOptions::new()
+                .bare(true)
+                .external_template(false),
+        )?),
+    }
+}
+
+fn tip(raw: &git2::Repository) -> Result<Option<git2::Commit<'_>>, Error> {
+    match raw.find_reference(REF) {
+        Ok(reference) => Ok(Some(reference.peel_to_commit()?)),
+        Err(e) if ext::is_not_found_err(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn read(raw: &git2::Repository, commit: &git2::Commit<'_>) -> Result<Record, Error> {
+    let oid = ext::Oid::from(commit.id());
+    let blob = commit
+        .tree()?
+        .get_name(BLOB_PATH)
+        .ok_or(Error::Malformed(oid))?
+        .to_object(raw)?
+        .peel_to_blob()
+        .map_err(|_| Error::Malformed(oid))?;
+    let Signed { entry, signature } =
+        serde_json::from_slice(blob.content()).map_err(|_| Error::Malformed(oid))?;
+    Ok(Record {
+        commit: oid,
+        entry,
+        signature,
+    })
+}
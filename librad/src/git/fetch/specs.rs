@@ -15,6 +15,7 @@ use crate::{
         types::{
             reference::{Reference, RefsCategory},
             AsRemote,
+            Exclusion,
             Fetchspec,
             Force,
             Namespace,
@@ -38,9 +39,13 @@ pub enum Fetchspecs<P, R> {
     /// Request the remote heads matching the signed refs of the respective
     /// tracked peers, as well as top-level delegates found in the identity
     /// document.
+    ///
+    /// Signed refs matching any of `exclude` (eg. `^refs/heads/wip/*`) are
+    /// not requested.
     Replicate {
         tracked_sigrefs: BTreeMap<P, Refs>,
         delegates: BTreeSet<Urn<R>>,
+        exclude: Vec<Exclusion>,
         limit: Limit,
     },
 }
@@ -71,8 +76,16 @@ where
             Self::Replicate {
                 tracked_sigrefs,
                 delegates,
+                exclude,
                 ..
-            } => refspecs::replicate(urn, &remote_peer, remote_heads, tracked_sigrefs, delegates),
+            } => refspecs::replicate(
+                urn,
+                &remote_peer,
+                remote_heads,
+                tracked_sigrefs,
+                delegates,
+                exclude,
+            ),
         }
     }
 
@@ -217,6 +230,7 @@ pub mod refspecs {
         remote_heads: &RemoteHeads,
         tracked_sigrefs: &BTreeMap<P, Refs>,
         delegates: &BTreeSet<Urn<R>>,
+        exclude: &[Exclusion],
     ) -> Vec<Fetchspec>
     where
         P: Clone + Ord + PartialEq + 'static,
@@ -235,6 +249,7 @@ pub mod refspecs {
                     remote_heads,
                     tracked_peer,
                     refs,
+                    exclude,
                 )
             })
             .collect::<Vec<_>>();
@@ -303,6 +318,7 @@ pub mod refspecs {
         remote_heads: &'a RemoteHeads,
         tracked_peer: &'a P,
         refs: &'a Refs,
+        exclude: &'a [Exclusion],
     ) -> impl Iterator<Item = Fetchspec> + 'a
     where
         P: Clone + PartialEq,
@@ -313,6 +329,12 @@ pub mod refspecs {
     {
         refs.iter_categorised()
             .filter_map(move |((name, target), category)| {
+                let qualified = name.clone().into_qualified(category.clone().into());
+                if exclude.iter().any(|excl| excl.matches(&qualified)) {
+                    tracing::debug!("{} is excluded", qualified);
+                    return None;
+                }
+
                 let namespaced_name = namespaced(
                     &namespace,
                     remote_peer,
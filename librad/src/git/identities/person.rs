@@ -9,6 +9,7 @@ use radicle_git_ext::{self as ext, is_not_found_err, OneLevel};
 
 use super::{
     super::{
+        audit,
         refs::Refs,
         storage::{self, ReadOnlyStorage as _, Storage},
         types::Reference,
@@ -125,7 +126,10 @@ where
     person.link(storage, &urn)?;
     Refs::update(storage, &urn)?;
 
-    Ok(person.into_inner().into_inner())
+    let person = person.into_inner().into_inner();
+    audit::try_record(storage, audit::Snapshot::from(&person).events(None));
+
+    Ok(person)
 }
 
 /// Update the [`Person`] at `urn`.
@@ -143,6 +147,7 @@ where
     D: Into<Option<delegation::Direct>> + Debug,
 {
     let prev = get(storage, urn)?.ok_or_else(|| Error::NotFound(urn.clone()))?;
+    let before = audit::Snapshot::from(&prev);
     let prev = Verifying::from(prev).signed()?;
     let next = identities(storage).update(prev, payload, delegations, storage.signer())?;
 
@@ -151,6 +156,27 @@ where
         local_id.link(storage, urn)?;
     }
     Refs::update(storage, urn)?;
+    audit::try_record(storage, audit::Snapshot::from(&next).events(Some(before)));
+
+    Ok(next)
+}
+
+/// Sign the current revision of the [`Person`] at `urn` with the key of
+/// `storage`, without changing it.
+///
+/// This is needed after rotating the key of `storage` (see
+/// [`Storage::rotate_key`]), when the previous key added the new one to the
+/// delegations: the revision only reaches a quorum once both keys signed it.
+#[tracing::instrument(level = "debug", skip(storage))]
+pub fn approve(storage: &Storage, urn: &Urn) -> Result<Person, Error> {
+    let prev = get(storage, urn)?.ok_or_else(|| Error::NotFound(urn.clone()))?;
+    let before = audit::Snapshot::from(&prev);
+    let prev = Verifying::from(prev).signed()?;
+    let next = identities(storage).create_from(prev, storage.signer())?;
+
+    common::IdRef::from(urn).update(storage, next.content_id, "approve")?;
+    Refs::update(storage, urn)?;
+    audit::try_record(storage, audit::Snapshot::from(&next).events(Some(before)));
 
     Ok(next)
 }
@@ -169,12 +195,14 @@ pub fn merge(storage: &Storage, urn: &Urn, from: PeerId) -> Result<Person, Error
         get(storage, &their_urn)?.ok_or(Error::NotFound(their_urn))?
     };
 
+    let before = audit::Snapshot::from(&ours);
     let ours = Verifying::from(ours).signed()?;
     let theirs = Verifying::from(theirs).signed()?;
     let next = identities(storage).update_from(ours, theirs, storage.signer())?;
 
     common::IdRef::from(urn).update(storage, next.content_id, &format!("merge from {}", from))?;
     Refs::update(storage, urn)?;
+    audit::try_record(storage, audit::Snapshot::from(&next).events(Some(before)));
 
     Ok(next)
 }
@@ -10,6 +10,7 @@ use git_ext::{is_not_found_err, OneLevel};
 
 use super::{
     super::{
+        audit,
         refs::Refs as Sigrefs,
         storage::{self, ReadOnlyStorage as _, Storage},
         types::{namespace, reference, Force, Reference, Single, SymbolicRef},
@@ -144,6 +145,7 @@ where
     ProjectRefs::Create(&project).apply(storage)?;
     whoami.link(storage, &urn)?;
     Sigrefs::update(storage, &urn)?;
+    audit::try_record(storage, audit::Snapshot::from(&project).events(None));
 
     Ok(project)
 }
@@ -163,6 +165,7 @@ where
     D: Into<Option<IndirectDelegation>> + Debug,
 {
     let prev = get(storage, urn)?.ok_or_else(|| Error::NotFound(urn.clone()))?;
+    let before = audit::Snapshot::from(&prev);
     let prev = Verifying::from(prev).signed()?;
     let next = identities(storage).update(prev, payload, delegations, storage.signer())?;
 
@@ -171,6 +174,7 @@ where
         local_id.link(storage, urn)?;
     }
     Sigrefs::update(storage, urn)?;
+    audit::try_record(storage, audit::Snapshot::from(&next).events(Some(before)));
 
     Ok(next)
 }
@@ -189,12 +193,14 @@ pub fn merge(storage: &Storage, urn: &Urn, from: PeerId) -> Result<Project, Erro
         get(storage, &their_urn)?.ok_or(Error::NotFound(their_urn))?
     };
 
+    let before = audit::Snapshot::from(&ours);
     let ours = Verifying::from(ours).signed()?;
     let theirs = Verifying::from(theirs).signed()?;
     let next = identities(storage).update_from(ours, theirs, storage.signer())?;
 
     ProjectRefs::Update(&next, &format!("merge from {}", from)).apply(storage)?;
     Sigrefs::update(storage, urn)?;
+    audit::try_record(storage, audit::Snapshot::from(&next).events(Some(before)));
 
     Ok(next)
 }
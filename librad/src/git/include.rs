@@ -14,10 +14,13 @@ use git_ext as ext;
 use tempfile::NamedTempFile;
 
 use super::{
+    identities::relations,
     local::url::LocalUrl,
+    storage::ReadOnly,
     types::{Flat, Force, GenericRef, Reference, Refspec, Remote},
+    Urn,
 };
-use crate::PeerId;
+use crate::{identities::relations::Peer, paths::Paths, PeerId};
 
 /// Config key to reference generated include files in working copies.
 pub const GIT_CONFIG_PATH_KEY: &str = "include.path";
@@ -40,6 +43,15 @@ pub enum Error {
 
     #[error(transparent)]
     Refname(#[from] ext::reference::name::Error),
+
+    #[error(transparent)]
+    Relations(Box<relations::Error>),
+}
+
+impl From<relations::Error> for Error {
+    fn from(err: relations::Error) -> Self {
+        Self::Relations(Box::new(err))
+    }
 }
 
 /// An `Include` is a representation of an include file which we want to
@@ -172,6 +184,10 @@ impl<Path> Include<Path> {
         let handle = handle.into();
         let name = ext::RefLike::try_from(format!("{}@{}", handle, peer))
             .expect("handle and peer are reflike");
+        Self::build_named_remote(url, peer, name)
+    }
+
+    fn build_named_remote(url: LocalUrl, peer: PeerId, name: ext::RefLike) -> Remote<LocalUrl> {
         Remote::new(url, name.clone()).with_fetchspecs(vec![Refspec {
             src: Reference::heads(Flat, peer),
             dst: GenericRef::heads(Flat, name),
@@ -180,9 +196,76 @@ impl<Path> Include<Path> {
     }
 }
 
+/// Regenerate the include file for `urn` from its current set of tracked
+/// peers, returning the path of the file.
+///
+/// The file is stored in [`Paths::git_includes_dir`]. Every tracked peer gets a
+/// remote named `<handle>@<peer_id>`, where the handle is the name found in
+/// the peer's `rad/self`. Peers which have not been replicated yet don't have
+/// a handle, so their remote is named only by their `<peer_id>`.
+///
+/// This should be called whenever the tracking graph of `urn` changes, so that
+/// working copies linked via [`link`] pick up the change.
+#[tracing::instrument(level = "debug", skip(storage, paths))]
+pub fn update<S>(storage: &S, paths: &Paths, urn: &Urn) -> Result<PathBuf, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    let url = LocalUrl::from(urn.clone());
+    let mut include = Include::new(paths.git_includes_dir().to_path_buf(), url.clone());
+    for peer in relations::tracked(storage, urn)? {
+        let peer_id = peer.peer_id();
+        let remote = match Peer::replicated_remote(peer) {
+            Some((peer_id, persona)) => {
+                let handle = ext::RefLike::try_from(persona.person().subject().name.as_str())?;
+                Include::<PathBuf>::build_remote(url.clone(), peer_id, handle)
+            },
+            None => Include::<PathBuf>::build_named_remote(
+                url.clone(),
+                peer_id,
+                ext::RefLike::try_from(peer_id.to_string())?,
+            ),
+        };
+        include.remotes.push(remote);
+    }
+    let path = include.file_path();
+    include.save()?;
+
+    tracing::debug!("updated include file @ '{}'", path.display());
+    Ok(path)
+}
+
+/// Link the working copy `repo` to the include file of `urn`, so that the
+/// remotes of all tracked peers are available to it.
+///
+/// The include file itself is maintained by [`update`], and need not exist
+/// yet. Linking is idempotent, and leaves any other include directives of
+/// `repo` untouched.
+pub fn link(repo: &git2::Repository, paths: &Paths, urn: &Urn) -> Result<PathBuf, Error> {
+    let include_path = Include::new(
+        paths.git_includes_dir().to_path_buf(),
+        LocalUrl::from(urn.clone()),
+    )
+    .file_path();
+    let value = format!("{}", include_path.display());
+
+    let mut config = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+    let mut linked = false;
+    for entry in &config.multivar(GIT_CONFIG_PATH_KEY, None)? {
+        linked |= entry?.value() == Some(value.as_str());
+    }
+    if !linked {
+        // `set_multivar` replaces the values matching the regex, or adds a new
+        // one if none match. Only empty values match `^$`.
+        config.set_multivar(GIT_CONFIG_PATH_KEY, "^$", &value)?;
+    }
+
+    Ok(include_path)
+}
+
 /// Adds an include directive to the `repo`.
 pub fn set_include_path(repo: &git2::Repository, include_path: PathBuf) -> Result<(), Error> {
-    let mut config = repo.config().unwrap();
+    let mut config = repo.config()?;
     config
         .set_str(GIT_CONFIG_PATH_KEY, &format!("{}", include_path.display()))
         .map_err(Error::from)
@@ -3,6 +3,9 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+pub mod hooks;
+pub mod import;
+pub mod receive;
 pub mod transport;
 pub mod url;
 
@@ -0,0 +1,192 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Git hooks for working copies linked to the monorepo.
+//!
+//! The hooks call back into `rad`, so that new commits are published to the
+//! monorepo -- which updates the signed refs -- and announced to the network
+//! without a manual sync step:
+//!
+//! * `post-commit` publishes the branch which was committed to
+//! * `post-push` announces after a push to the `rad` remote. Git doesn't have
+//!   a `post-push` hook, so it is [`run`] by the `rad` remote helper once a
+//!   push went through. Fetches never trigger it.
+//!
+//! Hooks which were not installed by [`install`] are never overwritten, unless
+//! forced to.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+};
+
+use thiserror::Error;
+
+/// Marker identifying the hooks installed by [`install`].
+const MARKER: &str = "# installed by radicle-link";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("the hook at `{0}` was not installed by radicle-link")]
+    Exists(PathBuf),
+
+    #[error("the repository at `{0}` is bare")]
+    Bare(PathBuf),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// The hooks managed by this module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hook {
+    PostCommit,
+    PostPush,
+}
+
+impl Hook {
+    pub const ALL: [Hook; 2] = [Hook::PostCommit, Hook::PostPush];
+
+    /// The name of the hook file in the `hooks` directory.
+    pub fn file_name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    /// The name of the hook as passed to the command, see [`install`].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PostCommit => "post-commit",
+            Self::PostPush => "post-push",
+        }
+    }
+
+    fn script(&self, command: &str) -> String {
+        format!(
+            "#!/bin/sh\n{}\nexec {} {}\n",
+            MARKER,
+            command,
+            self.as_str()
+        )
+    }
+}
+
+impl std::str::FromStr for Hook {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "post-commit" => Ok(Self::PostCommit),
+            "post-push" => Ok(Self::PostPush),
+            _ => Err(format!("unknown hook `{}`", s)),
+        }
+    }
+}
+
+/// Install all [`Hook`]s into the working copy `repo`.
+///
+/// Each hook runs `<command> <hook>`, e.g. `rad hooks run post-commit`. A
+/// hook which already exists, but was not installed by this function, results
+/// in an error unless `force` is set.
+///
+/// Returns the paths of the installed hooks.
+pub fn install(repo: &git2::Repository, command: &str, force: bool) -> Result<Vec<PathBuf>, Error> {
+    let dir = hooks_dir(repo)?;
+    fs::create_dir_all(&dir)?;
+
+    for hook in Hook::ALL.iter() {
+        let path = dir.join(hook.file_name());
+        if !force && path.exists() && !is_ours(&path)? {
+            return Err(Error::Exists(path));
+        }
+    }
+
+    Hook::ALL
+        .iter()
+        .map(|hook| {
+            let path = dir.join(hook.file_name());
+            fs::write(&path, hook.script(command))?;
+            make_executable(&path)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Remove the [`Hook`]s installed by [`install`] from `repo`, leaving other
+/// hooks in place.
+///
+/// Returns the paths of the removed hooks.
+pub fn uninstall(repo: &git2::Repository) -> Result<Vec<PathBuf>, Error> {
+    let dir = hooks_dir(repo)?;
+    let mut removed = Vec::new();
+    for hook in Hook::ALL.iter() {
+        let path = dir.join(hook.file_name());
+        if path.exists() && is_ours(&path)? {
+            fs::remove_file(&path)?;
+            removed.push(path);
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Run `hook` in the working copy `repo` the way git runs its own hooks, if it
+/// is installed.
+///
+/// The standard output of the hook is discarded, as it might be the
+/// channel of the git protocol. Returns `None` if `hook` is not installed.
+pub fn run(repo: &git2::Repository, hook: Hook) -> Result<Option<ExitStatus>, Error> {
+    let path = hooks_dir(repo)?.join(hook.file_name());
+    if !path.exists() {
+        return Ok(None);
+    }
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| Error::Bare(repo.path().to_path_buf()))?;
+    let status = Command::new(&path)
+        .current_dir(workdir)
+        .env("GIT_DIR", repo.path())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+
+    Ok(Some(status))
+}
+
+/// The `hooks` directory of `repo`, honouring `core.hooksPath`.
+fn hooks_dir(repo: &git2::Repository) -> Result<PathBuf, Error> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| Error::Bare(repo.path().to_path_buf()))?;
+    match repo.config()?.get_path("core.hooksPath") {
+        Ok(path) if path.is_absolute() => Ok(path),
+        Ok(path) => Ok(workdir.join(path)),
+        Err(e) if git_ext::is_not_found_err(&e) => Ok(repo.path().join("hooks")),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn is_ours(path: &Path) -> Result<bool, Error> {
+    Ok(fs::read_to_string(path)?.lines().any(|line| line == MARKER))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let mut perms = fs::metadata(path)?.permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(path, perms)
+}
+
+#[cfg(not(unix))]
+fn make_executable(_: &Path) -> io::Result<()> {
+    Ok(())
+}
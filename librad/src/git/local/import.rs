@@ -0,0 +1,240 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Turn an existing git repository into a Radicle project in one go.
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use super::url::LocalUrl;
+use crate::{
+    canonical::Cstring,
+    git::{
+        identities::{self, local::LocalIdentity, project, IndirectDelegation, Project},
+        refs::{self, Refs},
+        storage::Storage,
+        types::{remote::Remote, Force, Refspec},
+        Urn,
+    },
+    git_ext as ext,
+    identities::payload,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("the directory at `{0}` is not a git repository")]
+    NotARepo(PathBuf),
+
+    #[error("the repository at `{0}` is bare, a working copy is required")]
+    Bare(PathBuf),
+
+    #[error("the repository at `{path}` already has a `rad` remote, pointing to `{url}`")]
+    AlreadyLinked { path: PathBuf, url: String },
+
+    #[error("`HEAD` of the repository at `{0}` is not a branch, and no default branch was given")]
+    NoDefaultBranch(PathBuf),
+
+    #[error("the default branch `{0}` does not exist")]
+    MissingDefaultBranch(String),
+
+    #[error("no local identity was given, and no default one is configured")]
+    NoLocalIdentity,
+
+    #[error("the signed refs of {0} were modified concurrently")]
+    SigrefsRace(Urn),
+
+    #[error(transparent)]
+    Identities(#[from] Box<identities::Error>),
+
+    #[error(transparent)]
+    LocalId(#[from] identities::local::Error),
+
+    #[error(transparent)]
+    Sigrefs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+impl From<identities::Error> for Error {
+    fn from(e: identities::Error) -> Self {
+        Self::Identities(Box::new(e))
+    }
+}
+
+/// Optional overrides for [`import`].
+#[derive(Debug, Default)]
+pub struct Options {
+    /// The name of the project, defaults to the name of the directory of the
+    /// working copy.
+    pub name: Option<Cstring>,
+    pub description: Option<Cstring>,
+    /// The default branch of the project, defaults to the branch `HEAD` points
+    /// to.
+    pub default_branch: Option<Cstring>,
+    /// The identity used for `rad/self`, defaults to the default local
+    /// identity.
+    pub whoami: Option<LocalIdentity>,
+}
+
+/// The outcome of an [`import`].
+#[derive(Debug)]
+pub struct Imported {
+    pub project: Project,
+    /// The branches and tags which were imported.
+    pub refs: Vec<String>,
+    /// The commit of the initial signed refs.
+    pub signed_refs: ext::Oid,
+}
+
+/// Create a project from the working copy at `path`.
+///
+/// The project identity delegates to the key of the `storage`. All branches
+/// and tags of the working copy are imported into the project's namespace,
+/// and signed. Finally, a `rad` remote is added to the working copy, which
+/// becomes the upstream of the default branch.
+#[tracing::instrument(skip(storage, opts), fields(path = %path.display()))]
+pub fn import(storage: &Storage, path: &Path, opts: Options) -> Result<Imported, Error> {
+    let repo = git2::Repository::open(path).map_err(|e| {
+        if ext::is_not_found_err(&e) {
+            Error::NotARepo(path.to_path_buf())
+        } else {
+            e.into()
+        }
+    })?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| Error::Bare(path.to_path_buf()))?
+        .to_path_buf();
+    if let Ok(rad) = repo.find_remote("rad") {
+        return Err(Error::AlreadyLinked {
+            path: workdir,
+            url: rad.url().unwrap_or_default().to_owned(),
+        });
+    }
+
+    let default_branch = match opts.default_branch {
+        Some(branch) => branch,
+        None => {
+            let head = repo.head()?;
+            if !head.is_branch() {
+                return Err(Error::NoDefaultBranch(workdir));
+            }
+            head.shorthand()
+                .map(Cstring::from)
+                .ok_or_else(|| Error::NoDefaultBranch(workdir.clone()))?
+        },
+    };
+    if repo
+        .find_branch(default_branch.as_str(), git2::BranchType::Local)
+        .is_err()
+    {
+        return Err(Error::MissingDefaultBranch(default_branch.to_string()));
+    }
+    let name = match opts.name {
+        Some(name) => name,
+        None => Cstring::from(
+            workdir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        ),
+    };
+    let whoami = match opts.whoami {
+        Some(whoami) => whoami,
+        None => identities::local::default(storage)?.ok_or(Error::NoLocalIdentity)?,
+    };
+
+    let project = project::create(
+        storage,
+        whoami,
+        payload::Project {
+            name,
+            description: opts.description,
+            default_branch: Some(default_branch.clone()),
+        },
+        IndirectDelegation::from(*storage.peer_id().as_public_key()),
+    )?;
+    let urn = project.urn();
+
+    let refs = fetch_into(storage, &repo, &urn)?;
+    let signed_refs = match Refs::update(storage, &urn)? {
+        refs::Updated::Updated { at, .. } | refs::Updated::Unchanged { at, .. } => at.into(),
+        refs::Updated::ConcurrentlyModified => return Err(Error::SigrefsRace(urn)),
+    };
+
+    link(&repo, &urn, default_branch.as_str())?;
+
+    Ok(Imported {
+        project,
+        refs,
+        signed_refs,
+    })
+}
+
+/// Fetch the branches and tags of `repo` into the namespace of `urn`.
+///
+/// Returns the names of the fetched refs.
+fn fetch_into(storage: &Storage, repo: &git2::Repository, urn: &Urn) -> Result<Vec<String>, Error> {
+    let namespace = format!("refs/namespaces/{}", urn.encode_id());
+    let specs = ["refs/heads/*", "refs/tags/*"]
+        .iter()
+        .map(|pattern| format!("+{}:{}/{}", pattern, namespace, pattern))
+        .collect::<Vec<_>>();
+
+    let url = format!("file://{}", repo.path().display());
+    let mut remote = storage.as_raw().remote_anonymous(&url)?;
+    remote.fetch(&specs, None, None)?;
+
+    let mut refs = Vec::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        if let Some(name) = reference.name() {
+            if name.starts_with("refs/heads/") || name.starts_with("refs/tags/") {
+                refs.push(name.to_owned());
+            }
+        }
+    }
+    refs.sort();
+
+    Ok(refs)
+}
+
+/// Add the `rad` remote to `repo`, record the imported branches as its
+/// remote tracking branches, and make it the upstream of `default_branch`.
+fn link(repo: &git2::Repository, urn: &Urn, default_branch: &str) -> Result<(), Error> {
+    let mut rad = Remote::rad_remote(
+        LocalUrl::from(urn.clone()),
+        Refspec {
+            src: refspec_pattern!("refs/heads/*"),
+            dst: refspec_pattern!("refs/remotes/rad/*"),
+            force: Force::True,
+        },
+    );
+    rad.save(repo)?;
+
+    for branch in repo.branches(Some(git2::BranchType::Local))? {
+        let (branch, _) = branch?;
+        if let (Some(name), Some(target)) = (branch.name()?, branch.get().target()) {
+            repo.reference(
+                &format!("refs/remotes/rad/{}", name),
+                target,
+                true,
+                "imported into radicle",
+            )?;
+        }
+    }
+
+    let mut config = repo.config()?;
+    config.set_str(&format!("branch.{}.remote", default_branch), "rad")?;
+    config.set_str(
+        &format!("branch.{}.merge", default_branch),
+        &format!("refs/heads/{}", default_branch),
+    )?;
+
+    Ok(())
+}
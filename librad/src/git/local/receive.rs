@@ -0,0 +1,191 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Validation of pushes from working copies into the monorepo.
+//!
+//! Pushes are received by `git receive-pack` running in the monorepo, scoped to
+//! the namespace of the project (see [`super::transport::command`]). Before
+//! any ref is updated, a `pre-receive` hook checks the pushed refs against a
+//! [`Policy`]:
+//!
+//! * only branches and tags can be pushed, as those are the refs owned by the
+//!   local peer. Remote tracking branches and the `rad` refs are off-limits,
+//!   regardless of the policy
+//! * the names of the pushed refs must match one of [`Policy::refs`]
+//! * if [`Policy::require_signed`] is set, all commits which are new to the
+//!   namespace must be signed. Commits reachable from other namespaces only
+//!   count as new
+//!
+//! If any ref is rejected, the whole push is, and the reasons are reported to
+//! the git client (prefixed with `remote:`).
+//!
+//! The hook is a shell script generated from the [`Policy`], and installed
+//! into a directory of the monorepo keyed by its content. It is activated per
+//! push via `core.hooksPath`, so pushes with different policies don't
+//! interfere.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Marker identifying hooks generated by this module.
+const MARKER: &str = "# generated by radicle-link";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("invalid ref pattern `{0}`: must be below `refs/heads/` or `refs/tags/`")]
+    Namespace(String),
+
+    #[error("invalid ref pattern `{0}`: may only contain alphanumerics and `/._-*`")]
+    Pattern(String),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The rules a push into the monorepo must adhere to.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Policy {
+    /// Patterns of the refs which can be pushed, where `*` matches any
+    /// sequence of characters (including `/`). By default, all branches and
+    /// tags.
+    pub refs: Vec<String>,
+    /// Whether all pushed commits must be signed.
+    ///
+    /// Note that the signature is not verified, as the monorepo doesn't know
+    /// which keys are acceptable.
+    pub require_signed: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            refs: vec!["refs/heads/*".to_owned(), "refs/tags/*".to_owned()],
+            require_signed: false,
+        }
+    }
+}
+
+impl Policy {
+    /// Check that all [`Policy::refs`] are well-formed.
+    pub fn validate(&self) -> Result<(), Error> {
+        for pattern in &self.refs {
+            if !pattern
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "/._-*".contains(c))
+            {
+                return Err(Error::Pattern(pattern.clone()));
+            }
+            if !(pattern.starts_with("refs/heads/") || pattern.starts_with("refs/tags/")) {
+                return Err(Error::Namespace(pattern.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The `pre-receive` hook enforcing this policy.
+    pub fn script(&self) -> Result<String, Error> {
+        self.validate()?;
+
+        let allowed = if self.refs.is_empty() {
+            String::new()
+        } else {
+            format!("        {}) ;;\n", self.refs.join("|"))
+        };
+        let signed = if self.require_signed {
+            r#"    for commit in $(git rev-list "$new" --not --glob="refs/namespaces/$GIT_NAMESPACE/*")
+    do
+        if ! git cat-file commit "$commit" | sed '/^$/q' | grep -q '^gpgsig'
+        then
+            echo "rejected $ref: commit $commit is not signed" >&2
+            status=1
+            break
+        fi
+    done
+"#
+        } else {
+            ""
+        };
+
+        Ok(format!(
+            r#"#!/bin/sh
+{marker}
+zero=0000000000000000000000000000000000000000
+status=0
+while read -r old new ref
+do
+    case "$ref" in
+        refs/heads/*|refs/tags/*) ;;
+        *)
+            echo "rejected $ref: only branches and tags of the local peer can be pushed" >&2
+            status=1
+            continue
+            ;;
+    esac
+    case "$ref" in
+{allowed}        *)
+            echo "rejected $ref: not allowed by the push policy" >&2
+            status=1
+            continue
+            ;;
+    esac
+    [ "$new" = "$zero" ] && continue
+{signed}done
+exit $status
+"#,
+            marker = MARKER,
+            allowed = allowed,
+            signed = signed
+        ))
+    }
+}
+
+/// Install the `pre-receive` hook enforcing `policy` into the monorepo at
+/// `git_dir`.
+///
+/// Returns the directory containing the hook, suitable as the value of
+/// `core.hooksPath`. Installing the same policy again is a no-op.
+pub fn install(git_dir: &Path, policy: &Policy) -> Result<PathBuf, Error> {
+    let script = policy.script()?;
+    let oid = git2::Oid::hash_object(git2::ObjectType::Blob, script.as_bytes())?;
+    let dir = git_dir.join("hooks").join(format!("radicle-{}", oid));
+    let hook = dir.join("pre-receive");
+    if hook.exists() {
+        return Ok(dir);
+    }
+
+    fs::create_dir_all(&dir)?;
+    let tmp = dir.join("pre-receive.tmp");
+    fs::write(&tmp, script)?;
+    make_executable(&tmp)?;
+    fs::rename(&tmp, &hook)?;
+
+    Ok(dir)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let mut perms = fs::metadata(path)?.permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(path, perms)
+}
+
+#[cfg(not(unix))]
+fn make_executable(_: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -24,6 +24,7 @@ use super::{
         types::Namespace,
         Urn,
     },
+    receive,
     url::LocalUrl,
 };
 use crate::paths::Paths;
@@ -57,6 +58,9 @@ pub enum Error {
     #[error(transparent)]
     LocalId(#[from] identities::local::Error),
 
+    #[error(transparent)]
+    Receive(#[from] receive::Error),
+
     #[error(transparent)]
     Git(#[from] git2::Error),
 
@@ -158,23 +162,31 @@ pub enum Mode {
 #[derive(Clone)]
 pub struct LocalTransport {
     storage: Arc<Box<dyn CanOpenStorage>>,
+    policy: receive::Policy,
 }
 
 impl From<Arc<Box<dyn CanOpenStorage>>> for LocalTransport {
     fn from(storage: Arc<Box<dyn CanOpenStorage>>) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            policy: receive::Policy::default(),
+        }
     }
 }
 
 impl From<Box<dyn CanOpenStorage>> for LocalTransport {
     fn from(storage: Box<dyn CanOpenStorage>) -> Self {
-        Self {
-            storage: Arc::new(storage),
-        }
+        Self::from(Arc::new(storage))
     }
 }
 
 impl LocalTransport {
+    /// Validate pushes according to `policy`, instead of the
+    /// [`receive::Policy::default`].
+    pub fn with_policy(self, policy: receive::Policy) -> Self {
+        Self { policy, ..self }
+    }
+
     #[tracing::instrument(level = "debug", skip(self, service, stdio))]
     pub fn connect(
         &mut self,
@@ -187,35 +199,7 @@ impl LocalTransport {
         let storage = _box.as_ref();
 
         let urn = url.into();
-        guard_has_urn(storage.as_ref(), &urn)?;
-
-        let mut git = Command::new("git");
-        git.envs(::std::env::vars().filter(|(key, _)| key.starts_with("GIT_TRACE")))
-            .current_dir(storage.as_ref().path())
-            .args(&[
-                &format!("--namespace={}", Namespace::from(&urn)),
-                "-c",
-                "transfer.hiderefs=refs/",
-                "-c",
-                "transfer.hiderefs=!refs/heads",
-                "-c",
-                "transfer.hiderefs=!refs/tags",
-            ]);
-
-        match service {
-            Service::UploadPack | Service::UploadPackLs => {
-                // Fetching remotes is ok, pushing is not
-                visible_remotes(storage.as_ref(), &urn)?.for_each(|remote_ref| {
-                    git.arg("-c")
-                        .arg(format!("uploadpack.hiderefs=!^{}", remote_ref));
-                });
-                git.args(&["upload-pack", "--strict", "--timeout=5"]);
-            },
-
-            Service::ReceivePack | Service::ReceivePackLs => {
-                git.arg("receive-pack");
-            },
-        }
+        let mut git = command(storage.as_ref(), &urn, service, &self.policy)?;
 
         if let Mode::Stateless = mode {
             git.arg("--stateless-rpc");
@@ -275,6 +259,72 @@ impl LocalTransport {
     }
 }
 
+/// Prepare the `git` command serving `service` for `urn` from `storage`.
+///
+/// Only branches and tags are visible, and those of tracked peers can be
+/// fetched, but not pushed to. Fetches may be filtered, e.g. for blobless
+/// clones, and may ask for any object reachable from the visible refs, as
+/// promisor remotes do to fill in missing objects.
+///
+/// Pushes are validated according to `policy`, see [`receive`].
+///
+/// The command runs in the monorepo, and is missing the directory argument, as
+/// well as `--stateless-rpc` or `--advertise-refs` if those are required.
+pub fn command<S>(
+    storage: S,
+    urn: &Urn,
+    service: Service,
+    policy: &receive::Policy,
+) -> Result<Command, Error>
+where
+    S: AsRef<storage::ReadOnly>,
+{
+    let storage = storage.as_ref();
+    guard_has_urn(storage, urn)?;
+
+    let mut git = Command::new("git");
+    git.envs(::std::env::vars().filter(|(key, _)| key.starts_with("GIT_TRACE")))
+        .current_dir(storage.path())
+        .args(&[
+            &format!("--namespace={}", Namespace::from(urn)),
+            "-c",
+            "transfer.hiderefs=refs/",
+            "-c",
+            "transfer.hiderefs=!refs/heads",
+            "-c",
+            "transfer.hiderefs=!refs/tags",
+        ]);
+
+    match service {
+        Service::UploadPack | Service::UploadPackLs => {
+            // Fetching remotes is ok, pushing is not
+            visible_remotes(storage, urn)?.for_each(|remote_ref| {
+                git.arg("-c")
+                    .arg(format!("uploadpack.hiderefs=!^{}", remote_ref));
+            });
+            // Serve partial clones, and the objects they lazily fetch later on
+            git.args(&[
+                "-c",
+                "uploadpack.allowfilter=true",
+                "-c",
+                "uploadpack.allowreachablesha1inwant=true",
+                "upload-pack",
+                "--strict",
+                "--timeout=5",
+            ]);
+        },
+
+        Service::ReceivePack | Service::ReceivePackLs => {
+            let hooks = receive::install(storage.path(), policy)?;
+            git.arg("-c")
+                .arg(format!("core.hooksPath={}", hooks.display()))
+                .arg("receive-pack");
+        },
+    }
+
+    Ok(git)
+}
+
 fn guard_has_urn<S>(storage: S, urn: &Urn) -> Result<(), Error>
 where
     S: AsRef<storage::ReadOnly>,
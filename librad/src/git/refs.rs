@@ -10,8 +10,10 @@ use std::{
     marker::PhantomData,
     ops::{Deref, DerefMut},
     path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+pub mod cache;
 mod serde_impls;
 
 use git_ext::{is_not_found_err, reference};
@@ -39,6 +41,17 @@ pub use git_ext::Oid;
 // TODO(kim): bubble up as parameter
 pub const TRACKING_GRAPH_DEPTH: usize = 3;
 
+/// The version of the [`Freshness`] attestation written by this library.
+pub const FRESHNESS_VERSION: u32 = 1;
+
+/// How long [`Refs::update`] carries over the [`Freshness`] of otherwise
+/// unchanged [`Refs`], before attesting them anew.
+pub const FRESHNESS_REFRESH: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The key under which the [`Freshness`] is serialised alongside the
+/// categories. `^` is not valid in a ref name, so it can't clash with one.
+const FRESHNESS_KEY: &str = "^freshness";
+
 /// The transitive tracking graph.
 // **NOTE**: A recursion limit of 128 is imposed by `serde_json` when deserialising.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -157,6 +170,77 @@ impl<'a, A: Ord> Iterator for Flatten<'a, A> {
     }
 }
 
+/// A signed attestation of when [`Refs`] were published.
+///
+/// Attestations are only written if enabled via
+/// [`storage::Config::set_sigrefs_freshness`], as older versions of this
+/// library reject signed refs carrying one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Freshness {
+    /// Sequence number, incremented with every attestation of the publishing
+    /// peer.
+    pub seq: u64,
+    /// Seconds since the unix epoch, according to the publishing peer's
+    /// clock.
+    pub timestamp: u64,
+}
+
+impl Freshness {
+    /// The first attestation, made now.
+    pub fn new() -> Self {
+        Self {
+            seq: 0,
+            timestamp: unix_now(),
+        }
+    }
+
+    /// The attestation succeeding `self`, made now.
+    pub fn next(&self) -> Self {
+        Self {
+            seq: self.seq + 1,
+            timestamp: unix_now(),
+        }
+    }
+
+    /// The [`SystemTime`] of the attestation, or `None` if the timestamp can
+    /// not be represented on this platform.
+    pub fn time(&self) -> Option<SystemTime> {
+        UNIX_EPOCH.checked_add(Duration::from_secs(self.timestamp))
+    }
+
+    /// How long ago the attestation was made.
+    pub fn age(&self) -> Duration {
+        self.age_at(SystemTime::now())
+    }
+
+    /// How long before `now` the attestation was made.
+    ///
+    /// Attestations from the future, eg. due to clock skew, are zero seconds
+    /// old.
+    pub fn age_at(&self, now: SystemTime) -> Duration {
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Duration::from_secs(now.saturating_sub(self.timestamp))
+    }
+
+    /// Whether the attestation is older than `threshold`.
+    pub fn is_stale(&self, threshold: Duration) -> bool {
+        self.age() > threshold
+    }
+}
+
+impl Default for Freshness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 pub mod signing {
     use super::*;
     use std::error;
@@ -197,6 +281,9 @@ pub mod stored {
         #[error(transparent)]
         Store(#[from] storage::Error),
 
+        #[error(transparent)]
+        Config(#[from] storage::config::Error),
+
         #[error(transparent)]
         Git(#[from] git2::Error),
 
@@ -233,6 +320,9 @@ pub struct Refs {
     /// Note that this does does not include the oids, as they can be determined
     /// by inspecting the `rad/signed_refs` of the respective remote.
     pub remotes: Remotes<PeerId>,
+
+    /// When the [`Refs`] were published, if attested by the publishing peer.
+    pub freshness: Option<Freshness>,
 }
 
 impl Refs {
@@ -311,6 +401,7 @@ impl Refs {
         Ok(Self {
             categorised_refs,
             remotes,
+            freshness: None,
         })
     }
 
@@ -334,12 +425,26 @@ impl Refs {
 
     /// Compute the current [`Refs`], sign them, and store them at the
     /// `rad/signed_refs` branch of [`Urn`].
+    ///
+    /// If [`Freshness`] attestations are enabled, the stored [`Refs`] are
+    /// attested anew if they changed, or if their previous attestation is older
+    /// than [`FRESHNESS_REFRESH`].
     #[tracing::instrument(skip(storage, urn), fields(urn = %urn, local_peer = %storage.peer_id()))]
     pub fn update(storage: &Storage, urn: &Urn) -> Result<Updated, stored::Error> {
         let branch = Reference::rad_signed_refs(Namespace::from(urn), None);
         tracing::debug!("updating signed refs for {}", branch);
 
-        let signed_refs = Self::compute(storage, urn)?.sign(storage.signer())?;
+        let mut refs = Self::compute(storage, urn)?;
+        if storage.config_readonly()?.sigrefs_freshness()? {
+            let previous = Self::load(storage, urn, None)?
+                .and_then(|prev| prev.freshness.map(|fresh| (prev.same_refs(&refs), fresh)));
+            refs.freshness = Some(match previous {
+                Some((true, fresh)) if fresh.age() < FRESHNESS_REFRESH => fresh,
+                Some((_, fresh)) => fresh.next(),
+                None => Freshness::new(),
+            });
+        }
+        let signed_refs = refs.sign(storage.signer())?;
 
         let raw_git = storage.as_raw();
 
@@ -423,6 +528,7 @@ impl Refs {
         let Refs {
             categorised_refs,
             remotes: _,
+            freshness: _,
         } = self;
         categorised_refs
             .iter()
@@ -439,6 +545,11 @@ impl Refs {
             })
     }
 
+    /// Whether `self` and `other` are equal, disregarding their [`Freshness`].
+    pub fn same_refs(&self, other: &Refs) -> bool {
+        self.categorised_refs == other.categorised_refs && self.remotes == other.remotes
+    }
+
     fn canonical_form(&self) -> Result<Vec<u8>, CjsonError> {
         Cjson(self).canonical_form()
     }
@@ -504,6 +615,27 @@ impl Refs {
     }
 }
 
+/// The [`Freshness`] of the [`Refs`] of all peers tracked in the context of
+/// [`Urn`].
+///
+/// Peers which didn't attest their [`Refs`], or whose [`Refs`] were not
+/// replicated yet, are omitted.
+pub fn freshness<S>(storage: &S, urn: &Urn) -> Result<BTreeMap<PeerId, Freshness>, stored::Error>
+where
+    S: AsRef<storage::ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let mut attested = BTreeMap::new();
+    for peer in tracking::tracked_peers(storage, Some(urn))? {
+        let peer = peer?;
+        if let Some(fresh) = Refs::load(storage, urn, peer)?.and_then(|refs| refs.freshness) {
+            attested.insert(peer, fresh);
+        }
+    }
+
+    Ok(attested)
+}
+
 impl<V> From<Signed<V>> for Refs {
     fn from(sig: Signed<V>) -> Self {
         sig.refs
@@ -573,6 +705,16 @@ impl Signed<Verified> {
     }
 }
 
+impl<V> Clone for Signed<V> {
+    fn clone(&self) -> Self {
+        Self {
+            refs: self.refs.clone(),
+            signature: self.signature.clone(),
+            _verified: PhantomData,
+        }
+    }
+}
+
 impl<V> Deref for Signed<V> {
     type Target = Refs;
 
@@ -700,7 +842,7 @@ where
     let loaded = storage
         .as_ref()
         .blob_at(at, Path::new(stored::BLOB_PATH))?
-        .map(|blob| Signed::from_json(blob.content(), signer))
+        .map(|blob| cache::verify(&blob, signer, storage.as_ref().sigrefs_cache()))
         .transpose()
         .map_err(stored::Error::from)?
         .map(|refs| Loaded { at, refs });
@@ -0,0 +1,120 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! A cache of verified [`Signed`] refs.
+//!
+//! Loading signed refs means parsing the blob they are stored in, and checking
+//! the signature over their canonical form. Blobs are content-addressed, so
+//! the outcome is the same every time a blob is loaded for the same signer,
+//! and only needs to be computed once.
+//!
+//! The cache is kept in memory for the lifetime of the process. Optionally,
+//! the set of verified blobs can be [`Persisted`] for a storage, see
+//! [`crate::git::storage::ReadOnly::with_sigrefs_cache`], in which case only
+//! the parsing is repeated after a restart. Note that this trusts the
+//! persisted entries as much as the storage itself.
+
+use std::{
+    fs,
+    io,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use super::{signed, Oid, Signed, Unverified, Verified};
+use crate::PeerId;
+
+/// The maximum number of entries kept in memory.
+pub const CAPACITY: usize = 1024;
+
+type Key = (Oid, PeerId);
+
+static MEMORY: Lazy<Mutex<lru::LruCache<Key, Signed<Verified>>>> =
+    Lazy::new(|| Mutex::new(lru::LruCache::new(CAPACITY)));
+
+/// A directory the verified entries of the cache are persisted to.
+///
+/// The layout on disk is:
+///
+/// ```text
+/// <dir>
+/// |- v1
+/// |  |- <signer>
+/// |  |  |- <blob oid>
+/// ```
+///
+/// where each `<blob oid>` is an empty file, marking the signed refs in the
+/// blob as verified for `<signer>`.
+#[derive(Clone, Debug)]
+pub struct Persisted {
+    dir: PathBuf,
+}
+
+impl Persisted {
+    /// Persist to `dir`, creating it if it doesn't exist.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into().join("v1");
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn marker(&self, (oid, signer): &Key) -> PathBuf {
+        self.dir.join(signer.to_string()).join(oid.to_string())
+    }
+}
+
+/// Drop all in-memory entries of the cache.
+pub fn clear() {
+    MEMORY.lock().clear()
+}
+
+/// Parse and verify the signed refs stored in `blob`, unless the outcome is
+/// cached already, in memory or in `disk`.
+pub(super) fn verify(
+    blob: &git2::Blob,
+    signer: &PeerId,
+    disk: Option<&Persisted>,
+) -> Result<Signed<Verified>, signed::Error> {
+    let key = (Oid::from(blob.id()), *signer);
+    if let Some(signed) = MEMORY.lock().get(&key) {
+        tracing::trace!(blob = %key.0, "signed refs found in memory");
+        return Ok(signed.clone());
+    }
+
+    let marker = disk.map(|disk| disk.marker(&key));
+    let signed = match marker {
+        Some(marker) if marker.exists() => {
+            tracing::trace!(blob = %key.0, "signed refs found on disk");
+            let unknown: Signed<Unverified> = serde_json::from_slice(blob.content())?;
+            Signed {
+                refs: unknown.refs,
+                signature: unknown.signature,
+                _verified: PhantomData,
+            }
+        },
+        marker => {
+            let signed = Signed::from_json(blob.content(), signer)?;
+            if let Some(marker) = marker {
+                if let Err(err) = persist(&marker) {
+                    tracing::warn!(?err, "failed to persist signed refs cache entry");
+                }
+            }
+            signed
+        },
+    };
+    MEMORY.lock().put(key, signed.clone());
+
+    Ok(signed)
+}
+
+fn persist(marker: &Path) -> io::Result<()> {
+    if let Some(dir) = marker.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::File::create(marker).map(|_| ())
+}
@@ -3,14 +3,33 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use super::{Refs, Remotes};
+use super::{Freshness, Refs, Remotes, FRESHNESS_KEY, FRESHNESS_VERSION};
 
 use crypto::PeerId;
 use git_ext::Oid;
-use serde::ser::SerializeMap;
+use serde::{ser::SerializeMap, Deserialize, Serialize};
 
 use std::collections::BTreeMap;
 
+/// The serialised form of a [`Freshness`], tagged with the version of the
+/// format.
+#[derive(Deserialize, Serialize)]
+struct Attestation {
+    version: u32,
+    seq: u64,
+    timestamp: u64,
+}
+
+impl From<Freshness> for Attestation {
+    fn from(Freshness { seq, timestamp }: Freshness) -> Self {
+        Self {
+            version: FRESHNESS_VERSION,
+            seq,
+            timestamp,
+        }
+    }
+}
+
 impl<'de> serde::Deserialize<'de> for Refs {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -30,6 +49,7 @@ impl<'de> serde::Deserialize<'de> for Refs {
                 A: serde::de::MapAccess<'vde>,
             {
                 let mut remotes: Option<Remotes<PeerId>> = None;
+                let mut freshness: Option<Freshness> = None;
                 let mut categorised_refs: BTreeMap<String, BTreeMap<String, Oid>> = BTreeMap::new();
 
                 while let Some(key) = map.next_key::<String>()? {
@@ -38,6 +58,20 @@ impl<'de> serde::Deserialize<'de> for Refs {
                             let value = map.next_value()?;
                             remotes = Some(value);
                         },
+                        FRESHNESS_KEY => {
+                            let Attestation {
+                                version,
+                                seq,
+                                timestamp,
+                            } = map.next_value()?;
+                            if version != FRESHNESS_VERSION {
+                                return Err(serde::de::Error::custom(format!(
+                                    "unsupported freshness version {}, expected {}",
+                                    version, FRESHNESS_VERSION
+                                )));
+                            }
+                            freshness = Some(Freshness { seq, timestamp });
+                        },
                         _ => {
                             let value = map.next_value()?;
                             categorised_refs.insert(key, value);
@@ -48,6 +82,7 @@ impl<'de> serde::Deserialize<'de> for Refs {
                 Ok(Refs {
                     remotes,
                     categorised_refs,
+                    freshness,
                 })
             }
         }
@@ -65,6 +100,9 @@ impl serde::Serialize for Refs {
             map_s.serialize_entry(category, &values)?;
         }
         map_s.serialize_entry("remotes", &self.remotes)?;
+        if let Some(freshness) = self.freshness {
+            map_s.serialize_entry(FRESHNESS_KEY, &Attestation::from(freshness))?;
+        }
         map_s.end()
     }
 }
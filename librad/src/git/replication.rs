@@ -7,6 +7,7 @@ use std::{
     collections::{BTreeMap, BTreeSet},
     convert::{TryFrom, TryInto},
     iter,
+    time::Duration,
 };
 
 use either::Either;
@@ -20,7 +21,7 @@ use super::{
     refs::{self, Refs},
     storage::{self, ReadOnlyStorage, Storage},
     tracking,
-    types::{reference, Force, Namespace, One, Reference},
+    types::{reference, Exclusion, Force, Namespace, One, Reference},
 };
 use crate::{
     identities::git::{Person, Project, Revision, SomeIdentity, VerifiedPerson, VerifiedProject},
@@ -112,9 +113,12 @@ impl From<identities::error::Error> for Error {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Config {
     pub fetch_limit: fetch::Limit,
+    /// Refs of tracked peers which should not be replicated, even though they
+    /// are signed.
+    pub exclude: Vec<Exclusion>,
 }
 
 /// The success outcome of [`self::replicate`].
@@ -133,6 +137,21 @@ pub struct ReplicateResult {
     /// Whether the replicated [`Urn`] was previously present in local storage
     /// or not.
     pub mode: Mode,
+
+    /// The attested [`refs::Freshness`] of the signed refs of the tracked
+    /// peers, after replication. See [`refs::freshness`].
+    pub freshness: BTreeMap<PeerId, refs::Freshness>,
+}
+
+impl ReplicateResult {
+    /// The tracked peers whose view of the [`Urn`] is older than `threshold`,
+    /// along with its age.
+    pub fn stale(&self, threshold: Duration) -> impl Iterator<Item = (&PeerId, Duration)> + '_ {
+        self.freshness
+            .iter()
+            .map(|(peer, fresh)| (peer, fresh.age()))
+            .filter(move |(_, age)| *age > threshold)
+    }
 }
 
 /// The "freshness" of the local view of a repo identity wrt the delegates.
@@ -184,8 +203,8 @@ enum ModeInternal {
 ///    information.
 ///
 /// 2. Fetch the `rad/signed_refs` of all tracked peers, and compute the
-///    eligible heads (i.e. where the `remote_peer` advertises the same tip
-///    oid as found in the signed refs)
+///    eligible heads (i.e. where the `remote_peer` advertises the same tip oid
+///    as found in the signed refs)
 ///
 /// 3. Fetch the rest (i.e. eligible heads)
 ///
@@ -223,7 +242,7 @@ where
         urn.clone(),
         remote_peer,
     )?;
-    let (result, mut remove) = match next {
+    let (mut result, mut remove) = match next {
         ModeInternal::Clone {
             urn,
             identity,
@@ -243,7 +262,7 @@ where
                     } = project::ensure_setup(
                         storage,
                         &mut fetcher,
-                        config.fetch_limit,
+                        &config,
                         delegates,
                         &rad_id,
                         proj,
@@ -283,6 +302,7 @@ where
                     updated_tips,
                     identity: id_status,
                     mode: Mode::Clone,
+                    freshness: BTreeMap::new(),
                 },
                 fetched_peers.difference(&allowed).copied().collect(),
             ))
@@ -305,7 +325,7 @@ where
                     } = project::ensure_setup(
                         storage,
                         &mut fetcher,
-                        config.fetch_limit,
+                        &config,
                         delegate_views,
                         &rad_id,
                         proj,
@@ -320,6 +340,7 @@ where
                             updated_tips,
                             identity: id_status,
                             mode: Mode::Fetch,
+                            freshness: BTreeMap::new(),
                         },
                         updated_tracked,
                     )
@@ -332,6 +353,7 @@ where
                             updated_tips,
                             identity: id_status,
                             mode: Mode::Fetch,
+                            freshness: BTreeMap::new(),
                         },
                         tracking::tracked_peers(storage, Some(&urn))?
                             .collect::<Result<BTreeSet<_>, _>>()?,
@@ -352,6 +374,8 @@ where
     // Remove any remote tracking branches we don't need
     prune(storage, &urn, remove.iter())?;
 
+    result.freshness = refs::freshness(storage, &urn)?;
+
     // TODO: At this point, the tracking graph may have changed, and/or we
     // created top-level person namespaces. We will eventually converge, but
     // perhaps we'd want to return some kind of continuation here, so the caller
@@ -699,7 +723,7 @@ mod project {
     pub fn ensure_setup<F>(
         storage: &Storage,
         fetcher: &mut F,
-        limit: fetch::Limit,
+        config: &Config,
         delegates: BTreeMap<PeerId, project::DelegateView>,
         rad_id: &Urn,
         proj: VerifiedProject,
@@ -716,7 +740,7 @@ mod project {
         let (fetch_result, tracked) = replicate_signed_refs(
             storage,
             fetcher,
-            limit,
+            config,
             &urn,
             delegates
                 .values()
@@ -747,7 +771,7 @@ mod project {
     pub fn replicate_signed_refs<F>(
         storage: &Storage,
         fetcher: &mut F,
-        limit: fetch::Limit,
+        config: &Config,
         urn: &Urn,
         delegates: BTreeSet<Urn>,
     ) -> Result<(fetch::FetchResult, BTreeSet<PeerId>), Error>
@@ -774,7 +798,8 @@ mod project {
             .fetch(fetch::Fetchspecs::Replicate {
                 tracked_sigrefs: tracked_sigrefs.clone(),
                 delegates,
-                limit,
+                exclude: config.exclude.clone(),
+                limit: config.fetch_limit,
             })
             .map_err(|e| Error::Fetch(e.into()))?;
 
@@ -18,7 +18,10 @@ use std_ext::Void;
 
 use crate::{
     collaborative_objects::CollaborativeObjects,
-    git::types::{Many, One, Reference},
+    git::{
+        refs,
+        types::{Many, One, Reference},
+    },
     identities::git::Urn,
     paths::Paths,
     PeerId,
@@ -45,7 +48,7 @@ pub use read::{
     References,
     ReferencesGlob,
 };
-pub use watch::{NamespaceEvent, Watcher};
+pub use watch::{NamespaceEvent, SignedRefsEvent, Watcher};
 
 pub mod error {
     use thiserror::Error;
@@ -116,7 +119,7 @@ impl Storage {
         }
 
         Ok(Self {
-            inner: ReadOnly { backend, peer_id },
+            inner: ReadOnly::new(backend, peer_id, paths.audit_dir()),
             signer: BoxedSigner::from(SomeSigner { signer }),
         })
     }
@@ -139,6 +142,24 @@ impl Storage {
         Ok(())
     }
 
+    /// Rotate the key of an already initialised [`Storage`] to the key of
+    /// `signer`, and open it.
+    ///
+    /// Note that no refs are touched: the signed refs of the local peer, and
+    /// the identities delegating to the previous key, keep referring to the
+    /// previous key until they are updated.
+    pub fn rotate_key<S>(paths: &Paths, signer: S) -> Result<Self, error::Init>
+    where
+        S: Signer + Clone,
+        S::Error: std::error::Error + Send + Sync + 'static,
+    {
+        crate::git::init();
+
+        let mut backend = git2::Repository::open_bare(paths.git_dir())?;
+        Config::rotate(&mut backend, &signer)?;
+        Self::open(paths, signer)
+    }
+
     #[deprecated = "use `open` instead"]
     pub fn open_or_init<S>(paths: &Paths, signer: S) -> Result<Self, error::Init>
     where
@@ -167,6 +188,19 @@ impl Storage {
         &self.inner
     }
 
+    /// See [`ReadOnly::with_sigrefs_cache`].
+    pub fn with_sigrefs_cache(self, cache: refs::cache::Persisted) -> Self {
+        Self {
+            inner: self.inner.with_sigrefs_cache(cache),
+            ..self
+        }
+    }
+
+    /// See [`ReadOnly::refresh`].
+    pub fn refresh(&mut self) -> Result<bool, Error> {
+        self.inner.refresh()
+    }
+
     pub fn peer_id(&self) -> &PeerId {
         self.inner.peer_id()
     }
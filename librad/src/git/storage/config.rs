@@ -27,6 +27,7 @@ const CONFIG_USER_NAME: &str = "user.name";
 const CONFIG_USER_EMAIL: &str = "user.email";
 const CONFIG_RAD_SELF: &str = "rad.self";
 const CONFIG_RAD_PEER_ID: &str = "rad.peerid";
+const CONFIG_RAD_SIGREFS_FRESHNESS: &str = "rad.sigrefs.freshness";
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -133,6 +134,32 @@ where
         Ok(this)
     }
 
+    /// Change the peer id of an already initialised storage to the one of
+    /// `signer`, retaining the configured user name.
+    ///
+    /// Unlike [`Config::init`], this does not guard against a key change, so
+    /// it must only be used when the key of the storage is rotated on purpose.
+    pub fn rotate(repo: &mut git2::Repository, signer: &'a S) -> Result<Self, Error> {
+        let config = git2::Config::open(&self::path(repo))?;
+        let mut this = Config {
+            inner: config,
+            signer,
+        };
+        let name = this
+            .user_name()
+            .map(Some)
+            .or_matches::<Error, _, _>(
+                |err| matches!(err, Error::Git(e) if is_not_found_err(e)),
+                || Ok(None),
+            )?
+            .unwrap_or_else(|| "anonymous".to_owned());
+        this.ensure_reflog()?;
+        this.set_peer_id(PeerId::from_signer(signer))?;
+        this.set_user_info(&name)?;
+
+        Ok(this)
+    }
+
     fn set_user_info(&mut self, name: &str) -> Result<(), Error> {
         let peer_id = self.peer_id()?;
         self.inner.set_str(CONFIG_USER_NAME, name)?;
@@ -174,6 +201,17 @@ where
         }
     }
 
+    /// Enable or disable [`crate::git::refs::Freshness`] attestations in the
+    /// signed refs written by this storage.
+    ///
+    /// Peers running older versions of this library can not read signed refs
+    /// carrying an attestation, so this is disabled by default.
+    pub fn set_sigrefs_freshness(&mut self, enabled: bool) -> Result<(), Error> {
+        self.inner
+            .set_bool(CONFIG_RAD_SIGREFS_FRESHNESS, enabled)
+            .map_err(Error::from)
+    }
+
     pub(crate) fn as_raw(&self) -> &git2::Config {
         &self.inner
     }
@@ -200,6 +238,14 @@ impl<S> Config<'_, S> {
             .and_then(|peer_id| peer_id.parse().map_err(Error::from))
     }
 
+    /// Whether [`crate::git::refs::Freshness`] attestations are enabled, see
+    /// [`Config::set_sigrefs_freshness`].
+    pub fn sigrefs_freshness(&self) -> Result<bool, Error> {
+        self.inner
+            .get_bool(CONFIG_RAD_SIGREFS_FRESHNESS)
+            .or_matches::<Error, _, _>(is_not_found_err, || Ok(false))
+    }
+
     pub fn user(&self) -> Result<Option<Urn>, Error> {
         self.inner
             .get_string(CONFIG_RAD_SELF)
@@ -9,7 +9,7 @@ use std::{
     sync::Arc,
 };
 
-use deadpool::managed::{self, Manager, Object, RecycleResult};
+use deadpool::managed::{self, Manager, Object, RecycleError, RecycleResult};
 use parking_lot::RwLock;
 use std_ext::Void;
 use thiserror::Error;
@@ -131,8 +131,8 @@ impl Manager<ReadOnly, InitError> for ReadConfig {
         ReadOnly::open(&self.paths).map_err(InitError::from)
     }
 
-    async fn recycle(&self, _: &mut ReadOnly) -> RecycleResult<InitError> {
-        Ok(())
+    async fn recycle(&self, storage: &mut ReadOnly) -> RecycleResult<InitError> {
+        refresh(storage.refresh())
     }
 }
 
@@ -175,7 +175,14 @@ where
         }
     }
 
-    async fn recycle(&self, _: &mut Storage) -> RecycleResult<InitError> {
-        Ok(())
+    async fn recycle(&self, storage: &mut Storage) -> RecycleResult<InitError> {
+        refresh(storage.refresh())
     }
 }
+
+/// Keep a recycled storage if refreshing it succeeded, otherwise have the pool
+/// open a new one.
+fn refresh(res: Result<bool, read::Error>) -> RecycleResult<InitError> {
+    res.map(|_| ())
+        .map_err(|e| RecycleError::Message(format!("failed to refresh storage: {}", e)))
+}
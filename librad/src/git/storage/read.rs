@@ -4,7 +4,14 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{convert::TryFrom, fmt::Debug, marker::PhantomData, path::Path};
+use std::{
+    convert::TryFrom,
+    fmt::Debug,
+    fs,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use thiserror::Error;
 
@@ -13,7 +20,10 @@ use git_ext::{self as ext, blob, is_not_found_err, RefLike, RefspecPattern};
 use std_ext::prelude::*;
 
 use crate::{
-    git::types::{reference, Many, One, Reference},
+    git::{
+        refs,
+        types::{reference, Many, One, Reference},
+    },
     identities::git::{Identities, Urn},
     paths::Paths,
     PeerId,
@@ -146,6 +156,13 @@ pub trait ReadOnlyStorage {
 pub struct ReadOnly {
     pub(super) backend: git2::Repository,
     pub(super) peer_id: PeerId,
+    /// The modification time of the pack directory when it was last scanned,
+    /// see [`ReadOnly::refresh`].
+    pub(super) packs_seen: Option<SystemTime>,
+    /// See [`Paths::audit_dir`].
+    pub(super) audit_dir: PathBuf,
+    /// See [`ReadOnly::with_sigrefs_cache`].
+    pub(super) sigrefs_cache: Option<refs::cache::Persisted>,
 }
 
 impl ReadOnly {
@@ -165,7 +182,47 @@ impl ReadOnly {
         crate::git::init();
         let backend = git2::Repository::open(paths.git_dir())?;
         let peer_id = Config::try_from(&backend)?.peer_id()?;
-        Ok(Self { backend, peer_id })
+        Ok(Self::new(backend, peer_id, paths.audit_dir()))
+    }
+
+    pub(super) fn new(backend: git2::Repository, peer_id: PeerId, audit_dir: &Path) -> Self {
+        let packs_seen = packs_modified(&backend);
+        Self {
+            backend,
+            peer_id,
+            packs_seen,
+            audit_dir: audit_dir.to_path_buf(),
+            sigrefs_cache: None,
+        }
+    }
+
+    /// Persist the signed refs verified when loading them from this storage
+    /// to `cache`, see [`refs::cache`].
+    pub fn with_sigrefs_cache(self, cache: refs::cache::Persisted) -> Self {
+        Self {
+            sigrefs_cache: Some(cache),
+            ..self
+        }
+    }
+
+    /// Make packfiles added since this [`ReadOnly`] was opened, or last
+    /// refreshed, visible to it.
+    ///
+    /// `libgit2` only rescans the pack directory when an object can not be
+    /// found, so a long-lived handle would otherwise pay for a rescan on every
+    /// lookup of an object in a new pack. This is cheap if no packs were
+    /// added, and is done automatically when a handle is returned to a
+    /// [`super::Pool`].
+    ///
+    /// Returns `true` if the pack directory was rescanned.
+    pub fn refresh(&mut self) -> Result<bool, Error> {
+        let modified = packs_modified(&self.backend);
+        if modified.is_some() && modified == self.packs_seen {
+            return Ok(false);
+        }
+        self.backend.odb()?.refresh()?;
+        self.packs_seen = modified;
+        Ok(true)
     }
 
     pub fn peer_id(&self) -> &PeerId {
@@ -203,6 +260,20 @@ impl ReadOnly {
     pub fn identities<'a, T: 'a>(&'a self) -> Identities<'a, T> {
         Identities::from(&self.backend)
     }
+
+    pub(crate) fn audit_dir(&self) -> &Path {
+        &self.audit_dir
+    }
+
+    pub(crate) fn sigrefs_cache(&self) -> Option<&refs::cache::Persisted> {
+        self.sigrefs_cache.as_ref()
+    }
+}
+
+fn packs_modified(repo: &git2::Repository) -> Option<SystemTime> {
+    fs::metadata(repo.path().join("objects").join("pack"))
+        .and_then(|meta| meta.modified())
+        .ok()
 }
 
 impl ReadOnlyStorage for ReadOnly {
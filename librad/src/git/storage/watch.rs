@@ -14,6 +14,7 @@ use notify::Watcher as _;
 use thiserror::Error;
 
 use super::Storage;
+use crate::git::Urn;
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -42,6 +43,13 @@ pub struct NamespaceEvent {
     pub kind: EventKind,
 }
 
+/// The `rad/signed_refs` of the local peer were updated in the namespace
+/// `urn`.
+#[derive(Debug)]
+pub struct SignedRefsEvent {
+    pub urn: Urn,
+}
+
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 #[non_exhaustive]
 pub enum EventKind {
@@ -134,4 +142,63 @@ impl<'a> Watch<'a> {
 
         Ok((Watcher(Arc::new(watcher)), rx))
     }
+
+    /// Watch for updates of the `rad/signed_refs` of the local peer, ie. not
+    /// those of tracked peers.
+    ///
+    /// Implemented by watching `$GIT_DIR/logs/refs/namespaces` _recursively_
+    /// for changes to the reflog of `refs/namespaces/*/refs/rad/signed_refs`.
+    /// The same caveats as for [`Watch::namespaces`] apply, and in addition
+    /// updates may be missed on filesystem event backends which can't watch
+    /// directories created after the watch was established. A single update
+    /// may be reported more than once.
+    pub fn signed_refs(&self) -> Result<(Watcher, impl Iterator<Item = SignedRefsEvent>), Error> {
+        use notify::{RawEvent, RecursiveMode::Recursive};
+
+        fn namespace(p: &Path) -> Option<&str> {
+            let mut iter = p.iter();
+            let is_sigrefs =
+                iter.next() == Some("refs".as_ref()) && iter.next() == Some("namespaces".as_ref());
+            let ns = iter.next()?.to_str()?;
+            let is_sigrefs = is_sigrefs
+                && iter.next() == Some("refs".as_ref())
+                && iter.next() == Some("rad".as_ref())
+                && iter.next() == Some("signed_refs".as_ref())
+                && iter.next().is_none();
+            is_sigrefs.then(|| ns)
+        }
+
+        let repo_path = self.storage.path().to_owned();
+        let reflogs_path = repo_path.join("logs");
+        let namespaces_path = reflogs_path.join("refs/namespaces");
+
+        if !namespaces_path.exists() {
+            fs::create_dir_all(&namespaces_path)?;
+        }
+
+        let (tx, rx) = mpsc::channel();
+
+        let mut watcher = notify::raw_watcher(tx)?;
+        watcher.watch(&namespaces_path, Recursive)?;
+
+        let rx = rx.into_iter().filter_map(move |evt| {
+            tracing::trace!("{:?}", evt);
+
+            match evt {
+                RawEvent {
+                    path: Some(path),
+                    op: Ok(_),
+                    cookie: _,
+                } if path.is_file() => {
+                    let path = path.strip_prefix(&reflogs_path).ok()?;
+                    let urn = namespace(path).and_then(|ns| Urn::try_from_id(ns).ok())?;
+                    Some(SignedRefsEvent { urn })
+                },
+
+                _ => None,
+            }
+        });
+
+        Ok((Watcher(Arc::new(watcher)), rx))
+    }
 }
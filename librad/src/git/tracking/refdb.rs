@@ -9,7 +9,10 @@ use link_tracking::git::{
 };
 
 use crate::{
-    git::storage::{read, ReadOnly, ReadOnlyStorage, Storage},
+    git::{
+        audit,
+        storage::{read, ReadOnly, ReadOnlyStorage, Storage},
+    },
     git_ext as ext,
 };
 
@@ -176,10 +179,13 @@ impl Write for Storage {
         let raw = self.as_raw();
         let mut txn = raw.transaction().map_err(error::Txn::Acquire)?;
         let mut applied = Applied::default();
-        let mut reject_or_update =
-            |apply: Result<Updated<'a, Self::Oid>, PreviousError<Self::Oid>>| match apply {
-                Ok(update) => applied.updates.push(update),
-                Err(rejection) => applied.rejections.push(rejection),
+        let reject_or_update =
+            |applied: &mut Applied<'a, Self::Oid>,
+             apply: Result<Updated<'a, Self::Oid>, PreviousError<Self::Oid>>| {
+                match apply {
+                    Ok(update) => applied.updates.push(update),
+                    Err(rejection) => applied.rejections.push(rejection),
+                }
             };
 
         for update in updates {
@@ -204,12 +210,32 @@ impl Write for Storage {
                             })
                     };
                     match self.reference(&name)? {
-                        Some(r) => reject_or_update(
-                            previous
-                                .guard(r.target().map(ext::Oid::from).as_ref(), set)?
-                                .map_or(Ok(Updated::Written { name, target }), Err),
-                        ),
+                        Some(r) => {
+                            let current = r.target().map(ext::Oid::from);
+                            if current == Some(target) {
+                                // Nothing to write, but the `previous` condition
+                                // must still hold
+                                match previous
+                                    .guard(current.as_ref(), || Ok::<_, Self::TxnError>(()))?
+                                {
+                                    None => applied.unchanged.push(Update::Write {
+                                        name,
+                                        target,
+                                        previous,
+                                    }),
+                                    Some(rejection) => applied.rejections.push(rejection),
+                                }
+                            } else {
+                                reject_or_update(
+                                    &mut applied,
+                                    previous
+                                        .guard(current.as_ref(), set)?
+                                        .map_or(Ok(Updated::Written { name, target }), Err),
+                                )
+                            }
+                        },
                         None => reject_or_update(
+                            &mut applied,
                             previous
                                 .guard(None, set)?
                                 .map_or(Ok(Updated::Written { name, target }), Err),
@@ -230,6 +256,7 @@ impl Write for Storage {
                     };
                     match self.reference(&name)? {
                         Some(r) => reject_or_update(
+                            &mut applied,
                             previous
                                 .guard(r.target().map(ext::Oid::from).as_ref(), delete)?
                                 .map_or(
@@ -247,14 +274,31 @@ impl Write for Storage {
                         None => match previous {
                             refdb::PreviousValue::Any
                             | refdb::PreviousValue::MustNotExist
-                            | refdb::PreviousValue::IfExistsMustMatch(_) => { /* no-op */ },
-                            _ => reject_or_update(Err(PreviousError::DidNotExist)),
+                            | refdb::PreviousValue::IfExistsMustMatch(_) => {
+                                applied.unchanged.push(Update::Delete { name, previous })
+                            },
+                            _ => reject_or_update(&mut applied, Err(PreviousError::DidNotExist)),
                         },
                     }
                 },
             }
         }
         txn.commit().map_err(error::Txn::Commit)?;
+        audit::try_record(self, applied.updates.iter().map(audit_event));
         Ok(applied)
     }
 }
+
+fn audit_event(update: &Updated<'_, ext::Oid>) -> audit::Event {
+    match update {
+        Updated::Written { name, target } => audit::Event::Tracked {
+            urn: name.urn.clone().into_owned(),
+            peer: name.remote.into(),
+            config: *target,
+        },
+        Updated::Deleted { name, .. } => audit::Event::Untracked {
+            urn: name.urn.clone().into_owned(),
+            peer: name.remote.into(),
+        },
+    }
+}
@@ -22,7 +22,7 @@ pub use reference::{
     Single,
     SymbolicRef,
 };
-pub use refspec::{Fetchspec, Pushspec, Refspec};
+pub use refspec::{Exclusion, Fetchspec, Pushspec, Refspec};
 pub use remote::Remote;
 
 /// Helper to aid type inference constructing a [`Reference`] without a
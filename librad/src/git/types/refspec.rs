@@ -122,8 +122,80 @@ impl FromStr for Refspec<ext::RefLike, ext::RefLike> {
     }
 }
 
+/// A negative refspec, ie. `^<pattern>`.
+///
+/// Refs matching the pattern are excluded from a fetch, even if they match
+/// one of the other fetch specs. As with other refspecs, a `*` in the pattern
+/// matches any sequence of characters, including `/`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exclusion(ext::RefspecPattern);
+
+impl Exclusion {
+    pub fn new(pattern: impl Into<ext::RefspecPattern>) -> Self {
+        Self(pattern.into())
+    }
+
+    pub fn pattern(&self) -> &ext::RefspecPattern {
+        &self.0
+    }
+
+    /// Whether the ref `name` is excluded by this pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        let pattern = self.0.as_str();
+        match pattern.split_once('*') {
+            None => pattern == name,
+            Some((prefix, suffix)) => {
+                name.len() >= prefix.len() + suffix.len()
+                    && name.starts_with(prefix)
+                    && name.ends_with(suffix)
+            },
+        }
+    }
+}
+
+impl Display for Exclusion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "^{}", self.0)
+    }
+}
+
+impl TryFrom<&str> for Exclusion {
+    type Error = ext::reference::name::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.strip_prefix('^')
+            .ok_or(ext::reference::name::Error::RefFormat)
+            .and_then(ext::RefspecPattern::try_from)
+            .map(Self)
+    }
+}
+
+impl FromStr for Exclusion {
+    type Err = ext::reference::name::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s)
+    }
+}
+
 #[derive(Debug)]
-pub struct Fetchspec(Refspec<ext::RefspecPattern, ext::RefspecPattern>);
+pub struct Fetchspec(FetchspecInner);
+
+#[derive(Debug)]
+enum FetchspecInner {
+    Refspec(Refspec<ext::RefspecPattern, ext::RefspecPattern>),
+    Exclusion(Exclusion),
+}
+
+impl Fetchspec {
+    /// The [`Exclusion`], if this is a negative fetch spec.
+    pub fn as_exclusion(&self) -> Option<&Exclusion> {
+        match &self.0 {
+            FetchspecInner::Exclusion(excl) => Some(excl),
+            FetchspecInner::Refspec(_) => None,
+        }
+    }
+}
 
 impl<S, D> From<Refspec<S, D>> for Fetchspec
 where
@@ -131,11 +203,17 @@ where
     D: Into<ext::RefspecPattern>,
 {
     fn from(spec: Refspec<S, D>) -> Self {
-        Self(Refspec {
+        Self(FetchspecInner::Refspec(Refspec {
             src: spec.src.into(),
             dst: spec.dst.into(),
             force: spec.force,
-        })
+        }))
+    }
+}
+
+impl From<Exclusion> for Fetchspec {
+    fn from(excl: Exclusion) -> Self {
+        Self(FetchspecInner::Exclusion(excl))
     }
 }
 
@@ -143,7 +221,11 @@ impl TryFrom<&str> for Fetchspec {
     type Error = ext::reference::name::Error;
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        Refspec::try_from(s).map(Self)
+        if s.starts_with('^') {
+            Exclusion::try_from(s).map(Self::from)
+        } else {
+            Refspec::try_from(s).map(|spec| Self(FetchspecInner::Refspec(spec)))
+        }
     }
 }
 
@@ -157,7 +239,10 @@ impl FromStr for Fetchspec {
 
 impl Display for Fetchspec {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.0.fmt(f)
+        match &self.0 {
+            FetchspecInner::Refspec(spec) => spec.fmt(f),
+            FetchspecInner::Exclusion(excl) => excl.fmt(f),
+        }
     }
 }
 
@@ -3,7 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{convert::TryFrom, str::FromStr};
+use std::{collections::BTreeSet, convert::TryFrom, str::FromStr};
 
 use git_ext::{
     error::{is_exists_err, is_not_found_err},
@@ -36,6 +36,79 @@ pub enum FindError {
     Git(#[from] git2::Error),
 }
 
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("invalid remote name `{0}`")]
+    Name(RefLike),
+
+    #[error("invalid refspec `{spec}`: {reason}")]
+    Refspec { spec: String, reason: &'static str },
+
+    #[error("duplicate refspec `{0}`")]
+    Duplicate(String),
+}
+
+#[derive(Debug, Error)]
+pub enum PersistError {
+    #[error(transparent)]
+    Invalid(#[from] ValidationError),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// The changes made to the config by [`Remote::update`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Update {
+    /// Whether the remote didn't exist before.
+    pub created: bool,
+    /// Whether the `url` was changed.
+    pub url: bool,
+    /// The fetch specs which were added, and removed.
+    pub fetch: SpecChanges,
+    /// The push specs which were added, and removed.
+    pub push: SpecChanges,
+}
+
+impl Update {
+    /// Whether anything was changed.
+    pub fn is_changed(&self) -> bool {
+        self.created || self.url || self.fetch.is_changed() || self.push.is_changed()
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SpecChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl SpecChanges {
+    fn new<'a>(
+        persisted: impl IntoIterator<Item = Option<&'a str>>,
+        desired: impl IntoIterator<Item = String>,
+    ) -> Self {
+        let persisted = persisted.into_iter().flatten().collect::<Vec<_>>();
+        let desired = desired.into_iter().collect::<Vec<_>>();
+        Self {
+            added: desired
+                .iter()
+                .filter(|spec| !persisted.contains(&spec.as_str()))
+                .cloned()
+                .collect(),
+            removed: persisted
+                .into_iter()
+                .filter(|spec| !desired.iter().any(|d| d.as_str() == *spec))
+                .map(ToOwned::to_owned)
+                .collect(),
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        !(self.added.is_empty() && self.removed.is_empty())
+    }
+}
+
 #[derive(Debug)]
 pub struct Remote<Url> {
     /// The file path to the git monorepo.
@@ -52,6 +125,17 @@ pub struct Remote<Url> {
 }
 
 impl<Url> Remote<Url> {
+    /// Start building a [`Remote`] with the given `url` and `name`, which is
+    /// validated upon [`Builder::build`].
+    pub fn builder<R>(url: Url, name: R) -> Builder<Url>
+    where
+        R: Into<RefLike>,
+    {
+        Builder {
+            remote: Self::new(url, name),
+        }
+    }
+
     /// Create a `"rad"` remote with a single fetch spec.
     pub fn rad_remote<Ref, Spec>(url: Url, fetch_spec: Ref) -> Self
     where
@@ -114,15 +198,131 @@ impl<Url> Remote<Url> {
         self.pushspecs.push(spec.into())
     }
 
+    /// Check that the remote can be persisted and used.
+    ///
+    /// That is, the name must be a valid remote name, the refspecs must not
+    /// contain duplicates, and each side of a fetch spec must contain the
+    /// same number of wildcards -- at most one.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if !git2::Remote::is_valid_name(self.name.as_str()) {
+            return Err(ValidationError::Name(self.name.clone()));
+        }
+
+        let mut seen = BTreeSet::new();
+        for spec in self.fetchspecs.iter().map(ToString::to_string) {
+            if !seen.insert(spec.clone()) {
+                return Err(ValidationError::Duplicate(spec));
+            }
+            if spec.starts_with('^') {
+                continue;
+            }
+
+            let (src, dst) = spec
+                .trim_start_matches('+')
+                .split_once(':')
+                .ok_or_else(|| ValidationError::Refspec {
+                    spec: spec.clone(),
+                    reason: "missing destination",
+                })?;
+            let wildcards = src.matches('*').count();
+            if wildcards > 1 {
+                return Err(ValidationError::Refspec {
+                    spec,
+                    reason: "more than one wildcard",
+                });
+            }
+            if wildcards != dst.matches('*').count() {
+                return Err(ValidationError::Refspec {
+                    spec,
+                    reason: "wildcard on only one side",
+                });
+            }
+        }
+        for spec in self.pushspecs.iter().map(ToString::to_string) {
+            if !seen.insert(spec.clone()) {
+                return Err(ValidationError::Duplicate(spec));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Persist the remote in the `repo`'s config, unless it is already
+    /// persisted as is.
+    ///
+    /// The remote is [validated](Remote::validate) first. Returns `true` if the
+    /// config was written, `false` if the `url`, `fetch`, and `push` keys
+    /// already had the desired values. See also [`Remote::save`].
+    pub fn persist(&self, repo: &git2::Repository) -> Result<bool, PersistError>
+    where
+        Url: ToString,
+    {
+        self.update(repo).map(|update| update.is_changed())
+    }
+
+    /// Reconcile the remote of the same name in the `repo`'s config with
+    /// `self`, creating it if it doesn't exist.
+    ///
+    /// The `url` is set, fetch and push specs which are not in `self` are
+    /// removed, and missing ones are added. Other configuration keys are left
+    /// untouched. The remote is [validated](Remote::validate) first.
+    ///
+    /// Returns what was changed -- if nothing was, the config is not written.
+    #[tracing::instrument(skip(self, repo), fields(name = self.name.as_str()))]
+    pub fn update(&self, repo: &git2::Repository) -> Result<Update, PersistError>
+    where
+        Url: ToString,
+    {
+        self.validate()?;
+
+        let url = self.url.to_string();
+        let fetchspecs = self.fetchspecs.iter().map(ToString::to_string);
+        let pushspecs = self.pushspecs.iter().map(ToString::to_string);
+        let persisted = repo
+            .find_remote(self.name.as_str())
+            .map(Some)
+            .or_matches::<git2::Error, _, _>(is_not_found_err, || Ok(None))?;
+        let update = match persisted {
+            None => Update {
+                created: true,
+                url: true,
+                fetch: SpecChanges::new(None, fetchspecs),
+                push: SpecChanges::new(None, pushspecs),
+            },
+            Some(remote) => Update {
+                created: false,
+                url: remote.url() != Some(url.as_str()),
+                fetch: SpecChanges::new(&remote.fetch_refspecs()?, fetchspecs),
+                push: SpecChanges::new(&remote.push_refspecs()?, pushspecs),
+            },
+        };
+
+        if update.is_changed() {
+            tracing::debug!(?update, "updating remote");
+            self.write(repo)?;
+        }
+        Ok(update)
+    }
+
     /// Persist the remote in the `repo`'s config.
     ///
     /// If a remote with the same name already exists, previous values of the
     /// configuration keys `url`, `fetch`, and `push` will be overwritten.
     /// Note that this means that _other_ configuration keys are left
     /// untouched, if present.
-    #[allow(clippy::unit_arg)]
+    ///
+    /// Unlike [`Remote::persist`], the remote is not validated, and the config
+    /// is always written.
     #[tracing::instrument(skip(self, repo), fields(name = self.name.as_str()))]
     pub fn save(&mut self, repo: &git2::Repository) -> Result<(), git2::Error>
+    where
+        Url: ToString,
+    {
+        self.write(repo)
+    }
+
+    #[allow(clippy::unit_arg)]
+    fn write(&self, repo: &git2::Repository) -> Result<(), git2::Error>
     where
         Url: ToString,
     {
@@ -158,6 +358,18 @@ impl<Url> Remote<Url> {
         Ok(())
     }
 
+    /// Delete the remote `name` from the `repo`'s config.
+    ///
+    /// Like `git remote remove`, this also deletes the remote tracking
+    /// branches, ie. the references matching the destinations of the fetch
+    /// specs. Returns `false` if no such remote exists.
+    #[tracing::instrument(skip(repo))]
+    pub fn delete(repo: &git2::Repository, name: RefLike) -> Result<bool, git2::Error> {
+        repo.remote_delete(name.as_str())
+            .map(|()| true)
+            .or_matches::<git2::Error, _, _>(is_not_found_err, || Ok(false))
+    }
+
     /// Find a persisted remote by name.
     #[allow(clippy::unit_arg)]
     #[tracing::instrument(skip(repo))]
@@ -490,6 +702,56 @@ impl Remote<LocalUrl> {
     }
 }
 
+/// A [`Remote`] under construction, see [`Remote::builder`].
+#[derive(Debug)]
+pub struct Builder<Url> {
+    remote: Remote<Url>,
+}
+
+impl<Url> Builder<Url> {
+    /// Add a fetch spec.
+    pub fn fetchspec(mut self, spec: impl Into<Fetchspec>) -> Self {
+        self.remote.add_fetchspec(spec);
+        self
+    }
+
+    /// Add several fetch specs.
+    pub fn fetchspecs<I>(mut self, specs: I) -> Self
+    where
+        I: IntoIterator,
+        <I as IntoIterator>::Item: Into<Fetchspec>,
+    {
+        self.remote
+            .fetchspecs
+            .extend(specs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add a push spec.
+    pub fn pushspec(mut self, spec: impl Into<Pushspec>) -> Self {
+        self.remote.add_pushspec(spec);
+        self
+    }
+
+    /// Add several push specs.
+    pub fn pushspecs<I>(mut self, specs: I) -> Self
+    where
+        I: IntoIterator,
+        <I as IntoIterator>::Item: Into<Pushspec>,
+    {
+        self.remote
+            .pushspecs
+            .extend(specs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Finish building, see [`Remote::validate`].
+    pub fn build(self) -> Result<Remote<Url>, ValidationError> {
+        self.remote.validate()?;
+        Ok(self.remote)
+    }
+}
+
 impl<Url> AsRef<Url> for Remote<Url> {
     fn as_ref(&self) -> &Url {
         &self.url
@@ -21,14 +21,30 @@ pub extern crate radicle_data as data;
 pub extern crate radicle_git_ext as git_ext;
 pub extern crate radicle_std_ext as std_ext;
 
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
 pub mod collaborative_objects;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 pub mod git;
 pub mod internal;
 pub mod net;
 pub mod paths;
 pub mod profile;
 pub mod rate_limit;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 // Re-exports
-pub use link_crypto::{keystore, PeerId, PublicKey, SecStr, SecretKey, Signature, Signer};
+pub use link_crypto::{
+    keystore,
+    PeerId,
+    PublicKey,
+    Redacted,
+    SecStr,
+    SecretKey,
+    SecretString,
+    Signature,
+    Signer,
+};
 pub use radicle_macros::*;
@@ -3,7 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{fmt, net::SocketAddr, sync::Arc, time::Duration};
 
 use futures::{future, StreamExt as _, TryFutureExt as _, TryStreamExt as _};
 use link_async::Spawner;
@@ -15,6 +15,7 @@ use crate::{
         replication::{self, Replication},
     },
     PeerId,
+    Redacted,
     Signer,
 };
 
@@ -29,6 +30,8 @@ pub use crate::net::protocol::{
     PeerInfo,
 };
 
+mod announce;
+pub mod autosync;
 pub mod error;
 pub mod storage;
 pub use storage::Storage as PeerStorage;
@@ -40,17 +43,36 @@ pub struct Config<Signer> {
     pub storage: config::Storage,
 }
 
+impl<S> fmt::Debug for Config<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("signer", &Redacted::new(()))
+            .field("protocol", &self.protocol)
+            .field("storage", &self.storage)
+            .finish()
+    }
+}
+
 pub mod config {
-    #[derive(Clone, Copy, Default)]
+    #[derive(Clone, Copy, Debug, Default)]
     pub struct Storage {
         pub user: UserStorage,
         pub protocol: ProtocolStorage,
+        /// Announce updates of the local peer's `rad/signed_refs` made by any
+        /// process, by watching the monorepo for changes.
+        ///
+        /// The watch is recursive over all namespaces, and may thus exceed the
+        /// limits of the filesystem event backend on large monorepos. Failing
+        /// to establish it is logged, but is not an error.
+        ///
+        /// Cf. [`crate::git::storage::watch::Watch::signed_refs`]
+        pub announce_signed_refs: bool,
     }
 
     /// Settings for the user-facing storage.
     ///
     /// Cf. [`super::Peer::using_storage`]
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Debug)]
     pub struct UserStorage {
         /// Number of [`crate::git::storage::Storage`] instances to reserve.
         pub pool_size: usize,
@@ -67,7 +89,7 @@ pub mod config {
     /// Settings for the protocol storage.
     ///
     /// Cf. [`super::PeerStorage`]
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Debug)]
     pub struct ProtocolStorage {
         /// Number of [`crate::git::storage::Storage`] instances to reserve.
         pub pool_size: usize,
@@ -91,6 +113,7 @@ pub struct Peer<S> {
     caches: protocol::Caches,
     spawner: Arc<Spawner>,
     repl: Replication,
+    _announce: Option<git::storage::Watcher>,
 }
 
 impl<S> Peer<S>
@@ -117,11 +140,21 @@ where
             let urns = protocol::cache::urns::Filter::new(store, move |ev| phone.emit(ev))?;
             protocol::Caches { urns }
         };
+        let _announce = if config.storage.announce_signed_refs {
+            let store = git::storage::Storage::open(&config.protocol.paths, config.signer.clone())?;
+            announce::spawn(store, phone.clone())
+                .map_err(|err| {
+                    tracing::warn!(%err, "failed to watch signed refs, not announcing updates")
+                })
+                .ok()
+        } else {
+            None
+        };
 
         #[cfg(feature = "replication-v3")]
         let repl = Replication::new(&config.protocol.paths, config.protocol.replication)?;
         #[cfg(not(feature = "replication-v3"))]
-        let repl = Replication::new(config.protocol.replication);
+        let repl = Replication::new(config.protocol.replication.clone());
 
         let peer_store = PeerStorage::new(
             storage::Config {
@@ -151,6 +184,7 @@ where
             caches,
             spawner,
             repl,
+            _announce,
         })
     }
 
@@ -261,18 +295,7 @@ where
     ) -> Result<replication::Success, error::Replicate> {
         #[cfg(feature = "replication-v3")]
         {
-            // TODO: errors
-            let from = from.into();
-            let remote_peer = from.0;
-            let Connected(conn) = self
-                .connect(from)
-                .await
-                .ok_or(error::Replicate::NoConnection(remote_peer))?;
-            let store = self.user_store.get().await?;
-            self.repl
-                .replicate(&self.spawner, store, conn, urn, whoami)
-                .err_into()
-                .await
+            self.replicate_reporting(from, urn, whoami, None).await
         }
         #[cfg(not(feature = "replication-v3"))]
         {
@@ -283,9 +306,86 @@ where
         }
     }
 
+    /// Like [`Peer::replicate`], but report the progress of the replication
+    /// to `reporter`, see [`replication::progress`].
+    #[cfg(feature = "replication-v3")]
+    pub async fn replicate_with_progress(
+        &self,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        urn: Urn,
+        whoami: Option<LocalIdentity>,
+        reporter: replication::progress::Reporter,
+    ) -> Result<replication::Success, error::Replicate> {
+        self.replicate_reporting(from, urn, whoami, Some(reporter))
+            .await
+    }
+
+    #[cfg(feature = "replication-v3")]
+    async fn replicate_reporting(
+        &self,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        urn: Urn,
+        whoami: Option<LocalIdentity>,
+        reporter: Option<replication::progress::Reporter>,
+    ) -> Result<replication::Success, error::Replicate> {
+        // TODO: errors
+        let from = from.into();
+        let remote_peer = from.0;
+        let Connected(conn) = self
+            .connect(from)
+            .await
+            .ok_or(error::Replicate::NoConnection(remote_peer))?;
+        let store = self.user_store.get().await?;
+        self.repl
+            .replicate_with_progress(&self.spawner, store, conn, urn, whoami, reporter)
+            .err_into()
+            .await
+    }
+
+    /// Initiate replication of each of `urns` from the given peer.
+    ///
+    /// Like [`Peer::replicate`], but all `urns` are replicated over a single
+    /// connection to `from`, several of them concurrently. This is much
+    /// cheaper than replicating them one by one, eg. when syncing many
+    /// projects from a seed.
+    ///
+    /// Fails with [`error::Replicate::NoConnection`] if no connection to
+    /// `from` could be established. Otherwise, the outcome of each
+    /// replication is returned in the order of `urns`.
+    pub async fn replicate_many(
+        &self,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        urns: impl IntoIterator<Item = Urn>,
+        whoami: Option<LocalIdentity>,
+    ) -> Result<Vec<(Urn, Result<replication::Success, error::Replicate>)>, error::Replicate> {
+        let from = from.into();
+        let remote_peer = from.0;
+        #[cfg_attr(not(feature = "replication-v3"), allow(unused_variables))]
+        let Connected(conn) = self
+            .connect(from.clone())
+            .await
+            .ok_or(error::Replicate::NoConnection(remote_peer))?;
+
+        #[cfg(feature = "replication-v3")]
+        let res = self
+            .repl
+            .replicate_many(&self.spawner, &self.user_store, conn, urns, whoami)
+            .await;
+        // The fetches open their streams on the connection established above
+        #[cfg(not(feature = "replication-v3"))]
+        let res = self
+            .repl
+            .replicate_many(&self.spawner, &self.user_store, from, urns, whoami)
+            .await;
+
+        Ok(res
+            .into_iter()
+            .map(|(urn, res)| (urn, res.map_err(error::Replicate::from)))
+            .collect())
+    }
+
     // TODO: Augment `Connected` such that we can provide an alternative API,
     // a la `peer.connect((peer_id, addrs)).await.unwrap().replicate()`
-    #[allow(unused)] // unused without replication-v3
     async fn connect(&self, to: impl Into<(PeerId, Vec<SocketAddr>)>) -> Option<Connected> {
         self.phone.connect(to).await
     }
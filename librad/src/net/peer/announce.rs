@@ -0,0 +1,73 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Automatic announcements of the signed refs of the local peer.
+//!
+//! Whenever the `rad/signed_refs` of the local peer change -- eg. after a push
+//! from a working copy, or an identity update by another process sharing the
+//! monorepo -- a [`gossip::Payload`] carrying the new revision is announced,
+//! so that locally published commits propagate without an explicit
+//! [`super::Peer::announce`].
+//!
+//! Only enabled if [`super::config::Storage::announce_signed_refs`] is set.
+
+use std::{collections::HashMap, thread};
+
+use crate::{
+    git::{
+        storage::{watch, ReadOnlyStorage as _, SignedRefsEvent, Storage, Watcher},
+        types::{Namespace, Reference},
+        Urn,
+    },
+    git_ext::Oid,
+    net::protocol::{gossip, TinCans},
+};
+
+/// Start announcing updates of the signed refs in `storage` via `phone`.
+///
+/// Announcements are made for as long as the returned [`Watcher`] is alive.
+pub(super) fn spawn(storage: Storage, phone: TinCans) -> Result<Watcher, watch::Error> {
+    let (watcher, events) = storage.watch().signed_refs()?;
+    thread::spawn(move || announce_thread(storage, phone, events));
+    Ok(watcher)
+}
+
+fn announce_thread(
+    storage: Storage,
+    phone: TinCans,
+    events: impl Iterator<Item = SignedRefsEvent>,
+) {
+    let span = tracing::info_span!("announce-signed-refs");
+    let _guard = span.enter();
+
+    // Events may be reported more than once, only announce actual changes
+    let mut announced: HashMap<Urn, Oid> = HashMap::new();
+    for SignedRefsEvent { urn } in events {
+        let sigrefs = Reference::rad_signed_refs(Namespace::from(&urn), None);
+        let oid = match storage.reference_oid(&sigrefs) {
+            Ok(oid) => oid,
+            Err(err) => {
+                tracing::warn!(%urn, %err, "failed to resolve signed refs");
+                continue;
+            },
+        };
+        if announced.get(&urn) == Some(&oid) {
+            continue;
+        }
+
+        let have = gossip::Payload {
+            urn: urn.clone().with_path(reflike!("refs/rad/signed_refs")),
+            rev: Some(oid.into()),
+            origin: None,
+        };
+        match phone.announce(have) {
+            Ok(()) => {
+                tracing::debug!(%urn, %oid, "announced signed refs");
+                announced.insert(urn, oid);
+            },
+            Err(_) => tracing::warn!(%urn, %oid, "failed to announce signed refs"),
+        }
+    }
+}
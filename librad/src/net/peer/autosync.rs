@@ -0,0 +1,280 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Replication triggered by gossip announcements.
+//!
+//! When a peer announces new refs of a [`Urn`] we track, the
+//! [`super::PeerStorage`] fetches them right away. If that fetch fails, the
+//! announcement is lost until the next one arrives. [`run`] observes the
+//! outcome of announcements, and schedules [`super::Peer::replicate`] from the
+//! announcing peer where the announced data is still missing.
+//!
+//! Announcements which were not fetched due to rate limiting, or which
+//! claimed a revision the provider turned out not to have, are reported as
+//! [`PutResult::Stale`] and are not acted upon. Otherwise, the gossip rate
+//! limits could be bypassed, and a lying provider could make us replicate
+//! from it at will.
+//!
+//! * announcements of the same [`Urn`] by the same peer are deduplicated while
+//!   a replication is scheduled or running
+//! * replications of the same [`Urn`] from the same peer are at least
+//!   [`Config::min_interval`] apart
+//! * failed replications are retried with exponential backoff, up to
+//!   [`Config::max_attempts`] times
+//! * at most [`Config::max_concurrent`] replications run at the same time
+
+use std::{
+    cmp,
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_lock::Semaphore;
+use futures::{pin_mut, StreamExt as _};
+use git_ext as ext;
+use parking_lot::Mutex;
+
+use super::{Peer, ProtocolEvent};
+use crate::{
+    git::{storage::ReadOnlyStorage as _, Urn},
+    net::protocol::{broadcast::PutResult, event::upstream::Gossip, gossip, RecvError},
+    PeerId,
+    Signer,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Minimum time between two replications of the same [`Urn`] from the
+    /// same peer.
+    pub min_interval: Duration,
+    /// Time to wait before retrying a failed replication, doubled with every
+    /// consecutive failure.
+    pub backoff: Duration,
+    /// Upper bound of the [`Config::backoff`].
+    pub max_backoff: Duration,
+    /// Number of consecutive failures after which a replication is not
+    /// retried until the next announcement.
+    pub max_attempts: u32,
+    /// Maximum number of replications running at the same time, across all
+    /// [`Urn`]s and peers.
+    pub max_concurrent: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_secs(30),
+            backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(10 * 60),
+            max_attempts: 5,
+            max_concurrent: 4,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    /// A replication is scheduled or running.
+    busy: bool,
+    /// Another announcement arrived while `busy`.
+    again: bool,
+    /// When the last replication finished.
+    last: Option<Instant>,
+    /// Number of consecutive failures.
+    failures: u32,
+}
+
+/// Bookkeeping of the replications scheduled by [`run`], per [`Urn`] and peer.
+#[derive(Debug)]
+pub struct Schedule {
+    config: Config,
+    entries: HashMap<(PeerId, Urn), Entry>,
+}
+
+impl Schedule {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Request a replication of `urn` from `peer`.
+    ///
+    /// Returns when the replication should start, or `None` if one is already
+    /// scheduled or running. In the latter case, the running replication is
+    /// followed up by another one, see [`Schedule::finished`].
+    pub fn request(&mut self, peer: PeerId, urn: Urn, now: Instant) -> Option<Instant> {
+        self.prune(now);
+
+        let config = self.config;
+        let entry = self.entries.entry((peer, urn.with_path(None))).or_default();
+        if entry.busy {
+            entry.again = true;
+            return None;
+        }
+        entry.busy = true;
+        Some(earliest(&config, entry, now))
+    }
+
+    /// Record the outcome of a replication of `urn` from `peer`.
+    ///
+    /// Returns when the next replication should start, if the failed one is to
+    /// be retried, or if it was requested again in the meantime.
+    pub fn finished(&mut self, peer: PeerId, urn: Urn, ok: bool, now: Instant) -> Option<Instant> {
+        let config = self.config;
+        let entry = self.entries.entry((peer, urn.with_path(None))).or_default();
+        entry.last = Some(now);
+        if ok {
+            entry.failures = 0;
+        } else {
+            entry.failures = entry.failures.saturating_add(1);
+        }
+
+        let retry = !ok && entry.failures < config.max_attempts;
+        if entry.again || retry {
+            entry.again = false;
+            entry.busy = true;
+            Some(earliest(&config, entry, now))
+        } else {
+            entry.busy = false;
+            None
+        }
+    }
+
+    /// Forget about replications which no longer affect scheduling.
+    fn prune(&mut self, now: Instant) {
+        let keep = cmp::max(self.config.min_interval, self.config.max_backoff);
+        self.entries.retain(|_, entry| {
+            entry.busy
+                || entry
+                    .last
+                    .map(|last| now.saturating_duration_since(last) < keep)
+                    .unwrap_or(false)
+        })
+    }
+}
+
+fn earliest(config: &Config, entry: &Entry, now: Instant) -> Instant {
+    let wait = match entry.failures {
+        0 => config.min_interval,
+        n => config
+            .backoff
+            .checked_mul(2u32.saturating_pow(n - 1))
+            .map(|backoff| cmp::min(backoff, config.max_backoff))
+            .unwrap_or(config.max_backoff),
+    };
+    entry
+        .last
+        .map(|last| cmp::max(now, last + wait))
+        .unwrap_or(now)
+}
+
+/// Schedule replications for announcements received by `peer`, until its
+/// protocol event stream ends.
+pub async fn run<S>(peer: Peer<S>, config: Config)
+where
+    S: Signer + Clone,
+{
+    let schedule = Arc::new(Mutex::new(Schedule::new(config)));
+    let slots = Arc::new(Semaphore::new(config.max_concurrent.max(1)));
+    let events = peer.subscribe();
+    pin_mut!(events);
+
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(ProtocolEvent::Gossip(gossip)) => {
+                if let Gossip::Put {
+                    provider,
+                    payload,
+                    result,
+                } = *gossip
+                {
+                    // Only a failed fetch is worth another attempt, see the
+                    // module documentation.
+                    if !matches!(result, PutResult::Error) {
+                        continue;
+                    }
+                    if let Some(gossip::Rev::Git(rev)) = payload.rev {
+                        if has_object(&peer, rev).await {
+                            continue;
+                        }
+                    }
+
+                    let urn = payload.urn.with_path(None);
+                    let at = schedule
+                        .lock()
+                        .request(provider.peer_id, urn.clone(), Instant::now());
+                    if let Some(at) = at {
+                        let addr_hints = provider.seen_addrs.iter().copied().collect();
+                        peer.spawner
+                            .spawn(replicate(
+                                peer.clone(),
+                                schedule.clone(),
+                                slots.clone(),
+                                (provider.peer_id, addr_hints),
+                                urn,
+                                at,
+                            ))
+                            .detach();
+                    }
+                }
+            },
+            Ok(_) => {},
+            Err(RecvError::Lagged(n)) => {
+                tracing::warn!(skipped = n, "missed protocol events");
+            },
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn has_object<S>(peer: &Peer<S>, rev: git2::Oid) -> bool
+where
+    S: Signer + Clone,
+{
+    peer.using_read_only(move |storage| storage.has_object(ext::Oid::from(rev)))
+        .await
+        .map(|has| has.unwrap_or(false))
+        .unwrap_or(false)
+}
+
+#[tracing::instrument(skip(peer, schedule, slots, from), fields(remote_peer = %from.0))]
+async fn replicate<S>(
+    peer: Peer<S>,
+    schedule: Arc<Mutex<Schedule>>,
+    slots: Arc<Semaphore>,
+    from: (PeerId, Vec<SocketAddr>),
+    urn: Urn,
+    mut at: Instant,
+) where
+    S: Signer + Clone,
+{
+    loop {
+        link_async::sleep(at.saturating_duration_since(Instant::now())).await;
+        let slot = slots.acquire().await;
+        let res = peer.replicate(from.clone(), urn.clone(), None).await;
+        drop(slot);
+        let ok = match res {
+            Ok(_) => {
+                tracing::debug!("replicated announced refs");
+                true
+            },
+            Err(err) => {
+                tracing::warn!(%err, "failed to replicate announced refs");
+                false
+            },
+        };
+        match schedule
+            .lock()
+            .finished(from.0, urn.clone(), ok, Instant::now())
+        {
+            Some(next) => at = next,
+            None => break,
+        }
+    }
+}
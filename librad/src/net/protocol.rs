@@ -40,6 +40,7 @@ pub mod gossip;
 pub mod interrogation;
 pub mod io;
 pub mod membership;
+pub mod sessions;
 
 mod info;
 pub use info::{Capability, PartialPeerInfo, PeerAdvertisement, PeerInfo};
@@ -54,7 +55,7 @@ pub(super) use tincans::TinCans;
 pub use tincans::{Connected, Interrogation, RecvError};
 
 mod state;
-pub use state::Quota;
+pub use state::{FetchQuota, Quota};
 use state::{RateLimits, State, StateConfig, Storage};
 
 pub type Endpoint = quic::Endpoint<2>;
@@ -179,6 +180,7 @@ where
             config.rate_limits.membership,
             nonzero!(1024 * 1024usize),
         )),
+        fetches: sessions::Sessions::new(config.rate_limits.fetches),
     };
 
     let state = State {
@@ -365,6 +365,11 @@ where
             }
 
             let have = storage.ask(val.clone()).await;
+            let event = event::Gossip::Want {
+                requester: origin.clone(),
+                payload: val.clone(),
+                have,
+            };
             let tocks = if have {
                 let reply = Message::have(info(), val);
                 if origin.peer_id == remote_id {
@@ -386,7 +391,7 @@ where
                 )
             };
 
-            Ok((None, tocks))
+            Ok((Some(event), tocks))
         },
     }
 }
@@ -125,6 +125,15 @@ pub mod upstream {
             /// The result of applying to local storage
             result: broadcast::PutResult<Payload>,
         },
+        /// Triggered after receiving a `Want`.
+        Want {
+            /// The peer who is asking
+            requester: PeerInfo<Addr>,
+            /// The payload asked for
+            payload: Payload,
+            /// Whether we have the payload in local storage
+            have: bool,
+        },
     }
 
     impl From<Gossip<SocketAddr, gossip::Payload>> for Upstream {
@@ -198,6 +207,7 @@ pub mod upstream {
             move |event| match event {
                 Upstream::Gossip(gossip) => match gossip.as_ref() {
                     Gossip::Put { provider, .. } => provider.peer_id == peer,
+                    Gossip::Want { .. } => false,
                 },
                 _ => false,
             }
@@ -12,8 +12,8 @@ use thiserror::Error;
 use tracing::{error, info};
 
 use crate::net::{
-    connection::Duplex,
-    protocol::State,
+    connection::{Duplex, RemotePeer as _},
+    protocol::{sessions, State},
     upgrade::{self, Upgraded},
 };
 
@@ -22,6 +22,9 @@ enum Error {
     #[error("upload-pack exited with {0}")]
     UploadPack(ExitStatus),
 
+    #[error(transparent)]
+    Sessions(#[from] sessions::Error),
+
     #[error(transparent)]
     Io(#[from] io::Error),
 }
@@ -43,13 +46,24 @@ where
     T::Read: AsyncRead + Unpin,
     T::Write: AsyncWrite + Unpin,
 {
+    let remote_peer = stream.remote_peer_id();
+    // Held until the upload-pack process exits
+    let permit = state.limits.fetches.acquire(remote_peer).await?;
     let (recv, send) = stream.into_stream().split();
     let git_dir = state.config.paths.git_dir();
 
-    let (Header { path, host, extra }, run) = upload_pack(git_dir, recv, send).await?;
-    info!(%path, ?host, ?extra, "upload-pack");
+    let (Header { path, host, extra }, run) =
+        upload_pack(git_dir, recv, permit.throttle(send)).await?;
+    // Sent by replicating peers which opted in, see
+    // `link_replication::io::Network::with_correlation_id`
+    let correlation_id = extra.iter().find_map(|(k, v)| match v {
+        Some(v) if k == link_replication::correlation::PARAM => Some(v.as_str()),
+        _ => None,
+    });
+    info!(%path, ?host, ?extra, ?correlation_id, "upload-pack");
 
     let status = run.await?;
+    drop(permit);
     // XXX: #![feature(exit_status_error)] ?
     // https://github.com/rust-lang/rust/issues/84908
     if !status.success() {
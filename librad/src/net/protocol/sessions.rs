@@ -0,0 +1,395 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Limits on the fetches served to other peers.
+//!
+//! Every fetch served (ie. `upload-pack` session) requires a [`Permit`] from
+//! [`Sessions`], which bounds the number of concurrent sessions per peer and
+//! in total. Peers exceeding their share wait in a per-peer queue, and queues
+//! are served round-robin, so a peer issuing many fetches at once can not
+//! starve others. The pack data sent during a session is throttled to the
+//! configured bytes per minute per peer and in total, see
+//! [`Permit::throttle`].
+
+use std::{
+    cmp,
+    collections::{HashMap, VecDeque},
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::io::AsyncWrite;
+use parking_lot::Mutex;
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use super::FetchQuota;
+use crate::PeerId;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("too many fetches queued for {0}")]
+    QueueFull(PeerId),
+
+    #[error("session limiter was dropped")]
+    Closed,
+}
+
+/// Token bucket of bytes, refilled continuously.
+#[derive(Debug)]
+struct Bucket {
+    per_minute: u64,
+    available: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u64, now: Instant) -> Self {
+        Self {
+            per_minute,
+            available: per_minute as f64,
+            last: now,
+        }
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.per_minute == 0
+    }
+
+    fn is_full(&self) -> bool {
+        self.is_unlimited() || self.available >= self.per_minute as f64
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.available = (self.available + elapsed * self.rate()).min(self.per_minute as f64);
+        self.last = now;
+    }
+
+    /// Bytes per second.
+    fn rate(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+
+    fn allowance(&self, want: usize) -> usize {
+        if self.is_unlimited() {
+            want
+        } else {
+            cmp::min(want, self.available as usize)
+        }
+    }
+
+    /// How long until `want` bytes are available.
+    fn wait(&self, want: usize) -> Duration {
+        let want = cmp::min(want as u64, self.per_minute) as f64;
+        if self.is_unlimited() || self.available >= want {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64((want - self.available) / self.rate())
+        }
+    }
+
+    fn take(&mut self, n: usize) {
+        if !self.is_unlimited() {
+            self.available = (self.available - n as f64).max(0.0);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    quota: FetchQuota,
+    /// Number of sessions per peer.
+    active: HashMap<PeerId, usize>,
+    /// Number of sessions in total.
+    total: usize,
+    /// Peers waiting for a session, served round-robin.
+    ring: VecDeque<PeerId>,
+    /// Waiting sessions per peer.
+    queues: HashMap<PeerId, VecDeque<oneshot::Sender<()>>>,
+    /// Bytes sent in total.
+    bytes: Bucket,
+    /// Bytes sent per peer.
+    bytes_per_peer: HashMap<PeerId, Bucket>,
+}
+
+impl Inner {
+    fn can_start(&self, peer: &PeerId) -> bool {
+        self.total < self.quota.sessions
+            && self.active.get(peer).copied().unwrap_or(0) < self.quota.sessions_per_peer
+    }
+
+    fn start(&mut self, peer: PeerId) {
+        *self.active.entry(peer).or_default() += 1;
+        self.total += 1;
+    }
+
+    fn finish(&mut self, peer: &PeerId, now: Instant) {
+        self.total = self.total.saturating_sub(1);
+        if let Some(active) = self.active.get_mut(peer) {
+            *active -= 1;
+            if *active == 0 {
+                self.active.remove(peer);
+            }
+        }
+        self.dispatch();
+
+        // Keep the byte count of a peer only as long as it matters
+        let idle = !self.active.contains_key(peer);
+        if let Some(bucket) = self.bytes_per_peer.get_mut(peer) {
+            bucket.refill(now);
+            if idle && bucket.is_full() {
+                self.bytes_per_peer.remove(peer);
+            }
+        }
+    }
+
+    /// Start waiting sessions, visiting the waiting peers in turn.
+    fn dispatch(&mut self) {
+        let mut skipped = 0;
+        while self.total < self.quota.sessions && skipped < self.ring.len() {
+            let peer = match self.ring.pop_front() {
+                Some(peer) => peer,
+                None => break,
+            };
+            if !self.can_start(&peer) {
+                self.ring.push_back(peer);
+                skipped += 1;
+                continue;
+            }
+
+            let granted = {
+                let queue = self.queues.entry(peer).or_default();
+                // Waiters may have given up in the meantime
+                let mut granted = false;
+                while let Some(waiter) = queue.pop_front() {
+                    if waiter.send(()).is_ok() {
+                        granted = true;
+                        break;
+                    }
+                }
+                granted
+            };
+            if granted {
+                self.start(peer);
+                skipped = 0;
+            }
+            match self.queues.get(&peer) {
+                Some(queue) if !queue.is_empty() => self.ring.push_back(peer),
+                _ => {
+                    self.queues.remove(&peer);
+                },
+            }
+        }
+    }
+
+    /// How many of `want` bytes `peer` may send now, or how long to wait
+    /// until it may send some.
+    fn allowance(&mut self, peer: PeerId, want: usize, now: Instant) -> Result<usize, Duration> {
+        let per_peer = self.quota.bytes_per_peer;
+        let bucket = self
+            .bytes_per_peer
+            .entry(peer)
+            .or_insert_with(|| Bucket::new(per_peer, now));
+        bucket.refill(now);
+        self.bytes.refill(now);
+
+        let allowance = cmp::min(bucket.allowance(want), self.bytes.allowance(want));
+        if allowance > 0 || want == 0 {
+            Ok(allowance)
+        } else {
+            Err(cmp::max(bucket.wait(want), self.bytes.wait(want)))
+        }
+    }
+
+    fn sent(&mut self, peer: &PeerId, n: usize) {
+        self.bytes.take(n);
+        if let Some(bucket) = self.bytes_per_peer.get_mut(peer) {
+            bucket.take(n);
+        }
+    }
+}
+
+/// Limits the fetches served to other peers, see the module documentation.
+#[derive(Clone)]
+pub struct Sessions {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Sessions {
+    pub fn new(quota: FetchQuota) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                bytes: Bucket::new(quota.bytes, Instant::now()),
+                quota,
+                active: HashMap::new(),
+                total: 0,
+                ring: VecDeque::new(),
+                queues: HashMap::new(),
+                bytes_per_peer: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Wait for a session serving `peer`.
+    ///
+    /// Fails with [`Error::QueueFull`] if `peer` already has
+    /// [`FetchQuota::queued_per_peer`] sessions waiting.
+    pub async fn acquire(&self, peer: PeerId) -> Result<Permit, Error> {
+        let waiting = {
+            let mut inner = self.inner.lock();
+            let queued = match inner.queues.get_mut(&peer) {
+                None => 0,
+                Some(queue) => {
+                    queue.retain(|waiter| !waiter.is_closed());
+                    queue.len()
+                },
+            };
+            if queued == 0 && inner.ring.is_empty() && inner.can_start(&peer) {
+                inner.start(peer);
+                None
+            } else if queued >= inner.quota.queued_per_peer {
+                return Err(Error::QueueFull(peer));
+            } else {
+                let (tx, rx) = oneshot::channel();
+                inner.queues.entry(peer).or_default().push_back(tx);
+                if !inner.ring.contains(&peer) {
+                    inner.ring.push_back(peer);
+                }
+                // Capacity may be left if other peers are at their limit
+                inner.dispatch();
+                Some(Waiting {
+                    rx,
+                    peer,
+                    inner: self.inner.clone(),
+                })
+            }
+        };
+        if let Some(mut waiting) = waiting {
+            (&mut waiting.rx).await.map_err(|_| Error::Closed)?;
+        }
+
+        Ok(Permit {
+            peer,
+            inner: self.inner.clone(),
+        })
+    }
+
+    /// The number of sessions currently serving `peer`.
+    pub fn active(&self, peer: &PeerId) -> usize {
+        self.inner.lock().active.get(peer).copied().unwrap_or(0)
+    }
+
+    /// The number of sessions waiting to serve `peer`.
+    pub fn queued(&self, peer: &PeerId) -> usize {
+        self.inner
+            .lock()
+            .queues
+            .get(peer)
+            .map(VecDeque::len)
+            .unwrap_or(0)
+    }
+}
+
+/// A session waiting to be started by [`Inner::dispatch`].
+///
+/// If the waiting [`Sessions::acquire`] is cancelled after the session was
+/// started, the session ends right away.
+struct Waiting {
+    rx: oneshot::Receiver<()>,
+    peer: PeerId,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        self.rx.close();
+        if self.rx.try_recv().is_ok() {
+            self.inner.lock().finish(&self.peer, Instant::now())
+        }
+    }
+}
+
+/// A session obtained from [`Sessions::acquire`], which ends when dropped.
+pub struct Permit {
+    peer: PeerId,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Permit {
+    pub fn peer(&self) -> PeerId {
+        self.peer
+    }
+
+    /// Throttle the bytes written to `send` to the byte quota.
+    pub fn throttle<W>(&self, send: W) -> Throttled<W> {
+        Throttled {
+            inner: send,
+            peer: self.peer,
+            limits: self.inner.clone(),
+            sleep: None,
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.inner.lock().finish(&self.peer, Instant::now())
+    }
+}
+
+/// An [`AsyncWrite`] which is throttled to the byte quota of a [`Permit`].
+pub struct Throttled<W> {
+    inner: W,
+    peer: PeerId,
+    limits: Arc<Mutex<Inner>>,
+    sleep: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl<W> AsyncWrite for Throttled<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            if let Some(sleep) = self.sleep.as_mut() {
+                futures::ready!(sleep.as_mut().poll(cx));
+                self.sleep = None;
+            }
+
+            let peer = self.peer;
+            let allowance = self
+                .limits
+                .lock()
+                .allowance(peer, buf.len(), Instant::now());
+            match allowance {
+                Err(wait) => self.sleep = Some(Box::pin(link_async::sleep(wait))),
+                Ok(n) => {
+                    let written =
+                        futures::ready!(Pin::new(&mut self.inner).poll_write(cx, &buf[..n]))?;
+                    self.limits.lock().sent(&peer, written);
+                    return Poll::Ready(Ok(written));
+                },
+            }
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
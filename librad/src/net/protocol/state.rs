@@ -17,6 +17,7 @@ use super::{
     gossip,
     io,
     membership,
+    sessions::Sessions,
     tick,
     Endpoint,
     ProtocolStorage,
@@ -151,6 +152,7 @@ where
 #[derive(Clone)]
 pub(super) struct RateLimits {
     pub membership: Arc<RateLimiter<Keyed<PeerId>>>,
+    pub fetches: Sessions,
 }
 
 /// Rate limit quota.
@@ -167,6 +169,8 @@ pub struct Quota {
     pub membership: rate_limit::Quota,
     /// See [`StorageQuota`].
     pub storage: StorageQuota,
+    /// See [`FetchQuota`].
+    pub fetches: FetchQuota,
 }
 
 impl Default for Quota {
@@ -175,6 +179,7 @@ impl Default for Quota {
             gossip: GossipQuota::default(),
             membership: rate_limit::Quota::per_second(nonzero!(1u32)).allow_burst(nonzero!(10u32)),
             storage: StorageQuota::default(),
+            fetches: FetchQuota::default(),
         }
     }
 }
@@ -224,6 +229,44 @@ impl Default for StorageQuota {
     }
 }
 
+/// Quota for serving fetches to other peers, see [`super::sessions`].
+#[derive(Clone, Copy, Debug)]
+pub struct FetchQuota {
+    /// Fetches served concurrently per peer.
+    ///
+    /// Default: 2
+    pub sessions_per_peer: usize,
+    /// Fetches served concurrently in total.
+    ///
+    /// Default: 16
+    pub sessions: usize,
+    /// Fetches a peer may have waiting for a session. Further fetches from
+    /// the peer are rejected.
+    ///
+    /// Default: 8
+    pub queued_per_peer: usize,
+    /// Pack bytes sent per peer per minute, `0` meaning unlimited.
+    ///
+    /// Default: 256MiB
+    pub bytes_per_peer: u64,
+    /// Pack bytes sent in total per minute, `0` meaning unlimited.
+    ///
+    /// Default: 1GiB
+    pub bytes: u64,
+}
+
+impl Default for FetchQuota {
+    fn default() -> Self {
+        Self {
+            sessions_per_peer: 2,
+            sessions: 16,
+            queued_per_peer: 8,
+            bytes_per_peer: 256 * 1024 * 1024,
+            bytes: 1024 * 1024 * 1024,
+        }
+    }
+}
+
 //
 // Peer Storage (gossip)
 //
@@ -20,4 +20,4 @@ pub use v2::{error, Config, IdStatus, Mode, Replication, Success};
 #[cfg(feature = "replication-v3")]
 mod v3;
 #[cfg(feature = "replication-v3")]
-pub use v3::{error, Config, Replication, Success};
+pub use v3::{error, progress, Config, Replication, Success};
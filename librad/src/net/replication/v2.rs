@@ -5,6 +5,7 @@
 
 use std::{net::SocketAddr, time::Duration};
 
+use futures::{stream, StreamExt as _};
 use link_async::Spawner;
 
 use crate::{
@@ -38,10 +39,16 @@ pub mod error {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Config {
     pub limit: git::fetch::Limit,
     pub wait_slot: Duration,
+    /// Refs of tracked peers which should not be replicated, eg.
+    /// `^refs/heads/wip/*`.
+    pub exclude: Vec<git::types::Exclusion>,
+    /// The number of URNs replicated concurrently by
+    /// [`Replication::replicate_many`].
+    pub concurrency: usize,
 }
 
 impl Default for Config {
@@ -49,6 +56,8 @@ impl Default for Config {
         Self {
             limit: git::fetch::Limit::default(),
             wait_slot: Duration::from_secs(20),
+            exclude: vec![],
+            concurrency: 4,
         }
     }
 }
@@ -90,12 +99,48 @@ impl Replication {
             {
                 let config = legacy::Config {
                     fetch_limit: self.config.limit,
+                    exclude: self.config.exclude.clone(),
                 };
-                move |storage, fetcher| legacy::replicate(storage, fetcher, config, whoami.clone())
+                move |storage, fetcher| {
+                    legacy::replicate(storage, fetcher, config.clone(), whoami.clone())
+                }
             },
         )
         .await;
 
         Ok(res??)
     }
+
+    /// Replicate each of `urns` from the same peer.
+    ///
+    /// Up to [`Config::concurrency`] URNs are replicated concurrently, each
+    /// fetching over its own stream of the connection to `from`. The results
+    /// are returned in the order of `urns`.
+    pub async fn replicate_many<P>(
+        &self,
+        spawner: &Spawner,
+        pool: &P,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        urns: impl IntoIterator<Item = Urn>,
+        whoami: Option<LocalIdentity>,
+    ) -> Vec<(Urn, Result<Success, error::Replicate>)>
+    where
+        P: Pooled<Storage> + Send + 'static,
+    {
+        let from = from.into();
+        stream::iter(urns)
+            .map(|urn| {
+                let from = from.clone();
+                let whoami = whoami.clone();
+                async move {
+                    let res = self
+                        .replicate(spawner, pool, from, urn.clone(), whoami)
+                        .await;
+                    (urn, res)
+                }
+            })
+            .buffered(self.config.concurrency.max(1))
+            .collect()
+            .await
+    }
 }
@@ -6,14 +6,17 @@
 use std::{sync::Arc, time::Duration};
 
 use async_lock::Semaphore;
+use futures::{stream, StreamExt as _};
 use link_async::{timeout, Spawner};
-use link_replication::io::UserInfo;
+use link_git::protocol::sideband::OnProgress;
+use link_replication::io::{InFlight, Throttle, UserInfo};
 use tracing::debug;
 
 use crate::{
     git::{
         identities::local::LocalIdentity,
-        storage::{read::ReadOnlyStorage as _, Storage},
+        storage::{read::ReadOnlyStorage as _, Pooled, Storage},
+        types::Exclusion,
     },
     identities::git::Urn,
     net::{connection::RemotePeer as _, quic},
@@ -21,7 +24,19 @@ use crate::{
     PeerId,
 };
 
-pub use link_replication::FetchLimit;
+pub use link_git::{
+    odb::backend::Remote as RemoteOdb,
+    protocol::{fetch::FlowControl, packwriter::BaseCache},
+};
+pub use link_replication::{
+    io::{Bandwidth, Traffic},
+    progress,
+    FetchLimit,
+    FetchSpec,
+    Rollback,
+    Validate,
+    ValidationPolicy,
+};
 
 mod context;
 use context::Context;
@@ -43,8 +58,14 @@ pub mod error {
         #[error("timeout waiting for replication slot")]
         Timeout(#[from] link_async::Elapsed),
 
+        #[error("failed to borrow storage from pool")]
+        Pool(#[from] crate::git::storage::PoolError),
+
+        #[error("failed to prepare replication")]
+        Prepare(#[source] link_replication::Error),
+
         #[error(transparent)]
-        Replicate(#[from] link_replication::Error),
+        Replicate(#[from] link_replication::error::Replicate),
     }
 }
 
@@ -55,6 +76,28 @@ pub struct Config {
     pub limit: FetchLimit,
     pub slots: usize,
     pub wait_slot: Duration,
+    /// Bounds on the memory used for buffering fetch responses.
+    pub flow_control: FlowControl,
+    /// How to cache base objects while completing thin packs. `None` means
+    /// bases are not cached.
+    pub base_cache: Option<BaseCache>,
+    /// Whether to validate only the refs touched by a pull, or all refs of
+    /// the namespace.
+    pub validation: Validate,
+    /// What to do if validation finds inconsistencies.
+    pub validation_policy: ValidationPolicy,
+    /// Whether to accept signed refs of a peer which are older than the ones
+    /// already stored.
+    pub sigrefs_rollback: Rollback,
+    /// Whether to send the correlation id of a replication run to the remote
+    /// peer, so it can be found in the remote's logs.
+    pub send_correlation_id: bool,
+    /// Limits on the bytes per second sent and received by all replications
+    /// taken together.
+    pub bandwidth: Bandwidth,
+    /// How many hops away in the tracking graph the refs of peers are
+    /// fetched, see [`FetchSpec::with_tracking_cutoff`].
+    pub tracking_cutoff: usize,
 }
 
 impl Default for Config {
@@ -63,6 +106,14 @@ impl Default for Config {
             limit: FetchLimit::default(),
             slots: 4,
             wait_slot: Duration::from_secs(20),
+            flow_control: FlowControl::default(),
+            base_cache: None,
+            validation: Validate::default(),
+            validation_policy: ValidationPolicy::default(),
+            sigrefs_rollback: Rollback::default(),
+            send_correlation_id: false,
+            bandwidth: Bandwidth::default(),
+            tracking_cutoff: link_replication::fetch::DEFAULT_TRACKING_CUTOFF,
         }
     }
 }
@@ -73,12 +124,26 @@ pub struct Replication {
     slots: Arc<Semaphore>,
     odb: link_replication::io::Odb,
     rdb: link_git::refs::db::Refdb,
+    in_flight: InFlight,
+    throttle: Throttle,
+    fetch_spec: FetchSpec,
 }
 
 impl Replication {
     pub fn new(paths: &Paths, config: Config) -> Result<Self, error::Init> {
+        Self::with_remote_odb(paths, config, None)
+    }
+
+    /// Like [`Replication::new`], but look up objects not found on local disk
+    /// in the `remote` object store.
+    pub fn with_remote_odb(
+        paths: &Paths,
+        config: Config,
+        remote: Option<Box<dyn RemoteOdb>>,
+    ) -> Result<Self, error::Init> {
         let slots = Arc::new(Semaphore::new(config.slots));
-        let odb = link_replication::io::Odb::open(paths.git_dir()).map_err(error::Init::Odb)?;
+        let odb = link_replication::io::Odb::open_with(paths.git_dir(), remote)
+            .map_err(error::Init::Odb)?;
         let rdb = link_git::refs::db::Refdb::open(paths.git_dir())?;
 
         Ok(Self {
@@ -86,9 +151,42 @@ impl Replication {
             slots,
             odb,
             rdb,
+            in_flight: InFlight::default(),
+            throttle: Throttle::new(config.bandwidth),
+            fetch_spec: FetchSpec::default().with_tracking_cutoff(config.tracking_cutoff),
         })
     }
 
+    /// Fetch the refs of tracked peers described by `spec`, in addition to
+    /// the signed ones.
+    pub fn with_fetch_spec(self, spec: FetchSpec) -> Self {
+        Self {
+            fetch_spec: spec,
+            ..self
+        }
+    }
+
+    /// Don't fetch the refs of tracked peers matching any of `exclude`, as the
+    /// v2 replication does.
+    pub fn with_exclusions<'a>(self, exclude: impl IntoIterator<Item = &'a Exclusion>) -> Self {
+        let fetch_spec = exclude.into_iter().fold(self.fetch_spec, |spec, excl| {
+            spec.with_exclusion(excl.pattern().as_str())
+        });
+        Self { fetch_spec, ..self }
+    }
+
+    /// The bytes sent and received by all replications so far.
+    pub fn traffic(&self) -> &Traffic {
+        self.throttle.traffic()
+    }
+
+    /// Replicate `urn` from the remote end of `conn`.
+    ///
+    /// Cloning if `urn` isn't in `store` yet, pulling otherwise. The
+    /// replication is driven by the async executor, only updates of the
+    /// [`Storage`] and indexing the packs received are run on the blocking
+    /// pool of `spawner`. The number of concurrent replications is bounded by
+    /// [`Config::slots`].
     pub async fn replicate<S>(
         &self,
         spawner: &Spawner,
@@ -97,57 +195,150 @@ impl Replication {
         urn: Urn,
         whoami: Option<LocalIdentity>,
     ) -> Result<Success, error::Replicate>
+    where
+        S: AsRef<Storage> + Send + 'static,
+    {
+        self.replicate_with_progress(spawner, store, conn, urn, whoami, None)
+            .await
+    }
+
+    /// Like [`Replication::replicate`], but report the [`progress::Event`]s of
+    /// the replication to `reporter`, if given.
+    pub async fn replicate_with_progress<S>(
+        &self,
+        spawner: &Spawner,
+        store: S,
+        conn: quic::Connection,
+        urn: Urn,
+        whoami: Option<LocalIdentity>,
+        reporter: Option<progress::Reporter>,
+    ) -> Result<Success, error::Replicate>
     where
         S: AsRef<Storage> + Send + 'static,
     {
         let slot = timeout(self.config.wait_slot, self.slots.acquire_arc()).await?;
         let limit = self.config.limit;
+        let flow_control = self.config.flow_control;
+        let base_cache = self.config.base_cache;
+        let validation = self.config.validation;
+        let policy = self.config.validation_policy;
+        let rollback = self.config.sigrefs_rollback;
+        let send_correlation_id = self.config.send_correlation_id;
         let odb = self.odb.clone();
         let rdb = self.rdb.clone();
-        let res = spawner
-            .blocking(move || {
-                let store = store.as_ref();
-                let have_urn = store.has_urn(&urn)?;
-                let remote_id = conn.remote_peer_id();
-                let info = UserInfo {
-                    name: store.config()?.user_name()?,
-                    peer_id: *store.peer_id(),
-                };
-                let urn = context::Urn::from(urn);
-                let refdb = link_replication::io::Refdb::new(info, odb.clone(), rdb.clone(), &urn)?;
-                let net = link_replication::io::Network::new(
-                    refdb.clone(),
-                    conn,
-                    store.path(),
-                    urn.clone(),
-                );
-                let mut cx = Context {
-                    urn,
-                    store,
-                    refdb,
-                    net,
-                };
-                let whoami = whoami.map(|id| link_replication::LocalIdentity {
-                    tip: id.content_id.into(),
-                    ids: id
-                        .delegations()
-                        .into_iter()
-                        .copied()
-                        .map(PeerId::from)
-                        .collect(),
-                });
-
-                if have_urn {
-                    debug!("pull");
-                    link_replication::pull(&mut cx, limit, remote_id, whoami)
-                } else {
-                    debug!("clone");
-                    link_replication::clone(&mut cx, limit, remote_id, whoami)
+        let in_flight = self.in_flight.clone();
+        let throttle = self.throttle.clone();
+        let spec = self.fetch_spec.clone();
+        let store = context::Store::new(store);
+        let (have_urn, git_dir, refdb) = store
+            .blocking(spawner, {
+                let urn = urn.clone();
+                move |store| -> Result<_, link_replication::Error> {
+                    let have_urn = store.has_urn(&urn)?;
+                    let info = UserInfo {
+                        name: store.config()?.user_name()?,
+                        peer_id: *store.peer_id(),
+                    };
+                    let urn = context::Urn::from(urn);
+                    let refdb = link_replication::io::Refdb::new(info, odb, rdb, &urn)?;
+                    Ok((have_urn, store.path().to_path_buf(), refdb))
                 }
             })
             .await
-            .map_err(error::Replicate::Replicate);
+            .map_err(error::Replicate::Prepare)?;
+
+        let remote_id = conn.remote_peer_id();
+        let urn = context::Urn::from(urn);
+        let net = link_replication::io::Network::new(refdb.clone(), conn, git_dir, urn.clone())
+            .with_progress(OnProgress::new(
+                move |msg| debug!(remote = %remote_id, "{}", msg),
+            ))
+            .with_flow_control(flow_control)
+            .with_in_flight(in_flight)
+            .with_throttle(throttle);
+        let net = match base_cache {
+            Some(cache) => net.with_base_cache(cache),
+            None => net,
+        };
+        let net = match reporter {
+            Some(reporter) => net.with_progress_events(reporter),
+            None => net,
+        };
+        let net = if send_correlation_id {
+            net.with_correlation_id()
+        } else {
+            net
+        };
+        let peer_id = store.with(|store| *store.peer_id());
+        let mut cx = Context {
+            urn,
+            store,
+            peer_id,
+            spawner,
+            refdb,
+            net,
+        };
+        let whoami = whoami.map(|id| link_replication::LocalIdentity {
+            tip: id.content_id.into(),
+            ids: id
+                .delegations()
+                .into_iter()
+                .copied()
+                .map(PeerId::from)
+                .collect(),
+        });
+
+        let res = if have_urn {
+            debug!("pull");
+            link_replication::pull(
+                &mut cx, limit, spec, remote_id, whoami, validation, policy, rollback,
+            )
+            .await
+        } else {
+            debug!("clone");
+            link_replication::clone(
+                &mut cx, limit, spec, remote_id, whoami, validation, policy, rollback,
+            )
+            .await
+        }
+        .map_err(error::Replicate::Replicate);
         drop(slot);
         res
     }
+
+    /// Replicate each of `urns` over the same connection `conn`.
+    ///
+    /// Up to [`Config::slots`] URNs are replicated concurrently, interleaving
+    /// their negotiations on `conn`. The results are returned in the order of
+    /// `urns`.
+    pub async fn replicate_many<P>(
+        &self,
+        spawner: &Spawner,
+        pool: &P,
+        conn: quic::Connection,
+        urns: impl IntoIterator<Item = Urn>,
+        whoami: Option<LocalIdentity>,
+    ) -> Vec<(Urn, Result<Success, error::Replicate>)>
+    where
+        P: Pooled<Storage> + Send + 'static,
+    {
+        stream::iter(urns)
+            .map(|urn| {
+                let conn = conn.clone();
+                let whoami = whoami.clone();
+                async move {
+                    let res = match pool.get().await {
+                        Ok(store) => {
+                            self.replicate(spawner, store, conn, urn.clone(), whoami)
+                                .await
+                        },
+                        Err(e) => Err(e.into()),
+                    };
+                    (urn, res)
+                }
+            })
+            .buffered(self.config.slots.max(1))
+            .collect()
+            .await
+    }
 }
@@ -8,24 +8,34 @@ use std::{
     collections::{BTreeSet, HashMap},
     convert::TryFrom,
     ops::Deref,
+    path::Path,
+    sync::Arc,
     time::Duration,
 };
 
-use bstr::BStr;
+use bstr::{BStr, BString};
 use data::NonEmpty;
 use either::{Either, Either::*};
+use link_async::Spawner;
 use link_replication::{
     io,
     namespace,
     oid,
+    progress,
     Applied,
+    AsyncRefdb,
+    AsyncSignedRefs,
+    AsyncTracking,
+    CorrelationId,
     FilteredRef,
     Identities,
     LocalPeer,
     Namespace,
     Negotiation,
     Net,
+    Object,
     ObjectId,
+    Odb,
     RefScan,
     Refdb,
     SignedRefs,
@@ -36,6 +46,7 @@ use link_replication::{
     VerifiedIdentity,
 };
 use multihash::Multihash;
+use parking_lot::Mutex;
 use std_ext::Void;
 
 use crate::{
@@ -89,6 +100,9 @@ pub mod error {
 
         #[error(transparent)]
         Refs(#[from] refs::stored::Error),
+
+        #[error("failed to determine the ancestry of signed refs")]
+        Ancestry(#[source] git2::Error),
     }
 
     #[derive(Debug, Error)]
@@ -112,12 +126,50 @@ pub mod error {
 
 type Network = io::Network<Urn, io::Refdb<io::Odb>, io::Odb, quic::Connection>;
 
+/// Shared handle to the [`Storage`] of a replication.
+///
+/// [`Storage`] is not `Sync`, so it is guarded by a lock, which allows to move
+/// blocking operations onto another thread while the replication is driven by
+/// the async executor.
+#[derive(Clone)]
+pub(super) struct Store(Arc<Mutex<Box<dyn AsRef<Storage> + Send>>>);
+
+impl Store {
+    pub fn new<S>(store: S) -> Self
+    where
+        S: AsRef<Storage> + Send + 'static,
+    {
+        Self(Arc::new(Mutex::new(Box::new(store))))
+    }
+
+    /// Run `f` on the current thread.
+    pub fn with<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&Storage) -> T,
+    {
+        f(self.0.lock().as_ref().as_ref())
+    }
+
+    /// Run `f` on the blocking pool of `spawner`.
+    pub async fn blocking<F, T>(&self, spawner: &Spawner, f: F) -> T
+    where
+        F: FnOnce(&Storage) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.clone();
+        spawner.blocking(move || store.with(f)).await
+    }
+}
+
 /// Context for a replication v3 run.
 ///
 /// Implements the (effect) traits required by the `link-replication` crate.
+/// Storage updates are run on the blocking pool of `spawner`.
 pub struct Context<'a> {
     pub(super) urn: Urn,
-    pub(super) store: &'a Storage,
+    pub(super) store: Store,
+    pub(super) peer_id: PeerId,
+    pub(super) spawner: &'a Spawner,
     pub(super) refdb: io::Refdb<io::Odb>,
     pub(super) net: Network,
 }
@@ -134,24 +186,27 @@ impl<'a> Context<'a> {
     {
         match id {
             SomeIdentity::Person(p) => {
-                let verified = self
-                    .store
-                    .read_only()
-                    .identities::<Person>()
-                    .verify(*p.content_id)?;
+                let verified = self.store.with(|store| {
+                    store
+                        .read_only()
+                        .identities::<Person>()
+                        .verify(*p.content_id)
+                })?;
                 Ok(SomeVerifiedIdentity::Person(verified))
             },
 
             SomeIdentity::Project(p) => {
-                let verified = self.store.read_only().identities::<Project>().verify(
-                    *p.content_id,
-                    |urn| {
-                        let urn = Urn(urn);
-                        resolve(&urn)
-                            .map(|oid| git_ext::Oid::from(oid.as_ref().to_owned()).into())
-                            .ok_or(error::Verification::MissingDelegate(urn.0))
-                    },
-                )?;
+                let verified = self.store.with(|store| {
+                    store
+                        .read_only()
+                        .identities::<Project>()
+                        .verify(*p.content_id, |urn| {
+                            let urn = Urn(urn);
+                            resolve(&urn)
+                                .map(|oid| git_ext::Oid::from(oid.as_ref().to_owned()).into())
+                                .ok_or(error::Verification::MissingDelegate(urn.0))
+                        })
+                })?;
                 Ok(SomeVerifiedIdentity::Project(verified))
             },
 
@@ -285,11 +340,12 @@ impl Identities for Context<'_> {
         F: Fn(&Self::Urn) -> Option<T>,
         T: AsRef<oid>,
     {
-        let id = self
-            .store
-            .read_only()
-            .identities::<Void>()
-            .some_identity(*git_ext::Oid::from(head.as_ref().to_owned()))?;
+        let id = self.store.with(|store| {
+            store
+                .read_only()
+                .identities::<Void>()
+                .some_identity(*git_ext::Oid::from(head.as_ref().to_owned()))
+        })?;
         self.verify(id, resolve)
     }
 
@@ -307,16 +363,12 @@ impl Identities for Context<'_> {
         match (a, b) {
             (Person(x), Person(y)) => self
                 .store
-                .read_only()
-                .identities()
-                .newer(x, y)
+                .with(|store| store.read_only().identities().newer(x, y))
                 .map(Person)
                 .map_err(|e| Error::Other(Box::new(e))),
             (Project(x), Project(y)) => self
                 .store
-                .read_only()
-                .identities()
-                .newer(x, y)
+                .with(|store| store.read_only().identities().newer(x, y))
                 .map(Project)
                 .map_err(|e| Error::Other(Box::new(e))),
             (x, y) => Err(Error::TypeMismatch { a: x, b: y }),
@@ -329,18 +381,30 @@ impl SignedRefs for Context<'_> {
     type Error = error::Sigrefs;
 
     fn load(&self, of: &PeerId, cutoff: usize) -> Result<Option<Sigrefs<Self::Oid>>, Self::Error> {
-        match refs::load(&self.store, &self.urn, Some(of))? {
+        match self
+            .store
+            .with(|store| refs::load(store, &self.urn, Some(of)))?
+        {
             None => Ok(None),
             Some(refs::Loaded { at, refs: signed }) => {
                 let refs = signed
                     .iter_categorised()
                     .map(|((name, oid), cat)| (format!("refs/{}/{}", cat, name).into(), *oid))
                     .collect::<HashMap<_, _>>();
-                let mut remotes = refs::Refs::from(signed).remotes;
+                let refs::Refs {
+                    mut remotes,
+                    freshness,
+                    ..
+                } = refs::Refs::from(signed);
                 remotes.cutoff_mut(cutoff);
                 let remotes = remotes.flatten().copied().collect();
 
-                Ok(Some(Sigrefs { at, refs, remotes }))
+                Ok(Some(Sigrefs {
+                    at,
+                    refs,
+                    remotes,
+                    timestamp: freshness.map(|fresh| fresh.timestamp),
+                }))
             },
         }
     }
@@ -351,62 +415,96 @@ impl SignedRefs for Context<'_> {
         signed_by: &PeerId,
         cutoff: usize,
     ) -> Result<Option<Sigrefs<Self::Oid>>, Self::Error> {
-        match refs::load_at(&self.store, treeish.into().into(), Some(signed_by))? {
+        let at = treeish.into().into();
+        match self
+            .store
+            .with(|store| refs::load_at(store, at, Some(signed_by)))?
+        {
             None => Ok(None),
             Some(refs::Loaded { at, refs: signed }) => {
                 let refs = signed
                     .iter_categorised()
                     .map(|((name, oid), cat)| (format!("refs/{}/{}", cat, name).into(), *oid))
                     .collect::<HashMap<_, _>>();
-                let mut remotes = refs::Refs::from(signed).remotes;
+                let refs::Refs {
+                    mut remotes,
+                    freshness,
+                    ..
+                } = refs::Refs::from(signed);
                 remotes.cutoff_mut(cutoff);
                 let remotes = remotes.flatten().copied().collect();
 
-                Ok(Some(Sigrefs { at, refs, remotes }))
+                Ok(Some(Sigrefs {
+                    at,
+                    refs,
+                    remotes,
+                    timestamp: freshness.map(|fresh| fresh.timestamp),
+                }))
             },
         }
     }
 
     fn update(&self) -> Result<Option<Self::Oid>, Self::Error> {
-        use backoff::ExponentialBackoff;
-        use refs::Updated::*;
-
-        // XXX: let this be handled by `git-ref`
-        let cfg = ExponentialBackoff {
-            current_interval: Duration::from_millis(100),
-            initial_interval: Duration::from_millis(100),
-            max_interval: Duration::from_secs(1),
-            ..Default::default()
-        };
-        backoff::retry(cfg, || {
-            let op = refs::Refs::update(self.store, &self.urn)
-                .map_err(error::Sigrefs::from)
-                .map_err(backoff::Error::Permanent);
-            match op? {
-                Updated { at, .. } | Unchanged { at, .. } => Ok(Some(at.into())),
-                ConcurrentlyModified => Err(backoff::Error::Transient(error::Sigrefs::Contended)),
-            }
-        })
-        .map_err(|e| match e {
-            backoff::Error::Permanent(inner) => inner,
-            backoff::Error::Transient(inner) => inner,
-        })
+        self.store.with(|store| update_sigrefs(store, &self.urn))
+    }
+
+    fn succeeds(
+        &self,
+        new: impl Into<ObjectId>,
+        old: impl Into<ObjectId>,
+    ) -> Result<bool, Self::Error> {
+        let new = git2::Oid::from(git_ext::Oid::from(new.into()));
+        let old = git2::Oid::from(git_ext::Oid::from(old.into()));
+        if new == old {
+            return Ok(true);
+        }
+        self.store
+            .with(|store| store.as_raw().graph_descendant_of(new, old))
+            .map_err(error::Sigrefs::Ancestry)
+    }
+}
+
+#[async_trait]
+impl AsyncSignedRefs for Context<'_> {
+    async fn update(&mut self) -> Result<Option<Self::Oid>, Self::Error> {
+        let urn = self.urn.clone();
+        self.store
+            .blocking(self.spawner, move |store| update_sigrefs(store, &urn))
+            .await
     }
 }
 
-#[allow(clippy::type_complexity)]
-impl<'a> Tracking for Context<'a> {
+fn update_sigrefs(store: &Storage, urn: &Urn) -> Result<Option<git_ext::Oid>, error::Sigrefs> {
+    use backoff::ExponentialBackoff;
+    use refs::Updated::*;
+
+    // XXX: let this be handled by `git-ref`
+    let cfg = ExponentialBackoff {
+        current_interval: Duration::from_millis(100),
+        initial_interval: Duration::from_millis(100),
+        max_interval: Duration::from_secs(1),
+        ..Default::default()
+    };
+    backoff::retry(cfg, || {
+        let op = refs::Refs::update(store, urn)
+            .map_err(error::Sigrefs::from)
+            .map_err(backoff::Error::Permanent);
+        match op? {
+            Updated { at, .. } | Unchanged { at, .. } => Ok(Some(at.into())),
+            ConcurrentlyModified => Err(backoff::Error::Transient(error::Sigrefs::Contended)),
+        }
+    })
+    .map_err(|e| match e {
+        backoff::Error::Permanent(inner) => inner,
+        backoff::Error::Transient(inner) => inner,
+    })
+}
+
+impl Tracking for Context<'_> {
     type Urn = Urn;
 
-    type Tracked = tracking::TrackedPeers<
-        'a,
-        <Storage as tracking::git::refdb::Read<'a>>::References,
-        <Storage as tracking::git::refdb::Read<'a>>::IterError,
-    >;
-    type Updated = std::iter::Map<
-        std::vec::IntoIter<tracking::batch::Updated>,
-        fn(tracking::batch::Updated) -> Either<PeerId, Self::Urn>,
-    >;
+    type Tracked = std::vec::IntoIter<Result<PeerId, Self::TrackedError>>;
+    type Updated = std::vec::IntoIter<Either<PeerId, Self::Urn>>;
 
     type TrackedError = tracking::error::TrackedPeers;
     type TrackError = tracking::error::Batch;
@@ -415,51 +513,101 @@ impl<'a> Tracking for Context<'a> {
     where
         I: IntoIterator<Item = link_replication::TrackingRel<Self::Urn>>,
     {
-        use link_replication::TrackingRel;
-        use once_cell::sync::Lazy;
-        use tracking::{
-            batch::{Action, Applied, Updated::*},
-            reference::{RefName, Remote},
-            Ref,
-        };
+        self.store
+            .with(|store| track(store, &self.urn, iter))
+            .map(Vec::into_iter)
+    }
 
-        static CONFIG_FULL: Lazy<tracking::Config> = Lazy::new(|| tracking::Config {
-            data: true,
-            cobs: tracking::config::Cobs::allow_all(),
-        });
-        static CONFIG_MIN: Lazy<tracking::Config> = Lazy::new(|| tracking::Config {
-            data: false,
-            cobs: tracking::config::Cobs::deny_all(),
-        });
-
-        let iter = iter.into_iter();
-        let mut seen = BTreeSet::<Urn>::new();
-        let act = iter.filter_map(|rel| match rel {
-            TrackingRel::Delegation(Right(urn)) | TrackingRel::SelfRef(urn) => {
-                (!seen.contains(&urn)).then(|| {
-                    seen.insert(urn.clone());
-                    Action::Track {
-                        urn: Cow::from(urn.0),
-                        peer: None,
-                        config: &CONFIG_MIN,
-                        policy: tracking::policy::Track::MustNotExist,
-                    }
-                })
-            },
+    fn untrack<I>(&mut self, peers: I) -> Result<(), Self::TrackError>
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        self.store.with(|store| untrack(store, &self.urn, peers))
+    }
+
+    fn tracked(&self) -> Result<Self::Tracked, Self::TrackedError> {
+        // The lock on the storage can't outlive this call
+        self.store.with(|store| {
+            tracking::tracked_peers(store, Some(&self.urn))
+                .map(|peers| peers.collect::<Vec<_>>().into_iter())
+        })
+    }
+}
+
+#[async_trait]
+impl AsyncTracking for Context<'_> {
+    async fn track(
+        &mut self,
+        rels: Vec<link_replication::TrackingRel<Self::Urn>>,
+    ) -> Result<Vec<Either<PeerId, Self::Urn>>, Self::TrackError> {
+        let urn = self.urn.clone();
+        self.store
+            .blocking(self.spawner, move |store| track(store, &urn, rels))
+            .await
+    }
+
+    async fn untrack(&mut self, peers: Vec<PeerId>) -> Result<(), Self::TrackError> {
+        let urn = self.urn.clone();
+        self.store
+            .blocking(self.spawner, move |store| untrack(store, &urn, peers))
+            .await
+    }
+}
 
-            TrackingRel::Delegation(Left(id)) => (!seen.contains(&self.urn)).then(|| {
-                seen.insert(self.urn.clone());
+fn track<I>(
+    store: &Storage,
+    urn: &Urn,
+    iter: I,
+) -> Result<Vec<Either<PeerId, Urn>>, tracking::error::Batch>
+where
+    I: IntoIterator<Item = link_replication::TrackingRel<Urn>>,
+{
+    use link_replication::TrackingRel;
+    use once_cell::sync::Lazy;
+    use tracking::{
+        batch::{Action, Applied, Updated::*},
+        reference::{RefName, Remote},
+        Ref,
+    };
+
+    static CONFIG_FULL: Lazy<tracking::Config> = Lazy::new(|| tracking::Config {
+        data: true,
+        cobs: tracking::config::Cobs::allow_all(),
+    });
+    static CONFIG_MIN: Lazy<tracking::Config> = Lazy::new(|| tracking::Config {
+        data: false,
+        cobs: tracking::config::Cobs::deny_all(),
+    });
+
+    let iter = iter.into_iter();
+    let mut seen = BTreeSet::<Urn>::new();
+    let act = iter.filter_map(|rel| match rel {
+        TrackingRel::Delegation(Right(urn)) | TrackingRel::SelfRef(urn) => (!seen.contains(&urn))
+            .then(|| {
+                seen.insert(urn.clone());
                 Action::Track {
-                    urn: Cow::from(self.urn.deref()),
-                    peer: Some(id),
-                    config: &CONFIG_FULL,
+                    urn: Cow::from(urn.0),
+                    peer: None,
+                    config: &CONFIG_MIN,
                     policy: tracking::policy::Track::MustNotExist,
                 }
             }),
-        });
-        let Applied { updates, .. } = tracking::batch(self.store, act)?;
 
-        Ok(updates.into_iter().map(|up| match up {
+        TrackingRel::Delegation(Left(id)) => (!seen.contains(urn)).then(|| {
+            seen.insert(urn.clone());
+            Action::Track {
+                urn: Cow::from(urn.deref()),
+                peer: Some(id),
+                config: &CONFIG_FULL,
+                policy: tracking::policy::Track::MustNotExist,
+            }
+        }),
+    });
+    let Applied { updates, .. } = tracking::batch(store, act)?;
+
+    Ok(updates
+        .into_iter()
+        .map(|up| match up {
             Tracked {
                 reference:
                     Ref {
@@ -474,12 +622,24 @@ impl<'a> Tracking for Context<'a> {
             Untracked { .. } => {
                 unreachable!("`Action::Track` yielded `Updated::Untracked`")
             },
-        }))
-    }
+        })
+        .collect())
+}
 
-    fn tracked(&self) -> Result<Self::Tracked, Self::TrackedError> {
-        tracking::tracked_peers(self.store, Some(&self.urn))
-    }
+fn untrack<I>(store: &Storage, urn: &Urn, peers: I) -> Result<(), tracking::error::Batch>
+where
+    I: IntoIterator<Item = PeerId>,
+{
+    use tracking::batch::Action;
+
+    let act = peers.into_iter().map(|peer| Action::Untrack {
+        urn: Cow::from(urn.deref()),
+        peer,
+        policy: tracking::policy::Untrack::MustExist,
+    });
+    tracking::batch(store, act)?;
+
+    Ok(())
 }
 
 impl<'c> Refdb for Context<'c> {
@@ -506,6 +666,62 @@ impl<'c> Refdb for Context<'c> {
     fn reload(&mut self) -> Result<(), Self::ReloadError> {
         self.refdb.reload()
     }
+
+    fn find_case_folded(
+        &self,
+        refname: impl AsRef<BStr>,
+    ) -> Result<Option<BString>, Self::FindError> {
+        self.refdb.find_case_folded(refname)
+    }
+}
+
+#[async_trait]
+impl AsyncRefdb for Context<'_> {
+    async fn update(
+        &mut self,
+        updates: Vec<Update<'static>>,
+    ) -> Result<Applied<'static>, Self::TxError> {
+        let mut refdb = self.refdb.clone();
+        let (refdb, applied) = self
+            .spawner
+            .blocking(move || {
+                let applied = Refdb::update(&mut refdb, updates);
+                (refdb, applied)
+            })
+            .await;
+        self.refdb = refdb;
+        applied
+    }
+}
+
+impl Odb for Context<'_> {
+    type LookupError = <io::Refdb<io::Odb> as Odb>::LookupError;
+    type RevwalkError = <io::Refdb<io::Odb> as Odb>::RevwalkError;
+    type AddPackError = <io::Refdb<io::Odb> as Odb>::AddPackError;
+
+    fn contains(&self, oid: impl AsRef<oid>) -> bool {
+        Odb::contains(&self.refdb, oid)
+    }
+
+    fn lookup<'a>(
+        &self,
+        oid: impl AsRef<oid>,
+        buf: &'a mut Vec<u8>,
+    ) -> Result<Option<Object<'a>>, Self::LookupError> {
+        Odb::lookup(&self.refdb, oid, buf)
+    }
+
+    fn is_in_ancestry_path(
+        &self,
+        new: impl Into<ObjectId>,
+        old: impl Into<ObjectId>,
+    ) -> Result<bool, Self::RevwalkError> {
+        Odb::is_in_ancestry_path(&self.refdb, new, old)
+    }
+
+    fn add_pack(&self, path: impl AsRef<Path>) -> Result<(), Self::AddPackError> {
+        Odb::add_pack(&self.refdb, path)
+    }
 }
 
 impl<'a> RefScan for &'a Context<'_> {
@@ -522,7 +738,7 @@ impl<'a> RefScan for &'a Context<'_> {
     }
 }
 
-#[async_trait(?Send)]
+#[async_trait]
 impl Net for Context<'_> {
     type Error = <Network as Net>::Error;
 
@@ -531,11 +747,27 @@ impl Net for Context<'_> {
         neg: N,
     ) -> Result<(N, Result<Vec<FilteredRef<T>>, SkippedFetch>), Self::Error>
     where
-        N: Negotiation<T> + Send,
+        N: Negotiation<T> + Send + Sync,
         T: Send + 'static,
     {
         self.net.run_fetch(neg).await
     }
+
+    async fn has_objects(&self, oids: Vec<ObjectId>) -> Result<BTreeSet<ObjectId>, Self::Error> {
+        self.net.has_objects(oids).await
+    }
+
+    fn correlate(&mut self, id: CorrelationId) {
+        self.net.correlate(id)
+    }
+
+    fn progress(&self, event: progress::Event) {
+        self.net.progress(event)
+    }
+
+    fn bytes_received(&self) -> u64 {
+        self.net.bytes_received()
+    }
 }
 
 #[async_trait]
@@ -551,10 +783,15 @@ impl io::Connection for quic::Connection {
         let up = upgrade::upgrade(bi, upgrade::Git).await?;
         Ok(up.into_stream().split())
     }
+
+    fn preopen_fetch_stream(&self) -> bool {
+        // Streams are multiplexed over the same QUIC connection
+        true
+    }
 }
 
 impl LocalPeer for Context<'_> {
     fn id(&self) -> &PeerId {
-        self.store.peer_id()
+        &self.peer_id
     }
 }
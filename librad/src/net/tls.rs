@@ -24,23 +24,49 @@ use rustls::{
     SignatureScheme,
     TLSError,
 };
-use time::{Date, OffsetDateTime};
+use time::{Date, Duration, OffsetDateTime};
 
-use crate::{net::x509, PeerId, Signer};
+use crate::{
+    net::x509::{self, Clock},
+    PeerId,
+    Signer,
+};
+
+/// How far the clocks of two peers may diverge for them to accept each other's
+/// certificates.
+///
+/// The validity periods of certificates have a granularity of days, so the
+/// tolerance is, too.
+pub const MAX_CLOCK_SKEW: Duration = Duration::DAY;
 
 pub fn make_client_config<S>(signer: S) -> Result<rustls::ClientConfig, S::Error>
 where
     S: Signer + Clone + Send + Sync + 'static,
     S::Error: std::error::Error + Send + Sync + 'static,
+{
+    make_client_config_with_clock(signer, x509::SystemClock)
+}
+
+/// Like [`make_client_config`], but determine the validity of certificates
+/// using `clock`.
+pub fn make_client_config_with_clock<S, C>(
+    signer: S,
+    clock: C,
+) -> Result<rustls::ClientConfig, S::Error>
+where
+    S: Signer + Clone + Send + Sync + 'static,
+    S::Error: std::error::Error + Send + Sync + 'static,
+    C: Clock + 'static,
 {
     let peer_id = PeerId::from_signer(&signer);
-    let cert = x509::Certificate::generate(&signer)?;
+    let clock: Arc<dyn Clock> = Arc::new(clock);
+    let cert = x509::Certificate::generate_at(&signer, clock.now())?;
 
     let mut cfg = rustls::ClientConfig::new();
     cfg.versions = vec![rustls::ProtocolVersion::TLSv1_3];
-    cfg.client_auth_cert_resolver = Arc::new(CertResolver::new(signer, cert));
+    cfg.client_auth_cert_resolver = Arc::new(CertResolver::new(signer, cert, clock.clone()));
     cfg.dangerous()
-        .set_certificate_verifier(Arc::new(RadServerCertVerifier::new(peer_id)));
+        .set_certificate_verifier(Arc::new(RadServerCertVerifier::new(peer_id, clock)));
 
     Ok(cfg)
 }
@@ -49,13 +75,29 @@ pub fn make_server_config<S>(signer: S) -> Result<rustls::ServerConfig, S::Error
 where
     S: Signer + Clone + Send + Sync + 'static,
     S::Error: std::error::Error + Send + Sync + 'static,
+{
+    make_server_config_with_clock(signer, x509::SystemClock)
+}
+
+/// Like [`make_server_config`], but determine the validity of certificates
+/// using `clock`.
+pub fn make_server_config_with_clock<S, C>(
+    signer: S,
+    clock: C,
+) -> Result<rustls::ServerConfig, S::Error>
+where
+    S: Signer + Clone + Send + Sync + 'static,
+    S::Error: std::error::Error + Send + Sync + 'static,
+    C: Clock + 'static,
 {
     let peer_id = PeerId::from_signer(&signer);
-    let cert = x509::Certificate::generate(&signer)?;
+    let clock: Arc<dyn Clock> = Arc::new(clock);
+    let cert = x509::Certificate::generate_at(&signer, clock.now())?;
 
-    let mut cfg = rustls::ServerConfig::new(Arc::new(RadClientCertVerifier::new(peer_id)));
+    let mut cfg =
+        rustls::ServerConfig::new(Arc::new(RadClientCertVerifier::new(peer_id, clock.clone())));
     cfg.versions = vec![rustls::ProtocolVersion::TLSv1_3];
-    cfg.cert_resolver = Arc::new(CertResolver::new(signer, cert));
+    cfg.cert_resolver = Arc::new(CertResolver::new(signer, cert, clock));
     // FIXME: session resumption is broken in rustls < 0.19 -- we can't get at
     // the client certs when resuming. Disable until we can upgrade (depends on
     // https://github.com/quinn-rs/quinn/pull/873)
@@ -72,31 +114,33 @@ struct Cert {
 struct CertResolver {
     signer: BoxedSigner,
     cert: RwLock<Cert>,
+    clock: Arc<dyn Clock>,
 }
 
 impl CertResolver {
-    fn new<S>(signer: S, cert: x509::Certificate) -> Self
+    fn new<S>(signer: S, cert: x509::Certificate, clock: Arc<dyn Clock>) -> Self
     where
         S: Signer + Clone + Send + Sync + 'static,
         S::Error: std::error::Error + Send + Sync + 'static,
     {
         let signer = BoxedSigner::from(SomeSigner { signer });
-        let expires = x509::validity_time_as_date(&cert.tbs_certificate.validity.not_after);
+        let expires = cert.not_after();
         let cert = rustls::Certificate(cert.to_der());
         Self {
             signer,
             cert: RwLock::new(Cert { expires, cert }),
+            clock,
         }
     }
 
     /// Get the certificate, or generate a fresh one if it has expired
     fn cert(&self) -> Result<rustls::Certificate, BoxedSignError> {
-        let now = OffsetDateTime::now_utc().date();
+        let now = self.clock.now();
         let read = self.cert.read().unwrap();
-        if now >= read.expires {
+        if now.date() >= read.expires {
             drop(read);
-            let fresh = x509::Certificate::generate(&self.signer)?;
-            let expires = x509::validity_time_as_date(&fresh.tbs_certificate.validity.not_after);
+            let fresh = x509::Certificate::generate_at(&self.signer, now)?;
+            let expires = fresh.not_after();
             let der = rustls::Certificate(fresh.to_der());
             {
                 let mut cert = self.cert.write().unwrap();
@@ -179,14 +223,19 @@ impl ResolvesServerCert for CertResolver {
 /// A certificte verifier for both server and client certificates which applies
 /// our own validation logic.
 ///
+/// Certificates must be valid at the local time, give or take
+/// [`MAX_CLOCK_SKEW`]. Their authenticity rests solely on the [`PeerId`] they
+/// are signed by.
+///
 /// From the standpoint of proper TLS, this is unutterably insecure.
 struct AccursedUnutterableUnsafeInsecureCertificateVerifier {
     local_id: PeerId,
+    clock: Arc<dyn Clock>,
 }
 
 impl AccursedUnutterableUnsafeInsecureCertificateVerifier {
-    fn new(local_id: PeerId) -> Self {
-        AccursedUnutterableUnsafeInsecureCertificateVerifier { local_id }
+    fn new(local_id: PeerId, clock: Arc<dyn Clock>) -> Self {
+        AccursedUnutterableUnsafeInsecureCertificateVerifier { local_id, clock }
     }
 }
 
@@ -201,19 +250,22 @@ impl ServerCertVerifier for AccursedUnutterableUnsafeInsecureCertificateVerifier
         dns_name: webpki::DNSNameRef,
         _ocsp_response: &[u8],
     ) -> Result<ServerCertVerified, TLSError> {
-        let (cert, ca) = presented_cert(presented_certs)?;
+        let (ee, ca) = presented_cert(presented_certs)?;
+        // Verify that the certificate's public key is a `PeerId`
+        let cert = x509::Certificate::from_der(&presented_certs[0].0)
+            .map_err(|e| TLSError::PeerIncompatibleError(e.to_string()))?;
 
         // Verify that we got a self-signed ed25519 certificate
-        cert.verify_is_valid_tls_server_cert(
+        ee.verify_is_valid_tls_server_cert(
             &[&webpki::ED25519],
             &webpki::TLSServerTrustAnchors(&[ca]),
             &[],
-            try_now()?,
+            valid_at(&cert, self.clock.now())?,
         )
         .map_err(TLSError::WebPKIError)?;
 
         // Check that it is valid for the DNS name the other side sent
-        cert.verify_is_valid_for_dns_name(dns_name)
+        ee.verify_is_valid_for_dns_name(dns_name)
             .map_err(TLSError::WebPKIError)?;
 
         // Verify that the DNS name is a radicle `PeerId`
@@ -224,10 +276,6 @@ impl ServerCertVerifier for AccursedUnutterableUnsafeInsecureCertificateVerifier
             ))
         })?;
 
-        // Verify that the certificate's public key is also a `PeerId`
-        let cert = x509::Certificate::from_der(&presented_certs[0].0)
-            .map_err(|e| TLSError::PeerIncompatibleError(e.to_string()))?;
-
         // Both must be equal
         if &peer_id_dns != cert.peer_id_ref() {
             return Err(TLSError::PeerIncompatibleError(
@@ -268,20 +316,20 @@ impl ClientCertVerifier for AccursedUnutterableUnsafeInsecureCertificateVerifier
         presented_certs: &[rustls::Certificate],
         _sni: Option<&webpki::DNSName>,
     ) -> Result<ClientCertVerified, TLSError> {
-        let (cert, ca) = presented_cert(presented_certs)?;
+        let (ee, ca) = presented_cert(presented_certs)?;
+        // Verify the presented cert's public key is a `PeerId`
+        let cert = x509::Certificate::from_der(&presented_certs[0].0)
+            .map_err(|e| TLSError::PeerIncompatibleError(e.to_string()))?;
+
         // Verify that we've got a self-signed ed25519 cert
-        cert.verify_is_valid_tls_client_cert(
+        ee.verify_is_valid_tls_client_cert(
             &[&webpki::ED25519],
             &webpki::TLSClientTrustAnchors(&[ca]),
             &[],
-            try_now()?,
+            valid_at(&cert, self.clock.now())?,
         )
         .map_err(TLSError::WebPKIError)?;
 
-        // Verify the presented cert's public key is a `PeerId`
-        let cert = x509::Certificate::from_der(&presented_certs[0].0)
-            .map_err(|e| TLSError::PeerIncompatibleError(e.to_string()))?;
-
         // We don't allow self-connections
         if cert.peer_id_ref() == &self.local_id {
             return Err(TLSError::PeerMisbehavedError(
@@ -312,7 +360,20 @@ fn presented_cert(
     Ok((cert, ca))
 }
 
-fn try_now() -> Result<webpki::Time, TLSError> {
-    webpki::Time::try_from(std::time::SystemTime::now())
-        .map_err(|_| TLSError::FailedToGetCurrentTime)
+/// The time at which to check the validity of `cert`: `now`, unless that is
+/// outside of the validity period of `cert` by no more than
+/// [`MAX_CLOCK_SKEW`], in which case the nearest end of the period.
+fn valid_at(cert: &x509::Certificate, now: OffsetDateTime) -> Result<webpki::Time, TLSError> {
+    let not_before = cert.not_before().midnight().assume_utc();
+    let not_after = cert.not_after().midnight().assume_utc();
+    let at = if now < not_before && not_before - now <= MAX_CLOCK_SKEW {
+        not_before
+    } else if now > not_after && now - not_after <= MAX_CLOCK_SKEW {
+        not_after
+    } else {
+        now
+    };
+    u64::try_from(at.unix_timestamp())
+        .map(webpki::Time::from_seconds_since_unix_epoch)
+        .map_err(|_| TLSError::General("validity time predates the epoch".into()))
 }
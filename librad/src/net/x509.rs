@@ -34,6 +34,33 @@ use time::{Date, OffsetDateTime};
 
 use crate::{keystore::sign::Signer, PeerId};
 
+/// A source of the current time.
+///
+/// The time determines the validity period of generated [`Certificate`]s, and
+/// whether presented ones are still valid.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+}
+
+/// The [`Clock`] of the local system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> OffsetDateTime + Send + Sync,
+{
+    fn now(&self) -> OffsetDateTime {
+        self()
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum FromDerError {
     #[error("the subject common name must be a valid PeerId")]
@@ -65,6 +92,15 @@ pub struct Certificate {
 impl Certificate {
     /// Generate a new self-signed [`Certificate`].
     pub fn generate<S>(signer: &S) -> Result<Self, S::Error>
+    where
+        S: Signer,
+        S::Error: std::error::Error,
+    {
+        Self::generate_at(signer, SystemClock.now())
+    }
+
+    /// Generate a new self-signed [`Certificate`], valid from `now`.
+    pub fn generate_at<S>(signer: &S, now: OffsetDateTime) -> Result<Self, S::Error>
     where
         S: Signer,
         S::Error: std::error::Error,
@@ -81,7 +117,7 @@ impl Certificate {
                 BitString::with_bytes(signer.public_key().as_ref()).into(),
             ),
         };
-        let validity = valid_until(Duration::from_secs(7889400)); // 3 months
+        let validity = valid_until(now, Duration::from_secs(7889400)); // 3 months
         let extensions = Extensions(vec![
             Extension::new_subject_alt_name(vec![GeneralName::DnsName(
                 peer_id.to_string().parse::<IA5String>().unwrap().into(),
@@ -169,6 +205,16 @@ impl Certificate {
     pub fn peer_id_ref(&self) -> &PeerId {
         self.as_ref()
     }
+
+    /// The first day this certificate is valid.
+    pub fn not_before(&self) -> Date {
+        validity_time_as_date(&self.cert.tbs_certificate.validity.not_before)
+    }
+
+    /// The day this certificate expires.
+    pub fn not_after(&self) -> Date {
+        validity_time_as_date(&self.cert.tbs_certificate.validity.not_after)
+    }
 }
 
 impl Deref for Certificate {
@@ -185,8 +231,7 @@ impl AsRef<PeerId> for Certificate {
     }
 }
 
-fn valid_until(d: Duration) -> Validity {
-    let now = OffsetDateTime::now_utc();
+fn valid_until(now: OffsetDateTime, d: Duration) -> Validity {
     let until = now + d;
     Validity {
         not_before: into_validity_time(now),
@@ -205,7 +250,7 @@ fn into_validity_time(t: OffsetDateTime) -> validity::Time {
     }
 }
 
-pub(crate) fn validity_time_as_date(t: &validity::Time) -> Date {
+fn validity_time_as_date(t: &validity::Time) -> Date {
     use validity::Time::*;
 
     let (y, m, d) = match t {
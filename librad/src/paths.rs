@@ -18,10 +18,12 @@ use directories::ProjectDirs;
 /// directory when created with [`Paths::from_root`].
 #[derive(Clone, Debug)]
 pub struct Paths {
+    config_dir: PathBuf,
     keys_dir: PathBuf,
     git_dir: PathBuf,
     git_includes_dir: PathBuf,
     cob_cache_dir: PathBuf,
+    audit_dir: PathBuf,
 }
 
 impl Paths {
@@ -40,6 +42,8 @@ impl Paths {
             git_dir: data_dir.join("git"),
             git_includes_dir: config_dir.join("git-includes"),
             cob_cache_dir: cache_dir.join("cob-cache"),
+            audit_dir: data_dir.join("audit"),
+            config_dir,
         }
         .init()
     }
@@ -48,14 +52,21 @@ impl Paths {
     pub fn from_root(root: impl AsRef<Path>) -> Result<Self, io::Error> {
         let root = root.as_ref();
         Self {
+            config_dir: root.to_path_buf(),
             keys_dir: root.join("keys"),
             git_dir: root.join("git"),
             git_includes_dir: root.join("git-includes"),
             cob_cache_dir: root.join("cob-cache"),
+            audit_dir: root.join("audit"),
         }
         .init()
     }
 
+    /// The directory containing the configuration files of the profile.
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
     pub fn keys_dir(&self) -> &Path {
         &self.keys_dir
     }
@@ -72,21 +83,30 @@ impl Paths {
         &self.cob_cache_dir
     }
 
+    /// The repository holding the [`crate::git::audit`] log of the profile.
+    pub fn audit_dir(&self) -> &Path {
+        &self.audit_dir
+    }
+
     pub fn all_dirs(&self) -> impl Iterator<Item = &Path> {
         // Nb. this pattern match is here to keep the map consistent with the
         // struct fields
         let Self {
+            config_dir,
             keys_dir,
             git_dir,
             git_includes_dir,
             cob_cache_dir,
+            audit_dir,
         } = self;
 
         vec![
+            config_dir.as_path(),
             keys_dir.as_path(),
             git_dir.as_path(),
             git_includes_dir.as_path(),
             cob_cache_dir.as_path(),
+            audit_dir.as_path(),
         ]
         .into_iter()
     }
@@ -6,18 +6,40 @@
 use std::{
     env,
     fmt,
+    fs,
     io,
     path::{Path, PathBuf},
 };
 
-use crate::paths::{project_dirs, Paths};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    crypto::{
+        keystore::{crypto::Crypto, FileStorage, Keystore as _},
+        PeerId,
+        PublicKey,
+        SecretKey,
+    },
+    git::storage::{self, Storage},
+    paths::{project_dirs, Paths},
+};
 
 pub mod id;
 pub use id::ProfileId;
 
+pub mod petnames;
+pub use petnames::Petnames;
+
+pub mod settings;
+pub use settings::Settings;
+
 pub const RAD_HOME: &str = "RAD_HOME";
 pub const RAD_PROFILE: &str = "RAD_PROFILE";
 
+/// The name of the file the [`SecretKey`] of a profile is stored in, within
+/// its [`Paths::keys_dir`].
+pub const KEY_FILE: &str = "librad.key";
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("the profile {0} does not exist")]
@@ -26,6 +48,10 @@ pub enum Error {
     ProfileId(#[from] id::Error),
     #[error(transparent)]
     Io(#[from] io::Error),
+    #[error(transparent)]
+    Keystore(Box<dyn std::error::Error + Send + Sync + 'static>),
+    #[error(transparent)]
+    Storage(#[from] storage::error::Init),
 }
 
 /// A [`Profile`] provides [`Paths`] scoped by an identifier.
@@ -72,6 +98,21 @@ impl RadHome {
             Self::Root(root) => root.clone(),
         })
     }
+
+    /// All directories under which data of the profile `id` may be stored.
+    fn profile_dirs(&self, id: &ProfileId) -> Result<Vec<PathBuf>, io::Error> {
+        Ok(match self {
+            Self::ProjectDirs => {
+                let proj = project_dirs()?;
+                vec![
+                    proj.config_dir().join(id),
+                    proj.data_dir().join(id),
+                    proj.cache_dir().join(id),
+                ]
+            },
+            Self::Root(root) => vec![root.join(id)],
+        })
+    }
 }
 
 impl Profile {
@@ -83,6 +124,30 @@ impl Profile {
         Self::from_home(home, Some(id))
     }
 
+    /// Create a new `Profile` like [`Profile::new`], and initialise it with a
+    /// freshly generated [`SecretKey`].
+    ///
+    /// The key is stored in the profile's [`Paths::keys_dir`], encrypted using
+    /// `crypto`, and the [`Storage`] of the profile is initialised for it.
+    /// Note that this will not set the active profile, to do that use
+    /// [`Profile::set`].
+    pub fn create<C>(home: &RadHome, crypto: C) -> Result<(Self, PeerId), Error>
+    where
+        C: Crypto,
+        C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+        C::SecretBox: Serialize + DeserializeOwned,
+    {
+        let profile = Self::new(home)?;
+        let key = SecretKey::new();
+        profile
+            .key_storage(crypto)
+            .put_key(key.clone())
+            .map_err(|e| Error::Keystore(Box::new(e)))?;
+        Storage::open(profile.paths(), key.clone())?;
+
+        Ok((profile, PeerId::from(key)))
+    }
+
     pub fn active(home: &RadHome) -> Result<Option<Self>, Error> {
         let id = ProfileId::active(home)?;
         id.map(|id| Self::from_home(home, Some(id))).transpose()
@@ -114,7 +179,11 @@ impl Profile {
     pub fn list(home: &RadHome) -> Result<Vec<Self>, Error> {
         let mut profiles = Vec::new();
         let config = home.config()?;
-        for entry in config.read_dir()? {
+        let entries = match config.read_dir() {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(profiles),
+            res => res?,
+        };
+        for entry in entries {
             let entry = entry?;
             if !entry.file_type()?.is_dir() {
                 continue;
@@ -127,6 +196,27 @@ impl Profile {
         Ok(profiles)
     }
 
+    /// Delete the profile `id` under `home`, including its keys and storage.
+    ///
+    /// If `id` is the active profile, there is no active profile afterwards.
+    /// This will error if the `id` does not exist under `home`.
+    pub fn delete(home: &RadHome, id: ProfileId) -> Result<(), Error> {
+        if !exists(home, &id)? {
+            return Err(Error::DoesNotExist(id));
+        }
+        if ProfileId::active(home)?.as_ref() == Some(&id) {
+            ProfileId::unset_active(home)?;
+        }
+        for dir in home.profile_dirs(&id)? {
+            match fs::remove_dir_all(&dir) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+                res => res?,
+            }
+        }
+
+        Ok(())
+    }
+
     /// Creates a profile by loading the profile identifier and paths from
     /// the environment variables or well-known file.
     ///
@@ -194,6 +284,25 @@ impl Profile {
     pub fn paths(&self) -> &Paths {
         &self.paths
     }
+
+    /// The [`settings::Store`] of this profile.
+    pub fn settings(&self) -> settings::Store {
+        settings::Store::new(self)
+    }
+
+    /// The [`petnames::Store`] of this profile.
+    pub fn petnames(&self) -> petnames::Store {
+        petnames::Store::new(self)
+    }
+
+    /// The [`FileStorage`] holding the [`SecretKey`] of this profile, see
+    /// [`KEY_FILE`].
+    pub fn key_storage<C>(&self, crypto: C) -> FileStorage<C, PublicKey, SecretKey, ()>
+    where
+        C: Crypto,
+    {
+        FileStorage::new(&self.paths.keys_dir().join(KEY_FILE), crypto)
+    }
 }
 
 fn exists(home: &RadHome, id: &ProfileId) -> Result<bool, Error> {
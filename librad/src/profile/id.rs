@@ -166,6 +166,15 @@ impl ProfileId {
         Ok(())
     }
 
+    /// Remove the `active_profile` file, if it exists.
+    pub fn unset_active(home: &RadHome) -> Result<(), Error> {
+        let path = home.config()?.join(ACTIVE);
+        match fs::remove_file(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            res => Ok(res?),
+        }
+    }
+
     pub fn as_str(&self) -> &str {
         &self.0
     }
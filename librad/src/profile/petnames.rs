@@ -0,0 +1,561 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Local petnames of a [`Profile`].
+//!
+//! A petname is a human-chosen name for a [`Urn`] or a [`PeerId`], which is
+//! only meaningful to the local user. Names are unique across both kinds of
+//! targets, so that a name can be resolved without knowing what it refers to.
+//!
+//! The petnames are stored in the [`super::settings`] of the profile, for
+//! example:
+//!
+//! ```toml
+//! version = 1
+//!
+//! [settings.petnames.urns]
+//! radicle-link = "rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo"
+//!
+//! [settings.petnames.peers]
+//! seed = "hyy5s7ysg96fqa91gbe7h38yddh4mkokft7y4htt8szt9e17sxoe3h"
+//! ```
+//!
+//! [`Store::export`] and [`Store::import`] use a standalone format, so the
+//! petnames can be shared between profiles:
+//!
+//! ```toml
+//! version = 1
+//!
+//! [urns]
+//! radicle-link = "rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo"
+//!
+//! [peers]
+//! seed = "hyy5s7ysg96fqa91gbe7h38yddh4mkokft7y4htt8szt9e17sxoe3h"
+//! ```
+//!
+//! This is also the format of [`PETNAMES_FILE`], where petnames were stored
+//! before they became part of the settings. The file is merged into the
+//! settings on the next write, and removed.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fmt,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{settings, Profile};
+use crate::{git::Urn, PeerId};
+
+/// The name of the file petnames were stored in before they became part of
+/// the [`settings`].
+pub const PETNAMES_FILE: &str = "petnames.toml";
+
+/// The version of the petnames file written by this library.
+pub const VERSION: u32 = 1;
+
+/// The maximum length of a [`Petname`].
+pub const MAX_LEN: usize = 64;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("invalid petname `{0}`: {1}")]
+    Invalid(String, &'static str),
+
+    #[error("petname `{name}` already refers to {existing}")]
+    Conflict { name: Petname, existing: Target },
+
+    #[error("unknown petname `{0}`")]
+    NotFound(Petname),
+
+    #[error("petname `{name}` refers to {target}, not a {expected}")]
+    Kind {
+        name: Petname,
+        target: Target,
+        expected: &'static str,
+    },
+
+    #[error("`{0}` is neither a Radicle URN, a peer id, nor a petname")]
+    Unresolvable(String),
+
+    #[error("unsupported petnames version {found}, expected at most {VERSION}")]
+    Version { found: u32 },
+
+    #[error("failed to parse {0}")]
+    Parse(PathBuf, #[source] toml::de::Error),
+
+    #[error(transparent)]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error(transparent)]
+    Settings(#[from] settings::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A validated petname.
+///
+/// Petnames are between 1 and [`MAX_LEN`] characters long, consist of ASCII
+/// alphanumerics and `._-`, and start with an alphanumeric. They must not be
+/// valid [`PeerId`]s, so that resolving a name is unambiguous.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Petname(String);
+
+impl Petname {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Petname {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if s.is_empty() || s.len() > MAX_LEN {
+            return Err(Error::Invalid(s, "must be between 1 and 64 characters"));
+        }
+        if !s.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return Err(Error::Invalid(s, "must start with a letter or digit"));
+        }
+        if !s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+        {
+            return Err(Error::Invalid(
+                s,
+                "may only contain letters, digits, and `._-`",
+            ));
+        }
+        if s.parse::<PeerId>().is_ok() {
+            return Err(Error::Invalid(s, "must not be a peer id"));
+        }
+
+        Ok(Self(s))
+    }
+}
+
+impl FromStr for Petname {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s.to_owned())
+    }
+}
+
+impl From<Petname> for String {
+    fn from(name: Petname) -> Self {
+        name.0
+    }
+}
+
+impl fmt::Display for Petname {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What a [`Petname`] refers to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Urn(Urn),
+    Peer(PeerId),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Urn(urn) => write!(f, "{}", urn),
+            Self::Peer(peer) => write!(f, "{}", peer),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(Self::Urn)
+            .or_else(|_| s.parse().map(Self::Peer))
+            .map_err(|_| Error::Unresolvable(s.to_owned()))
+    }
+}
+
+impl From<Urn> for Target {
+    fn from(urn: Urn) -> Self {
+        Self::Urn(urn)
+    }
+}
+
+impl From<PeerId> for Target {
+    fn from(peer: PeerId) -> Self {
+        Self::Peer(peer)
+    }
+}
+
+/// A [`PeerId`] given by the user, either directly or by its petname.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PeerOrPetname {
+    Peer(PeerId),
+    Petname(Petname),
+}
+
+impl PeerOrPetname {
+    /// Resolve to a [`PeerId`], looking up a petname in `petnames`.
+    pub fn resolve(&self, petnames: &Petnames) -> Result<PeerId, Error> {
+        match self {
+            Self::Peer(peer) => Ok(*peer),
+            Self::Petname(name) => petnames.resolve_peer(name.as_str()),
+        }
+    }
+}
+
+impl FromStr for PeerOrPetname {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(peer) => Ok(Self::Peer(peer)),
+            Err(_) => s
+                .parse()
+                .map(Self::Petname)
+                .map_err(|_| Error::Unresolvable(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for PeerOrPetname {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Peer(peer) => write!(f, "{}", peer),
+            Self::Petname(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl From<PeerId> for PeerOrPetname {
+    fn from(peer: PeerId) -> Self {
+        Self::Peer(peer)
+    }
+}
+
+/// How to handle a [`Petname`] which refers to different [`Target`]s when
+/// merging petnames, see [`Petnames::merge`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Abort, without changing anything.
+    Fail,
+    /// Keep the existing target.
+    Keep,
+    /// Replace the existing target.
+    Replace,
+}
+
+/// A [`Petname`] referring to different [`Target`]s, see [`Petnames::merge`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub name: Petname,
+    pub ours: Target,
+    pub theirs: Target,
+}
+
+/// A set of petnames.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Petnames {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    urns: BTreeMap<Petname, Urn>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    peers: BTreeMap<Petname, PeerId>,
+}
+
+impl Petnames {
+    pub fn is_empty(&self) -> bool {
+        self.urns.is_empty() && self.peers.is_empty()
+    }
+
+    /// The target `name` refers to, if any.
+    pub fn get(&self, name: &Petname) -> Option<Target> {
+        self.urns
+            .get(name)
+            .cloned()
+            .map(Target::Urn)
+            .or_else(|| self.peers.get(name).copied().map(Target::Peer))
+    }
+
+    /// Let `name` refer to `target`.
+    ///
+    /// Returns `false` if `name` already referred to `target`, and fails with
+    /// [`Error::Conflict`] if it refers to a different target.
+    pub fn insert(&mut self, name: Petname, target: impl Into<Target>) -> Result<bool, Error> {
+        let target = target.into();
+        match self.get(&name) {
+            Some(existing) if existing == target => Ok(false),
+            Some(existing) => Err(Error::Conflict { name, existing }),
+            None => {
+                self.replace(name, target);
+                Ok(true)
+            },
+        }
+    }
+
+    /// Let `name` refer to `target`, returning the target it previously
+    /// referred to.
+    pub fn replace(&mut self, name: Petname, target: impl Into<Target>) -> Option<Target> {
+        let previous = self.remove(&name);
+        match target.into() {
+            Target::Urn(urn) => {
+                self.urns.insert(name, urn);
+            },
+            Target::Peer(peer) => {
+                self.peers.insert(name, peer);
+            },
+        }
+        previous
+    }
+
+    /// Forget `name`, returning the target it referred to.
+    pub fn remove(&mut self, name: &Petname) -> Option<Target> {
+        self.urns
+            .remove(name)
+            .map(Target::Urn)
+            .or_else(|| self.peers.remove(name).map(Target::Peer))
+    }
+
+    /// The names referring to `target`.
+    pub fn names<'a>(&'a self, target: &'a Target) -> impl Iterator<Item = &'a Petname> + 'a {
+        self.iter()
+            .filter(move |(_, other)| other == target)
+            .map(|(name, _)| name)
+    }
+
+    /// All petnames, URNs first, in order of their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&Petname, Target)> + '_ {
+        self.urns
+            .iter()
+            .map(|(name, urn)| (name, Target::Urn(urn.clone())))
+            .chain(
+                self.peers
+                    .iter()
+                    .map(|(name, peer)| (name, Target::Peer(*peer))),
+            )
+    }
+
+    /// Resolve `s` to a [`Urn`], either by parsing it, or by looking it up as
+    /// a petname.
+    pub fn resolve_urn(&self, s: &str) -> Result<Urn, Error> {
+        if let Ok(urn) = s.parse() {
+            return Ok(urn);
+        }
+        match self.lookup(s)? {
+            Target::Urn(urn) => Ok(urn),
+            target => Err(Error::Kind {
+                name: s.parse()?,
+                target,
+                expected: "URN",
+            }),
+        }
+    }
+
+    /// Resolve `s` to a [`PeerId`], either by parsing it, or by looking it up
+    /// as a petname.
+    pub fn resolve_peer(&self, s: &str) -> Result<PeerId, Error> {
+        if let Ok(peer) = s.parse() {
+            return Ok(peer);
+        }
+        match self.lookup(s)? {
+            Target::Peer(peer) => Ok(peer),
+            target => Err(Error::Kind {
+                name: s.parse()?,
+                target,
+                expected: "peer id",
+            }),
+        }
+    }
+
+    /// Add the petnames of `other`, returning the names which referred to
+    /// different targets.
+    ///
+    /// With [`OnConflict::Fail`], nothing is added if there are any conflicts,
+    /// and the first one is returned as an [`Error::Conflict`].
+    pub fn merge(
+        &mut self,
+        other: Petnames,
+        on_conflict: OnConflict,
+    ) -> Result<Vec<Conflict>, Error> {
+        let mut conflicts = Vec::new();
+        let mut additions = Vec::new();
+        for (name, theirs) in other.iter() {
+            match self.get(name) {
+                Some(ours) if ours == theirs => {},
+                Some(ours) => conflicts.push(Conflict {
+                    name: name.clone(),
+                    ours,
+                    theirs,
+                }),
+                None => additions.push((name.clone(), theirs)),
+            }
+        }
+
+        if on_conflict == OnConflict::Fail {
+            if let Some(Conflict { name, ours, .. }) = conflicts.into_iter().next() {
+                return Err(Error::Conflict {
+                    name,
+                    existing: ours,
+                });
+            }
+            conflicts = Vec::new();
+        }
+        for (name, target) in additions {
+            self.replace(name, target);
+        }
+        if on_conflict == OnConflict::Replace {
+            for Conflict { name, theirs, .. } in &conflicts {
+                self.replace(name.clone(), theirs.clone());
+            }
+        }
+
+        Ok(conflicts)
+    }
+
+    fn lookup(&self, s: &str) -> Result<Target, Error> {
+        let name = s
+            .parse::<Petname>()
+            .map_err(|_| Error::Unresolvable(s.to_owned()))?;
+        self.get(&name).ok_or(Error::NotFound(name))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct File {
+    version: u32,
+    #[serde(default)]
+    urns: BTreeMap<Petname, Urn>,
+    #[serde(default)]
+    peers: BTreeMap<Petname, PeerId>,
+}
+
+impl File {
+    fn parse(path: &Path, content: &str) -> Result<Petnames, Error> {
+        let file: File = toml::from_str(content).map_err(|e| Error::Parse(path.to_owned(), e))?;
+        if file.version > VERSION {
+            return Err(Error::Version {
+                found: file.version,
+            });
+        }
+
+        Ok(Petnames {
+            urns: file.urns,
+            peers: file.peers,
+        })
+    }
+
+    fn render(petnames: &Petnames) -> Result<String, Error> {
+        Ok(toml::to_string(&File {
+            version: VERSION,
+            urns: petnames.urns.clone(),
+            peers: petnames.peers.clone(),
+        })?)
+    }
+}
+
+/// Access to the [`Petnames`] stored in the [`settings::Settings`].
+#[derive(Clone, Debug)]
+pub struct Store {
+    settings: settings::Store,
+    legacy: PathBuf,
+}
+
+impl Store {
+    /// The [`Store`] of `profile`.
+    pub fn new(profile: &Profile) -> Self {
+        Self::at(profile.settings().path())
+    }
+
+    /// A [`Store`] backed by the settings at a custom `path`.
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            legacy: path.with_file_name(PETNAMES_FILE),
+            settings: settings::Store::at(path),
+        }
+    }
+
+    /// The path of the settings the petnames are stored in.
+    pub fn path(&self) -> &Path {
+        self.settings.path()
+    }
+
+    /// Read the [`Petnames`], which are empty if they were never written.
+    ///
+    /// Petnames still found in [`PETNAMES_FILE`] are included, unless the
+    /// settings have a conflicting name.
+    pub fn load(&self) -> Result<Petnames, Error> {
+        let mut petnames = self.settings.load()?.petnames;
+        match fs::read_to_string(&self.legacy) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            res => {
+                let legacy = File::parse(&self.legacy, &res?)?;
+                petnames.merge(legacy, OnConflict::Keep)?;
+            },
+        }
+        Ok(petnames)
+    }
+
+    /// Write `petnames`, replacing the previous ones.
+    ///
+    /// The settings are replaced atomically, so concurrent readers see either
+    /// the previous or the new [`Petnames`]. Since `petnames` supersede the
+    /// ones in [`PETNAMES_FILE`], that file is removed afterwards.
+    pub fn store(&self, petnames: &Petnames) -> Result<(), Error> {
+        self.settings
+            .update(|settings| settings.petnames = petnames.clone())?;
+        match fs::remove_file(&self.legacy) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            res => Ok(res?),
+        }
+    }
+
+    /// Modify the [`Petnames`] using `f`, and write the result unless `f`
+    /// fails.
+    pub fn update<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Petnames) -> Result<T, Error>,
+    {
+        let mut petnames = self.load()?;
+        let res = f(&mut petnames)?;
+        self.store(&petnames)?;
+        Ok(res)
+    }
+
+    /// Write the [`Petnames`] to `out`.
+    pub fn export<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        let content = File::render(&self.load()?)?;
+        out.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    /// Merge the [`Petnames`] read from `input`, which were written by
+    /// [`Store::export`], see [`Petnames::merge`].
+    pub fn import<R: Read>(
+        &self,
+        mut input: R,
+        on_conflict: OnConflict,
+    ) -> Result<Vec<Conflict>, Error> {
+        let mut content = String::new();
+        input.read_to_string(&mut content)?;
+        let theirs = File::parse(Path::new("<import>"), &content)?;
+        self.update(|ours| ours.merge(theirs, on_conflict))
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Typed, versioned settings of a [`Profile`].
+//!
+//! The settings are stored in [`SETTINGS_FILE`] in the
+//! [`crate::paths::Paths::config_dir`] of the profile, for example:
+//!
+//! ```toml
+//! version = 1
+//!
+//! [settings]
+//! seeds = ["hyy5s7ysg96fqa91gbe7h38yddh4mkokft7y4htt8szt9e17sxoe3h@seed.example.com:12345"]
+//!
+//! [settings.tracking]
+//! data = true
+//! cobs = true
+//!
+//! [settings.network]
+//! network = "main"
+//! listen-addr = "0.0.0.0:8776"
+//!
+//! [settings.push]
+//! refs = ["refs/heads/*"]
+//! require-signed = true
+//!
+//! [[settings.mirrors]]
+//! url = "https://github.com/radicle-dev/radicle-link.git"
+//! urn = "rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo"
+//!
+//! [settings.petnames.peers]
+//! seed = "hyy5s7ysg96fqa91gbe7h38yddh4mkokft7y4htt8szt9e17sxoe3h"
+//! ```
+//!
+//! A missing file is equivalent to the [`Settings::default`]. Files written by
+//! a newer version of this library are rejected rather than misinterpreted.
+
+use std::{
+    fs,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+};
+
+use notify::Watcher as _;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{petnames::Petnames, Profile};
+use crate::{
+    git::{local::receive, tracking, Urn},
+    net,
+};
+
+/// The name of the settings file.
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+/// The version of the settings file written by this library.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("unsupported settings version {found}, expected at most {VERSION}")]
+    Version { found: u32 },
+
+    #[error("failed to parse {0}")]
+    Parse(PathBuf, #[source] toml::de::Error),
+
+    #[error(transparent)]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error("invalid network name: {0}")]
+    Network(&'static str),
+
+    #[error(transparent)]
+    Notify(#[from] notify::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The settings of a profile.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+    /// The seeds to connect to, in the form `<peer id>@<host>:<port>`.
+    pub seeds: Vec<String>,
+    /// The tracking policy applied when tracking a peer without specifying
+    /// one.
+    pub tracking: Tracking,
+    pub network: Network,
+    /// External repositories to mirror projects to.
+    pub mirrors: Vec<Mirror>,
+    /// The rules pushes from working copies into the monorepo must adhere to.
+    pub push: receive::Policy,
+    /// The local names of URNs and peers, see [`super::petnames`].
+    #[serde(skip_serializing_if = "Petnames::is_empty")]
+    pub petnames: Petnames,
+}
+
+/// The default tracking policy, see [`tracking::Config`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Tracking {
+    /// Whether to replicate the `heads`, `tags` and `notes` of tracked peers.
+    pub data: bool,
+    /// Whether to replicate all collaborative objects of tracked peers, or
+    /// none.
+    pub cobs: bool,
+}
+
+impl Default for Tracking {
+    fn default() -> Self {
+        Self {
+            data: true,
+            cobs: true,
+        }
+    }
+}
+
+impl Tracking {
+    pub fn config(&self) -> tracking::Config {
+        tracking::Config {
+            data: self.data,
+            cobs: if self.cobs {
+                tracking::config::Cobs::allow_all()
+            } else {
+                tracking::config::Cobs::deny_all()
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Network {
+    /// The name of the network to join, `main` if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// The address to listen on for peer connections.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_addr: Option<SocketAddr>,
+}
+
+impl Network {
+    /// The [`net::Network`] given by [`Network::network`].
+    pub fn network(&self) -> Result<net::Network, Error> {
+        match self.network.as_deref() {
+            None => Ok(net::Network::Main),
+            Some(main) if main.eq_ignore_ascii_case("main") => Ok(net::Network::Main),
+            Some("") => Err(Error::Network("custom network can't be empty")),
+            Some(custom) => custom.parse().map_err(Error::Network),
+        }
+    }
+}
+
+/// An external repository to mirror to.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Mirror {
+    /// The URL to push to.
+    pub url: String,
+    /// The project to mirror. If not set, the mirror applies to all projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub urn: Option<Urn>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct File {
+    version: u32,
+    #[serde(default)]
+    settings: Settings,
+}
+
+/// Access to the [`Settings`] stored at a path.
+#[derive(Clone, Debug)]
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    /// The [`Store`] of `profile`.
+    pub fn new(profile: &Profile) -> Self {
+        Self::at(profile.paths().config_dir().join(SETTINGS_FILE))
+    }
+
+    /// A [`Store`] at a custom `path`.
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the [`Settings`], or the defaults if they were never written.
+    pub fn load(&self) -> Result<Settings, Error> {
+        let content = match fs::read_to_string(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Settings::default()),
+            res => res?,
+        };
+        let file: File =
+            toml::from_str(&content).map_err(|e| Error::Parse(self.path.clone(), e))?;
+        if file.version > VERSION {
+            return Err(Error::Version {
+                found: file.version,
+            });
+        }
+
+        Ok(file.settings)
+    }
+
+    /// Write `settings`, replacing the previous ones.
+    ///
+    /// The file is replaced atomically, so concurrent readers see either the
+    /// previous or the new [`Settings`].
+    pub fn store(&self, settings: &Settings) -> Result<(), Error> {
+        let content = toml::to_string(&File {
+            version: VERSION,
+            settings: settings.clone(),
+        })?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("toml.tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &self.path)?;
+
+        Ok(())
+    }
+
+    /// Modify the [`Settings`] using `f`, and write the result.
+    pub fn update<F>(&self, f: F) -> Result<Settings, Error>
+    where
+        F: FnOnce(&mut Settings),
+    {
+        let mut settings = self.load()?;
+        f(&mut settings);
+        self.store(&settings)?;
+        Ok(settings)
+    }
+
+    /// Watch for changes to the [`Settings`].
+    ///
+    /// Implemented in terms of filesystem events, and so changes are reported
+    /// regardless of which process or [`Store`] instance makes them. The new
+    /// [`Settings`] are yielded after every change, while changes which fail
+    /// to load are skipped. Note that a single change may be reported more
+    /// than once.
+    pub fn watch(&self) -> Result<(Watcher, impl Iterator<Item = Settings>), Error> {
+        use notify::{RawEvent, RecursiveMode::NonRecursive};
+
+        let dir = match self.path.parent() {
+            Some(dir) => dir.to_owned(),
+            None => PathBuf::from("."),
+        };
+        fs::create_dir_all(&dir)?;
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::raw_watcher(tx)?;
+        watcher.watch(&dir, NonRecursive)?;
+
+        let store = self.clone();
+        let rx = rx.into_iter().filter_map(move |evt| {
+            tracing::trace!("{:?}", evt);
+
+            match evt {
+                RawEvent {
+                    path: Some(path),
+                    op: Ok(_),
+                    cookie: _,
+                } if path.file_name() == store.path.file_name() && store.path.exists() => {
+                    match store.load() {
+                        Ok(settings) => Some(settings),
+                        Err(err) => {
+                            tracing::warn!(?err, "failed to load changed settings");
+                            None
+                        },
+                    }
+                },
+
+                _ => None,
+            }
+        });
+
+        Ok((Watcher(Arc::new(watcher)), rx))
+    }
+}
+
+/// A handle to a filesystem watcher, see [`Store::watch`].
+///
+/// If and when this value is dropped, the corresponding iterator will return
+/// `None`.
+#[derive(Clone)]
+pub struct Watcher(Arc<notify::RecommendedWatcher>);
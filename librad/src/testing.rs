@@ -0,0 +1,21 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fixtures for testing applications built on `librad`.
+//!
+//! Only available with the `testing` feature enabled, eg. as a
+//! `dev-dependency`:
+//!
+//! ```toml
+//! [dev-dependencies.librad]
+//! path = "../librad"
+//! features = ["testing"]
+//! ```
+
+pub mod fixture;
+pub mod identities;
+pub mod logging;
+pub mod storage;
+pub mod tempdir;
@@ -0,0 +1,439 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Declarative construction of storage states.
+//!
+//! A [`Builder`] describes a scenario as a sequence of steps over a number of
+//! peers, each owning a [`Storage`] in a temporary directory. Steps are
+//! applied in the order they were added, so eg. a peer must have a copy of a
+//! project before it can commit to it:
+//!
+//! ```rust,ignore
+//! let fixture = Builder::new(2)
+//!     .project("radicle-link", 0)
+//!     .delegate("radicle-link", 1)
+//!     .replicate("radicle-link", 0, 1)
+//!     .commits("radicle-link", 1, "main", 3)
+//!     .build()?;
+//!
+//! let urn = fixture.urn("radicle-link");
+//! let storage = fixture.peer(1);
+//! ```
+//!
+//! [`Builder::replicate`] copies refs between storages directly, mimicking
+//! the layout replication produces without going through the network stack.
+//!
+//! [`Builder::signed_at`] makes the signed refs of a peer point to an older
+//! tip of a branch, eg. to set up validation failures.
+
+use std::collections::BTreeMap;
+
+use either::Either;
+
+use super::storage::{storage, TmpStorage};
+use crate::{
+    git::{
+        identities::{self, Person},
+        refs::Refs,
+        storage::Storage,
+        tracking,
+        types::{Force, Namespace, Reference},
+        Urn,
+    },
+    identities::{delegation, payload},
+    PeerId,
+    SecretKey,
+};
+
+/// A scenario under construction, see the [module documentation](self).
+pub struct Builder {
+    peers: usize,
+    steps: Vec<Step>,
+}
+
+enum Step {
+    Project {
+        name: String,
+        peer: usize,
+    },
+    Delegate {
+        name: String,
+        peer: usize,
+    },
+    Replicate {
+        name: String,
+        from: usize,
+        to: usize,
+    },
+    Commits {
+        name: String,
+        peer: usize,
+        branch: String,
+        count: usize,
+    },
+    Track {
+        name: String,
+        peer: usize,
+        remote: usize,
+    },
+    SignedAt {
+        name: String,
+        peer: usize,
+        branch: String,
+        back: usize,
+    },
+}
+
+impl Builder {
+    /// Start a scenario with `peers` peers, each with a fresh key and storage.
+    pub fn new(peers: usize) -> Self {
+        Self {
+            peers,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Create the project `name` on `peer`, delegating to the personal
+    /// identity of `peer`.
+    ///
+    /// The personal identity is created on first use.
+    pub fn project(mut self, name: impl Into<String>, peer: usize) -> Self {
+        self.steps.push(Step::Project {
+            name: name.into(),
+            peer,
+        });
+        self
+    }
+
+    /// Add the key of `peer` to the delegations of the project `name`.
+    ///
+    /// The update is made on the peer which created the project.
+    pub fn delegate(mut self, name: impl Into<String>, peer: usize) -> Self {
+        self.steps.push(Step::Delegate {
+            name: name.into(),
+            peer,
+        });
+        self
+    }
+
+    /// Copy the project `name` from peer `from` to peer `to`, as if `to` had
+    /// replicated it from `from`.
+    ///
+    /// The refs of `from` are stored as remote tracking refs on `to`, `to`
+    /// tracks `from`, and the signed refs of `to` are updated.
+    pub fn replicate(mut self, name: impl Into<String>, from: usize, to: usize) -> Self {
+        self.steps.push(Step::Replicate {
+            name: name.into(),
+            from,
+            to,
+        });
+        self
+    }
+
+    /// Create `count` commits on top of `branch` of the project `name` on
+    /// `peer`, and update the signed refs of `peer`.
+    pub fn commits(
+        mut self,
+        name: impl Into<String>,
+        peer: usize,
+        branch: impl Into<String>,
+        count: usize,
+    ) -> Self {
+        self.steps.push(Step::Commits {
+            name: name.into(),
+            peer,
+            branch: branch.into(),
+            count,
+        });
+        self
+    }
+
+    /// Make `peer` track `remote` for the project `name`.
+    pub fn track(mut self, name: impl Into<String>, peer: usize, remote: usize) -> Self {
+        self.steps.push(Step::Track {
+            name: name.into(),
+            peer,
+            remote,
+        });
+        self
+    }
+
+    /// Update the signed refs of the project `name` on `peer` as if `branch`
+    /// pointed `back` commits behind its current tip.
+    ///
+    /// The branch itself is left as is, so unless `back` is zero, the signed
+    /// refs no longer match it.
+    pub fn signed_at(
+        mut self,
+        name: impl Into<String>,
+        peer: usize,
+        branch: impl Into<String>,
+        back: usize,
+    ) -> Self {
+        self.steps.push(Step::SignedAt {
+            name: name.into(),
+            peer,
+            branch: branch.into(),
+            back,
+        });
+        self
+    }
+
+    /// Apply all steps.
+    ///
+    /// # Errors
+    ///
+    /// If a step refers to an unknown peer or project, or any storage
+    /// operation fails.
+    pub fn build(self) -> anyhow::Result<Fixture> {
+        let mut fixture = Fixture {
+            peers: (0..self.peers).map(|_| storage(SecretKey::new())).collect(),
+            persons: BTreeMap::new(),
+            projects: BTreeMap::new(),
+        };
+        for step in self.steps {
+            fixture.apply(step)?;
+        }
+
+        Ok(fixture)
+    }
+}
+
+/// The outcome of [`Builder::build`].
+pub struct Fixture {
+    peers: Vec<TmpStorage>,
+    persons: BTreeMap<usize, Person>,
+    projects: BTreeMap<String, (Urn, usize)>,
+}
+
+impl Fixture {
+    /// The storage of `peer`.
+    ///
+    /// # Panics
+    ///
+    /// If `peer` is out of range.
+    pub fn peer(&self, peer: usize) -> &Storage {
+        &self.peers[peer]
+    }
+
+    /// The [`PeerId`] of `peer`.
+    ///
+    /// # Panics
+    ///
+    /// If `peer` is out of range.
+    pub fn peer_id(&self, peer: usize) -> PeerId {
+        *self.peer(peer).peer_id()
+    }
+
+    /// The number of peers.
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// The personal identity of `peer`, if one was created.
+    pub fn person(&self, peer: usize) -> Option<&Person> {
+        self.persons.get(&peer)
+    }
+
+    /// The [`Urn`] of the project `name`.
+    ///
+    /// # Panics
+    ///
+    /// If there is no project `name`.
+    pub fn urn(&self, name: &str) -> &Urn {
+        &self.projects[name].0
+    }
+
+    fn storage(&self, peer: usize) -> anyhow::Result<&Storage> {
+        self.peers
+            .get(peer)
+            .map(|s| &**s)
+            .ok_or_else(|| anyhow::anyhow!("unknown peer {}", peer))
+    }
+
+    fn project(&self, name: &str) -> anyhow::Result<(Urn, usize)> {
+        self.projects
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("unknown project {}", name))
+    }
+
+    fn apply(&mut self, step: Step) -> anyhow::Result<()> {
+        match step {
+            Step::Project { name, peer } => {
+                let owner = self.ensure_person(peer)?;
+                let storage = self.storage(peer)?;
+                let whoami = identities::local::load(storage, owner.urn())?
+                    .ok_or_else(|| anyhow::anyhow!("local identity of peer {} vanished", peer))?;
+                let project = identities::project::create(
+                    storage,
+                    whoami,
+                    payload::Project {
+                        name: name.clone().into(),
+                        description: None,
+                        default_branch: Some("main".into()),
+                    },
+                    delegation::Indirect::from(owner),
+                )?;
+                self.projects.insert(name, (project.urn(), peer));
+            },
+
+            Step::Delegate { name, peer } => {
+                let (urn, owner) = self.project(&name)?;
+                let key = *self.storage(peer)?.peer_id().as_public_key();
+                let storage = self.storage(owner)?;
+                let project = identities::project::get(storage, &urn)?
+                    .ok_or_else(|| anyhow::anyhow!("project {} vanished", name))?;
+                let delegations = delegation::Indirect::try_from_iter(
+                    project
+                        .delegations()
+                        .clone()
+                        .into_iter()
+                        .chain(Some(Either::Left(key))),
+                )?;
+                identities::project::update(storage, &urn, None, None, delegations)?;
+            },
+
+            Step::Replicate { name, from, to } => {
+                let (urn, _) = self.project(&name)?;
+                let remote = *self.storage(from)?.peer_id();
+                let source = self.storage(from)?.path().to_owned();
+                let storage = self.storage(to)?;
+                let repo = storage.as_raw();
+
+                let namespace = Namespace::from(&urn);
+                let refspecs = ["heads", "rad", "tags"]
+                    .iter()
+                    .map(|category| {
+                        format!(
+                            "+refs/namespaces/{ns}/refs/{cat}/*:refs/namespaces/{ns}/refs/remotes/{remote}/{cat}/*",
+                            ns = namespace,
+                            cat = category,
+                            remote = remote
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                repo.remote_anonymous(&source.to_string_lossy())?
+                    .fetch(&refspecs, None, None)?;
+
+                let rad_id = Reference::rad_id(namespace.clone());
+                if rad_id.oid(repo).is_err() {
+                    let tip = rad_id.clone().with_remote(remote).oid(repo)?;
+                    rad_id.create(repo, tip, Force::False, "fixture: replicate")?;
+                }
+                track(storage, &urn, remote)?;
+                Refs::update(storage, &urn)?;
+            },
+
+            Step::Commits {
+                name,
+                peer,
+                branch,
+                count,
+            } => {
+                let (urn, _) = self.project(&name)?;
+                let storage = self.storage(peer)?;
+                let repo = storage.as_raw();
+
+                let head = Reference::head(Namespace::from(&urn), None, branch.parse()?);
+                let mut parent = head
+                    .oid(repo)
+                    .ok()
+                    .map(|oid| repo.find_commit(oid))
+                    .transpose()?;
+                let tree = {
+                    let oid = repo.treebuilder(None)?.write()?;
+                    repo.find_tree(oid)?
+                };
+                let author = git2::Signature::now("fixture", "fixture@localhost")?;
+                for i in 0..count {
+                    let oid = repo.commit(
+                        None,
+                        &author,
+                        &author,
+                        &format!("{} {}", branch, i),
+                        &tree,
+                        &parent.iter().collect::<Vec<_>>(),
+                    )?;
+                    parent = Some(repo.find_commit(oid)?);
+                }
+                if let Some(tip) = parent {
+                    head.create(repo, tip.id(), Force::True, "fixture: commits")?;
+                }
+                Refs::update(storage, &urn)?;
+            },
+
+            Step::Track { name, peer, remote } => {
+                let (urn, _) = self.project(&name)?;
+                let remote = *self.storage(remote)?.peer_id();
+                track(self.storage(peer)?, &urn, remote)?;
+            },
+
+            Step::SignedAt {
+                name,
+                peer,
+                branch,
+                back,
+            } => {
+                let (urn, _) = self.project(&name)?;
+                let storage = self.storage(peer)?;
+                let repo = storage.as_raw();
+
+                let head = Reference::head(Namespace::from(&urn), None, branch.parse()?);
+                let tip = head.oid(repo)?;
+                let mut signed = repo.find_commit(tip)?;
+                for _ in 0..back {
+                    signed = signed.parent(0).map_err(|_| {
+                        anyhow::anyhow!(
+                            "{} of peer {} has fewer than {} ancestors",
+                            branch,
+                            peer,
+                            back
+                        )
+                    })?;
+                }
+                // Sign the older tip, then move the branch back to where it was
+                head.create(repo, signed.id(), Force::True, "fixture: signed at")?;
+                Refs::update(storage, &urn)?;
+                head.create(repo, tip, Force::True, "fixture: signed at")?;
+            },
+        }
+
+        Ok(())
+    }
+
+    fn ensure_person(&mut self, peer: usize) -> anyhow::Result<Person> {
+        if let Some(person) = self.persons.get(&peer) {
+            return Ok(person.clone());
+        }
+
+        let storage = self.storage(peer)?;
+        let person = identities::person::create(
+            storage,
+            payload::Person {
+                name: format!("peer-{}", peer).into(),
+            },
+            delegation::Direct::new(*storage.peer_id().as_public_key()),
+        )?;
+        self.persons.insert(peer, person.clone());
+        Ok(person)
+    }
+}
+
+fn track(storage: &Storage, urn: &Urn, remote: PeerId) -> anyhow::Result<()> {
+    // Tracking an already tracked peer is fine
+    let _ = tracking::track(
+        storage,
+        urn,
+        Some(remote),
+        tracking::Config::default(),
+        tracking::policy::Track::Any,
+    )?;
+    Ok(())
+}
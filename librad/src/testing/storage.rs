@@ -0,0 +1,23 @@
+// Copyright © 2019-2020 The Radicle Foundation <hello@radicle.foundation>
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::io;
+
+use super::tempdir::WithTmpDir;
+use crate::{git::storage::Storage, paths::Paths, SecretKey};
+
+pub type TmpStorage = WithTmpDir<Storage>;
+
+/// Open a fresh [`Storage`] for `signer` in a temporary directory, which is
+/// removed when the [`TmpStorage`] is dropped.
+pub fn storage(signer: SecretKey) -> TmpStorage {
+    WithTmpDir::new(|path| -> Result<_, io::Error> {
+        let paths = Paths::from_root(path)?;
+        let storage =
+            Storage::open(&paths, signer).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok::<_, io::Error>(storage)
+    })
+    .unwrap()
+}
@@ -19,6 +19,8 @@ use zeroize::Zeroize;
 
 use keystore::{sign, SecretKeyExt};
 
+use crate::Redacted;
+
 pub const PUBLICKEYBYTES: usize = std::mem::size_of::<ed25519::VerificationKeyBytes>();
 pub use keystore::SecStr;
 
@@ -34,7 +36,6 @@ impl<T: error::Error + Send + Sync + 'static> SignError for T {}
 
 /// A device-specific signing key
 #[derive(Clone, Zeroize)]
-#[cfg_attr(test, derive(Debug))]
 #[zeroize(drop)]
 pub struct SecretKey(ed25519::SigningKey);
 
@@ -92,6 +93,14 @@ impl SecretKey {
     }
 }
 
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SecretKey")
+            .field(&Redacted::new(()))
+            .finish()
+    }
+}
+
 impl fmt::Display for SecretKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.public().fmt(f)
@@ -27,5 +27,8 @@ pub use keys::{
 pub mod peer;
 pub use peer::PeerId;
 
+pub mod redacted;
+pub use redacted::{Redacted, SecretString};
+
 mod signer;
 pub use signer::{BoxedSignError, BoxedSigner, Signer, SomeSigner};
@@ -0,0 +1,71 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fmt;
+
+use zeroize::Zeroize;
+
+/// What [`Redacted`] values are rendered as.
+pub const PLACEHOLDER: &str = "<redacted>";
+
+/// A value which must not appear in logs or error messages.
+///
+/// Both the [`fmt::Debug`] and [`fmt::Display`] impls print [`PLACEHOLDER`]
+/// instead of the value, so a `Redacted` can be passed to `tracing` or
+/// included in a `#[derive(Debug)]` struct without leaking it. The value
+/// itself is only reachable through [`Redacted::expose`] and
+/// [`Redacted::into_inner`], which makes every use of it explicit.
+///
+/// Note that this only governs formatting: the memory held by `T` is wiped on
+/// drop only if `T` does so itself, as eg. [`crate::SecStr`] does.
+#[derive(Clone, Copy, Default, Eq, PartialEq)]
+pub struct Redacted<T>(T);
+
+/// A secret string, such as a passphrase or an access token.
+pub type SecretString = Redacted<String>;
+
+impl<T> Redacted<T> {
+    pub fn new(secret: T) -> Self {
+        Self(secret)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Redacted<T> {
+    fn from(secret: T) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self::new(secret.to_owned())
+    }
+}
+
+impl<T> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(PLACEHOLDER)
+    }
+}
+
+impl<T> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(PLACEHOLDER)
+    }
+}
+
+impl<T: Zeroize> Zeroize for Redacted<T> {
+    fn zeroize(&mut self) {
+        self.0.zeroize()
+    }
+}
@@ -0,0 +1,38 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fuzzing entry points, see `fuzz/` in the repository root.
+//!
+//! The functions in this module must not panic for any input.
+
+use futures_lite::future::block_on;
+use git_packetline::{PacketLineRef, StreamingPeekableIter};
+
+use crate::protocol::upload_pack::Header;
+
+/// Read `data` as a stream of pkt-lines, up to the first flush packet.
+///
+/// Data packets are parsed as the header a client sends to initiate a fetch,
+/// which is the first thing read off the wire from a connecting peer.
+pub fn pkt_line(data: &[u8]) {
+    block_on(async {
+        let mut lines = StreamingPeekableIter::new(data, &[]);
+        while let Some(Ok(Ok(line))) = lines.read_line().await {
+            if let PacketLineRef::Data(data) = line {
+                if let Ok(s) = std::str::from_utf8(data) {
+                    let _ = s.parse::<Header>();
+                }
+            }
+        }
+    })
+}
+
+/// Parse `data` as the header sent by legacy clients, which is not framed as
+/// a pkt-line.
+pub fn upload_pack_header(data: &[u8]) {
+    if let Ok(s) = std::str::from_utf8(data) {
+        let _ = s.parse::<Header>();
+    }
+}
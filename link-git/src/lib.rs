@@ -6,6 +6,8 @@
 #[macro_use]
 extern crate async_trait;
 
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 pub mod odb;
 pub mod protocol;
 pub mod refs;
@@ -3,7 +3,8 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use git_hash::oid;
+use git_hash::{oid, ObjectId};
+use git_odb::Write as _;
 use thiserror::Error;
 
 pub mod backend;
@@ -20,11 +21,28 @@ pub enum Error {
 
     #[error(transparent)]
     Loose(#[from] git_odb::loose::find::Error),
+
+    #[error("error looking up object in remote store")]
+    Remote(#[source] backend::RemoteError),
+
+    #[error("remote store returned object {actual} when asked for {expected}")]
+    RemoteMismatch {
+        expected: ObjectId,
+        actual: ObjectId,
+    },
+
+    #[error("failed to hash object from remote store")]
+    Hash(#[source] std::io::Error),
+
+    #[error("failed to cache object from remote store")]
+    Cache(#[from] git_odb::loose::write::Error),
 }
 
 pub struct Odb<I, D> {
     pub loose: backend::Loose,
     pub packed: backend::Packed<I, D>,
+    /// Fallback for objects not found locally, see [`backend::Remote`].
+    pub remote: Option<Box<dyn backend::Remote>>,
 }
 
 impl<I, D> Odb<I, D>
@@ -32,8 +50,23 @@ where
     I: index::Index,
     D: window::Cache,
 {
+    /// Test if `id` is present in any of the backends.
+    ///
+    /// The packs known already and the loose objects are consulted before the
+    /// pack directory is rescanned, so checking for objects which were just
+    /// unpacked, or added with [`index::Shared::push`], does not incur a
+    /// rescan.
+    ///
+    /// An object found only in the [`backend::Remote`] is copied to the
+    /// [`backend::Loose`] store first, so that other readers of the repository,
+    /// such as `libgit2`, can find it, too. Objects are only downloaded if
+    /// [`backend::Remote::contains`] reports them as present.
     pub fn contains(&self, id: impl AsRef<oid>) -> bool {
-        self.packed.contains(id.as_ref()) || self.loose.contains(id)
+        let id = id.as_ref();
+        self.packed.contains_loaded(id)
+            || self.loose.contains(id)
+            || self.packed.contains(id)
+            || self.remote_contains(id)
     }
 
     pub fn find<'a>(
@@ -43,9 +76,60 @@ where
         cache: &mut impl cache::DecodeEntry,
     ) -> Result<Option<Object<'a>>, Error> {
         let id = id.as_ref();
+        if self.packed.contains_loaded(id) {
+            return self.packed.find(id, buf, cache).map_err(Into::into);
+        }
+        if self.loose.contains(id) {
+            return self.loose.try_find(id, buf).map_err(Into::into);
+        }
         if self.packed.contains(id) {
             return self.packed.find(id, buf, cache).map_err(Into::into);
         }
-        self.loose.try_find(id, buf).map_err(Into::into)
+        self.remote_find(id, buf)
+    }
+
+    fn remote_contains(&self, id: &oid) -> bool {
+        match self.remote.as_ref().map(|remote| remote.contains(id)) {
+            None | Some(Ok(false)) => return false,
+            Some(Ok(true)) => {},
+            Some(Err(e)) => {
+                tracing::warn!(err = %e, %id, "error looking up object in remote store");
+                return false;
+            },
+        }
+        let mut buf = Vec::new();
+        match self.remote_find(id, &mut buf) {
+            Ok(found) => found.is_some(),
+            Err(e) => {
+                tracing::warn!(err = %e, %id, "error copying object from remote store");
+                false
+            },
+        }
+    }
+
+    fn remote_find<'a>(&self, id: &oid, buf: &'a mut Vec<u8>) -> Result<Option<Object<'a>>, Error> {
+        let remote = match &self.remote {
+            None => return Ok(None),
+            Some(remote) => remote,
+        };
+        match remote.fetch(id, buf).map_err(Error::Remote)? {
+            None => Ok(None),
+            Some(kind) => {
+                // Don't store what the remote sent under a name it doesn't hash to
+                let actual = git_odb::sink()
+                    .write_buf(kind, buf, git_hash::Kind::Sha1)
+                    .map_err(Error::Hash)?;
+                let expected = id.to_owned();
+                if actual != expected {
+                    return Err(Error::RemoteMismatch { expected, actual });
+                }
+                self.loose.write_buf(kind, buf, git_hash::Kind::Sha1)?;
+                Ok(Some(Object {
+                    kind,
+                    data: buf,
+                    pack_location: None,
+                }))
+            },
+        }
     }
 }
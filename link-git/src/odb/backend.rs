@@ -3,7 +3,10 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use std::error::Error as StdError;
+
 use git_hash::oid;
+use git_object::Kind;
 use git_pack::{cache::DecodeEntry, data::Object};
 
 use super::{index, pack, window};
@@ -24,6 +27,11 @@ where
         self.index.contains(id)
     }
 
+    /// See [`index::Index::contains_loaded`].
+    pub fn contains_loaded(&self, id: impl AsRef<oid>) -> bool {
+        self.index.contains_loaded(id)
+    }
+
     pub fn find<'a>(
         &self,
         id: impl AsRef<oid>,
@@ -34,3 +42,35 @@ where
             .lookup(|info| self.data.get(info), id, buf, cache)
     }
 }
+
+pub type RemoteError = Box<dyn StdError + Send + Sync + 'static>;
+
+/// An object store which is not kept on local disk, e.g. an object storage
+/// service or IPFS.
+///
+/// Objects not found locally are looked up in the remote store, and written
+/// to the [`Loose`] store when found, which thus acts as a local cache.
+/// Keeping only the cold objects remotely is up to the deployment, e.g. by
+/// moving packs there and letting `git gc` prune the cache.
+pub trait Remote: Send + Sync {
+    /// Test if the object `id` exists, without downloading it.
+    fn contains(&self, id: &oid) -> Result<bool, RemoteError>;
+
+    /// Look up the object `id`, writing its data to `buf`.
+    ///
+    /// Returns the kind of the object if it was found. The data is verified
+    /// to hash to `id` before it is used.
+    fn fetch(&self, id: &oid, buf: &mut Vec<u8>) -> Result<Option<Kind>, RemoteError>;
+}
+
+/// A [`Loose`] store can act as a [`Remote`], for example when it resides on a
+/// network filesystem, or a bucket mounted via FUSE.
+impl Remote for Loose {
+    fn contains(&self, id: &oid) -> Result<bool, RemoteError> {
+        Ok(Loose::contains(self, id))
+    }
+
+    fn fetch(&self, id: &oid, buf: &mut Vec<u8>) -> Result<Option<Kind>, RemoteError> {
+        Ok(self.try_find(id, buf)?.map(|obj| obj.kind))
+    }
+}
@@ -55,6 +55,12 @@ pub mod error {
 pub trait Index {
     fn contains(&self, id: impl AsRef<oid>) -> bool;
 
+    /// Like [`Index::contains`], but only consider the indices loaded already,
+    /// even if `id` is not found.
+    fn contains_loaded(&self, id: impl AsRef<oid>) -> bool {
+        self.contains(id)
+    }
+
     fn lookup<'a, F, E>(
         &self,
         pack_cache: F,
@@ -83,7 +89,9 @@ pub trait Index {
 ///   found (assuming that this is due to a compaction)
 ///
 /// Unless a reload occurs, lookups are lock-free and mostly wait-free. Writes
-/// ([`Shared::push`], [`Shared::reload`]) are guarded by a [`Mutex`].
+/// ([`Shared::push`], [`Shared::reload`]) are guarded by a [`Mutex`]. Indices
+/// are memory-mapped, and shared between reloads: a rescan only opens the
+/// indices which were not loaded before.
 // TODO: consecutive lookups also tend to resolve to the same pack, so we could
 // remember the index into the `im::Vector` where we found a match and look
 // there first. This is what libgit2 does, but the heuristic is not necessarily
@@ -99,7 +107,7 @@ pub struct Shared<M> {
 impl Shared<()> {
     pub fn open(git_dir: impl AsRef<Path>) -> Result<Self, error::Discover> {
         let pack_dir = git_dir.as_ref().join("objects").join("pack");
-        let indices = discover(&pack_dir)?;
+        let indices = discover(&pack_dir, &im::Vector::new())?;
 
         Ok(Self {
             pack_dir,
@@ -154,7 +162,7 @@ where
     /// method, as [`Shared`] manages reloads automatically.
     pub fn reload(&self) -> Result<(), error::Discover> {
         let lock = self.write.lock();
-        let indices = discover(&self.pack_dir)?;
+        let indices = discover(&self.pack_dir, &self.indices.load())?;
         self.indices.store(Arc::new(indices));
         drop(lock);
 
@@ -171,6 +179,14 @@ where
         self.indices.load().len()
     }
 
+    fn contains_loaded(&self, id: impl AsRef<oid>) -> bool {
+        let found = self.indices.load().iter().any(|idx| idx.contains(&id));
+        if found {
+            self.stats.record_hit();
+        }
+        found
+    }
+
     fn contains(&self, id: impl AsRef<oid>) -> bool {
         for i in 0..2 {
             for idx in self.indices.load().iter() {
@@ -247,7 +263,13 @@ where
     Ok(obj)
 }
 
-fn discover(pack_dir: impl AsRef<Path>) -> Result<im::Vector<Arc<pack::Index>>, error::Discover> {
+/// Open the indices in `pack_dir`, most recently modified first.
+///
+/// Indices already present in `known` are reused instead of opened again.
+fn discover(
+    pack_dir: impl AsRef<Path>,
+    known: &im::Vector<Arc<pack::Index>>,
+) -> Result<im::Vector<Arc<pack::Index>>, error::Discover> {
     let pack_dir = pack_dir.as_ref();
     let pack_dir_disp = pack_dir.display();
     trace!("discovering packs at {}", pack_dir_disp);
@@ -269,7 +291,13 @@ fn discover(pack_dir: impl AsRef<Path>) -> Result<im::Vector<Arc<pack::Index>>,
             let indices = paths
                 .into_iter()
                 .rev()
-                .map(|(path, _)| Ok(pack::Index::open(path).map(Arc::new)?))
+                .map(|(path, _)| {
+                    let data_path = path.with_extension("pack");
+                    match known.iter().find(|idx| idx.info.data_path == data_path) {
+                        Some(idx) => Ok(Arc::clone(idx)),
+                        None => Ok(pack::Index::open(path).map(Arc::new)?),
+                    }
+                })
                 .collect::<Result<_, error::Discover>>()?;
 
             Ok(indices)
@@ -292,6 +320,10 @@ where
         self.contains(id)
     }
 
+    fn contains_loaded(&self, id: impl AsRef<oid>) -> bool {
+        self.contains_loaded(id)
+    }
+
     fn lookup<'a, F, E>(
         &self,
         pack_cache: F,
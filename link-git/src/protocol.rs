@@ -7,16 +7,29 @@ use bstr::ByteSlice as _;
 use git_protocol::transport::client;
 use versions::Version;
 
+pub mod bundle_uri;
+pub mod capabilities;
 pub mod fetch;
+pub mod keepalive;
 pub mod ls;
+pub mod object_info;
 pub mod packwriter;
+pub mod push;
+pub mod receive_pack;
+pub mod sideband;
 pub mod take;
+pub mod trace;
 pub mod transport;
 pub mod upload_pack;
 
+pub use bundle_uri::bundle_uri;
+pub use capabilities::Capabilities;
 pub use fetch::{fetch, Ref};
-pub use ls::ls_refs;
+pub use ls::{ls_refs, RefExt};
+pub use object_info::object_info;
 pub use packwriter::PackWriter;
+pub use push::push;
+pub use receive_pack::receive_pack;
 pub use upload_pack::upload_pack;
 
 pub use git_hash::{oid, ObjectId};
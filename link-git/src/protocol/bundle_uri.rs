@@ -0,0 +1,330 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Support for the [bundle-uri] extension, allowing servers to offload initial
+//! clones to pre-generated bundles.
+//!
+//! A server advertises the list of bundles for a namespace via the protocol v2
+//! `bundle-uri` command. The client downloads them, imports the packfiles they
+//! contain, and then tops up via a regular, incremental fetch.
+//!
+//! [bundle-uri]: https://git-scm.com/docs/bundle-uri
+
+use std::{
+    io::{self, BufRead},
+    path::Path,
+};
+
+use async_process::{Command, Stdio};
+use bstr::BString;
+use futures_lite::io::{AsyncBufReadExt as _, AsyncRead, AsyncWrite};
+use futures_util::io::AllowStdIo;
+use git_features::progress;
+use git_hash::ObjectId;
+use git_protocol::transport::{
+    client::{SetServiceResponse, Transport as _, TransportV2Ext as _},
+    Service,
+};
+use once_cell::sync::Lazy;
+use versions::Version;
+
+use super::{transport, PackWriter};
+
+#[derive(Debug)]
+pub struct Options {
+    /// The remote (logical) repository to ask about, i.e. the name of a
+    /// namespace.
+    pub repo: BString,
+
+    /// [Extra Parameters][extra] to send with the initial transport header.
+    ///
+    /// [extra]: https://git.kernel.org/pub/scm/git/git.git/tree/Documentation/technical/pack-protocol.txt#n52
+    pub extra_params: Vec<(String, Option<String>)>,
+}
+
+/// Whether all bundles of a [`BundleList`] are needed, or any one of them
+/// suffices.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    All,
+    Any,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Bundle {
+    pub id: String,
+    pub uri: String,
+    /// Bundles with a lower creation token are to be applied first.
+    pub creation_token: Option<u64>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BundleList {
+    pub mode: Mode,
+    /// The bundles, in the order they should be applied.
+    pub bundles: Vec<Bundle>,
+}
+
+impl Default for BundleList {
+    fn default() -> Self {
+        Self {
+            mode: Mode::All,
+            bundles: vec![],
+        }
+    }
+}
+
+impl BundleList {
+    /// Parse the `key=value` pairs of a bundle list.
+    ///
+    /// Unknown keys are ignored, as are bundles without a `uri`.
+    pub fn parse<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> io::Result<Self> {
+        let mut list = Self::default();
+        let mut bundles: Vec<Bundle> = Vec::new();
+        for (key, val) in pairs {
+            match key.strip_prefix("bundle.") {
+                Some("version") if val != "1" => {
+                    return Err(invalid_data(format!(
+                        "unsupported bundle list version {}",
+                        val
+                    )))
+                },
+                Some("version") => {},
+                Some("mode") => {
+                    list.mode = match val {
+                        "all" => Mode::All,
+                        "any" => Mode::Any,
+                        _ => return Err(invalid_data(format!("invalid bundle mode {}", val))),
+                    }
+                },
+                Some(rest) => {
+                    let (id, attr) = match rest.rsplit_once('.') {
+                        Some(split) => split,
+                        None => continue,
+                    };
+                    let idx = match bundles.iter().position(|b| b.id == id) {
+                        Some(idx) => idx,
+                        None => {
+                            bundles.push(Bundle {
+                                id: id.to_owned(),
+                                uri: String::new(),
+                                creation_token: None,
+                            });
+                            bundles.len() - 1
+                        },
+                    };
+                    match attr {
+                        "uri" => bundles[idx].uri = val.to_owned(),
+                        "creationtoken" | "creationToken" => {
+                            bundles[idx].creation_token = val.parse().ok()
+                        },
+                        _ => {},
+                    }
+                },
+                None => {},
+            }
+        }
+        bundles.retain(|b| !b.uri.is_empty());
+        // Stable, so bundles without a token keep their relative order
+        bundles.sort_by_key(|b| b.creation_token);
+        list.bundles = bundles;
+
+        Ok(list)
+    }
+}
+
+/// Ask the server for the [`BundleList`] of [`Options::repo`].
+///
+/// If the server doesn't advertise `bundle-uri`, an error of kind
+/// [`io::ErrorKind::Unsupported`] is returned.
+pub async fn bundle_uri<R, W>(opt: Options, recv: R, send: W) -> io::Result<BundleList>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut conn = transport::Stateless::new(opt.repo.clone(), recv, send);
+
+    let extra_params = opt
+        .extra_params
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_deref()))
+        .collect::<Vec<_>>();
+    let SetServiceResponse { capabilities, .. } = conn
+        .handshake(Service::UploadPack, &extra_params)
+        .await
+        .map_err(io_other)?;
+    if capabilities.capability("bundle-uri").is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "server does not support `bundle-uri`",
+        ));
+    }
+
+    let mut reader = conn
+        .invoke(
+            "bundle-uri",
+            None::<(&str, Option<&str>)>.into_iter(),
+            None::<std::iter::Empty<BString>>,
+        )
+        .await
+        .map_err(io_other)?;
+
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        lines.push(line);
+    }
+
+    BundleList::parse(
+        lines
+            .iter()
+            .filter_map(|line| line.trim_end_matches('\n').split_once('=')),
+    )
+}
+
+/// The header of a bundle file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Header {
+    /// Objects which must exist for the packfile to be complete.
+    pub prerequisites: Vec<ObjectId>,
+    /// The refs contained in the bundle.
+    pub refs: Vec<(BString, ObjectId)>,
+}
+
+/// Read the header of a v2 or v3 bundle from `r`, leaving it positioned at the
+/// start of the packfile.
+pub fn read_header(r: &mut impl BufRead) -> io::Result<Header> {
+    let mut line = String::new();
+    r.read_line(&mut line)?;
+    match line.trim_end() {
+        "# v2 git bundle" | "# v3 git bundle" => {},
+        _ => return Err(invalid_data("not a v2 or v3 git bundle")),
+    }
+
+    let mut hdr = Header::default();
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated bundle header",
+            ));
+        }
+        let line = line.trim_end_matches('\n');
+        if line.is_empty() {
+            break;
+        }
+        if let Some(capability) = line.strip_prefix('@') {
+            match capability {
+                "object-format=sha1" => continue,
+                _ => {
+                    return Err(invalid_data(format!(
+                        "unsupported bundle capability {}",
+                        capability
+                    )))
+                },
+            }
+        }
+        if let Some(prereq) = line.strip_prefix('-') {
+            let hex = prereq.split(' ').next().unwrap_or(prereq);
+            hdr.prerequisites
+                .push(ObjectId::from_hex(hex.as_bytes()).map_err(invalid_data)?);
+            continue;
+        }
+        let (oid, name) = line
+            .split_once(' ')
+            .ok_or_else(|| invalid_data("malformed bundle ref"))?;
+        hdr.refs.push((
+            name.into(),
+            ObjectId::from_hex(oid.as_bytes()).map_err(invalid_data)?,
+        ));
+    }
+
+    Ok(hdr)
+}
+
+/// Write the packfile of a bundle using `pack_writer`.
+///
+/// `r` must be positioned at the start of the packfile, i.e. the header must
+/// have been consumed using [`read_header`]. Note that the packfile is thin if
+/// the bundle has prerequisites.
+pub fn unbundle<R, P>(r: R, pack_writer: &P) -> io::Result<P::Output>
+where
+    R: BufRead + Unpin,
+    P: PackWriter,
+{
+    pack_writer.write_pack(AllowStdIo::new(r), progress::Discard)
+}
+
+/// The URIs of the bundles configured for `namespace` in the repository at
+/// `git_dir`, in the order they are to be applied.
+///
+/// Bundles are configured as the multi-valued key `bundle-uri.<namespace>.uri`.
+pub(super) async fn configured(git_dir: &Path, namespace: &str) -> io::Result<Vec<String>> {
+    let out = Command::new("git")
+        .current_dir(git_dir)
+        .args(&["config", "--get-all"])
+        .arg(format!("bundle-uri.{}.uri", namespace))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await?;
+    // Exits with 1 if the key is not set
+    if !out.status.success() {
+        return Ok(vec![]);
+    }
+
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter(|uri| !uri.is_empty())
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+/// The `-c` options for `git-upload-pack` to serve the bundle list `uris`.
+///
+/// The bundles are listed in mode `all`, with creation tokens following the
+/// order of `uris`. If `uris` is empty, no options are returned.
+pub(super) fn upload_pack_config(uris: &[String]) -> Vec<String> {
+    if uris.is_empty() {
+        return vec![];
+    }
+
+    let mut cfg = vec![
+        "uploadpack.advertiseBundleURIs=true".to_owned(),
+        "bundle.version=1".to_owned(),
+        "bundle.mode=all".to_owned(),
+    ];
+    for (i, uri) in uris.iter().enumerate() {
+        cfg.push(format!("bundle.b{}.uri={}", i, uri));
+        cfg.push(format!("bundle.b{}.creationToken={}", i, i + 1));
+    }
+    cfg.into_iter()
+        .flat_map(|kv| vec!["-c".to_owned(), kv])
+        .collect()
+}
+
+/// Whether `git_version` can serve the `bundle-uri` command.
+pub(super) fn supported(git_version: &Version) -> bool {
+    static MIN_GIT_VERSION: Lazy<Version> = Lazy::new(|| Version::new("2.40.0").unwrap());
+    *git_version >= *MIN_GIT_VERSION
+}
+
+fn io_other<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+fn invalid_data<E>(inner: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Sync + Send>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, inner)
+}
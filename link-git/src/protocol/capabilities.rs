@@ -0,0 +1,49 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use git_protocol::transport::client;
+
+/// The features of the protocol v2 `fetch` command advertised by the server,
+/// as far as they change what a client may ask for.
+///
+/// Features which are not advertised must not be used. Those without any
+/// support on our end, i.e. `sideband-all` and `packfile-uris`, are never
+/// requested, but are exposed for diagnostics.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Capabilities {
+    /// `filter <filter-spec>` is accepted, e.g. for partial clones.
+    pub filter: bool,
+    /// `want-ref <ref>` is accepted.
+    pub ref_in_want: bool,
+    /// Sideband multiplexing may be used for the whole response, not only for
+    /// the packfile section.
+    pub sideband_all: bool,
+    /// Parts of the packfile may be offloaded to URIs.
+    pub packfile_uris: bool,
+}
+
+impl Capabilities {
+    /// Extract the `fetch` features from the capabilities advertised by the
+    /// server.
+    ///
+    /// A server speaking protocol v0 or v1 doesn't advertise a `fetch`
+    /// capability, and so supports none of the features.
+    pub fn from_advertised(caps: &client::Capabilities) -> Self {
+        let fetch = caps.capability("fetch");
+        let supports = |feature| {
+            fetch
+                .as_ref()
+                .and_then(|cap| cap.supports(feature))
+                .unwrap_or(false)
+        };
+
+        Self {
+            filter: supports("filter"),
+            ref_in_want: supports("ref-in-want"),
+            sideband_all: supports("sideband-all"),
+            packfile_uris: supports("packfile-uris"),
+        }
+    }
+}
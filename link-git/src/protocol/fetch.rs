@@ -18,21 +18,30 @@ use std::{
 use bstr::{BString, ByteSlice as _};
 use futures_lite::{
     future,
-    io::{AsyncBufRead, AsyncRead, AsyncWrite},
+    io::{AsyncBufRead, AsyncRead, AsyncReadExt as _, AsyncWrite},
 };
-use git_features::progress::{self, Progress};
+use futures_util::TryStreamExt as _;
+use git_features::progress::Progress;
 use git_protocol::{
     fetch::{response, Action, Arguments, Delegate, DelegateBlocking, LsRefsAction, Response},
     transport::client,
 };
 use once_cell::sync::Lazy;
 use pin_project::{pin_project, pinned_drop};
+use tracing::warn;
 use versions::Version;
 
 pub use git_hash::ObjectId;
 pub use git_protocol::fetch::Ref;
 
-use super::{packwriter::PackWriter, remote_git_version, transport};
+use super::{
+    keepalive::SkipKeepalive,
+    packwriter::PackWriter,
+    remote_git_version,
+    sideband::{OnProgress, Sideband},
+    transport,
+    Capabilities,
+};
 
 // Work around `git-upload-pack` not handling namespaces properly,
 //
@@ -68,6 +77,54 @@ pub struct Options {
 
     /// Known refs to ask the server to include in the packfile.
     pub want_refs: Vec<BString>,
+
+    /// The [filter-spec] to omit objects from the packfile with, e.g.
+    /// `blob:none` for a partial clone.
+    ///
+    /// If the server does not advertise the `filter` capability, the filter is
+    /// not sent, and the packfile contains all objects.
+    ///
+    /// [filter-spec]: https://git-scm.com/docs/git-rev-list#Documentation/git-rev-list.txt---filterltfilter-specgt
+    pub filter: Option<BString>,
+
+    /// Receives the progress messages sent by the server.
+    pub on_progress: Option<OnProgress>,
+
+    /// Bounds on the memory used for buffering the response.
+    pub flow_control: FlowControl,
+}
+
+/// Backpressure settings for receiving the response to a [`fetch`].
+///
+/// The response is read from the network ahead of the [`PackWriter`]
+/// consuming it. Once [`FlowControl::max_in_flight_bytes`] are buffered,
+/// reading pauses until the [`PackWriter`] has caught up.
+#[derive(Clone, Copy, Debug)]
+pub struct FlowControl {
+    /// The size of the buffer for a single read from the network.
+    pub read_buffer_size: usize,
+    /// The maximum number of bytes read from the network, but not yet consumed
+    /// by the [`PackWriter`].
+    ///
+    /// This is rounded up to a multiple of [`FlowControl::read_buffer_size`].
+    pub max_in_flight_bytes: usize,
+}
+
+impl FlowControl {
+    /// The number of reads which may be buffered.
+    fn capacity(&self) -> usize {
+        let size = self.read_buffer_size.max(1);
+        ((self.max_in_flight_bytes + size - 1) / size).max(1)
+    }
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        Self {
+            read_buffer_size: 64 * 1024,
+            max_in_flight_bytes: 1024 * 1024,
+        }
+    }
 }
 
 /// Result of a succesful [`fetch`].
@@ -77,6 +134,11 @@ pub struct Outputs<T> {
     pub wanted_refs: Vec<Ref>,
     /// If a packfile was received successfully, some info about it.
     pub pack: Option<T>,
+    /// The capabilities advertised by the server.
+    pub capabilities: Capabilities,
+    /// Whether [`Options::filter`] was sent, and so objects may be missing from
+    /// the packfile.
+    pub filtered: bool,
 }
 
 impl<T> Default for Outputs<T> {
@@ -84,6 +146,8 @@ impl<T> Default for Outputs<T> {
         Self {
             wanted_refs: Vec::new(),
             pack: None,
+            capabilities: Capabilities::default(),
+            filtered: false,
         }
     }
 }
@@ -138,7 +202,10 @@ impl<P: PackWriter> DelegateBlocking for Fetch<P, P::Output> {
         _: &mut Vec<(&str, Option<&str>)>,
         _: &[Ref],
     ) -> io::Result<Action> {
-        if !self.opt.want_refs.is_empty() && !remote_supports_ref_in_want(caps) {
+        let capabilities = Capabilities::from_advertised(caps);
+        self.out.capabilities = capabilities;
+
+        if !self.opt.want_refs.is_empty() && !capabilities.ref_in_want {
             return Err(io::Error::new(
                 io::ErrorKind::Unsupported,
                 "`want-ref`s given, but server does not support `ref-in-want`",
@@ -154,6 +221,10 @@ impl<P: PackWriter> DelegateBlocking for Fetch<P, P::Output> {
 
         self.need_namespaced_want_ref = must_namespace_want_ref(caps);
 
+        if self.opt.filter.is_some() && !capabilities.filter {
+            warn!("server does not support `filter`, fetching all objects");
+        }
+
         Ok(Action::Continue)
     }
 
@@ -171,6 +242,13 @@ impl<P: PackWriter> DelegateBlocking for Fetch<P, P::Output> {
             args.have(oid)
         }
 
+        if let Some(spec) = self.opt.filter.as_ref() {
+            if self.out.capabilities.filter {
+                args.filter(&spec.to_str_lossy());
+                self.out.filtered = true;
+            }
+        }
+
         for name in &self.opt.want_refs {
             if self.need_namespaced_want_ref {
                 let want_ref = format!("refs/namespaces/{}/{}", self.opt.repo, name);
@@ -258,30 +336,61 @@ where
     W: AsyncWrite + Unpin + Send + 'static,
 {
     let stop = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = async_channel::bounded(opt.flow_control.capacity());
+    let read_ahead = read_ahead(recv, tx, opt.flow_control.read_buffer_size.max(1));
     let task = blocking::unblock({
+        let recv = SkipKeepalive::new(rx.into_async_read());
         let mut conn = transport::Stateless::new(opt.repo.clone(), recv, send);
         let pack_writer = build_pack_writer(Arc::clone(&stop));
 
         move || {
+            let sideband = Sideband::new(opt.on_progress.clone());
+            let errors = sideband.errors();
             let mut delegate = Fetch::new(opt, pack_writer);
             future::block_on(git_protocol::fetch(
                 &mut conn,
                 &mut delegate,
                 |_| unreachable!("credentials helper requested"),
-                progress::Discard,
+                sideband,
                 git_protocol::FetchConnection::AllowReuse,
             ))
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(|e| Sideband::into_io_error(&errors, e))?;
 
             Ok(delegate.out)
         }
     });
+    // `read_ahead` finishes when the remote end closes the stream, which may
+    // or may not happen before the fetch is done.
+    let task = future::or(task, async move {
+        read_ahead.await;
+        future::pending().await
+    });
 
     Fetching { stop, task }
 }
 
-fn remote_supports_ref_in_want(caps: &client::Capabilities) -> bool {
-    caps.capability("fetch")
-        .and_then(|cap| cap.supports("ref-in-want"))
-        .unwrap_or(false)
+/// Read from `recv` in chunks of `buf_size` into `tx`, until EOF or an error
+/// occurs.
+///
+/// The capacity of `tx` bounds the number of bytes read ahead.
+async fn read_ahead<R>(mut recv: R, tx: async_channel::Sender<io::Result<Vec<u8>>>, buf_size: usize)
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        let mut buf = vec![0; buf_size];
+        let chunk = match recv.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => {
+                buf.truncate(n);
+                Ok(buf)
+            },
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => Err(e),
+        };
+        let fatal = chunk.is_err();
+        if tx.send(chunk).await.is_err() || fatal {
+            break;
+        }
+    }
 }
@@ -0,0 +1,178 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    cmp,
+    io,
+    pin::Pin,
+    str,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_lite::{io::AsyncRead, ready};
+
+/// The interval at which `git-upload-pack` is asked to send keepalive packets
+/// while it is preparing the packfile.
+///
+/// This must be well below the idle timeout of the underlying transport, so
+/// that long-running pack generation on the server doesn't get the connection
+/// reaped.
+pub const INTERVAL: Duration = Duration::from_secs(5);
+
+/// The keepalive packet: an empty packet on sideband channel 1.
+const KEEPALIVE: &[u8; 5] = b"0005\x01";
+
+/// An [`AsyncRead`] over a pkt-line stream which drops the keepalive packets
+/// sent by `git-upload-pack`.
+///
+/// The server sends empty data packets on the sideband to signal that it is
+/// still busy. Passed on to the sideband demultiplexer, those would read as
+/// the end of the packfile.
+///
+/// The stream is otherwise passed through unchanged. If it turns out not to be
+/// a valid pkt-line stream, filtering stops.
+pub struct SkipKeepalive<R> {
+    inner: R,
+    /// The pkt-line header, and the first byte of the payload if the packet
+    /// might be a keepalive.
+    head: [u8; 5],
+    /// How many bytes of `head` were read.
+    filled: usize,
+    state: State,
+}
+
+enum State {
+    /// Reading the next pkt-line header.
+    Head,
+    /// Emitting `head` from `pos`, then passing through `payload` more bytes.
+    Emit { pos: usize, payload: usize },
+    /// Passing through `payload` more bytes.
+    Payload { payload: usize },
+    /// Not a pkt-line stream, pass through everything.
+    Raw,
+}
+
+impl<R> SkipKeepalive<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            head: [0; 5],
+            filled: 0,
+            state: State::Head,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> AsyncRead for SkipKeepalive<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        loop {
+            match this.state {
+                State::Head => {
+                    let want = match this.filled {
+                        0..=3 => 4,
+                        _ => match packet_len(&this.head[..4]) {
+                            None => {
+                                this.state = State::Emit {
+                                    pos: 0,
+                                    payload: usize::MAX,
+                                };
+                                continue;
+                            },
+                            Some(len) => {
+                                let want = if len == KEEPALIVE.len() { 5 } else { 4 };
+                                if this.filled == want {
+                                    if &this.head == KEEPALIVE {
+                                        this.filled = 0;
+                                    } else {
+                                        this.state = State::Emit {
+                                            pos: 0,
+                                            payload: len.saturating_sub(want),
+                                        };
+                                    }
+                                    continue;
+                                }
+                                want
+                            },
+                        },
+                    };
+
+                    let n =
+                        ready!(Pin::new(&mut this.inner)
+                            .poll_read(cx, &mut this.head[this.filled..want]))?;
+                    if n == 0 {
+                        return Poll::Ready(if this.filled == 0 {
+                            Ok(0)
+                        } else {
+                            Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "truncated pkt-line header",
+                            ))
+                        });
+                    }
+                    this.filled += n;
+                },
+
+                State::Emit { pos, payload } => {
+                    let n = cmp::min(buf.len(), this.filled - pos);
+                    buf[..n].copy_from_slice(&this.head[pos..pos + n]);
+                    let pos = pos + n;
+                    if pos < this.filled {
+                        this.state = State::Emit { pos, payload };
+                    } else {
+                        this.filled = 0;
+                        this.state = match payload {
+                            0 => State::Head,
+                            usize::MAX => State::Raw,
+                            payload => State::Payload { payload },
+                        };
+                    }
+                    return Poll::Ready(Ok(n));
+                },
+
+                State::Payload { payload } => {
+                    let max = cmp::min(buf.len(), payload);
+                    let n = ready!(Pin::new(&mut this.inner).poll_read(cx, &mut buf[..max]))?;
+                    if n > 0 {
+                        this.state = match payload - n {
+                            0 => State::Head,
+                            payload => State::Payload { payload },
+                        };
+                    }
+                    return Poll::Ready(Ok(n));
+                },
+
+                State::Raw => return Pin::new(&mut this.inner).poll_read(cx, buf),
+            }
+        }
+    }
+}
+
+/// Parse the length of a pkt-line from its hex header.
+///
+/// The special packets `0000`, `0001`, and `0002` have no payload, and are
+/// reported as having length 4.
+fn packet_len(head: &[u8]) -> Option<usize> {
+    let len = str::from_utf8(head)
+        .ok()
+        .and_then(|hex| usize::from_str_radix(hex, 16).ok())?;
+    Some(cmp::max(len, 4))
+}
@@ -5,7 +5,7 @@
 
 use std::io;
 
-use bstr::{BString, ByteVec as _};
+use bstr::{BStr, BString, ByteSlice as _, ByteVec as _};
 use futures_lite::io::{AsyncBufRead, AsyncRead, AsyncWrite};
 use git_features::progress::{self, Progress};
 use git_protocol::{
@@ -17,7 +17,7 @@ use versions::Version;
 
 pub use git_protocol::fetch::Ref;
 
-use super::{remote_git_version, transport};
+use super::{oid, remote_git_version, transport, Capabilities};
 
 // Work around `git-upload-pack` not handling namespaces properly
 //
@@ -53,17 +53,73 @@ pub struct Options {
     pub ref_prefixes: Vec<BString>,
 }
 
+/// Result of a successful [`ls_refs`].
+#[derive(Debug, Default)]
+pub struct Outputs {
+    /// The refs advertised by the server.
+    pub refs: Vec<Ref>,
+    /// The capabilities advertised by the server, which determine what a
+    /// subsequent [`crate::protocol::fetch`] may ask for.
+    pub capabilities: Capabilities,
+}
+
+/// Accessors for the attributes of an advertised [`Ref`].
+///
+/// [`ls_refs`] always asks for the `symrefs` and `peel` attributes, so that
+/// e.g. the default branch of a namespace can be learned from its `HEAD`.
+pub trait RefExt {
+    /// The name of the ref.
+    fn name(&self) -> &BStr;
+
+    /// The object the ref points to, which is the tag object for annotated
+    /// tags.
+    fn object(&self) -> &oid;
+
+    /// The object an annotated tag points to, if the ref is one.
+    fn peeled(&self) -> Option<&oid>;
+
+    /// The name of the ref a symbolic ref points to, if the ref is one.
+    fn symref_target(&self) -> Option<&BStr>;
+}
+
+impl RefExt for Ref {
+    fn name(&self) -> &BStr {
+        self.unpack().0.as_bstr()
+    }
+
+    fn object(&self) -> &oid {
+        match self {
+            Self::Peeled { tag, .. } => tag,
+            _ => self.unpack().1,
+        }
+    }
+
+    fn peeled(&self) -> Option<&oid> {
+        match self {
+            Self::Peeled { object, .. } => Some(object),
+            _ => None,
+        }
+    }
+
+    fn symref_target(&self) -> Option<&BStr> {
+        match self {
+            Self::Symbolic { target, .. } => Some(target.as_bstr()),
+            _ => None,
+        }
+    }
+}
+
 /// [`Delegate`] for running a stateless `ls-refs` command.
 pub struct LsRefs {
     opt: Options,
-    out: Vec<Ref>,
+    out: Outputs,
 }
 
 impl LsRefs {
     pub fn new(opt: Options) -> Self {
         Self {
             opt,
-            out: Vec::new(),
+            out: Outputs::default(),
         }
     }
 }
@@ -79,6 +135,7 @@ impl DelegateBlocking for LsRefs {
         args: &mut Vec<BString>,
         _: &mut Vec<(&str, Option<&str>)>,
     ) -> io::Result<LsRefsAction> {
+        self.out.capabilities = Capabilities::from_advertised(caps);
         let must_namespace = must_namespace(caps);
         for prefix in &self.opt.ref_prefixes {
             let mut arg = BString::from("ref-prefix ");
@@ -90,6 +147,11 @@ impl DelegateBlocking for LsRefs {
             arg.push_str(prefix);
             args.push(arg)
         }
+        for attr in &["symrefs", "peel"] {
+            if !args.iter().any(|arg| arg.as_slice() == attr.as_bytes()) {
+                args.push(BString::from(*attr))
+            }
+        }
         Ok(LsRefsAction::Continue)
     }
 
@@ -100,7 +162,26 @@ impl DelegateBlocking for LsRefs {
         _: &mut Vec<(&str, Option<&str>)>,
         refs: &[Ref],
     ) -> io::Result<Action> {
-        self.out.extend_from_slice(refs);
+        // Servers affected by the namespace bug may also leak the namespace
+        // into symref targets
+        let namespace = format!("refs/namespaces/{}/", self.opt.repo);
+        self.out.refs.extend(refs.iter().map(|r| {
+            match r {
+                Ref::Symbolic {
+                    path,
+                    target,
+                    object,
+                } => Ref::Symbolic {
+                    path: path.clone(),
+                    target: target
+                        .strip_prefix(namespace.as_bytes())
+                        .map(BString::from)
+                        .unwrap_or_else(|| target.clone()),
+                    object: *object,
+                },
+                r => r.clone(),
+            }
+        }));
         Ok(Action::Cancel)
     }
 
@@ -127,7 +208,7 @@ impl Delegate for LsRefs {
     }
 }
 
-pub async fn ls_refs<R, W>(opt: Options, recv: R, send: W) -> io::Result<Vec<Ref>>
+pub async fn ls_refs<R, W>(opt: Options, recv: R, send: W) -> io::Result<Outputs>
 where
     R: AsyncRead + Unpin,
     W: AsyncWrite + Unpin,
@@ -0,0 +1,141 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::BTreeMap, io};
+
+use bstr::BString;
+use futures_lite::io::{AsyncBufReadExt as _, AsyncRead, AsyncWrite};
+use git_hash::ObjectId;
+use git_protocol::transport::{
+    client::{SetServiceResponse, Transport as _, TransportV2Ext as _},
+    Service,
+};
+
+use super::transport;
+
+#[derive(Debug)]
+pub struct Options {
+    /// The remote (logical) repository to ask about, i.e. the name of a
+    /// namespace.
+    pub repo: BString,
+
+    /// [Extra Parameters][extra] to send with the initial transport header.
+    ///
+    /// [extra]: https://git.kernel.org/pub/scm/git/git.git/tree/Documentation/technical/pack-protocol.txt#n52
+    pub extra_params: Vec<(String, Option<String>)>,
+
+    /// The objects to ask about.
+    pub oids: Vec<ObjectId>,
+}
+
+/// Result of a successful [`object_info`].
+#[derive(Debug, Default)]
+pub struct Outputs {
+    /// The size of each requested object, or `None` if the server doesn't
+    /// have it.
+    pub sizes: BTreeMap<ObjectId, Option<u64>>,
+}
+
+impl Outputs {
+    /// The requested objects which the server has.
+    pub fn present(&self) -> impl Iterator<Item = &ObjectId> {
+        self.sizes
+            .iter()
+            .filter_map(|(oid, size)| size.map(|_| oid))
+    }
+}
+
+/// Ask the server whether it has the objects [`Options::oids`], using the
+/// protocol v2 `object-info` command.
+///
+/// This is much cheaper than negotiating a packfile, and can be used to decide
+/// whether a fetch is worthwhile.
+///
+/// A link server only reports objects as present if they are reachable from
+/// the refs of [`Options::repo`], so as to not reveal the contents of other
+/// namespaces.
+///
+/// If the server doesn't advertise `object-info`, an error of kind
+/// [`io::ErrorKind::Unsupported`] is returned.
+pub async fn object_info<R, W>(opt: Options, recv: R, send: W) -> io::Result<Outputs>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut conn = transport::Stateless::new(opt.repo.clone(), recv, send);
+
+    let extra_params = opt
+        .extra_params
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_deref()))
+        .collect::<Vec<_>>();
+    let SetServiceResponse { capabilities, .. } = conn
+        .handshake(Service::UploadPack, &extra_params)
+        .await
+        .map_err(io_other)?;
+    if capabilities.capability("object-info").is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "server does not support `object-info`",
+        ));
+    }
+
+    let mut out = Outputs::default();
+    if opt.oids.is_empty() {
+        return Ok(out);
+    }
+
+    let args = Some("size".into())
+        .into_iter()
+        .chain(opt.oids.iter().map(|oid| format!("oid {}", oid).into()))
+        .collect::<Vec<BString>>();
+    let mut reader = conn
+        .invoke(
+            "object-info",
+            None::<(&str, Option<&str>)>.into_iter(),
+            Some(args.into_iter()),
+        )
+        .await
+        .map_err(io_other)?;
+
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    if line.trim_end() != "size" {
+        return Err(invalid_data("expected `size` attribute"));
+    }
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        // "<oid> <size>", where the size is empty if the object is missing
+        let (oid, size) = line
+            .trim_end_matches('\n')
+            .split_once(' ')
+            .ok_or_else(|| invalid_data("malformed object-info"))?;
+        let oid = ObjectId::from_hex(oid.as_bytes()).map_err(invalid_data)?;
+        let size = match size {
+            "" => None,
+            size => Some(size.parse::<u64>().map_err(invalid_data)?),
+        };
+        out.sizes.insert(oid, size);
+    }
+
+    Ok(out)
+}
+
+fn io_other<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+fn invalid_data<E>(inner: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Sync + Send>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, inner)
+}
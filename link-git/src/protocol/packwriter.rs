@@ -4,8 +4,11 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 use std::{
-    io,
+    env,
+    fs,
+    io::{self, Read as _, Write as _},
     path::{Path, PathBuf},
+    process::{Command, Stdio},
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -14,11 +17,15 @@ use std::{
 
 use futures_lite::io::{AsyncBufRead, BlockOn};
 use git_features::progress::Progress;
-use git_hash::ObjectId;
+use git_hash::{oid, ObjectId};
 use git_odb::{self as odb, pack};
+use tempfile::TempDir;
 
 use super::take::TryTake;
 
+mod cache;
+pub use cache::{BaseCache, Cached};
+
 #[cfg(feature = "git2")]
 pub use libgit::Libgit;
 
@@ -42,11 +49,25 @@ pub trait PackWriter {
 pub struct Options {
     /// How many threads the packfile indexer is allowed to spawn. `None` means
     /// unlimited.
+    ///
+    /// Every indexer thread resolves deltas in its own buffers, which hold the
+    /// objects of one delta chain at a time. Their size is bounded only by the
+    /// objects in the packfile.
     pub max_indexer_threads: Option<usize>,
     /// The maximum size in bytes of the packfile.
     ///
     /// If the remote sends a larger file, the transfer will be aborted.
     pub max_pack_bytes: u64,
+    /// Cache the base objects looked up to complete a thin pack, see
+    /// [`Cached`]. `None` means bases are not cached.
+    pub base_cache: Option<BaseCache>,
+    /// Explode packfiles containing fewer than this many objects into loose
+    /// objects as they are received, see [`Quarantine`]. `None` means
+    /// packfiles are always kept.
+    ///
+    /// This is analogous to `git`'s `fetch.unpackLimit`, and avoids
+    /// accumulating many tiny packfiles from incremental fetches.
+    pub unpack_limit: Option<u32>,
 }
 
 impl Default for Options {
@@ -54,6 +75,8 @@ impl Default for Options {
         Self {
             max_indexer_threads: Some(1),
             max_pack_bytes: u64::MAX,
+            base_cache: None,
+            unpack_limit: None,
         }
     }
 }
@@ -196,10 +219,24 @@ impl BuildThickener for StandardThickener {
     }
 }
 
+/// The output of the [`Standard`] [`PackWriter`].
+pub enum Received {
+    /// The packfile was written to the pack directory.
+    Pack(PackReceived),
+    /// The packfile was exploded into loose objects, see
+    /// [`Options::unpack_limit`].
+    Loose(Quarantine),
+}
+
 /// The default [`PackWriter`].
 ///
 /// Writes the packfile into the given output directory, along with a v2
-/// index. The packfile is verified.
+/// index. The packfile is verified, and objects are indexed as they are
+/// received from the stream.
+///
+/// If the packfile contains fewer objects than [`Options::unpack_limit`], it
+/// is instead exploded into loose objects while it is received, so it is never
+/// stored as a whole. See [`Quarantine`].
 pub struct Standard<F> {
     git_dir: PathBuf,
     opt: Options,
@@ -225,7 +262,7 @@ impl<F> Drop for Standard<F> {
 }
 
 impl<F: BuildThickener> PackWriter for Standard<F> {
-    type Output = PackReceived;
+    type Output = Received;
 
     fn write_pack(
         &self,
@@ -234,27 +271,190 @@ impl<F: BuildThickener> PackWriter for Standard<F> {
     ) -> io::Result<Self::Output> {
         use pack::{bundle::write::Options, data::input::Mode, index::Version, Bundle};
 
+        let mut pack = BlockOn::new(TryTake::new(pack, self.opt.max_pack_bytes));
+        // Peek at the header to decide whether to explode the packfile
+        let mut header = [0; 12];
+        pack.read_exact(&mut header)?;
+        let (version, num_objects) = parse_header(&header)?;
+        if let Some(limit) = self.opt.unpack_limit {
+            if num_objects < limit {
+                return Quarantine::unpack(&self.git_dir, version, num_objects, pack, &self.stop)
+                    .map(Received::Loose);
+            }
+        }
+
         let opts = Options {
             thread_limit: self.opt.max_indexer_threads,
             index_kind: Version::V2,
             iteration_mode: Mode::Verify,
         };
-        let thickener = self
-            .thick
-            .build_thickener()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let thickener = self.thick.build_thickener().map_err(io_other)?;
+        let lookup: Box<dyn FnMut(ObjectId, &mut Vec<u8>) -> Option<pack::data::Object<'_>>> =
+            match self.opt.base_cache {
+                Some(config) => {
+                    let thickener = Cached::new(thickener, config)?;
+                    Box::new(move |oid, buf| thickener.find_object(oid, buf))
+                },
+                None => Box::new(move |oid, buf| thickener.find_object(oid, buf)),
+            };
         Bundle::write_to_directory(
-            BlockOn::new(TryTake::new(pack, self.opt.max_pack_bytes)),
+            io::Cursor::new(header).chain(pack),
             Some(self.git_dir.join("objects").join("pack")),
             prog,
             &self.stop,
-            Some(Box::new(move |oid, buf| thickener.find_object(oid, buf))),
+            Some(lookup),
             opts,
         )
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        .map(Received::Pack)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 }
 
+/// Parse the version and number of objects from a packfile `header`.
+fn parse_header(header: &[u8; 12]) -> io::Result<(u32, u32)> {
+    let word = |at: usize| {
+        let mut be = [0; 4];
+        be.copy_from_slice(&header[at..at + 4]);
+        u32::from_be_bytes(be)
+    };
+    if &header[..4] != b"PACK" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid packfile signature",
+        ));
+    }
+    Ok((word(4), word(8)))
+}
+
+/// Loose objects exploded from a packfile by [`Standard`].
+///
+/// The objects are written to a quarantine directory inside the object
+/// database, and only become visible once they are [`Quarantine::migrate`]d,
+/// e.g. after checking that the wanted objects were received. Dropping the
+/// [`Quarantine`] discards them.
+///
+/// The objects are unpacked by `git unpack-objects` as the packfile is
+/// received, so the packfile itself is never stored. The bases of thin packs
+/// are looked up in the object database, and its alternates, only: the
+/// [`BuildThickener`] is not consulted.
+pub struct Quarantine {
+    dir: TempDir,
+    num_objects: u32,
+}
+
+impl Quarantine {
+    fn unpack(
+        git_dir: &Path,
+        version: u32,
+        num_objects: u32,
+        mut pack: impl io::Read,
+        stop: &AtomicBool,
+    ) -> io::Result<Self> {
+        let objects = git_dir.join("objects");
+        let dir = tempfile::Builder::new()
+            .prefix("incoming-")
+            .tempdir_in(&objects)?;
+        let mut child = Command::new("git")
+            .current_dir(git_dir)
+            .env_clear()
+            .envs(env::vars().filter(|(key, _)| key == "PATH" || key.starts_with("GIT_TRACE")))
+            .env("GIT_DIR", git_dir)
+            .env("GIT_OBJECT_DIRECTORY", dir.path())
+            .env("GIT_ALTERNATE_OBJECT_DIRECTORIES", &objects)
+            .arg("unpack-objects")
+            .arg("-q")
+            // The header was consumed already
+            .arg(format!("--pack_header={},{}", version, num_objects))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        let copied = {
+            let mut stdin = child.stdin.take().expect("stdin is piped");
+            copy_until_stopped(&mut pack, &mut stdin, stop)
+        };
+        if copied.is_err() {
+            // Don't wait for `git` to notice the truncated input
+            child.kill().ok();
+        }
+        let status = child.wait()?;
+        copied?;
+        if !status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("`git unpack-objects` failed: {}", status),
+            ));
+        }
+
+        Ok(Self { dir, num_objects })
+    }
+
+    /// The number of objects in the exploded packfile.
+    pub fn num_objects(&self) -> u32 {
+        self.num_objects
+    }
+
+    /// Test if `id` was unpacked into this [`Quarantine`].
+    pub fn contains(&self, id: impl AsRef<oid>) -> bool {
+        let hex = id.as_ref().to_owned().to_string();
+        self.dir.path().join(&hex[..2]).join(&hex[2..]).is_file()
+    }
+
+    /// Move the objects into the object database at `git_dir`.
+    ///
+    /// This only renames files within the object database, so the objects are
+    /// never copied.
+    pub fn migrate(self, git_dir: impl AsRef<Path>) -> io::Result<()> {
+        let objects = git_dir.as_ref().join("objects");
+        for fanout in fs::read_dir(self.dir.path())? {
+            let fanout = fanout?;
+            if !fanout.file_type()?.is_dir() {
+                continue;
+            }
+            let target = objects.join(fanout.file_name());
+            fs::create_dir_all(&target)?;
+            for obj in fs::read_dir(fanout.path())? {
+                let obj = obj?;
+                let dest = target.join(obj.file_name());
+                // Objects are immutable, so one which exists already is the same
+                if !dest.exists() {
+                    fs::rename(obj.path(), dest)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Copy `r` to `w`, checking `stop` in between chunks.
+fn copy_until_stopped(
+    r: &mut impl io::Read,
+    w: &mut impl io::Write,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        if stop.load(Ordering::Acquire) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        match r.read(&mut buf) {
+            Ok(0) => return w.flush(),
+            Ok(n) => w.write_all(&buf[..n])?,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn io_other<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
 /// No-op [`PackWriter`] which just drains the input.
 pub struct Discard;
 
@@ -0,0 +1,169 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::VecDeque, fs, io};
+
+use git_hash::ObjectId;
+use git_object::Kind;
+use git_odb::pack;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use tempfile::TempDir;
+use tracing::warn;
+
+use super::Thickener;
+
+/// How to cache the base objects looked up to complete a thin pack, see
+/// [`Cached`].
+#[derive(Clone, Copy, Debug)]
+pub struct BaseCache {
+    /// Keep at most this many bytes of base objects in memory.
+    pub capacity: usize,
+    /// Write the base objects evicted from memory to a temporary directory,
+    /// instead of dropping them.
+    ///
+    /// This suits devices which can't afford to keep many bases in memory,
+    /// but would rather not look them up again.
+    pub spill: bool,
+}
+
+/// A [`Thickener`] which caches the base objects found by the wrapped
+/// [`Thickener`].
+///
+/// Thin packs tend to refer to the same bases from many deltas, each of which
+/// would otherwise be looked up (and possibly delta-resolved from the local
+/// packs) again. When the objects in memory take up more than
+/// [`BaseCache::capacity`] bytes, the least recently inserted ones are
+/// evicted, or spilled to disk if [`BaseCache::spill`] is set. Spilled objects
+/// are removed when the [`Cached`] is dropped.
+pub struct Cached<T> {
+    inner: T,
+    cache: Mutex<Cache>,
+}
+
+impl<T> Cached<T> {
+    pub fn new(inner: T, config: BaseCache) -> io::Result<Self> {
+        let spilled = if config.spill {
+            Some(Spilled {
+                objects: FxHashMap::default(),
+                dir: tempfile::tempdir()?,
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            inner,
+            cache: Mutex::new(Cache {
+                capacity: config.capacity,
+                used: 0,
+                mem: FxHashMap::default(),
+                order: VecDeque::new(),
+                spilled,
+            }),
+        })
+    }
+}
+
+impl<T: Thickener> Thickener for Cached<T> {
+    fn find_object<'a>(
+        &self,
+        id: ObjectId,
+        buf: &'a mut Vec<u8>,
+    ) -> Option<pack::data::Object<'a>> {
+        let mut cache = self.cache.lock();
+        if let Some(kind) = cache.get(&id, buf) {
+            return Some(pack::data::Object {
+                kind,
+                data: buf,
+                pack_location: None,
+            });
+        }
+
+        let obj = self.inner.find_object(id, buf)?;
+        cache.put(id, obj.kind, obj.data);
+        Some(obj)
+    }
+}
+
+struct Cache {
+    capacity: usize,
+    used: usize,
+    mem: FxHashMap<ObjectId, (Kind, Vec<u8>)>,
+    /// Insertion order of `mem`, oldest first.
+    order: VecDeque<ObjectId>,
+    spilled: Option<Spilled>,
+}
+
+struct Spilled {
+    objects: FxHashMap<ObjectId, Kind>,
+    dir: TempDir,
+}
+
+impl Cache {
+    fn get(&self, id: &ObjectId, buf: &mut Vec<u8>) -> Option<Kind> {
+        if let Some((kind, data)) = self.mem.get(id) {
+            buf.clear();
+            buf.extend_from_slice(data);
+            return Some(*kind);
+        }
+
+        let spilled = self.spilled.as_ref()?;
+        let kind = spilled.objects.get(id)?;
+        match fs::read(spilled.dir.path().join(id.to_string())) {
+            Ok(data) => {
+                *buf = data;
+                Some(*kind)
+            },
+            Err(e) => {
+                warn!(err = %e, "failed to read spilled base object {}", id);
+                None
+            },
+        }
+    }
+
+    fn put(&mut self, id: ObjectId, kind: Kind, data: &[u8]) {
+        let spilled = self
+            .spilled
+            .as_ref()
+            .map(|spilled| spilled.objects.contains_key(&id))
+            .unwrap_or(false);
+        if spilled || self.mem.contains_key(&id) {
+            return;
+        }
+
+        if data.len() > self.capacity {
+            self.evict(id, kind, data);
+            return;
+        }
+        while self.used + data.len() > self.capacity {
+            match self.order.pop_front() {
+                None => break,
+                Some(oldest) => {
+                    if let Some((kind, data)) = self.mem.remove(&oldest) {
+                        self.used -= data.len();
+                        self.evict(oldest, kind, &data);
+                    }
+                },
+            }
+        }
+        self.used += data.len();
+        self.mem.insert(id, (kind, data.to_vec()));
+        self.order.push_back(id);
+    }
+
+    fn evict(&mut self, id: ObjectId, kind: Kind, data: &[u8]) {
+        let spilled = match &mut self.spilled {
+            None => return,
+            Some(spilled) => spilled,
+        };
+        // Failing to spill only means the object needs to be looked up again
+        match fs::write(spilled.dir.path().join(id.to_string()), data) {
+            Ok(()) => {
+                spilled.objects.insert(id, kind);
+            },
+            Err(e) => warn!(err = %e, "failed to spill base object {}", id),
+        }
+    }
+}
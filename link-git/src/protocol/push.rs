@@ -0,0 +1,410 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{io, path::PathBuf};
+
+use async_process::{ChildStdout, Command, Stdio};
+use bstr::{BString, ByteSlice as _};
+use futures_lite::io::{copy, AsyncRead, AsyncWrite, AsyncWriteExt as _};
+use futures_util::try_join;
+use git_hash::ObjectId;
+use git_packetline::{self as packetline, PacketLineRef};
+
+use super::Ref;
+
+#[derive(Debug)]
+pub struct Options {
+    /// The remote (logical) repository to push to, i.e. the name of a
+    /// namespace.
+    pub repo: BString,
+
+    /// [Extra Parameters][extra] to send with the initial transport header.
+    ///
+    /// [extra]: https://git.kernel.org/pub/scm/git/git.git/tree/Documentation/technical/pack-protocol.txt#n52
+    pub extra_params: Vec<(String, Option<String>)>,
+
+    /// The refs to update.
+    pub updates: Vec<Update>,
+
+    /// Whether either all or none of the [`Options::updates`] should be
+    /// applied.
+    ///
+    /// If the server does not support `atomic` pushes, the push is aborted.
+    pub atomic: bool,
+
+    /// [Push options][opts] to pass to the hooks on the server.
+    ///
+    /// If the server does not support `push-options`, the push is aborted.
+    ///
+    /// [opts]: https://git-scm.com/docs/git-push#Documentation/git-push.txt---push-optionltoptiongt
+    pub push_options: Vec<BString>,
+}
+
+/// An update of the ref `name` from `old` to `new`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Update {
+    /// The name of the ref, relative to the namespace.
+    pub name: BString,
+    /// The value the ref is expected to have on the remote end, with `None`
+    /// meaning the value it was advertised with.
+    ///
+    /// If the value doesn't match, the update is rejected.
+    pub old: Option<ObjectId>,
+    /// The new value of the ref, with `None` meaning the ref should be deleted.
+    pub new: Option<ObjectId>,
+}
+
+/// Result of a [`push`].
+#[derive(Debug, Default)]
+pub struct Outputs {
+    /// The refs advertised by the server before the update.
+    pub refs: Vec<Ref>,
+    /// The status of unpacking the packfile on the server.
+    ///
+    /// If unpacking fails, no refs are updated.
+    pub unpack: Option<Result<(), BString>>,
+    /// The status of each command, as reported by the server.
+    pub statuses: Vec<Status>,
+}
+
+/// The status of a single ref update.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Status {
+    pub name: BString,
+    /// Either `ok`, or `ng` along with the reason.
+    pub result: Result<(), BString>,
+    /// Amendments to the update made by the server, as per
+    /// `report-status-v2`.
+    pub options: Vec<StatusOption>,
+}
+
+/// The options of a [`Status`], reporting that the server updated a different
+/// ref, or to a different value, than the one requested.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatusOption {
+    Refname(BString),
+    OldOid(ObjectId),
+    NewOid(ObjectId),
+    ForcedUpdate,
+}
+
+/// Produce the packfile to send to the server.
+#[async_trait(?Send)]
+pub trait PackBuilder {
+    type Pack: AsyncRead + Unpin;
+
+    /// Build a packfile containing the objects reachable from `wants`, but
+    /// not from `haves`.
+    ///
+    /// The `haves` are the objects the server is known to have, some of which
+    /// may not exist locally.
+    async fn build_pack(&self, wants: &[ObjectId], haves: &[ObjectId]) -> io::Result<Self::Pack>;
+}
+
+/// A [`PackBuilder`] using `git pack-objects`.
+pub struct PackObjects {
+    git_dir: PathBuf,
+}
+
+impl PackObjects {
+    pub fn new(git_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            git_dir: git_dir.into(),
+        }
+    }
+
+    fn git(&self) -> Command {
+        let mut cmd = Command::new("git");
+        cmd.current_dir(&self.git_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        cmd
+    }
+
+    async fn existing(&self, oids: &[ObjectId]) -> io::Result<Vec<ObjectId>> {
+        if oids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut child = self
+            .git()
+            .args(&["cat-file", "--batch-check=%(objectname)"])
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let input = oids
+            .iter()
+            .map(|oid| format!("{}\n", oid))
+            .collect::<String>();
+        let (_, out) = try_join!(
+            async move { stdin.write_all(input.as_bytes()).await },
+            child.output()
+        )?;
+        if !out.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("git cat-file failed: {}", out.status),
+            ));
+        }
+
+        // Missing objects are reported as "<oid> missing"
+        out.stdout
+            .lines()
+            .filter(|line| !line.contains(&b' '))
+            .map(|line| ObjectId::from_hex(line).map_err(invalid_data))
+            .collect()
+    }
+}
+
+#[async_trait(?Send)]
+impl PackBuilder for PackObjects {
+    type Pack = ChildStdout;
+
+    async fn build_pack(&self, wants: &[ObjectId], haves: &[ObjectId]) -> io::Result<Self::Pack> {
+        let mut revs = wants
+            .iter()
+            .map(|oid| format!("{}\n", oid))
+            .collect::<String>();
+        for oid in self.existing(haves).await? {
+            revs.push_str(&format!("^{}\n", oid));
+        }
+
+        let mut child = self
+            .git()
+            .args(&[
+                "pack-objects",
+                "--stdout",
+                "--revs",
+                "--delta-base-offset",
+                "-q",
+            ])
+            .spawn()?;
+        // `pack-objects` reads all revs before writing anything
+        {
+            let mut stdin = child.stdin.take().unwrap();
+            stdin.write_all(revs.as_bytes()).await?;
+        }
+        Ok(child.stdout.take().unwrap())
+    }
+}
+
+/// Push to the namespace [`Options::repo`] using the [pack protocol].
+///
+/// Unlike fetches, pushes are not supported by version 2 of the protocol, so
+/// this speaks version 0. The report of the server is requested as
+/// `report-status-v2` if it is supported.
+///
+/// Rejected updates are not errors, but are reported in
+/// [`Outputs::statuses`].
+///
+/// [pack protocol]: https://git.kernel.org/pub/scm/git/git.git/tree/Documentation/technical/pack-protocol.txt
+pub async fn push<B, R, W>(
+    opt: Options,
+    pack_builder: B,
+    recv: R,
+    mut send: W,
+) -> io::Result<Outputs>
+where
+    B: PackBuilder,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut out = Outputs::default();
+
+    {
+        let mut header = format!("git-receive-pack {}\0", opt.repo);
+        if !opt.extra_params.is_empty() {
+            header.push('\0');
+            for (key, val) in &opt.extra_params {
+                match val {
+                    Some(val) => header.push_str(&format!("{}={}\0", key, val)),
+                    None => header.push_str(&format!("{}\0", key)),
+                }
+            }
+        }
+        packetline::encode::data_to_write(header.as_bytes(), &mut send).await?;
+    }
+
+    let mut recv = packetline::StreamingPeekableIter::new(recv, &[PacketLineRef::Flush]);
+    recv.fail_on_err_lines(true);
+
+    // Reference discovery
+    let mut caps = Vec::new();
+    let mut haves = Vec::new();
+    while let Some(line) = recv.read_line().await {
+        let line = line?.map_err(invalid_data)?;
+        let line = line
+            .as_slice()
+            .ok_or_else(|| invalid_data("unexpected special packet"))?;
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = match line.split_once_str(b"\0") {
+            Some((line, capabilities)) => {
+                caps = capabilities
+                    .split_str(" ")
+                    .map(BString::from)
+                    .collect::<Vec<_>>();
+                line
+            },
+            None => line,
+        };
+        let (oid, name) = line
+            .split_once_str(b" ")
+            .ok_or_else(|| invalid_data("malformed ref advertisement"))?;
+        let object = ObjectId::from_hex(oid).map_err(invalid_data)?;
+        match name {
+            b"capabilities^{}" => {},
+            b".have" => haves.push(object),
+            path => {
+                haves.push(object);
+                out.refs.push(Ref::Direct {
+                    path: path.into(),
+                    object,
+                })
+            },
+        }
+    }
+    let has_cap = |name: &[u8]| caps.iter().any(|cap| cap == name);
+
+    if opt.atomic && !has_cap(b"atomic") {
+        return Err(unsupported("`atomic` push requested, but not supported"));
+    }
+    if !opt.push_options.is_empty() && !has_cap(b"push-options") {
+        return Err(unsupported(
+            "push options given, but server does not support `push-options`",
+        ));
+    }
+
+    if opt.updates.is_empty() {
+        packetline::encode::flush_to_write(&mut send).await?;
+        return Ok(out);
+    }
+
+    // Update commands
+    let mut request_caps = Vec::new();
+    let report_status = if has_cap(b"report-status-v2") {
+        request_caps.push("report-status-v2");
+        true
+    } else if has_cap(b"report-status") {
+        request_caps.push("report-status");
+        true
+    } else {
+        false
+    };
+    if opt.atomic {
+        request_caps.push("atomic");
+    }
+    if !opt.push_options.is_empty() {
+        request_caps.push("push-options");
+    }
+    if has_cap(b"ofs-delta") {
+        request_caps.push("ofs-delta");
+    }
+
+    let null = ObjectId::null_sha1();
+    let mut wants = Vec::new();
+    for (i, Update { name, old, new }) in opt.updates.iter().enumerate() {
+        let old = old.unwrap_or_else(|| {
+            out.refs
+                .iter()
+                .find_map(|r| match r {
+                    Ref::Direct { path, object } if path == name => Some(*object),
+                    _ => None,
+                })
+                .unwrap_or(null)
+        });
+        let new = new.unwrap_or(null);
+        if !new.is_null() {
+            wants.push(new);
+        }
+
+        let mut cmd = format!("{} {} {}", old, new, name).into_bytes();
+        if i == 0 {
+            cmd.push(b'\0');
+            cmd.extend_from_slice(request_caps.join(" ").as_bytes());
+        }
+        packetline::encode::data_to_write(&cmd, &mut send).await?;
+    }
+    packetline::encode::flush_to_write(&mut send).await?;
+
+    if !opt.push_options.is_empty() {
+        for option in &opt.push_options {
+            packetline::encode::data_to_write(option, &mut send).await?;
+        }
+        packetline::encode::flush_to_write(&mut send).await?;
+    }
+
+    // A packfile is only sent if there is anything to unpack
+    if !wants.is_empty() {
+        let mut pack = pack_builder.build_pack(&wants, &haves).await?;
+        copy(&mut pack, &mut send).await?;
+    }
+    send.flush().await?;
+
+    if !report_status {
+        return Ok(out);
+    }
+
+    // Report
+    recv.reset();
+    while let Some(line) = recv.read_line().await {
+        let line = line?.map_err(invalid_data)?;
+        let line = line
+            .as_slice()
+            .ok_or_else(|| invalid_data("unexpected special packet"))?;
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+
+        if let Some(unpack) = line.strip_prefix(b"unpack ") {
+            out.unpack = Some(match unpack {
+                b"ok" => Ok(()),
+                err => Err(err.into()),
+            });
+        } else if let Some(name) = line.strip_prefix(b"ok ") {
+            out.statuses.push(Status {
+                name: name.into(),
+                result: Ok(()),
+                options: vec![],
+            });
+        } else if let Some(ng) = line.strip_prefix(b"ng ") {
+            let (name, reason) = ng.split_once_str(b" ").unwrap_or((ng, b""));
+            out.statuses.push(Status {
+                name: name.into(),
+                result: Err(reason.into()),
+                options: vec![],
+            });
+        } else if let Some(option) = line.strip_prefix(b"option ") {
+            let option = match option.split_once_str(b" ") {
+                Some((b"refname", name)) => StatusOption::Refname(name.into()),
+                Some((b"old-oid", oid)) => {
+                    StatusOption::OldOid(ObjectId::from_hex(oid).map_err(invalid_data)?)
+                },
+                Some((b"new-oid", oid)) => {
+                    StatusOption::NewOid(ObjectId::from_hex(oid).map_err(invalid_data)?)
+                },
+                None if option == b"forced-update" => StatusOption::ForcedUpdate,
+                _ => return Err(invalid_data("unknown report-status option")),
+            };
+            out.statuses
+                .last_mut()
+                .ok_or_else(|| invalid_data("report-status option without command status"))?
+                .options
+                .push(option);
+        } else {
+            return Err(invalid_data("malformed report-status"));
+        }
+    }
+
+    Ok(out)
+}
+
+fn unsupported(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, msg)
+}
+
+fn invalid_data<E>(inner: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Sync + Send>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, inner)
+}
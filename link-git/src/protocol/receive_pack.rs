@@ -0,0 +1,96 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{future::Future, io, path::Path, process::ExitStatus};
+
+use async_process::{Command, Stdio};
+use futures_lite::io::{copy, AsyncRead, AsyncWrite, BufReader};
+use futures_util::try_join;
+use git_packetline::{self as packetline, PacketLineRef};
+
+pub use super::upload_pack::Header;
+
+/// Serve a push to the namespace given by the [`Header`] sent by the client.
+///
+/// Only refs within the namespace are advertised, and can be updated. The
+/// server supports `report-status-v2`, `atomic` pushes, and push options.
+///
+/// As with [`super::upload_pack`], the returned future must be polled to
+/// completion in order to run the exchange.
+pub async fn receive_pack<R, W>(
+    git_dir: impl AsRef<Path>,
+    recv: R,
+    mut send: W,
+) -> io::Result<(Header, impl Future<Output = io::Result<ExitStatus>>)>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut pktline = packetline::StreamingPeekableIter::new(BufReader::new(recv), &[]);
+    let header = {
+        let pkt = pktline
+            .read_line()
+            .await
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "expected header"))?
+            .map_err(invalid_data)?
+            .map_err(invalid_data)?;
+        match pkt {
+            PacketLineRef::Data(data) => {
+                let hdr = std::str::from_utf8(data).map_err(invalid_data)?;
+                Header::parse("git-receive-pack", hdr).map_err(invalid_data)
+            },
+            _ => Err(invalid_data("not a header packet")),
+        }?
+    };
+    let mut recv = pktline.into_inner();
+
+    let namespace = header
+        .path
+        .strip_prefix("rad:git:")
+        .map(ToOwned::to_owned)
+        .unwrap_or_else(|| header.path.clone());
+    let git_dir = git_dir.as_ref().to_path_buf();
+
+    let fut = async move {
+        let mut child = Command::new("git")
+            .current_dir(git_dir)
+            .env_clear()
+            .envs(std::env::vars().filter(|(key, _)| key == "PATH" || key.starts_with("GIT_TRACE")))
+            .env("GIT_NAMESPACE", namespace)
+            .args(&[
+                "-c",
+                "receive.advertiseAtomic=true",
+                "-c",
+                "receive.advertisePushOptions=true",
+                "receive-pack",
+                ".",
+            ])
+            .stdout(Stdio::piped())
+            .stdin(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .reap_on_drop(true)
+            .spawn()?;
+
+        let mut stdin = child.stdin.take().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+
+        try_join!(
+            copy(&mut recv, &mut stdin),
+            copy(&mut stdout, &mut send),
+            child.status(),
+        )
+        .map(|(_, _, status)| status)
+    };
+
+    Ok((header, fut))
+}
+
+fn invalid_data<E>(inner: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Sync + Send>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, inner)
+}
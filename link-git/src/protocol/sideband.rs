@@ -0,0 +1,159 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Handling of the sideband channels of a `fetch` response: progress messages
+//! on channel 2, and fatal errors on channel 3.
+//!
+//! [`git_protocol`] demultiplexes the channels, and translates the messages
+//! into calls on a [`Progress`], which is implemented here by [`Sideband`].
+
+use std::{fmt, io, sync::Arc};
+
+use git_features::progress::{MessageLevel, Progress, Unit};
+use parking_lot::Mutex;
+use thiserror::Error;
+
+/// Callback receiving the progress messages sent by the server, e.g.
+/// `Counting objects: 21/50`.
+///
+/// The callback may be invoked several times for a single message, as the
+/// step and the total become known.
+#[derive(Clone)]
+pub struct OnProgress(Arc<dyn Fn(&str) + Send + Sync>);
+
+impl OnProgress {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for OnProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnProgress")
+    }
+}
+
+/// A fatal error reported by the server, which aborted the `fetch`.
+///
+/// It is returned as the inner error of an [`io::Error`], see
+/// [`RemoteError::from_io`].
+#[derive(Clone, Debug, Error)]
+#[error("remote error: {0}")]
+pub struct RemoteError(pub String);
+
+impl RemoteError {
+    /// The [`RemoteError`] which caused `err`, if any.
+    pub fn from_io(err: &io::Error) -> Option<&Self> {
+        err.get_ref().and_then(|inner| inner.downcast_ref())
+    }
+}
+
+/// [`Progress`] forwarding progress messages to an [`OnProgress`], and
+/// recording fatal errors.
+pub(super) struct Sideband {
+    name: Option<String>,
+    max: Option<usize>,
+    step: usize,
+    on_progress: Option<OnProgress>,
+    errors: Arc<Mutex<Vec<String>>>,
+}
+
+impl Sideband {
+    pub(super) fn new(on_progress: Option<OnProgress>) -> Self {
+        Self {
+            name: None,
+            max: None,
+            step: 0,
+            on_progress,
+            errors: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// The fatal errors reported by the server, shared with all children.
+    pub(super) fn errors(&self) -> Arc<Mutex<Vec<String>>> {
+        Arc::clone(&self.errors)
+    }
+
+    /// Convert the error of a failed `fetch` into a [`RemoteError`] if the
+    /// server reported one, as it explains the failure better than the
+    /// transport error which results from the server hanging up.
+    pub(super) fn into_io_error<E>(errors: &Mutex<Vec<String>>, err: E) -> io::Error
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let errors = errors.lock();
+        if errors.is_empty() {
+            io::Error::new(io::ErrorKind::Other, err)
+        } else {
+            io::Error::new(io::ErrorKind::Other, RemoteError(errors.join("\n")))
+        }
+    }
+
+    fn report(&self) {
+        if let (Some(on_progress), Some(name)) = (&self.on_progress, &self.name) {
+            match self.max {
+                Some(max) => (on_progress.0)(&format!("{}: {}/{}", name, self.step, max)),
+                None => (on_progress.0)(name),
+            }
+        }
+    }
+}
+
+impl Progress for Sideband {
+    type SubProgress = Self;
+
+    fn add_child(&mut self, name: impl Into<String>) -> Self::SubProgress {
+        Self {
+            name: Some(name.into()),
+            max: None,
+            step: 0,
+            on_progress: self.on_progress.clone(),
+            errors: Arc::clone(&self.errors),
+        }
+    }
+
+    fn init(&mut self, max: Option<usize>, _unit: Option<Unit>) {
+        self.max = max;
+        self.step = 0;
+    }
+
+    fn set(&mut self, step: usize) {
+        self.step = step;
+        self.report()
+    }
+
+    fn step(&self) -> usize {
+        self.step
+    }
+
+    fn inc_by(&mut self, step: usize) {
+        self.step += step;
+        self.report()
+    }
+
+    fn set_name(&mut self, name: impl Into<String>) {
+        self.name = Some(name.into());
+        self.report()
+    }
+
+    fn name(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    fn message(&mut self, level: MessageLevel, message: impl Into<String>) {
+        let message = message.into();
+        match level {
+            MessageLevel::Failure => self.errors.lock().push(message),
+            MessageLevel::Info | MessageLevel::Success => {
+                if let Some(on_progress) = &self.on_progress {
+                    (on_progress.0)(&message)
+                }
+            },
+        }
+    }
+}
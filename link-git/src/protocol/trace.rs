@@ -0,0 +1,335 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Capturing of the pkt-lines exchanged over a connection, for diagnosing
+//! interoperability issues.
+//!
+//! A [`Tracer`] wraps the `recv` and `send` halves of a connection before they
+//! are handed to any of the protocol functions (client or server), and reports
+//! every pkt-line to a sink as a [`Record`].
+
+use std::{
+    cmp,
+    fmt,
+    io,
+    pin::Pin,
+    str,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use bstr::BString;
+use futures_lite::io::{AsyncRead, AsyncWrite};
+
+/// The side of the connection a pkt-line was seen on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Send,
+    Recv,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Packet {
+    Flush,
+    Delimiter,
+    ResponseEnd,
+    Data {
+        /// The length of the payload on the wire.
+        len: usize,
+        /// The payload, possibly truncated or redacted.
+        payload: BString,
+        /// Whether `payload` was redacted.
+        redacted: bool,
+    },
+    /// Data which is not framed as pkt-lines, e.g. a packfile sent without
+    /// sideband. Only reported once the stream is dropped.
+    Raw {
+        len: usize,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Record {
+    pub direction: Direction,
+    pub packet: Packet,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Payloads longer than this are truncated.
+    pub max_payload: usize,
+    /// Stop recording after this many payload bytes were recorded in total,
+    /// per direction.
+    pub max_total: usize,
+    /// Payloads starting with any of these prefixes are redacted, retaining
+    /// only the prefix.
+    pub redact: Vec<BString>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_payload: 1024,
+            max_total: 1024 * 1024,
+            redact: vec!["server-option=".into(), "push-cert".into()],
+        }
+    }
+}
+
+/// Reports pkt-lines to a sink callback.
+///
+/// Packfile data (sideband channel 1) is never recorded, only its length.
+#[derive(Clone)]
+pub struct Tracer {
+    opt: Arc<Options>,
+    sink: Arc<dyn Fn(Record) + Send + Sync>,
+}
+
+impl Tracer {
+    pub fn new<F>(opt: Options, sink: F) -> Self
+    where
+        F: Fn(Record) + Send + Sync + 'static,
+    {
+        Self {
+            opt: Arc::new(opt),
+            sink: Arc::new(sink),
+        }
+    }
+
+    /// Wrap the `recv` and `send` halves of a connection.
+    pub fn wrap<R, W>(&self, recv: R, send: W) -> (Traced<R>, Traced<W>) {
+        (
+            Traced::new(recv, Direction::Recv, Some(self)),
+            Traced::new(send, Direction::Send, Some(self)),
+        )
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer").field("opt", &self.opt).finish()
+    }
+}
+
+/// An [`AsyncRead`] or [`AsyncWrite`] reporting the pkt-lines passing through
+/// it to a [`Tracer`].
+///
+/// If no [`Tracer`] is given, this is a no-op wrapper.
+pub struct Traced<S> {
+    inner: S,
+    framer: Option<Framer>,
+}
+
+impl<S> Traced<S> {
+    pub fn new(inner: S, direction: Direction, tracer: Option<&Tracer>) -> Self {
+        Self {
+            inner,
+            framer: tracer.map(|tracer| Framer::new(tracer.clone(), direction)),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<R> AsyncRead for Traced<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(n)), Some(framer)) = (&res, &mut this.framer) {
+            framer.feed(&buf[..*n])
+        }
+        res
+    }
+}
+
+impl<W> AsyncWrite for Traced<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(n)), Some(framer)) = (&res, &mut this.framer) {
+            framer.feed(&buf[..*n])
+        }
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+/// Splits a byte stream into pkt-lines.
+struct Framer {
+    tracer: Tracer,
+    direction: Direction,
+    head: [u8; 4],
+    filled: usize,
+    state: State,
+    /// How many bytes of a payload to buffer: enough to truncate to
+    /// [`Options::max_payload`], and to detect the prefixes to redact.
+    keep: usize,
+    /// Payload bytes recorded so far.
+    total: usize,
+}
+
+enum State {
+    Head,
+    Payload {
+        len: usize,
+        remaining: usize,
+        buf: Vec<u8>,
+    },
+    Raw {
+        len: usize,
+    },
+}
+
+impl Framer {
+    fn new(tracer: Tracer, direction: Direction) -> Self {
+        let keep = tracer
+            .opt
+            .redact
+            .iter()
+            .map(|prefix| prefix.len())
+            .fold(tracer.opt.max_payload, cmp::max);
+        Self {
+            tracer,
+            direction,
+            head: [0; 4],
+            filled: 0,
+            state: State::Head,
+            keep,
+            total: 0,
+        }
+    }
+
+    fn feed(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            match &mut self.state {
+                State::Head => {
+                    let n = cmp::min(4 - self.filled, bytes.len());
+                    self.head[self.filled..self.filled + n].copy_from_slice(&bytes[..n]);
+                    self.filled += n;
+                    bytes = &bytes[n..];
+                    if self.filled < 4 {
+                        continue;
+                    }
+                    self.filled = 0;
+
+                    let len = str::from_utf8(&self.head)
+                        .ok()
+                        .and_then(|hex| usize::from_str_radix(hex, 16).ok());
+                    match len {
+                        Some(0) => self.emit(Packet::Flush),
+                        Some(1) => self.emit(Packet::Delimiter),
+                        Some(2) => self.emit(Packet::ResponseEnd),
+                        Some(4) => self.emit(Packet::Data {
+                            len: 0,
+                            payload: BString::default(),
+                            redacted: false,
+                        }),
+                        Some(len) if len > 4 => {
+                            let len = len - 4;
+                            self.state = State::Payload {
+                                len,
+                                remaining: len,
+                                buf: Vec::with_capacity(cmp::min(len, self.keep)),
+                            }
+                        },
+                        _ => self.state = State::Raw { len: 4 },
+                    }
+                },
+
+                State::Payload {
+                    len,
+                    remaining,
+                    buf,
+                } => {
+                    let n = cmp::min(*remaining, bytes.len());
+                    let keep = cmp::min(n, self.keep - buf.len());
+                    buf.extend_from_slice(&bytes[..keep]);
+                    *remaining -= n;
+                    bytes = &bytes[n..];
+                    if *remaining == 0 {
+                        let len = *len;
+                        let buf = std::mem::take(buf);
+                        self.state = State::Head;
+                        self.emit_data(len, buf)
+                    }
+                },
+
+                State::Raw { len } => {
+                    *len += bytes.len();
+                    bytes = &[];
+                },
+            }
+        }
+    }
+
+    fn emit_data(&mut self, len: usize, mut payload: Vec<u8>) {
+        let mut redacted = false;
+        // Packfile data
+        if payload.first() == Some(&1) {
+            payload.truncate(1);
+        } else if let Some(prefix) = self
+            .tracer
+            .opt
+            .redact
+            .iter()
+            .find(|prefix| payload.starts_with(prefix))
+        {
+            payload.truncate(prefix.len());
+            redacted = true;
+        } else {
+            payload.truncate(self.tracer.opt.max_payload);
+        }
+
+        self.emit(Packet::Data {
+            len,
+            payload: payload.into(),
+            redacted,
+        })
+    }
+
+    fn emit(&mut self, packet: Packet) {
+        if self.total > self.tracer.opt.max_total {
+            return;
+        }
+        if let Packet::Data { payload, .. } = &packet {
+            self.total += payload.len();
+        }
+        (self.tracer.sink)(Record {
+            direction: self.direction,
+            packet,
+        })
+    }
+}
+
+impl Drop for Framer {
+    fn drop(&mut self) {
+        if let State::Raw { len } = self.state {
+            self.emit(Packet::Raw { len })
+        }
+    }
+}
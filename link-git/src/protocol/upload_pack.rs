@@ -12,7 +12,14 @@ use git_packetline::{self as packetline, PacketLineRef};
 use once_cell::sync::Lazy;
 use versions::Version;
 
+use super::{bundle_uri, keepalive};
+
 mod legacy;
+mod object_info;
+
+// Thou shallt not upgrade your `git` installation while a link instance is
+// running!
+static GIT_VERSION: Lazy<Version> = Lazy::new(|| git_version().unwrap());
 
 #[derive(Debug, PartialEq)]
 pub struct Header {
@@ -21,12 +28,13 @@ pub struct Header {
     pub extra: Vec<(String, Option<String>)>,
 }
 
-impl FromStr for Header {
-    type Err = &'static str;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl Header {
+    /// Parse the header sent by a client requesting `service`, e.g.
+    /// `git-receive-pack`.
+    pub(super) fn parse(service: &str, s: &str) -> Result<Self, &'static str> {
         let mut parts = s
-            .strip_prefix("git-upload-pack ")
+            .strip_prefix(service)
+            .and_then(|rest| rest.strip_prefix(' '))
             .ok_or("unsupported service")?
             .split_terminator('\0');
 
@@ -62,6 +70,14 @@ impl FromStr for Header {
     }
 }
 
+impl FromStr for Header {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse("git-upload-pack", s)
+    }
+}
+
 pub async fn upload_pack<R, W>(
     git_dir: impl AsRef<Path>,
     recv: R,
@@ -131,15 +147,23 @@ where
     let stateless_ls = header.extra.iter().any(|(k, _)| k == "ls");
 
     let fut = async move {
+        let mut bundles = vec![];
         if protocol_version < 2 {
             if stateless_ls {
                 return legacy::advertise_refs(git_dir, &namespace, recv, send).await;
             }
         } else {
-            advertise_capabilities(&mut send).await?;
+            if bundle_uri::supported(&GIT_VERSION) {
+                bundles = bundle_uri::configured(git_dir.as_ref(), &namespace).await?;
+            }
+            advertise_capabilities(&mut send, !bundles.is_empty()).await?;
+            if object_info::is_request(recv.fill_buf().await?) {
+                return object_info::serve(git_dir, &namespace, recv, send).await;
+            }
         }
 
         let mut child = {
+            let keepalive = format!("uploadpack.keepalive={}", keepalive::INTERVAL.as_secs());
             let mut cmd = Command::new("git");
             cmd.current_dir(git_dir)
                 .env_clear()
@@ -149,12 +173,17 @@ where
                 )
                 .env("GIT_PROTOCOL", format!("version={}", protocol_version))
                 .env("GIT_NAMESPACE", namespace)
+                .args(bundle_uri::upload_pack_config(&bundles))
                 .args(&[
                     "-c",
                     "uploadpack.allowanysha1inwant=true",
                     "-c",
                     "uploadpack.allowrefinwant=true",
                     "-c",
+                    "uploadpack.allowfilter=true",
+                    "-c",
+                    &keepalive,
+                    "-c",
                     "lsrefs.unborn=ignore",
                     "upload-pack",
                     "--strict",
@@ -183,26 +212,27 @@ where
     Ok((header, fut))
 }
 
-async fn advertise_capabilities<W>(mut send: W) -> io::Result<()>
+async fn advertise_capabilities<W>(mut send: W, bundle_uri: bool) -> io::Result<()>
 where
     W: AsyncWrite + Unpin,
 {
-    // Thou shallt not upgrade your `git` installation while a link instance is
-    // running!
-    static GIT_VERSION: Lazy<Version> = Lazy::new(|| git_version().unwrap());
     static AGENT: Lazy<Vec<u8>> = Lazy::new(|| format!("agent=git/{}", *GIT_VERSION).into_bytes());
-    static CAPABILITIES: Lazy<[&[u8]; 4]> = Lazy::new(|| {
+    static CAPABILITIES: Lazy<[&[u8]; 5]> = Lazy::new(|| {
         [
             b"version 2",
             AGENT.as_slice(),
             b"object-format=sha1",
-            b"fetch=ref-in-want",
+            b"fetch=ref-in-want filter",
+            b"object-info",
         ]
     });
 
     for cap in *CAPABILITIES {
         packetline::encode::text_to_write(cap, &mut send).await?;
     }
+    if bundle_uri {
+        packetline::encode::text_to_write(b"bundle-uri", &mut send).await?;
+    }
     packetline::encode::flush_to_write(&mut send).await?;
 
     Ok(())
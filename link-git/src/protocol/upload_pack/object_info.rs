@@ -0,0 +1,179 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write as _},
+    path::Path,
+    process::{Command, ExitStatus, Output, Stdio},
+    thread,
+};
+
+use bstr::ByteSlice as _;
+use futures_lite::io::{AsyncRead, AsyncReadExt as _, AsyncWrite};
+use git_hash::ObjectId;
+use git_packetline::{self as packetline, PacketLineRef};
+
+/// Whether the buffered start of a request, `buf`, is an `object-info`
+/// command.
+pub(super) fn is_request(buf: &[u8]) -> bool {
+    // Skip the pkt-line length
+    buf.get(4..)
+        .map(|cmd| cmd.starts_with(b"command=object-info"))
+        .unwrap_or(false)
+}
+
+/// Serve a protocol v2 `object-info` request.
+///
+/// `git upload-pack` would answer it from the whole object database, thereby
+/// revealing which objects exist in other namespaces. Instead, only objects
+/// reachable from the refs of `namespace` are reported as present.
+pub(super) async fn serve<R, W>(
+    git_dir: impl AsRef<Path>,
+    namespace: &str,
+    recv: R,
+    mut send: W,
+) -> io::Result<ExitStatus>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut pktline = packetline::StreamingPeekableIter::new(recv, &[PacketLineRef::Flush]);
+    let mut oids = Vec::new();
+    while let Some(line) = pktline.read_line().await {
+        let line = line?.map_err(invalid_data)?;
+        // Skip the command, capabilities and delimiter
+        if let Some(oid) = line
+            .as_slice()
+            .and_then(|line| line.strip_prefix(b"oid "))
+            .map(|oid| oid.trim_end())
+        {
+            oids.push(ObjectId::from_hex(oid).map_err(invalid_data)?);
+        }
+    }
+    let mut recv = pktline.into_inner();
+
+    let (sizes, status) = blocking::unblock({
+        let git_dir = git_dir.as_ref().to_path_buf();
+        let namespace = namespace.to_owned();
+        let oids = oids.clone();
+        move || sizes(&git_dir, &namespace, &oids)
+    })
+    .await?;
+
+    packetline::encode::text_to_write(b"size", &mut send).await?;
+    for oid in oids {
+        let size = sizes
+            .get(&oid)
+            .copied()
+            .flatten()
+            .map(|size| size.to_string())
+            .unwrap_or_default();
+        packetline::encode::text_to_write(format!("{} {}", oid, size).as_bytes(), &mut send)
+            .await?;
+    }
+    packetline::encode::flush_to_write(&mut send).await?;
+
+    // Drive the read stream to completion, cf. `legacy::advertise_refs`
+    let mut buf = [0; 1];
+    recv.read(&mut buf).await?;
+
+    Ok(status)
+}
+
+/// The sizes of those of `oids` which are reachable from the refs of
+/// `namespace`.
+///
+/// Objects only reachable from commits which are not on the boundary of the
+/// walk may be reported as missing, but objects outside of `namespace` are
+/// never reported as present.
+fn sizes(
+    git_dir: &Path,
+    namespace: &str,
+    oids: &[ObjectId],
+) -> io::Result<(BTreeMap<ObjectId, Option<u64>>, ExitStatus)> {
+    let input = oids
+        .iter()
+        .map(|oid| format!("{}\n", oid))
+        .collect::<String>();
+    let out = git(
+        git_dir,
+        &["cat-file", "--batch-check=%(objectname) %(objectsize)"],
+        input,
+    )?;
+    let mut sizes = out
+        .stdout
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_str(" ");
+            let oid = ObjectId::from_hex(parts.next()?).ok()?;
+            // `missing` if the object doesn't exist
+            let size = parts.next()?.to_str().ok()?.parse::<u64>().ok();
+            Some((oid, size))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let present = sizes
+        .iter()
+        .filter_map(|(oid, size)| size.map(|_| format!("{}\n", oid)))
+        .collect::<String>();
+    if present.is_empty() {
+        return Ok((sizes, out.status));
+    }
+    // Lists the objects which are _not_ reachable from `namespace`
+    let glob = format!("--glob=refs/namespaces/{}/*", namespace);
+    let out = git(
+        git_dir,
+        &["rev-list", "--objects", "--stdin", "--not", &glob],
+        present,
+    )?;
+    for line in out.stdout.lines() {
+        let oid = line.split_str(" ").next().unwrap_or(line);
+        if let Some(size) = ObjectId::from_hex(oid)
+            .ok()
+            .and_then(|oid| sizes.get_mut(&oid))
+        {
+            *size = None;
+        }
+    }
+
+    Ok((sizes, out.status))
+}
+
+/// Run `git` with `args` in `git_dir`, feeding it `input`, and fail if it
+/// doesn't exit successfully.
+fn git(git_dir: &Path, args: &[&str], input: String) -> io::Result<Output> {
+    let mut child = Command::new("git")
+        .current_dir(git_dir)
+        .env_clear()
+        .envs(std::env::vars().filter(|(key, _)| key == "PATH" || key.starts_with("GIT_TRACE")))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    // Write from another thread, so `git` doesn't block on a full stdout
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let out = child.wait_with_output()?;
+    writer
+        .join()
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "writer panicked"))??;
+    if !out.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("`git {}` failed: {}", args[0], out.status),
+        ));
+    }
+
+    Ok(out)
+}
+
+fn invalid_data<E>(inner: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Sync + Send>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, inner)
+}
@@ -262,7 +262,7 @@ impl Packed {
 
             Ok(meta) => {
                 let mtime = meta.modified()?;
-                Ok(self.mtime == mtime)
+                Ok(self.mtime != mtime)
             },
         }
     }
@@ -0,0 +1,23 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fuzzing entry points, see `fuzz/` in the repository root.
+//!
+//! The functions in this module must not panic for any input.
+
+use canonical::Cjson;
+
+use crate::git::load::SomeDoc;
+
+/// Decode `data` as the identity document blob of a person or project.
+///
+/// A document which decodes successfully must also have a canonical form.
+pub fn identity_doc(data: &[u8]) {
+    if let Ok(doc) = Cjson::<SomeDoc>::from_slice(data) {
+        Cjson(&doc.into_inner())
+            .canonical_form()
+            .expect("decoded document has a canonical form");
+    }
+}
@@ -25,7 +25,7 @@ pub mod iter;
 
 pub use generic::Verifying;
 
-mod load;
+pub(crate) mod load;
 pub mod sign;
 
 use iter::Iter;
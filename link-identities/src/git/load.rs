@@ -31,7 +31,7 @@ pub type ByOid<'a> = (&'a git2::Repository, git2::Oid);
 
 #[derive(Debug, serde::Serialize)]
 #[serde(untagged)]
-enum SomeDoc {
+pub(crate) enum SomeDoc {
     Person(Doc<PersonPayload, PersonDelegations>),
     Project(Doc<ProjectPayload, ProjectDelegations<Revision>>),
 }
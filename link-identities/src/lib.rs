@@ -22,6 +22,8 @@ extern crate radicle_git_ext as git_ext;
 extern crate radicle_std_ext as std_ext;
 
 pub mod delegation;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 pub mod generic;
 pub mod git;
 pub mod payload;
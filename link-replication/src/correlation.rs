@@ -0,0 +1,52 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{fmt, num::ParseIntError, str::FromStr};
+
+/// The name of the protocol parameter carrying a [`CorrelationId`], if it is
+/// sent to the remote end.
+pub const PARAM: &str = "correlation-id";
+
+/// Identifies a single [`crate::pull`] or [`crate::clone`].
+///
+/// The id is recorded as the `correlation_id` field of the tracing span of
+/// the replication run, returned in its [`crate::Success`] or
+/// [`crate::error::Replicate`] error, and optionally sent to the remote end
+/// (see [`crate::io::Network::with_correlation_id`]). This allows to join the
+/// logs of all peers involved in a fetch.
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CorrelationId(u64);
+
+impl CorrelationId {
+    pub fn new() -> Self {
+        Self(rand::random())
+    }
+}
+
+impl Default for CorrelationId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for CorrelationId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Self)
+    }
+}
@@ -3,15 +3,156 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::fmt::Debug;
+use std::{fmt::Debug, io};
 
-use bstr::BString;
+use bstr::{BStr, BString, ByteSlice as _};
 use link_crypto::PeerId;
-use link_git::protocol::ObjectId;
+use link_git::{object, protocol::ObjectId};
 use thiserror::Error;
 
+use crate::{refs, CorrelationId};
+
 pub type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
 
+/// The error returned by [`crate::pull`] and [`crate::clone`].
+#[derive(Debug, Error)]
+#[error("replication {id} failed")]
+pub struct Replicate {
+    /// The [`CorrelationId`] of the failed replication run.
+    pub id: CorrelationId,
+    #[source]
+    pub failure: Failure,
+}
+
+impl Replicate {
+    /// See [`Failure::code`].
+    pub fn code(&self) -> &'static str {
+        self.failure.code()
+    }
+
+    /// See [`Failure::is_retryable`].
+    pub fn is_retryable(&self) -> bool {
+        self.failure.is_retryable()
+    }
+}
+
+/// Why a replication run failed.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Failure {
+    #[error("cannot replicate from self")]
+    SelfReplication,
+
+    #[error("missing `rad/id`")]
+    MissingRadId,
+
+    #[error("remote did not advertise verification refs")]
+    NoVerificationRefs,
+
+    #[error("network error")]
+    Net(#[source] Error),
+
+    #[error("remote advertised an invalid ref layout")]
+    Layout(#[source] Layout),
+
+    #[error("identity verification failed")]
+    Verification(#[source] Error),
+
+    #[error("failed to load signed refs")]
+    Sigrefs(#[source] Error),
+
+    #[error("remote sent inconsistent data")]
+    Integrity(#[source] Error),
+
+    #[error("validation found {} inconsistencies", .0.len())]
+    Validation(Vec<Validation>),
+
+    #[error("storage error")]
+    Storage(#[source] Error),
+}
+
+impl Failure {
+    /// A stable, machine-readable identifier of the kind of failure.
+    ///
+    /// Unlike the [`std::fmt::Display`] output, codes are not going to change
+    /// between versions.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::SelfReplication => "self-replication",
+            Self::MissingRadId => "missing-rad-id",
+            Self::NoVerificationRefs => "no-verification-refs",
+            Self::Net(_) => "net",
+            Self::Layout(_) => "layout",
+            Self::Verification(_) => "verification",
+            Self::Sigrefs(_) => "sigrefs",
+            Self::Integrity(_) => "integrity",
+            Self::Validation(_) => "validation",
+            Self::Storage(_) => "storage",
+        }
+    }
+
+    /// Whether the failure may be transient, ie. retrying the same replication
+    /// later may succeed.
+    ///
+    /// Only network failures are considered transient. All other failures are
+    /// either due to local state, or due to the data the remote peer sent,
+    /// which is not going to change by asking again.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Net(_))
+    }
+
+    /// Classify an error returned by [`crate::Net::run_fetch`].
+    ///
+    /// [`io::Error`]s of kind [`io::ErrorKind::InvalidData`] mean that the
+    /// remote peer sent a corrupt packfile, or one lacking the wanted objects,
+    /// and are [`Failure::Integrity`] errors. All others are
+    /// [`Failure::Net`] errors.
+    pub(crate) fn fetch<E: Into<Error>>(e: E) -> Self {
+        let e = e.into();
+        let invalid = e
+            .downcast_ref::<io::Error>()
+            .map(|e| e.kind() == io::ErrorKind::InvalidData)
+            .unwrap_or(false);
+        if invalid {
+            Self::Integrity(e)
+        } else {
+            Self::Net(e)
+        }
+    }
+
+    pub(crate) fn verification<E: Into<Error>>(e: E) -> Self {
+        Self::Verification(e.into())
+    }
+
+    pub(crate) fn sigrefs<E: Into<Error>>(e: E) -> Self {
+        Self::Sigrefs(e.into())
+    }
+
+    pub(crate) fn storage<E: Into<Error>>(e: E) -> Self {
+        Self::Storage(e.into())
+    }
+}
+
+impl From<Layout> for Failure {
+    fn from(e: Layout) -> Self {
+        Self::Layout(e)
+    }
+}
+
+impl<V, R> From<Prepare<V, R>> for Failure
+where
+    V: std::error::Error + Send + Sync + 'static,
+    R: std::error::Error + Send + Sync + 'static,
+{
+    fn from(e: Prepare<V, R>) -> Self {
+        match e {
+            Prepare::Verification(e) => Self::verification(e),
+            Prepare::FindRef { .. } => Self::storage(e),
+            Prepare::ObjectType(_) => Self::Integrity(e.into()),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum Layout {
@@ -46,6 +187,54 @@ where
         #[source]
         source: R,
     },
+
+    #[error(transparent)]
+    ObjectType(#[from] ObjectType),
+}
+
+#[derive(Debug, Error)]
+pub enum ObjectType {
+    #[error("{name} must point to a {expected}, but {oid} is a {actual:?}")]
+    Mismatch {
+        name: BString,
+        oid: ObjectId,
+        expected: &'static str,
+        actual: object::Kind,
+    },
+
+    #[error("failed to look up {oid} of {name}")]
+    Lookup {
+        name: BString,
+        oid: ObjectId,
+        #[source]
+        source: Error,
+    },
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum InvalidId<E>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    #[error("empty identity")]
+    Empty,
+
+    #[error("identity exceeds {max} bytes: {len}")]
+    TooLong { len: usize, max: usize },
+
+    #[error("identity contains invalid byte {byte:#04x} at {pos}")]
+    Alphabet { byte: u8, pos: usize },
+
+    #[error("malformed identity {id}")]
+    Decode {
+        id: String,
+        #[source]
+        source: E,
+    },
+
+    #[error("identity {given} is not in canonical encoding, expected {canonical}")]
+    NotCanonical { given: String, canonical: String },
 }
 
 #[derive(Debug, Error, Eq, PartialEq, PartialOrd, Ord)]
@@ -76,6 +265,9 @@ pub enum Validation {
     #[error("strange refname or category: {0}")]
     Strange(BString),
 
+    #[error("{name} must be signed by {remote}, but is not")]
+    Unsigned { name: BString, remote: PeerId },
+
     #[error("strange refname or prunable ref: {0}")]
     StrangeOrPrunable(BString),
 
@@ -83,6 +275,74 @@ pub enum Validation {
     NoData(PeerId),
 }
 
+impl Validation {
+    /// A stable, machine-readable identifier of the kind of inconsistency.
+    ///
+    /// Unlike the [`std::fmt::Display`] output, codes are not going to change
+    /// between versions, and can be used to aggregate or filter failures.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Unrecognised(_) => "unrecognised",
+            Self::Unexpected(_) => "unexpected",
+            Self::Missing { .. } => "missing",
+            Self::MissingRadId(_) => "missing-rad-id",
+            Self::MissingSigRefs(_) => "missing-sigrefs",
+            Self::MismatchedTips { .. } => "mismatched-tips",
+            Self::Strange(_) => "strange",
+            Self::Unsigned { .. } => "unsigned",
+            Self::StrangeOrPrunable(_) => "strange-or-prunable",
+            Self::NoData(_) => "no-data",
+        }
+    }
+
+    /// The peer whose refs are inconsistent, if known.
+    ///
+    /// For failures which only carry a refname, the peer is determined from
+    /// the refname if it is a remote tracking ref.
+    pub fn peer(&self) -> Option<PeerId> {
+        match self {
+            Self::Missing { remote, .. } | Self::Unsigned { remote, .. } => Some(*remote),
+            Self::MissingRadId(peer) | Self::MissingSigRefs(peer) | Self::NoData(peer) => {
+                Some(*peer)
+            },
+            Self::Unrecognised(name)
+            | Self::Unexpected(name)
+            | Self::MismatchedTips { name, .. }
+            | Self::Strange(name)
+            | Self::StrangeOrPrunable(name) => remote_of(name),
+        }
+    }
+
+    /// The offending ref, if any.
+    pub fn refname(&self) -> Option<&BStr> {
+        match self {
+            Self::Missing { refname: name, .. }
+            | Self::Unsigned { name, .. }
+            | Self::Unrecognised(name)
+            | Self::Unexpected(name)
+            | Self::MismatchedTips { name, .. }
+            | Self::Strange(name)
+            | Self::StrangeOrPrunable(name) => Some(name.as_bstr()),
+            Self::MissingRadId(_) | Self::MissingSigRefs(_) | Self::NoData(_) => None,
+        }
+    }
+
+    /// The expected (ie. signed) and actual tip, if the failure is about a
+    /// mismatch between the two.
+    pub fn tips(&self) -> Option<(ObjectId, ObjectId)> {
+        match self {
+            Self::MismatchedTips { signed, actual, .. } => Some((*signed, *actual)),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn remote_of(name: &BStr) -> Option<PeerId> {
+    let rest = name.strip_prefix(refs::Prefix::Remotes.as_bytes())?;
+    let id = rest.split(refs::is_separator).next()?;
+    std::str::from_utf8(id).ok()?.parse().ok()
+}
+
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum IdentityHistory<I: Debug + Send + Sync + 'static> {
@@ -97,6 +357,18 @@ pub enum IdentityHistory<I: Debug + Send + Sync + 'static> {
 #[error("`rad/id` is behind and requires confirmation")]
 pub struct ConfirmationRequired;
 
+/// The signed refs of a peer fetched in a replication run, which were
+/// withheld because they do not succeed the stored ones.
+///
+/// See [`crate::Rollback::Reject`].
+#[derive(Clone, Debug, Error)]
+#[error("signed refs of {remote} at {fetched} do not succeed the stored ones at {stored}")]
+pub struct SigrefsRollback {
+    pub remote: PeerId,
+    pub stored: ObjectId,
+    pub fetched: ObjectId,
+}
+
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum OwnRad<T: Debug + Send + Sync + 'static> {
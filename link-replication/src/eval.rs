@@ -4,6 +4,6 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 mod pull;
-pub(crate) use pull::pull;
+pub(crate) use pull::{pull, pull_many};
 
 mod rad;
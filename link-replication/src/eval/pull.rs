@@ -3,51 +3,84 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{collections::BTreeSet, fmt::Debug, marker::PhantomData};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    iter,
+    marker::PhantomData,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use bstr::BStr;
+use either::Either;
+use futures_util::future;
 
 use super::rad;
 use crate::{
     error,
     fetch,
     ids,
+    oid,
     peek,
+    progress,
+    refs,
+    report::Timings,
     sigrefs,
     state::FetchState,
-    validate,
-    Error,
+    track,
+    validation::{validate, validate_peers},
+    Applied,
+    AsyncRefdb,
+    AsyncSignedRefs,
+    AsyncTracking,
+    CorrelationId,
+    DelegationChanges,
     FetchLimit,
+    FetchSpec,
     Identities,
     LocalIdentity,
     LocalPeer,
     Net,
+    ObjectId,
+    Odb,
     PeerId,
     Refdb,
+    Rollback,
     SignedRefs,
     SkippedFetch,
     Success,
-    Tracking,
+    Validate,
+    ValidationPolicy,
 };
 
-pub(crate) fn pull<U, C>(
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn pull<U, C>(
     state: &mut FetchState<U>,
     cx: &mut C,
+    id: CorrelationId,
     limit: FetchLimit,
+    spec: FetchSpec,
     anchor: C::VerifiedIdentity,
     remote_id: PeerId,
     whoami: Option<LocalIdentity>,
-) -> Result<Success<<C as Identities>::Urn>, Error>
+    validation: Validate,
+    policy: ValidationPolicy,
+    rollback: Rollback,
+) -> Result<Success<<C as Identities>::Urn>, error::Failure>
 where
     U: ids::Urn + Clone + Debug + Ord,
     C: Identities<Urn = U>
         + LocalPeer
         + Net
-        + Refdb
-        + SignedRefs<Oid = <C as Identities>::Oid>
-        + Tracking<Urn = U>,
+        + Odb
+        + AsyncRefdb
+        + AsyncSignedRefs<Oid = <C as Identities>::Oid>
+        + AsyncTracking<Urn = U>,
     <C as Identities>::Oid: Debug + PartialEq + Send + Sync + 'static,
 {
-    use either::Either::*;
-
+    let mut timings = Timings::default();
+    let started = Instant::now();
     info!("fetching verification refs");
     let (
         peek::ForFetch {
@@ -56,50 +89,414 @@ where
             delegates,
             mut tracked,
             limit: _,
+            rollback: _,
         },
         skip,
     ) = {
-        let spec = peek::for_fetch(&state.as_shim(cx), limit.peek, &anchor, remote_id)?;
+        let spec = peek::for_fetch(&state.as_shim(cx), limit.peek, &anchor, remote_id, rollback)
+            .map_err(error::Failure::storage)?;
         debug!(?spec);
-        state.step(cx, spec)?
+        state.step(cx, spec).await?
     };
 
     if matches!(skip, Some(SkippedFetch::NoMatchingRefs)) {
-        return Ok(Success {
+        return Ok(Success::nothing(id, started.elapsed()));
+    }
+
+    let setup = setup(
+        state,
+        cx,
+        anchor,
+        &local_id,
+        delegates,
+        skip.is_some(),
+        whoami,
+        rollback,
+    )
+    .await?;
+    tracked.extend(setup.newly_tracked.iter().filter_map(|x| x.as_ref().left()));
+    timings.peek = started.elapsed();
+
+    // The trackings stored by `setup` are only kept if the fetched refs are
+    // applied, too.
+    let res: Result<_, error::Failure> = async {
+        info!("loading combined sigrefs");
+        let cutoff = spec.tracking_cutoff();
+        let select = || sigrefs::Select {
+            must: &setup.delegates,
+            may: &tracked,
+            cutoff,
+        };
+        let signed_refs =
+            sigrefs::combined(&state.as_shim(cx), select()).map_err(error::Failure::sigrefs)?;
+        let known_peers = signed_refs.peers();
+        let step = fetch::Fetch {
+            local_id,
+            remote_id,
+            signed_refs,
+            limit: limit.data,
+            spec: spec.clone(),
+        };
+        info!(?step, "fetching data");
+        let started = Instant::now();
+        match step.spec.concurrent_fetches() {
+            None => {
+                state.step(cx, step).await?;
+            },
+            Some(concurrency) => {
+                state
+                    .step_concurrently(cx, step.per_peer(), concurrency)
+                    .await?;
+            },
+        }
+        timings.fetch = started.elapsed();
+
+        info!("post-validation");
+        let started = Instant::now();
+        let (signed_refs, warnings) =
+            validate_fetched(state, cx, &spec, select(), known_peers, validation, policy)?;
+        timings.validate = started.elapsed();
+        Net::progress(
+            cx,
+            progress::Event::Validated {
+                warnings: warnings.len(),
+            },
+        );
+
+        let started = Instant::now();
+        let applied = apply(state, cx).await?;
+        timings.apply = started.elapsed();
+        Ok((signed_refs, warnings, applied))
+    }
+    .await;
+    let (signed_refs, warnings, applied) = match res {
+        Ok(res) => res,
+        Err(e) => {
+            untrack(cx, &setup).await;
+            return Err(e);
+        },
+    };
+    info!("updating signed refs");
+    AsyncSignedRefs::update(cx)
+        .await
+        .map_err(error::Failure::storage)?;
+    Net::progress(
+        cx,
+        progress::Event::Applied {
+            updated: applied.updated.len(),
+            rejected: applied.rejected.len(),
+        },
+    );
+
+    Ok(setup.into_success(id, applied, warnings, &signed_refs, timings))
+}
+
+/// Like [`pull`], but from all of `remotes` at once.
+///
+/// The verification refs of all remotes are fetched concurrently, and the
+/// refs of each peer are then fetched from the remote which has the most
+/// recent signed refs of that peer, see [`FetchState::step_many`]. The
+/// fetched refs are validated and applied in a single pass. Phase events are
+/// reported to the [`Net`] of every remote.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn pull_many<U, C, N>(
+    state: &mut FetchState<U>,
+    cx: &mut C,
+    id: CorrelationId,
+    limit: FetchLimit,
+    spec: FetchSpec,
+    anchor: C::VerifiedIdentity,
+    remotes: &[(PeerId, N)],
+    whoami: Option<LocalIdentity>,
+    validation: Validate,
+    policy: ValidationPolicy,
+    rollback: Rollback,
+) -> Result<Success<<C as Identities>::Urn>, error::Failure>
+where
+    U: ids::Urn + Clone + Debug + Ord,
+    C: Identities<Urn = U>
+        + LocalPeer
+        + Odb
+        + AsyncRefdb
+        + AsyncSignedRefs<Oid = <C as Identities>::Oid>
+        + AsyncTracking<Urn = U>,
+    <C as Identities>::Oid: Debug + PartialEq + Send + Sync + 'static,
+    N: Net,
+{
+    let mut timings = Timings::default();
+    let started = Instant::now();
+    info!("fetching verification refs");
+    let steps = remotes
+        .iter()
+        .map(|(remote_id, net)| {
+            let spec = peek::for_fetch(
+                &state.as_shim(cx),
+                limit.peek,
+                &anchor,
+                *remote_id,
+                rollback,
+            )?;
+            debug!(?spec);
+            Ok::<_, error::Error>((net, spec))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(error::Failure::storage)?;
+    let steps = state.step_many(cx, steps, remotes.len()).await?;
+    if steps
+        .iter()
+        .all(|(_, skip)| matches!(skip, Some(SkippedFetch::NoMatchingRefs)))
+    {
+        return Ok(Success::nothing(id, started.elapsed()));
+    }
+
+    let skip = steps.iter().all(|(_, skip)| skip.is_some());
+    let (local_id, delegates, mut tracked) = match steps.into_iter().next() {
+        Some((peek, _)) => (peek.local_id, peek.delegates, peek.tracked),
+        None => return Ok(Success::nothing(id, started.elapsed())),
+    };
+    let setup = setup(
+        state, cx, anchor, &local_id, delegates, skip, whoami, rollback,
+    )
+    .await?;
+    tracked.extend(setup.newly_tracked.iter().filter_map(|x| x.as_ref().left()));
+    timings.peek = started.elapsed();
+
+    // The trackings stored by `setup` are only kept if the fetched refs are
+    // applied, too.
+    let res: Result<_, error::Failure> = async {
+        info!("loading combined sigrefs");
+        let cutoff = spec.tracking_cutoff();
+        let select = || sigrefs::Select {
+            must: &setup.delegates,
+            may: &tracked,
+            cutoff,
+        };
+        let signed_refs =
+            sigrefs::combined(&state.as_shim(cx), select()).map_err(error::Failure::sigrefs)?;
+        let known_peers = signed_refs.peers();
+        let missing = missing_tips(cx, &local_id, &signed_refs);
+        let available = probe(remotes, &missing).await;
+        let concurrency = spec.concurrent_fetches();
+        let steps = split(signed_refs, &*state, &missing, &available)
+            .into_iter()
+            .zip(remotes)
+            .flat_map(|(signed_refs, (remote_id, net))| {
+                let step = fetch::Fetch {
+                    local_id,
+                    remote_id: *remote_id,
+                    signed_refs,
+                    limit: limit.data,
+                    spec: spec.clone(),
+                };
+                info!(?step, "fetching data");
+                let steps = match concurrency {
+                    None => vec![step],
+                    Some(_) => step.per_peer(),
+                };
+                steps.into_iter().map(move |step| (net, step))
+            })
+            .collect::<Vec<_>>();
+        let started = Instant::now();
+        let concurrency = concurrency.unwrap_or_else(|| remotes.len());
+        state.step_many(cx, steps, concurrency).await?;
+        timings.fetch = started.elapsed();
+
+        info!("post-validation");
+        let started = Instant::now();
+        let (signed_refs, warnings) =
+            validate_fetched(state, cx, &spec, select(), known_peers, validation, policy)?;
+        timings.validate = started.elapsed();
+        for (_, net) in remotes {
+            Net::progress(
+                net,
+                progress::Event::Validated {
+                    warnings: warnings.len(),
+                },
+            );
+        }
+
+        let started = Instant::now();
+        let applied = apply(state, cx).await?;
+        timings.apply = started.elapsed();
+        Ok((signed_refs, warnings, applied))
+    }
+    .await;
+    let (signed_refs, warnings, applied) = match res {
+        Ok(res) => res,
+        Err(e) => {
+            untrack(cx, &setup).await;
+            return Err(e);
+        },
+    };
+    info!("updating signed refs");
+    AsyncSignedRefs::update(cx)
+        .await
+        .map_err(error::Failure::storage)?;
+    for (_, net) in remotes {
+        Net::progress(
+            net,
+            progress::Event::Applied {
+                updated: applied.updated.len(),
+                rejected: applied.rejected.len(),
+            },
+        );
+    }
+
+    Ok(setup.into_success(id, applied, warnings, &signed_refs, timings))
+}
+
+/// The outcome of setting up the local `rad/` hierarchy and trackings.
+struct Setup<U> {
+    /// The delegates, excluding the local peer.
+    delegates: BTreeSet<PeerId>,
+    requires_confirmation: bool,
+    delegation_changes: Option<DelegationChanges<U>>,
+    newly_tracked: Vec<Either<PeerId, U>>,
+    tracked_delegates: Vec<Either<PeerId, U>>,
+    rollbacks: Vec<error::SigrefsRollback>,
+}
+
+impl<U> Setup<U> {
+    fn into_success<Oid>(
+        self,
+        id: CorrelationId,
+        applied: Applied<'static>,
+        warnings: Vec<error::Validation>,
+        signed_refs: &sigrefs::Combined<Oid>,
+        timings: Timings,
+    ) -> Success<U> {
+        let attested = signed_refs
+            .refs
+            .iter()
+            .filter_map(|(peer, refs)| {
+                refs.timestamp
+                    .and_then(|secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+                    .map(|time| (*peer, time))
+            })
+            .collect();
+
+        Success {
+            correlation_id: id,
+            applied,
+            tracked: self.newly_tracked,
+            tracked_delegates: self.tracked_delegates,
+            delegation_changes: self.delegation_changes,
+            requires_confirmation: self.requires_confirmation,
+            validation: warnings,
+            rollbacks: self.rollbacks,
+            attested,
+            bytes_received: 0,
+            timings,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<U> Success<U> {
+    /// The outcome of a run which found nothing to fetch.
+    fn nothing(id: CorrelationId, peek: Duration) -> Self {
+        Self {
+            correlation_id: id,
             applied: Default::default(),
             tracked: vec![],
+            tracked_delegates: vec![],
+            delegation_changes: None,
             requires_confirmation: false,
             validation: vec![],
+            rollbacks: vec![],
+            attested: Default::default(),
+            bytes_received: 0,
+            timings: Timings {
+                peek,
+                ..Timings::default()
+            },
             _marker: PhantomData,
-        });
+        }
     }
+}
+
+/// Adopt the newest identity of the `delegates` if it is a fast-forward of
+/// `anchor`, and store the trackings resulting from the verification refs
+/// fetched so far.
+///
+/// If `skipped` is true, nothing was fetched, and the identity is left alone.
+#[allow(clippy::too_many_arguments)]
+async fn setup<U, C>(
+    state: &mut FetchState<U>,
+    cx: &mut C,
+    anchor: C::VerifiedIdentity,
+    local_id: &PeerId,
+    delegates: BTreeSet<PeerId>,
+    skipped: bool,
+    whoami: Option<LocalIdentity>,
+    rollback: Rollback,
+) -> Result<Setup<U>, error::Failure>
+where
+    U: ids::Urn + Clone + Debug + Ord,
+    C: Identities<Urn = U>
+        + LocalPeer
+        + Refdb
+        + SignedRefs<Oid = <C as Identities>::Oid>
+        + AsyncTracking<Urn = U>,
+    <C as Identities>::Oid: PartialEq,
+{
+    use either::Either::*;
+
+    let rollbacks = match rollback {
+        Rollback::Reject => guard_rollback(state, cx)?,
+        Rollback::Allow => vec![],
+    };
 
     let delegates: BTreeSet<PeerId> = delegates
         .into_iter()
-        .filter(move |id| id != &local_id)
+        .filter(move |id| id != local_id)
         .collect();
 
+    // Delegation changes are only meaningful relative to the identity we had
+    // locally, not the one we are cloning from
+    let anchor_is_local = Refdb::refname_to_id(&*cx, Cow::from(refs::RadId))
+        .map_err(error::Failure::storage)?
+        .map(|tip| tip.as_ref() == anchor.content_id().as_ref())
+        .unwrap_or(false);
+
+    let mut delegation_changes = None;
+    let mut delegation_rels = BTreeSet::new();
     let requires_confirmation = {
-        if skip.is_some() {
+        if skipped {
             false
         } else {
             info!("setting up local rad/ hierarchy");
             let shim = state.as_shim(cx);
-            match ids::newest(&shim, &delegates)? {
+            match ids::newest(&shim, &delegates).map_err(error::Failure::verification)? {
                 None => false,
-                Some((their_id, theirs)) => match rad::newer(&shim, Some(anchor), theirs)? {
-                    Err(error::ConfirmationRequired) => true,
-                    Ok(newest) => {
-                        let rad::Rad { track, up } = match newest {
-                            Left(ours) => rad::setup(&shim, None, &ours, whoami)?,
-                            Right(theirs) => rad::setup(&shim, Some(their_id), &theirs, whoami)?,
-                        };
-
-                        state.track_all(track);
-                        state.update_all(up);
-
-                        false
-                    },
+                Some((their_id, theirs)) => {
+                    let changes = anchor_is_local
+                        .then(|| DelegationChanges::between(&anchor, &theirs))
+                        .flatten();
+                    match rad::newer(&shim, Some(anchor), theirs)
+                        .map_err(error::Failure::verification)?
+                    {
+                        Err(error::ConfirmationRequired) => true,
+                        Ok(newest) => {
+                            let rad::Rad { track, up } = match newest {
+                                Left(ours) => rad::setup(&shim, None, &ours, whoami),
+                                Right(theirs) => {
+                                    delegation_changes = changes;
+                                    rad::setup(&shim, Some(their_id), &theirs, whoami)
+                                },
+                            }
+                            .map_err(error::Failure::verification)?;
+
+                            delegation_rels.extend(track.iter().filter_map(|rel| match rel {
+                                track::Rel::Delegation(x) => Some(x.clone()),
+                                track::Rel::SelfRef(_) => None,
+                            }));
+                            state.track_all(track);
+                            state.update_all(up);
+
+                            false
+                        },
+                    }
                 },
             }
         }
@@ -111,56 +508,330 @@ where
     //
     // XXX: Can we statically prevent new trackings to be added after here?
     info!("updating trackings");
-    let newly_tracked = Tracking::track(cx, state.drain_trackings())?
+    let newly_tracked = AsyncTracking::track(cx, state.drain_trackings().collect())
+        .await
+        .map_err(error::Failure::storage)?;
+    let tracked_delegates = newly_tracked
+        .iter()
+        .filter(|x| delegation_rels.contains(*x))
+        .cloned()
+        .collect();
+
+    Ok(Setup {
+        delegates,
+        requires_confirmation,
+        delegation_changes,
+        newly_tracked,
+        tracked_delegates,
+        rollbacks,
+    })
+}
+
+/// The signed tips of each peer in `signed_refs` which are not in the local
+/// odb.
+fn missing_tips<C, Oid>(
+    cx: &C,
+    local_id: &PeerId,
+    signed_refs: &sigrefs::Combined<Oid>,
+) -> BTreeMap<PeerId, BTreeSet<ObjectId>>
+where
+    C: Odb,
+    Oid: AsRef<oid>,
+{
+    signed_refs
+        .refs
+        .iter()
+        .filter(|(peer, _)| *peer != local_id)
+        .filter_map(|(peer, refs)| {
+            let missing = refs
+                .refs
+                .values()
+                .filter(|tip| !cx.contains(tip))
+                .map(|tip| tip.as_ref().to_owned())
+                .collect::<BTreeSet<_>>();
+            (!missing.is_empty()).then(|| (*peer, missing))
+        })
+        .collect()
+}
+
+/// Ask each of `remotes` which of the `missing` tips it has.
+///
+/// `None` means it is not known which tips the remote has, either because
+/// there is only one remote to fetch from anyway, or because it couldn't be
+/// asked.
+async fn probe<N>(
+    remotes: &[(PeerId, N)],
+    missing: &BTreeMap<PeerId, BTreeSet<ObjectId>>,
+) -> Vec<Option<BTreeSet<ObjectId>>>
+where
+    N: Net,
+{
+    if remotes.len() < 2 || missing.is_empty() {
+        return vec![None; remotes.len()];
+    }
+
+    let oids = missing
+        .values()
+        .flatten()
+        .copied()
+        .collect::<BTreeSet<_>>()
         .into_iter()
         .collect::<Vec<_>>();
-    tracked.extend(newly_tracked.iter().filter_map(|x| x.as_ref().left()));
+    future::join_all(remotes.iter().map(|(remote_id, net)| {
+        let oids = oids.clone();
+        async move {
+            net.has_objects(oids)
+                .await
+                .map_err(|e| warn!(remote_id = %remote_id, err = %e, "failed to query objects"))
+                .ok()
+        }
+    }))
+    .await
+}
 
-    info!("loading combined sigrefs");
-    let signed_refs = sigrefs::combined(
-        &state.as_shim(cx),
-        sigrefs::Select {
-            must: &delegates,
-            may: &tracked,
-            cutoff: 2,
-        },
-    )?;
-    let step = fetch::Fetch {
-        local_id,
-        remote_id,
-        signed_refs,
-        limit: limit.data,
+/// Split `signed_refs` into one per remote fetched from, according to which
+/// remote the verification refs of each peer were taken from.
+///
+/// If that remote is known to lack some of the `missing` tips of a peer, the
+/// peer is assigned to the first remote which is not, as reported in
+/// `available`. Peers for which no verification refs were fetched are
+/// assigned to the first remote.
+fn split<U, Oid>(
+    signed_refs: sigrefs::Combined<Oid>,
+    state: &FetchState<U>,
+    missing: &BTreeMap<PeerId, BTreeSet<ObjectId>>,
+    available: &[Option<BTreeSet<ObjectId>>],
+) -> Vec<sigrefs::Combined<Oid>> {
+    let remotes = available.len();
+    let mut parts = iter::repeat_with(sigrefs::Combined::default)
+        .take(remotes)
+        .collect::<Vec<_>>();
+    let has_tips = |i: usize, peer: &PeerId| match (&available[i], missing.get(peer)) {
+        (Some(have), Some(want)) => want.is_subset(have),
+        _ => true,
     };
-    info!(?step, "fetching data");
-    state.step(cx, step)?;
+    let owner = |peer: &PeerId| {
+        let preferred = state.owner(peer).filter(|i| *i < remotes).unwrap_or(0);
+        if has_tips(preferred, peer) {
+            preferred
+        } else {
+            (0..remotes)
+                .find(|i| has_tips(*i, peer))
+                .unwrap_or(preferred)
+        }
+    };
+    for (peer, refs) in signed_refs.refs {
+        parts[owner(&peer)].refs.insert(peer, refs);
+    }
+    for peer in signed_refs.remotes {
+        parts[owner(&peer)].remotes.insert(peer);
+    }
+    parts
+}
+
+/// Reload the signed refs selected by `select`, and validate the fetched refs
+/// against them and `spec`, handling inconsistencies according to `policy`.
+///
+/// `known_peers` are the peers of the signed refs before the fetch.
+fn validate_fetched<U, C>(
+    state: &mut FetchState<U>,
+    cx: &mut C,
+    spec: &FetchSpec,
+    select: sigrefs::Select<'_>,
+    known_peers: BTreeSet<PeerId>,
+    validation: Validate,
+    policy: ValidationPolicy,
+) -> Result<
+    (
+        sigrefs::Combined<<C as Identities>::Oid>,
+        Vec<error::Validation>,
+    ),
+    error::Failure,
+>
+where
+    U: ids::Urn + Ord,
+    C: Identities<Urn = U> + LocalPeer + Odb + Refdb + SignedRefs<Oid = <C as Identities>::Oid>,
+    <C as Identities>::Oid: Debug,
+{
     // TODO: is this necessary?
     info!("reloading combined sigrefs");
-    let signed_refs = sigrefs::combined(
-        &state.as_shim(cx),
-        sigrefs::Select {
-            must: &delegates,
-            may: &tracked,
-            cutoff: 2,
+    let signed_refs =
+        sigrefs::combined(&state.as_shim(cx), select).map_err(error::Failure::sigrefs)?;
+
+    let scope = match validation {
+        Validate::Full => None,
+        Validate::Incremental => {
+            let mut peers = state.updated_remotes();
+            peers.extend(signed_refs.peers().difference(&known_peers));
+            Some(peers)
         },
-    )?;
+    };
+    let run_validation = |state: &mut FetchState<U>, cx: &mut C| match &scope {
+        None => validate(&state.as_shim(cx), spec, &signed_refs),
+        Some(peers) => validate_peers(&state.as_shim(cx), spec, &signed_refs, peers),
+    };
+    let mut warnings = run_validation(state, cx).map_err(error::Failure::storage)?;
+    if !warnings.is_empty() {
+        match policy {
+            ValidationPolicy::Warn => {},
+            ValidationPolicy::Reject => return Err(error::Failure::Validation(warnings)),
+            ValidationPolicy::Repair => {
+                if repair(state, cx, &warnings)? {
+                    warnings = run_validation(state, cx).map_err(error::Failure::storage)?;
+                }
+            },
+        }
+    }
 
-    info!("post-validation");
-    let warnings = validate(&state.as_shim(cx), &signed_refs)?;
+    Ok((signed_refs, warnings))
+}
 
+/// Apply the pending updates.
+///
+/// The signed refs of the local peer are updated separately, once the run
+/// can no longer be rolled back, see [`untrack`].
+async fn apply<U, C>(
+    state: &mut FetchState<U>,
+    cx: &mut C,
+) -> Result<Applied<'static>, error::Failure>
+where
+    U: ids::Urn + Ord,
+    C: AsyncRefdb,
+{
     info!("updating tips");
-    let applied = Refdb::update(cx, state.drain_updates())?;
+    let updates = state.drain_updates().collect();
+    let applied = AsyncRefdb::update(cx, updates)
+        .await
+        .map_err(error::Failure::storage)?;
     for u in &applied.updated {
         debug!("applied {:?}", u);
     }
 
-    info!("updating signed refs");
-    SignedRefs::update(cx)?;
+    Ok(applied)
+}
 
-    Ok(Success {
-        applied,
-        tracked: newly_tracked,
-        requires_confirmation,
-        validation: warnings,
-        _marker: PhantomData,
-    })
+/// Remove the trackings of the peers newly tracked by [`setup`].
+///
+/// The trackings are stored before fetching, so that the data transferred
+/// doesn't need to be discarded if storing them fails. If the run fails
+/// before the fetched refs are applied, they are removed again, as the peers
+/// would otherwise be tracked without any of their refs being stored.
+/// Failing to remove them is only logged, so the original error is reported.
+async fn untrack<U, C>(cx: &mut C, setup: &Setup<U>)
+where
+    C: AsyncTracking<Urn = U>,
+{
+    let peers = setup
+        .newly_tracked
+        .iter()
+        .filter_map(|x| x.as_ref().left())
+        .copied()
+        .collect::<Vec<_>>();
+    if peers.is_empty() {
+        return;
+    }
+
+    info!("rolling back trackings");
+    if let Err(e) = AsyncTracking::untrack(cx, peers).await {
+        warn!(err = %e, "failed to roll back trackings");
+    }
+}
+
+/// Withhold or correct the pending updates of the refs named in `warnings`.
+///
+/// Returns whether any pending update was changed.
+fn repair<U, C>(
+    state: &mut FetchState<U>,
+    cx: &C,
+    warnings: &[error::Validation],
+) -> Result<bool, error::Failure>
+where
+    U: ids::Urn + Ord,
+    C: Odb + Refdb,
+{
+    use error::Validation::*;
+
+    let mut repaired = false;
+    for warning in warnings {
+        match warning {
+            MismatchedTips { signed, name, .. } => {
+                let name = name.as_ref();
+                repaired |= if Odb::contains(cx, signed) && fast_forwards(cx, name, *signed)? {
+                    state.retarget_update(name, *signed)
+                } else {
+                    state.discard_update(name)
+                };
+            },
+            Unrecognised(name)
+            | Unexpected(name)
+            | Strange(name)
+            | Unsigned { name, .. }
+            | StrangeOrPrunable(name) => {
+                repaired |= state.discard_update(name.as_ref());
+            },
+            // Nothing to withhold
+            Missing { .. } | MissingRadId(_) | MissingSigRefs(_) | NoData(_) => {},
+        }
+    }
+
+    if repaired {
+        warn!("withheld or corrected updates of inconsistent refs");
+    }
+    Ok(repaired)
+}
+
+/// Whether pointing the stored ref `name` at `target` creates it, or is a
+/// fast-forward.
+///
+/// The signed tip may be older than the stored one, in which case
+/// retargeting the update would make it fail.
+fn fast_forwards<C>(cx: &C, name: &BStr, target: ObjectId) -> Result<bool, error::Failure>
+where
+    C: Odb + Refdb,
+{
+    let stored = match Refdb::refname_to_id(cx, name).map_err(error::Failure::storage)? {
+        None => return Ok(true),
+        Some(stored) => stored.as_ref().to_owned(),
+    };
+    if stored == target {
+        return Ok(true);
+    }
+    Odb::is_in_ancestry_path(cx, target, stored).map_err(error::Failure::storage)
+}
+
+/// Ensure the signed refs fetched in the peek phase succeed the ones we have
+/// stored already.
+///
+/// The verification refs of peers for which they don't are withheld, and the
+/// peers are returned.
+fn guard_rollback<U, C>(
+    state: &mut FetchState<U>,
+    cx: &C,
+) -> Result<Vec<error::SigrefsRollback>, error::Failure>
+where
+    U: ids::Urn + Ord,
+    C: Refdb + SignedRefs,
+{
+    let mut rollbacks = Vec::new();
+    for (remote, fetched) in state.sigref_tips() {
+        let name = refs::remote_tracking(remote, refs::Signed);
+        let stored = match Refdb::refname_to_id(cx, &name).map_err(error::Failure::storage)? {
+            None => continue,
+            Some(stored) => stored.as_ref().to_owned(),
+        };
+        if !SignedRefs::succeeds(cx, *fetched, stored).map_err(error::Failure::sigrefs)? {
+            rollbacks.push(error::SigrefsRollback {
+                remote: *remote,
+                stored,
+                fetched: *fetched,
+            });
+        }
+    }
+
+    for rollback in &rollbacks {
+        warn!(%rollback, "withholding verification refs");
+        state.withhold_sigrefs(&rollback.remote, rollback.stored);
+    }
+
+    Ok(rollbacks)
 }
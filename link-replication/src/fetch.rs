@@ -5,7 +5,7 @@
 
 use std::{
     borrow::Cow,
-    collections::{BTreeSet, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     iter,
 };
 
@@ -23,12 +23,155 @@ use crate::{
     FilteredRef,
     Identities,
     Negotiation,
+    Odb,
     Policy,
     Refdb,
     Update,
     WantsHaves,
 };
 
+/// The default of [`FetchSpec::tracking_cutoff`].
+pub const DEFAULT_TRACKING_CUTOFF: usize = 2;
+
+/// Which refs of tracked peers are fetched, besides the ones they signed.
+///
+/// By default, only `refs/heads`, `refs/notes`, `refs/tags`, and `refs/cobs`
+/// are fetched. Refs of other categories are only fetched if they are signed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FetchSpec {
+    categories: BTreeSet<BString>,
+    exclusions: BTreeSet<BString>,
+    tracking_cutoff: usize,
+    concurrent_fetches: Option<usize>,
+    fold_case: bool,
+}
+
+impl Default for FetchSpec {
+    fn default() -> Self {
+        Self {
+            categories: BTreeSet::new(),
+            exclusions: BTreeSet::new(),
+            tracking_cutoff: DEFAULT_TRACKING_CUTOFF,
+            concurrent_fetches: None,
+            fold_case: cfg!(any(target_os = "macos", target_os = "ios", windows)),
+        }
+    }
+}
+
+impl FetchSpec {
+    /// Fetch the refs of peers up to `cutoff` hops away in the tracking graph,
+    /// as published in the signed refs of the tracked peers.
+    ///
+    /// Larger values widen the fan-out of a fetch, which may be desirable for
+    /// seed nodes. The default is [`DEFAULT_TRACKING_CUTOFF`].
+    pub fn with_tracking_cutoff(self, cutoff: usize) -> Self {
+        Self {
+            tracking_cutoff: cutoff,
+            ..self
+        }
+    }
+
+    pub fn tracking_cutoff(&self) -> usize {
+        self.tracking_cutoff
+    }
+
+    /// Fetch the refs of each peer separately, running up to `limit` fetches
+    /// concurrently.
+    ///
+    /// By default, the refs of all peers are fetched at once, which requires
+    /// negotiating a single, possibly very large, set of wants. The
+    /// [`crate::FetchLimit`] is split evenly among the fetches, so they can't
+    /// receive more data than a single one could.
+    pub fn with_concurrent_fetches(self, limit: usize) -> Self {
+        Self {
+            concurrent_fetches: Some(limit.max(1)),
+            ..self
+        }
+    }
+
+    pub fn concurrent_fetches(&self) -> Option<usize> {
+        self.concurrent_fetches
+    }
+
+    /// Skip refs whose names differ from the ones of other fetched or stored
+    /// refs only by case.
+    ///
+    /// Such refs would clobber each other on case-insensitive filesystems.
+    /// The default is `true` on macOS and Windows, and `false` elsewhere.
+    pub fn with_case_folding(self, fold_case: bool) -> Self {
+        Self { fold_case, ..self }
+    }
+
+    pub fn fold_case(&self) -> bool {
+        self.fold_case
+    }
+
+    /// Also fetch `refs/<category>/*` of tracked peers, eg. `patches`.
+    ///
+    /// The categories fetched by default, `rad`, `remotes`, and names
+    /// containing a `/` are ignored.
+    pub fn with_category(mut self, category: impl Into<BString>) -> Self {
+        use refs::component::*;
+
+        let category = category.into();
+        let reserved = [HEADS, NOTES, TAGS, COBS, RAD, REMOTES];
+        if !category.is_empty()
+            && !category.contains(&refs::SEPARATOR)
+            && !reserved.contains(&category.as_slice())
+        {
+            self.categories.insert(category);
+        }
+        self
+    }
+
+    /// The categories fetched in addition to the default ones.
+    pub fn categories(&self) -> impl Iterator<Item = &BStr> {
+        self.categories.iter().map(|cat| cat.as_bstr())
+    }
+
+    /// Don't fetch the refs matching `pattern`, eg. `refs/heads/wip/*`, even if
+    /// they are signed.
+    ///
+    /// Patterns are matched against the names of the refs as published by
+    /// their owner, ie. without the `refs/remotes/<peer>` prefix. As with
+    /// refspecs, a `*` matches any sequence of characters, including `/`.
+    pub fn with_exclusion(mut self, pattern: impl Into<BString>) -> Self {
+        self.exclusions.insert(pattern.into());
+        self
+    }
+
+    /// The patterns of refs which are not fetched.
+    pub fn exclusions(&self) -> impl Iterator<Item = &BStr> {
+        self.exclusions.iter().map(|pattern| pattern.as_bstr())
+    }
+
+    /// Whether the ref `name` matches any of the
+    /// [`FetchSpec::with_exclusion`] patterns.
+    pub fn excludes(&self, name: &BStr) -> bool {
+        self.exclusions
+            .iter()
+            .any(|pattern| match pattern.find_byte(b'*') {
+                None => pattern == name,
+                Some(i) => {
+                    let (prefix, suffix) = (&pattern[..i], &pattern[i + 1..]);
+                    name.len() >= prefix.len() + suffix.len()
+                        && name.starts_with(prefix)
+                        && name.ends_with(suffix)
+                },
+            })
+    }
+
+    /// Whether unsigned refs of category `cat` are fetched from tracked peers.
+    pub fn allows(&self, cat: &refs::parsed::Cat) -> bool {
+        use refs::parsed::Cat;
+
+        match cat {
+            Cat::Heads | Cat::Notes | Cat::Tags | Cat::Cobs => true,
+            Cat::Unknown(x) => self.categories.contains(x),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Fetch<Oid> {
     /// The local id.
@@ -39,9 +182,52 @@ pub struct Fetch<Oid> {
     pub signed_refs: sigrefs::Combined<Oid>,
     /// Maximum number of bytes the fetched packfile can have.
     pub limit: u64,
+    /// The refs of tracked peers to fetch.
+    pub spec: FetchSpec,
 }
 
 impl<T> Fetch<T> {
+    /// Split into one [`Fetch`] per peer whose refs are to be fetched, see
+    /// [`FetchSpec::with_concurrent_fetches`].
+    ///
+    /// The `limit` is divided evenly among the returned [`Fetch`]es.
+    pub fn per_peer(self) -> Vec<Self> {
+        let Self {
+            local_id,
+            remote_id,
+            signed_refs: sigrefs::Combined { mut refs, remotes },
+            limit,
+            spec,
+        } = self;
+
+        let peers = refs
+            .keys()
+            .chain(remotes.iter())
+            .filter(|peer| **peer != local_id)
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let limit = (limit / peers.len().max(1) as u64).max(1);
+        peers
+            .into_iter()
+            .map(|peer| {
+                let mut signed_refs = sigrefs::Combined::default();
+                if let Some(signed) = refs.remove(&peer) {
+                    signed_refs.refs.insert(peer, signed);
+                }
+                if remotes.contains(&peer) {
+                    signed_refs.remotes.insert(peer);
+                }
+                Self {
+                    local_id,
+                    remote_id,
+                    signed_refs,
+                    limit,
+                    spec: spec.clone(),
+                }
+            })
+            .collect()
+    }
+
     fn scoped<'a, 'b: 'a>(
         &self,
         id: &'a PeerId,
@@ -66,6 +252,16 @@ impl<T> Fetch<T> {
     }
 }
 
+/// Whether refs of category `cat` are only accepted if they are signed by the
+/// peer owning them, even if that peer is tracked.
+///
+/// Notes annotate objects after the fact -- e.g. with code review metadata --
+/// so a tracked peer advertising notes it didn't sign could attribute
+/// annotations to the wrong peer.
+pub(crate) fn must_be_signed(cat: &refs::parsed::Cat) -> bool {
+    matches!(cat, refs::parsed::Cat::Notes)
+}
+
 impl<T: AsRef<oid>> Negotiation for Fetch<T> {
     fn ref_prefixes(&self) -> Vec<refs::Scoped<'_, '_>> {
         let remotes = self
@@ -80,6 +276,11 @@ impl<T: AsRef<oid>> Negotiation for Fetch<T> {
                     self.scoped(id, refs::Prefix::Tags),
                     self.scoped(id, refs::Prefix::Cobs),
                 ]
+                .into_iter()
+                .chain(self.spec.categories.iter().map(move |cat| {
+                    let prefix = BString::from(format!("refs/{}/", cat));
+                    self.scoped(id, Cow::<BStr>::Owned(prefix))
+                }))
             });
         let signed = self
             .signed_refs
@@ -114,8 +315,25 @@ impl<T: AsRef<oid>> Negotiation for Fetch<T> {
                 )
                 .collect();
                 let remote_id = *parsed.remote.as_ref().unwrap_or(&self.remote_id);
-                if self.is_tracked(&remote_id) || self.is_signed(&remote_id, &refname_no_remote) {
+                if self.spec.excludes(refname_no_remote.as_bstr()) {
+                    debug!(%refname_no_remote, "skipping {} as it is excluded", refname);
+                    None
+                } else if self.is_signed(&remote_id, &refname_no_remote) {
                     Some(FilteredRef::new(refname, tip, &remote_id, parsed))
+                } else if must_be_signed(cat) {
+                    warn!(
+                        %refname_no_remote,
+                        "skipping {} as it is not signed by {}", refname, remote_id
+                    );
+                    None
+                } else if self.is_tracked(&remote_id) && self.spec.allows(cat) {
+                    Some(FilteredRef::new(refname, tip, &remote_id, parsed))
+                } else if self.is_tracked(&remote_id) {
+                    debug!(
+                        %refname_no_remote,
+                        "skipping {} as its category is not fetched", refname
+                    );
+                    None
                 } else {
                     warn!(
                         %refname_no_remote,
@@ -185,16 +403,25 @@ impl<T: AsRef<oid>> UpdateTips for Fetch<T> {
     fn prepare<'a, U, C>(
         &self,
         _: &FetchState<U>,
-        _: &C,
+        cx: &C,
         refs: &'a [FilteredRef<Self>],
     ) -> Result<internal::Updates<'a, U>, error::Prepare<C::VerificationError, C::FindError>>
     where
-        C: Identities + Refdb,
+        C: Identities + Odb + Refdb,
     {
+        let colliding = if self.spec.fold_case {
+            case_collisions(cx, refs)?
+        } else {
+            HashSet::new()
+        };
         let mut tips = Vec::new();
         for r in refs {
             debug_assert!(r.remote_id != self.local_id, "never touch our own");
+            internal::guard_object_type(cx, r)?;
             let refname = refs::remote_tracking(&r.remote_id, r.name.as_bstr());
+            if colliding.contains(refname.as_bstr()) {
+                continue;
+            }
             tips.push(Update::Direct {
                 name: Cow::from(refname),
                 target: r.tip,
@@ -209,6 +436,51 @@ impl<T: AsRef<oid>> UpdateTips for Fetch<T> {
     }
 }
 
+/// The remote tracking names of the `refs` which differ from the names of
+/// other `refs`, or of refs stored in `cx`, only by case.
+///
+/// On case-insensitive filesystems, such refs would clobber each other. Of
+/// two fetched refs, both are skipped, while stored refs take precedence over
+/// fetched ones.
+fn case_collisions<C, T, V>(
+    cx: &C,
+    refs: &[FilteredRef<T>],
+) -> Result<HashSet<BString>, error::Prepare<V, C::FindError>>
+where
+    C: Refdb,
+    V: std::error::Error + Send + Sync + 'static,
+{
+    let mut folded = HashMap::<_, BTreeSet<BString>>::new();
+    for r in refs {
+        let name = refs::remote_tracking(&r.remote_id, r.name.as_bstr())
+            .as_bstr()
+            .to_owned();
+        folded.entry(name.to_lowercase()).or_default().insert(name);
+    }
+
+    let mut colliding = HashSet::new();
+    for names in folded.into_values() {
+        if names.len() > 1 {
+            warn!(?names, "skipping refs which differ only by case");
+            colliding.extend(names);
+            continue;
+        }
+        for name in names {
+            let stored =
+                Refdb::find_case_folded(cx, &name).map_err(|source| error::Prepare::FindRef {
+                    name: name.clone(),
+                    source,
+                })?;
+            if let Some(stored) = stored {
+                warn!(%name, %stored, "skipping ref which differs from a stored ref only by case");
+                colliding.insert(name);
+            }
+        }
+    }
+
+    Ok(colliding)
+}
+
 impl<T> Layout for Fetch<T> {
     // [`Fetch`] may request only a part of the refs tree, so no layout error
     // can be determined from the advertised refs alone.
@@ -0,0 +1,18 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fuzzing entry points, see `fuzz/` in the repository root.
+//!
+//! The functions in this module must not panic for any input.
+
+use bstr::ByteSlice as _;
+
+use crate::refs::{self, parsed::Identity};
+
+/// Parse `data` as a (possibly remote tracking) refname.
+pub fn refs_parse(data: &[u8]) {
+    let _ = refs::parse::<Identity>(data.as_bstr());
+    let _ = refs::parsed::parse_id::<Identity>(data);
+}
@@ -0,0 +1,80 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Additional `have`s to offer the remote end of a fetch.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+};
+
+use link_git::object::{CommitRef, Kind};
+
+use crate::{ObjectId, Odb, RefScan};
+
+/// Select up to `limit` commits pointed to by refs in the current namespace,
+/// most recently committed first.
+///
+/// Recent tips are likely to be ancestors of what is wanted from the remote
+/// end, so offering them as `have`s allows it to send a smaller packfile. In
+/// particular, the tips of other remotes tend to share most of their history
+/// with the wanted refs of a remote.
+///
+/// Refs which can not be read, or don't point to a commit, are skipped: the
+/// result is only an optimisation.
+///
+/// Note that this scans all refs in the namespace, and looks up the object
+/// each of them points to, so the time it takes is proportional to the size
+/// of the namespace rather than `limit`.
+pub fn recent<'a, C>(cx: &'a C, limit: usize) -> Vec<ObjectId>
+where
+    C: Odb,
+    &'a C: RefScan,
+{
+    if limit == 0 {
+        return vec![];
+    }
+
+    let scan = match RefScan::scan::<_, String>(cx, None) {
+        Ok(scan) => scan,
+        Err(e) => {
+            warn!(err = %e, "failed to scan refs for haves");
+            return vec![];
+        },
+    };
+
+    let mut seen = HashSet::new();
+    let mut tips = BinaryHeap::with_capacity(limit + 1);
+    let mut buf = Vec::new();
+    for item in scan {
+        let oid: ObjectId = match item {
+            Ok((_, oid)) => oid.into(),
+            Err(e) => {
+                warn!(err = %e, "failed to read ref for haves");
+                continue;
+            },
+        };
+        if !seen.insert(oid) {
+            continue;
+        }
+        let time = match cx.lookup(&oid, &mut buf) {
+            Ok(Some(obj)) if obj.kind == Kind::Commit => match CommitRef::from_bytes(obj.data) {
+                Ok(commit) => commit.committer.time.time,
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+        // Min-heap on commit time, so the oldest tip is evicted first
+        tips.push(Reverse((time, oid)));
+        if tips.len() > limit {
+            tips.pop();
+        }
+    }
+
+    tips.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((_, oid))| oid)
+        .collect()
+}
@@ -3,7 +3,21 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use crate::{error, ids, track, FetchState, FilteredRef, Identities, Refdb, Update};
+use either::Either;
+use link_git::object::Kind;
+
+use crate::{
+    error,
+    ids,
+    refs::parsed::Cat,
+    track,
+    FetchState,
+    FilteredRef,
+    Identities,
+    Odb,
+    Refdb,
+    Update,
+};
 
 pub(crate) struct Updates<'a, U> {
     pub tips: Vec<Update<'a>>,
@@ -19,7 +33,7 @@ pub(crate) trait UpdateTips<T = Self> {
     ) -> Result<Updates<'a, U>, error::Prepare<C::VerificationError, C::FindError>>
     where
         U: ids::Urn + Ord,
-        C: Identities<Urn = U> + Refdb;
+        C: Identities<Urn = U> + Odb + Refdb;
 }
 
 pub(crate) trait Layout<T = Self> {
@@ -29,3 +43,40 @@ pub(crate) trait Layout<T = Self> {
     /// [`crate::Negotiation::ref_filter`].
     fn pre_validate(&self, refs: &[FilteredRef<T>]) -> Result<(), error::Layout>;
 }
+
+/// Ensure the tip of `r` is of a type its category can point to: a commit for
+/// `rad/` refs, heads, notes and cobs, and a commit or tag for tags.
+///
+/// Refs of unknown categories, and tips which are not (yet) in the [`Odb`],
+/// are not checked.
+pub(crate) fn guard_object_type<O, T>(odb: &O, r: &FilteredRef<T>) -> Result<(), error::ObjectType>
+where
+    O: Odb,
+{
+    let (expected, allowed): (_, &[Kind]) = match &r.parsed {
+        Either::Left(_) => ("commit", &[Kind::Commit]),
+        Either::Right(refs) => match &refs.cat {
+            Cat::Heads | Cat::Notes | Cat::Cobs => ("commit", &[Kind::Commit]),
+            Cat::Tags => ("commit or tag", &[Kind::Commit, Kind::Tag]),
+            Cat::Unknown(_) => return Ok(()),
+        },
+    };
+
+    let mut buf = Vec::new();
+    let obj = odb
+        .lookup(&r.tip, &mut buf)
+        .map_err(|e| error::ObjectType::Lookup {
+            name: r.name.clone(),
+            oid: r.tip,
+            source: Box::new(e),
+        })?;
+    match obj {
+        Some(obj) if !allowed.contains(&obj.kind) => Err(error::ObjectType::Mismatch {
+            name: r.name.clone(),
+            oid: r.tip,
+            expected,
+            actual: obj.kind,
+        }),
+        _ => Ok(()),
+    }
+}
@@ -3,6 +3,15 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod bundle;
+pub use bundle::Policy as BundlePolicy;
+
+pub mod faulty;
+pub use faulty::{Faults, Faulty};
+
+mod inflight;
+pub use inflight::{Claim, Elsewhere, InFlight};
+
 mod net;
 pub use net::{Connection, Network};
 
@@ -10,4 +19,15 @@ mod odb;
 pub use odb::Odb;
 
 mod refdb;
-pub use refdb::{Refdb, UserInfo};
+pub use refdb::{Refdb, UserInfo, MAX_SYMREF_DEPTH};
+
+pub mod session;
+pub use session::{Recorder, Replay};
+
+pub mod throttle;
+pub use throttle::{Bandwidth, Throttle, Traffic};
+
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+pub use unix::Unix;
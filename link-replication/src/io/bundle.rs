@@ -0,0 +1,97 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+/// Which of the bundle URIs advertised by a remote end may be opened.
+///
+/// The remote end can advertise arbitrary URIs, which would let it make us
+/// issue requests to, eg., services on the local network. Hence nothing is
+/// allowed by default: bundles are only downloaded via `https` from the hosts
+/// allowed explicitly, and only read from local files if
+/// [`Policy::allow_file`] is set.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    hosts: BTreeSet<String>,
+    allow_file: bool,
+}
+
+impl Policy {
+    /// Allow downloading bundles via `https` from `host`.
+    ///
+    /// `host` may include a port, eg. `bundles.example.com:8443`, in which
+    /// case only URIs with that port are allowed.
+    pub fn allow_host(mut self, host: impl AsRef<str>) -> Self {
+        self.hosts.insert(host.as_ref().to_ascii_lowercase());
+        self
+    }
+
+    /// Allow bundles to be local files, which is only sensible if the remote
+    /// end is on the same machine.
+    pub fn allow_file(self, allow_file: bool) -> Self {
+        Self { allow_file, ..self }
+    }
+
+    /// Whether nothing is allowed, ie. there is no point in asking for bundles.
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty() && !self.allow_file
+    }
+
+    /// Whether the bundle at `uri` may be opened.
+    pub fn allows(&self, uri: &str) -> bool {
+        match uri.strip_prefix("https://") {
+            Some(rest) => {
+                let authority = rest.split(&['/', '?', '#'][..]).next().unwrap_or_default();
+                // Credentials could be used to smuggle in a different host
+                !authority.contains('@') && self.hosts.contains(&authority.to_ascii_lowercase())
+            },
+            None => self.allow_file && local_path(uri).is_some(),
+        }
+    }
+}
+
+/// The path of `uri` if it refers to a local file.
+fn local_path(uri: &str) -> Option<&Path> {
+    let path = Path::new(uri.strip_prefix("file://").unwrap_or(uri));
+    path.is_absolute().then(|| path)
+}
+
+/// Open the bundle at `uri` for reading, if `policy` allows it.
+///
+/// This blocks, and must be run off the async executor.
+pub(super) fn open(uri: &str, policy: &Policy) -> io::Result<Box<dyn BufRead + Send>> {
+    if !policy.allows(uri) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("bundle uri {} not allowed", uri),
+        ));
+    }
+
+    match local_path(uri) {
+        Some(path) => Ok(Box::new(BufReader::new(File::open(path)?))),
+        None => {
+            // Redirects could lead to hosts which are not allowed
+            let resp = ureq::AgentBuilder::new()
+                .redirects(0)
+                .build()
+                .get(uri)
+                .call()
+                .map_err(io_other)?;
+            Ok(Box::new(BufReader::new(resp.into_reader())))
+        },
+    }
+}
+
+fn io_other<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::Other, e)
+}
@@ -0,0 +1,270 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fault injection for [`Connection`]s.
+//!
+//! A [`Faulty`] connection wraps another [`Connection`], and degrades the
+//! streams it opens according to its [`Faults`]: it adds latency, caps the
+//! bandwidth, drops the connection at random, or hangs up after a number of
+//! bytes (eg. in the middle of a packfile). This allows to exercise the error
+//! handling of a fetch in tests, without depending on a flaky real network.
+//!
+//! Random faults are drawn from a generator seeded with [`Faults::seed`], so a
+//! failing run can be reproduced given the streams are opened in the same
+//! order.
+
+use std::{
+    future::Future as _,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_io::Timer;
+use futures_lite::{
+    io::{AsyncRead, AsyncWrite},
+    ready,
+};
+use parking_lot::Mutex;
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+
+use super::Connection;
+
+/// The faults to inject, none by default.
+#[derive(Clone, Copy, Debug)]
+pub struct Faults {
+    /// Delay opening a stream, and the first read after a write, by this
+    /// amount. That is, every request-response exchange costs one round-trip.
+    pub latency: Option<Duration>,
+    /// Limit the rate at which a stream can be read from, in bytes per second.
+    pub bytes_per_sec: Option<u64>,
+    /// The probability, between `0.0` and `1.0`, that the connection drops
+    /// on any given read or write.
+    ///
+    /// Once dropped, all further operations on the stream fail with
+    /// [`io::ErrorKind::ConnectionReset`].
+    pub disconnect: f64,
+    /// Signal end-of-file after this many bytes were read from a stream, as
+    /// if the remote end hung up.
+    pub truncate_after: Option<u64>,
+    /// Seed for the random faults.
+    pub seed: u64,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Self {
+            latency: None,
+            bytes_per_sec: None,
+            disconnect: 0.0,
+            truncate_after: None,
+            seed: 0,
+        }
+    }
+}
+
+/// A [`Connection`] injecting [`Faults`] into the streams of another
+/// [`Connection`].
+pub struct Faulty<C> {
+    inner: C,
+    faults: Faults,
+    rng: Mutex<StdRng>,
+}
+
+impl<C> Faulty<C> {
+    pub fn new(inner: C, faults: Faults) -> Self {
+        Self {
+            inner,
+            faults,
+            rng: Mutex::new(StdRng::seed_from_u64(faults.seed)),
+        }
+    }
+
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<C> Connection for Faulty<C>
+where
+    C: Connection + Send + Sync,
+    C::Read: Send,
+    C::Write: Send,
+{
+    type Read = FaultyRead<C::Read>;
+    type Write = FaultyWrite<C::Write>;
+    type Error = C::Error;
+
+    async fn open_stream(&self) -> Result<(Self::Read, Self::Write), Self::Error> {
+        if let Some(latency) = self.faults.latency {
+            Timer::after(latency).await;
+        }
+        let (recv, send) = self.inner.open_stream().await?;
+
+        let (seed_recv, seed_send) = {
+            let mut rng = self.rng.lock();
+            (rng.gen(), rng.gen())
+        };
+        let wrote = Arc::new(AtomicBool::new(false));
+        Ok((
+            FaultyRead {
+                inner: recv,
+                faults: self.faults,
+                rng: StdRng::seed_from_u64(seed_recv),
+                wrote: wrote.clone(),
+                delay: None,
+                read: 0,
+                dropped: false,
+            },
+            FaultyWrite {
+                inner: send,
+                faults: self.faults,
+                rng: StdRng::seed_from_u64(seed_send),
+                wrote,
+                dropped: false,
+            },
+        ))
+    }
+
+    fn preopen_fetch_stream(&self) -> bool {
+        self.inner.preopen_fetch_stream()
+    }
+}
+
+/// The read half of a [`Faulty`] stream.
+pub struct FaultyRead<R> {
+    inner: R,
+    faults: Faults,
+    rng: StdRng,
+    /// Set by the write half, so the next read incurs the latency.
+    wrote: Arc<AtomicBool>,
+    delay: Option<Timer>,
+    read: u64,
+    dropped: bool,
+}
+
+impl<R> AsyncRead for FaultyRead<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        if this.dropped {
+            return Poll::Ready(Err(dropped()));
+        }
+
+        loop {
+            if let Some(timer) = &mut this.delay {
+                ready!(Pin::new(timer).poll(cx));
+                this.delay = None;
+            }
+            match this.faults.latency {
+                Some(latency) if this.wrote.swap(false, Ordering::AcqRel) => {
+                    this.delay = Some(Timer::after(latency))
+                },
+                _ => break,
+            }
+        }
+
+        let mut len = buf.len();
+        if let Some(limit) = this.faults.truncate_after {
+            let left = limit.saturating_sub(this.read);
+            if left == 0 {
+                return Poll::Ready(Ok(0));
+            }
+            len = len.min(left as usize);
+        }
+        if let Some(rate) = this.faults.bytes_per_sec {
+            // Hand out at most 100ms worth of bytes at a time
+            len = len.min((rate / 10).max(1) as usize);
+        }
+
+        let n = ready!(Pin::new(&mut this.inner).poll_read(cx, &mut buf[..len]))?;
+        if roll(&mut this.rng, this.faults.disconnect) {
+            this.dropped = true;
+            return Poll::Ready(Err(dropped()));
+        }
+        this.read += n as u64;
+        if let Some(rate) = this.faults.bytes_per_sec {
+            if n > 0 {
+                this.delay = Some(Timer::after(Duration::from_secs_f64(
+                    n as f64 / rate.max(1) as f64,
+                )));
+            }
+        }
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+/// The write half of a [`Faulty`] stream.
+pub struct FaultyWrite<W> {
+    inner: W,
+    faults: Faults,
+    rng: StdRng,
+    wrote: Arc<AtomicBool>,
+    dropped: bool,
+}
+
+impl<W> AsyncWrite for FaultyWrite<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        if this.dropped {
+            return Poll::Ready(Err(dropped()));
+        }
+
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        if roll(&mut this.rng, this.faults.disconnect) {
+            this.dropped = true;
+            return Poll::Ready(Err(dropped()));
+        }
+        if n > 0 {
+            this.wrote.store(true, Ordering::Release);
+        }
+
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        if this.dropped {
+            return Poll::Ready(Err(dropped()));
+        }
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+fn roll(rng: &mut StdRng, p: f64) -> bool {
+    p > 0.0 && rng.gen_bool(p.min(1.0))
+}
+
+fn dropped() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionReset, "injected disconnect")
+}
@@ -0,0 +1,123 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::HashMap, sync::Arc};
+
+use event_listener::{Event, EventListener};
+use link_git::protocol::ObjectId;
+use parking_lot::Mutex;
+
+/// Coordinates the `want`s of concurrent fetches into the same object
+/// database.
+///
+/// When many URNs are replicated from the same remote, the same objects are
+/// often wanted by several fetches at once. A fetch [`InFlight::claim`]s its
+/// `want`s before sending them, and only asks for the ones not already
+/// claimed by another fetch. The [`Claim`] is released when it is dropped, at
+/// which point the objects are expected to be in the object database.
+///
+/// A fetch may fail before delivering the objects it claimed, so the other
+/// fetches check which of them are still missing once the claim is released
+/// (see [`Elsewhere::released`]), and request those themselves.
+///
+/// Cloning an [`InFlight`] yields a handle to the same set of claims.
+#[derive(Clone, Default)]
+pub struct InFlight {
+    claims: Arc<Mutex<HashMap<ObjectId, Arc<Event>>>>,
+}
+
+impl InFlight {
+    /// Claim the `oids` not already claimed by someone else.
+    ///
+    /// Returns the [`Claim`], along with the `oids` claimed by someone else.
+    pub fn claim<I>(&self, oids: I) -> (Claim, Elsewhere)
+    where
+        I: IntoIterator<Item = ObjectId>,
+    {
+        let done = Arc::new(Event::new());
+        let mut claimed = Vec::new();
+        let mut elsewhere = Elsewhere::default();
+
+        let mut claims = self.claims.lock();
+        for oid in oids {
+            match claims.get(&oid) {
+                Some(other) if !Arc::ptr_eq(other, &done) => {
+                    elsewhere.0.push((oid, other.listen()))
+                },
+                Some(_) => {},
+                None => {
+                    claims.insert(oid, done.clone());
+                    claimed.push(oid);
+                },
+            }
+        }
+
+        (
+            Claim {
+                in_flight: self.clone(),
+                oids: claimed,
+                done,
+            },
+            elsewhere,
+        )
+    }
+}
+
+/// The `want`s claimed by other fetches, see [`InFlight::claim`].
+#[derive(Default)]
+pub struct Elsewhere(Vec<(ObjectId, EventListener)>);
+
+impl Elsewhere {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Wait until all the claims are released, and return the oids for which
+    /// `contains` is still `false`.
+    ///
+    /// Those were claimed by a fetch which failed, and need to be requested
+    /// again.
+    pub async fn released<F>(self, contains: F) -> Vec<ObjectId>
+    where
+        F: Fn(&ObjectId) -> bool,
+    {
+        let mut missing = Vec::new();
+        for (oid, listener) in self.0 {
+            listener.await;
+            if !contains(&oid) {
+                missing.push(oid);
+            }
+        }
+        missing
+    }
+}
+
+/// The `want`s a fetch is responsible for, see [`InFlight`].
+pub struct Claim {
+    in_flight: InFlight,
+    oids: Vec<ObjectId>,
+    done: Arc<Event>,
+}
+
+impl Claim {
+    pub fn oids(&self) -> &[ObjectId] {
+        &self.oids
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let mut claims = self.in_flight.claims.lock();
+        for oid in &self.oids {
+            claims.remove(oid);
+        }
+        drop(claims);
+        self.done.notify(usize::MAX);
+    }
+}
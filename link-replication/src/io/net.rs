@@ -3,13 +3,51 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{borrow::Cow, io, marker::PhantomData, path::PathBuf};
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    io,
+    marker::PhantomData,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+};
 
 use bstr::BString;
-use futures_lite::io::{AsyncRead, AsyncWrite};
-use link_git::protocol as git;
+use futures_lite::{
+    future,
+    io::{AsyncRead, AsyncWrite},
+};
+use link_git::protocol::{
+    self as git,
+    sideband::OnProgress,
+    trace::{Direction, Traced, Tracer},
+    ObjectId,
+};
 
-use crate::{FilteredRef, Negotiation, Net, Odb, Refdb, SkippedFetch, Urn, WantsHaves};
+use super::{
+    bundle,
+    session::{Recorded, Recorder},
+    throttle::{Metered, Throttle, Traffic},
+    Elsewhere,
+    InFlight,
+};
+
+use crate::{
+    correlation,
+    haves,
+    progress,
+    refdb,
+    CorrelationId,
+    FilteredRef,
+    Negotiation,
+    Net,
+    Odb,
+    RefScan,
+    Refdb,
+    SkippedFetch,
+    Urn,
+    WantsHaves,
+};
 
 #[async_trait]
 pub trait Connection {
@@ -18,6 +56,17 @@ pub trait Connection {
     type Error: std::error::Error + Send + Sync + 'static;
 
     async fn open_stream(&self) -> Result<(Self::Read, Self::Write), Self::Error>;
+
+    /// Whether to open the stream for the `fetch` phase while `ls-refs` is
+    /// still in progress.
+    ///
+    /// This saves a round-trip on high-latency links if streams are cheap to
+    /// open concurrently on this connection. The stream is discarded if the
+    /// fetch turns out to be unnecessary. Note that the advertisement is
+    /// still received in full before the `want`s are determined.
+    fn preopen_fetch_stream(&self) -> bool {
+        false
+    }
 }
 
 pub struct Network<U, D, B, C> {
@@ -25,6 +74,20 @@ pub struct Network<U, D, B, C> {
     urn: U,
     db: D,
     conn: C,
+    on_progress: Option<OnProgress>,
+    reporter: Option<progress::Reporter>,
+    traffic: Traffic,
+    unpack_limit: Option<u32>,
+    base_cache: Option<git::packwriter::BaseCache>,
+    bundle_uris: Option<bundle::Policy>,
+    tracer: Option<Tracer>,
+    recorder: Option<Recorder>,
+    flow_control: git::fetch::FlowControl,
+    in_flight: Option<InFlight>,
+    throttle: Throttle,
+    recent_haves: usize,
+    correlation_id: Option<CorrelationId>,
+    send_correlation_id: bool,
     _marker: PhantomData<B>,
 }
 
@@ -35,42 +98,335 @@ impl<U, D, B, C> Network<U, D, B, C> {
             db,
             conn,
             urn,
+            on_progress: None,
+            reporter: None,
+            traffic: Traffic::default(),
+            unpack_limit: None,
+            base_cache: None,
+            bundle_uris: None,
+            tracer: None,
+            recorder: None,
+            flow_control: git::fetch::FlowControl::default(),
+            in_flight: None,
+            throttle: Throttle::default(),
+            recent_haves: 0,
+            correlation_id: None,
+            send_correlation_id: false,
             _marker: PhantomData,
         }
     }
+
+    /// Forward the progress messages sent by the remote end during a fetch to
+    /// `on_progress`.
+    pub fn with_progress(self, on_progress: OnProgress) -> Self {
+        Self {
+            on_progress: Some(on_progress),
+            ..self
+        }
+    }
+
+    /// Report the [`progress::Event`]s of replication runs using this
+    /// [`Network`] to `reporter`.
+    pub fn with_progress_events(self, reporter: progress::Reporter) -> Self {
+        Self {
+            reporter: Some(reporter),
+            ..self
+        }
+    }
+
+    /// Explode received packfiles containing fewer than `limit` objects into
+    /// loose objects as they are received, see
+    /// [`git::packwriter::Quarantine`].
+    ///
+    /// The objects only become visible after it has been checked that all the
+    /// wanted tips were received.
+    pub fn with_unpack_limit(self, limit: u32) -> Self {
+        Self {
+            unpack_limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Cache the base objects looked up to complete thin packs, see
+    /// [`git::packwriter::Cached`].
+    pub fn with_base_cache(self, cache: git::packwriter::BaseCache) -> Self {
+        Self {
+            base_cache: Some(cache),
+            ..self
+        }
+    }
+
+    /// On initial clones, ask the remote end for pre-generated bundles, and
+    /// import the ones `policy` allows before fetching the remainder.
+    pub fn with_bundle_uris(self, policy: bundle::Policy) -> Self {
+        Self {
+            bundle_uris: Some(policy).filter(|policy| !policy.is_empty()),
+            ..self
+        }
+    }
+
+    /// Report the pkt-lines exchanged on every stream opened by this
+    /// [`Network`] to `tracer`.
+    pub fn with_tracer(self, tracer: Tracer) -> Self {
+        Self {
+            tracer: Some(tracer),
+            ..self
+        }
+    }
+
+    /// Record the bytes exchanged on every stream opened by this [`Network`]
+    /// to `recorder`, so the session can be replayed later, see
+    /// [`super::session`].
+    pub fn with_recorder(self, recorder: Recorder) -> Self {
+        Self {
+            recorder: Some(recorder),
+            ..self
+        }
+    }
+
+    /// Bound the memory used for buffering fetch responses, see
+    /// [`git::fetch::FlowControl`].
+    pub fn with_flow_control(self, flow_control: git::fetch::FlowControl) -> Self {
+        Self {
+            flow_control,
+            ..self
+        }
+    }
+
+    /// Don't `want` objects which are already being fetched by other
+    /// [`Network`]s sharing `in_flight`, but wait for them to arrive instead.
+    pub fn with_in_flight(self, in_flight: InFlight) -> Self {
+        Self {
+            in_flight: Some(in_flight),
+            ..self
+        }
+    }
+
+    /// Limit the bandwidth of the streams opened on the connection to the
+    /// bandwidth of `throttle`, shared with all other streams it throttles.
+    ///
+    /// By default, the bandwidth is unlimited.
+    pub fn with_throttle(self, throttle: Throttle) -> Self {
+        Self { throttle, ..self }
+    }
+
+    /// The bytes sent and received on the streams opened on the connection.
+    ///
+    /// The [`Throttle::traffic`] also includes all other streams sharing the
+    /// [`Throttle`].
+    pub fn traffic(&self) -> &Traffic {
+        &self.traffic
+    }
+
+    /// In addition to the current targets of the refs being fetched, offer up
+    /// to `limit` of the most recent tips in the namespace as `have`s, see
+    /// [`haves::recent`].
+    ///
+    /// This is disabled by default, as every fetch then scans all refs in the
+    /// namespace, and looks up the object each of them points to. Zero
+    /// disables it again.
+    pub fn with_recent_haves(self, limit: usize) -> Self {
+        Self {
+            recent_haves: limit,
+            ..self
+        }
+    }
+
+    /// Send the [`CorrelationId`] of the replication run to the remote end, as
+    /// the [`correlation::PARAM`] protocol parameter.
+    pub fn with_correlation_id(self) -> Self {
+        Self {
+            send_correlation_id: true,
+            ..self
+        }
+    }
+
+    fn extra_params(&self) -> Vec<(String, Option<String>)> {
+        match self.correlation_id {
+            Some(id) if self.send_correlation_id => {
+                vec![(correlation::PARAM.to_owned(), Some(id.to_string()))]
+            },
+            _ => vec![],
+        }
+    }
 }
 
-#[async_trait(?Send)]
-impl<U, D, B, C> Net for Network<U, D, B, C>
+impl<U, D, B, C> Network<U, D, B, C>
+where
+    C: Connection,
+{
+    async fn open_stream(
+        &self,
+    ) -> io::Result<(
+        Traced<Recorded<Metered<C::Read>>>,
+        Traced<Recorded<Metered<C::Write>>>,
+    )> {
+        let (recv, send) = self.conn.open_stream().await.map_err(io_other)?;
+        let (recv, send) =
+            self.throttle
+                .wrap_counted(recv, send, Some(&self.traffic), self.reporter.clone());
+        let (recv, send) = match &self.recorder {
+            Some(recorder) => recorder.wrap(recv, send),
+            None => (Recorded::passthrough(recv), Recorded::passthrough(send)),
+        };
+        Ok((
+            Traced::new(recv, Direction::Recv, self.tracer.as_ref()),
+            Traced::new(send, Direction::Send, self.tracer.as_ref()),
+        ))
+    }
+}
+
+impl<U, D, B, C> Network<U, D, B, C>
 where
     U: Urn,
 
     D: Refdb + Odb + AsRef<B>,
-    D::FindError: Send + Sync,
 
     B: ToOwned,
     <B as ToOwned>::Owned: git::packwriter::BuildThickener + Send + 'static,
 
     C: Connection,
+{
+    /// Import the bundles advertised by the remote end, returning the tips they
+    /// contain.
+    ///
+    /// Bundles which can't be applied are skipped. If the remote end doesn't
+    /// support `bundle-uri`, nothing is imported.
+    async fn apply_bundles(
+        &self,
+        repo: BString,
+        max_pack_bytes: u64,
+        policy: &bundle::Policy,
+    ) -> io::Result<Vec<ObjectId>> {
+        let list = {
+            let (recv, send) = self.open_stream().await?;
+            let res = git::bundle_uri(
+                git::bundle_uri::Options {
+                    repo,
+                    extra_params: self.extra_params(),
+                },
+                recv,
+                send,
+            )
+            .await;
+            match res {
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => return Ok(vec![]),
+                res => res?,
+            }
+        };
+        debug!(?list);
+
+        let mut tips = Vec::new();
+        for git::bundle_uri::Bundle { uri, .. } in list.bundles {
+            let (header, reader) = match blocking::unblock({
+                let uri = uri.clone();
+                let policy = policy.clone();
+                move || {
+                    let mut reader = bundle::open(&uri, &policy)?;
+                    let header = git::bundle_uri::read_header(&mut reader)?;
+                    Ok::<_, io::Error>((header, reader))
+                }
+            })
+            .await
+            {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!(%uri, err = %e, "skipping bundle");
+                    continue;
+                },
+            };
+            if let Some(missing) = header
+                .prerequisites
+                .iter()
+                .find(|oid| !self.db.contains(oid))
+            {
+                warn!(%uri, %missing, "skipping bundle with missing prerequisite");
+                continue;
+            }
+
+            let pack = blocking::unblock({
+                let git_dir = self.git_dir.clone();
+                let thick: B::Owned = self.db.as_ref().to_owned();
+                let base_cache = self.base_cache;
+                move || {
+                    let writer = git::packwriter::Standard::new(
+                        git_dir,
+                        git::packwriter::Options {
+                            max_pack_bytes,
+                            base_cache,
+                            ..Default::default()
+                        },
+                        thick,
+                        Arc::new(AtomicBool::new(false)),
+                    );
+                    git::bundle_uri::unbundle(reader, &writer)
+                }
+            })
+            .await;
+            match pack {
+                Ok(pack) => {
+                    match pack {
+                        git::packwriter::Received::Pack(pack) => {
+                            if let Some(index_path) = pack.index_path {
+                                self.db.add_pack(&index_path).map_err(io_other)?;
+                            }
+                        },
+                        // Not reached, as no `unpack_limit` is set for bundles
+                        git::packwriter::Received::Loose(objects) => {
+                            objects.migrate(&self.git_dir)?
+                        },
+                    }
+                    tips.extend(header.refs.into_iter().map(|(_, oid)| oid));
+                    if list.mode == git::bundle_uri::Mode::Any {
+                        break;
+                    }
+                },
+                Err(e) => warn!(%uri, err = %e, "failed to import bundle"),
+            }
+        }
+
+        Ok(tips)
+    }
+}
+
+#[async_trait]
+impl<U, D, B, C> Net for Network<U, D, B, C>
+where
+    U: Urn + Send + Sync,
+
+    D: Refdb + Odb + AsRef<B> + Clone + Send + Sync + 'static,
+    D::FindError: Send + Sync,
+    for<'a> &'a D: RefScan,
+
+    B: ToOwned + Sync,
+    <B as ToOwned>::Owned: git::packwriter::BuildThickener + Send + 'static,
+
+    C: Connection + Send + Sync,
     C::Read: Send + 'static,
     C::Write: Send + 'static,
     C::Error: Send + Sync,
 {
     type Error = io::Error;
 
-    #[tracing::instrument(level = "debug", skip(self, neg), err)]
+    #[tracing::instrument(
+        level = "debug",
+        skip(self, neg),
+        fields(correlation_id = ?self.correlation_id),
+        err
+    )]
     async fn run_fetch<N, T>(
         &self,
         neg: N,
     ) -> Result<(N, Result<Vec<FilteredRef<T>>, SkippedFetch>), io::Error>
     where
-        N: Negotiation<T> + Send,
+        N: Negotiation<T> + Send + Sync,
         T: Send + 'static,
     {
         let git_dir = self.git_dir.clone();
         let repo = BString::from(self.urn.encode_id());
 
-        let refs = {
+        let mut fetch_stream = None;
+        let git::ls::Outputs { refs, capabilities } = {
             let mut ref_prefixes = neg
                 .ref_prefixes()
                 .into_iter()
@@ -79,31 +435,55 @@ where
             ref_prefixes.sort();
             ref_prefixes.dedup();
 
-            let (recv, send) = self.conn.open_stream().await.map_err(io_other)?;
-            git::ls_refs(
-                git::ls::Options {
-                    repo: repo.clone(),
-                    extra_params: vec![],
-                    ref_prefixes,
-                },
-                recv,
-                send,
-            )
-            .await?
+            self.progress(progress::Event::LsRefs);
+            let ls = async {
+                let (recv, send) = self.open_stream().await?;
+                git::ls_refs(
+                    git::ls::Options {
+                        repo: repo.clone(),
+                        extra_params: self.extra_params(),
+                        ref_prefixes,
+                    },
+                    recv,
+                    send,
+                )
+                .await
+            };
+            // Open the stream for the fetch while the advertisement is in flight
+            if self.conn.preopen_fetch_stream() {
+                let (ls, stream) = future::zip(ls, self.open_stream()).await;
+                fetch_stream = Some(stream);
+                ls?
+            } else {
+                ls.await?
+            }
         };
+        debug!(?capabilities);
 
         if refs.is_empty() {
             info!("no matching refs");
             return Ok((neg, Err(SkippedFetch::NoMatchingRefs)));
         }
 
+        let refs = refs
+            .into_iter()
+            .filter_map(|r| neg.ref_filter(r))
+            .collect::<Vec<_>>();
+        // Load the remote tracking refs of all advertised remotes in one go,
+        // instead of looking them up one by one
+        let local = {
+            let remotes = refs.iter().map(|r| r.remote_id).collect::<BTreeSet<_>>();
+            refdb::Mem::load(
+                &self.db,
+                remotes.iter().map(|id| format!("refs/remotes/{}", id)),
+            )
+            .map_err(io_other)?
+        };
         let WantsHaves {
             wanted,
             mut wants,
-            haves,
-        } = neg
-            .wants_haves(&self.db, refs.into_iter().filter_map(|r| neg.ref_filter(r)))
-            .map_err(io_other)?;
+            mut haves,
+        } = neg.wants_haves(&local, refs).map_err(io_other)?;
 
         debug!(?wants, ?haves);
 
@@ -112,72 +492,180 @@ where
             info!("want nothing");
             return Ok((neg, Err(SkippedFetch::WantNothing)));
         }
-        let wants: Vec<_> = wants.into_iter().collect();
+
+        // Initial clone: try to offload as much as possible to bundles
+        if let Some(policy) = self.bundle_uris.as_ref().filter(|_| haves.is_empty()) {
+            match self
+                .apply_bundles(repo.clone(), neg.fetch_limit(), policy)
+                .await
+            {
+                Err(e) => warn!(err = %e, "failed to apply bundles"),
+                Ok(tips) => {
+                    haves.extend(tips.into_iter().filter(|oid| self.db.contains(oid)));
+                    wants.retain(|oid| !self.db.contains(oid));
+                    if wants.is_empty() {
+                        info!("bundles contained all wants");
+                        return Ok((neg, Ok(wanted.into_iter().collect())));
+                    }
+                },
+            }
+        }
+        // Let the remote end know about more of our history than the refs
+        // being fetched
+        haves.extend(haves::recent(&self.db, self.recent_haves));
         let haves: Vec<_> = haves.into_iter().collect();
+        self.progress(progress::Event::WantsHaves {
+            wants: wants.len(),
+            haves: haves.len(),
+        });
 
-        let out = {
-            let wants = wants.clone();
-            let thick: B::Owned = self.db.as_ref().to_owned();
-            let (recv, send) = self.conn.open_stream().await.map_err(io_other)?;
-            git::fetch(
-                git::fetch::Options {
-                    repo,
-                    extra_params: vec![],
-                    wants,
-                    haves,
-                    want_refs: vec![],
+        // Leave the objects wanted by concurrent fetches to them. If a
+        // concurrent fetch fails to deliver, request its share ourselves.
+        let all_wants: Vec<_> = wants.into_iter().collect();
+        let mut pending = all_wants.clone();
+        let mut wanted_refs = Vec::new();
+        while !pending.is_empty() {
+            let (claim, elsewhere) = match &self.in_flight {
+                None => (None, Elsewhere::default()),
+                Some(in_flight) => {
+                    let (claim, elsewhere) = in_flight.claim(pending.iter().copied());
+                    (Some(claim), elsewhere)
                 },
-                {
+            };
+            let wants = match &claim {
+                None => pending.clone(),
+                Some(claim) => claim.oids().to_vec(),
+            };
+            if !elsewhere.is_empty() {
+                debug!(
+                    "{} wants are in flight elsewhere, fetching {}",
+                    elsewhere.len(),
+                    wants.len()
+                );
+            }
+
+            // Degrade to fetching all objects if partial packfiles are not supported
+            let filter = neg.filter().filter(|spec| {
+                if !capabilities.filter {
+                    warn!(%spec, "remote does not support `filter`");
+                }
+                capabilities.filter
+            });
+
+            if !wants.is_empty() {
+                let fetched = wants.clone();
+                let out = {
+                    let thick: B::Owned = self.db.as_ref().to_owned();
+                    let (recv, send) = match fetch_stream.take() {
+                        Some(stream) => stream?,
+                        None => self.open_stream().await?,
+                    };
+                    git::fetch(
+                        git::fetch::Options {
+                            repo: repo.clone(),
+                            extra_params: self.extra_params(),
+                            wants,
+                            haves: haves.clone(),
+                            want_refs: vec![],
+                            filter,
+                            on_progress: self.on_progress.clone(),
+                            flow_control: self.flow_control,
+                        },
+                        {
+                            let git_dir = git_dir.clone();
+                            let max_pack_bytes = neg.fetch_limit();
+                            let base_cache = self.base_cache;
+                            let unpack_limit = self.unpack_limit;
+                            move |stop| {
+                                git::packwriter::Standard::new(
+                                    git_dir,
+                                    git::packwriter::Options {
+                                        max_pack_bytes,
+                                        base_cache,
+                                        unpack_limit,
+                                        ..Default::default()
+                                    },
+                                    thick,
+                                    stop,
+                                )
+                            }
+                        },
+                        recv,
+                        send,
+                    )
+                    .await?
+                };
+                let pack = out.pack.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "empty or no packfile received",
+                    )
+                })?;
+
+                // Reading the index and adding the pack to the odb is blocking
+                // I/O, so keep it off the executor
+                blocking::unblock({
                     let git_dir = git_dir.clone();
-                    let max_pack_bytes = neg.fetch_limit();
-                    move |stop| {
-                        git::packwriter::Standard::new(
-                            git_dir,
-                            git::packwriter::Options {
-                                max_pack_bytes,
-                                ..Default::default()
+                    let db = self.db.clone();
+                    move || {
+                        // Validate we got all requested tips in the pack, before
+                        // any of its objects become visible in the odb
+                        let missing = |oid: &ObjectId| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!("wanted {} not found in pack", oid),
+                            )
+                        };
+                        match pack {
+                            git::packwriter::Received::Pack(pack) => {
+                                use link_git::odb::index::IndexFile;
+
+                                let index_path =
+                                    pack.index_path.expect("written packfile must have a path");
+                                let idx = IndexFile::at(&index_path).map_err(io_other)?;
+                                if let Some(oid) =
+                                    fetched.iter().find(|oid| idx.lookup(oid).is_none())
+                                {
+                                    return Err(missing(oid));
+                                }
+                                drop(idx);
+                                // abstraction leak: we could add the `Index` directly if
+                                // we knew the type of our odb.
+                                db.add_pack(&index_path).map_err(io_other)
                             },
-                            thick,
-                            stop,
-                        )
+                            git::packwriter::Received::Loose(objects) => {
+                                if let Some(oid) = fetched.iter().find(|oid| !objects.contains(oid))
+                                {
+                                    return Err(missing(oid));
+                                }
+                                objects.migrate(&git_dir)
+                            },
+                        }
                     }
-                },
-                recv,
-                send,
-            )
-            .await?
-        };
-        let pack_path = out
-            .pack
-            .ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "empty or no packfile received",
-                )
-            })?
-            .index_path
-            .expect("written packfile must have a path");
-
-        // Validate we got all requested tips in the pack
-        {
-            use link_git::odb::index::IndexFile;
-
-            let idx = IndexFile::at(&pack_path).map_err(io_other)?;
-            for oid in wants {
-                if idx.lookup(oid).is_none() {
-                    return Err(io::Error::new(
-                        io::ErrorKind::NotFound,
-                        format!("wanted {} not found in pack", oid),
-                    ));
-                }
+                })
+                .await?;
+                wanted_refs.extend(out.wanted_refs);
+            }
+            // Our wants are in the odb now, let the concurrent fetches know
+            drop(claim);
+            pending = elsewhere.released(|oid| self.db.contains(oid)).await;
+            if !pending.is_empty() {
+                debug!(
+                    "{} wants were not delivered by concurrent fetches, requesting them",
+                    pending.len()
+                );
             }
         }
-        // abstraction leak: we could add the `Index` directly if we knew the
-        // type of our odb.
-        self.db.add_pack(&pack_path).map_err(io_other)?;
 
-        let refs_in_pack = out
-            .wanted_refs
+        // Validate the concurrent fetches delivered the remaining tips
+        if let Some(oid) = all_wants.into_iter().find(|oid| !self.db.contains(oid)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("wanted {} not found in pack", oid),
+            ));
+        }
+
+        let refs_in_pack = wanted_refs
             .into_iter()
             .filter_map(|r| neg.ref_filter(r))
             .chain(wanted)
@@ -185,6 +673,42 @@ where
 
         Ok((neg, Ok(refs_in_pack)))
     }
+
+    #[tracing::instrument(
+        level = "debug",
+        skip(self),
+        fields(correlation_id = ?self.correlation_id),
+        err
+    )]
+    async fn has_objects(&self, oids: Vec<ObjectId>) -> Result<BTreeSet<ObjectId>, io::Error> {
+        let (recv, send) = self.open_stream().await?;
+        let out = git::object_info(
+            git::object_info::Options {
+                repo: BString::from(self.urn.encode_id()),
+                extra_params: self.extra_params(),
+                oids,
+            },
+            recv,
+            send,
+        )
+        .await?;
+
+        Ok(out.present().copied().collect())
+    }
+
+    fn correlate(&mut self, id: CorrelationId) {
+        self.correlation_id = Some(id)
+    }
+
+    fn progress(&self, event: progress::Event) {
+        if let Some(reporter) = &self.reporter {
+            reporter.report(event)
+        }
+    }
+
+    fn bytes_received(&self) -> u64 {
+        self.traffic.received()
+    }
 }
 
 fn io_other<E>(e: E) -> io::Error
@@ -19,6 +19,17 @@ pub struct Odb(Arc<odb::Odb<index::Shared<index::Stats>, window::Small<window::S
 
 impl Odb {
     pub fn open(git_dir: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::open_with(git_dir, None)
+    }
+
+    /// Open the object database at `git_dir`, falling back to the `remote`
+    /// store for objects not found on local disk.
+    ///
+    /// See [`odb::backend::Remote`].
+    pub fn open_with(
+        git_dir: impl AsRef<Path>,
+        remote: Option<Box<dyn odb::backend::Remote>>,
+    ) -> Result<Self, Error> {
         let git_dir = git_dir.as_ref();
         let loose = odb::backend::Loose::at(git_dir.join("objects"));
         let packed = {
@@ -27,7 +38,11 @@ impl Odb {
             odb::backend::Packed { index, data }
         };
 
-        Ok(Self(Arc::new(odb::Odb { loose, packed })))
+        Ok(Self(Arc::new(odb::Odb {
+            loose,
+            packed,
+            remote,
+        })))
     }
 }
 
@@ -13,7 +13,6 @@ use std::{
 };
 
 use bstr::{BStr, BString, ByteSlice as _, ByteVec as _};
-use either::Either;
 use itertools::Itertools as _;
 use link_crypto::PeerId;
 use link_git::{
@@ -29,6 +28,8 @@ use link_git::{
     },
 };
 
+use tracing::warn;
+
 use crate::{
     odb::Odb,
     refdb::{self, Applied, Policy, SymrefTarget, Update, Updated},
@@ -53,6 +54,9 @@ pub mod error {
 
         #[error(transparent)]
         Find(#[from] refs::file::find::Error),
+
+        #[error(transparent)]
+        Scan(#[from] Scan),
     }
 
     #[derive(Debug, Error)]
@@ -82,6 +86,12 @@ pub mod error {
         #[error("symref target {0} is itself a symref")]
         TargetSymbolic(BString),
 
+        #[error("symbolic ref {name} would form a cycle: {chain:?}")]
+        SymrefCycle { name: BString, chain: Vec<BString> },
+
+        #[error("symbolic ref {name} would resolve through more than {max} symrefs")]
+        SymrefDepth { name: BString, max: usize },
+
         #[error("expected symref {name} to point to {expected}, but got {actual}")]
         UnexpectedSymrefTarget {
             name: BString,
@@ -127,6 +137,10 @@ pub mod error {
     }
 }
 
+/// The maximum number of symbolic refs which may be followed to resolve a
+/// ref, same as `git`.
+pub const MAX_SYMREF_DEPTH: usize = 5;
+
 #[derive(Clone)]
 pub struct UserInfo {
     pub name: String,
@@ -187,6 +201,16 @@ impl<D> Refdb<D> {
     }
 }
 
+/// What to do about an [`Update`].
+enum Action<'a> {
+    /// The [`Update`] was rejected as per its [`Policy`].
+    Reject(Update<'a>),
+    /// The ref is already at the target of the [`Update`].
+    Unchanged(Update<'a>),
+    /// Apply the [`Update`] using the given edits.
+    Edit(Vec<RefEdit>),
+}
+
 impl<D: Odb> Refdb<D> {
     fn find_namespaced(&self, name: &FullName) -> Result<Option<ObjectId>, error::Find> {
         match self.snap.find(name.to_partial())? {
@@ -195,12 +219,69 @@ impl<D: Odb> Refdb<D> {
         }
     }
 
-    fn as_edits<'a>(
+    /// Find the symbolic refs in `edits` which form a cycle, or a chain
+    /// longer than [`MAX_SYMREF_DEPTH`], taking into account both the other
+    /// `edits` and the existing refs.
+    ///
+    /// Returns the names of the offending refs, along with the reason.
+    fn check_symrefs(
         &self,
-        mut update: Update<'a>,
-    ) -> Result<Either<Update<'a>, Vec<RefEdit>>, error::Tx> {
-        use Either::*;
+        edits: &HashMap<FullName, RefEdit>,
+    ) -> Result<HashMap<FullName, error::Tx>, error::Find> {
+        let symbolic_target = |name: &FullName| -> Result<Option<FullName>, error::Find> {
+            let target = match edits.get(name) {
+                Some(RefEdit {
+                    change: Change::Update { new, .. },
+                    ..
+                }) => Some(new.clone()),
+                _ => self.snap.find(name.to_partial())?.map(|r| r.target),
+            };
+            Ok(match target {
+                Some(Target::Symbolic(dst)) => Some(dst),
+                _ => None,
+            })
+        };
+
+        let mut offending = HashMap::new();
+        'edits: for (name, edit) in edits {
+            let mut next = match &edit.change {
+                Change::Update {
+                    new: Target::Symbolic(dst),
+                    ..
+                } => Some(dst.clone()),
+                _ => None,
+            };
+            let mut chain = vec![name.as_bstr().to_owned()];
+            while let Some(cur) = next {
+                chain.push(cur.as_bstr().to_owned());
+                if &cur == name {
+                    offending.insert(
+                        name.clone(),
+                        error::Tx::SymrefCycle {
+                            name: name.as_bstr().to_owned(),
+                            chain,
+                        },
+                    );
+                    continue 'edits;
+                }
+                next = symbolic_target(&cur)?;
+                if next.is_some() && chain.len() > MAX_SYMREF_DEPTH {
+                    offending.insert(
+                        name.clone(),
+                        error::Tx::SymrefDepth {
+                            name: name.as_bstr().to_owned(),
+                            max: MAX_SYMREF_DEPTH,
+                        },
+                    );
+                    continue 'edits;
+                }
+            }
+        }
+
+        Ok(offending)
+    }
 
+    fn as_edits<'a>(&self, mut update: Update<'a>) -> Result<Action<'a>, error::Tx> {
         match update {
             Update::Direct {
                 ref mut name,
@@ -211,7 +292,8 @@ impl<D: Odb> Refdb<D> {
                 let name = self.namespaced(name)?;
                 let tip = self.find_namespaced(&name)?;
                 match tip {
-                    None => Ok(Right(vec![RefEdit {
+                    Some(prev) if prev == target => Ok(Action::Unchanged(update)),
+                    None => Ok(Action::Edit(vec![RefEdit {
                         change: Change::Update {
                             log: LogChange {
                                 mode: RefLog::AndReference,
@@ -241,8 +323,8 @@ impl<D: Odb> Refdb<D> {
                                     new: target,
                                     cur: prev,
                                 }),
-                                Policy::Reject => Ok(Left(update)),
-                                Policy::Allow => Ok(Right(vec![RefEdit {
+                                Policy::Reject => Ok(Action::Reject(update)),
+                                Policy::Allow => Ok(Action::Edit(vec![RefEdit {
                                     change: Change::Update {
                                         log: LogChange {
                                             mode: RefLog::AndReference,
@@ -259,7 +341,7 @@ impl<D: Odb> Refdb<D> {
                                 }])),
                             }
                         } else {
-                            Ok(Right(vec![RefEdit {
+                            Ok(Action::Edit(vec![RefEdit {
                                 change: Change::Update {
                                     log: LogChange {
                                         mode: RefLog::AndReference,
@@ -297,7 +379,7 @@ impl<D: Odb> Refdb<D> {
                         Err(error::Tx::TypeChange(name.into_inner()))
                     },
                     Some(Target::Peeled(_prev)) if matches!(type_change, Policy::Reject) => {
-                        Ok(Left(update))
+                        Ok(Action::Reject(update))
                     },
 
                     _ => {
@@ -391,6 +473,13 @@ impl<D: Odb> Refdb<D> {
                                 }
 
                                 let dst_name = FullName::try_from(dst_name.qualified())?;
+                                let is_current = matches!(
+                                    &src,
+                                    Some(Target::Symbolic(cur)) if cur == &dst_name
+                                );
+                                if edits.is_empty() && is_current {
+                                    return Ok(Action::Unchanged(update));
+                                }
                                 edits.push(RefEdit {
                                     change: Change::Update {
                                         log: LogChange {
@@ -408,7 +497,7 @@ impl<D: Odb> Refdb<D> {
                             },
                         };
 
-                        Ok(Right(edits))
+                        Ok(Action::Edit(edits))
                     },
                 }
             },
@@ -460,26 +549,67 @@ impl<D: Odb> refdb::Refdb for Refdb<D> {
     where
         I: IntoIterator<Item = Update<'a>>,
     {
-        use Either::*;
-
         #[derive(Default)]
         struct Edits<'a> {
             rejected: Vec<Update<'a>>,
+            unchanged: Vec<Update<'a>>,
             // XXX: annoyingly, gitoxide refuses multiple edits of the same ref
             // in a transaction
             edits: HashMap<FullName, RefEdit>,
+            /// The [`Update::Symbolic`]s which resulted in `edits`, by the name
+            /// of the symbolic ref.
+            symbolic: Vec<(FullName, Update<'a>)>,
         }
 
-        let Edits { rejected, edits } = updates.into_iter().map(|up| self.as_edits(up)).fold_ok(
-            Edits::default(),
-            |mut es, e| {
+        let Edits {
+            mut rejected,
+            unchanged,
+            mut edits,
+            symbolic,
+        } = updates
+            .into_iter()
+            .map(|up| {
+                let symbolic = match up {
+                    Update::Symbolic { .. } => Some(up.clone()),
+                    Update::Direct { .. } => None,
+                };
+                self.as_edits(up).map(|action| (action, symbolic))
+            })
+            .fold_ok(Edits::default(), |mut es, (e, symbolic)| {
                 match e {
-                    Left(rej) => es.rejected.push(rej),
-                    Right(ed) => es.edits.extend(ed.into_iter().map(|e| (e.name.clone(), e))),
+                    Action::Reject(rej) => es.rejected.push(rej),
+                    Action::Unchanged(up) => es.unchanged.push(up),
+                    Action::Edit(ed) => {
+                        if let Some(up) = symbolic {
+                            let src = ed.iter().find(|e| {
+                                matches!(
+                                    e.change,
+                                    Change::Update {
+                                        new: Target::Symbolic(_),
+                                        ..
+                                    }
+                                )
+                            });
+                            if let Some(src) = src {
+                                es.symbolic.push((src.name.clone(), up));
+                            }
+                        }
+                        es.edits.extend(ed.into_iter().map(|e| (e.name.clone(), e)))
+                    },
                 }
                 es
-            },
-        )?;
+            })?;
+
+        // Reject only the offending symbolic refs. The implicit creation or
+        // fast-forward of their targets is a valid update on its own.
+        let offending = self.check_symrefs(&edits)?;
+        for (name, up) in symbolic {
+            if let Some(e) = offending.get(&name) {
+                warn!(err = %e, "rejecting symbolic ref update");
+                edits.remove(&name);
+                rejected.push(up);
+            }
+        }
         let tx = self
             .snap
             .transaction()
@@ -510,12 +640,33 @@ impl<D: Odb> refdb::Refdb for Refdb<D> {
         Ok(Applied {
             rejected,
             updated: applied,
+            unchanged,
         })
     }
 
     fn reload(&mut self) -> Result<(), Self::ReloadError> {
         self.reload()
     }
+
+    fn find_case_folded(
+        &self,
+        refname: impl AsRef<BStr>,
+    ) -> Result<Option<BString>, Self::FindError> {
+        let refname = refname.as_ref();
+        let parent = match refname.rfind_byte(b'/').map(|i| refname[..i].to_str()) {
+            Some(Ok(parent)) => parent,
+            _ => return Ok(None),
+        };
+        let folded = refname.to_lowercase();
+        for item in refdb::RefScan::scan(self, Some(parent))? {
+            let (name, _) = item?;
+            if name.as_bstr() != refname && name.to_lowercase() == folded {
+                return Ok(Some(name));
+            }
+        }
+
+        Ok(None)
+    }
 }
 
 impl<D: Odb> Odb for Refdb<D> {
@@ -0,0 +1,503 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Recording of protocol sessions, and replaying them offline.
+//!
+//! A [`Recorder`] captures the bytes exchanged on every stream a
+//! [`super::Network`] opens (see [`super::Network::with_recorder`]), including
+//! the ref advertisements, the negotiation and the packfile. The resulting
+//! file can be attached to a bug report, and served back by a [`Replay`]
+//! [`Connection`] to reproduce the fetch without network access.
+//!
+//! The file format is a header line `radicle-link session v1\n`, followed by
+//! any number of chunks:
+//!
+//! ```text
+//! stream: u32 BE | direction: u8 (0 = send, 1 = recv) | len: u32 BE | bytes
+//! ```
+//!
+//! where `stream` identifies the stream the chunk belongs to. Every chunk is
+//! flushed to the file as soon as it is recorded, so a session is available
+//! up to the point where the process crashed.
+//!
+//! Streams are not replayed in the order they were opened, which varies when
+//! streams are opened concurrently (e.g. by
+//! [`crate::FetchState::step_concurrently`], or when
+//! [`Connection::preopen_fetch_stream`] is set). Instead, a stream is keyed
+//! by the request sent on it, i.e. the pkt-lines up to the first flush-pkt,
+//! which names the command and its arguments.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read as _, Write as _},
+    path::Path,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use futures_lite::io::{AsyncRead, AsyncWrite, Cursor};
+use link_git::protocol::trace::Direction;
+use parking_lot::Mutex;
+
+use super::Connection;
+
+const MAGIC: &[u8] = b"radicle-link session v1\n";
+
+/// Writes the bytes passing through the streams it wraps to a session file.
+///
+/// Cloning a [`Recorder`] yields a handle to the same file.
+#[derive(Clone)]
+pub struct Recorder {
+    out: Arc<Mutex<BufWriter<File>>>,
+    streams: Arc<AtomicU32>,
+}
+
+impl Recorder {
+    /// Create (or truncate) the session file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        Ok(Self {
+            out: Arc::new(Mutex::new(out)),
+            streams: Arc::new(AtomicU32::new(0)),
+        })
+    }
+
+    /// Wrap the `recv` and `send` halves of a newly opened stream.
+    pub fn wrap<R, W>(&self, recv: R, send: W) -> (Recorded<R>, Recorded<W>) {
+        let stream = self.streams.fetch_add(1, Ordering::Relaxed);
+        (
+            Recorded::new(recv, Some(Tap::new(self, stream, Direction::Recv))),
+            Recorded::new(send, Some(Tap::new(self, stream, Direction::Send))),
+        )
+    }
+
+    fn chunk(&self, stream: u32, direction: Direction, bytes: &[u8]) {
+        let direction = match direction {
+            Direction::Send => 0u8,
+            Direction::Recv => 1u8,
+        };
+        let mut out = self.out.lock();
+        let res = out
+            .write_all(&stream.to_be_bytes())
+            .and_then(|()| out.write_all(&[direction]))
+            .and_then(|()| out.write_all(&(bytes.len() as u32).to_be_bytes()))
+            .and_then(|()| out.write_all(bytes))
+            .and_then(|()| out.flush());
+        if let Err(e) = res {
+            warn!(err = %e, "failed to record session chunk");
+        }
+    }
+}
+
+struct Tap {
+    recorder: Recorder,
+    stream: u32,
+    direction: Direction,
+}
+
+impl Tap {
+    fn new(recorder: &Recorder, stream: u32, direction: Direction) -> Self {
+        Self {
+            recorder: recorder.clone(),
+            stream,
+            direction,
+        }
+    }
+
+    fn feed(&self, bytes: &[u8]) {
+        if !bytes.is_empty() {
+            self.recorder.chunk(self.stream, self.direction, bytes)
+        }
+    }
+}
+
+/// An [`AsyncRead`] or [`AsyncWrite`] recording the bytes passing through it
+/// to a [`Recorder`].
+///
+/// If no [`Recorder`] is given, this is a no-op wrapper.
+pub struct Recorded<S> {
+    inner: S,
+    tap: Option<Tap>,
+}
+
+impl<S> Recorded<S> {
+    fn new(inner: S, tap: Option<Tap>) -> Self {
+        Self { inner, tap }
+    }
+
+    /// Wrap `inner` without recording anything.
+    pub fn passthrough(inner: S) -> Self {
+        Self::new(inner, None)
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<R> AsyncRead for Recorded<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(n)), Some(tap)) = (&res, &this.tap) {
+            tap.feed(&buf[..*n])
+        }
+        res
+    }
+}
+
+impl<W> AsyncWrite for Recorded<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(n)), Some(tap)) = (&res, &this.tap) {
+            tap.feed(&buf[..*n])
+        }
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+/// The bytes exchanged on a single recorded stream.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stream {
+    pub send: Vec<u8>,
+    pub recv: Vec<u8>,
+}
+
+/// A recorded session, see [`Recorder`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Session {
+    pub streams: Vec<Stream>,
+}
+
+impl Session {
+    /// Load the session file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid_data("not a session file"));
+        }
+
+        let mut streams = BTreeMap::<u32, Stream>::new();
+        loop {
+            let mut head = [0; 9];
+            match file.read_exact(&mut head[..1]) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                res => res?,
+            }
+            file.read_exact(&mut head[1..])?;
+
+            let stream = u32::from_be_bytes([head[0], head[1], head[2], head[3]]);
+            let len = u32::from_be_bytes([head[5], head[6], head[7], head[8]]) as usize;
+            let stream = streams.entry(stream).or_default();
+            let buf = match head[4] {
+                0 => &mut stream.send,
+                1 => &mut stream.recv,
+                x => return Err(invalid_data(format!("invalid direction {}", x))),
+            };
+            let start = buf.len();
+            buf.resize(start + len, 0);
+            file.read_exact(&mut buf[start..])?;
+        }
+
+        Ok(Self {
+            streams: streams.into_iter().map(|(_, stream)| stream).collect(),
+        })
+    }
+}
+
+/// A [`Connection`] serving a recorded [`Session`].
+///
+/// A stream is served once the request written to it is complete, by the
+/// first unused recorded stream which was sent the same request. The read
+/// half then yields the bytes the remote end sent originally.
+///
+/// By default, whatever else is written to a stream is discarded, and a
+/// stream which is read from before a recorded request was written to it is
+/// served the first unused stream. If [`Replay::strict`] is set, the writes
+/// must match the recorded ones, so changes to the requests made are
+/// detected.
+pub struct Replay {
+    streams: Arc<Mutex<Vec<Stream>>>,
+    strict: bool,
+}
+
+impl Replay {
+    pub fn new(session: Session) -> Self {
+        Self {
+            streams: Arc::new(Mutex::new(session.streams)),
+            strict: false,
+        }
+    }
+
+    /// Load a [`Replay`] from the session file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Session::load(path).map(Self::new)
+    }
+
+    /// Fail writes which deviate from the recorded session.
+    pub fn strict(self) -> Self {
+        Self {
+            strict: true,
+            ..self
+        }
+    }
+}
+
+#[async_trait]
+impl Connection for Replay {
+    type Read = Response;
+    type Write = Expect;
+    type Error = io::Error;
+
+    async fn open_stream(&self) -> Result<(Self::Read, Self::Write), Self::Error> {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        Ok((
+            Response {
+                streams: (!self.strict).then(|| self.streams.clone()),
+                slot: slot.clone(),
+            },
+            Expect {
+                streams: self.streams.clone(),
+                strict: self.strict,
+                slot,
+                request: Some(Vec::new()),
+                expected: None,
+                pos: 0,
+            },
+        ))
+    }
+}
+
+/// The state shared between the halves of a [`Replay`] stream.
+#[derive(Default)]
+struct Slot {
+    recv: Option<Cursor<Vec<u8>>>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Slot {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake()
+        }
+    }
+}
+
+/// The read half of a [`Replay`] stream.
+///
+/// Reads are pending until the request has been written to the [`Expect`]
+/// half, unless the [`Replay`] is not strict.
+pub struct Response {
+    /// Where to pick a stream from if nothing was written yet.
+    streams: Option<Arc<Mutex<Vec<Stream>>>>,
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl AsyncRead for Response {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let mut guard = this.slot.lock();
+        let slot = &mut *guard;
+        if slot.recv.is_none() && !slot.closed {
+            if let Some(streams) = &this.streams {
+                let mut streams = streams.lock();
+                if streams.is_empty() {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "no more recorded streams",
+                    )));
+                }
+                slot.recv = Some(Cursor::new(streams.remove(0).recv));
+            }
+        }
+        match slot.recv.as_mut() {
+            Some(recv) => Pin::new(recv).poll_read(cx, buf),
+            None if slot.closed => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "no recorded stream for request",
+            ))),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+/// The write half of a [`Replay`] stream.
+pub struct Expect {
+    streams: Arc<Mutex<Vec<Stream>>>,
+    strict: bool,
+    slot: Arc<Mutex<Slot>>,
+    /// The bytes written until a recorded stream was picked.
+    request: Option<Vec<u8>>,
+    expected: Option<Vec<u8>>,
+    pos: usize,
+}
+
+impl Expect {
+    /// Pick the recorded stream to serve once the request is complete.
+    fn pick(&mut self) -> io::Result<()> {
+        let request = match self.request.as_ref() {
+            Some(written) => match request_len(written) {
+                Some(len) => &written[..len],
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        let picked = {
+            let mut streams = self.streams.lock();
+            match streams
+                .iter()
+                .position(|stream| stream.send.starts_with(request))
+            {
+                Some(i) => Some(streams.remove(i)),
+                None if !self.strict && !streams.is_empty() => Some(streams.remove(0)),
+                None => None,
+            }
+        };
+        let slot = self.slot.clone();
+        let mut slot = slot.lock();
+        let res = match picked {
+            None => {
+                slot.closed = true;
+                Err(invalid_data("no recorded stream for request"))
+            },
+            Some(Stream { send, recv }) => {
+                slot.recv = Some(Cursor::new(recv));
+                self.pos = 0;
+                self.expected = self.strict.then(|| send);
+                let written = self.request.take().unwrap_or_default();
+                self.check(&written)
+            },
+        };
+        slot.wake();
+        res
+    }
+
+    fn check(&mut self, buf: &[u8]) -> io::Result<()> {
+        if let Some(expected) = &self.expected {
+            let end = self.pos + buf.len();
+            if expected.get(self.pos..end) != Some(buf) {
+                return Err(invalid_data(format!(
+                    "write at offset {} deviates from the recorded session",
+                    self.pos
+                )));
+            }
+        }
+        self.pos += buf.len();
+        Ok(())
+    }
+}
+
+impl AsyncWrite for Expect {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        if this.request.is_some() && this.slot.lock().recv.is_some() {
+            // Picked by a read already
+            this.request = None;
+        }
+        let res = match this.request.as_mut() {
+            Some(request) => {
+                request.extend_from_slice(buf);
+                this.pick()
+            },
+            None => this.check(buf),
+        };
+        Poll::Ready(res.map(|()| buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for Expect {
+    fn drop(&mut self) {
+        let mut slot = self.slot.lock();
+        slot.closed = true;
+        slot.wake();
+    }
+}
+
+/// The length of the request at the start of `buf`, i.e. up to and including
+/// the first flush-pkt, or `None` if the request is incomplete.
+///
+/// If `buf` does not consist of pkt-lines, all of it is taken to be the
+/// request.
+fn request_len(buf: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    loop {
+        let hex = buf.get(pos..pos + 4)?;
+        let len = match std::str::from_utf8(hex)
+            .ok()
+            .and_then(|hex| usize::from_str_radix(hex, 16).ok())
+        {
+            Some(len) => len,
+            None => return Some(buf.len()),
+        };
+        match len {
+            0 => return Some(pos + 4),
+            // delim-pkt, response-end-pkt
+            1 | 2 => pos += 4,
+            3 => return Some(buf.len()),
+            _ if buf.len() < pos + len => return None,
+            _ => pos += len,
+        }
+    }
+}
+
+fn invalid_data<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
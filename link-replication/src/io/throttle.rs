@@ -0,0 +1,318 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Bandwidth limits for the streams of a [`super::Network`].
+//!
+//! A [`Throttle`] caps the rate at which all the streams it wraps can be read
+//! from and written to, taken together, and counts the bytes transferred.
+//! Clones of a [`Throttle`] share both the limits and the [`Traffic`], so a
+//! single [`Throttle`] can cap the replication bandwidth of several
+//! [`super::Network`]s at once.
+//!
+//! The [`Metered`] stream halves are the only place bytes are counted: they
+//! also maintain the [`Traffic`] of the [`super::Network`] they belong to,
+//! and report [`Event::Received`].
+
+use std::{
+    future::Future as _,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use async_io::Timer;
+use futures_lite::{
+    io::{AsyncRead, AsyncWrite},
+    ready,
+};
+use parking_lot::Mutex;
+
+use crate::progress::{Event, Reporter};
+
+/// Bandwidth limits in bytes per second, unlimited by default.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Bandwidth {
+    /// Limit the rate at which streams are written to.
+    pub up: Option<u64>,
+    /// Limit the rate at which streams are read from.
+    pub down: Option<u64>,
+}
+
+/// The number of bytes transferred on a set of [`Metered`] streams.
+#[derive(Clone, Debug, Default)]
+pub struct Traffic {
+    sent: Arc<AtomicU64>,
+    received: Arc<AtomicU64>,
+}
+
+impl Traffic {
+    /// Bytes written to streams so far.
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Bytes read from streams so far.
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+}
+
+/// Token bucket of bytes, refilled continuously.
+///
+/// The bucket holds up to 100ms worth of bytes, so transfers are spread
+/// evenly rather than in bursts of a second.
+#[derive(Debug)]
+struct Bucket {
+    per_sec: u64,
+    available: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(per_sec: u64) -> Self {
+        let per_sec = per_sec.max(1);
+        Self {
+            per_sec,
+            available: Self::capacity_of(per_sec),
+            last: Instant::now(),
+        }
+    }
+
+    fn capacity_of(per_sec: u64) -> f64 {
+        (per_sec as f64 / 10.0).max(1.0)
+    }
+
+    /// Reserve up to `want` bytes to be transferred now, or return how long to
+    /// wait until some are available.
+    ///
+    /// Reserving while holding the lock ensures concurrent streams can't be
+    /// granted the same bytes.
+    fn reserve(&mut self, want: usize, now: Instant) -> Result<usize, Duration> {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.available =
+            (self.available + elapsed * self.per_sec as f64).min(Self::capacity_of(self.per_sec));
+        self.last = now;
+
+        let reserved = want.min(self.available as usize);
+        if reserved > 0 || want == 0 {
+            self.available -= reserved as f64;
+            Ok(reserved)
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.available) / self.per_sec as f64,
+            ))
+        }
+    }
+
+    /// Return `n` reserved bytes which were not transferred.
+    fn refund(&mut self, n: usize) {
+        self.available = (self.available + n as f64).min(Self::capacity_of(self.per_sec));
+    }
+}
+
+/// Limits the [`Bandwidth`] of the streams it wraps, see the module
+/// documentation.
+#[derive(Clone, Debug, Default)]
+pub struct Throttle {
+    up: Option<Arc<Mutex<Bucket>>>,
+    down: Option<Arc<Mutex<Bucket>>>,
+    traffic: Traffic,
+}
+
+impl Throttle {
+    pub fn new(bandwidth: Bandwidth) -> Self {
+        let bucket = |limit: Option<u64>| limit.map(|l| Arc::new(Mutex::new(Bucket::new(l))));
+        Self {
+            up: bucket(bandwidth.up),
+            down: bucket(bandwidth.down),
+            traffic: Traffic::default(),
+        }
+    }
+
+    pub fn traffic(&self) -> &Traffic {
+        &self.traffic
+    }
+
+    /// Throttle the read half `recv` and the write half `send` of a stream.
+    pub fn wrap<R, W>(&self, recv: R, send: W) -> (Metered<R>, Metered<W>) {
+        self.wrap_counted(recv, send, None, None)
+    }
+
+    /// Like [`Throttle::wrap`], but also count the bytes transferred in
+    /// `traffic`, and report the bytes read from `recv` as
+    /// [`Event::Received`] if there is a `reporter`.
+    pub(crate) fn wrap_counted<R, W>(
+        &self,
+        recv: R,
+        send: W,
+        traffic: Option<&Traffic>,
+        reporter: Option<Reporter>,
+    ) -> (Metered<R>, Metered<W>) {
+        (
+            Metered {
+                inner: recv,
+                bucket: self.down.clone(),
+                granted: 0,
+                delay: None,
+                counts: Counts {
+                    shared: self.traffic.received.clone(),
+                    own: traffic.map(|t| t.received.clone()),
+                    stream: 0,
+                    reporter,
+                },
+            },
+            Metered {
+                inner: send,
+                bucket: self.up.clone(),
+                granted: 0,
+                delay: None,
+                counts: Counts {
+                    shared: self.traffic.sent.clone(),
+                    own: traffic.map(|t| t.sent.clone()),
+                    stream: 0,
+                    reporter: None,
+                },
+            },
+        )
+    }
+}
+
+/// The counters a [`Metered`] stream half adds the bytes it transfers to.
+struct Counts {
+    /// The [`Traffic`] of the [`Throttle`].
+    shared: Arc<AtomicU64>,
+    /// The [`Traffic`] of the [`super::Network`] owning the stream, if any.
+    own: Option<Arc<AtomicU64>>,
+    /// The bytes transferred on this stream half.
+    stream: u64,
+    reporter: Option<Reporter>,
+}
+
+impl Counts {
+    fn add(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let n = n as u64;
+        self.stream += n;
+        self.shared.fetch_add(n, Ordering::Relaxed);
+        if let Some(own) = &self.own {
+            own.fetch_add(n, Ordering::Relaxed);
+        }
+        if let Some(reporter) = &self.reporter {
+            reporter.report(Event::Received { bytes: self.stream });
+        }
+    }
+}
+
+/// A stream half wrapped by a [`Throttle`], which also counts the bytes
+/// transferred.
+pub struct Metered<S> {
+    inner: S,
+    bucket: Option<Arc<Mutex<Bucket>>>,
+    /// Bytes reserved from the `bucket`, but not yet transferred.
+    granted: usize,
+    delay: Option<Timer>,
+    counts: Counts,
+}
+
+impl<S> Metered<S> {
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Wait until some of `want` bytes may be transferred, and return how
+    /// many.
+    ///
+    /// The bytes stay reserved until [`Metered::transferred`] is called, even
+    /// if the inner stream isn't ready.
+    fn poll_allowance(&mut self, cx: &mut Context<'_>, want: usize) -> Poll<usize> {
+        let Self {
+            bucket,
+            granted,
+            delay,
+            ..
+        } = self;
+        let bucket = match bucket {
+            None => return Poll::Ready(want),
+            Some(bucket) => bucket,
+        };
+        if *granted > 0 {
+            return Poll::Ready(want.min(*granted));
+        }
+        loop {
+            if let Some(timer) = delay {
+                ready!(Pin::new(timer).poll(cx));
+                *delay = None;
+            }
+            match bucket.lock().reserve(want, Instant::now()) {
+                Ok(n) => {
+                    *granted = n;
+                    return Poll::Ready(n);
+                },
+                Err(wait) => *delay = Some(Timer::after(wait)),
+            }
+        }
+    }
+
+    /// Count `n` transferred bytes, and refund the rest of the reservation.
+    fn transferred(&mut self, n: usize) {
+        let unused = self.granted.saturating_sub(n);
+        self.granted = 0;
+        if unused > 0 {
+            if let Some(bucket) = &self.bucket {
+                bucket.lock().refund(unused);
+            }
+        }
+        self.counts.add(n);
+    }
+}
+
+impl<R> AsyncRead for Metered<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let len = ready!(this.poll_allowance(cx, buf.len()));
+        let res = ready!(Pin::new(&mut this.inner).poll_read(cx, &mut buf[..len]));
+        this.transferred(*res.as_ref().unwrap_or(&0));
+        Poll::Ready(res)
+    }
+}
+
+impl<W> AsyncWrite for Metered<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let len = ready!(this.poll_allowance(cx, buf.len()));
+        let res = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..len]));
+        this.transferred(*res.as_ref().unwrap_or(&0));
+        Poll::Ready(res)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
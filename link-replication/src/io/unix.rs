@@ -0,0 +1,47 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use async_net::unix::UnixStream;
+
+use super::Connection;
+
+/// A [`Connection`] to a replication endpoint listening on a unix domain
+/// socket.
+///
+/// This allows processes on the same machine to replicate from a daemon without
+/// running the p2p stack themselves. Every stream is a new connection to the
+/// socket, on which the daemon is expected to serve a single exchange of the
+/// `git` protocol, e.g. using [`link_git::protocol::upload_pack`].
+#[derive(Clone, Debug)]
+pub struct Unix {
+    path: PathBuf,
+}
+
+impl Unix {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[async_trait]
+impl Connection for Unix {
+    type Read = UnixStream;
+    type Write = UnixStream;
+    type Error = io::Error;
+
+    async fn open_stream(&self) -> Result<(Self::Read, Self::Write), Self::Error> {
+        let stream = UnixStream::connect(&self.path).await?;
+        Ok((stream.clone(), stream))
+    }
+}
@@ -7,7 +7,7 @@
 #![warn(clippy::extra_unused_lifetimes)]
 #![deny(rustdoc::broken_intra_doc_links)]
 
-use std::fmt::Debug;
+use std::{fmt::Debug, time::Instant};
 
 #[macro_use]
 extern crate async_trait;
@@ -17,14 +17,26 @@ extern crate tracing;
 use link_crypto::PeerId;
 use radicle_std_ext::prelude::*;
 
+pub mod correlation;
+pub use correlation::CorrelationId;
+
 pub mod error;
 pub use error::Error;
 
 pub mod fetch;
+pub use fetch::FetchSpec;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod haves;
 pub mod internal;
 pub mod io;
 pub mod peek;
+pub mod progress;
 pub mod refs;
+pub mod report;
+pub use report::Report;
+#[cfg(feature = "sim")]
+pub mod sim;
 
 mod eval;
 
@@ -32,28 +44,28 @@ mod ids;
 pub use ids::{Identities, LocalIdentity, Urn, VerifiedIdentity};
 
 mod odb;
-pub use odb::Odb;
+pub use odb::{Object, Odb};
 
-mod refdb;
-pub use refdb::{Applied, Policy, RefScan, Refdb, SymrefTarget, Update, Updated};
+pub mod refdb;
+pub use refdb::{Applied, AsyncRefdb, Policy, RefScan, Refdb, SymrefTarget, Update, Updated};
 
 mod sigrefs;
-pub use sigrefs::{SignedRefs, Sigrefs};
+pub use sigrefs::{AsyncSignedRefs, Rollback, SignedRefs, Sigrefs};
 
 mod state;
 use state::FetchState;
 
 mod success;
-pub use success::Success;
+pub use success::{DelegationChanges, Success};
 
 mod track;
-pub use track::{Rel as TrackingRel, Tracking};
+pub use track::{AsyncTracking, Rel as TrackingRel, Tracking};
 
 mod transmit;
 pub use transmit::{FilteredRef, Negotiation, Net, SkippedFetch, WantsHaves};
 
 mod validation;
-pub use validation::validate;
+pub use validation::{validate, validate_peers, Validate, ValidationPolicy};
 
 // Re-exports
 pub use link_git::{
@@ -80,80 +92,247 @@ impl Default for FetchLimit {
     }
 }
 
-#[tracing::instrument(skip(cx, whoami), fields(local_id = %LocalPeer::id(cx)))]
-pub fn pull<C>(
+/// Fetch updates for the local URN from `remote_id`.
+///
+/// Network I/O is performed via [`Net`], and is fully asynchronous. So are
+/// the storage updates, via [`AsyncRefdb`], [`AsyncSignedRefs`] and
+/// [`AsyncTracking`], which allows implementations to run them off the async
+/// executor. The remaining context traits are synchronous, and only used for
+/// lookups.
+///
+/// Every invocation is assigned a new [`CorrelationId`], see
+/// [`Success::correlation_id`] and [`error::Replicate`].
+///
+/// Inconsistencies found when validating the fetched refs are handled
+/// according to `policy`, see [`ValidationPolicy`].
+///
+/// If the run fails before the fetched refs are applied, the peers it started
+/// to track are untracked again, see [`AsyncTracking::untrack`].
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    skip(cx, whoami),
+    fields(local_id = %LocalPeer::id(cx), correlation_id = tracing::field::Empty)
+)]
+pub async fn pull<C>(
     cx: &mut C,
     limit: FetchLimit,
+    spec: FetchSpec,
     remote_id: PeerId,
     whoami: Option<LocalIdentity>,
-) -> Result<Success<<C as Identities>::Urn>, Error>
+    validation: Validate,
+    policy: ValidationPolicy,
+    rollback: Rollback,
+) -> Result<Success<<C as Identities>::Urn>, error::Replicate>
 where
     C: Identities
         + LocalPeer
         + Net
-        + Refdb
-        + SignedRefs<Oid = <C as Identities>::Oid>
-        + Tracking<Urn = <C as Identities>::Urn>,
+        + Odb
+        + AsyncRefdb
+        + AsyncSignedRefs<Oid = <C as Identities>::Oid>
+        + AsyncTracking<Urn = <C as Identities>::Urn>,
     <C as Identities>::Oid: Debug + PartialEq + Send + Sync + 'static,
     <C as Identities>::Urn: Clone + Debug + Ord,
 {
-    if LocalPeer::id(cx) == &remote_id {
-        return Err("cannot replicate from self".into());
+    let id = correlate(Some(&mut *cx));
+    let started = Instant::now();
+    let received = Net::bytes_received(cx);
+    let res: Result<_, error::Failure> = async {
+        if LocalPeer::id(cx) == &remote_id {
+            return Err(error::Failure::SelfReplication);
+        }
+        let anchor = ids::current(cx)
+            .map_err(error::Failure::verification)?
+            .ok_or(error::Failure::MissingRadId)?;
+        eval::pull(
+            &mut FetchState::default(),
+            cx,
+            id,
+            limit,
+            spec,
+            anchor,
+            remote_id,
+            whoami,
+            validation,
+            policy,
+            rollback,
+        )
+        .await
     }
-    let anchor = ids::current(cx)?.ok_or("pull: missing `rad/id`")?;
-    eval::pull(
-        &mut FetchState::default(),
-        cx,
-        limit,
-        anchor,
-        remote_id,
-        whoami,
-    )
+    .await;
+    res.map(|success| success.measured(started, Net::bytes_received(cx).saturating_sub(received)))
+        .map_err(|failure| error::Replicate { id, failure })
 }
 
-#[tracing::instrument(skip(cx, whoami), fields(local_id = %LocalPeer::id(cx)))]
-pub fn clone<C>(
+/// Fetch the local URN from `remote_id` for the first time.
+///
+/// Network I/O is performed via [`Net`], and is fully asynchronous. So are
+/// the storage updates, via [`AsyncRefdb`], [`AsyncSignedRefs`] and
+/// [`AsyncTracking`], which allows implementations to run them off the async
+/// executor. The remaining context traits are synchronous, and only used for
+/// lookups.
+///
+/// Every invocation is assigned a new [`CorrelationId`], see
+/// [`Success::correlation_id`] and [`error::Replicate`].
+///
+/// Inconsistencies found when validating the fetched refs are handled
+/// according to `policy`, see [`ValidationPolicy`].
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    skip(cx, whoami),
+    fields(local_id = %LocalPeer::id(cx), correlation_id = tracing::field::Empty)
+)]
+pub async fn clone<C>(
     cx: &mut C,
     limit: FetchLimit,
+    spec: FetchSpec,
     remote_id: PeerId,
     whoami: Option<LocalIdentity>,
-) -> Result<Success<<C as Identities>::Urn>, Error>
+    validation: Validate,
+    policy: ValidationPolicy,
+    rollback: Rollback,
+) -> Result<Success<<C as Identities>::Urn>, error::Replicate>
 where
     C: Identities
         + LocalPeer
         + Net
-        + Refdb
-        + SignedRefs<Oid = <C as Identities>::Oid>
-        + Tracking<Urn = <C as Identities>::Urn>,
+        + Odb
+        + AsyncRefdb
+        + AsyncSignedRefs<Oid = <C as Identities>::Oid>
+        + AsyncTracking<Urn = <C as Identities>::Urn>,
     <C as Identities>::Oid: Debug + PartialEq + Send + Sync + 'static,
     <C as Identities>::Urn: Clone + Debug + Ord,
 {
-    info!("fetching initial verification refs");
-    if LocalPeer::id(cx) == &remote_id {
-        return Err("cannot replicate from self".into());
+    let id = correlate(Some(&mut *cx));
+    let started = Instant::now();
+    let received = Net::bytes_received(cx);
+    let res: Result<_, error::Failure> = async {
+        info!("fetching initial verification refs");
+        if LocalPeer::id(cx) == &remote_id {
+            return Err(error::Failure::SelfReplication);
+        }
+        let mut state = FetchState::default();
+        let (_, res) = state
+            .step(
+                cx,
+                peek::ForClone {
+                    remote_id,
+                    limit: limit.peek,
+                },
+            )
+            .await?;
+        let anchor = match res {
+            Some(SkippedFetch::NoMatchingRefs) => return Err(error::Failure::NoVerificationRefs),
+            Some(SkippedFetch::WantNothing) => ids::of(cx, &remote_id)
+                .map_err(error::Failure::verification)?
+                .expect("BUG: wanted nothing, but don't have it either"),
+            None => Identities::verify(
+                cx,
+                state
+                    .id_tip(&remote_id)
+                    .expect("BUG: peek step must ensure we got a rad/id ref"),
+                state.lookup_delegations(&remote_id),
+            )
+            .map_err(error::Failure::verification)?,
+        };
+        let peeked = started.elapsed();
+        eval::pull(
+            &mut state, cx, id, limit, spec, anchor, remote_id, whoami, validation, policy,
+            rollback,
+        )
+        .await
+        .map(|mut success| {
+            success.timings.peek += peeked;
+            success
+        })
     }
-    let mut state = FetchState::default();
-    let (_, res) = state.step(
-        cx,
-        peek::ForClone {
-            remote_id,
-            limit: limit.peek,
-        },
-    )?;
-    let anchor = match res {
-        Some(SkippedFetch::NoMatchingRefs) => {
-            return Err("remote did not advertise verification refs".into())
-        },
-        Some(SkippedFetch::WantNothing) => {
-            ids::of(cx, &remote_id)?.expect("BUG: wanted nothing, but don't have it either")
-        },
-        None => Identities::verify(
-            cx,
-            state
-                .id_tip(&remote_id)
-                .expect("BUG: peek step must ensure we got a rad/id ref"),
-            state.lookup_delegations(&remote_id),
-        )?,
+    .await;
+    res.map(|success| success.measured(started, Net::bytes_received(cx).saturating_sub(received)))
+        .map_err(|failure| error::Replicate { id, failure })
+}
+
+/// Fetch updates for the local URN from all of `remotes` at once.
+///
+/// Each remote is paired with the [`Net`] to reach it. The verification refs
+/// of all remotes are fetched concurrently, and the refs of every peer are
+/// then fetched from the remote which has the most recent signed refs of that
+/// peer. All fetched refs are validated and applied in a single pass, ie.
+/// either the refs from all `remotes` are updated, or none.
+///
+/// Otherwise, this behaves like [`pull`]. Note that if fetching from any of
+/// the `remotes` fails, the whole run fails.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    skip(cx, remotes, whoami),
+    fields(local_id = %LocalPeer::id(cx), correlation_id = tracing::field::Empty)
+)]
+pub async fn pull_many<C, N>(
+    cx: &mut C,
+    remotes: &mut [(PeerId, N)],
+    limit: FetchLimit,
+    spec: FetchSpec,
+    whoami: Option<LocalIdentity>,
+    validation: Validate,
+    policy: ValidationPolicy,
+    rollback: Rollback,
+) -> Result<Success<<C as Identities>::Urn>, error::Replicate>
+where
+    C: Identities
+        + LocalPeer
+        + Odb
+        + AsyncRefdb
+        + AsyncSignedRefs<Oid = <C as Identities>::Oid>
+        + AsyncTracking<Urn = <C as Identities>::Urn>,
+    <C as Identities>::Oid: Debug + PartialEq + Send + Sync + 'static,
+    <C as Identities>::Urn: Clone + Debug + Ord,
+    N: Net,
+{
+    let id = correlate(remotes.iter_mut().map(|(_, net)| net));
+    let remotes: &[(PeerId, N)] = remotes;
+    let started = Instant::now();
+    let bytes_received = |remotes: &[(PeerId, N)]| {
+        remotes
+            .iter()
+            .map(|(_, net)| Net::bytes_received(net))
+            .sum::<u64>()
     };
-    eval::pull(&mut state, cx, limit, anchor, remote_id, whoami)
+    let received = bytes_received(remotes);
+    let res: Result<_, error::Failure> = async {
+        if remotes
+            .iter()
+            .any(|(remote_id, _)| LocalPeer::id(cx) == remote_id)
+        {
+            return Err(error::Failure::SelfReplication);
+        }
+        let anchor = ids::current(cx)
+            .map_err(error::Failure::verification)?
+            .ok_or(error::Failure::MissingRadId)?;
+        eval::pull_many(
+            &mut FetchState::default(),
+            cx,
+            id,
+            limit,
+            spec,
+            anchor,
+            remotes,
+            whoami,
+            validation,
+            policy,
+            rollback,
+        )
+        .await
+    }
+    .await;
+    res.map(|success| success.measured(started, bytes_received(remotes).saturating_sub(received)))
+        .map_err(|failure| error::Replicate { id, failure })
+}
+
+/// Assign a new [`CorrelationId`] to the current replication run.
+fn correlate<'a, N: Net + 'a>(nets: impl IntoIterator<Item = &'a mut N>) -> CorrelationId {
+    let id = CorrelationId::new();
+    tracing::Span::current().record("correlation_id", &tracing::field::display(id));
+    for net in nets {
+        Net::correlate(net, id);
+    }
+    id
 }
@@ -17,6 +17,7 @@ use crate::{
     Identities,
     LocalPeer,
     PeerId,
+    Rollback,
     SignedRefs,
     Tracking,
     Update,
@@ -34,6 +35,7 @@ pub fn for_fetch<C>(
     limit: u64,
     anchor: &C::VerifiedIdentity,
     remote_id: PeerId,
+    rollback: Rollback,
 ) -> Result<ForFetch, error::Error>
 where
     C: Identities + LocalPeer + SignedRefs + Tracking<Urn = <C as Identities>::Urn>,
@@ -64,6 +66,7 @@ where
         delegates: delegates.into_inner(),
         tracked,
         limit,
+        rollback,
     })
 }
 
@@ -112,7 +115,11 @@ fn guard_required<'a, 'b, 'c>(
     }
 }
 
-fn mk_ref_update<T, Urn>(fref: &FilteredRef<T>) -> Option<Update<'_>>
+/// Make the update for a `rad/` ref.
+///
+/// `sigrefs_no_ff` is the [`refdb::Policy`] for non-fast-forward updates of
+/// `rad/signed_refs`.
+fn mk_ref_update<T, Urn>(fref: &FilteredRef<T>, sigrefs_no_ff: refdb::Policy) -> Option<Update<'_>>
 where
     Urn: ids::Urn,
 {
@@ -122,12 +129,18 @@ where
 
     let track_as = Cow::from(refs::remote_tracking(&fref.remote_id, fref.name.as_bstr()));
     fref.parsed.as_ref().left().and_then(|rad| match rad {
-        Rad::Id | Rad::SignedRefs => Some(Update::Direct {
+        Rad::Id => Some(Update::Direct {
             name: track_as,
             target: fref.tip,
             no_ff: Policy::Abort,
         }),
 
+        Rad::SignedRefs => Some(Update::Direct {
+            name: track_as,
+            target: fref.tip,
+            no_ff: sigrefs_no_ff,
+        }),
+
         Rad::Ids { urn } => Some(Update::Symbolic {
             name: track_as,
             target: SymrefTarget {
@@ -14,11 +14,13 @@ use crate::{
     error,
     ids,
     internal::{self, Layout, UpdateTips},
+    refdb,
     refs,
     FetchState,
     FilteredRef,
     Identities,
     Negotiation,
+    Odb,
     Refdb,
     WantsHaves,
 };
@@ -103,7 +105,7 @@ impl UpdateTips for ForClone {
     ) -> Result<internal::Updates<'a, U>, error::Prepare<C::VerificationError, C::FindError>>
     where
         U: ids::Urn + Ord,
-        C: Identities<Urn = U> + Refdb,
+        C: Identities<Urn = U> + Odb + Refdb,
     {
         use ids::VerifiedIdentity as _;
 
@@ -116,7 +118,12 @@ impl UpdateTips for ForClone {
         .map_err(error::Prepare::Verification)?;
 
         let tips = if verified.delegate_ids().contains(&self.remote_id) {
-            refs.iter().filter_map(mk_ref_update::<_, C::Urn>).collect()
+            let mut tips = Vec::with_capacity(refs.len());
+            for r in refs {
+                internal::guard_object_type(cx, r)?;
+                tips.extend(mk_ref_update::<_, C::Urn>(r, refdb::Policy::Abort));
+            }
+            tips
         } else {
             vec![]
         };
@@ -24,7 +24,9 @@ use crate::{
     FilteredRef,
     Identities,
     Negotiation,
+    Odb,
     Refdb,
+    Rollback,
     Update,
     WantsHaves,
 };
@@ -42,6 +44,8 @@ pub struct ForFetch {
     pub tracked: BTreeSet<PeerId>,
     /// Maximum number of bytes the fetched packfile is allowed to have.
     pub limit: u64,
+    /// Whether signed refs older than the ones we have may be accepted.
+    pub rollback: Rollback,
 }
 
 impl ForFetch {
@@ -134,7 +138,7 @@ impl UpdateTips for ForFetch {
     ) -> Result<internal::Updates<'a, U>, error::Prepare<C::VerificationError, C::FindError>>
     where
         U: ids::Urn + Ord,
-        C: Identities<Urn = U> + Refdb,
+        C: Identities<Urn = U> + Odb + Refdb,
     {
         use ids::VerifiedIdentity as _;
         use refdb::{Policy, SymrefTarget};
@@ -143,6 +147,7 @@ impl UpdateTips for ForFetch {
         let mut track = Vec::new();
         for r in refs {
             debug_assert!(r.remote_id != self.local_id, "never touch our own");
+            internal::guard_object_type(cx, r)?;
             let is_delegate = self.delegates.contains(&r.remote_id);
             // symref `rad/self` if we already have the top-level identity
             if r.name.ends_with(b"rad/self") {
@@ -189,7 +194,11 @@ impl UpdateTips for ForFetch {
                     Identities::verify(cx, r.tip, s.lookup_delegations(&r.remote_id))
                         .map_err(error::Prepare::Verification)?;
                 }
-                if let Some(u) = mk_ref_update::<_, C::Urn>(r) {
+                let sigrefs_no_ff = match self.rollback {
+                    Rollback::Reject => refdb::Policy::Abort,
+                    Rollback::Allow => refdb::Policy::Allow,
+                };
+                if let Some(u) = mk_ref_update::<_, C::Urn>(r, sigrefs_no_ff) {
                     tips.push(u)
                 }
             }
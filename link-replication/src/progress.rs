@@ -0,0 +1,56 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Progress of a replication run.
+//!
+//! [`crate::pull`] and [`crate::clone`] report the [`Event`]s of every phase
+//! via [`crate::Net::progress`]. [`crate::io::Network`] forwards them, along
+//! with the events of the fetches it runs, to a [`Reporter`], see
+//! [`crate::io::Network::with_progress_events`].
+
+use std::{fmt, sync::Arc};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    /// Started asking the remote end which refs it has.
+    LsRefs,
+    /// Determined the objects to fetch, and the ones to offer as a basis.
+    WantsHaves { wants: usize, haves: usize },
+    /// The number of bytes received on the current stream so far, including
+    /// protocol overhead.
+    Received { bytes: u64 },
+    /// Updated the local refs.
+    Applied { updated: usize, rejected: usize },
+    /// Validated the replicated refs against the signed refs.
+    Validated { warnings: usize },
+}
+
+/// Reports [`Event`]s to a sink callback.
+#[derive(Clone)]
+pub struct Reporter {
+    sink: Arc<dyn Fn(Event) + Send + Sync>,
+}
+
+impl Reporter {
+    pub fn new<F>(sink: F) -> Self
+    where
+        F: Fn(Event) + Send + Sync + 'static,
+    {
+        Self {
+            sink: Arc::new(sink),
+        }
+    }
+
+    pub fn report(&self, event: Event) {
+        (self.sink)(event)
+    }
+}
+
+impl fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reporter").finish()
+    }
+}
@@ -36,7 +36,9 @@ pub trait Refdb {
     ///
     /// On success, return the actually applied updates. That is, if an update
     /// has a [`Policy::Reject`], and was inded rejected, it is not included
-    /// in the result.
+    /// in the result. Updates which had nothing to do, because the ref is
+    /// already at the requested target, are reported as
+    /// [`Applied::unchanged`].
     ///
     /// Note that refnames in [`Update`]s are to be interpreted as relative to
     /// the current namespace, _unless_ they are of type [`refs::Namespaced`].
@@ -46,6 +48,33 @@ pub trait Refdb {
 
     /// Ensure on-disk state is considered.
     fn reload(&mut self) -> Result<(), Self::ReloadError>;
+
+    /// Find a ref whose name differs from `refname` only by case.
+    ///
+    /// Such refs would clobber each other on case-insensitive filesystems.
+    /// Only refs in the same hierarchy as `refname` are considered, ie. a
+    /// difference in case of a parent component is not found. The default
+    /// implementation finds nothing.
+    fn find_case_folded(
+        &self,
+        refname: impl AsRef<BStr>,
+    ) -> Result<Option<BString>, Self::FindError> {
+        let _ = refname;
+        Ok(None)
+    }
+}
+
+/// The [`Refdb`] operations which a replication awaits.
+///
+/// Implementations backed by blocking storage can run them off the async
+/// executor, eg. on a thread pool.
+#[async_trait]
+pub trait AsyncRefdb: Refdb {
+    /// Like [`Refdb::update`].
+    async fn update(
+        &mut self,
+        updates: Vec<Update<'static>>,
+    ) -> Result<Applied<'static>, Self::TxError>;
 }
 
 pub trait RefScan {
@@ -164,18 +193,23 @@ pub enum Updated {
 pub struct Applied<'a> {
     pub rejected: Vec<Update<'a>>,
     pub updated: Vec<Updated>,
+    /// [`Update`]s which were not applied because the ref was already at the
+    /// requested target.
+    pub unchanged: Vec<Update<'a>>,
 }
 
 impl Applied<'_> {
     pub fn append(&mut self, other: &mut Self) {
         self.rejected.append(&mut other.rejected);
         self.updated.append(&mut other.updated);
+        self.unchanged.append(&mut other.unchanged);
     }
 
     pub fn into_owned<'b>(self) -> Applied<'b> {
         Applied {
             rejected: self.rejected.into_iter().map(Update::into_owned).collect(),
             updated: self.updated,
+            unchanged: self.unchanged.into_iter().map(Update::into_owned).collect(),
         }
     }
 }
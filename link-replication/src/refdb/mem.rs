@@ -5,7 +5,7 @@
 
 use std::collections::{hash_map, HashMap};
 
-use bstr::{BStr, BString};
+use bstr::{BStr, BString, ByteSlice as _};
 
 use super::{Applied, RefScan, Refdb, Update, Updated};
 use crate::{ObjectId, Void};
@@ -19,6 +19,30 @@ pub struct Mem {
     refs: HashMap<BString, ObjectId>,
 }
 
+impl Mem {
+    /// Load the refs matching any of the `prefixes` from `db`.
+    pub fn load<'a, R, I, P>(db: &'a R, prefixes: I) -> Result<Self, <&'a R as RefScan>::Error>
+    where
+        &'a R: RefScan,
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        let mut refs = HashMap::new();
+        for prefix in prefixes {
+            for item in RefScan::scan(db, prefix)? {
+                let (name, oid) = item?;
+                refs.insert(name, oid.into());
+            }
+        }
+        Ok(Self { refs })
+    }
+
+    /// Forget about `name`, as if it was never updated.
+    pub(crate) fn remove(&mut self, name: &BStr) -> Option<ObjectId> {
+        self.refs.remove(name)
+    }
+}
+
 impl From<HashMap<BString, ObjectId>> for Mem {
     fn from(refs: HashMap<BString, ObjectId>) -> Self {
         Self { refs }
@@ -49,8 +73,16 @@ impl Refdb for Mem {
                 Update::Direct {
                     name,
                     target,
-                    no_ff: _,
+                    no_ff,
                 } => {
+                    if self.refs.get(name.as_ref()) == Some(&target) {
+                        ap.unchanged.push(Update::Direct {
+                            name,
+                            target,
+                            no_ff,
+                        });
+                        continue;
+                    }
                     let name = name.into_owned();
                     self.refs.insert(name.clone(), target);
                     ap.updated.push(Updated::Direct { name, target });
@@ -76,6 +108,19 @@ impl Refdb for Mem {
     fn reload(&mut self) -> Result<(), Self::ReloadError> {
         Ok(())
     }
+
+    fn find_case_folded(
+        &self,
+        refname: impl AsRef<BStr>,
+    ) -> Result<Option<BString>, Self::FindError> {
+        let refname = refname.as_ref();
+        let folded = refname.to_lowercase();
+        Ok(self
+            .refs
+            .keys()
+            .find(|name| name.as_bstr() != refname && name.to_lowercase() == folded)
+            .cloned())
+    }
 }
 
 impl<'a> RefScan for &'a Mem {
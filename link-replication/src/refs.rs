@@ -6,6 +6,8 @@
 use bstr::BString;
 use link_git::protocol::{ObjectId, Ref};
 
+mod intern;
+
 mod lit;
 pub use lit::{component, Prefix, RadId, RadSelf, Signed};
 
@@ -0,0 +1,38 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Interning of the textual representation of [`PeerId`]s.
+//!
+//! Encoding a [`PeerId`] allocates several times, which adds up when rewriting
+//! every ref of every peer in an advertisement. The encodings are instead
+//! computed once per thread, and reused.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use link_crypto::PeerId;
+
+/// The maximum number of encodings kept per thread.
+///
+/// The cache is cleared when full, which is cheaper than tracking recency and
+/// is rare in practice.
+const CAPACITY: usize = 1024;
+
+thread_local! {
+    static ENCODED: RefCell<HashMap<PeerId, Box<str>>> = RefCell::new(HashMap::new());
+}
+
+/// Call `f` with the default encoding of `id`.
+pub(super) fn with_encoded<R>(id: &PeerId, f: impl FnOnce(&str) -> R) -> R {
+    ENCODED.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if !cache.contains_key(id) && cache.len() >= CAPACITY {
+            cache.clear();
+        }
+        let encoded = cache
+            .entry(*id)
+            .or_insert_with(|| id.default_encoding().into_boxed_str());
+        f(encoded)
+    })
+}
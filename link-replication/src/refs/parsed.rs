@@ -14,7 +14,13 @@ use either::Either;
 use link_crypto::PeerId;
 
 use super::{is_separator, lit::component::*};
-use crate::ids;
+use crate::{error::InvalidId, ids};
+
+/// The maximum length in bytes of an encoded identity, as found in
+/// `refs/rad/ids/*` or `refs/namespaces/*`.
+///
+/// The base32-z encoding of a SHA-256 multihash is 56 bytes long.
+pub const MAX_ID_LEN: usize = 64;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Parsed<Urn> {
@@ -107,6 +113,49 @@ impl AsRef<str> for Identity {
     }
 }
 
+/// Parse an encoded identity strictly.
+///
+/// The input must be non-empty, at most [`MAX_ID_LEN`] bytes of ASCII
+/// alphanumerics, be accepted by [`ids::Urn::try_from_id`], and be the
+/// canonical encoding of the result, ie. roundtrip through
+/// [`ids::Urn::encode_id`].
+pub fn parse_id<Urn>(s: &[u8]) -> Result<Urn, InvalidId<Urn::Error>>
+where
+    Urn: ids::Urn,
+{
+    if s.is_empty() {
+        return Err(InvalidId::Empty);
+    }
+    if s.len() > MAX_ID_LEN {
+        return Err(InvalidId::TooLong {
+            len: s.len(),
+            max: MAX_ID_LEN,
+        });
+    }
+    if let Some((pos, byte)) = s
+        .iter()
+        .enumerate()
+        .find(|(_, b)| !b.is_ascii_alphanumeric())
+    {
+        return Err(InvalidId::Alphabet { byte: *byte, pos });
+    }
+    // Safety: checked to be ASCII above
+    let given = std::str::from_utf8(s).unwrap();
+    let urn = Urn::try_from_id(given).map_err(|source| InvalidId::Decode {
+        id: given.to_owned(),
+        source,
+    })?;
+    let canonical = urn.encode_id();
+    if canonical != given {
+        return Err(InvalidId::NotCanonical {
+            given: given.to_owned(),
+            canonical,
+        });
+    }
+
+    Ok(urn)
+}
+
 pub fn parse<Urn>(orig: &BStr) -> Option<Parsed<Urn>>
 where
     Urn: ids::Urn,
@@ -152,10 +201,7 @@ where
                 inner: Either::Left(Rad::SignedRefs),
             }),
             IDS => {
-                let urn = iter
-                    .next()
-                    .and_then(|s| std::str::from_utf8(s).ok())
-                    .and_then(|s| Urn::try_from_id(s).ok())?;
+                let urn = iter.next().and_then(|s| parse_id(s).ok())?;
                 iter.next().is_none().then(|| Parsed {
                     remote,
                     inner: Either::Left(Rad::Ids { urn }),
@@ -11,14 +11,14 @@
 
 use std::{borrow::Cow, ops::Deref};
 
-use bstr::{BStr, BString, ByteVec as _};
+use bstr::{BStr, BString, ByteSlice as _};
 use either::{
     Either,
     Either::{Left, Right},
 };
 use link_crypto::PeerId;
 
-use super::{is_separator, Prefix};
+use super::{intern, Prefix, SEPARATOR};
 
 /// A ref which optionally is relative to a namespace.
 ///
@@ -34,13 +34,17 @@ impl Namespaced<'_> {
     pub fn qualified(&self) -> BString {
         const PREFIX: &str = "refs/namespaces/";
 
-        let mut name = BString::from(self.refname.as_ref());
-        if let Some(ns) = &self.namespace {
-            name.insert_str(0, PREFIX);
-            name.insert_str(PREFIX.len(), ns.as_ref());
-            name.insert_char(PREFIX.len() + ns.len(), '/');
+        match &self.namespace {
+            None => BString::from(self.refname.as_ref()),
+            Some(ns) => {
+                let mut name = Vec::with_capacity(PREFIX.len() + ns.len() + 1 + self.refname.len());
+                name.extend_from_slice(PREFIX.as_bytes());
+                name.extend_from_slice(ns);
+                name.push(SEPARATOR);
+                name.extend_from_slice(&self.refname);
+                BString::from(name)
+            },
         }
-        name
     }
 
     pub fn into_owned(self) -> Namespaced<'static> {
@@ -73,16 +77,26 @@ pub fn remote_tracking<'a>(
 ) -> RemoteTracking<'a> {
     use super::component::REFS;
 
-    let mut name = name.into();
-    if !name.starts_with(Prefix::Remotes.as_bytes()) {
-        let name = name.to_mut();
-        if name.starts_with(REFS) {
-            name.insert_str(REFS.len() + 1, format!("remotes/{}/", remote_id));
-        } else {
-            name.insert_str(0, format!("refs/remotes/{}/", remote_id));
-        }
+    let name = name.into();
+    if name.starts_with(Prefix::Remotes.as_bytes()) {
+        return RemoteTracking(name);
     }
-    RemoteTracking(name)
+
+    let rest = if name.starts_with(REFS) {
+        &name[REFS.len() + 1..]
+    } else {
+        &name[..]
+    };
+    let tracking = intern::with_encoded(remote_id, |id| {
+        let remotes = Prefix::Remotes.as_bytes();
+        let mut buf = Vec::with_capacity(remotes.len() + id.len() + 1 + rest.len());
+        buf.extend_from_slice(remotes);
+        buf.extend_from_slice(id.as_bytes());
+        buf.push(SEPARATOR);
+        buf.extend_from_slice(rest);
+        BString::from(buf)
+    });
+    RemoteTracking(Cow::Owned(tracking))
 }
 
 impl Deref for RemoteTracking<'_> {
@@ -113,19 +127,23 @@ pub struct Owned<'a>(Cow<'a, BStr>);
 /// Essentially removes `refs/remotes/*/` from `name`. Returns `None` if the
 /// result would be the empty string.
 pub fn owned<'a>(name: impl Into<Cow<'a, BStr>>) -> Option<Owned<'a>> {
-    use super::component::*;
+    use super::component::REFS;
 
     let name = name.into();
-    match name.splitn(4, is_separator).collect::<Vec<_>>()[..] {
-        [REFS, REMOTES, _, rest] => (!rest.is_empty()).then(|| {
-            let mut name = BString::from(REFS);
-            name.insert_char(REFS.len(), '/');
-            name.insert_str(REFS.len() + 1, rest);
-            Owned(name.into())
-        }),
-        [REFS, REMOTES] | [REFS, REMOTES, _] => None,
-        _ => Some(Owned(name)),
-    }
+    let rest = match name.strip_prefix(Prefix::Remotes.as_bytes()) {
+        Some(remote_and_rest) => remote_and_rest
+            .find_byte(SEPARATOR)
+            .map(|i| &remote_and_rest[i + 1..]),
+        None if name.as_bytes() == b"refs/remotes" => None,
+        None => return Some(Owned(name)),
+    };
+    let rest = rest.filter(|rest| !rest.is_empty())?;
+
+    let mut buf = Vec::with_capacity(REFS.len() + 1 + rest.len());
+    buf.extend_from_slice(REFS);
+    buf.push(SEPARATOR);
+    buf.extend_from_slice(rest);
+    Some(Owned(Cow::Owned(BString::from(buf))))
 }
 
 impl Deref for Owned<'_> {
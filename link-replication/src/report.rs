@@ -0,0 +1,181 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! A serializable summary of a replication run, see [`crate::Success::report`].
+
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use bstr::{BStr, ByteSlice as _};
+use serde::{Serialize, Serializer};
+
+use crate::{error, ids, CorrelationId, PeerId, Success, Updated};
+
+/// The outcome of a successful replication run.
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    #[serde(serialize_with = "serialize_display")]
+    pub correlation_id: CorrelationId,
+    /// The refs which were created or updated.
+    pub updated: Vec<String>,
+    /// The refs whose update was rejected.
+    pub rejected: Vec<String>,
+    /// Ref updates per peer, by the remote tracking refs they affect.
+    pub peers: BTreeMap<PeerId, Counts>,
+    /// Ref updates of refs which are not remote tracking refs, eg. `rad/id`.
+    pub local: Counts,
+    /// New tracking relationships, as peer ids or URNs.
+    pub tracked: Vec<String>,
+    /// Top-level URNs created.
+    pub urns_created: Vec<String>,
+    pub requires_confirmation: bool,
+    pub validation_errors: Vec<Warning>,
+    /// Refs which are neither signed nor expected, and could be pruned.
+    pub prunable: Vec<String>,
+    /// Seconds since the unix epoch at which peers attested their signed refs.
+    pub attested: BTreeMap<PeerId, u64>,
+    /// Bytes received on the streams of fetches, including protocol overhead.
+    pub bytes_received: u64,
+    pub timings: Timings,
+}
+
+/// Number of ref updates by outcome.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct Counts {
+    pub updated: usize,
+    pub rejected: usize,
+    pub unchanged: usize,
+}
+
+/// A post-validation error, see [`error::Validation`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Warning {
+    /// See [`error::Validation::code`].
+    pub code: &'static str,
+    pub peer: Option<PeerId>,
+    pub refname: Option<String>,
+    pub message: String,
+}
+
+impl From<&error::Validation> for Warning {
+    fn from(e: &error::Validation) -> Self {
+        Self {
+            code: e.code(),
+            peer: e.peer(),
+            refname: e.refname().map(|name| name.to_string()),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Time spent in the phases of a replication run. Serialized as milliseconds.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct Timings {
+    /// Fetching and verifying the identity refs, and setting up tracking.
+    #[serde(serialize_with = "serialize_millis")]
+    pub peek: Duration,
+    /// Fetching the signed and tracked refs.
+    #[serde(serialize_with = "serialize_millis")]
+    pub fetch: Duration,
+    /// Validating the fetched refs against the signed refs.
+    #[serde(serialize_with = "serialize_millis")]
+    pub validate: Duration,
+    /// Updating the local refs.
+    #[serde(serialize_with = "serialize_millis")]
+    pub apply: Duration,
+    /// The whole run.
+    #[serde(serialize_with = "serialize_millis")]
+    pub total: Duration,
+}
+
+impl<Urn> Success<Urn>
+where
+    Urn: ids::Urn,
+{
+    pub fn report(&self) -> Report {
+        let mut peers = BTreeMap::new();
+        let mut local = Counts::default();
+        let mut updated = Vec::new();
+        for up in self.updated_refs() {
+            let name = match up {
+                Updated::Direct { name, .. } | Updated::Symbolic { name, .. } => name.as_bstr(),
+            };
+            counts(&mut peers, &mut local, name).updated += 1;
+            updated.push(name.to_string());
+        }
+        let mut rejected = Vec::new();
+        for up in self.rejected_updates() {
+            counts(&mut peers, &mut local, up.refname()).rejected += 1;
+            rejected.push(up.refname().to_string());
+        }
+        for up in self.unchanged_updates() {
+            counts(&mut peers, &mut local, up.refname()).unchanged += 1;
+        }
+
+        Report {
+            correlation_id: self.correlation_id(),
+            updated,
+            rejected,
+            peers,
+            local,
+            tracked: self
+                .tracked()
+                .iter()
+                .map(|x| {
+                    x.as_ref()
+                        .either(|peer| peer.to_string(), |urn| urn.encode_id())
+                })
+                .collect(),
+            urns_created: self.urns_created().map(|urn| urn.encode_id()).collect(),
+            requires_confirmation: self.requires_confirmation(),
+            validation_errors: self.validation_errors().iter().map(Warning::from).collect(),
+            prunable: self
+                .validation_errors()
+                .iter()
+                .filter_map(|e| match e {
+                    error::Validation::StrangeOrPrunable(name) => Some(name.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            attested: self
+                .attested()
+                .iter()
+                .filter_map(|(peer, time)| {
+                    let secs = time.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs();
+                    Some((*peer, secs))
+                })
+                .collect(),
+            bytes_received: self.bytes_received(),
+            timings: self.timings(),
+        }
+    }
+}
+
+/// The [`Counts`] of the peer owning the remote tracking ref `name`, or the
+/// `local` ones if `name` is not a remote tracking ref.
+fn counts<'a>(
+    peers: &'a mut BTreeMap<PeerId, Counts>,
+    local: &'a mut Counts,
+    name: &BStr,
+) -> &'a mut Counts {
+    match error::remote_of(name) {
+        Some(peer) => peers.entry(peer).or_default(),
+        None => local,
+    }
+}
+
+fn serialize_display<T, S>(val: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: fmt::Display,
+    S: Serializer,
+{
+    serializer.collect_str(val)
+}
+
+fn serialize_millis<S>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u64(d.as_millis().min(u64::MAX as u128) as u64)
+}
@@ -47,6 +47,49 @@ pub trait SignedRefs {
     /// A `None` return value denotes a no-op (ie. the sigrefs were already
     /// up-to-date).
     fn update(&self) -> Result<Option<Self::Oid>, Self::Error>;
+
+    /// Whether the signed refs at `new` succeed the ones at `old`, ie. whether
+    /// `old` is reachable from `new`.
+    ///
+    /// Signed refs are equal to themselves, so this is `true` if `new == old`.
+    fn succeeds(
+        &self,
+        new: impl Into<ObjectId>,
+        old: impl Into<ObjectId>,
+    ) -> Result<bool, Self::Error>;
+}
+
+/// The [`SignedRefs`] operations which a replication awaits.
+///
+/// Implementations backed by blocking storage can run them off the async
+/// executor, eg. on a thread pool.
+#[async_trait]
+pub trait AsyncSignedRefs: SignedRefs {
+    /// Like [`SignedRefs::update`].
+    async fn update(&mut self) -> Result<Option<Self::Oid>, Self::Error>;
+}
+
+/// What to do if the fetched signed refs of a peer do not succeed the ones
+/// already stored, see [`SignedRefs::succeeds`].
+///
+/// A peer going back to an older state of its signed refs, be it maliciously
+/// or by restoring from a backup, would otherwise rewind our view of its refs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rollback {
+    /// Keep the stored signed refs of the peer, and withhold the updates of
+    /// its verification refs.
+    ///
+    /// The refs of other peers are replicated as usual. The peer is reported
+    /// in [`crate::Success::rollbacks`].
+    Reject,
+    /// Accept the older signed refs.
+    Allow,
+}
+
+impl Default for Rollback {
+    fn default() -> Self {
+        Self::Reject
+    }
 }
 
 #[derive(Debug)]
@@ -54,6 +97,9 @@ pub struct Sigrefs<Oid> {
     pub at: Oid,
     pub refs: HashMap<BString, Oid>,
     pub remotes: BTreeSet<PeerId>,
+    /// Seconds since the unix epoch at which the signed refs were published,
+    /// if attested by the signer.
+    pub timestamp: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -64,6 +110,17 @@ pub struct Combined<Oid> {
     pub remotes: BTreeSet<PeerId>,
 }
 
+impl<T> Combined<T> {
+    /// All peers with signed refs, or which are remotes of a peer.
+    pub fn peers(&self) -> BTreeSet<PeerId> {
+        self.refs
+            .keys()
+            .chain(self.remotes.iter())
+            .copied()
+            .collect()
+    }
+}
+
 impl<T> Default for Combined<T> {
     fn default() -> Self {
         Self {
@@ -79,6 +136,9 @@ pub struct Refs<Oid> {
     pub at: Oid,
     /// The signed `(refname, head)` pairs.
     pub refs: HashMap<BString, Oid>,
+    /// Seconds since the unix epoch at which the signed refs were published,
+    /// if attested by the signer.
+    pub timestamp: Option<u64>,
 }
 
 pub struct Select<'a> {
@@ -119,9 +179,17 @@ where
                 at,
                 refs,
                 mut remotes,
+                timestamp,
             },
         )| {
-            comb.refs.insert(*id, Refs { at, refs });
+            comb.refs.insert(
+                *id,
+                Refs {
+                    at,
+                    refs,
+                    timestamp,
+                },
+            );
             comb.remotes.append(&mut remotes);
             comb
         },
@@ -0,0 +1,911 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Deterministic, in-memory simulation of replication.
+//!
+//! A [`Network`] is made up of [`Peer`]s, each owning a [`refdb::Mem`] and an
+//! in-memory [`Odb`]. A [`Conn`] between two peers implements [`Net`] by
+//! advertising the remote's refs and copying the wanted objects over, without
+//! any git repositories or sockets involved.
+//!
+//! A [`Conn`] also implements the remaining context traits from the point of
+//! view of its local peer, so that [`crate::pull`] and [`crate::clone`] can be
+//! run against it. Identities and signed refs are modelled as commits with a
+//! line-based payload, see [`Peer::identity`] and [`Peer::sign_refs`]. They
+//! are not actually signed: the simulator is for exercising the replication
+//! logic, not the cryptography.
+//!
+//! The order in which peers fetch from each other is determined by an
+//! [`Order`], so that properties like convergence can be checked for any
+//! interleaving, and reproduced from the seed of a failing run.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap},
+    hash::{Hash as _, Hasher as _},
+    path::Path,
+};
+
+use bstr::{BStr, BString, ByteSlice as _};
+use either::Either;
+use link_crypto::PeerId;
+use link_git::{
+    object::Kind,
+    protocol::{oid, ObjectId, Ref},
+};
+use parking_lot::RwLock;
+use radicle_data::NonEmpty;
+use thiserror::Error;
+
+use crate::{
+    error,
+    ids,
+    odb::{self, Odb as _},
+    refdb::{self, Applied, Policy, RefScan, Refdb, Update},
+    refs,
+    sigrefs::Sigrefs,
+    track,
+    AsyncRefdb,
+    AsyncSignedRefs,
+    AsyncTracking,
+    FilteredRef,
+    Identities,
+    LocalPeer,
+    Negotiation,
+    Net,
+    SignedRefs,
+    SkippedFetch,
+    Tracking,
+    Void,
+    WantsHaves,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("object {0} not found")]
+    NotFound(ObjectId),
+
+    #[error("object {0} is not a valid {1}")]
+    Malformed(ObjectId, &'static str),
+
+    #[error("signed refs at {at} are not signed by {expected}")]
+    WrongSigner { at: ObjectId, expected: PeerId },
+
+    #[error("non-fast-forward update of {0}")]
+    NonFastForward(BString),
+}
+
+/// An object in an [`Odb`].
+///
+/// Only the ancestry of commits is modelled, their content is opaque.
+#[derive(Clone, Debug)]
+pub struct Object {
+    pub kind: Kind,
+    pub data: Vec<u8>,
+    pub parents: Vec<ObjectId>,
+}
+
+impl Object {
+    /// The content-derived id of this object.
+    ///
+    /// Ids are stable for the same build of the simulator, but are not the
+    /// ids `git` would compute.
+    pub fn id(&self) -> ObjectId {
+        let mut hex = String::with_capacity(40);
+        for salt in 0u8..3 {
+            let mut hasher = DefaultHasher::new();
+            salt.hash(&mut hasher);
+            (self.kind as u8).hash(&mut hasher);
+            self.data.hash(&mut hasher);
+            for parent in &self.parents {
+                parent.hash(&mut hasher);
+            }
+            hex.push_str(&format!("{:016x}", hasher.finish()));
+        }
+        hex.truncate(40);
+        ObjectId::from_hex(hex.as_bytes()).expect("valid hex")
+    }
+}
+
+/// An in-memory object database.
+#[derive(Debug, Default)]
+pub struct Odb {
+    objects: RwLock<HashMap<ObjectId, Object>>,
+}
+
+impl Odb {
+    /// Create a commit with the given `parents` and `message`.
+    pub fn commit(&self, parents: &[ObjectId], message: &str) -> ObjectId {
+        self.insert(Object {
+            kind: Kind::Commit,
+            data: message.as_bytes().to_vec(),
+            parents: parents.to_vec(),
+        })
+    }
+
+    /// Insert `obj`, returning its id.
+    pub fn insert(&self, obj: Object) -> ObjectId {
+        let id = obj.id();
+        self.objects.write().entry(id).or_insert(obj);
+        id
+    }
+
+    /// The object `id`, if present.
+    pub fn get(&self, id: &ObjectId) -> Option<Object> {
+        self.objects.read().get(id).cloned()
+    }
+
+    /// All objects reachable from `tips`, but not from `haves`.
+    fn closure(
+        &self,
+        tips: impl IntoIterator<Item = ObjectId>,
+        haves: &BTreeSet<ObjectId>,
+    ) -> Vec<Object> {
+        let objects = self.objects.read();
+        let mut seen = BTreeSet::new();
+        let mut todo = tips.into_iter().collect::<Vec<_>>();
+        let mut out = Vec::new();
+        while let Some(id) = todo.pop() {
+            if haves.contains(&id) || !seen.insert(id) {
+                continue;
+            }
+            if let Some(obj) = objects.get(&id) {
+                todo.extend(obj.parents.iter().copied());
+                out.push(obj.clone());
+            }
+        }
+        out
+    }
+}
+
+impl odb::Odb for Odb {
+    type LookupError = Void;
+    type RevwalkError = Void;
+    type AddPackError = Void;
+
+    fn contains(&self, oid: impl AsRef<oid>) -> bool {
+        self.objects.read().contains_key(oid.as_ref())
+    }
+
+    fn lookup<'a>(
+        &self,
+        oid: impl AsRef<oid>,
+        buf: &'a mut Vec<u8>,
+    ) -> Result<Option<odb::Object<'a>>, Self::LookupError> {
+        let kind = match self.objects.read().get(oid.as_ref()) {
+            None => return Ok(None),
+            Some(obj) => {
+                buf.clear();
+                buf.extend_from_slice(&obj.data);
+                obj.kind
+            },
+        };
+        Ok(Some(odb::Object { kind, data: buf }))
+    }
+
+    fn is_in_ancestry_path(
+        &self,
+        new: impl Into<ObjectId>,
+        old: impl Into<ObjectId>,
+    ) -> Result<bool, Self::RevwalkError> {
+        let old = old.into();
+        Ok(self
+            .closure(Some(new.into()), &BTreeSet::new())
+            .iter()
+            .any(|obj| obj.id() == old))
+    }
+
+    fn add_pack(&self, _: impl AsRef<Path>) -> Result<(), Self::AddPackError> {
+        Ok(())
+    }
+}
+
+/// A participant in a [`Network`].
+#[derive(Debug)]
+pub struct Peer {
+    pub id: PeerId,
+    pub odb: Odb,
+    refdb: RwLock<refdb::Mem>,
+    tracked: RwLock<BTreeSet<PeerId>>,
+    tracked_urns: RwLock<BTreeSet<Urn>>,
+}
+
+impl Peer {
+    pub fn new(id: PeerId) -> Self {
+        Self {
+            id,
+            odb: Odb::default(),
+            refdb: RwLock::new(refdb::Mem::default()),
+            tracked: RwLock::new(BTreeSet::new()),
+            tracked_urns: RwLock::new(BTreeSet::new()),
+        }
+    }
+
+    /// Point the ref `name` at `target`, eg. to simulate a local commit.
+    pub fn set_ref(&self, name: impl Into<BString>, target: ObjectId) {
+        let up = Update::Direct {
+            name: name.into().into(),
+            target,
+            no_ff: Policy::Allow,
+        };
+        match self.refdb.write().update(Some(up)) {
+            Ok(_) => {},
+            Err(v) => match v {},
+        }
+    }
+
+    /// The tip of the ref `name`, if any.
+    pub fn get_ref(&self, name: impl AsRef<[u8]>) -> Option<ObjectId> {
+        match self.refdb.read().refname_to_id(name.as_ref().as_bstr()) {
+            Ok(tip) => tip,
+            Err(v) => match v {},
+        }
+    }
+
+    /// All refs of this peer.
+    pub fn refs(&self) -> BTreeMap<BString, ObjectId> {
+        let refdb = self.refdb.read();
+        let scan = match RefScan::scan(&*refdb, None::<&str>) {
+            Ok(scan) => scan,
+            Err(v) => match v {},
+        };
+        scan.map(|item| match item {
+            Ok(r) => r,
+            Err(v) => match v {},
+        })
+        .collect()
+    }
+
+    /// Update the remote tracking refs for the `wanted` refs of a fetch.
+    pub fn track<T>(&self, wanted: impl IntoIterator<Item = FilteredRef<T>>) -> Applied<'static> {
+        let updates = wanted
+            .into_iter()
+            .map(|r| Update::Direct {
+                name: refs::remote_tracking(&r.remote_id, r.name).into(),
+                target: r.tip,
+                no_ff: Policy::Allow,
+            })
+            .collect::<Vec<_>>();
+        match self.refdb.write().update(updates) {
+            Ok(applied) => applied,
+            Err(v) => match v {},
+        }
+    }
+
+    /// Track `peer`, returning `false` if it was already tracked.
+    pub fn track_peer(&self, peer: PeerId) -> bool {
+        peer != self.id && self.tracked.write().insert(peer)
+    }
+
+    /// The peers tracked by this peer.
+    pub fn tracked(&self) -> BTreeSet<PeerId> {
+        self.tracked.read().clone()
+    }
+
+    /// Create a new revision of the identity `name`, delegating to
+    /// `delegates`, and point `refs/rad/id` at it.
+    ///
+    /// The payload lists the name and the delegates, one per line, and the
+    /// parent is the previous `refs/rad/id`, if any.
+    pub fn identity(&self, name: &str, delegates: impl IntoIterator<Item = PeerId>) -> ObjectId {
+        let mut data = format!("identity {}\n", name);
+        for delegate in delegates.into_iter().collect::<BTreeSet<_>>() {
+            data.push_str(&format!("delegate {}\n", delegate));
+        }
+        let parents = self.get_ref(refs::RadId.as_bytes()).into_iter().collect();
+        let tip = self.odb.insert(Object {
+            kind: Kind::Commit,
+            data: data.into_bytes(),
+            parents,
+        });
+        self.set_ref(refs::RadId.as_bytes(), tip);
+        tip
+    }
+
+    /// Sign the current refs of this peer, and point `refs/rad/signed_refs`
+    /// at the result.
+    ///
+    /// The signed refs are all refs outside of `refs/remotes`, except for the
+    /// `refs/rad` hierarchy other than `refs/rad/id`, as well as the tracked
+    /// peers. If nothing changed since the last time, no new commit is
+    /// created.
+    pub fn sign_refs(&self) -> ObjectId {
+        let mut data = format!("signed-refs {}\n", self.id);
+        for (name, tip) in self.refs() {
+            let is_rad = name.starts_with(refs::Prefix::Rad.as_bytes())
+                && name.as_bstr() != refs::RadId.as_bytes().as_bstr();
+            if !is_rad && !name.starts_with(refs::Prefix::Remotes.as_bytes()) {
+                data.push_str(&format!("ref {} {}\n", name, tip));
+            }
+        }
+        for peer in self.tracked() {
+            data.push_str(&format!("remote {}\n", peer));
+        }
+
+        let previous = self.get_ref(refs::Signed.as_bytes());
+        if let Some(previous) = previous {
+            if self.odb.get(&previous).map(|obj| obj.data) == Some(data.as_bytes().to_vec()) {
+                return previous;
+            }
+        }
+        let tip = self.odb.insert(Object {
+            kind: Kind::Commit,
+            data: data.into_bytes(),
+            parents: previous.into_iter().collect(),
+        });
+        self.set_ref(refs::Signed.as_bytes(), tip);
+        tip
+    }
+
+    /// Verify the identity at `head`.
+    pub fn verify(&self, head: impl AsRef<oid>) -> Result<Identity, Error> {
+        let content_id = head.as_ref().to_owned();
+        let obj = self
+            .odb
+            .get(&content_id)
+            .ok_or(Error::NotFound(content_id))?;
+        let malformed = || Error::Malformed(content_id, "identity");
+        let mut lines = obj.data.lines();
+        let urn = lines
+            .next()
+            .and_then(|line| line.strip_prefix(b"identity "))
+            .and_then(|name| name.to_str().ok())
+            .map(|name| Urn(name.to_owned()))
+            .ok_or_else(malformed)?;
+        let delegates = lines
+            .map(|line| {
+                line.strip_prefix(b"delegate ")
+                    .and_then(|peer| peer.to_str().ok())
+                    .and_then(|peer| peer.parse().ok())
+                    .ok_or_else(malformed)
+            })
+            .collect::<Result<BTreeSet<PeerId>, _>>()?;
+        let revision = Object {
+            kind: Kind::Blob,
+            data: obj.data,
+            parents: vec![],
+        }
+        .id();
+
+        Ok(Identity {
+            content_id,
+            revision,
+            urn,
+            delegates: NonEmpty::from_maybe_empty(delegates).ok_or_else(malformed)?,
+        })
+    }
+
+    /// Load the signed refs at `at`, which must be signed by `of`.
+    pub fn load_sigrefs(&self, at: ObjectId, of: &PeerId) -> Result<Sigrefs<ObjectId>, Error> {
+        let obj = self.odb.get(&at).ok_or(Error::NotFound(at))?;
+        let malformed = || Error::Malformed(at, "signed refs");
+        let mut lines = obj.data.lines();
+        let signer = lines
+            .next()
+            .and_then(|line| line.strip_prefix(b"signed-refs "))
+            .and_then(|peer| peer.to_str().ok())
+            .and_then(|peer| peer.parse::<PeerId>().ok())
+            .ok_or_else(malformed)?;
+        if &signer != of {
+            return Err(Error::WrongSigner { at, expected: *of });
+        }
+
+        let mut refs = HashMap::new();
+        let mut remotes = BTreeSet::new();
+        for line in lines {
+            let line = line.to_str().map_err(|_| malformed())?;
+            match line.split(' ').collect::<Vec<_>>().as_slice() {
+                ["ref", name, tip] => {
+                    let tip = ObjectId::from_hex(tip.as_bytes()).map_err(|_| malformed())?;
+                    refs.insert(BString::from(*name), tip);
+                },
+                ["remote", peer] => {
+                    remotes.insert(peer.parse().map_err(|_| malformed())?);
+                },
+                _ => return Err(malformed()),
+            }
+        }
+
+        Ok(Sigrefs {
+            at,
+            refs,
+            remotes,
+            timestamp: None,
+        })
+    }
+
+    /// Apply `updates` as a transaction, honouring their [`Policy`].
+    ///
+    /// Unlike [`refdb::Mem`], an update which is not a fast-forward is
+    /// rejected or aborts the transaction, as demanded by its policy.
+    fn apply<'a>(
+        &self,
+        updates: impl IntoIterator<Item = Update<'a>>,
+    ) -> Result<Applied<'a>, Error> {
+        let mut refdb = self.refdb.write();
+        let mut rejected = Vec::new();
+        let mut accepted = Vec::new();
+        for up in updates {
+            let no_ff = match &up {
+                Update::Direct {
+                    name,
+                    target,
+                    no_ff,
+                } => match refdb.refname_to_id(name) {
+                    Ok(Some(old)) if &old != target => {
+                        match self.odb.is_in_ancestry_path(*target, old) {
+                            Ok(true) => None,
+                            Ok(false) => Some((name.clone(), *no_ff)),
+                            Err(v) => match v {},
+                        }
+                    },
+                    Ok(_) => None,
+                    Err(v) => match v {},
+                },
+                Update::Symbolic { .. } => None,
+            };
+            match no_ff {
+                None | Some((_, Policy::Allow)) => accepted.push(up),
+                Some((_, Policy::Reject)) => rejected.push(up),
+                Some((name, Policy::Abort)) => {
+                    return Err(Error::NonFastForward(name.into_owned()))
+                },
+            }
+        }
+
+        let mut applied = match refdb.update(accepted) {
+            Ok(applied) => applied,
+            Err(v) => match v {},
+        };
+        applied.rejected.append(&mut rejected);
+        Ok(applied)
+    }
+}
+
+/// The URN of a simulated identity, which is just its name.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Urn(pub String);
+
+impl ids::Urn for Urn {
+    type Error = Void;
+
+    fn try_from_id(s: impl AsRef<str>) -> Result<Self, Self::Error> {
+        Ok(Self(s.as_ref().to_owned()))
+    }
+
+    fn encode_id(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// A simulated identity, see [`Peer::identity`].
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub content_id: ObjectId,
+    pub revision: ObjectId,
+    pub urn: Urn,
+    pub delegates: NonEmpty<BTreeSet<PeerId>>,
+}
+
+impl ids::VerifiedIdentity for Identity {
+    type Rev = ObjectId;
+    type Oid = ObjectId;
+    type Urn = Urn;
+
+    fn revision(&self) -> Self::Rev {
+        self.revision
+    }
+
+    fn content_id(&self) -> Self::Oid {
+        self.content_id
+    }
+
+    fn urn(&self) -> Self::Urn {
+        self.urn.clone()
+    }
+
+    fn delegate_ids(&self) -> NonEmpty<BTreeSet<PeerId>> {
+        self.delegates.clone()
+    }
+
+    fn delegate_urns(&self) -> BTreeSet<Self::Urn> {
+        BTreeSet::new()
+    }
+}
+
+/// The order in which a [`Network::schedule`] is run.
+#[derive(Clone, Copy, Debug)]
+pub enum Order {
+    /// In the order the peers were added.
+    Fifo,
+    /// In reverse order.
+    Lifo,
+    /// Shuffled, deterministically for the same seed.
+    Shuffle(u64),
+}
+
+/// A set of [`Peer`]s which can fetch from each other.
+#[derive(Debug, Default)]
+pub struct Network {
+    peers: Vec<Peer>,
+}
+
+impl Network {
+    pub fn add(&mut self, peer: Peer) -> &Peer {
+        self.peers.push(peer);
+        self.peers.last().unwrap()
+    }
+
+    pub fn peer(&self, id: &PeerId) -> Option<&Peer> {
+        self.peers.iter().find(|peer| &peer.id == id)
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &Peer> {
+        self.peers.iter()
+    }
+
+    /// A connection from `local` to `remote`.
+    ///
+    /// # Panics
+    ///
+    /// If either peer is not part of the network.
+    pub fn conn(&self, local: &PeerId, remote: &PeerId) -> Conn<'_> {
+        Conn {
+            local: self.peer(local).expect("unknown local peer"),
+            remote: self.peer(remote).expect("unknown remote peer"),
+        }
+    }
+
+    /// All pairs of distinct `(local, remote)` peers, in the given [`Order`].
+    pub fn schedule(&self, order: Order) -> Vec<(PeerId, PeerId)> {
+        let mut pairs = self
+            .peers
+            .iter()
+            .flat_map(|a| {
+                self.peers
+                    .iter()
+                    .filter(move |b| b.id != a.id)
+                    .map(move |b| (a.id, b.id))
+            })
+            .collect::<Vec<_>>();
+        match order {
+            Order::Fifo => {},
+            Order::Lifo => pairs.reverse(),
+            Order::Shuffle(seed) => {
+                // xorshift64*, which is good enough to permute a schedule
+                let mut state = seed.max(1);
+                for i in (1..pairs.len()).rev() {
+                    state ^= state >> 12;
+                    state ^= state << 25;
+                    state ^= state >> 27;
+                    let j = (state.wrapping_mul(0x2545_f491_4f6c_dd1d) % (i as u64 + 1)) as usize;
+                    pairs.swap(i, j);
+                }
+            },
+        }
+        pairs
+    }
+}
+
+/// A [`Net`] from the `local` to the `remote` [`Peer`].
+///
+/// Fetching copies all objects reachable from the wanted tips, which are not
+/// reachable from the haves, to the `local` [`Odb`]. Refs are not updated by
+/// [`Net::run_fetch`] itself, see [`Peer::track`].
+///
+/// All other context traits operate on the `local` [`Peer`], so that a
+/// [`Conn`] can be passed to [`crate::pull`] or [`crate::clone`].
+pub struct Conn<'a> {
+    local: &'a Peer,
+    remote: &'a Peer,
+}
+
+#[async_trait]
+impl Net for Conn<'_> {
+    type Error = Void;
+
+    async fn run_fetch<N, T>(
+        &self,
+        neg: N,
+    ) -> Result<(N, Result<Vec<FilteredRef<T>>, SkippedFetch>), Self::Error>
+    where
+        N: Negotiation<T> + Send + Sync,
+        T: Send + 'static,
+    {
+        let prefixes = neg
+            .ref_prefixes()
+            .into_iter()
+            .map(|p| p.as_ref().to_owned())
+            .collect::<Vec<_>>();
+        let advertised = self
+            .remote
+            .refs()
+            .into_iter()
+            .filter(|(name, _)| {
+                prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p.as_slice()))
+            })
+            .collect::<Vec<_>>();
+        if advertised.is_empty() {
+            return Ok((neg, Err(SkippedFetch::NoMatchingRefs)));
+        }
+
+        let refs = advertised
+            .into_iter()
+            .filter_map(|(path, object)| neg.ref_filter(Ref::Direct { path, object }))
+            .collect::<Vec<_>>();
+        let WantsHaves {
+            wanted,
+            mut wants,
+            haves,
+        } = neg.wants_haves(&*self.local.refdb.read(), refs)?;
+        wants.retain(|oid| !haves.contains(oid));
+        if wants.is_empty() {
+            return Ok((neg, Err(SkippedFetch::WantNothing)));
+        }
+
+        for obj in self.remote.odb.closure(wants, &haves) {
+            self.local.odb.insert(obj);
+        }
+
+        Ok((neg, Ok(wanted.into_iter().collect())))
+    }
+
+    async fn has_objects(&self, oids: Vec<ObjectId>) -> Result<BTreeSet<ObjectId>, Self::Error> {
+        use odb::Odb as _;
+
+        Ok(oids
+            .into_iter()
+            .filter(|oid| self.remote.odb.contains(oid))
+            .collect())
+    }
+}
+
+impl LocalPeer for Conn<'_> {
+    fn id(&self) -> &PeerId {
+        &self.local.id
+    }
+}
+
+impl odb::Odb for Conn<'_> {
+    type LookupError = Void;
+    type RevwalkError = Void;
+    type AddPackError = Void;
+
+    fn contains(&self, oid: impl AsRef<oid>) -> bool {
+        self.local.odb.contains(oid)
+    }
+
+    fn lookup<'a>(
+        &self,
+        oid: impl AsRef<oid>,
+        buf: &'a mut Vec<u8>,
+    ) -> Result<Option<odb::Object<'a>>, Self::LookupError> {
+        self.local.odb.lookup(oid, buf)
+    }
+
+    fn is_in_ancestry_path(
+        &self,
+        new: impl Into<ObjectId>,
+        old: impl Into<ObjectId>,
+    ) -> Result<bool, Self::RevwalkError> {
+        self.local.odb.is_in_ancestry_path(new, old)
+    }
+
+    fn add_pack(&self, path: impl AsRef<Path>) -> Result<(), Self::AddPackError> {
+        self.local.odb.add_pack(path)
+    }
+}
+
+impl Refdb for Conn<'_> {
+    type Oid = ObjectId;
+
+    type FindError = Void;
+    type TxError = Error;
+    type ReloadError = Void;
+
+    fn refname_to_id(
+        &self,
+        refname: impl AsRef<BStr>,
+    ) -> Result<Option<Self::Oid>, Self::FindError> {
+        self.local.refdb.read().refname_to_id(refname)
+    }
+
+    fn find_case_folded(
+        &self,
+        refname: impl AsRef<BStr>,
+    ) -> Result<Option<BString>, Self::FindError> {
+        self.local.refdb.read().find_case_folded(refname)
+    }
+
+    fn update<'a, I>(&mut self, updates: I) -> Result<Applied<'a>, Self::TxError>
+    where
+        I: IntoIterator<Item = Update<'a>>,
+    {
+        self.local.apply(updates)
+    }
+
+    fn reload(&mut self) -> Result<(), Self::ReloadError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsyncRefdb for Conn<'_> {
+    async fn update(
+        &mut self,
+        updates: Vec<Update<'static>>,
+    ) -> Result<Applied<'static>, Self::TxError> {
+        Refdb::update(self, updates)
+    }
+}
+
+impl Identities for Conn<'_> {
+    type Urn = Urn;
+    type Oid = ObjectId;
+
+    type VerifiedIdentity = Identity;
+    type VerificationError = Error;
+
+    fn verify<H, F, T>(
+        &self,
+        head: H,
+        _resolve: F,
+    ) -> Result<Self::VerifiedIdentity, Self::VerificationError>
+    where
+        H: AsRef<oid>,
+        F: Fn(&Self::Urn) -> Option<T>,
+        T: AsRef<oid>,
+    {
+        self.local.verify(head)
+    }
+
+    fn newer(
+        &self,
+        a: Self::VerifiedIdentity,
+        b: Self::VerifiedIdentity,
+    ) -> Result<Self::VerifiedIdentity, error::IdentityHistory<Self::VerifiedIdentity>> {
+        let descends = |new: &Identity, old: &Identity| match self
+            .local
+            .odb
+            .is_in_ancestry_path(new.content_id, old.content_id)
+        {
+            Ok(descends) => descends,
+            Err(v) => match v {},
+        };
+        if a.urn != b.urn {
+            Err(error::IdentityHistory::TypeMismatch { a, b })
+        } else if descends(&a, &b) {
+            Ok(a)
+        } else if descends(&b, &a) {
+            Ok(b)
+        } else {
+            Err(error::IdentityHistory::Other(
+                format!(
+                    "identity histories at {} and {} diverged",
+                    a.content_id, b.content_id
+                )
+                .into(),
+            ))
+        }
+    }
+}
+
+impl SignedRefs for Conn<'_> {
+    type Oid = ObjectId;
+    type Error = Error;
+
+    fn load(&self, of: &PeerId, cutoff: usize) -> Result<Option<Sigrefs<Self::Oid>>, Self::Error> {
+        let name = if of == &self.local.id {
+            BString::from(refs::Signed.as_bytes())
+        } else {
+            BString::from(refs::remote_tracking(of, refs::Signed).as_bytes())
+        };
+        match self.local.get_ref(name) {
+            None => Ok(None),
+            Some(at) => self.load_at(at, of, cutoff),
+        }
+    }
+
+    fn load_at(
+        &self,
+        treeish: impl Into<ObjectId>,
+        of: &PeerId,
+        cutoff: usize,
+    ) -> Result<Option<Sigrefs<Self::Oid>>, Self::Error> {
+        let mut sigrefs = self.local.load_sigrefs(treeish.into(), of)?;
+        if cutoff == 0 {
+            sigrefs.remotes.clear();
+        }
+        Ok(Some(sigrefs))
+    }
+
+    fn update(&self) -> Result<Option<Self::Oid>, Self::Error> {
+        Ok(Some(self.local.sign_refs()))
+    }
+
+    fn succeeds(
+        &self,
+        new: impl Into<ObjectId>,
+        old: impl Into<ObjectId>,
+    ) -> Result<bool, Self::Error> {
+        match self.local.odb.is_in_ancestry_path(new, old) {
+            Ok(succeeds) => Ok(succeeds),
+            Err(v) => match v {},
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncSignedRefs for Conn<'_> {
+    async fn update(&mut self) -> Result<Option<Self::Oid>, Self::Error> {
+        SignedRefs::update(self)
+    }
+}
+
+impl Tracking for Conn<'_> {
+    type Urn = Urn;
+
+    type Updated = std::vec::IntoIter<Either<PeerId, Urn>>;
+    type Tracked = std::vec::IntoIter<Result<PeerId, Void>>;
+
+    type TrackError = Void;
+    type TrackedError = Void;
+
+    fn track<I>(&mut self, iter: I) -> Result<Self::Updated, Self::TrackError>
+    where
+        I: IntoIterator<Item = track::Rel<Self::Urn>>,
+    {
+        let mut updated = Vec::new();
+        for rel in iter {
+            match rel {
+                track::Rel::Delegation(Either::Left(peer)) => {
+                    if self.local.track_peer(peer) {
+                        updated.push(Either::Left(peer));
+                    }
+                },
+                track::Rel::Delegation(Either::Right(urn)) | track::Rel::SelfRef(urn) => {
+                    if self.local.tracked_urns.write().insert(urn.clone()) {
+                        updated.push(Either::Right(urn));
+                    }
+                },
+            }
+        }
+        Ok(updated.into_iter())
+    }
+
+    fn untrack<I>(&mut self, peers: I) -> Result<(), Self::TrackError>
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        let mut tracked = self.local.tracked.write();
+        for peer in peers {
+            tracked.remove(&peer);
+        }
+        Ok(())
+    }
+
+    fn tracked(&self) -> Result<Self::Tracked, Self::TrackedError> {
+        Ok(self
+            .local
+            .tracked()
+            .into_iter()
+            .map(Ok)
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+}
+
+#[async_trait]
+impl AsyncTracking for Conn<'_> {
+    async fn track(
+        &mut self,
+        rels: Vec<track::Rel<Self::Urn>>,
+    ) -> Result<Vec<Either<PeerId, Self::Urn>>, Self::TrackError> {
+        Tracking::track(self, rels).map(Iterator::collect)
+    }
+
+    async fn untrack(&mut self, peers: Vec<PeerId>) -> Result<(), Self::TrackError> {
+        Tracking::untrack(self, peers)
+    }
+}
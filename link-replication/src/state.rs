@@ -3,11 +3,14 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::collections::BTreeMap;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
-use bstr::BStr;
+use bstr::{BStr, BString};
 use either::Either;
-use futures_lite::future::block_on;
+use futures_util::{stream, StreamExt as _, TryStreamExt as _};
 use tracing::Instrument as _;
 
 use crate::{
@@ -19,11 +22,13 @@ use crate::{
     refs,
     track,
     Applied,
+    FilteredRef,
     Identities,
     LocalPeer,
     Negotiation,
     Net,
     ObjectId,
+    Odb,
     PeerId,
     RefScan,
     Refdb,
@@ -46,6 +51,9 @@ pub(crate) struct FetchState<Urn> {
     sigs: SigrefTips,
     tips: Vec<Update<'static>>,
     trks: Vec<track::Rel<Urn>>,
+    /// Index of the step the refs of a peer were first taken from, see
+    /// [`FetchState::step_many`].
+    owners: BTreeMap<PeerId, usize>,
 }
 
 impl<Urn> Default for FetchState<Urn> {
@@ -57,6 +65,7 @@ impl<Urn> Default for FetchState<Urn> {
             sigs: Default::default(),
             tips: Default::default(),
             trks: Default::default(),
+            owners: Default::default(),
         }
     }
 }
@@ -65,47 +74,204 @@ impl<U> FetchState<U>
 where
     U: ids::Urn + Ord,
 {
-    pub fn step<C, S>(
+    pub async fn step<C, S>(
         &mut self,
         cx: &mut C,
         step: S,
-    ) -> Result<(S, Option<SkippedFetch>), error::Error>
+    ) -> Result<(S, Option<SkippedFetch>), error::Failure>
     where
-        C: Identities<Urn = U> + Net + Refdb,
+        C: Identities<Urn = U> + Net + Odb + Refdb,
         S: Layout + Negotiation + UpdateTips + Send + Sync + 'static,
     {
-        Refdb::reload(cx)?;
-        let (step, res) = block_on(Net::run_fetch(cx, step).in_current_span())?;
+        Refdb::reload(cx).map_err(error::Failure::storage)?;
+        let (step, res) = Net::run_fetch(cx, step)
+            .in_current_span()
+            .await
+            .map_err(error::Failure::fetch)?;
         if let Ok(refs) = &res {
             Layout::pre_validate(&step, refs)?;
+            self.absorb(&*cx, &step, refs)?;
+        }
+
+        Ok((step, res.err()))
+    }
+
+    /// Like [`FetchState::step`], but run `steps` via the same [`Net`], up to
+    /// `concurrency` of them at a time.
+    ///
+    /// The refs yielded by all steps are merged, and the updates for them are
+    /// prepared at once by the first step. This is thus only meaningful for
+    /// steps which differ in the refs they ask for, but not in how they
+    /// prepare updates, such as the ones obtained from
+    /// [`crate::fetch::Fetch::per_peer`].
+    pub async fn step_concurrently<C, S>(
+        &mut self,
+        cx: &mut C,
+        steps: Vec<S>,
+        concurrency: usize,
+    ) -> Result<Vec<(S, Option<SkippedFetch>)>, error::Failure>
+    where
+        C: Identities<Urn = U> + Net + Odb + Refdb,
+        S: Layout + Negotiation + UpdateTips + Send + Sync + 'static,
+    {
+        Refdb::reload(cx).map_err(error::Failure::storage)?;
+        let net: &C = cx;
+        let fetched = stream::iter(
+            steps
+                .into_iter()
+                .map(|step| Net::run_fetch(net, step).in_current_span()),
+        )
+        .buffered(concurrency.max(1))
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(error::Failure::fetch)?;
+
+        let mut refs = Vec::new();
+        let mut steps = Vec::with_capacity(fetched.len());
+        for (step, res) in fetched {
+            match res {
+                Ok(mut fetched) => {
+                    Layout::pre_validate(&step, &fetched)?;
+                    refs.append(&mut fetched);
+                    steps.push((step, None));
+                },
+                Err(skip) => steps.push((step, Some(skip))),
+            }
+        }
+        if let Some((step, _)) = steps.first() {
+            self.absorb(&*cx, step, &refs)?;
+        }
+
+        Ok(steps)
+    }
+
+    /// Like [`FetchState::step`], but run each step via its own [`Net`], up
+    /// to `concurrency` of them at a time.
+    ///
+    /// Where several steps yield refs of the same peer, only the refs of one
+    /// of them are considered: the one which yielded the most recent
+    /// `rad/signed_refs` of that peer, or else the first one.
+    pub async fn step_many<C, N, S>(
+        &mut self,
+        cx: &mut C,
+        steps: Vec<(&N, S)>,
+        concurrency: usize,
+    ) -> Result<Vec<(S, Option<SkippedFetch>)>, error::Failure>
+    where
+        C: Identities<Urn = U> + Odb + Refdb + SignedRefs,
+        N: Net,
+        S: Layout + Negotiation + UpdateTips + Send + Sync + 'static,
+    {
+        Refdb::reload(cx).map_err(error::Failure::storage)?;
+        let fetched = stream::iter(
+            steps
+                .into_iter()
+                .map(|(net, step)| Net::run_fetch(net, step).in_current_span()),
+        )
+        .buffered(concurrency.max(1))
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(error::Failure::fetch)?;
+
+        let mut signed = BTreeMap::<PeerId, (usize, ObjectId)>::new();
+        for (i, (_, res)) in fetched.iter().enumerate() {
+            let refs = match res {
+                Ok(refs) => refs,
+                Err(_) => continue,
+            };
             for r in refs {
-                if let Some(rad) = r.parsed.as_ref().left() {
-                    match rad {
-                        refs::parsed::Rad::Id => {
-                            self.insert_id_tip(r.remote_id, r.tip);
-                        },
-
-                        refs::parsed::Rad::Ids { urn } => {
-                            if let Ok(urn) = C::Urn::try_from_id(urn) {
-                                self.insert_delegation_tip(r.remote_id, urn, r.tip);
-                            }
-                        },
-
-                        refs::parsed::Rad::SignedRefs => {
-                            self.insert_sigref_tip(r.remote_id, r.tip);
-                        },
-
-                        _ => {},
-                    }
+                if !matches!(r.parsed, Either::Left(refs::parsed::Rad::SignedRefs)) {
+                    continue;
+                }
+                let newer = match signed.get(&r.remote_id) {
+                    None => true,
+                    Some((_, tip)) => {
+                        *tip != r.tip
+                            && SignedRefs::succeeds(&*cx, r.tip, *tip)
+                                .map_err(error::Failure::sigrefs)?
+                    },
+                };
+                if newer {
+                    signed.insert(r.remote_id, (i, r.tip));
                 }
             }
+        }
+        let mut owners = signed
+            .into_iter()
+            .map(|(peer, (i, _))| (peer, i))
+            .collect::<BTreeMap<_, _>>();
+        for (i, (_, res)) in fetched.iter().enumerate() {
+            if let Ok(refs) = res {
+                for r in refs {
+                    owners.entry(r.remote_id).or_insert(i);
+                }
+            }
+        }
 
-            let up = UpdateTips::prepare(&step, self, cx, refs)?;
-            self.track_all(up.track);
-            self.update_all(up.tips.into_iter().map(|u| u.into_owned()));
+        let mut steps = Vec::with_capacity(fetched.len());
+        for (i, (step, res)) in fetched.into_iter().enumerate() {
+            match res {
+                Ok(refs) => {
+                    Layout::pre_validate(&step, &refs)?;
+                    let refs = refs
+                        .into_iter()
+                        .filter(|r| owners.get(&r.remote_id) == Some(&i))
+                        .collect::<Vec<_>>();
+                    self.absorb(&*cx, &step, &refs)?;
+                    steps.push((step, None));
+                },
+                Err(skip) => steps.push((step, Some(skip))),
+            }
+        }
+        for (peer, i) in owners {
+            self.owners.entry(peer).or_insert(i);
         }
 
-        Ok((step, res.err()))
+        Ok(steps)
+    }
+
+    /// Record the tips of the verification refs among `refs`, and the
+    /// updates and trackings `step` prepares for them.
+    fn absorb<C, S>(
+        &mut self,
+        cx: &C,
+        step: &S,
+        refs: &[FilteredRef<S>],
+    ) -> Result<(), error::Failure>
+    where
+        C: Identities<Urn = U> + Odb + Refdb,
+        S: UpdateTips,
+    {
+        for r in refs {
+            if let Some(rad) = r.parsed.as_ref().left() {
+                match rad {
+                    refs::parsed::Rad::Id => {
+                        self.insert_id_tip(r.remote_id, r.tip);
+                    },
+
+                    refs::parsed::Rad::Ids { urn } => {
+                        match refs::parsed::parse_id::<C::Urn>(urn.as_ref().as_bytes()) {
+                            Ok(urn) => self.insert_delegation_tip(r.remote_id, urn, r.tip),
+                            Err(e) => {
+                                tracing::warn!(err = %e, "skipping malformed delegation")
+                            },
+                        }
+                    },
+
+                    refs::parsed::Rad::SignedRefs => {
+                        self.insert_sigref_tip(r.remote_id, r.tip);
+                    },
+
+                    _ => {},
+                }
+            }
+        }
+
+        let up = UpdateTips::prepare(step, self, cx, refs)?;
+        self.track_all(up.track);
+        self.update_all(up.tips.into_iter().map(|u| u.into_owned()));
+
+        Ok(())
     }
 }
 
@@ -129,10 +295,20 @@ where
         self.idts.insert(of, tip);
     }
 
+    /// The index of the step passed to the first [`FetchState::step_many`]
+    /// which yielded refs of `peer`.
+    pub fn owner(&self, peer: &PeerId) -> Option<usize> {
+        self.owners.get(peer).copied()
+    }
+
     pub fn sigref_tip(&self, of: &PeerId) -> Option<&ObjectId> {
         self.sigs.get(of)
     }
 
+    pub fn sigref_tips(&self) -> impl Iterator<Item = (&PeerId, &ObjectId)> {
+        self.sigs.iter()
+    }
+
     fn insert_sigref_tip(&mut self, of: PeerId, tip: ObjectId) {
         self.sigs.insert(of, tip);
     }
@@ -167,6 +343,79 @@ where
         ap
     }
 
+    /// The peers for which remote tracking refs are pending to be updated.
+    pub fn updated_remotes(&self) -> BTreeSet<PeerId> {
+        use refs::component::{REFS, REMOTES};
+
+        self.tips
+            .iter()
+            .filter_map(|up| {
+                match up
+                    .refname()
+                    .splitn(4, refs::is_separator)
+                    .collect::<Vec<_>>()[..]
+                {
+                    [REFS, REMOTES, id, _] => std::str::from_utf8(id).ok()?.parse().ok(),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Withhold the pending update of the ref `name`.
+    pub fn discard_update(&mut self, name: &BStr) -> bool {
+        let pending = self.tips.len();
+        self.tips.retain(|up| up.refname() != name);
+        self.refs.remove(name);
+        self.tips.len() != pending
+    }
+
+    /// Withhold the pending updates of the verification refs of `peer`, and
+    /// load its signed refs at `stored` instead of the fetched ones.
+    pub fn withhold_sigrefs(&mut self, peer: &PeerId, stored: ObjectId) {
+        let prefix = format!("refs/remotes/{}/rad/", peer);
+        let withheld = self
+            .tips
+            .iter()
+            .map(|up| up.refname())
+            .filter(|name| name.starts_with(prefix.as_bytes()))
+            .map(ToOwned::to_owned)
+            .collect::<Vec<BString>>();
+        for name in withheld {
+            self.discard_update(&name);
+        }
+        self.sigs.insert(*peer, stored);
+    }
+
+    /// Point the pending direct update of the ref `name` to `target`.
+    pub fn retarget_update(&mut self, name: &BStr, target: ObjectId) -> bool {
+        let mut found = false;
+        for up in &mut self.tips {
+            if let Update::Direct {
+                name: pending,
+                target: tip,
+                ..
+            } = up
+            {
+                if pending.as_ref() == name {
+                    *tip = target;
+                    found = true;
+                }
+            }
+        }
+        if found {
+            self.refs.remove(name);
+            self.refs
+                .update(Some(Update::Direct {
+                    name: Cow::Borrowed(name),
+                    target,
+                    no_ff: refdb::Policy::Allow,
+                }))
+                .expect("absurd");
+        }
+        found
+    }
+
     pub fn drain_updates(&mut self) -> impl Iterator<Item = Update<'static>> + '_ {
         self.tips.drain(..)
     }
@@ -219,6 +468,16 @@ where
     fn reload(&mut self) -> Result<(), Self::ReloadError> {
         self.fetch.refs.reload()
     }
+
+    fn find_case_folded(
+        &self,
+        refname: impl AsRef<BStr>,
+    ) -> Result<Option<BString>, Self::FindError> {
+        match self.fetch.refs.find_case_folded(&refname).expect("absurd") {
+            Some(name) => Ok(Some(name)),
+            None => self.inner.find_case_folded(refname),
+        }
+    }
 }
 
 impl<'a, T, U> RefScan for &'a Shim<'_, T, U> {
@@ -266,6 +525,14 @@ where
     fn update(&self) -> Result<Option<Self::Oid>, Self::Error> {
         self.inner.update()
     }
+
+    fn succeeds(
+        &self,
+        new: impl Into<ObjectId>,
+        old: impl Into<ObjectId>,
+    ) -> Result<bool, Self::Error> {
+        self.inner.succeeds(new, old)
+    }
 }
 
 impl<T, U> Tracking for Shim<'_, T, U>
@@ -297,6 +564,13 @@ where
         }))
     }
 
+    fn untrack<I>(&mut self, peers: I) -> Result<(), Self::TrackError>
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        self.inner.untrack(peers)
+    }
+
     fn tracked(&self) -> Result<Self::Tracked, Self::TrackedError> {
         self.inner.tracked()
     }
@@ -3,18 +3,40 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::marker::PhantomData;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+    time::{Duration, Instant, SystemTime},
+};
 
 use either::Either;
 
-use crate::{error, ids, refs, Applied, PeerId, Update, Updated};
+use crate::{
+    error,
+    ids,
+    refs,
+    report::Timings,
+    Applied,
+    CorrelationId,
+    PeerId,
+    Update,
+    Updated,
+    VerifiedIdentity,
+};
 
 #[derive(Debug)]
 pub struct Success<Urn> {
+    pub(crate) correlation_id: CorrelationId,
     pub(crate) applied: Applied<'static>,
     pub(crate) tracked: Vec<Either<PeerId, Urn>>,
+    pub(crate) tracked_delegates: Vec<Either<PeerId, Urn>>,
+    pub(crate) delegation_changes: Option<DelegationChanges<Urn>>,
     pub(crate) requires_confirmation: bool,
     pub(crate) validation: Vec<error::Validation>,
+    pub(crate) rollbacks: Vec<error::SigrefsRollback>,
+    pub(crate) attested: BTreeMap<PeerId, SystemTime>,
+    pub(crate) bytes_received: u64,
+    pub(crate) timings: Timings,
     pub(crate) _marker: PhantomData<Urn>,
 }
 
@@ -22,12 +44,35 @@ impl<Urn> Success<Urn>
 where
     Urn: ids::Urn,
 {
+    /// Record the total duration and bytes received of the replication run.
+    pub(crate) fn measured(self, started: Instant, bytes_received: u64) -> Self {
+        Self {
+            bytes_received,
+            timings: Timings {
+                total: started.elapsed(),
+                ..self.timings
+            },
+            ..self
+        }
+    }
+
+    /// The [`CorrelationId`] of the replication run.
+    pub fn correlation_id(&self) -> CorrelationId {
+        self.correlation_id
+    }
+
     /// All refs which have been created or updated as a result of the
     /// replication run.
     pub fn updated_refs(&self) -> &[Updated] {
         &self.applied.updated
     }
 
+    /// Ref updates which had nothing to do, because the ref was already at the
+    /// requested target.
+    pub fn unchanged_updates(&self) -> &[Update<'static>] {
+        &self.applied.unchanged
+    }
+
     /// Ref updates which have been rejected, eg. due to not being fast-forwards
     /// when required.
     pub fn rejected_updates(&self) -> &[Update<'static>] {
@@ -43,6 +88,21 @@ where
         &self.tracked
     }
 
+    /// The subset of [`Success::tracked`] which was established because of
+    /// the delegations of the replicated identity.
+    pub fn tracked_delegates(&self) -> &[Either<PeerId, Urn>] {
+        &self.tracked_delegates
+    }
+
+    /// How the delegations of the replicated identity changed, if the
+    /// replication run adopted a newer revision of an identity which existed
+    /// locally before.
+    ///
+    /// `None` if the identity was cloned, or its delegations did not change.
+    pub fn delegation_changes(&self) -> Option<&DelegationChanges<Urn>> {
+        self.delegation_changes.as_ref()
+    }
+
     /// Top-level URNs created as a result of the replication run.
     ///
     /// This happens due to new `refs/rad/ids/*` being discovered, which are
@@ -59,8 +119,7 @@ where
                         [REFS, NAMESPACES, id, REFS, RAD, ID] => Some(id),
                         _ => None,
                     }?;
-                    let id = std::str::from_utf8(id).ok()?;
-                    Urn::try_from_id(id).ok()
+                    refs::parsed::parse_id(id).ok()
                 },
 
                 _ => None,
@@ -79,4 +138,87 @@ where
     pub fn validation_errors(&self) -> &[error::Validation] {
         &self.validation
     }
+
+    /// The peers whose fetched signed refs did not succeed the stored ones,
+    /// and were thus withheld, see [`crate::Rollback::Reject`].
+    pub fn rollbacks(&self) -> &[error::SigrefsRollback] {
+        &self.rollbacks
+    }
+
+    /// The time at which the peers whose signed refs were replicated published
+    /// them, as attested by the peers themselves.
+    ///
+    /// Peers which did not attest their signed refs are omitted.
+    pub fn attested(&self) -> &BTreeMap<PeerId, SystemTime> {
+        &self.attested
+    }
+
+    /// The number of bytes received on the streams of fetches, including
+    /// protocol overhead.
+    ///
+    /// Zero if the [`crate::Net`] implementation does not count them.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// The time spent in the phases of the replication run.
+    pub fn timings(&self) -> Timings {
+        self.timings
+    }
+
+    /// The peers whose view is older than `threshold`, along with its age.
+    ///
+    /// Ages are relative to the local clock, so they are only as accurate as
+    /// the clocks of the local and the remote peers are synchronised.
+    pub fn stale(&self, threshold: Duration) -> impl Iterator<Item = (&PeerId, Duration)> + '_ {
+        let now = SystemTime::now();
+        self.attested
+            .iter()
+            .map(move |(peer, time)| (peer, now.duration_since(*time).unwrap_or_default()))
+            .filter(move |(_, age)| *age > threshold)
+    }
+}
+
+/// Changes to the delegations of an identity, see
+/// [`Success::delegation_changes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DelegationChanges<Urn> {
+    /// Peers which became delegates, directly or indirectly.
+    pub added: BTreeSet<PeerId>,
+    /// Peers which are no longer delegates.
+    pub removed: BTreeSet<PeerId>,
+    /// Identities which became indirect delegations.
+    pub added_urns: BTreeSet<Urn>,
+    /// Identities which are no longer indirect delegations.
+    pub removed_urns: BTreeSet<Urn>,
+}
+
+impl<Urn> DelegationChanges<Urn>
+where
+    Urn: Clone + Ord,
+{
+    /// The changes from the delegations of `before` to the ones of `after`,
+    /// or `None` if they are the same.
+    pub fn between<V>(before: &V, after: &V) -> Option<Self>
+    where
+        V: VerifiedIdentity<Urn = Urn>,
+    {
+        let (ids_before, ids_after) = (before.delegate_ids(), after.delegate_ids());
+        let (urns_before, urns_after) = (before.delegate_urns(), after.delegate_urns());
+        let changes = Self {
+            added: ids_after.difference(&ids_before).copied().collect(),
+            removed: ids_before.difference(&ids_after).copied().collect(),
+            added_urns: urns_after.difference(&urns_before).cloned().collect(),
+            removed_urns: urns_before.difference(&urns_after).cloned().collect(),
+        };
+
+        (!changes.is_empty()).then(|| changes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.added_urns.is_empty()
+            && self.removed_urns.is_empty()
+    }
 }
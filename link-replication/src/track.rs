@@ -33,6 +33,29 @@ pub trait Tracking {
     where
         I: IntoIterator<Item = Rel<Self::Urn>>;
 
+    /// Atomically remove the tracking relationships with `peers` in the
+    /// context of the current [`Urn`], eg. to roll back the ones created by
+    /// [`Tracking::track`].
+    fn untrack<I>(&mut self, peers: I) -> Result<(), Self::TrackError>
+    where
+        I: IntoIterator<Item = PeerId>;
+
     /// All tracked [`PeerId`]s in the context of the current [`Urn`].
     fn tracked(&self) -> Result<Self::Tracked, Self::TrackedError>;
 }
+
+/// The [`Tracking`] operations which a replication awaits.
+///
+/// Implementations backed by blocking storage can run them off the async
+/// executor, eg. on a thread pool.
+#[async_trait]
+pub trait AsyncTracking: Tracking {
+    /// Like [`Tracking::track`], collecting the updated relationships.
+    async fn track(
+        &mut self,
+        rels: Vec<Rel<Self::Urn>>,
+    ) -> Result<Vec<Either<PeerId, Self::Urn>>, Self::TrackError>;
+
+    /// Like [`Tracking::untrack`].
+    async fn untrack(&mut self, peers: Vec<PeerId>) -> Result<(), Self::TrackError>;
+}
@@ -15,7 +15,7 @@ use link_crypto::PeerId;
 use link_git::protocol::{ObjectId, Ref};
 use thiserror::Error;
 
-use crate::{refs, Refdb};
+use crate::{progress, refs, CorrelationId, Refdb};
 
 #[derive(Debug, Error)]
 pub enum SkippedFetch {
@@ -25,17 +25,47 @@ pub enum SkippedFetch {
     WantNothing,
 }
 
-#[async_trait(?Send)]
+#[async_trait]
 pub trait Net {
     type Error: std::error::Error + Send + Sync + 'static;
 
+    /// Fetch the refs and objects `neg` asks for.
+    ///
+    /// Errors which are [`std::io::Error`]s of kind
+    /// [`std::io::ErrorKind::InvalidData`] are taken to mean that the remote
+    /// end sent invalid data, and fail the replication as not retryable.
     async fn run_fetch<N, T>(
         &self,
         neg: N,
     ) -> Result<(N, Result<Vec<FilteredRef<T>>, SkippedFetch>), Self::Error>
     where
-        N: Negotiation<T> + Send,
+        N: Negotiation<T> + Send + Sync,
         T: Send + 'static;
+
+    /// Ask the remote end which of `oids` it has, without negotiating a
+    /// packfile.
+    ///
+    /// This is cheap compared to [`Net::run_fetch`], and can be used to decide
+    /// whether a remote is worth fetching from.
+    async fn has_objects(&self, oids: Vec<ObjectId>) -> Result<BTreeSet<ObjectId>, Self::Error>;
+
+    /// Associate subsequent fetches with the replication run `id`.
+    ///
+    /// Implementations may send `id` to the remote end, so the logs of both
+    /// sides can be joined. The default implementation does nothing.
+    fn correlate(&mut self, _id: CorrelationId) {}
+
+    /// Report the progress of the replication run.
+    ///
+    /// The default implementation does nothing.
+    fn progress(&self, _event: progress::Event) {}
+
+    /// The total number of bytes received by fetches so far.
+    ///
+    /// The default implementation does not count them, and returns zero.
+    fn bytes_received(&self) -> u64 {
+        0
+    }
 }
 
 pub trait Negotiation<T = Self> {
@@ -53,7 +83,8 @@ pub trait Negotiation<T = Self> {
     /// would need updating after the `fetch` succeeds.
     ///
     /// The `refs` are the advertised refs from executing `ls-refs`, filtered
-    /// through [`Negotiation::ref_filter`].
+    /// through [`Negotiation::ref_filter`]. `db` may only contain the remote
+    /// tracking refs of the remotes the `refs` belong to.
     fn wants_haves<R: Refdb>(
         &self,
         db: &R,
@@ -62,6 +93,14 @@ pub trait Negotiation<T = Self> {
 
     /// Maximum number of bytes the fetched packfile is allowed to have.
     fn fetch_limit(&self) -> u64;
+
+    /// The filter-spec to fetch a partial packfile with, e.g. `blob:none`.
+    ///
+    /// The filter is only used if the remote advertises support for it,
+    /// otherwise all objects are fetched.
+    fn filter(&self) -> Option<BString> {
+        None
+    }
 }
 
 pub struct WantsHaves<T: ?Sized> {
@@ -8,23 +8,123 @@ use std::{
     fmt::Debug,
 };
 
-use bstr::ByteSlice as _;
+use bstr::{BStr, ByteSlice as _};
 use link_crypto::PeerId;
 use link_git::protocol::oid;
 
 use crate::{
     error::{self, Validation},
+    fetch,
     refs,
     sigrefs,
     LocalPeer,
     RefScan,
 };
 
-#[tracing::instrument(level = "debug", skip(cx, sigrefs), err)]
+/// How much of the namespace to validate after a fetch.
+///
+/// [`Validate::Incremental`] takes time proportional to the refs of the peers
+/// which were fetched, rather than the size of the namespace. The price is
+/// that refs which don't belong to any of those peers are not looked at:
+/// orphaned remote tracking refs, or refs which became inconsistent outside
+/// of replication, are only reported by a [`Validate::Full`] run. Nodes using
+/// [`Validate::Incremental`] should thus do a full run now and then.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Validate {
+    /// Only validate the refs of peers for which refs were updated, or which
+    /// newly appeared in the signed refs. See [`validate_peers`].
+    ///
+    /// The namespace is not scanned for orphaned refs.
+    Incremental,
+    /// Validate the refs of all peers in the signed refs, and scan the
+    /// namespace for orphaned refs. See [`validate`].
+    Full,
+}
+
+impl Default for Validate {
+    fn default() -> Self {
+        Self::Incremental
+    }
+}
+
+/// What to do if validation after a fetch finds inconsistencies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationPolicy {
+    /// Apply all updates, and report the inconsistencies in
+    /// [`crate::Success::validation_errors`].
+    Warn,
+    /// Apply none of the updates, and fail with
+    /// [`crate::error::Failure::Validation`].
+    ///
+    /// Note that new tracking relationships are already stored at this point,
+    /// and are not undone.
+    Reject,
+    /// Withhold the updates of offending refs, and report the remaining
+    /// inconsistencies like [`ValidationPolicy::Warn`].
+    ///
+    /// An update whose target does not match the signed tip is pointed to the
+    /// signed tip instead if that object was received. Refs which are already
+    /// stored are not deleted.
+    Repair,
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+
+/// Validate the refs of all peers in `sigrefs`, and scan the namespace for
+/// orphaned refs.
+///
+/// Unsigned refs of the categories configured in `spec` are accepted for
+/// tracked peers, as they are fetched by [`fetch::Fetch`]. Refs excluded by
+/// `spec` are not fetched, and thus not validated.
+///
+/// Notes which are not signed by a peer publishing signed refs are reported
+/// as [`Validation::Unsigned`]. The notes of tracked peers without signed refs
+/// were stored before notes had to be signed, and are accepted: no new ones
+/// are fetched.
 pub fn validate<'a, C, Oid>(
     cx: &'a C,
+    spec: &'a fetch::FetchSpec,
     sigrefs: &'a sigrefs::Combined<Oid>,
 ) -> Result<Vec<error::Validation>, <&'a C as RefScan>::Error>
+where
+    C: LocalPeer,
+    &'a C: RefScan,
+    Oid: Debug + AsRef<oid>,
+{
+    validate_scoped(cx, spec, sigrefs, None)
+}
+
+/// Like [`validate`], but only consider the refs of `peers`.
+///
+/// Refs of peers not in `peers` are assumed to have been validated before, so
+/// the namespace is not scanned for orphans. The time this takes is thus
+/// proportional to the number of refs of `peers`, not the size of the
+/// namespace.
+pub fn validate_peers<'a, C, Oid>(
+    cx: &'a C,
+    spec: &'a fetch::FetchSpec,
+    sigrefs: &'a sigrefs::Combined<Oid>,
+    peers: &'a BTreeSet<PeerId>,
+) -> Result<Vec<error::Validation>, <&'a C as RefScan>::Error>
+where
+    C: LocalPeer,
+    &'a C: RefScan,
+    Oid: Debug + AsRef<oid>,
+{
+    validate_scoped(cx, spec, sigrefs, Some(peers))
+}
+
+#[tracing::instrument(level = "debug", skip(cx, spec, sigrefs), err)]
+fn validate_scoped<'a, C, Oid>(
+    cx: &'a C,
+    spec: &'a fetch::FetchSpec,
+    sigrefs: &'a sigrefs::Combined<Oid>,
+    peers: Option<&'a BTreeSet<PeerId>>,
+) -> Result<Vec<error::Validation>, <&'a C as RefScan>::Error>
 where
     C: LocalPeer,
     &'a C: RefScan,
@@ -37,10 +137,11 @@ where
     info!(?sigrefs, "validating");
 
     let local_id = LocalPeer::id(cx);
+    let in_scope = |peer: &PeerId| peer != local_id && peers.map_or(true, |ps| ps.contains(peer));
 
     // signed refs
     for (peer, refs) in &sigrefs.refs {
-        if peer == local_id {
+        if !in_scope(peer) {
             continue;
         }
 
@@ -72,7 +173,19 @@ where
             {
                 continue;
             }
+            if spec.excludes(owned.as_ref()) {
+                continue;
+            }
             match refs.refs.get(owned.as_ref()) {
+                // Unsigned refs of configured categories are fetched if the
+                // peer is also tracked, see `fetch::Fetch::ref_filter`
+                None if sigrefs.remotes.contains(peer) && is_configured(spec, owned.as_ref()) => {},
+                // Notes are only ever accepted if signed by their owner, see
+                // `fetch::must_be_signed`
+                None if must_be_signed(owned.as_ref()) => fail.push(Validation::Unsigned {
+                    name,
+                    remote: *peer,
+                }),
                 None => fail.push(Validation::Unexpected(name)),
                 Some(signed_oid) => {
                     seen_refs.insert(owned.as_ref().to_owned());
@@ -91,7 +204,7 @@ where
         for missing in refs
             .refs
             .keys()
-            .filter(|k| !seen_refs.contains(k.as_bstr()))
+            .filter(|k| !seen_refs.contains(k.as_bstr()) && !spec.excludes(k.as_bstr()))
         {
             fail.push(Validation::Missing {
                 refname: (*missing).to_owned(),
@@ -115,7 +228,7 @@ where
 
         let mut seen_peers = BTreeSet::new();
         for peer in &sigrefs.remotes {
-            if peer == local_id {
+            if !in_scope(peer) {
                 continue;
             }
 
@@ -149,9 +262,9 @@ where
                         },
 
                         Right(Refs {
-                            cat: Cat::Unknown(_),
+                            cat: ref cat @ Cat::Unknown(_),
                             ..
-                        }) => {
+                        }) if !spec.allows(cat) => {
                             fail.push(Validation::Strange(name));
                         },
 
@@ -174,14 +287,14 @@ where
         for missing in sigrefs
             .remotes
             .iter()
-            .filter(|p| p != &local_id && !seen_peers.contains(p))
+            .filter(|p| in_scope(*p) && !seen_peers.contains(p))
         {
             fail.push(Validation::NoData(*missing))
         }
     }
 
     // finally, find orphans and other strange refs
-    {
+    if peers.is_none() {
         let pids = sigrefs
             .refs
             .keys()
@@ -219,3 +332,36 @@ where
 
     Ok(fail)
 }
+
+/// Whether the owned ref `owned` is only accepted if signed, see
+/// [`fetch::must_be_signed`].
+fn must_be_signed(owned: &BStr) -> bool {
+    use either::Either::Right;
+    use refs::parsed::{Identity, Refs};
+
+    matches!(
+        refs::parse::<Identity>(owned),
+        Some(refs::Parsed {
+            inner: Right(Refs { ref cat, .. }),
+            ..
+        }) if fetch::must_be_signed(cat)
+    )
+}
+
+/// `true` if `owned` is of a category which is only fetched because it is
+/// configured in `spec`.
+fn is_configured(spec: &fetch::FetchSpec, owned: &BStr) -> bool {
+    use either::Either::Right;
+    use refs::parsed::{Cat, Identity, Refs};
+
+    matches!(
+        refs::parse::<Identity>(owned),
+        Some(refs::Parsed {
+            inner: Right(Refs {
+                cat: ref cat @ Cat::Unknown(_),
+                ..
+            }),
+            ..
+        }) if spec.allows(cat)
+    )
+}
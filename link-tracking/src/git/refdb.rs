@@ -79,6 +79,10 @@ pub struct Applied<'a, Oid: ToOwned + Clone> {
     pub updates: Vec<Updated<'a, Oid>>,
     /// The rejected [`Update`]s based on their [`PreviousValue`].
     pub rejections: Vec<PreviousError<Oid>>,
+    /// The [`Update`]s which had nothing to do, ie. writing a reference which
+    /// already had the given `target`, or deleting a reference which did not
+    /// exist.
+    pub unchanged: Vec<Update<'a, Oid>>,
 }
 
 impl<'a, Oid: ToOwned + Clone> Default for Applied<'a, Oid> {
@@ -86,6 +90,7 @@ impl<'a, Oid: ToOwned + Clone> Default for Applied<'a, Oid> {
         Applied {
             updates: Vec::new(),
             rejections: Vec::new(),
+            unchanged: Vec::new(),
         }
     }
 }
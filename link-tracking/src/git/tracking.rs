@@ -88,9 +88,10 @@ where
         |refdb::Applied {
              updates,
              rejections,
+             unchanged,
          }| {
-            match updates.first() {
-                Some(updated) => {
+            match (updates.first(), unchanged.first()) {
+                (Some(updated), _) => {
                     debug_assert!(rejections.is_empty());
                     match updated {
                         refdb::Updated::Written { name, target } => Ok(Ref {
@@ -102,7 +103,19 @@ where
                         },
                     }
                 },
-                None => {
+                (None, Some(unchanged)) => {
+                    debug_assert!(rejections.is_empty());
+                    match unchanged {
+                        refdb::Update::Write { name, target, .. } => Ok(Ref {
+                            name: name.clone().into_owned(),
+                            target: *target,
+                        }),
+                        refdb::Update::Delete { .. } => {
+                            panic!("BUG: Update::Write was expected, found Update::Delete")
+                        },
+                    }
+                },
+                (None, None) => {
                     debug_assert!(!rejections.is_empty());
                     Err(*rejections.first().unwrap())
                 },
@@ -173,9 +186,10 @@ where
         |refdb::Applied {
              updates,
              rejections,
+             unchanged,
          }| {
-            match updates.first() {
-                Some(updated) => {
+            match (updates.first(), unchanged.first()) {
+                (Some(updated), _) => {
                     debug_assert!(rejections.is_empty());
                     match updated {
                         refdb::Updated::Written { name, target } => Ok(Ref {
@@ -187,7 +201,19 @@ where
                         },
                     }
                 },
-                None => {
+                (None, Some(unchanged)) => {
+                    debug_assert!(rejections.is_empty());
+                    match unchanged {
+                        refdb::Update::Write { name, target, .. } => Ok(Ref {
+                            name: name.clone().into_owned(),
+                            target: *target,
+                        }),
+                        refdb::Update::Delete { .. } => {
+                            panic!("BUG: Update::Write was expected, found Update::Delete")
+                        },
+                    }
+                },
+                (None, None) => {
                     debug_assert!(!rejections.is_empty());
                     Err(*rejections.first().unwrap())
                 },
@@ -234,6 +260,7 @@ where
         |refdb::Applied {
              updates,
              rejections,
+             ..
          }| {
             match updates.first() {
                 Some(updated) => match updated {
@@ -309,6 +336,7 @@ where
             |refdb::Applied {
                  updates,
                  rejections,
+                 ..
              }| {
                 updates
                     .into_iter()
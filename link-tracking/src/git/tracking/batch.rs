@@ -31,6 +31,9 @@ pub enum Action<'a, Oid: Clone> {
 pub struct Applied {
     pub updates: Vec<Updated>,
     pub rejections: Vec<PreviousError>,
+    /// The references which were already in the state requested by an
+    /// [`Action`], and were left untouched.
+    pub unchanged: Vec<RefName<'static, Oid>>,
 }
 
 impl From<refdb::Applied<'_, Oid>> for Applied {
@@ -38,11 +41,20 @@ impl From<refdb::Applied<'_, Oid>> for Applied {
         refdb::Applied {
             updates,
             rejections,
+            unchanged,
         }: refdb::Applied<'_, Oid>,
     ) -> Self {
         Self {
             updates: updates.into_iter().map(Updated::from).collect(),
             rejections,
+            unchanged: unchanged
+                .into_iter()
+                .map(|update| match update {
+                    refdb::Update::Write { name, .. } | refdb::Update::Delete { name, .. } => {
+                        name.into_owned()
+                    },
+                })
+                .collect(),
         }
     }
 }
@@ -0,0 +1,376 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Read-only HTTP API exposing the state of the node as JSON, so that
+//! frontends don't need to link against `librad`.
+//!
+//! The following routes are served:
+//!
+//! * `GET /v1/status`: statistics of the p2p protocol
+//! * `GET /v1/peers`: the connected peers and their addresses
+//! * `GET /v1/projects`: the identity documents of all projects
+//! * `GET /v1/identities/<urn id>`: the identity document of a project or
+//!   person
+//! * `GET /v1/tracking[/<urn id>]`: the tracking entries, optionally for a
+//!   single URN only
+//! * `GET /v1/replication/<urn id>`: the signed refs of the local and tracked
+//!   peers for a URN, i.e. what has been replicated so far
+//! * `GET /v1/audit`: the verified audit log of identity and tracking changes,
+//!   see [`librad::git::audit`]
+//!
+//! Every request has to present the [`Token`] the API was started with in an
+//! `Authorization: Bearer <token>` header, otherwise it is rejected with `401
+//! Unauthorized`.
+
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt,
+    fs,
+    io,
+    net::{SocketAddr, TcpListener},
+    path::Path,
+    sync::Arc,
+};
+
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body,
+    Method,
+    Request,
+    Response,
+    Server,
+    StatusCode,
+};
+use serde::Serialize;
+use tracing::{debug, error, info, instrument};
+
+use librad::{
+    git::{
+        audit,
+        identities::{self, SomeIdentity},
+        refs::Refs,
+        storage::{ReadOnly, ReadOnlyStorage as _},
+        tracking,
+        Urn,
+    },
+    net::peer::Peer,
+    PeerId,
+    Signer,
+};
+
+/// The secret clients of the API have to present as a bearer token.
+#[derive(Clone)]
+pub struct Token(Arc<str>);
+
+impl Token {
+    /// Read the token from the file at `path`, ignoring surrounding
+    /// whitespace.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let token = fs::read_to_string(path)?;
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the api token file is empty",
+            ));
+        }
+        Ok(Self(token.into()))
+    }
+
+    /// The secret itself.
+    pub(crate) fn secret(&self) -> Arc<str> {
+        self.0.clone()
+    }
+
+    /// Whether the `Authorization` header of `req` carries this token.
+    fn authorizes(&self, req: &Request<Body>) -> bool {
+        req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map_or(false, |given| {
+                // Compare in constant time, so the token can't be guessed byte
+                // by byte
+                given.len() == self.0.len()
+                    && given
+                        .bytes()
+                        .zip(self.0.bytes())
+                        .fold(0, |acc, (a, b)| acc | (a ^ b))
+                        == 0
+            })
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Token(..)")
+    }
+}
+
+enum Route {
+    Status,
+    Peers,
+    Projects,
+    Identity(Urn),
+    Tracking(Option<Urn>),
+    Replication(Urn),
+    Audit,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    peer_id: PeerId,
+    connections_total: usize,
+    connected_peers: usize,
+    membership_active: usize,
+    membership_passive: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectedPeer {
+    peer_id: PeerId,
+    addrs: Vec<SocketAddr>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TrackingEntry {
+    urn: Urn,
+    /// `None` for the default entry, which applies to all peers.
+    peer_id: Option<PeerId>,
+    data: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Replicated {
+    /// `None` for the local peer.
+    peer_id: Option<PeerId>,
+    heads: BTreeMap<String, String>,
+    tags: BTreeMap<String, String>,
+}
+
+#[instrument(name = "api subroutine", skip(peer, listener, token))]
+pub async fn routine<S>(peer: Peer<S>, listener: TcpListener, token: Token) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    info!("starting api routine");
+
+    let make_svc = make_service_fn(move |_conn| {
+        let peer = peer.clone();
+        let token = token.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                serve(peer.clone(), token.clone(), req)
+            }))
+        }
+    });
+    let server = Server::from_tcp(listener)?.serve(make_svc);
+    info!("serving api at {}", server.local_addr());
+    server.await?;
+
+    Ok(())
+}
+
+async fn serve<S>(
+    peer: Peer<S>,
+    token: Token,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible>
+where
+    S: Signer + Clone,
+{
+    debug!(method = %req.method(), uri = %req.uri(), "request");
+    if !token.authorizes(&req) {
+        let mut response = respond(StatusCode::UNAUTHORIZED, "unauthorized");
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Bearer"),
+        );
+        return Ok(response);
+    }
+    Ok(handle(peer, req).await.unwrap_or_else(|err| {
+        error!(err = ?err, "failed to serve request");
+        respond(StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
+    }))
+}
+
+async fn handle<S>(peer: Peer<S>, req: Request<Body>) -> anyhow::Result<Response<Body>>
+where
+    S: Signer + Clone,
+{
+    if req.method() != Method::GET {
+        return Ok(respond(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method not allowed",
+        ));
+    }
+    let route = match route(req.uri().path()) {
+        Some(route) => route,
+        None => return Ok(respond(StatusCode::NOT_FOUND, "not found")),
+    };
+
+    match route {
+        Route::Status => {
+            let stats = peer.stats().await;
+            json(&Status {
+                peer_id: peer.peer_id(),
+                connections_total: stats.connections_total,
+                connected_peers: stats.connected_peers.len(),
+                membership_active: stats.membership_active,
+                membership_passive: stats.membership_passive,
+            })
+        },
+
+        Route::Peers => {
+            let stats = peer.stats().await;
+            json(
+                &stats
+                    .connected_peers
+                    .into_iter()
+                    .map(|(peer_id, addrs)| ConnectedPeer { peer_id, addrs })
+                    .collect::<Vec<_>>(),
+            )
+        },
+
+        Route::Projects => {
+            let projects = peer
+                .using_read_only(|storage| {
+                    identities::any::list(storage)?
+                        .filter_map(|identity| match identity {
+                            Ok(SomeIdentity::Project(project)) => Some(Ok(project)),
+                            Ok(_) => None,
+                            Err(err) => Some(Err(err)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .await??;
+            json(&projects)
+        },
+
+        Route::Identity(urn) => {
+            let identity = peer
+                .using_read_only(move |storage| identities::any::get(storage, &urn))
+                .await??;
+            match identity {
+                None => Ok(respond(StatusCode::NOT_FOUND, "not found")),
+                Some(SomeIdentity::Project(project)) => json(&project),
+                Some(SomeIdentity::Person(person)) => json(&person),
+                Some(_) => Ok(respond(
+                    StatusCode::NOT_IMPLEMENTED,
+                    "unsupported identity type",
+                )),
+            }
+        },
+
+        Route::Tracking(urn) => {
+            let entries = peer
+                .using_read_only(move |storage| tracking_entries(storage, urn.as_ref()))
+                .await??;
+            json(&entries)
+        },
+
+        Route::Replication(urn) => {
+            let replicated = peer
+                .using_read_only(move |storage| replicated(storage, &urn))
+                .await??;
+            match replicated {
+                None => Ok(respond(StatusCode::NOT_FOUND, "not found")),
+                Some(replicated) => json(&replicated),
+            }
+        },
+
+        Route::Audit => {
+            let records = peer.using_read_only(audit::entries).await??;
+            json(&records)
+        },
+    }
+}
+
+/// Parse the `/v1/...` routes.
+fn route(path: &str) -> Option<Route> {
+    let path = path.strip_prefix("/v1/")?;
+    let segments = path.trim_end_matches('/').split('/').collect::<Vec<_>>();
+    let urn = |id: &str| Urn::try_from_id(id).ok();
+
+    match segments[..] {
+        ["status"] => Some(Route::Status),
+        ["peers"] => Some(Route::Peers),
+        ["projects"] => Some(Route::Projects),
+        ["identities", id] => urn(id).map(Route::Identity),
+        ["tracking"] => Some(Route::Tracking(None)),
+        ["tracking", id] => urn(id).map(|urn| Route::Tracking(Some(urn))),
+        ["replication", id] => urn(id).map(Route::Replication),
+        ["audit"] => Some(Route::Audit),
+        _ => None,
+    }
+}
+
+fn tracking_entries(storage: &ReadOnly, urn: Option<&Urn>) -> anyhow::Result<Vec<TrackingEntry>> {
+    tracking::tracked(storage, urn)?
+        .map(|tracked| {
+            let tracked = tracked?;
+            Ok(TrackingEntry {
+                urn: tracked.urn().clone(),
+                peer_id: tracked.peer_id(),
+                data: tracked.config().data,
+            })
+        })
+        .collect()
+}
+
+/// The signed refs of the local peer and all tracked peers for `urn`, or
+/// `None` if `urn` doesn't exist.
+fn replicated(storage: &ReadOnly, urn: &Urn) -> anyhow::Result<Option<Vec<Replicated>>> {
+    if !storage.has_urn(urn)? {
+        return Ok(None);
+    }
+
+    let mut peers = vec![None];
+    for peer_id in tracking::tracked_peers(storage, Some(urn))? {
+        peers.push(Some(peer_id?));
+    }
+
+    let mut replicated = Vec::new();
+    for peer_id in peers {
+        if let Some(refs) = Refs::load(storage, urn, peer_id)? {
+            replicated.push(Replicated {
+                peer_id,
+                heads: refs
+                    .heads()
+                    .map(|(name, oid)| (name.to_string(), oid.to_string()))
+                    .collect(),
+                tags: refs
+                    .tags()
+                    .map(|(name, oid)| (name.to_string(), oid.to_string()))
+                    .collect(),
+            });
+        }
+    }
+
+    Ok(Some(replicated))
+}
+
+fn json<T: Serialize>(value: &T) -> anyhow::Result<Response<Body>> {
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from(serde_json::to_vec(value)?))?)
+}
+
+fn respond(status: StatusCode, msg: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(format!("{}\n", msg)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain"),
+    );
+    response
+}
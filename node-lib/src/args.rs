@@ -15,7 +15,7 @@ use librad::{
     crypto,
     git::Urn,
     net::Network,
-    profile::{ProfileId, RadHome},
+    profile::{petnames::PeerOrPetname, ProfileId, RadHome},
     PeerId,
 };
 use rad_clib::keys::ssh::SshAuthSock;
@@ -46,6 +46,21 @@ pub struct Args {
     #[structopt(long, default_value)]
     pub signer: Signer,
 
+    #[structopt(flatten)]
+    pub api: ApiArgs,
+
+    #[structopt(flatten)]
+    pub autosync: AutosyncArgs,
+
+    #[structopt(flatten)]
+    pub control: ControlArgs,
+
+    #[structopt(flatten)]
+    pub feed: FeedArgs,
+
+    #[structopt(flatten)]
+    pub http: HttpArgs,
+
     #[structopt(flatten)]
     pub key: KeyArgs,
 
@@ -133,6 +148,68 @@ impl FromStr for Signer {
     }
 }
 
+#[derive(Debug, Default, Eq, PartialEq, StructOpt)]
+pub struct ApiArgs {
+    /// Serve the read-only JSON API, exposing the state of the node, e.g.
+    /// projects, peers and tracking entries, on the loopback interface.
+    /// Disabled if neither this nor `--api-listen` is provided.
+    #[structopt(long = "api")]
+    pub enable: bool,
+
+    /// Address to serve the JSON API on instead of the loopback interface.
+    /// Implies `--api`.
+    #[structopt(long = "api-listen", name = "api-listen")]
+    pub listen: Option<SocketAddr>,
+
+    /// File containing the token clients of the JSON API have to present in an
+    /// `Authorization: Bearer <token>` header. Required if the API is
+    /// enabled.
+    #[structopt(long = "api-token-file", name = "api-token-file", parse(from_str))]
+    pub token_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, StructOpt)]
+pub struct AutosyncArgs {
+    /// Replicate from peers announcing new refs of tracked URNs when fetching
+    /// them on receipt of the announcement failed. Disabled if not provided.
+    #[structopt(long = "autosync")]
+    pub enable: bool,
+
+    /// Maximum number of replications started by `--autosync` running at the
+    /// same time.
+    #[structopt(long = "autosync-max-concurrent", name = "autosync-max-concurrent")]
+    pub max_concurrent: Option<usize>,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, StructOpt)]
+pub struct ControlArgs {
+    /// Path of the Unix domain socket to serve the control API on, which allows
+    /// to sync, track and untrack URNs, and to shut down the node. Only the
+    /// user running the node may connect. A stale socket left behind by a
+    /// previous run is removed. Disabled if not provided.
+    #[structopt(long = "control-socket", name = "control-socket", parse(from_str))]
+    pub socket: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, StructOpt)]
+pub struct FeedArgs {
+    /// Address to serve the WebSocket feed of node events on, e.g. peers
+    /// connecting and projects being replicated. Clients have to present the
+    /// token of the JSON API, see `--api-token-file`. Disabled if not
+    /// provided.
+    #[structopt(long = "feed-listen", name = "feed-listen")]
+    pub listen: Option<SocketAddr>,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, StructOpt)]
+pub struct HttpArgs {
+    /// Address to serve the projects of the monorepo on over the read-only git
+    /// smart HTTP protocol, so they can be cloned without any radicle tooling.
+    /// Disabled if not provided.
+    #[structopt(long = "http-listen", name = "http-listen")]
+    pub listen: Option<SocketAddr>,
+}
+
 #[derive(Debug, Default, Eq, PartialEq, StructOpt)]
 pub struct KeyArgs {
     /// Location of the key file on disk.
@@ -296,6 +373,13 @@ pub struct ProtocolArgs {
         parse(try_from_str = parse_protocol_network))
     ]
     pub network: Network,
+
+    /// How many hops away in the tracking graph of the replicated projects the
+    /// refs of peers are fetched. Seed nodes may want to widen the fan-out, 0
+    /// only fetches the refs of tracked peers. Only supported by the
+    /// `replication-v3` backend.
+    #[structopt(long = "protocol-tracking-cutoff", name = "protocol-tracking-cutoff")]
+    pub tracking_cutoff: Option<usize>,
     // TODO(xla): Expose protocol args (membership, replication, etc.).
 }
 
@@ -338,24 +422,50 @@ pub struct TrackingArgs {
     /// Instruct the node to automatically track either everything it observes
     /// or a selected set of peer ids and urns which need to be provided
     /// through extra arguments to take effect.
+    ///
+    /// In `open` mode, the node tracks everything it observes as well as
+    /// everything it is asked to provide, subject to the `--track-max-*` quotas
+    /// and `--block-*` lists.
     #[structopt(long = "track", name = "track")]
     pub mode: Option<TrackingMode>,
 
-    /// Track all updates from a specific peer. Argument can be repeated. Use in
-    /// conjunction with `--track="selected"`.
+    /// Track all updates from a specific peer, given by its peer id or
+    /// petname. Argument can be repeated. Use in conjunction with
+    /// `--track="selected"`.
     #[structopt(long = "track-peer-id", name = "track-peer-id")]
-    pub peer_ids: Vec<PeerId>,
+    pub peer_ids: Vec<PeerOrPetname>,
 
     /// Track all updates for a specific project urn. Argument can be repeated.
     /// Use in conjunction with `--track="selected"`.
     #[structopt(long = "track-urn", name = "track-urn")]
     pub urns: Vec<Urn>,
+
+    /// Maximum number of urns to track. Use in conjunction with
+    /// `--track="open"`.
+    #[structopt(long = "track-max-urns", name = "track-max-urns")]
+    pub max_urns: Option<usize>,
+
+    /// Maximum number of peers to track per urn. Use in conjunction with
+    /// `--track="open"`.
+    #[structopt(long = "track-max-peers", name = "track-max-peers")]
+    pub max_peers: Option<usize>,
+
+    /// Never track a specific peer, given by its peer id or petname. Argument
+    /// can be repeated. Use in conjunction with `--track="open"`.
+    #[structopt(long = "block-peer-id", name = "block-peer-id")]
+    pub blocked_peer_ids: Vec<PeerOrPetname>,
+
+    /// Never track a specific project urn. Argument can be repeated. Use in
+    /// conjunction with `--track="open"`.
+    #[structopt(long = "block-urn", name = "block-urn")]
+    pub blocked_urns: Vec<Urn>,
 }
 
 #[derive(Debug, Eq, PartialEq, StructOpt)]
 pub enum TrackingMode {
     Everything,
     Selected,
+    Open,
 }
 
 impl FromStr for TrackingMode {
@@ -365,6 +475,7 @@ impl FromStr for TrackingMode {
         match input {
             "everything" => Ok(Self::Everything),
             "selected" => Ok(Self::Selected),
+            "open" => Ok(Self::Open),
             _ => Err(format!("unsupported tracking mode `{}`", input)),
         }
     }
@@ -7,6 +7,7 @@ use std::{
     convert::TryFrom,
     io,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs as _},
+    path::PathBuf,
     time::Duration,
 };
 
@@ -23,13 +24,16 @@ use librad::{
     git::storage,
     keystore::SecretKeyExt as _,
     net,
-    net::{discovery, peer::Config as PeerConfig},
+    net::{
+        discovery,
+        peer::{autosync, Config as PeerConfig},
+    },
     profile::{Profile, RadHome},
     SecretKey,
 };
 use rad_clib::keys;
 
-use crate::{args, tracking::Tracker};
+use crate::{api, args, tracking::Tracker};
 
 mod seed;
 pub use seed::{Seed, Seeds};
@@ -42,10 +46,17 @@ lazy_static::lazy_static! {
     /// Localhost binding to any available port, i.e. `127.0.0.1:0`.
     pub static ref LOCALHOST: SocketAddr =
         SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+
+    /// Default binding of the JSON API, i.e. `127.0.0.1:8777`.
+    pub static ref API_LOCALHOST: SocketAddr =
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 8777));
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("the api and the event feed require a token, see `--api-token-file`")]
+    ApiToken,
+
     #[error("decoding base64 key")]
     Base64(#[from] base64::DecodeError),
 
@@ -61,6 +72,9 @@ pub enum Error {
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 
+    #[error(transparent)]
+    Petnames(#[from] librad::profile::petnames::Error),
+
     #[error(transparent)]
     Profile(#[from] librad::profile::Error),
 
@@ -72,10 +86,19 @@ pub enum Error {
 
     #[error(transparent)]
     Timeout(#[from] Elapsed),
+
+    #[error("`--protocol-tracking-cutoff` requires the replication-v3 backend")]
+    TrackingCutoff,
 }
 
 pub struct Cfg<Disco, Signer> {
+    pub api: Option<SocketAddr>,
+    pub api_token: Option<api::Token>,
+    pub autosync: Option<autosync::Config>,
+    pub control: Option<PathBuf>,
     pub disco: Disco,
+    pub feed: Option<SocketAddr>,
+    pub http: Option<SocketAddr>,
     pub metrics: Option<Metrics>,
     pub peer: PeerConfig<Signer>,
     pub tracker: Option<Tracker>,
@@ -97,6 +120,26 @@ impl Cfg<discovery::Static, BoxedSigner> {
             args::ProtocolListen::Provided { addr } => addr,
         };
 
+        let api = (args.api.enable || args.api.listen.is_some())
+            .then(|| args.api.listen.unwrap_or(*API_LOCALHOST));
+        let api_token = args
+            .api
+            .token_file
+            .as_ref()
+            .map(api::Token::from_file)
+            .transpose()?;
+        if (api.is_some() || args.feed.listen.is_some()) && api_token.is_none() {
+            return Err(Error::ApiToken);
+        }
+
+        let autosync = args.autosync.enable.then(|| {
+            let default = autosync::Config::default();
+            autosync::Config {
+                max_concurrent: args.autosync.max_concurrent.unwrap_or(default.max_concurrent),
+                ..default
+            }
+        });
+
         let metrics = match args.metrics.provider {
             Some(args::MetricsProvider::Graphite) => Some(Metrics::Graphite(
                 args.metrics
@@ -109,7 +152,13 @@ impl Cfg<discovery::Static, BoxedSigner> {
         };
 
         Ok(Self {
+            api,
+            api_token,
+            autosync,
+            control: args.control.socket.clone(),
             disco,
+            feed: args.feed.listen,
+            http: args.http.listen,
             metrics,
             peer: PeerConfig {
                 signer,
@@ -119,22 +168,33 @@ impl Cfg<discovery::Static, BoxedSigner> {
                     advertised_addrs: None,
                     membership: Default::default(),
                     network: args.protocol.network.clone(),
-                    replication: Default::default(),
+                    replication: replication(&args.protocol)?,
                     rate_limits: Default::default(),
                 },
                 storage: Default::default(),
             },
-            tracker: args.tracking.mode.as_ref().map(|arg| match arg {
-                args::TrackingMode::Everything => Tracker::Everything,
-                args::TrackingMode::Selected => Tracker::Selected {
-                    peer_ids: args.tracking.peer_ids.clone().into_iter().collect(),
-                    urns: args.tracking.urns.clone().into_iter().collect(),
-                },
-            }),
+            tracker: Tracker::from_args(&args.tracking, &profile.petnames().load()?)?,
         })
     }
 }
 
+#[cfg(feature = "replication-v3")]
+fn replication(args: &args::ProtocolArgs) -> Result<net::replication::Config, Error> {
+    let default = net::replication::Config::default();
+    Ok(net::replication::Config {
+        tracking_cutoff: args.tracking_cutoff.unwrap_or(default.tracking_cutoff),
+        ..default
+    })
+}
+
+#[cfg(not(feature = "replication-v3"))]
+fn replication(args: &args::ProtocolArgs) -> Result<net::replication::Config, Error> {
+    if args.tracking_cutoff.is_some() {
+        return Err(Error::TrackingCutoff);
+    }
+    Ok(net::replication::Config::default())
+}
+
 pub enum Metrics {
     Graphite(SocketAddr),
 }
@@ -0,0 +1,321 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Control API for driving the node programmatically.
+//!
+//! The API is served on a Unix domain socket. Each request is a single line of
+//! JSON of the form `{"method": <method>, "params": {...}}`, and is answered
+//! by a single line of JSON, either `{"ok": <result>}` or `{"error":
+//! <message>}`. Multiple requests may be sent over the same connection, they
+//! are answered in order.
+//!
+//! The supported methods are:
+//!
+//! * `sync`: replicate a URN from the given peer, or else from all providers
+//!   found on the network
+//! * `track` / `untrack`: modify the tracking entry of a URN, optionally for a
+//!   peer only. The include file of the URN is regenerated on change, see
+//!   [`librad::git::include::update`]
+//! * `providers`: query the network for providers of a URN
+//! * `shutdown`: stop the node
+//!
+//! Only the user running the node is allowed to connect: the socket is created
+//! with mode `0600`, and the credentials of each connecting peer are checked
+//! as well, as a socket passed by the service manager may be more permissive.
+
+use std::{
+    fs,
+    io,
+    net::SocketAddr,
+    os::unix::fs::{FileTypeExt as _, PermissionsExt as _},
+    path::Path,
+    time::Duration,
+};
+
+use futures::StreamExt as _;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+};
+use tracing::{debug, error, info, instrument, warn};
+
+use librad::{
+    git::{tracking, Urn},
+    net::peer::{Peer, PeerInfo},
+    PeerId,
+    Signer,
+};
+
+/// The time to wait for providers if none is given in the request.
+const DEFAULT_PROVIDERS_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "camelCase")]
+enum Request {
+    #[serde(rename_all = "camelCase")]
+    Sync {
+        urn: Urn,
+        /// If not given, providers are queried for.
+        peer_id: Option<PeerId>,
+        #[serde(default)]
+        addrs: Vec<SocketAddr>,
+        timeout_ms: Option<u64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Track {
+        urn: Urn,
+        peer_id: Option<PeerId>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Untrack {
+        urn: Urn,
+        peer_id: PeerId,
+    },
+    #[serde(rename_all = "camelCase")]
+    Providers {
+        urn: Urn,
+        timeout_ms: Option<u64>,
+    },
+    Shutdown,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Provider {
+    peer_id: PeerId,
+    addrs: Vec<SocketAddr>,
+}
+
+/// Bind the control socket at `path`, accessible to the current user only.
+///
+/// A socket left behind by a node which didn't shut down cleanly is removed,
+/// but binding fails if another node is still serving on `path`.
+pub fn bind(path: &Path) -> io::Result<std::os::unix::net::UnixListener> {
+    let is_socket = fs::symlink_metadata(path)
+        .map(|meta| meta.file_type().is_socket())
+        .unwrap_or(false);
+    if is_socket {
+        match std::os::unix::net::UnixStream::connect(path) {
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("control socket {} is in use", path.display()),
+                ))
+            },
+            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                warn!(path = %path.display(), "removing stale control socket");
+                fs::remove_file(path)?;
+            },
+            Err(_) => {},
+        }
+    }
+
+    let listener = std::os::unix::net::UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Whether the peer connected via `stream` runs as the same user as the node.
+pub fn authorized(stream: &UnixStream) -> io::Result<bool> {
+    let cred = stream.peer_cred()?;
+    Ok(cred.uid() == nix::unistd::geteuid().as_raw())
+}
+
+#[instrument(name = "control subroutine", skip(peer, listener, shutdown_tx))]
+pub async fn routine<S>(
+    peer: Peer<S>,
+    listener: UnixListener,
+    shutdown_tx: mpsc::Sender<()>,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    info!("starting control routine");
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        match authorized(&stream) {
+            Ok(true) => {},
+            Ok(false) => {
+                warn!("rejecting control connection from another user");
+                continue;
+            },
+            Err(err) => {
+                warn!(err = ?err, "rejecting control connection of unknown peer");
+                continue;
+            },
+        }
+        let peer = peer.clone();
+        let shutdown_tx = shutdown_tx.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve(peer, stream, shutdown_tx).await {
+                error!(err = ?err, "control connection failed");
+            }
+        });
+    }
+}
+
+async fn serve<S>(
+    peer: Peer<S>,
+    stream: UnixStream,
+    shutdown_tx: mpsc::Sender<()>,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let (recv, mut send) = stream.into_split();
+    let mut lines = BufReader::new(recv).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Err(err) => json!({ "error": format!("invalid request: {}", err) }),
+            Ok(req) => {
+                debug!(?req, "request");
+                match handle(&peer, req, &shutdown_tx).await {
+                    Ok(ok) => json!({ "ok": ok }),
+                    Err(err) => {
+                        error!(err = ?err, "failed to handle request");
+                        json!({ "error": err.to_string() })
+                    },
+                }
+            },
+        };
+        let mut response = serde_json::to_vec(&response)?;
+        response.push(b'\n');
+        send.write_all(&response).await?;
+    }
+
+    Ok(())
+}
+
+async fn handle<S>(
+    peer: &Peer<S>,
+    req: Request,
+    shutdown_tx: &mpsc::Sender<()>,
+) -> anyhow::Result<Value>
+where
+    S: Signer + Clone,
+{
+    match req {
+        Request::Sync {
+            urn,
+            peer_id,
+            addrs,
+            timeout_ms,
+        } => {
+            let from = match peer_id {
+                Some(peer_id) => vec![(peer_id, addrs)],
+                None => providers(peer, urn.clone(), timeout_ms)
+                    .await
+                    .into_iter()
+                    .map(|provider| (provider.peer_id, provider.addrs))
+                    .collect(),
+            };
+            if from.is_empty() {
+                anyhow::bail!("no providers found for {}", urn);
+            }
+
+            let mut synced = Vec::new();
+            for (peer_id, addrs) in from {
+                match peer.replicate((peer_id, addrs), urn.clone(), None).await {
+                    Ok(_) => synced.push(peer_id),
+                    Err(err) => error!(err = ?err, %urn, %peer_id, "sync failed"),
+                }
+            }
+            if synced.is_empty() {
+                anyhow::bail!("failed to sync {}", urn);
+            }
+
+            Ok(json!({ "synced": synced }))
+        },
+
+        Request::Track { urn, peer_id } => {
+            let updated = peer
+                .using_storage({
+                    let urn = urn.clone();
+                    move |storage| {
+                        tracking::track(
+                            storage,
+                            &urn,
+                            peer_id,
+                            tracking::Config::default(),
+                            tracking::policy::Track::MustNotExist,
+                        )
+                        .map(|res| res.is_ok())
+                    }
+                })
+                .await??;
+            if updated {
+                crate::tracking::update_include(peer, urn).await;
+            }
+            Ok(json!({ "updated": updated }))
+        },
+
+        Request::Untrack { urn, peer_id } => {
+            let updated = peer
+                .using_storage({
+                    let urn = urn.clone();
+                    move |storage| {
+                        tracking::untrack(
+                            storage,
+                            &urn,
+                            peer_id,
+                            tracking::policy::Untrack::MustExist,
+                        )
+                        .map(|res| res.is_ok())
+                    }
+                })
+                .await??;
+            if updated {
+                crate::tracking::update_include(peer, urn).await;
+            }
+            Ok(json!({ "updated": updated }))
+        },
+
+        Request::Providers { urn, timeout_ms } => Ok(serde_json::to_value(
+            providers(peer, urn, timeout_ms).await,
+        )?),
+
+        Request::Shutdown => {
+            info!("shutdown requested");
+            let _ = shutdown_tx.try_send(());
+            Ok(json!({}))
+        },
+    }
+}
+
+/// Query the network for providers of `urn`, collecting the responses until
+/// the timeout elapses.
+async fn providers<S>(peer: &Peer<S>, urn: Urn, timeout_ms: Option<u64>) -> Vec<Provider>
+where
+    S: Signer + Clone,
+{
+    let timeout = timeout_ms.map_or(DEFAULT_PROVIDERS_TIMEOUT, Duration::from_millis);
+    let mut providers: Vec<Provider> = Vec::new();
+    peer.providers(urn, timeout)
+        .for_each(
+            |PeerInfo {
+                 peer_id,
+                 seen_addrs,
+                 ..
+             }| {
+                if !providers.iter().any(|p| p.peer_id == peer_id) {
+                    providers.push(Provider {
+                        peer_id,
+                        addrs: seen_addrs.iter().copied().collect(),
+                    });
+                }
+                futures::future::ready(())
+            },
+        )
+        .await;
+
+    providers
+}
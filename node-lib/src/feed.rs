@@ -0,0 +1,63 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! WebSocket feed of node events, so that frontends can follow the activity
+//! of the node without polling the API.
+//!
+//! The messages are those of [`radicle_daemon::feed`], limited to what is
+//! observable from the protocol: peers connecting and disconnecting, and
+//! projects being replicated. Clients present the token of the
+//! [`crate::api`].
+
+use std::net::TcpListener;
+
+use futures::{pin_mut, Stream, StreamExt as _};
+use tokio::sync::broadcast;
+use tracing::{info, instrument, warn};
+
+use librad::net::{peer::ProtocolEvent, protocol::RecvError};
+use radicle_daemon::{
+    feed,
+    peer::{Event, RECEIVER_CAPACITY},
+};
+
+use crate::api;
+
+#[instrument(name = "feed subroutine", skip(listener, token, events))]
+pub async fn routine<E>(listener: TcpListener, token: api::Token, events: E) -> anyhow::Result<()>
+where
+    E: Stream<Item = Result<ProtocolEvent, RecvError>>,
+{
+    info!("starting feed routine");
+
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    info!("serving feed at {}", listener.local_addr()?);
+
+    let (sender, receiver) = broadcast::channel(RECEIVER_CAPACITY);
+    let forward = async move {
+        pin_mut!(events);
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(event) => {
+                    if let Some(event) = Event::from_protocol(&event) {
+                        // Ignore if there are no clients.
+                        sender.send(event).ok();
+                    }
+                },
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "feed lagging behind the protocol")
+                },
+                Err(RecvError::Closed) => break,
+            }
+        }
+        // Dropping the sender stops the feed.
+    };
+    let token = feed::Token::new(token.secret());
+    let ((), served) = futures::future::join(forward, feed::serve(listener, token, receiver)).await;
+    served?;
+
+    Ok(())
+}
@@ -0,0 +1,323 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Serve the projects of the monorepo over the git smart HTTP protocol.
+//!
+//! A project is served at `/<urn id>.git`, so that it can be cloned without
+//! any radicle tooling, e.g. `git clone http://seed.example/<urn id>.git`. The
+//! bridge is read-only: only `git-upload-pack` is supported, and the same refs
+//! are visible as via the local transport, see
+//! [`librad::git::local::transport::command`].
+
+use std::{convert::Infallible, io::Read as _, net::TcpListener, process::Stdio};
+
+use flate2::read::GzDecoder;
+use git2::transport::Service;
+use hyper::{
+    body::{Bytes, HttpBody as _},
+    header,
+    service::{make_service_fn, service_fn},
+    Body,
+    Method,
+    Request,
+    Response,
+    Server,
+    StatusCode,
+};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    process::Command,
+};
+use tracing::{debug, error, info, instrument};
+
+use librad::{
+    git::{
+        local::{receive, transport},
+        Urn,
+    },
+    net::peer::Peer,
+    Signer,
+};
+
+const UPLOAD_PACK: &str = "git-upload-pack";
+const RECEIVE_PACK: &str = "git-receive-pack";
+
+/// The maximum size of an `upload-pack` request, after decompression.
+const MAX_REQUEST_SIZE: usize = 8 * 1024 * 1024;
+
+enum Route {
+    InfoRefs,
+    UploadPack,
+}
+
+#[instrument(name = "git http subroutine", skip(peer, listener))]
+pub async fn routine<S>(peer: Peer<S>, listener: TcpListener) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    info!("starting git http routine");
+
+    let make_svc = make_service_fn(move |_conn| {
+        let peer = peer.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| serve(peer.clone(), req))) }
+    });
+    let server = Server::from_tcp(listener)?.serve(make_svc);
+    info!("serving git over http at {}", server.local_addr());
+    server.await?;
+
+    Ok(())
+}
+
+async fn serve<S>(peer: Peer<S>, req: Request<Body>) -> Result<Response<Body>, Infallible>
+where
+    S: Signer + Clone,
+{
+    debug!(method = %req.method(), uri = %req.uri(), "request");
+    Ok(handle(peer, req).await.unwrap_or_else(|err| {
+        error!(err = ?err, "failed to serve request");
+        respond(StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
+    }))
+}
+
+async fn handle<S>(peer: Peer<S>, req: Request<Body>) -> anyhow::Result<Response<Body>>
+where
+    S: Signer + Clone,
+{
+    let (urn, route) = match route(req.uri().path()) {
+        Some(route) => route,
+        None => return Ok(respond(StatusCode::NOT_FOUND, "not found")),
+    };
+    let protocol = req
+        .headers()
+        .get("Git-Protocol")
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned);
+
+    match (req.method(), route) {
+        (&Method::GET, Route::InfoRefs) => {
+            let service = req.uri().query().and_then(|query| {
+                query
+                    .split('&')
+                    .find_map(|param| param.strip_prefix("service="))
+            });
+            match service {
+                Some(UPLOAD_PACK) => {},
+                Some(RECEIVE_PACK) => {
+                    return Ok(respond(StatusCode::FORBIDDEN, "pushing is not supported"))
+                },
+                _ => {
+                    return Ok(respond(
+                        StatusCode::FORBIDDEN,
+                        "only the smart HTTP protocol is supported",
+                    ))
+                },
+            }
+
+            // The service announcement is omitted for protocol v2, as
+            // `git-http-backend` does.
+            let is_v2 = protocol
+                .as_deref()
+                .map_or(false, |p| p.split(':').any(|param| param == "version=2"));
+            let preamble = if is_v2 {
+                vec![]
+            } else {
+                let mut preamble = pkt_line(&format!("# service={}\n", UPLOAD_PACK));
+                preamble.extend_from_slice(b"0000");
+                preamble
+            };
+
+            match upload_pack(&peer, urn, protocol, true).await? {
+                None => Ok(respond(StatusCode::NOT_FOUND, "not found")),
+                Some(git) => Ok(Response::builder()
+                    .header(
+                        header::CONTENT_TYPE,
+                        "application/x-git-upload-pack-advertisement",
+                    )
+                    .header(header::CACHE_CONTROL, "no-cache")
+                    .body(run(git, preamble, vec![])?)?),
+            }
+        },
+
+        (&Method::POST, Route::UploadPack) => {
+            let gzip = req
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .map_or(false, |encoding| encoding == "gzip");
+            let input = match read_request(req.into_body()).await? {
+                None => {
+                    return Ok(respond(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        "request is too large",
+                    ))
+                },
+                Some(input) if gzip => {
+                    let mut decoded = Vec::new();
+                    GzDecoder::new(&input[..])
+                        .take(MAX_REQUEST_SIZE as u64 + 1)
+                        .read_to_end(&mut decoded)?;
+                    if decoded.len() > MAX_REQUEST_SIZE {
+                        return Ok(respond(
+                            StatusCode::PAYLOAD_TOO_LARGE,
+                            "request is too large",
+                        ));
+                    }
+                    decoded
+                },
+                Some(input) => input,
+            };
+
+            match upload_pack(&peer, urn, protocol, false).await? {
+                None => Ok(respond(StatusCode::NOT_FOUND, "not found")),
+                Some(git) => Ok(Response::builder()
+                    .header(header::CONTENT_TYPE, "application/x-git-upload-pack-result")
+                    .header(header::CACHE_CONTROL, "no-cache")
+                    .body(run(git, vec![], input)?)?),
+            }
+        },
+
+        _ => Ok(respond(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method not allowed",
+        )),
+    }
+}
+
+/// Parse `/<urn id>[.git]/info/refs` and `/<urn id>[.git]/git-upload-pack`.
+fn route(path: &str) -> Option<(Urn, Route)> {
+    let path = path.strip_prefix('/')?;
+    let (repo, route) = if let Some(repo) = path.strip_suffix("/info/refs") {
+        (repo, Route::InfoRefs)
+    } else if let Some(repo) = path.strip_suffix("/git-upload-pack") {
+        (repo, Route::UploadPack)
+    } else {
+        return None;
+    };
+    let id = repo.strip_suffix(".git").unwrap_or(repo);
+    Urn::try_from_id(id).ok().map(|urn| (urn, route))
+}
+
+/// Prepare `git upload-pack` for `urn`, or `None` if `urn` doesn't exist.
+async fn upload_pack<S>(
+    peer: &Peer<S>,
+    urn: Urn,
+    protocol: Option<String>,
+    advertise: bool,
+) -> anyhow::Result<Option<Command>>
+where
+    S: Signer + Clone,
+{
+    // Pushes are refused, so the receive policy doesn't matter
+    let git = peer
+        .using_storage(move |storage| {
+            transport::command(
+                storage,
+                &urn,
+                Service::UploadPack,
+                &receive::Policy::default(),
+            )
+        })
+        .await?;
+    let mut git = match git {
+        Ok(git) => Command::from(git),
+        Err(transport::Error::NoSuchUrn(_)) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    git.arg("--stateless-rpc");
+    if advertise {
+        git.arg("--advertise-refs");
+    }
+    git.arg(".");
+    if let Some(protocol) = protocol {
+        git.env("GIT_PROTOCOL", protocol);
+    }
+    git.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true);
+
+    Ok(Some(git))
+}
+
+/// Spawn `git`, feeding it `input`, and stream its output after `preamble` as
+/// the response body.
+///
+/// If `git` fails, the body is aborted, so that the client doesn't mistake a
+/// truncated response for a complete one.
+fn run(mut git: Command, preamble: Vec<u8>, input: Vec<u8>) -> anyhow::Result<Body> {
+    let mut child = git.spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let (mut tx, body) = Body::channel();
+
+    tokio::spawn(async move {
+        let res = async {
+            if !preamble.is_empty() {
+                tx.send_data(preamble.into()).await?;
+            }
+            let write = async move {
+                stdin.write_all(&input).await?;
+                // Close stdin, so that `git` sees the end of the request
+                drop(stdin);
+                Ok::<_, anyhow::Error>(())
+            };
+            let read = async {
+                let mut buf = vec![0; 64 * 1024];
+                loop {
+                    let n = stdout.read(&mut buf).await?;
+                    if n == 0 {
+                        break;
+                    }
+                    tx.send_data(Bytes::copy_from_slice(&buf[..n])).await?;
+                }
+                Ok::<_, anyhow::Error>(())
+            };
+            let (written, read) = futures::join!(write, read);
+            written?;
+            read?;
+
+            let status = child.wait().await?;
+            if !status.success() {
+                anyhow::bail!("upload-pack exited unsuccessfully: {}", status)
+            }
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+
+        if let Err(err) = res {
+            error!(err = ?err, "failed to stream upload-pack");
+            tx.abort();
+        }
+    });
+
+    Ok(body)
+}
+
+/// Read the request body, or `None` if it exceeds [`MAX_REQUEST_SIZE`].
+async fn read_request(mut body: Body) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buf.len() + chunk.len() > MAX_REQUEST_SIZE {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Some(buf))
+}
+
+fn respond(status: StatusCode, msg: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(format!("{}\n", msg)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain"),
+    );
+    response
+}
+
+fn pkt_line(line: &str) -> Vec<u8> {
+    format!("{:04x}{}", line.len() + 4, line).into_bytes()
+}
@@ -3,17 +3,25 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+pub mod api;
 pub mod args;
 
 mod cfg;
 pub use cfg::{Seed, Seeds};
 
+#[cfg(unix)]
+pub mod control;
+
+pub mod feed;
+pub mod http;
 mod logging;
 mod metrics;
 pub mod node;
-mod protocol;
-mod signals;
-mod tracking;
+pub mod protocol;
+pub mod signals;
+pub mod tracking;
 
 #[cfg(unix)]
 pub mod socket_activation;
+#[cfg(target_os = "linux")]
+pub mod systemd;
@@ -3,7 +3,10 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::panic;
+use std::{
+    net::{SocketAddr, TcpListener},
+    panic,
+};
 
 use futures::future::{select_all, FutureExt as _};
 use structopt::StructOpt as _;
@@ -12,18 +15,28 @@ use tracing::info;
 
 use librad::{
     crypto::BoxedSigner,
-    net::{discovery, peer::Peer},
+    net::{
+        discovery,
+        peer::{autosync, Peer},
+    },
 };
 
+#[cfg(target_os = "linux")]
+use crate::systemd;
 use crate::{
+    api,
     args::Args,
     cfg::{self, Cfg},
+    feed,
+    http,
     logging,
     metrics::graphite,
     protocol,
     signals,
     tracking,
 };
+#[cfg(unix)]
+use crate::{control, socket_activation};
 
 pub async fn run() -> anyhow::Result<()> {
     logging::init();
@@ -32,39 +45,92 @@ pub async fn run() -> anyhow::Result<()> {
     let cfg: Cfg<discovery::Static, BoxedSigner> = cfg(&args).await?;
 
     let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+    #[cfg(unix)]
+    let control_shutdown_tx = shutdown_tx.clone();
     let signals_task = tokio::spawn(signals::routine(shutdown_tx));
 
     let mut coalesced = vec![];
     let peer = Peer::new(cfg.peer)?;
+    #[cfg(target_os = "linux")]
+    {
+        // Subscribe before the protocol is bound, so the endpoint coming up
+        // can't be missed.
+        let systemd_task = spawn(systemd::routine(peer.clone(), peer.subscribe())).fuse();
+        coalesced.push(systemd_task);
+    }
     let peer_task = spawn(protocol::routine(peer.clone(), cfg.disco, shutdown_rx)).fuse();
     coalesced.push(peer_task);
 
+    if let Some(config) = cfg.autosync {
+        let autosync_task = spawn({
+            let peer = peer.clone();
+            async move {
+                autosync::run(peer, config).await;
+                Ok(())
+            }
+        })
+        .fuse();
+        coalesced.push(autosync_task);
+    }
+
+    let mut listeners = listeners()?;
+
     if let Some(cfg::Metrics::Graphite(addr)) = cfg.metrics {
         let graphite_task = spawn(graphite::routine(peer.clone(), addr)).fuse();
         coalesced.push(graphite_task);
     }
 
+    if let Some(listener) = listen(&mut listeners, "api", cfg.api)? {
+        // A socket activated listener may be passed without `--api`
+        let token = cfg.api_token.clone().ok_or(cfg::Error::ApiToken)?;
+        let api_task = spawn(api::routine(peer.clone(), listener, token)).fuse();
+        coalesced.push(api_task);
+    }
+
+    if let Some(listener) = listen(&mut listeners, "feed", cfg.feed)? {
+        let token = cfg.api_token.clone().ok_or(cfg::Error::ApiToken)?;
+        let feed_task = spawn(feed::routine(listener, token, peer.subscribe())).fuse();
+        coalesced.push(feed_task);
+    }
+
+    if let Some(listener) = listen(&mut listeners, "http", cfg.http)? {
+        let http_task = spawn(http::routine(peer.clone(), listener)).fuse();
+        coalesced.push(http_task);
+    }
+
+    #[cfg(unix)]
+    {
+        let listener = match listeners.unix("control")? {
+            Some(listener) => Some(listener),
+            None => cfg.control.as_deref().map(control::bind).transpose()?,
+        };
+        if let Some(listener) = listener {
+            listener.set_nonblocking(true)?;
+            let listener = tokio::net::UnixListener::from_std(listener)?;
+            let control_task = spawn(control::routine(
+                peer.clone(),
+                listener,
+                control_shutdown_tx,
+            ))
+            .fuse();
+            coalesced.push(control_task);
+        }
+    }
+
     if let Some(tracker) = cfg.tracker {
         let tracking_task = spawn(tracking::routine(peer.clone(), tracker)).fuse();
         coalesced.push(tracking_task);
     }
 
-    // if let Some(_listener) = socket_activation::env()? {
-    // TODO(xla): Schedule listen loop.
-    // } else {
-    // TODO(xla): Bind to configured/default socket path, constructed from
-    // profile info.
-    // TODO(xla): Schedule listen loop.
-    // }
-
     // TODO(xla): Setup subroutines.
-    //  - Public API
     //  - Anncouncemnets
     //  - Replication Requests
     //  - Tracking
 
     info!("starting node");
     let (res, _idx, _rest) = select_all(coalesced).await;
+    #[cfg(target_os = "linux")]
+    systemd::notify(systemd::STOPPING).ok();
 
     if let Err(e) = res {
         if e.is_panic() {
@@ -77,6 +143,39 @@ pub async fn run() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// The listener for the socket named `name` passed by the service manager, or
+/// else a listener bound to `addr`, if any.
+fn listen(
+    listeners: &mut Listeners,
+    name: &str,
+    addr: Option<SocketAddr>,
+) -> anyhow::Result<Option<TcpListener>> {
+    #[cfg(unix)]
+    if let Some(listener) = listeners.tcp(name)? {
+        info!(%name, "using socket activated listener");
+        return Ok(Some(listener));
+    }
+    #[cfg(windows)]
+    let _ = (listeners, name);
+
+    Ok(addr.map(TcpListener::bind).transpose()?)
+}
+
+#[cfg(unix)]
+type Listeners = socket_activation::Listeners;
+#[cfg(windows)]
+type Listeners = ();
+
+#[cfg(unix)]
+fn listeners() -> anyhow::Result<Listeners> {
+    socket_activation::listeners()
+}
+
+#[cfg(windows)]
+fn listeners() -> anyhow::Result<Listeners> {
+    Ok(())
+}
+
 #[cfg(unix)]
 async fn cfg(args: &Args) -> anyhow::Result<Cfg<discovery::Static, BoxedSigner>> {
     Ok(Cfg::from_args(args).await?)
@@ -3,9 +3,16 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::os::unix::net::UnixListener;
+use std::{
+    net::TcpListener,
+    os::unix::{io::RawFd, net::UnixListener, prelude::FromRawFd},
+};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use nix::{
+    fcntl::{fcntl, FcntlArg::F_SETFD, FdFlag},
+    sys::socket::{getsockname, SockAddr},
+};
 
 #[cfg(all(unix, target_os = "macos"))]
 mod macos;
@@ -30,3 +37,67 @@ use unix as imp;
 pub fn env() -> Result<Option<UnixListener>> {
     imp::env()
 }
+
+/// All sockets passed through the environment, see [`env`] for the supported
+/// platforms.
+///
+/// Note that the environment is consumed, so this should only be called once,
+/// and not in combination with [`env`].
+pub fn listeners() -> Result<Listeners> {
+    imp::listeners()
+}
+
+/// Pre-bound sockets passed by the service manager, by name.
+///
+/// Under [systemd], the name is given by the `FileDescriptorName` option of the
+/// socket unit, and defaults to the name of the socket unit.
+///
+/// [systemd]: https://www.freedesktop.org/software/systemd/man/systemd.socket.html
+#[derive(Debug, Default)]
+pub struct Listeners {
+    fds: Vec<(String, RawFd)>,
+}
+
+impl Listeners {
+    pub(crate) fn new(fds: Vec<(String, RawFd)>) -> Self {
+        Self { fds }
+    }
+
+    /// Take the TCP listener named `name`, if any.
+    pub fn tcp(&mut self, name: &str) -> Result<Option<TcpListener>> {
+        match self.take(name)? {
+            None => Ok(None),
+            Some(fd) => {
+                if !matches!(getsockname(fd)?, SockAddr::Inet(_)) {
+                    bail!("file descriptor {} named {} is not a tcp socket", fd, name);
+                }
+                Ok(Some(unsafe { FromRawFd::from_raw_fd(fd) }))
+            },
+        }
+    }
+
+    /// Take the Unix listener named `name`, if any.
+    pub fn unix(&mut self, name: &str) -> Result<Option<UnixListener>> {
+        match self.take(name)? {
+            None => Ok(None),
+            Some(fd) => {
+                if !matches!(getsockname(fd)?, SockAddr::Unix(_)) {
+                    bail!("file descriptor {} named {} is not a unix socket", fd, name);
+                }
+                Ok(Some(unsafe { FromRawFd::from_raw_fd(fd) }))
+            },
+        }
+    }
+
+    fn take(&mut self, name: &str) -> Result<Option<RawFd>> {
+        match self.fds.iter().position(|(n, _)| n == name) {
+            None => Ok(None),
+            Some(idx) => {
+                let (_, fd) = self.fds.remove(idx);
+                // Set FD_CLOEXEC to avoid further inheritance to children.
+                fcntl(fd, F_SETFD(FdFlag::FD_CLOEXEC))?;
+                Ok(Some(fd))
+            },
+        }
+    }
+}
@@ -7,6 +7,14 @@ use std::os::unix::net::UnixListener;
 
 use anyhow::Result;
 
+use super::Listeners;
+
 pub fn env() -> Result<Option<UnixListener>> {
     todo!()
 }
+
+pub fn listeners() -> Result<Listeners> {
+    // TODO: Support launchd, which passes sockets via `launch_activate_socket`
+    // rather than the environment.
+    Ok(Listeners::default())
+}
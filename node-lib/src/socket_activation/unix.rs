@@ -7,8 +7,7 @@
 //! <http://0pointer.de/blog/projects/socket-activation.html>
 //!
 //! TODO
-//! * support FDs beyond 3
-//! * support mapping from listen names
+//! * support FDs beyond 3 in [`env`]
 
 use std::{
     env,
@@ -22,15 +21,20 @@ use nix::{
     unistd::Pid,
 };
 
+use super::Listeners;
+
 /// Environemnt variable which carries the amount of file descriptors passed
 /// down.
 const LISTEN_FDS: &str = "LISTEN_FDS";
 /// Environment variable containing colon-separated list of names corresponding
 /// to the `FileDescriptorName` option in the service file.
-const _LISTEN_NAMES: &str = "LISTEN_NAMES";
+const LISTEN_FDNAMES: &str = "LISTEN_FDNAMES";
 /// Environemnt variable when present should match PID of the current process.
 const LISTEN_PID: &str = "LISTEN_PID";
 
+/// Name systemd assigns to file descriptors if `LISTEN_FDNAMES` is not set.
+const UNKNOWN: &str = "unknown";
+
 pub fn env() -> Result<Option<UnixListener>> {
     // TODO(xla): Enable usage of more than the first fd. For now the assumption
     // should be safe as long as the service files are defined in accordance.
@@ -51,6 +55,29 @@ pub fn env() -> Result<Option<UnixListener>> {
     Ok(None)
 }
 
+pub fn listeners() -> Result<Listeners> {
+    let names = env::var(LISTEN_FDNAMES).ok();
+    let fds = match fds() {
+        Some(fds) => fds,
+        None => return Ok(Listeners::default()),
+    };
+    env::remove_var(LISTEN_FDNAMES);
+
+    let names = names
+        .as_deref()
+        .map(|names| names.split(':').map(ToOwned::to_owned).collect::<Vec<_>>())
+        .unwrap_or_default();
+    Ok(Listeners::new(
+        fds.into_iter()
+            .enumerate()
+            .map(|(i, fd)| {
+                let name = names.get(i).cloned().unwrap_or_else(|| UNKNOWN.to_owned());
+                (name, fd)
+            })
+            .collect(),
+    ))
+}
+
 fn fds() -> Option<Vec<RawFd>> {
     if let Some(count) = env::var(LISTEN_FDS).ok().and_then(|x| x.parse().ok()) {
         if env::var(LISTEN_PID).ok() == Some(Pid::this().to_string()) {
@@ -0,0 +1,154 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Implementation of the systemd service notification protocol.
+//! <https://www.freedesktop.org/software/systemd/man/sd_notify.html>
+//!
+//! The node reports readiness once the protocol endpoint is up, so that units
+//! can use `Type=notify`. If the unit sets `WatchdogSec`, the node keeps
+//! pinging the watchdog for as long as the protocol is responsive, so that a
+//! hung node gets restarted.
+
+use std::{env, time::Duration};
+
+use futures::{pin_mut, Stream, StreamExt as _};
+use nix::{
+    sys::socket::{
+        sendto,
+        socket,
+        AddressFamily,
+        MsgFlags,
+        SockAddr,
+        SockFlag,
+        SockType,
+        UnixAddr,
+    },
+    unistd::{close, Pid},
+};
+use tokio::time::{interval, timeout};
+use tracing::{info, instrument, warn};
+
+use librad::{
+    net::{
+        peer::{event::upstream, Peer, ProtocolEvent},
+        protocol::RecvError,
+    },
+    Signer,
+};
+
+/// Environment variable carrying the path of the socket to send
+/// notifications to.
+const NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+/// Environment variable carrying the watchdog timeout in microseconds.
+const WATCHDOG_USEC: &str = "WATCHDOG_USEC";
+/// Environment variable when present should match PID of the current process.
+const WATCHDOG_PID: &str = "WATCHDOG_PID";
+
+/// The service is ready.
+pub const READY: &str = "READY=1";
+/// The service is shutting down.
+pub const STOPPING: &str = "STOPPING=1";
+/// Keep-alive ping for the watchdog.
+pub const WATCHDOG: &str = "WATCHDOG=1";
+
+/// Send `state` to the service manager.
+///
+/// Returns `false` if the process is not supervised, i.e. `NOTIFY_SOCKET` is
+/// not set.
+pub fn notify(state: &str) -> anyhow::Result<bool> {
+    match env::var(NOTIFY_SOCKET) {
+        Ok(path) => send(&path, state).map(|()| true),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Send `state` to the socket at `path`, in the format of `NOTIFY_SOCKET`.
+///
+/// A `path` starting with `@` denotes a socket in the abstract namespace.
+pub fn send(path: &str, state: &str) -> anyhow::Result<()> {
+    let addr = match path.strip_prefix('@') {
+        Some(name) => UnixAddr::new_abstract(name.as_bytes())?,
+        None => UnixAddr::new(path)?,
+    };
+
+    let fd = socket(
+        AddressFamily::Unix,
+        SockType::Datagram,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )?;
+    let sent = sendto(
+        fd,
+        state.as_bytes(),
+        &SockAddr::Unix(addr),
+        MsgFlags::empty(),
+    );
+    close(fd)?;
+    sent?;
+
+    Ok(())
+}
+
+/// The interval at which the service manager expects watchdog pings, if the
+/// watchdog is enabled for this process.
+pub fn watchdog_timeout() -> Option<Duration> {
+    if let Ok(pid) = env::var(WATCHDOG_PID) {
+        if pid != Pid::this().to_string() {
+            return None;
+        }
+    }
+    env::var(WATCHDOG_USEC)
+        .ok()
+        .and_then(|usec| usec.parse().ok())
+        .filter(|usec| *usec > 0)
+        .map(Duration::from_micros)
+}
+
+/// Report readiness once the protocol endpoint is up, and ping the watchdog
+/// for as long as the protocol is responsive.
+///
+/// `events` must be subscribed to before the protocol is bound, so that the
+/// endpoint coming up isn't missed.
+#[instrument(name = "systemd subroutine", skip(peer, events))]
+pub async fn routine<S, E>(peer: Peer<S>, events: E) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    E: Stream<Item = Result<ProtocolEvent, RecvError>>,
+{
+    pin_mut!(events);
+    while let Some(event) = events.next().await {
+        if let Ok(ProtocolEvent::Endpoint(upstream::Endpoint::Up { .. })) = event {
+            match notify(READY) {
+                Ok(true) => info!("notified readiness"),
+                Ok(false) => {},
+                Err(err) => warn!(?err, "failed to notify readiness"),
+            }
+            break;
+        }
+    }
+
+    match watchdog_timeout() {
+        // Keep running, so as to not terminate the node.
+        None => futures::future::pending::<anyhow::Result<()>>().await,
+        Some(watchdog) => {
+            let period = watchdog / 2;
+            let mut ticker = interval(period);
+            loop {
+                ticker.tick().await;
+                // A protocol which doesn't answer in time is considered hung
+                match timeout(period, peer.stats()).await {
+                    Ok(_) => {
+                        // The next tick tries again, and the service manager
+                        // decides when enough pings were missed
+                        if let Err(err) = notify(WATCHDOG) {
+                            warn!(?err, "failed to ping the watchdog")
+                        }
+                    },
+                    Err(_) => warn!("protocol unresponsive, skipping watchdog ping"),
+                }
+            }
+        },
+    }
+}
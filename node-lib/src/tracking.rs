@@ -3,30 +3,105 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::collections::BTreeSet;
+use std::{
+    collections::{BTreeSet, HashSet},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use futures::{pin_mut, StreamExt as _};
-use tracing::{error, info, instrument, trace};
+use futures::{future, pin_mut, StreamExt as _};
+use tracing::{debug, error, info, instrument, trace, warn};
 
 use librad::{
-    git::{tracking, Urn},
+    git::{include, storage::Storage, tracking, Urn},
     net::{
         peer::{event::upstream::Gossip, Peer, PeerInfo, ProtocolEvent},
         protocol::{broadcast::PutResult::Uninteresting, gossip::Payload},
     },
+    profile::petnames::{self, PeerOrPetname, Petnames},
     PeerId,
     Signer,
 };
 
+use crate::args::{TrackingArgs, TrackingMode};
+
+/// How long to wait for a provider of a urn we were asked for in
+/// [`Tracker::Open`] mode.
+const PROVIDERS_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub enum Tracker {
     Everything,
     Selected {
         peer_ids: BTreeSet<PeerId>,
         urns: BTreeSet<Urn>,
     },
+    /// Track everything observed or asked for, unless blocked or over quota.
+    Open {
+        quota: Quota,
+        blocked: Blocklist,
+    },
+}
+
+/// Upper bounds on what is tracked in [`Tracker::Open`] mode.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Quota {
+    pub urns: Option<usize>,
+    pub peers_per_urn: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Blocklist {
+    pub peer_ids: BTreeSet<PeerId>,
+    pub urns: BTreeSet<Urn>,
+}
+
+impl Blocklist {
+    fn is_blocked(&self, peer_id: &PeerId, urn: &Urn) -> bool {
+        self.peer_ids.contains(peer_id) || self.urns.contains(urn)
+    }
 }
 
 impl Tracker {
+    /// Construct the [`Tracker`] described by `args`, if any tracking mode was
+    /// selected.
+    ///
+    /// Peers given by their petname are resolved using `petnames`.
+    pub fn from_args(
+        args: &TrackingArgs,
+        petnames: &Petnames,
+    ) -> Result<Option<Self>, petnames::Error> {
+        let resolve = |peers: &[PeerOrPetname]| {
+            peers
+                .iter()
+                .map(|peer| peer.resolve(petnames))
+                .collect::<Result<BTreeSet<_>, _>>()
+        };
+
+        args.mode
+            .as_ref()
+            .map(|mode| {
+                Ok(match mode {
+                    TrackingMode::Everything => Self::Everything,
+                    TrackingMode::Selected => Self::Selected {
+                        peer_ids: resolve(&args.peer_ids)?,
+                        urns: args.urns.iter().cloned().collect(),
+                    },
+                    TrackingMode::Open => Self::Open {
+                        quota: Quota {
+                            urns: args.max_urns,
+                            peers_per_urn: args.max_peers,
+                        },
+                        blocked: Blocklist {
+                            peer_ids: resolve(&args.blocked_peer_ids)?,
+                            urns: args.blocked_urns.iter().cloned().collect(),
+                        },
+                    },
+                })
+            })
+            .transpose()
+    }
+
     fn is_tracked(&self, peer_id: &PeerId, urn: &Urn) -> bool {
         match self {
             Tracker::Everything => true,
@@ -34,9 +109,39 @@ impl Tracker {
                 ref peer_ids,
                 ref urns,
             } if peer_ids.contains(peer_id) || urns.contains(urn) => true,
+            Tracker::Open { ref blocked, .. } => !blocked.is_blocked(peer_id, urn),
             _ => false,
         }
     }
+
+    /// Whether tracking `peer_id` for `urn` stays within the [`Quota`].
+    fn within_quota(&self, storage: &Storage, peer_id: &PeerId, urn: &Urn) -> anyhow::Result<bool> {
+        let quota = match self {
+            Tracker::Open { quota, .. } => quota,
+            _ => return Ok(true),
+        };
+
+        let peers =
+            tracking::tracked_peers(storage, Some(urn))?.collect::<Result<BTreeSet<_>, _>>()?;
+        if peers.contains(peer_id) {
+            return Ok(true);
+        }
+        if let Some(max) = quota.peers_per_urn {
+            if peers.len() >= max {
+                return Ok(false);
+            }
+        }
+        if let Some(max) = quota.urns {
+            let urns = tracking::tracked(storage, None)?
+                .map(|tracked| tracked.map(|tracked| tracked.urn().encode_id()))
+                .collect::<Result<BTreeSet<_>, _>>()?;
+            if !urns.contains(&urn.encode_id()) && urns.len() >= max {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
 }
 
 #[instrument(name = "tracking subroutine", skip(peer, tracker))]
@@ -44,13 +149,15 @@ pub async fn routine<S>(peer: Peer<S>, tracker: Tracker) -> anyhow::Result<()>
 where
     S: Signer + Clone,
 {
+    let tracker = Arc::new(tracker);
+    let wanted = Arc::new(Mutex::new(HashSet::new()));
     let events = peer.subscribe();
     pin_mut!(events);
 
     while let Some(res) = events.next().await {
         match res {
-            Ok(ProtocolEvent::Gossip(gossip)) => {
-                let Gossip::Put {
+            Ok(ProtocolEvent::Gossip(gossip)) => match *gossip {
+                Gossip::Put {
                     payload: Payload { urn, .. },
                     provider:
                         PeerInfo {
@@ -59,52 +166,51 @@ where
                             ..
                         },
                     result,
-                } = *gossip;
+                } => {
+                    if result != Uninteresting || !tracker.is_tracked(&peer_id, &urn) {
+                        continue;
+                    }
 
-                if result != Uninteresting || !tracker.is_tracked(&peer_id, &urn) {
-                    continue;
-                }
+                    let addr_hints = seen_addrs.iter().copied().collect::<Vec<_>>();
+                    track(&peer, &tracker, urn, peer_id, addr_hints).await
+                },
 
-                let go = async {
-                    let updated = peer
-                    .using_storage({
-                        let urn = urn.clone();
-                        move |storage| -> anyhow::Result<bool> {
-                            match tracking::track(
-                                storage,
-                                &urn,
-                                Some(peer_id),
-                                tracking::Config::default(),
-                                tracking::policy::Track::MustNotExist,
-                            )? {
-                                Ok(reference) => {
-                                    trace!(name=%reference.name, target=%reference.target, "created tracking entry");
-                                    Ok(true)
+                // Asked for something we don't have: look for a provider, and
+                // track that.
+                Gossip::Want {
+                    payload: Payload { urn, .. },
+                    have: false,
+                    ..
+                } if matches!(*tracker, Tracker::Open { .. }) => {
+                    if !wanted.lock().unwrap().insert(urn.clone()) {
+                        continue;
+                    }
+                    tokio::spawn({
+                        let peer = peer.clone();
+                        let tracker = tracker.clone();
+                        let wanted = wanted.clone();
+                        async move {
+                            let provider = peer
+                                .providers(urn.clone(), PROVIDERS_TIMEOUT)
+                                .filter(|info| {
+                                    future::ready(tracker.is_tracked(&info.peer_id, &urn))
+                                })
+                                .next()
+                                .await;
+                            match provider {
+                                Some(info) => {
+                                    let addr_hints = info.seen_addrs.iter().copied().collect();
+                                    track(&peer, &tracker, urn.clone(), info.peer_id, addr_hints)
+                                        .await
                                 },
-                                Err(err) => {
-                                    trace!(err = %err, "tracking policy error");
-                                    Ok(false)
-                                }
+                                None => info!("no provider found for wanted {}", urn),
                             }
+                            wanted.lock().unwrap().remove(&urn);
                         }
-                    })
-                    .await??;
-
-                    // Skip explicit replication if the peer is already tracked.
-                    if updated {
-                        let addr_hints = seen_addrs.iter().copied().collect::<Vec<_>>();
-                        peer.replicate((peer_id, addr_hints), urn.clone(), None)
-                            .await?;
-                    }
-
-                    Ok::<_, anyhow::Error>(updated)
-                };
+                    });
+                },
 
-                match go.await {
-                    Ok(true) => info!("tracked project {} from {}", urn, peer_id),
-                    Ok(false) => info!("already tracked {} from {}", urn, peer_id),
-                    Err(err) => error!(?err, "tracking failed for {} from {}", urn, peer_id),
-                }
+                Gossip::Want { .. } => {},
             },
 
             Ok(_) => {},
@@ -116,3 +222,84 @@ where
 
     Ok(())
 }
+
+/// Track `peer_id` for `urn`, and replicate from it if it wasn't tracked
+/// already.
+async fn track<S>(
+    peer: &Peer<S>,
+    tracker: &Arc<Tracker>,
+    urn: Urn,
+    peer_id: PeerId,
+    addr_hints: Vec<SocketAddr>,
+) where
+    S: Signer + Clone,
+{
+    let go = async {
+        let updated = peer
+            .using_storage({
+                let urn = urn.clone();
+                let tracker = tracker.clone();
+                move |storage| -> anyhow::Result<bool> {
+                    if !tracker.within_quota(storage, &peer_id, &urn)? {
+                        info!("tracking quota exceeded, not tracking {} from {}", urn, peer_id);
+                        return Ok(false);
+                    }
+                    match tracking::track(
+                        storage,
+                        &urn,
+                        Some(peer_id),
+                        tracking::Config::default(),
+                        tracking::policy::Track::MustNotExist,
+                    )? {
+                        Ok(reference) => {
+                            trace!(name=%reference.name, target=%reference.target, "created tracking entry");
+                            Ok(true)
+                        },
+                        Err(err) => {
+                            trace!(err = %err, "tracking policy error");
+                            Ok(false)
+                        },
+                    }
+                }
+            })
+            .await??;
+
+        // Skip explicit replication if the peer is already tracked.
+        if updated {
+            peer.replicate((peer_id, addr_hints), urn.clone(), None)
+                .await?;
+            update_include(peer, urn.clone()).await;
+        }
+
+        Ok::<_, anyhow::Error>(updated)
+    };
+
+    match go.await {
+        Ok(true) => info!("tracked project {} from {}", urn, peer_id),
+        Ok(false) => info!("not tracking {} from {}", urn, peer_id),
+        Err(err) => error!(?err, "tracking failed for {} from {}", urn, peer_id),
+    }
+}
+
+/// Regenerate the include file of `urn`, so that working copies pick up a
+/// change in its tracked peers.
+///
+/// Failures are only logged, as the include files are not essential to running
+/// the node.
+pub(crate) async fn update_include<S>(peer: &Peer<S>, urn: Urn)
+where
+    S: Signer + Clone,
+{
+    let paths = peer.protocol_config().paths.clone();
+    let updated = peer
+        .using_storage({
+            let urn = urn.clone();
+            move |storage| include::update(storage, &paths, &urn)
+        })
+        .await;
+    match updated {
+        Ok(Ok(path)) => debug!(%urn, path = %path.display(), "updated include file"),
+        Ok(Err(err)) => warn!(%urn, ?err, "failed to update include file"),
+        Err(err) => warn!(%urn, ?err, "failed to update include file"),
+    }
+}
@@ -10,16 +10,17 @@ use librad::{
     SecretKey,
 };
 
+pub mod passphrase;
 pub mod prompt;
 pub mod ssh;
 
 /// The filename for storing the secret key.
-pub const LIBRAD_KEY_FILE: &str = "librad.key";
+pub const LIBRAD_KEY_FILE: &str = librad::profile::KEY_FILE;
 
 /// Create a [`FileStorage`] for [`SecretKey`]s.
 pub fn file_storage<C>(profile: &Profile, crypto: C) -> FileStorage<C, PublicKey, SecretKey, ()>
 where
     C: Crypto,
 {
-    FileStorage::new(&profile.paths().keys_dir().join(LIBRAD_KEY_FILE), crypto)
+    profile.key_storage(crypto)
 }
@@ -0,0 +1,205 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Acquiring the passphrase the key storage is locked with.
+//!
+//! The passphrase is read from, in order of precedence:
+//!
+//!   1. a file descriptor, one passphrase per line, e.g. as given by
+//!      `--rad-passphrase-fd`,
+//!   2. the [`RAD_PASSPHRASE`] environment variable, or [`RAD_NEW_PASSPHRASE`]
+//!      when choosing a new passphrase, or
+//!   3. an interactive prompt, asking for a new passphrase twice to confirm it.
+
+use std::{
+    env,
+    fs::File,
+    io::{self, BufRead as _, BufReader},
+    sync::{Arc, Mutex},
+};
+
+use once_cell::sync::OnceCell;
+use thiserror::Error;
+
+use librad::{
+    crypto::{
+        keystore::{
+            crypto::{KdfParams, Pwhash, SecretBoxError},
+            file,
+            pinentry::{Pinentry, Prompt, SecUtf8},
+            Keystore as _,
+        },
+        BoxedSigner,
+        IntoSecretKeyError,
+    },
+    profile::Profile,
+};
+
+/// The passphrase used to unlock the key storage, and to lock a new key
+/// storage unless [`RAD_NEW_PASSPHRASE`] is set.
+pub const RAD_PASSPHRASE: &str = "RAD_PASSPHRASE";
+
+/// The passphrase used to lock a new key storage, e.g. when re-encrypting the
+/// key.
+pub const RAD_NEW_PASSPHRASE: &str = "RAD_NEW_PASSPHRASE";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    File(#[from] file::Error<SecretBoxError<io::Error>, IntoSecretKeyError>),
+}
+
+/// What a passphrase is acquired for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Purpose {
+    /// Unlocking an existing key storage.
+    Unlock,
+    /// Locking a new key storage.
+    Choose,
+}
+
+enum Source {
+    Prompt,
+    Env,
+    Fd(Mutex<BufReader<File>>),
+}
+
+impl Source {
+    fn read(&self, purpose: Purpose) -> io::Result<SecUtf8> {
+        match (self, purpose) {
+            (Self::Prompt, Purpose::Unlock) => {
+                Prompt::new("please enter your passphrase: ").get_passphrase()
+            },
+            (Self::Prompt, Purpose::Choose) => {
+                let passphrase = Prompt::new("please enter a new passphrase: ").get_passphrase()?;
+                let confirmation =
+                    Prompt::new("please confirm the new passphrase: ").get_passphrase()?;
+                if passphrase.unsecure() != confirmation.unsecure() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the passphrases do not match",
+                    ));
+                }
+                Ok(passphrase)
+            },
+            (Self::Env, Purpose::Unlock) => var(RAD_PASSPHRASE),
+            (Self::Env, Purpose::Choose) => {
+                var(RAD_NEW_PASSPHRASE).or_else(|_| var(RAD_PASSPHRASE))
+            },
+            (Self::Fd(reader), _) => {
+                let mut reader = reader.lock().expect("passphrase reader lock poisoned");
+                let mut line = String::new();
+                if reader.read_line(&mut line)? == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "no passphrase left to read from the file descriptor",
+                    ));
+                }
+                let len = line.trim_end_matches(&['\r', '\n'][..]).len();
+                line.truncate(len);
+                Ok(SecUtf8::from(line))
+            },
+        }
+    }
+}
+
+fn var(name: &str) -> io::Result<SecUtf8> {
+    env::var(name).map(SecUtf8::from).map_err(|e| {
+        // `VarError::NotUnicode` carries, and displays, the passphrase
+        let e = match e {
+            env::VarError::NotPresent => "not present",
+            env::VarError::NotUnicode(_) => "not valid unicode",
+        };
+        io::Error::new(io::ErrorKind::NotFound, format!("{}: {}", name, e))
+    })
+}
+
+/// The shared source of passphrases for a single invocation.
+///
+/// Each [`Pwhash`] handed out reads its passphrase at most once, on first use.
+#[derive(Clone)]
+pub struct Passphrases {
+    source: Arc<Source>,
+}
+
+impl Passphrases {
+    /// Read passphrases from `fd` if given, otherwise from the environment if
+    /// [`RAD_PASSPHRASE`] is set, otherwise by prompting.
+    ///
+    /// Ownership of `fd` is taken, i.e. it is closed once the [`Passphrases`]
+    /// are dropped.
+    pub fn new(fd: Option<i32>) -> io::Result<Self> {
+        let source = match fd {
+            Some(fd) => Source::Fd(Mutex::new(BufReader::new(from_fd(fd)?))),
+            None if env::var_os(RAD_PASSPHRASE).is_some() => Source::Env,
+            None => Source::Prompt,
+        };
+        Ok(Self {
+            source: Arc::new(source),
+        })
+    }
+
+    /// The [`Pwhash`] for unlocking an existing key storage.
+    pub fn unlock(&self) -> Pwhash<Passphrase> {
+        Pwhash::new(self.passphrase(Purpose::Unlock), KdfParams::recommended())
+    }
+
+    /// The [`Pwhash`] for locking a new key storage. When prompting, the
+    /// passphrase is asked for twice to confirm it.
+    pub fn choose(&self) -> Pwhash<Passphrase> {
+        Pwhash::new(self.passphrase(Purpose::Choose), KdfParams::recommended())
+    }
+
+    /// Get the signer from the file store, decrypting the secret key using
+    /// [`Passphrases::unlock`].
+    pub fn signer(&self, profile: &Profile) -> Result<BoxedSigner, Error> {
+        let store = super::file_storage(profile, self.unlock());
+        let key = store.get_key()?.secret_key;
+        Ok(key.into())
+    }
+
+    fn passphrase(&self, purpose: Purpose) -> Passphrase {
+        Passphrase {
+            source: self.source.clone(),
+            purpose,
+            passphrase: Arc::new(OnceCell::new()),
+        }
+    }
+}
+
+/// A [`Pinentry`] reading from the [`Passphrases`] it was created by.
+#[derive(Clone)]
+pub struct Passphrase {
+    source: Arc<Source>,
+    purpose: Purpose,
+    passphrase: Arc<OnceCell<SecUtf8>>,
+}
+
+impl Pinentry for Passphrase {
+    type Error = io::Error;
+
+    fn get_passphrase(&self) -> Result<SecUtf8, Self::Error> {
+        self.passphrase
+            .get_or_try_init(|| self.source.read(self.purpose))
+            .map(Clone::clone)
+    }
+}
+
+#[cfg(unix)]
+fn from_fd(fd: i32) -> io::Result<File> {
+    use std::os::unix::io::FromRawFd as _;
+
+    // SAFETY: the caller hands the descriptor over to us, see
+    // `Passphrases::new`.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+fn from_fd(_fd: i32) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "reading the passphrase from a file descriptor is only supported on unix",
+    ))
+}
@@ -64,3 +64,39 @@ impl Format {
         }
     }
 }
+
+/// The format in which the CLI prints its results to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable output, as given by the [`fmt::Display`] of the result.
+    Plain,
+    /// Structured output, as given by the [`Serialize`] of the result.
+    Json,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Plain
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plain => write!(f, "plain"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "json" => Ok(Self::Json),
+            _ => Err("unknown output format, expected `plain` or `json`"),
+        }
+    }
+}
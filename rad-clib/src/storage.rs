@@ -20,6 +20,8 @@ pub enum Error {
     #[error(transparent)]
     ReadWriteInit(#[from] error::Init),
     #[error(transparent)]
+    Passphrase(#[from] super::keys::passphrase::Error),
+    #[error(transparent)]
     PromptKeys(#[from] super::keys::prompt::Error),
     #[error(transparent)]
     SshKeys(#[from] super::keys::ssh::Error),
@@ -45,6 +47,25 @@ pub mod prompt {
     }
 }
 
+pub mod passphrase {
+    use super::*;
+
+    use crate::keys::passphrase::Passphrases;
+
+    /// Initialise [`Storage`].
+    ///
+    /// The decryption will happen using the passphrase acquired from
+    /// `passphrases`.
+    pub fn storage(
+        profile: &Profile,
+        passphrases: &Passphrases,
+    ) -> Result<(BoxedSigner, Storage), Error> {
+        let paths = profile.paths();
+        let signer = passphrases.signer(profile)?;
+        Ok((signer.clone(), Storage::open(paths, signer)?))
+    }
+}
+
 pub mod ssh {
     use super::*;
 
@@ -3,22 +3,53 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::env;
+use std::{env, io, net::SocketAddr, path::PathBuf, str::FromStr};
 
-use structopt::StructOpt;
+use structopt::{clap::Shell, StructOpt};
+use thiserror::Error;
 
-use librad::profile::{ProfileId, RAD_PROFILE};
-use rad_clib::keys::ssh::SshAuthSock;
+use librad::{
+    git::Urn,
+    profile::{ProfileId, RAD_PROFILE},
+};
+use rad_clib::{
+    keys::{passphrase::Passphrases, ssh::SshAuthSock},
+    ser::OutputFormat,
+};
+
+use crate::{completions::Candidates, config::Config, logging::LogFormat, petname::UrnOrPetname};
 
 /// `--rad-profile` command line name
 pub const RAD_PROFILE_ARG: &str = "--rad-profile";
 
+/// `--rad-ssh-auth-sock` command line name
+pub const RAD_SSH_AUTH_SOCK_ARG: &str = "--rad-ssh-auth-sock";
+
 /// `--rad-quiet` command line name
 pub const RAD_QUIET_ARG: &str = "--rad-quiet";
 
 /// `--rad-verbose` command line name
 pub const RAD_VERBOSE_ARG: &str = "--rad-verbose";
 
+/// `--rad-format` command line name
+pub const RAD_FORMAT_ARG: &str = "--rad-format";
+
+/// `--rad-passphrase-fd` command line name
+pub const RAD_PASSPHRASE_FD_ARG: &str = "--rad-passphrase-fd";
+
+/// `--rad-log` command line name
+pub const RAD_LOG_ARG: &str = "--rad-log";
+
+/// An environment variable standing in for a global option holds a value
+/// which can't be parsed.
+#[derive(Debug, Error)]
+#[error("invalid value {value:?} of {var}: {reason}")]
+pub struct InvalidEnv {
+    pub var: &'static str,
+    pub value: String,
+    pub reason: String,
+}
+
 #[derive(Debug, StructOpt)]
 pub struct Args {
     #[structopt(flatten)]
@@ -35,11 +66,12 @@ pub struct Global {
     #[structopt(long)]
     pub rad_profile: Option<ProfileId>,
 
-    /// Which unix domain socket to use for connecting to the ssh-agent. The
-    /// default will defer to SSH_AUTH_SOCK, otherwise the value given should be
-    /// a valid path.
-    #[structopt(long, default_value)]
-    pub rad_ssh_auth_sock: SshAuthSock,
+    /// Which unix domain socket to use for connecting to the ssh-agent. If
+    /// not given then RAD_SSH_AUTH_SOCK is used, and finally `env`, which will
+    /// defer to SSH_AUTH_SOCK. Otherwise, the value given should be a valid
+    /// path.
+    #[structopt(long)]
+    pub rad_ssh_auth_sock: Option<SshAuthSock>,
 
     /// No output printed to stdout
     #[structopt(long)]
@@ -48,6 +80,87 @@ pub struct Global {
     /// Use verbose output
     #[structopt(long)]
     pub rad_verbose: bool,
+
+    /// The format of the output printed to stdout, either `plain` or `json`. If
+    /// not given then RAD_FORMAT is used, defaulting to `plain`.
+    #[structopt(long)]
+    pub rad_format: Option<OutputFormat>,
+
+    /// The file descriptor to read the passphrase of the key storage from, one
+    /// passphrase per line. If not given then RAD_PASSPHRASE_FD is used,
+    /// followed by the passphrase in RAD_PASSPHRASE, and finally prompting
+    /// for it.
+    #[structopt(long)]
+    pub rad_passphrase_fd: Option<i32>,
+
+    /// The format of the logs printed to stderr, either `pretty`, `json`, or
+    /// `compact`. If not given then RAD_LOG is used, defaulting to `pretty`.
+    #[structopt(long)]
+    pub rad_log: Option<LogFormat>,
+}
+
+impl Global {
+    /// Fill in the globals which are neither given on the command line, nor by
+    /// their environment variables, from the configuration files. See
+    /// [`crate::config`].
+    pub fn layer(mut self, config: &Config) -> Self {
+        if self.rad_profile.is_none() && env::var_os(RAD_PROFILE).is_none() {
+            self.rad_profile = config.profile.clone();
+        }
+        if !self.rad_quiet && env::var_os("RAD_QUIET").is_none() {
+            self.rad_quiet = config.quiet.unwrap_or_default();
+        }
+        if !self.rad_verbose && env::var_os("RAD_VERBOSE").is_none() {
+            self.rad_verbose = config.verbose.unwrap_or_default();
+        }
+        if self.rad_format.is_none() && env::var_os("RAD_FORMAT").is_none() {
+            self.rad_format = config.format;
+        }
+        if self.rad_log.is_none() && env::var_os("RAD_LOG").is_none() {
+            self.rad_log = config.log;
+        }
+        self
+    }
+
+    /// The [`OutputFormat`] given on the command line, falling back to
+    /// RAD_FORMAT, and finally the default format.
+    ///
+    /// It is an error if RAD_FORMAT is set to an unknown format.
+    pub fn format(&self) -> Result<OutputFormat, InvalidEnv> {
+        match self.rad_format {
+            Some(format) => Ok(format),
+            None => Ok(from_env("RAD_FORMAT")?.unwrap_or_default()),
+        }
+    }
+
+    /// The [`LogFormat`] given on the command line, falling back to RAD_LOG,
+    /// and finally the default format.
+    ///
+    /// It is an error if RAD_LOG is set to an unknown format.
+    pub fn log(&self) -> Result<LogFormat, InvalidEnv> {
+        match self.rad_log {
+            Some(log) => Ok(log),
+            None => Ok(from_env("RAD_LOG")?.unwrap_or_default()),
+        }
+    }
+
+    /// The [`SshAuthSock`] given on the command line, falling back to
+    /// RAD_SSH_AUTH_SOCK, and finally SSH_AUTH_SOCK.
+    pub fn ssh_auth_sock(&self) -> SshAuthSock {
+        self.rad_ssh_auth_sock
+            .clone()
+            .or_else(|| env::var("RAD_SSH_AUTH_SOCK").ok()?.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// The [`Passphrases`] to unlock the key storage with, reading from the
+    /// file descriptor given on the command line or RAD_PASSPHRASE_FD, if any.
+    pub fn passphrases(&self) -> io::Result<Passphrases> {
+        let fd = self
+            .rad_passphrase_fd
+            .or_else(|| env::var("RAD_PASSPHRASE_FD").ok()?.parse().ok());
+        Passphrases::new(fd)
+    }
 }
 
 #[derive(Debug, StructOpt)]
@@ -56,10 +169,438 @@ pub enum Command {
     Identities(rad_identities::cli::args::Args),
     /// Manage your Radicle profiles
     Profile(rad_profile::cli::args::Args),
+    /// Replicate one or all local projects from seed nodes, and announce the
+    /// local state to them
+    Sync(Sync),
+    Ls(Ls),
+    Inspect(Inspect),
+    Graph(Graph),
+    Completions(Completions),
+    Daemon(Daemon),
+    Commands(Commands),
+    Key(Key),
+    Seed(Seed),
+    Identity(Identity),
+    Hooks(Hooks),
+    Petname(Petname),
     #[structopt(external_subcommand)]
     External(Vec<String>),
 }
 
+/// replicate one or all local projects from seed nodes, and announce the local
+/// state to them
+#[derive(Debug, StructOpt)]
+pub struct Sync {
+    /// the Radicle URN, or petname, of the project to synchronise. If no URN
+    /// is provided, all local projects are synchronised.
+    pub urn: Option<UrnOrPetname>,
+
+    /// the seeds to synchronise with, in the form `<peer id>@<address>`. If no
+    /// seeds are given, the seeds of the profile are used, see `rad seed`.
+    #[structopt(long = "seed", name = "seed")]
+    pub seeds: Vec<crate::sync::Seed>,
+
+    /// also synchronise with providers found on the network, waiting the given
+    /// number of seconds for them to respond
+    #[structopt(long)]
+    pub providers: Option<u64>,
+
+    /// only replicate from the seeds, without announcing the local state
+    #[structopt(long, conflicts_with = "push")]
+    pub fetch_only: bool,
+
+    /// only announce the local state to the seeds, without replicating from
+    /// them
+    #[structopt(long)]
+    pub push: bool,
+
+    /// the maximum number of projects being replicated at the same time
+    #[structopt(long, default_value = "4")]
+    pub concurrency: usize,
+}
+
+/// list the projects in the local storage
+#[derive(Debug, StructOpt)]
+pub struct Ls {}
+
+/// show the verified identity document, tracked peers, and `rad/*` refs of a
+/// Radicle URN
+#[derive(Debug, StructOpt)]
+pub struct Inspect {
+    /// the Radicle URN, or petname, to inspect
+    pub urn: UrnOrPetname,
+}
+
+/// export the tracking relationships, identity delegations, and remotes of
+/// the local storage as a graph. The graph is printed in the DOT language of
+/// graphviz, or as JSON with `--rad-format json`
+#[derive(Debug, StructOpt)]
+pub struct Graph {
+    /// the Radicle URN, or petname, to restrict the graph to. If no URN is
+    /// provided, the graph spans all namespaces of the local storage.
+    #[structopt(long)]
+    pub urn: Option<UrnOrPetname>,
+}
+
+/// generate the completion script for a shell, e.g. `rad completions bash >
+/// /etc/bash_completion.d/rad`
+#[derive(Debug, StructOpt)]
+pub struct Completions {
+    /// the shell to generate the completion script for
+    #[structopt(possible_values = &Shell::variants(), required_unless = "list")]
+    pub shell: Option<Shell>,
+
+    /// list the candidates for a dynamically completed value. This is used by
+    /// the generated completion scripts.
+    #[structopt(long, hidden = true, possible_values = Candidates::variants())]
+    pub list: Option<Candidates>,
+}
+
+/// run a long-lived network endpoint serving the local projects to other peers
+#[derive(Debug, StructOpt)]
+pub struct Daemon {
+    /// the address to accept connections on
+    #[structopt(long, default_value = "0.0.0.0:0")]
+    pub listen: SocketAddr,
+
+    /// the seeds to connect to on startup, in the form `<peer id>@<address>`.
+    /// If no seeds are given, the seeds of the profile are used, see `rad
+    /// seed`.
+    #[structopt(long = "seed", name = "seed")]
+    pub seeds: Vec<crate::sync::Seed>,
+
+    /// the number of seconds between announcements of the local projects
+    #[structopt(long, default_value = "600")]
+    pub announce_interval: u64,
+
+    /// do not announce the local projects
+    #[structopt(long)]
+    pub no_announce: bool,
+
+    #[structopt(flatten)]
+    pub tracking: node_lib::args::TrackingArgs,
+}
+
+/// list the external subcommands, i.e. the `rad-*` executables found on the
+/// PATH
+#[derive(Debug, StructOpt)]
+pub struct Commands {}
+
+/// show, export, and manage the key of the active profile
+#[derive(Debug, StructOpt)]
+pub struct Key {
+    #[structopt(subcommand)]
+    pub options: key::Options,
+}
+
+pub mod key {
+    use super::*;
+
+    use crate::key::ExportFormat;
+
+    #[derive(Debug, StructOpt)]
+    pub enum Options {
+        Show(Show),
+        Export(Export),
+        Rotate(Rotate),
+        Reencrypt(Reencrypt),
+    }
+
+    /// show the peer id and public key of the profile
+    #[derive(Debug, StructOpt)]
+    pub struct Show {}
+
+    /// export the public key of the profile. Exporting as `pgp` requires
+    /// unlocking the key, since the exported key is self-signed.
+    #[derive(Debug, StructOpt)]
+    pub struct Export {
+        /// the format to export the public key in
+        #[structopt(long, default_value = "ssh", possible_values = ExportFormat::variants())]
+        pub format: ExportFormat,
+
+        /// the user id the `pgp` key is certified for, defaults to
+        /// `rad:<peer id>`
+        #[structopt(long)]
+        pub user_id: Option<String>,
+    }
+
+    /// replace the key of the profile with a newly generated one. This changes
+    /// the peer id of the profile, so any identities delegating to the
+    /// previous key need to be updated.
+    #[derive(Debug, StructOpt)]
+    pub struct Rotate {
+        /// do not ask for confirmation
+        #[structopt(long)]
+        pub yes: bool,
+    }
+
+    /// change the passphrase the key of the profile is locked with
+    #[derive(Debug, StructOpt)]
+    pub struct Reencrypt {
+        /// do not ask for confirmation
+        #[structopt(long)]
+        pub yes: bool,
+    }
+}
+
+/// manage the seeds of the profile, which are used by `sync` and `daemon` when
+/// no `--seed` is given
+#[derive(Debug, StructOpt)]
+pub struct Seed {
+    #[structopt(subcommand)]
+    pub options: seed::Options,
+}
+
+pub mod seed {
+    use super::*;
+
+    #[derive(Debug, StructOpt)]
+    pub enum Options {
+        Add(Add),
+        Remove(Remove),
+        List(List),
+    }
+
+    /// add a seed to the profile
+    #[derive(Debug, StructOpt)]
+    pub struct Add {
+        /// the seed to add, in the form `<peer id>@<host>:<port>`
+        pub seed: String,
+    }
+
+    /// remove a seed from the profile
+    #[derive(Debug, StructOpt)]
+    pub struct Remove {
+        /// the seed to remove, either in the form `<peer id>@<host>:<port>` or
+        /// only its peer id
+        pub seed: String,
+    }
+
+    /// list the seeds of the profile
+    #[derive(Debug, StructOpt)]
+    pub struct List {}
+}
+
+/// create and update Radicle identities field by field. See `rad identities`
+/// for creating them from JSON payloads.
+#[derive(Debug, StructOpt)]
+pub struct Identity {
+    #[structopt(subcommand)]
+    pub options: identity::Options,
+}
+
+pub mod identity {
+    use super::*;
+
+    use crate::identity::Delegate;
+
+    #[derive(Debug, StructOpt)]
+    pub enum Options {
+        Create(Create),
+        Update(Update),
+    }
+
+    /// create a person or a project
+    #[derive(Debug, StructOpt)]
+    pub enum Create {
+        Person(Person),
+        Project(Project),
+    }
+
+    /// create a person, delegating to the key of the profile
+    #[derive(Debug, StructOpt)]
+    pub struct Person {
+        /// the name of the person
+        #[structopt(long)]
+        pub name: String,
+
+        /// additional delegates, in peer id form
+        #[structopt(long = "delegate", name = "delegate")]
+        pub delegates: Vec<Delegate>,
+
+        /// set the person as the default local identity, which is used for
+        /// `rad/self` of new projects
+        #[structopt(long)]
+        pub default: bool,
+    }
+
+    /// create a project, delegating to the local identity
+    #[derive(Debug, StructOpt)]
+    pub struct Project {
+        /// the name of the project
+        #[structopt(long)]
+        pub name: String,
+
+        /// the description of the project
+        #[structopt(long)]
+        pub description: Option<String>,
+
+        /// the default branch of the project
+        #[structopt(long)]
+        pub default_branch: Option<String>,
+
+        /// additional delegates, either as a peer id or the URN of a person in
+        /// the local storage
+        #[structopt(long = "delegate", name = "delegate")]
+        pub delegates: Vec<Delegate>,
+
+        /// the Radicle URN of the local identity used for `rad/self`. If not
+        /// given, the default local identity is used.
+        #[structopt(long)]
+        pub whoami: Option<Urn>,
+    }
+
+    /// publish a new revision of an identity, changing only the given fields
+    #[derive(Debug, StructOpt)]
+    pub struct Update {
+        /// the Radicle URN, or petname, of the identity
+        pub urn: UrnOrPetname,
+
+        /// the new name
+        #[structopt(long)]
+        pub name: Option<String>,
+
+        /// the new description, only for projects
+        #[structopt(long)]
+        pub description: Option<String>,
+
+        /// the new default branch, only for projects
+        #[structopt(long)]
+        pub default_branch: Option<String>,
+
+        /// a delegate to add, either as a peer id or, only for projects, the
+        /// URN of a person in the local storage
+        #[structopt(long = "add-delegate", name = "add-delegate")]
+        pub delegates: Vec<Delegate>,
+
+        /// the Radicle URN of the local identity used for `rad/self`
+        #[structopt(long)]
+        pub whoami: Option<Urn>,
+    }
+}
+
+/// install git hooks into a working copy, which publish new commits to the
+/// monorepo and announce them to the seeds of the profile
+#[derive(Debug, StructOpt)]
+pub struct Hooks {
+    #[structopt(subcommand)]
+    pub options: hooks::Options,
+}
+
+pub mod hooks {
+    use super::*;
+
+    use librad::git::local::hooks::Hook;
+
+    #[derive(Debug, StructOpt)]
+    pub enum Options {
+        Install(Install),
+        Uninstall(Uninstall),
+        Run(Run),
+    }
+
+    /// install the `post-commit` and `post-push` hooks into the working copy
+    #[derive(Debug, StructOpt)]
+    pub struct Install {
+        /// the path to the working copy, defaults to the current directory
+        #[structopt(long, default_value = ".")]
+        pub path: PathBuf,
+
+        /// overwrite existing hooks which were not installed by `rad`
+        #[structopt(long)]
+        pub force: bool,
+    }
+
+    /// remove the hooks installed by `rad hooks install`
+    #[derive(Debug, StructOpt)]
+    pub struct Uninstall {
+        /// the path to the working copy, defaults to the current directory
+        #[structopt(long, default_value = ".")]
+        pub path: PathBuf,
+    }
+
+    /// run a hook, this is called by the installed hooks
+    #[derive(Debug, StructOpt)]
+    #[structopt(setting = structopt::clap::AppSettings::Hidden)]
+    pub struct Run {
+        /// the hook to run, either `post-commit` or `post-push`
+        pub hook: Hook,
+    }
+}
+
+/// manage the petnames of the profile, local names for Radicle URNs and peer
+/// ids. A petname can be given instead of a Radicle URN to any command.
+#[derive(Debug, StructOpt)]
+pub struct Petname {
+    #[structopt(subcommand)]
+    pub options: petname::Options,
+}
+
+pub mod petname {
+    use super::*;
+
+    use librad::profile::petnames::{self, Target};
+
+    #[derive(Debug, StructOpt)]
+    pub enum Options {
+        Add(Add),
+        Remove(Remove),
+        List(List),
+        Export(Export),
+        Import(Import),
+    }
+
+    /// give a Radicle URN or peer id a petname
+    #[derive(Debug, StructOpt)]
+    pub struct Add {
+        /// the petname, consisting of letters, digits, and `._-`
+        pub name: petnames::Petname,
+
+        /// the Radicle URN or peer id the petname refers to
+        pub target: Target,
+
+        /// replace the target if the petname is already in use
+        #[structopt(long)]
+        pub force: bool,
+    }
+
+    /// remove a petname
+    #[derive(Debug, StructOpt)]
+    pub struct Remove {
+        /// the petname to remove
+        pub name: petnames::Petname,
+    }
+
+    /// list the petnames of the profile
+    #[derive(Debug, StructOpt)]
+    pub struct List {}
+
+    /// export the petnames of the profile, in the TOML format they are stored
+    /// in
+    #[derive(Debug, StructOpt)]
+    pub struct Export {
+        /// the file to write to, defaults to stdout
+        #[structopt(long)]
+        pub output: Option<PathBuf>,
+    }
+
+    /// import petnames exported by `rad petname export`. By default, nothing
+    /// is imported if any petname is already in use for a different target.
+    #[derive(Debug, StructOpt)]
+    pub struct Import {
+        /// the file to read from
+        pub input: PathBuf,
+
+        /// keep the existing targets of conflicting petnames
+        #[structopt(long, conflicts_with = "replace")]
+        pub keep: bool,
+
+        /// replace the existing targets of conflicting petnames
+        #[structopt(long)]
+        pub replace: bool,
+    }
+}
+
 /// If an external subcommand is called, we sanitise the global arguments according to the rules defined in [RFC 698](https://github.com/radicle-dev/radicle-link/blob/master/docs/rfc/0698-cli-infrastructure.adoc#global-parameters).
 ///
 /// The rules are summarised as:
@@ -92,6 +633,16 @@ pub fn sanitise_globals(mut args: Args) -> Args {
                 external,
             );
 
+            sanitise_option(
+                RAD_SSH_AUTH_SOCK_ARG,
+                "RAD_SSH_AUTH_SOCK",
+                args.global
+                    .rad_ssh_auth_sock
+                    .as_ref()
+                    .map(|sock| sock.to_string()),
+                external,
+            );
+
             sanitise_flag(RAD_QUIET_ARG, "RAD_QUIET", args.global.rad_quiet, external);
 
             sanitise_flag(
@@ -101,12 +652,54 @@ pub fn sanitise_globals(mut args: Args) -> Args {
                 external,
             );
 
+            sanitise_option(
+                RAD_FORMAT_ARG,
+                "RAD_FORMAT",
+                args.global.rad_format.map(|format| format.to_string()),
+                external,
+            );
+
+            sanitise_option(
+                RAD_PASSPHRASE_FD_ARG,
+                "RAD_PASSPHRASE_FD",
+                args.global.rad_passphrase_fd.map(|fd| fd.to_string()),
+                external,
+            );
+
+            sanitise_option(
+                RAD_LOG_ARG,
+                "RAD_LOG",
+                args.global.rad_log.map(|log| log.to_string()),
+                external,
+            );
+
             args
         },
         _ => args,
     }
 }
 
+/// Parse the environment variable `var`, if it is set.
+fn from_env<T>(var: &'static str) -> Result<Option<T>, InvalidEnv>
+where
+    T: FromStr,
+    T::Err: ToString,
+{
+    match env::var(var) {
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(value)) => Err(InvalidEnv {
+            var,
+            value: value.to_string_lossy().into_owned(),
+            reason: "not valid unicode".to_owned(),
+        }),
+        Ok(value) => value.parse().map(Some).map_err(|e: T::Err| InvalidEnv {
+            var,
+            reason: e.to_string(),
+            value,
+        }),
+    }
+}
+
 fn sanitise_option(arg: &str, env: &str, global: Option<String>, external: &mut Vec<String>) {
     let env = env::var(env).ok();
     let ex_arg = {
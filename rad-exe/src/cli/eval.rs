@@ -0,0 +1,17 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+pub mod commands;
+pub mod completions;
+pub mod daemon;
+pub mod graph;
+pub mod hooks;
+pub mod identity;
+pub mod inspect;
+pub mod key;
+pub mod ls;
+pub mod petname;
+pub mod seed;
+pub mod sync;
@@ -0,0 +1,21 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use rad_clib::ser::OutputFormat;
+
+use crate::{cli::args::Commands, external};
+
+pub fn eval(format: OutputFormat, Commands {}: Commands) -> anyhow::Result<()> {
+    let commands = external::commands();
+    match format {
+        OutputFormat::Plain => {
+            for command in commands {
+                println!("{}", command);
+            }
+        },
+        OutputFormat::Json => println!("{}", serde_json::to_string(&commands)?),
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::io;
+
+use structopt::StructOpt as _;
+
+use librad::profile::{ProfileId, RadHome};
+
+use crate::{
+    cli::args::{Args, Completions},
+    completions,
+};
+
+/// The name of the executable the completions are generated for.
+const BIN: &str = "rad";
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    Completions { shell, list }: Completions,
+) -> anyhow::Result<()> {
+    if let Some(kind) = list {
+        for candidate in completions::candidates(&RadHome::default(), profile, kind)? {
+            println!("{}", candidate);
+        }
+        return Ok(());
+    }
+
+    let shell = shell.ok_or_else(|| anyhow::anyhow!("no shell was provided"))?;
+    Args::clap().gen_completions_to(BIN, shell, &mut io::stdout());
+    if let Some(hook) = completions::hook(BIN, shell) {
+        print!("{}", hook);
+    }
+    Ok(())
+}
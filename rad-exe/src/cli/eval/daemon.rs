@@ -0,0 +1,49 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::profile::{Profile, ProfileId, RadHome};
+use rad_clib::{keys::ssh, runtime, storage};
+
+use crate::{
+    cli::args::Daemon,
+    daemon::{self, Options, Tracker},
+    seed,
+};
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    sock: ssh::SshAuthSock,
+    Daemon {
+        listen,
+        seeds,
+        announce_interval,
+        no_announce,
+        tracking,
+    }: Daemon,
+) -> anyhow::Result<()> {
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    // ensures the storage is initialised for the profile and signer
+    let (signer, storage) = storage::ssh::storage(&profile, sock)?;
+    drop(storage);
+
+    let settings = profile.settings().load()?;
+    let seeds = if seeds.is_empty() {
+        seed::resolve(&settings.seeds)
+    } else {
+        seeds
+    };
+
+    let opts = Options {
+        listen_addr: listen,
+        network: settings.network.network()?,
+        seeds,
+        announce: (!no_announce).then(|| Duration::from_secs(announce_interval.max(1))),
+        tracker: Tracker::from_args(&tracking, &profile.petnames().load()?)?,
+    };
+    runtime::block_on(daemon::run(&profile, signer, opts))
+}
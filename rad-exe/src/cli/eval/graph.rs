@@ -0,0 +1,26 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::profile::{Profile, ProfileId, RadHome};
+use rad_clib::{ser::OutputFormat, storage};
+
+use crate::{cli::args::Graph, graph};
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    format: OutputFormat,
+    Graph { urn }: Graph,
+) -> anyhow::Result<()> {
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    let urn = urn.map(|urn| urn.resolve(&profile)).transpose()?;
+    let storage = storage::read_only(&profile)?;
+    let graph = graph::graph(&storage, urn.as_ref())?;
+    match format {
+        OutputFormat::Plain => print!("{}", graph),
+        OutputFormat::Json => println!("{}", serde_json::to_string(&graph)?),
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::local::{
+        hooks::{self as local_hooks, Hook},
+        transport,
+    },
+    profile::{Profile, ProfileId, RadHome},
+};
+use rad_clib::{keys::ssh, runtime, ser::OutputFormat, storage};
+use serde_json::json;
+
+use crate::{
+    cli::args::{hooks::*, Hooks},
+    hooks,
+    progress::Progress,
+    seed,
+    sync::{self, Mode, Options},
+};
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    sock: ssh::SshAuthSock,
+    format: OutputFormat,
+    Hooks { options }: Hooks,
+) -> anyhow::Result<()> {
+    match options {
+        Options::Install(Install { path, force }) => {
+            let repo = git2::Repository::open(path)?;
+            let (urn, installed) = hooks::install(&repo, force)?;
+            match format {
+                OutputFormat::Plain => {
+                    for path in &installed {
+                        println!("installed {}", path);
+                    }
+                },
+                OutputFormat::Json => {
+                    println!("{}", json!({ "urn": urn, "installed": installed }))
+                },
+            }
+        },
+        Options::Uninstall(Uninstall { path }) => {
+            let repo = git2::Repository::open(path)?;
+            let removed = local_hooks::uninstall(&repo)?
+                .into_iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>();
+            match format {
+                OutputFormat::Plain => {
+                    for path in &removed {
+                        println!("removed {}", path);
+                    }
+                },
+                OutputFormat::Json => println!("{}", json!({ "removed": removed })),
+            }
+        },
+        Options::Run(Run { hook }) => run(profile, sock, hook)?,
+    }
+
+    Ok(())
+}
+
+/// Publish and announce the working copy the hook runs in. Working copies
+/// without a `rad` remote are ignored, so that a shared `core.hooksPath`
+/// doesn't get in the way of other repositories.
+fn run(profile: Option<ProfileId>, sock: ssh::SshAuthSock, hook: Hook) -> anyhow::Result<()> {
+    let repo = git2::Repository::open_from_env()?;
+    let rad = match hooks::rad_remote(&repo)? {
+        Some(rad) => rad,
+        None => return Ok(()),
+    };
+    let urn = rad.url.urn.clone();
+
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    let (signer, storage) = storage::ssh::storage(&profile, sock)?;
+    drop(storage);
+
+    if hook == Hook::PostCommit {
+        let settings = transport::Settings {
+            paths: profile.paths().clone(),
+            signer: signer.clone(),
+        };
+        for published in hooks::publish(&repo, rad, settings)? {
+            eprintln!("published {} to {}", published, urn);
+        }
+    }
+
+    let settings = profile.settings().load()?;
+    let seeds = seed::resolve(&settings.seeds);
+    if seeds.is_empty() {
+        eprintln!("no seeds configured, not announcing {}", urn);
+        return Ok(());
+    }
+    let opts = Options {
+        mode: Mode::Push,
+        concurrency: 1,
+        providers: None,
+        network: settings.network.network()?,
+    };
+    let synced = runtime::block_on(sync::sync(
+        &profile,
+        signer,
+        vec![urn],
+        seeds,
+        opts,
+        &Progress::hidden(),
+    ))?;
+    for urn in &synced.announced {
+        eprintln!("announced {}", urn);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::profile::{Profile, ProfileId, RadHome};
+use rad_clib::{keys::ssh, ser::OutputFormat, storage};
+
+use crate::{
+    cli::args::{identity::*, Identity},
+    identity::{self, Edit},
+};
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    sock: ssh::SshAuthSock,
+    format: OutputFormat,
+    Identity { options }: Identity,
+) -> anyhow::Result<()> {
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    let (signer, storage) = storage::ssh::storage(&profile, sock)?;
+    let paths = profile.paths();
+    let published = match options {
+        Options::Create(Create::Person(Person {
+            name,
+            delegates,
+            default,
+        })) => identity::create_person(&storage, paths, signer, name, delegates, default)?,
+        Options::Create(Create::Project(Project {
+            name,
+            description,
+            default_branch,
+            delegates,
+            whoami,
+        })) => identity::create_project(
+            &storage,
+            paths,
+            signer,
+            whoami,
+            name,
+            description,
+            default_branch,
+            delegates,
+        )?,
+        Options::Update(Update {
+            urn,
+            name,
+            description,
+            default_branch,
+            delegates,
+            whoami,
+        }) => identity::update(
+            &storage,
+            &urn.resolve(&profile)?,
+            whoami,
+            Edit {
+                name,
+                description,
+                default_branch,
+                delegates,
+            },
+        )?,
+    };
+    match format {
+        OutputFormat::Plain => println!("{}", published),
+        OutputFormat::Json => println!("{}", serde_json::to_string(&published)?),
+    }
+    Ok(())
+}
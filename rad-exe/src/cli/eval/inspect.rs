@@ -0,0 +1,30 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::identities,
+    profile::{Profile, ProfileId, RadHome},
+};
+use rad_clib::{ser::OutputFormat, storage};
+
+use crate::{cli::args::Inspect, inspect};
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    format: OutputFormat,
+    Inspect { urn }: Inspect,
+) -> anyhow::Result<()> {
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    let urn = urn.resolve(&profile)?;
+    let storage = storage::read_only(&profile)?;
+    let inspection = inspect::inspect(&storage, &urn)?
+        .ok_or_else(|| identities::Error::NotFound(urn.clone()))?;
+    match format {
+        OutputFormat::Plain => print!("{}", inspection),
+        OutputFormat::Json => println!("{}", serde_json::to_string(&inspection)?),
+    }
+    Ok(())
+}
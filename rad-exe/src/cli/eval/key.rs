@@ -0,0 +1,91 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::io::{self, Write as _};
+
+use librad::profile::{Profile, ProfileId, RadHome};
+use rad_clib::{keys::passphrase::Passphrases, ser::OutputFormat};
+use serde_json::json;
+
+use crate::{
+    cli::args::{key::*, Key},
+    key::{self, ExportFormat},
+};
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    passphrases: Passphrases,
+    format: OutputFormat,
+    Key { options }: Key,
+) -> anyhow::Result<()> {
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    match options {
+        Options::Show(Show {}) => {
+            let key = key::show(&profile)?;
+            match format {
+                OutputFormat::Plain => println!("{}", key),
+                OutputFormat::Json => println!("{}", serde_json::to_string(&key)?),
+            }
+        },
+        Options::Export(Export {
+            format: export,
+            user_id,
+        }) => {
+            let exported = match export {
+                ExportFormat::Ssh => key::ssh_public_key(&key::show(&profile)?.peer_id),
+                ExportFormat::Pgp => {
+                    let user_id = match user_id {
+                        Some(user_id) => user_id,
+                        None => format!("rad:{}", key::show(&profile)?.peer_id),
+                    };
+                    key::pgp_public_key(&profile, passphrases.unlock(), &user_id)?
+                },
+            };
+            match format {
+                OutputFormat::Plain => println!("{}", exported.trim_end()),
+                OutputFormat::Json => println!(
+                    "{}",
+                    json!({ "format": export.to_string(), "key": exported })
+                ),
+            }
+        },
+        Options::Rotate(Rotate { yes }) => {
+            if !yes
+                && !confirm(
+                    "rotating the key changes the peer id of this profile, and identities \
+                     delegating to the current key will need to be updated",
+                )?
+            {
+                return Ok(());
+            }
+            let rotated = key::rotate(&profile, passphrases.unlock(), passphrases.choose())?;
+            match format {
+                OutputFormat::Plain => println!("{}", rotated),
+                OutputFormat::Json => println!("{}", serde_json::to_string(&rotated)?),
+            }
+        },
+        Options::Reencrypt(Reencrypt { yes }) => {
+            if !yes && !confirm("the key will be locked with a new passphrase")? {
+                return Ok(());
+            }
+            let peer_id = key::reencrypt(&profile, passphrases.unlock(), passphrases.choose())?;
+            match format {
+                OutputFormat::Plain => println!("re-encrypted key {}", peer_id),
+                OutputFormat::Json => println!("{}", json!({ "peer_id": peer_id })),
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Ask the user to confirm the operation described by `warning` on stdin.
+fn confirm(warning: &str) -> anyhow::Result<bool> {
+    eprint!("{}. Continue? [y/N] ", warning);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
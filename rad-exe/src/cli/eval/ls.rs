@@ -0,0 +1,25 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::profile::{Profile, ProfileId, RadHome};
+use rad_clib::{ser::OutputFormat, storage};
+
+use crate::{cli::args::Ls, ls};
+
+pub fn eval(profile: Option<ProfileId>, format: OutputFormat, Ls {}: Ls) -> anyhow::Result<()> {
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    let storage = storage::read_only(&profile)?;
+    let listings = ls::list(&storage)?;
+    match format {
+        OutputFormat::Plain => {
+            for listing in listings {
+                println!("{}", listing);
+            }
+        },
+        OutputFormat::Json => println!("{}", serde_json::to_string(&listings)?),
+    }
+    Ok(())
+}
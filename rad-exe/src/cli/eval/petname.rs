@@ -0,0 +1,126 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{fs::File, io};
+
+use librad::profile::{
+    petnames::{Conflict, OnConflict},
+    Profile,
+    ProfileId,
+    RadHome,
+};
+use rad_clib::ser::OutputFormat;
+use serde_json::json;
+
+use crate::cli::args::{petname::*, Petname};
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    format: OutputFormat,
+    Petname { options }: Petname,
+) -> anyhow::Result<()> {
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    let store = profile.petnames();
+    match options {
+        Options::Add(Add {
+            name,
+            target,
+            force,
+        }) => {
+            let added = store.update(|petnames| {
+                if force {
+                    Ok(petnames.replace(name.clone(), target.clone()).as_ref() != Some(&target))
+                } else {
+                    petnames.insert(name.clone(), target.clone())
+                }
+            })?;
+            match format {
+                OutputFormat::Plain if added => println!("{} -> {}", name, target),
+                OutputFormat::Plain => println!("{} already refers to {}", name, target),
+                OutputFormat::Json => println!(
+                    "{}",
+                    json!({ "name": name, "target": target.to_string(), "added": added })
+                ),
+            }
+        },
+        Options::Remove(Remove { name }) => {
+            let removed = store.update(|petnames| Ok(petnames.remove(&name)))?;
+            let target = removed.ok_or_else(|| anyhow::anyhow!("unknown petname `{}`", name))?;
+            match format {
+                OutputFormat::Plain => println!("removed {} -> {}", name, target),
+                OutputFormat::Json => {
+                    println!("{}", json!({ "name": name, "target": target.to_string() }))
+                },
+            }
+        },
+        Options::List(List {}) => {
+            let petnames = store.load()?;
+            match format {
+                OutputFormat::Plain => {
+                    for (name, target) in petnames.iter() {
+                        println!("{} -> {}", name, target);
+                    }
+                },
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::Value::Array(
+                        petnames
+                            .iter()
+                            .map(|(name, target)| json!({
+                                "name": name,
+                                "target": target.to_string()
+                            }))
+                            .collect()
+                    )
+                ),
+            }
+        },
+        Options::Export(Export { output }) => match output {
+            Some(path) => store.export(File::create(path)?)?,
+            None => store.export(io::stdout())?,
+        },
+        Options::Import(Import {
+            input,
+            keep,
+            replace,
+        }) => {
+            let on_conflict = if replace {
+                OnConflict::Replace
+            } else if keep {
+                OnConflict::Keep
+            } else {
+                OnConflict::Fail
+            };
+            let conflicts = store.import(File::open(input)?, on_conflict)?;
+            match format {
+                OutputFormat::Plain => {
+                    for Conflict { name, ours, theirs } in conflicts {
+                        match on_conflict {
+                            OnConflict::Replace => {
+                                println!("replaced {} -> {} with {}", name, ours, theirs)
+                            },
+                            _ => println!("kept {} -> {} instead of {}", name, ours, theirs),
+                        }
+                    }
+                },
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::Value::Array(
+                        conflicts
+                            .into_iter()
+                            .map(|Conflict { name, ours, theirs }| json!({
+                                "name": name,
+                                "ours": ours.to_string(),
+                                "theirs": theirs.to_string(),
+                            }))
+                            .collect()
+                    )
+                ),
+            }
+        },
+    }
+    Ok(())
+}
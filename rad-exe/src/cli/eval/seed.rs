@@ -0,0 +1,60 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::profile::{Profile, ProfileId, RadHome};
+use rad_clib::ser::OutputFormat;
+use serde_json::json;
+
+use crate::{
+    cli::args::{seed::*, Seed},
+    seed,
+};
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    format: OutputFormat,
+    Seed { options }: Seed,
+) -> anyhow::Result<()> {
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    match options {
+        Options::Add(Add { seed }) => {
+            let added = seed::add(&profile, &seed)?;
+            match format {
+                OutputFormat::Plain if added => println!("added seed {}", seed),
+                OutputFormat::Plain => println!("seed {} is already configured", seed),
+                OutputFormat::Json => println!("{}", json!({ "seed": seed, "added": added })),
+            }
+        },
+        Options::Remove(Remove { seed }) => {
+            let removed = seed::remove(&profile, &seed)?;
+            anyhow::ensure!(
+                !removed.is_empty(),
+                "no seed matching `{}` is configured",
+                seed
+            );
+            match format {
+                OutputFormat::Plain => {
+                    for seed in removed {
+                        println!("removed seed {}", seed);
+                    }
+                },
+                OutputFormat::Json => println!("{}", json!({ "removed": removed })),
+            }
+        },
+        Options::List(List {}) => {
+            let seeds = seed::list(&profile)?;
+            match format {
+                OutputFormat::Plain => {
+                    for seed in seeds {
+                        println!("{}", seed);
+                    }
+                },
+                OutputFormat::Json => println!("{}", serde_json::to_string(&seeds)?),
+            }
+        },
+    }
+    Ok(())
+}
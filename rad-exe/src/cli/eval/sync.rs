@@ -0,0 +1,87 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::{
+    git::identities,
+    profile::{Profile, ProfileId, RadHome},
+};
+use rad_clib::{keys::ssh, runtime, ser::OutputFormat, storage};
+
+use crate::{
+    cli::args::Sync,
+    progress::Progress,
+    seed,
+    sync::{self, Mode, Options},
+};
+
+pub fn eval(
+    profile: Option<ProfileId>,
+    sock: ssh::SshAuthSock,
+    format: OutputFormat,
+    quiet: bool,
+    Sync {
+        urn,
+        seeds,
+        providers,
+        fetch_only,
+        push,
+        concurrency,
+    }: Sync,
+) -> anyhow::Result<()> {
+    let home = RadHome::default();
+    let profile = Profile::from_home(&home, profile)?;
+    let urn = urn.map(|urn| urn.resolve(&profile)).transpose()?;
+    let (signer, storage) = storage::ssh::storage(&profile, sock)?;
+    let urns = match urn {
+        Some(urn) => {
+            // ensure that the URN exists and is indeed a project
+            rad_identities::project::get(&storage, &urn)?
+                .ok_or_else(|| identities::Error::NotFound(urn.clone()))?;
+            vec![urn]
+        },
+        None => vec![],
+    };
+    drop(storage);
+
+    let settings = profile.settings().load()?;
+    let seeds = if seeds.is_empty() {
+        seed::resolve(&settings.seeds)
+    } else {
+        seeds
+    };
+
+    let opts = Options {
+        mode: Mode::new(fetch_only, push),
+        concurrency,
+        providers: providers.map(Duration::from_secs),
+        network: settings.network.network()?,
+    };
+    let progress = Progress::new(urns.len() as u64, quiet);
+    let synced = runtime::block_on(sync::sync(&profile, signer, urns, seeds, opts, &progress))?;
+
+    match format {
+        OutputFormat::Plain => {
+            for summary in &synced.succeeded {
+                println!("{}", summary);
+            }
+            for urn in &synced.announced {
+                println!("announced {}", urn);
+            }
+            for failed in &synced.failed {
+                eprintln!("failed to sync {}", failed);
+            }
+        },
+        OutputFormat::Json => println!("{}", serde_json::to_string(&synced)?),
+    }
+    anyhow::ensure!(
+        synced.failed.is_empty(),
+        "{} replication(s) failed",
+        synced.failed.len()
+    );
+
+    Ok(())
+}
@@ -10,30 +10,77 @@ use std::{
 
 use structopt::StructOpt;
 
-use super::args::{self, sanitise_globals, Args};
+use librad::profile::RadHome;
+
+use crate::{
+    config::Config,
+    exit::{Code, External},
+    logging,
+};
+
+use super::{
+    args::{self, sanitise_globals, Args},
+    eval,
+};
 
 pub fn main() -> anyhow::Result<()> {
-    let Args { global, command } = sanitise_globals(Args::from_args());
+    let mut args = Args::from_args_safe()?;
+    let config = Config::load_or_default(&RadHome::default(), args.global.rad_profile.as_ref());
+    args.global = args.global.layer(&config);
+    let Args { global, command } = sanitise_globals(args);
+    logging::init(global.log()?, global.rad_verbose);
+    let format = global.format()?;
     match command {
         args::Command::Identities(args) => {
-            rad_identities::cli::main(args, global.rad_profile, global.rad_ssh_auth_sock)
+            rad_identities::cli::main(args, global.rad_profile, global.ssh_auth_sock())
+        },
+        args::Command::Profile(args) => {
+            let passphrases = global.passphrases()?;
+            rad_profile::cli::main(args, global.ssh_auth_sock(), passphrases, format)
         },
-        args::Command::Profile(args) => rad_profile::cli::main(args, global.rad_ssh_auth_sock),
+        args::Command::Ls(args) => eval::ls::eval(global.rad_profile, format, args),
+        args::Command::Inspect(args) => eval::inspect::eval(global.rad_profile, format, args),
+        args::Command::Graph(args) => eval::graph::eval(global.rad_profile, format, args),
+        args::Command::Completions(args) => eval::completions::eval(global.rad_profile, args),
+        args::Command::Daemon(args) => {
+            eval::daemon::eval(global.rad_profile, global.ssh_auth_sock(), args)
+        },
+        args::Command::Commands(args) => eval::commands::eval(format, args),
+        args::Command::Key(args) => {
+            let passphrases = global.passphrases()?;
+            eval::key::eval(global.rad_profile, passphrases, format, args)
+        },
+        args::Command::Identity(args) => {
+            eval::identity::eval(global.rad_profile, global.ssh_auth_sock(), format, args)
+        },
+        args::Command::Seed(args) => eval::seed::eval(global.rad_profile, format, args),
+        args::Command::Petname(args) => eval::petname::eval(global.rad_profile, format, args),
+        args::Command::Hooks(args) => {
+            eval::hooks::eval(global.rad_profile, global.ssh_auth_sock(), format, args)
+        },
+        args::Command::Sync(args) => eval::sync::eval(
+            global.rad_profile,
+            global.ssh_auth_sock(),
+            format,
+            global.rad_quiet,
+            args,
+        ),
         args::Command::External(external) => {
             let exe = external.first();
             match exe {
                 Some(exe) => {
                     let exe = format!("rad-{}", exe);
-                    let status = Command::new(exe.clone()).args(&external[1..]).status();
+                    let status = Command::new(exe.clone())
+                        .args(&external[1..])
+                        .envs(crate::external::environment(&external[1..]))
+                        .status();
                     match status {
-                        Ok(status) => {
-                            anyhow::ensure!(status.success(), status);
-                            Ok(())
-                        },
+                        Ok(status) if status.success() => Ok(()),
+                        Ok(status) => Err(External { name: exe, status }.into()),
                         Err(err) => {
                             if let ErrorKind::NotFound = err.kind() {
                                 eprintln!("{} not found", exe);
-                                exit(1)
+                                exit(Code::NotFound.into())
                             } else {
                                 Err(err.into())
                             }
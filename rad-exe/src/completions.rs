@@ -0,0 +1,183 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Shell completion support.
+//!
+//! The static part of the completion scripts is generated from the command
+//! line definitions. The dynamic part -- values which depend on the state of
+//! the local machine -- is provided by hooks which call back into `rad` to list
+//! the candidates, see [`Candidates`].
+
+use std::{fmt, str::FromStr};
+
+use structopt::clap::Shell;
+use thiserror::Error;
+
+use librad::{
+    git::{identities, storage::ReadOnly},
+    profile::{self, petnames::Target, Profile, ProfileId, RadHome},
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Petnames(#[from] profile::petnames::Error),
+
+    #[error(transparent)]
+    Profile(#[from] profile::Error),
+
+    #[error(transparent)]
+    ReadOnly(#[from] librad::git::storage::read::error::Init),
+}
+
+/// The kinds of values which are completed dynamically.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Candidates {
+    /// The identifiers of all profiles.
+    ProfileIds,
+    /// The URNs of all identities found in the storage of the active profile,
+    /// and the petnames referring to URNs.
+    Urns,
+}
+
+impl Candidates {
+    pub fn variants() -> &'static [&'static str] {
+        &["profile-ids", "urns"]
+    }
+}
+
+impl fmt::Display for Candidates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ProfileIds => write!(f, "profile-ids"),
+            Self::Urns => write!(f, "urns"),
+        }
+    }
+}
+
+impl FromStr for Candidates {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "profile-ids" => Ok(Self::ProfileIds),
+            "urns" => Ok(Self::Urns),
+            _ => Err("unknown candidates, expected `profile-ids` or `urns`"),
+        }
+    }
+}
+
+/// List the values for the given [`Candidates`].
+pub fn candidates(
+    home: &RadHome,
+    profile: Option<ProfileId>,
+    kind: Candidates,
+) -> Result<Vec<String>, Error> {
+    match kind {
+        Candidates::ProfileIds => Ok(Profile::list(home)?
+            .iter()
+            .map(|profile| profile.id().to_string())
+            .collect()),
+        Candidates::Urns => {
+            let profile = Profile::from_home(home, profile)?;
+            let storage = ReadOnly::open(profile.paths())?;
+            let mut urns = identities::any::list_urns(&storage)?
+                .map(|urn| urn.map(|urn| urn.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            urns.extend(
+                profile
+                    .petnames()
+                    .load()?
+                    .iter()
+                    .filter(|(_, target)| matches!(target, Target::Urn(_)))
+                    .map(|(name, _)| name.to_string()),
+            );
+            Ok(urns)
+        },
+    }
+}
+
+/// The script which hooks the dynamic completions into the completion script
+/// generated for `shell`, if the `shell` is supported.
+pub fn hook(bin: &str, shell: Shell) -> Option<String> {
+    match shell {
+        Shell::Bash => Some(format!(
+            r#"
+_{bin}_dynamic() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "${{prev}}" in
+        --rad-profile|--id)
+            COMPREPLY=($(compgen -W "$({bin} completions --list profile-ids 2>/dev/null)" -- "${{cur}}"))
+            return 0
+            ;;
+        --urn|--whoami)
+            COMPREPLY=($(compgen -W "$({bin} completions --list urns 2>/dev/null)" -- "${{cur}}"))
+            return 0
+            ;;
+    esac
+    # The subcommands taking a URN as positional argument.
+    case " ${{COMP_WORDS[*]:1:COMP_CWORD-1}} " in
+        *" sync "*|*" inspect "*|*" identity update "*)
+            if [[ "${{cur}}" != -* && "${{prev}}" != --* ]]; then
+                COMPREPLY=($(compgen -W "$({bin} completions --list urns 2>/dev/null)" -- "${{cur}}"))
+                return 0
+            fi
+            ;;
+    esac
+    _{bin} "$@"
+}}
+
+complete -F _{bin}_dynamic -o bashdefault -o default {bin}
+"#,
+            bin = bin
+        )),
+        Shell::Zsh => Some(format!(
+            r#"
+_{bin}_dynamic() {{
+    case "${{words[CURRENT-1]}}" in
+        --rad-profile|--id)
+            compadd -- ${{(f)"$({bin} completions --list profile-ids 2>/dev/null)"}}
+            return
+            ;;
+        --urn|--whoami)
+            compadd -- ${{(f)"$({bin} completions --list urns 2>/dev/null)"}}
+            return
+            ;;
+    esac
+    # The subcommands taking a URN as positional argument.
+    case " ${{words[2,CURRENT-1]}} " in
+        *" sync "*|*" inspect "*|*" identity update "*)
+            if [[ "${{words[CURRENT]}}" != -* && "${{words[CURRENT-1]}}" != --* ]]; then
+                compadd -- ${{(f)"$({bin} completions --list urns 2>/dev/null)"}}
+                return
+            fi
+            ;;
+    esac
+    _{bin} "$@"
+}}
+
+compdef _{bin}_dynamic {bin}
+"#,
+            bin = bin
+        )),
+        Shell::Fish => Some(format!(
+            r#"
+complete -c {bin} -l rad-profile -f -a '({bin} completions --list profile-ids 2>/dev/null)'
+complete -c {bin} -l id -f -a '({bin} completions --list profile-ids 2>/dev/null)'
+complete -c {bin} -l urn -f -a '({bin} completions --list urns 2>/dev/null)'
+complete -c {bin} -l whoami -f -a '({bin} completions --list urns 2>/dev/null)'
+complete -c {bin} -n '__fish_seen_subcommand_from sync inspect' -f -a '({bin} completions --list urns 2>/dev/null)'
+complete -c {bin} -n '__fish_seen_subcommand_from identity; and __fish_seen_subcommand_from update' -f -a '({bin} completions --list urns 2>/dev/null)'
+"#,
+            bin = bin
+        )),
+        _ => None,
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Layered configuration files.
+//!
+//! The configuration is read from two files, where the latter takes precedence
+//! over the former:
+//!
+//!   1. the user configuration, `~/.config/radicle/config.toml` on Linux, or
+//!      `$RAD_HOME/config.toml` if `RAD_HOME` is set, and
+//!   2. the configuration of the profile, `config.toml` in the
+//!      [`librad::paths::Paths::config_dir`] of the profile in use.
+//!
+//! Both files are layered beneath the environment variables and command line
+//! arguments, following the precedence rules of [RFC 698], see
+//! [`crate::cli::args::Global::layer`].
+//!
+//! An example configuration:
+//!
+//! ```toml
+//! profile = "e8ae552d-3285-405c-a156-b9b7af6daa49"
+//! quiet = false
+//! verbose = true
+//! format = "json"
+//! log = "compact"
+//! ```
+//!
+//! The `profile` key is only read from the user configuration, since it
+//! decides which profile configuration is read.
+//!
+//! The settings which are shared with other applications using the profile,
+//! such as the seeds and the network, are not kept here, but in the
+//! [`librad::profile::settings`] of the profile.
+//!
+//! [RFC 698]: https://github.com/radicle-dev/radicle-link/blob/master/docs/rfc/0698-cli-infrastructure.adoc
+
+use std::{
+    env,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use directories::BaseDirs;
+use serde::Deserialize;
+use thiserror::Error;
+
+use librad::profile::{Profile, ProfileId, RadHome, RAD_PROFILE};
+use rad_clib::ser::OutputFormat;
+
+use crate::logging::LogFormat;
+
+/// The name of the configuration files.
+pub const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("failed to parse {0}")]
+    Parse(PathBuf, #[source] toml::de::Error),
+
+    #[error("invalid `{key}` in {path}: {reason}")]
+    Invalid {
+        path: PathBuf,
+        key: &'static str,
+        reason: String,
+    },
+
+    #[error(transparent)]
+    Profile(#[from] rad_profile::Error),
+}
+
+/// The configuration resulting from layering the profile configuration over
+/// the user configuration. Unset values are `None`.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub profile: Option<ProfileId>,
+    pub quiet: Option<bool>,
+    pub verbose: Option<bool>,
+    pub format: Option<OutputFormat>,
+    pub log: Option<LogFormat>,
+}
+
+/// A single configuration file, as found on disk.
+#[derive(Debug, Default, Deserialize)]
+struct Layer {
+    profile: Option<String>,
+    quiet: Option<bool>,
+    verbose: Option<bool>,
+    format: Option<String>,
+    log: Option<String>,
+}
+
+impl Config {
+    /// Load the configuration for the profile given by `profile`, falling back
+    /// to `RAD_PROFILE`, the `profile` of the user configuration, and finally
+    /// the active profile.
+    ///
+    /// Missing files are treated as empty, and neither the profile nor any
+    /// files are created.
+    pub fn load(home: &RadHome, profile: Option<&ProfileId>) -> Result<Self, Error> {
+        let user_file = user_file(home)?;
+        let user = read(&user_file)?;
+        let mut config = Self::default();
+        config.apply(&user_file, user)?;
+
+        let id = match profile.cloned() {
+            Some(id) => Some(id),
+            // An invalid `RAD_PROFILE` is reported once the profile is loaded.
+            None => match env::var(RAD_PROFILE) {
+                Ok(id) => id.parse().ok(),
+                Err(_) => config.profile.clone(),
+            },
+        };
+        if let Some(profile) = rad_profile::get(home.clone(), id)? {
+            let profile_file = profile_file(&profile);
+            let mut layer = read(&profile_file)?;
+            layer.profile = None;
+            config.apply(&profile_file, layer)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Like [`Config::load`], but fall back to the defaults if the
+    /// configuration is malformed, printing a warning to stderr.
+    ///
+    /// The configuration only provides defaults for the global options, so a
+    /// broken file shouldn't lock the user out of eg. `rad key` or `rad
+    /// profile`, which may be needed to repair it.
+    pub fn load_or_default(home: &RadHome, profile: Option<&ProfileId>) -> Self {
+        Self::load(home, profile).unwrap_or_else(|err| {
+            eprintln!(
+                "warning: ignoring the configuration: {:#}",
+                anyhow::Error::from(err)
+            );
+            Self::default()
+        })
+    }
+
+    fn apply(&mut self, path: &Path, layer: Layer) -> Result<(), Error> {
+        let Layer {
+            profile,
+            quiet,
+            verbose,
+            format,
+            log,
+        } = layer;
+
+        if let Some(profile) = profile {
+            self.profile = Some(parse(path, "profile", &profile)?);
+        }
+        if let Some(format) = format {
+            self.format = Some(parse(path, "format", &format)?);
+        }
+        if let Some(log) = log {
+            self.log = Some(parse(path, "log", &log)?);
+        }
+        self.quiet = quiet.or(self.quiet);
+        self.verbose = verbose.or(self.verbose);
+        Ok(())
+    }
+}
+
+/// The path of the user configuration.
+pub fn user_file(home: &RadHome) -> Result<PathBuf, io::Error> {
+    match home {
+        RadHome::Root(root) => Ok(root.join(CONFIG_FILE)),
+        RadHome::ProjectDirs => BaseDirs::new()
+            .map(|dirs| dirs.config_dir().join("radicle").join(CONFIG_FILE))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "couldn't determine the home directory",
+                )
+            }),
+    }
+}
+
+/// The path of the configuration of `profile`.
+pub fn profile_file(profile: &Profile) -> PathBuf {
+    profile.paths().config_dir().join(CONFIG_FILE)
+}
+
+fn read(path: &Path) -> Result<Layer, Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents).map_err(|e| Error::Parse(path.to_path_buf(), e)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Layer::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn parse<T>(path: &Path, key: &'static str, value: &str) -> Result<T, Error>
+where
+    T: FromStr,
+    T::Err: ToString,
+{
+    value.parse().map_err(|e: T::Err| Error::Invalid {
+        path: path.to_path_buf(),
+        key,
+        reason: e.to_string(),
+    })
+}
@@ -0,0 +1,134 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Run a long-lived network endpoint for the local storage, i.e. a minimal
+//! seed node.
+//!
+//! The daemon serves fetches for all namespaces in the local storage, and
+//! periodically announces the local projects to the peers it is connected to.
+//! Optionally, it tracks and replicates projects announced by other peers,
+//! see [`Tracker`].
+
+use std::{net::SocketAddr, panic, time::Duration};
+
+use futures::future::{select_all, FutureExt as _};
+use tokio::{spawn, sync::mpsc};
+use tracing::{info, instrument, warn};
+
+use librad::{
+    net::{
+        discovery,
+        peer::{self, Peer},
+        protocol::{self, gossip},
+        Network,
+    },
+    profile::Profile,
+    Signer,
+};
+use node_lib::{signals, tracking};
+
+pub use node_lib::tracking::Tracker;
+
+use crate::sync::{self, Seed};
+
+pub struct Options {
+    /// The address to accept connections on.
+    pub listen_addr: SocketAddr,
+    pub network: Network,
+    /// The seeds to connect to on startup.
+    pub seeds: Vec<Seed>,
+    /// How often to announce the local projects. If `None`, the local projects
+    /// are not announced.
+    pub announce: Option<Duration>,
+    /// Whether to automatically track, and replicate, projects announced by
+    /// other peers.
+    pub tracker: Option<Tracker>,
+}
+
+/// Run the daemon until a termination signal is received.
+pub async fn run<S>(profile: &Profile, signer: S, opts: Options) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let peer = Peer::new(peer::Config {
+        signer,
+        protocol: protocol::Config {
+            paths: profile.paths().clone(),
+            listen_addr: opts.listen_addr,
+            advertised_addrs: None,
+            membership: Default::default(),
+            network: opts.network,
+            replication: Default::default(),
+            rate_limits: Default::default(),
+        },
+        storage: Default::default(),
+    })?;
+    let disco = discovery::Static::resolve(
+        opts.seeds
+            .iter()
+            .map(|seed| (seed.peer_id, seed.addrs.as_slice())),
+    )
+    .expect("seed addresses are already resolved");
+
+    let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+    let signals_task = spawn(signals::routine(shutdown_tx));
+
+    let mut coalesced = vec![];
+    let protocol_task = spawn(node_lib::protocol::routine(
+        peer.clone(),
+        disco,
+        shutdown_rx,
+    ))
+    .fuse();
+    coalesced.push(protocol_task);
+
+    if let Some(interval) = opts.announce {
+        let announce_task = spawn(announce(peer.clone(), interval)).fuse();
+        coalesced.push(announce_task);
+    }
+
+    if let Some(tracker) = opts.tracker {
+        let tracking_task = spawn(tracking::routine(peer.clone(), tracker)).fuse();
+        coalesced.push(tracking_task);
+    }
+
+    info!(peer_id = %peer.peer_id(), "starting daemon");
+    let (res, _idx, _rest) = select_all(coalesced).await;
+
+    match res {
+        Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+        Err(e) => return Err(e.into()),
+        Ok(res) => res?,
+    }
+
+    signals_task.await??;
+
+    Ok(())
+}
+
+/// Announce all local projects every `interval`.
+#[instrument(name = "announce subroutine", skip(peer))]
+async fn announce<S>(peer: Peer<S>, interval: Duration) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let urns = peer
+            .using_read_only(|storage| sync::local_projects(storage))
+            .await??;
+        for urn in urns {
+            let payload = gossip::Payload {
+                urn,
+                rev: None,
+                origin: None,
+            };
+            if let Err(payload) = peer.announce(payload) {
+                warn!(urn = %payload.urn, "failed to announce URN");
+            }
+        }
+    }
+}
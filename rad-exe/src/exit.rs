@@ -0,0 +1,265 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! The exit codes of `rad`, so that scripts wrapping it can branch on the
+//! class of a failure instead of parsing stderr.
+//!
+//! | code | meaning                                                    |
+//! |------|------------------------------------------------------------|
+//! | 0    | success                                                    |
+//! | 1    | any failure not covered below                              |
+//! | 2    | the command line arguments are invalid                     |
+//! | 3    | a profile, identity, file, or subcommand was not found     |
+//! | 4    | an identity failed to verify                               |
+//! | 5    | a network operation failed                                 |
+//! | 6    | the key is locked, i.e. it is not in the ssh-agent, or the |
+//! |      | passphrase is wrong                                        |
+//! | 7    | a configuration file is invalid                            |
+//! | 8    | a network operation failed, but may succeed when retried,  |
+//! |      | e.g. the peer was not connected or was busy                |
+//!
+//! External subcommands exit with their own codes, which are passed through
+//! unchanged.
+
+use std::{error, fmt, io, process};
+
+use structopt::clap;
+
+use librad::{
+    git::identities,
+    identities::git::{error::Verify, VerificationError},
+    net::{peer, protocol, replication},
+    profile,
+};
+use rad_clib::{keys, storage};
+
+use crate::{cli::args::InvalidEnv, config, identity, key, seed, sync};
+
+/// The class of a failure, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Code {
+    Success,
+    Failure,
+    Usage,
+    NotFound,
+    Verification,
+    Network,
+    Locked,
+    Config,
+    Retryable,
+}
+
+impl From<Code> for i32 {
+    fn from(code: Code) -> Self {
+        match code {
+            Code::Success => 0,
+            Code::Failure => 1,
+            Code::Usage => 2,
+            Code::NotFound => 3,
+            Code::Verification => 4,
+            Code::Network => 5,
+            Code::Locked => 6,
+            Code::Config => 7,
+            Code::Retryable => 8,
+        }
+    }
+}
+
+impl Code {
+    /// Classify `err` by the first error in its chain which is known to belong
+    /// to a class, defaulting to [`Code::Failure`].
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.chain().find_map(classify).unwrap_or(Self::Failure)
+    }
+}
+
+/// An external subcommand which did not exit successfully.
+#[derive(Debug)]
+pub struct External {
+    pub name: String,
+    pub status: process::ExitStatus,
+}
+
+impl fmt::Display for External {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed: {}", self.name, self.status)
+    }
+}
+
+impl error::Error for External {}
+
+/// Report the outcome of a `rad` invocation on stderr, and exit the process
+/// with the corresponding code.
+pub fn exit(result: anyhow::Result<()>) -> ! {
+    let err = match result {
+        Ok(()) => process::exit(Code::Success.into()),
+        Err(err) => err,
+    };
+
+    if let Some(err) = err.downcast_ref::<clap::Error>() {
+        // `--help` and `--version` are reported as errors, too.
+        if !err.use_stderr() {
+            err.exit()
+        }
+        eprintln!("{}", err.message);
+        process::exit(Code::Usage.into())
+    }
+    if let Some(External { status, .. }) = err.downcast_ref::<External>() {
+        // The subcommand is expected to have reported the failure itself.
+        process::exit(status.code().unwrap_or_else(|| Code::Failure.into()))
+    }
+
+    eprintln!("Error: {:?}", err);
+    process::exit(Code::of(&err).into())
+}
+
+fn classify(err: &(dyn error::Error + 'static)) -> Option<Code> {
+    if let Some(err) = err.downcast_ref::<clap::Error>() {
+        return Some(if err.use_stderr() {
+            Code::Usage
+        } else {
+            Code::Success
+        });
+    }
+    if err.is::<InvalidEnv>() {
+        return Some(Code::Usage);
+    }
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        return (err.kind() == io::ErrorKind::NotFound).then(|| Code::NotFound);
+    }
+    if err.is::<config::Error>() {
+        return Some(Code::Config);
+    }
+    if let Some(err) = err.downcast_ref::<seed::Error>() {
+        return match err {
+            seed::Error::InvalidSeed(_) => Some(Code::Usage),
+            seed::Error::InvalidPeer(_, err) => classify(err),
+            seed::Error::Settings(err) => classify(err),
+        };
+    }
+    if let Some(err) = err.downcast_ref::<profile::petnames::Error>() {
+        return match err {
+            profile::petnames::Error::Settings(err) => classify(err),
+            profile::petnames::Error::Io(err) => classify(err),
+            profile::petnames::Error::Version { .. } | profile::petnames::Error::Parse(..) => {
+                Some(Code::Config)
+            },
+            profile::petnames::Error::Serialize(_) => None,
+            _ => Some(Code::Usage),
+        };
+    }
+    if let Some(err) = err.downcast_ref::<profile::settings::Error>() {
+        return match err {
+            profile::settings::Error::Io(err) => classify(err),
+            profile::settings::Error::Serialize(_) | profile::settings::Error::Notify(_) => None,
+            _ => Some(Code::Config),
+        };
+    }
+    if let Some(err) = err.downcast_ref::<identity::Error>() {
+        return match err {
+            identity::Error::IndirectPerson(_)
+            | identity::Error::NotAProject
+            | identity::Error::NoChanges => Some(Code::Usage),
+            identity::Error::Identities(err) => classify(err),
+            _ => None,
+        };
+    }
+    if let Some(err) = err.downcast_ref::<profile::Error>() {
+        return matches!(err, profile::Error::DoesNotExist(_)).then(|| Code::NotFound);
+    }
+    if let Some(err) = err.downcast_ref::<rad_profile::Error>() {
+        return match err {
+            rad_profile::Error::NoActiveProfile | rad_profile::Error::NoProfile(_) => {
+                Some(Code::NotFound)
+            },
+            rad_profile::Error::Keystore(_) => Some(Code::Locked),
+            rad_profile::Error::AddKey(err) => classify(err),
+            rad_profile::Error::Profile(err) => classify(err),
+            _ => None,
+        };
+    }
+    if let Some(err) = err.downcast_ref::<storage::Error>() {
+        return match err {
+            storage::Error::Passphrase(_) | storage::Error::PromptKeys(_) => Some(Code::Locked),
+            storage::Error::SshKeys(err) => classify(err),
+            _ => None,
+        };
+    }
+    if let Some(err) = err.downcast_ref::<keys::ssh::Error>() {
+        return match err {
+            keys::ssh::Error::NoSuchKey(_)
+            | keys::ssh::Error::GetKey(_)
+            | keys::ssh::Error::SshConnect(_) => Some(Code::Locked),
+            _ => None,
+        };
+    }
+    if err.is::<keys::passphrase::Error>()
+        || matches!(
+            err.downcast_ref::<key::Error>(),
+            Some(key::Error::Keystore(_))
+        )
+    {
+        return Some(Code::Locked);
+    }
+    if let Some(err) = err.downcast_ref::<identities::Error>() {
+        return match err {
+            identities::Error::NotFound(_) => Some(Code::NotFound),
+            identities::Error::Verification(_)
+            | identities::Error::Verify(_)
+            | identities::Error::LocalId(_) => Some(Code::Verification),
+            _ => None,
+        };
+    }
+    if err.is::<VerificationError>() || err.is::<Verify>() {
+        return Some(Code::Verification);
+    }
+    if let Some(err) = err.downcast_ref::<sync::Error>() {
+        return match err {
+            sync::Error::Identities(err) => classify(err),
+            sync::Error::Replicate(err) => classify(err),
+            sync::Error::Storage(_) => None,
+            _ => Some(Code::Network),
+        };
+    }
+    if let Some(err) = err.downcast_ref::<peer::error::Replicate>() {
+        return Some(match err {
+            peer::error::Replicate::NoConnection(_) | peer::error::Replicate::Pool(_) => {
+                Code::Retryable
+            },
+            peer::error::Replicate::Replicate(err) => replicate(err),
+        });
+    }
+    if err.is::<protocol::error::Bootstrap>() {
+        return Some(Code::Network);
+    }
+    None
+}
+
+#[cfg(not(feature = "replication-v3"))]
+fn replicate(err: &replication::error::Replicate) -> Code {
+    use replication::error::Replicate;
+
+    match err {
+        // Another fetch of the same URN from the same peer is in-flight, or
+        // the storage pool is exhausted.
+        Replicate::Retrying(_) => Code::Retryable,
+        Replicate::Replication(_) => Code::Network,
+    }
+}
+
+#[cfg(feature = "replication-v3")]
+fn replicate(err: &replication::error::Replicate) -> Code {
+    use link_replication::error::Failure;
+    use replication::error::Replicate;
+
+    match err {
+        Replicate::Timeout(_) | Replicate::Pool(_) => Code::Retryable,
+        Replicate::Replicate(err) if err.is_retryable() => Code::Retryable,
+        Replicate::Replicate(err) => match err.failure {
+            Failure::Verification(_) => Code::Verification,
+            _ => Code::Network,
+        },
+    }
+}
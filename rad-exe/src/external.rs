@@ -0,0 +1,184 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Discovery of, and the environment passed to, external `rad-*`
+//! subcommands.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use librad::{
+    git::storage::ReadOnly,
+    profile::{RadHome, RAD_PROFILE},
+};
+
+use crate::cli::args::{
+    find_arg,
+    RAD_FORMAT_ARG,
+    RAD_LOG_ARG,
+    RAD_PASSPHRASE_FD_ARG,
+    RAD_PROFILE_ARG,
+    RAD_QUIET_ARG,
+    RAD_SSH_AUTH_SOCK_ARG,
+    RAD_VERBOSE_ARG,
+};
+
+/// The prefix of the executables which are treated as `rad` subcommands.
+pub const PREFIX: &str = "rad-";
+
+/// The peer id of the resolved profile.
+pub const RAD_PEER_ID: &str = "RAD_PEER_ID";
+/// The keys directory of the resolved profile.
+pub const RAD_KEYS_DIR: &str = "RAD_KEYS_DIR";
+/// The storage directory of the resolved profile.
+pub const RAD_GIT_DIR: &str = "RAD_GIT_DIR";
+/// The git include files directory of the resolved profile.
+pub const RAD_GIT_INCLUDES_DIR: &str = "RAD_GIT_INCLUDES_DIR";
+/// The collaborative object cache directory of the resolved profile.
+pub const RAD_COB_CACHE_DIR: &str = "RAD_COB_CACHE_DIR";
+
+/// An executable found on the `PATH` which can be called as `rad <name>`.
+#[derive(Debug, Serialize)]
+pub struct External {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for External {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}", self.name, self.path.display())
+    }
+}
+
+/// List the `rad-*` executables found on the `PATH`, sorted by name.
+///
+/// If the same name is found in more than one directory, the first one on the
+/// `PATH` is listed, since that is the one which gets executed.
+pub fn commands() -> Vec<External> {
+    let path = match env::var_os("PATH") {
+        Some(path) => path,
+        None => return vec![],
+    };
+
+    let mut found = BTreeMap::new();
+    for dir in env::split_paths(&path) {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let name = match file_name
+                .to_str()
+                .and_then(|name| name.strip_prefix(PREFIX))
+                .map(|name| name.strip_suffix(env::consts::EXE_SUFFIX).unwrap_or(name))
+            {
+                Some(name) if !name.is_empty() => name.to_owned(),
+                _ => continue,
+            };
+            let path = entry.path();
+            if is_executable(&path) {
+                found.entry(name).or_insert(path);
+            }
+        }
+    }
+
+    found
+        .into_iter()
+        .map(|(name, path)| External { name, path })
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The environment variables to set for an external subcommand, given its
+/// sanitised arguments (see [`crate::cli::args::sanitise_globals`]).
+///
+/// The globals are exported under the same names used to read them as
+/// fallbacks, i.e. `RAD_PROFILE`, `RAD_QUIET`, `RAD_VERBOSE`, `RAD_FORMAT`,
+/// `RAD_LOG`, `RAD_PASSPHRASE_FD`, and `RAD_SSH_AUTH_SOCK`. If the profile can
+/// be resolved, its paths and peer id are exported as well, so that subcommands
+/// do not have to resolve them again.
+pub fn environment(external: &[String]) -> Vec<(&'static str, String)> {
+    let mut env = vec![];
+
+    if flag(RAD_QUIET_ARG, external) {
+        env.push(("RAD_QUIET", "1".to_string()));
+    }
+    if flag(RAD_VERBOSE_ARG, external) {
+        env.push(("RAD_VERBOSE", "1".to_string()));
+    }
+    if let Some(format) = option(RAD_FORMAT_ARG, external) {
+        env.push(("RAD_FORMAT", format));
+    }
+    if let Some(log) = option(RAD_LOG_ARG, external) {
+        env.push(("RAD_LOG", log));
+    }
+    if let Some(fd) = option(RAD_PASSPHRASE_FD_ARG, external) {
+        env.push(("RAD_PASSPHRASE_FD", fd));
+    }
+    if let Some(sock) = option(RAD_SSH_AUTH_SOCK_ARG, external) {
+        env.push(("RAD_SSH_AUTH_SOCK", sock));
+    }
+
+    let profile_id = option(RAD_PROFILE_ARG, external);
+    let profile = match profile_id.as_deref().map(str::parse).transpose() {
+        Ok(id) => rad_profile::get(RadHome::default(), id).ok().flatten(),
+        Err(_) => None,
+    };
+    match profile {
+        Some(profile) => {
+            let paths = profile.paths();
+            env.push((RAD_PROFILE, profile.id().to_string()));
+            env.push((RAD_KEYS_DIR, display(paths.keys_dir())));
+            env.push((RAD_GIT_DIR, display(paths.git_dir())));
+            env.push((RAD_GIT_INCLUDES_DIR, display(paths.git_includes_dir())));
+            env.push((RAD_COB_CACHE_DIR, display(paths.cob_cache_dir())));
+            if let Ok(storage) = ReadOnly::open(paths) {
+                env.push((RAD_PEER_ID, storage.peer_id().to_string()));
+            }
+        },
+        // The subcommand may well be the one creating the profile, so this is
+        // not an error.
+        None => {
+            if let Some(id) = profile_id {
+                env.push((RAD_PROFILE, id));
+            }
+        },
+    }
+
+    env
+}
+
+fn option(arg: &str, external: &[String]) -> Option<String> {
+    find_arg(arg, external).and_then(|index| external.get(index + 1).cloned())
+}
+
+fn flag(arg: &str, external: &[String]) -> bool {
+    find_arg(arg, external).is_some()
+}
+
+fn display(path: &Path) -> String {
+    path.display().to_string()
+}
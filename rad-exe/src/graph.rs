@@ -0,0 +1,292 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Export the social graph found in the local storage.
+//!
+//! The graph is made up of peers and identities, connected by:
+//!
+//! * the tracking entries of the local peer, see [`Label::Tracks`]
+//! * the delegations of the identity documents, see [`Label::Delegates`]
+//! * the remotes advertised in the signed refs of the local peer, and
+//!   transitively of the remotes, see [`Label::Remote`]
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use either::Either;
+use serde::Serialize;
+use thiserror::Error;
+
+use librad::{
+    git::{
+        identities::{self, SomeIdentity},
+        refs::{stored, Refs, Remotes},
+        storage::{self, ReadOnly},
+        tracking,
+        Urn,
+    },
+    PeerId,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Storage(#[from] storage::read::Error),
+
+    #[error(transparent)]
+    Sigrefs(#[from] stored::Error),
+
+    #[error(transparent)]
+    Tracked(#[from] tracking::error::Tracked),
+}
+
+/// A vertex of the [`Graph`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Node {
+    Peer {
+        id: PeerId,
+        /// Whether this is the peer of the local storage.
+        local: bool,
+    },
+    Identity {
+        urn: Urn,
+        /// Either `person` or `project`, or `None` if the identity was not
+        /// replicated (yet).
+        kind: Option<&'static str>,
+        /// The name found in the identity payload.
+        name: Option<String>,
+    },
+}
+
+impl Node {
+    fn id(&self) -> String {
+        match self {
+            Self::Peer { id, .. } => id.to_string(),
+            Self::Identity { urn, .. } => urn.to_string(),
+        }
+    }
+}
+
+/// What an [`Edge`] means.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum Label {
+    /// The local peer tracks a peer, or the identity itself, for `urn`.
+    Tracks { urn: Urn },
+    /// A key or person is a delegate of an identity.
+    Delegates,
+    /// A peer advertises a remote for `urn` in its signed refs.
+    Remote { urn: Urn },
+}
+
+/// A directed edge between the [`Node`]s with the ids `from` and `to`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    #[serde(flatten)]
+    pub label: Label,
+}
+
+/// The social graph of the local storage.
+///
+/// The [`fmt::Display`] implementation renders the graph in the DOT language
+/// understood by graphviz, e.g. `rad graph | dot -Tsvg > graph.svg`.
+#[derive(Debug, Default, Serialize)]
+pub struct Graph {
+    /// The nodes, by id. The id of a peer is its [`PeerId`], the id of an
+    /// identity is its [`Urn`].
+    pub nodes: BTreeMap<String, Node>,
+    pub edges: BTreeSet<Edge>,
+}
+
+impl Graph {
+    /// Add `node`, unless a node with the same id exists already.
+    fn node(&mut self, node: Node) -> String {
+        let id = node.id();
+        self.nodes.entry(id.clone()).or_insert(node);
+        id
+    }
+
+    fn peer(&mut self, id: PeerId) -> String {
+        self.node(Node::Peer { id, local: false })
+    }
+
+    fn edge(&mut self, from: String, to: String, label: Label) {
+        self.edges.insert(Edge { from, to, label });
+    }
+}
+
+impl fmt::Display for Graph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "digraph radicle {{")?;
+        for (id, node) in &self.nodes {
+            match node {
+                Node::Peer { local, .. } => writeln!(
+                    f,
+                    "  {} [shape={}];",
+                    quote(id),
+                    if *local { "doublecircle" } else { "ellipse" }
+                )?,
+                Node::Identity { kind, name, .. } => writeln!(
+                    f,
+                    "  {} [shape={}, label={}];",
+                    quote(id),
+                    match kind {
+                        Some("person") => "house",
+                        _ => "box",
+                    },
+                    quote(&match name {
+                        Some(name) => format!("{}\n{}", name, id),
+                        None => id.clone(),
+                    })
+                )?,
+            }
+        }
+        for Edge { from, to, label } in &self.edges {
+            let label = match label {
+                Label::Tracks { urn } => format!("tracks\n{}", urn),
+                Label::Delegates => "delegates".to_owned(),
+                Label::Remote { urn } => format!("remote\n{}", urn),
+            };
+            writeln!(
+                f,
+                "  {} -> {} [label={}];",
+                quote(from),
+                quote(to),
+                quote(&label)
+            )?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Quote `s` as a DOT identifier.
+fn quote(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+/// Build the graph of the namespace of `urn`, or of all namespaces in the
+/// storage if `urn` is `None`.
+pub fn graph<S>(storage: &S, urn: Option<&Urn>) -> Result<Graph, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let mut graph = Graph::default();
+    let local = graph.node(Node::Peer {
+        id: *storage.peer_id(),
+        local: true,
+    });
+
+    let mut urns = BTreeSet::new();
+    match urn {
+        Some(urn) => {
+            if let Some(identity) = identities::any::get(storage, urn)? {
+                add_identity(&mut graph, identity);
+            }
+            urns.insert(urn.clone());
+        },
+        None => {
+            for identity in identities::any::list(storage)? {
+                let identity = identity?;
+                urns.insert(identity.urn());
+                add_identity(&mut graph, identity);
+            }
+        },
+    }
+
+    for tracked in tracking::tracked(storage, urn)? {
+        let tracked = tracked?;
+        let urn = tracked.urn().clone();
+        let to = match tracked.peer_id() {
+            Some(peer) => graph.peer(peer),
+            None => graph.node(Node::Identity {
+                urn: urn.clone(),
+                kind: None,
+                name: None,
+            }),
+        };
+        graph.edge(local.clone(), to, Label::Tracks { urn: urn.clone() });
+        urns.insert(urn);
+    }
+
+    for urn in urns {
+        if let Some(refs) = Refs::load(storage, &urn, None)? {
+            add_remotes(&mut graph, &urn, local.clone(), &refs.remotes);
+        }
+    }
+
+    Ok(graph)
+}
+
+fn add_identity(graph: &mut Graph, identity: SomeIdentity) {
+    let urn = identity.urn();
+    let id = urn.to_string();
+    match identity {
+        SomeIdentity::Person(person) => {
+            graph.nodes.insert(
+                id.clone(),
+                Node::Identity {
+                    urn,
+                    kind: Some("person"),
+                    name: Some(person.subject().name.to_string()),
+                },
+            );
+            for key in person.delegations().iter() {
+                let from = graph.peer(PeerId::from(*key));
+                graph.edge(from, id.clone(), Label::Delegates);
+            }
+        },
+        SomeIdentity::Project(project) => {
+            graph.nodes.insert(
+                id.clone(),
+                Node::Identity {
+                    urn,
+                    kind: Some("project"),
+                    name: Some(project.subject().name.to_string()),
+                },
+            );
+            for delegation in project.delegations().iter() {
+                let from = match delegation {
+                    Either::Left(key) => graph.peer(PeerId::from(*key)),
+                    Either::Right(person) => {
+                        add_identity(graph, SomeIdentity::Person(person.clone()));
+                        person.urn().to_string()
+                    },
+                };
+                graph.edge(from, id.clone(), Label::Delegates);
+            }
+        },
+        _ => {
+            graph.node(Node::Identity {
+                urn,
+                kind: None,
+                name: None,
+            });
+        },
+    }
+}
+
+/// Add an edge from `from` to each of the `remotes`, and recursively from each
+/// remote to its own remotes.
+fn add_remotes(graph: &mut Graph, urn: &Urn, from: String, remotes: &Remotes<PeerId>) {
+    for (peer, remotes) in remotes.iter() {
+        let to = graph.peer(*peer);
+        graph.edge(from.clone(), to.clone(), Label::Remote { urn: urn.clone() });
+        add_remotes(graph, urn, to, remotes);
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Callbacks of the git hooks installed into working copies, see
+//! [`librad::git::local::hooks`].
+
+use std::convert::TryFrom as _;
+
+use thiserror::Error;
+
+use librad::{
+    git::{
+        local::{hooks, transport, url::LocalUrl},
+        types::{
+            remote::{self, LocalPushspec, Remote},
+            Force,
+        },
+        Urn,
+    },
+    git_ext::RefLike,
+    reflike,
+};
+
+/// The command the installed hooks call back into.
+pub const COMMAND: &str = "rad hooks run";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("`{0}` is not a working copy of a Radicle project, it has no `rad` remote")]
+    NotLinked(String),
+
+    #[error(transparent)]
+    Hooks(#[from] hooks::Error),
+
+    #[error(transparent)]
+    Remote(#[from] remote::FindError),
+
+    #[error(transparent)]
+    Transport(#[from] transport::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// The `rad` remote of the working copy `repo`, if any.
+pub fn rad_remote(repo: &git2::Repository) -> Result<Option<Remote<LocalUrl>>, Error> {
+    Ok(Remote::<LocalUrl>::find(repo, reflike!("rad"))?)
+}
+
+/// Install the hooks into the working copy `repo`, which must have a `rad`
+/// remote.
+pub fn install(repo: &git2::Repository, force: bool) -> Result<(Urn, Vec<String>), Error> {
+    let urn = rad_remote(repo)?
+        .ok_or_else(|| Error::NotLinked(repo.path().display().to_string()))?
+        .url
+        .urn;
+    let installed = hooks::install(repo, COMMAND, force)?;
+    Ok((
+        urn,
+        installed
+            .into_iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+    ))
+}
+
+/// Publish the checked out branch of `repo` to the monorepo via the `rad`
+/// remote, which also updates the signed refs of the project.
+///
+/// Returns the published refs, which are empty if `HEAD` is detached.
+pub fn publish(
+    repo: &git2::Repository,
+    mut rad: Remote<LocalUrl>,
+    settings: transport::Settings,
+) -> Result<Vec<RefLike>, Error> {
+    let head = repo.head()?;
+    let branch = match head.name() {
+        Some(name) if head.is_branch() => RefLike::try_from(name).ok(),
+        _ => None,
+    };
+    match branch {
+        None => Ok(vec![]),
+        Some(branch) => Ok(rad
+            .push(
+                settings,
+                repo,
+                LocalPushspec::Matching {
+                    pattern: branch.into(),
+                    force: Force::False,
+                },
+            )?
+            .collect()),
+    }
+}
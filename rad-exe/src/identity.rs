@@ -0,0 +1,299 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Create and update identities field by field, rather than from the JSON
+//! payloads taken by `rad identities`.
+//!
+//! The identity documents are signed with the key of the storage, and the
+//! `rad/id` ref of the identity is updated. Creating a person can also set it
+//! as the default local identity, i.e. the one `rad/self` of new projects
+//! points to.
+
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use either::Either;
+use serde::Serialize;
+use thiserror::Error;
+
+use librad::{
+    crypto::BoxedSigner,
+    git::{
+        identities::{self, SomeIdentity},
+        storage::Storage,
+        Urn,
+    },
+    identities::{
+        git::Revision,
+        payload::{self, KeyOrUrn, SomePayload},
+    },
+    paths::Paths,
+    PeerId,
+    PublicKey,
+};
+use rad_identities::{person, project};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Local(#[from] rad_identities::local::Error),
+
+    #[error(transparent)]
+    Person(#[from] person::Error),
+
+    #[error(transparent)]
+    Project(#[from] project::Error),
+
+    #[error("a person can only be delegated to by keys, but `{0}` is a URN")]
+    IndirectPerson(Urn),
+
+    #[error("only projects have a default branch and a description")]
+    NotAProject,
+
+    #[error("nothing to update")]
+    NoChanges,
+
+    #[error("the identity `{0}` found is not recognised/supported")]
+    UnknownIdentity(Urn),
+}
+
+/// A delegate of an identity, given either as a peer id or as the URN of a
+/// person.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Delegate {
+    Key(PeerId),
+    Person(Urn),
+}
+
+impl From<Delegate> for KeyOrUrn<Revision> {
+    fn from(delegate: Delegate) -> Self {
+        match delegate {
+            Delegate::Key(peer_id) => Either::Left(*peer_id.as_public_key()).into(),
+            Delegate::Person(urn) => Either::Right(urn).into(),
+        }
+    }
+}
+
+impl fmt::Display for Delegate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(peer_id) => write!(f, "{}", peer_id),
+            Self::Person(urn) => write!(f, "{}", urn),
+        }
+    }
+}
+
+impl FromStr for Delegate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<Urn>() {
+            Ok(urn) => Ok(Self::Person(urn)),
+            Err(urn_err) => s.parse::<PeerId>().map(Self::Key).map_err(|key_err| {
+                format!(
+                    "could not parse URN: \"{}\", nor peer id: \"{}\"",
+                    urn_err, key_err
+                )
+            }),
+        }
+    }
+}
+
+/// The changes made by [`update`]. Fields which are `None` are kept as they
+/// are.
+#[derive(Debug, Default)]
+pub struct Edit {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub default_branch: Option<String>,
+    /// Delegates to add to the existing ones.
+    pub delegates: Vec<Delegate>,
+}
+
+/// The URN and payload of a created or updated identity.
+#[derive(Debug, Serialize)]
+pub struct Published {
+    pub urn: Urn,
+    pub payload: SomePayload,
+}
+
+impl From<SomeIdentity> for Published {
+    fn from(identity: SomeIdentity) -> Self {
+        Self {
+            urn: identity.urn(),
+            payload: identity.payload(),
+        }
+    }
+}
+
+impl fmt::Display for Published {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.urn)
+    }
+}
+
+/// Create a person called `name`, delegating to the key of `storage` and any
+/// additional `delegates`.
+///
+/// If `default` is set, the person is set as the default local identity.
+pub fn create_person(
+    storage: &Storage,
+    paths: &Paths,
+    signer: BoxedSigner,
+    name: String,
+    delegates: Vec<Delegate>,
+    default: bool,
+) -> anyhow::Result<Published> {
+    let delegates = keys(delegates)?;
+    let person = person::create::<serde_json::Value>(
+        storage,
+        paths.clone(),
+        signer,
+        payload::Person { name: name.into() },
+        vec![],
+        delegates,
+        person::Creation::New { path: None },
+    )?;
+    if default {
+        let local = identities::local::load(storage, person.urn())?
+            .ok_or_else(|| identities::Error::NotFound(person.urn()))?;
+        rad_identities::local::set(storage, local)?;
+    }
+    Ok(SomeIdentity::Person(person).into())
+}
+
+/// Create a project called `name`, delegating to the local identity `whoami`,
+/// or the default local identity, and any additional `delegates`.
+#[allow(clippy::too_many_arguments)]
+pub fn create_project(
+    storage: &Storage,
+    paths: &Paths,
+    signer: BoxedSigner,
+    whoami: Option<Urn>,
+    name: String,
+    description: Option<String>,
+    default_branch: Option<String>,
+    delegates: Vec<Delegate>,
+) -> anyhow::Result<Published> {
+    let project = project::create::<serde_json::Value>(
+        storage,
+        paths.clone(),
+        signer,
+        whoami.into(),
+        delegates.into_iter().map(KeyOrUrn::from).collect(),
+        payload::Project {
+            name: name.into(),
+            description: description.map(Into::into),
+            default_branch: default_branch.map(Into::into),
+        },
+        vec![],
+        project::Creation::New { path: None },
+    )?;
+    Ok(SomeIdentity::Project(project).into())
+}
+
+/// Publish a new revision of the identity `urn`, applying `edit` to the
+/// latest verified revision.
+///
+/// The `rad/self` of the identity is set to `whoami`, if given.
+pub fn update(
+    storage: &Storage,
+    urn: &Urn,
+    whoami: Option<Urn>,
+    edit: Edit,
+) -> Result<Published, Error> {
+    if edit.name.is_none()
+        && edit.description.is_none()
+        && edit.default_branch.is_none()
+        && edit.delegates.is_empty()
+    {
+        return Err(Error::NoChanges);
+    }
+
+    let identity = identities::any::get(storage, urn)?
+        .ok_or_else(|| identities::Error::NotFound(urn.clone()))?;
+    let updated = match identity {
+        SomeIdentity::Person(_) => {
+            if edit.description.is_some() || edit.default_branch.is_some() {
+                return Err(Error::NotAProject);
+            }
+            let old = identities::person::verify(storage, urn)?
+                .ok_or_else(|| identities::Error::NotFound(urn.clone()))?
+                .into_inner();
+            let payload = edit.name.map(|name| payload::Person { name: name.into() });
+            let delegates = if edit.delegates.is_empty() {
+                None
+            } else {
+                let mut delegates = old.delegations().iter().copied().collect::<BTreeSet<_>>();
+                delegates.extend(keys(edit.delegates)?);
+                Some(delegates.into_iter())
+            };
+            SomeIdentity::Person(person::update(
+                storage,
+                urn,
+                whoami,
+                payload,
+                vec![],
+                delegates,
+            )?)
+        },
+        SomeIdentity::Project(_) => {
+            let old = identities::project::verify(storage, urn)?
+                .ok_or_else(|| identities::Error::NotFound(urn.clone()))?
+                .into_inner();
+            let subject = &old.payload().subject;
+            let payload = payload::Project {
+                name: edit
+                    .name
+                    .map(Into::into)
+                    .unwrap_or_else(|| subject.name.clone()),
+                description: edit
+                    .description
+                    .map(Into::into)
+                    .or_else(|| subject.description.clone()),
+                default_branch: edit
+                    .default_branch
+                    .map(Into::into)
+                    .or_else(|| subject.default_branch.clone()),
+            };
+            let delegates = if edit.delegates.is_empty() {
+                BTreeSet::new()
+            } else {
+                old.delegations()
+                    .iter()
+                    .map(|delegation| {
+                        delegation
+                            .map_left(|key| *key)
+                            .map_right(|person| person.urn())
+                            .into()
+                    })
+                    .chain(edit.delegates.into_iter().map(KeyOrUrn::from))
+                    .collect()
+            };
+            SomeIdentity::Project(project::update(
+                storage,
+                urn,
+                whoami,
+                Some(payload),
+                vec![],
+                delegates,
+            )?)
+        },
+        _ => return Err(Error::UnknownIdentity(urn.clone())),
+    };
+    Ok(updated.into())
+}
+
+fn keys(delegates: Vec<Delegate>) -> Result<Vec<PublicKey>, Error> {
+    delegates
+        .into_iter()
+        .map(|delegate| match delegate {
+            Delegate::Key(peer_id) => Ok(*peer_id.as_public_key()),
+            Delegate::Person(urn) => Err(Error::IndirectPerson(urn)),
+        })
+        .collect()
+}
@@ -0,0 +1,204 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Inspect the identity and tracking state of a [`Urn`].
+
+use std::{collections::BTreeMap, fmt};
+
+use serde::Serialize;
+use thiserror::Error;
+
+use librad::{
+    git::{
+        identities::{self, SomeIdentity},
+        storage::{self, ReadOnly, ReadOnlyStorage as _},
+        tracking,
+        types::{Namespace, Reference, RefsCategory},
+        Urn,
+    },
+    git_ext::RefspecPattern,
+    identities::payload::SomePayload,
+    PeerId,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Storage(#[from] storage::read::Error),
+
+    #[error(transparent)]
+    Tracked(#[from] tracking::error::TrackedPeers),
+
+    #[error("the identity `{0}` found is not recognised/supported")]
+    UnknownIdentity(Urn),
+}
+
+/// A delegation of an identity document.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Delegation {
+    /// A key delegating directly.
+    Key(PeerId),
+    /// A person delegating by means of the keys of their identity.
+    Person { urn: Urn, keys: Vec<PeerId> },
+}
+
+/// The verified identity document, tracking relationships, and `rad/*` refs of
+/// a [`Urn`].
+#[derive(Debug, Serialize)]
+pub struct Inspection {
+    pub urn: Urn,
+    /// Either `person` or `project`.
+    pub kind: &'static str,
+    pub revision: String,
+    pub content_id: String,
+    pub payload: SomePayload,
+    pub delegations: Vec<Delegation>,
+    /// The peers tracked for the [`Urn`].
+    pub tracked: Vec<PeerId>,
+    /// The local `rad/*` refs of the [`Urn`], mapped to what they point to.
+    pub rad_refs: BTreeMap<String, String>,
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "urn: {}", self.urn)?;
+        writeln!(f, "kind: {}", self.kind)?;
+        writeln!(f, "revision: {}", self.revision)?;
+        writeln!(f, "content id: {}", self.content_id)?;
+        match serde_json::to_string_pretty(&self.payload) {
+            Ok(payload) => writeln!(f, "payload: {}", payload)?,
+            Err(_) => writeln!(f, "payload: {:?}", self.payload)?,
+        }
+        writeln!(f, "delegations:")?;
+        for delegation in &self.delegations {
+            match delegation {
+                Delegation::Key(key) => writeln!(f, "  {}", key)?,
+                Delegation::Person { urn, keys } => {
+                    writeln!(f, "  {}", urn)?;
+                    for key in keys {
+                        writeln!(f, "    {}", key)?;
+                    }
+                },
+            }
+        }
+        writeln!(f, "tracked:")?;
+        for peer in &self.tracked {
+            writeln!(f, "  {}", peer)?;
+        }
+        writeln!(f, "refs:")?;
+        for (name, target) in &self.rad_refs {
+            writeln!(f, "  {} {}", target, name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Inspect the identity found at `urn`.
+///
+/// The identity is verified before being returned, so an identity which fails
+/// verification results in an error.
+pub fn inspect<S>(storage: &S, urn: &Urn) -> Result<Option<Inspection>, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let identity = match identities::any::get(storage, urn)? {
+        None => return Ok(None),
+        Some(identity) => identity,
+    };
+    let payload = identity.payload();
+
+    let (kind, revision, content_id, delegations) = match identity {
+        SomeIdentity::Person(_) => {
+            let person = identities::person::verify(storage, urn)?
+                .ok_or_else(|| identities::Error::NotFound(urn.clone()))?
+                .into_inner();
+            let delegations = person
+                .delegations()
+                .iter()
+                .map(|key| Delegation::Key(PeerId::from(*key)))
+                .collect();
+            (
+                "person",
+                person.revision.to_string(),
+                person.content_id.to_string(),
+                delegations,
+            )
+        },
+        SomeIdentity::Project(_) => {
+            let project = identities::project::verify(storage, urn)?
+                .ok_or_else(|| identities::Error::NotFound(urn.clone()))?
+                .into_inner();
+            let delegations = project
+                .delegations()
+                .iter()
+                .map(|delegation| {
+                    delegation.either(
+                        |key| Delegation::Key(PeerId::from(*key)),
+                        |person| Delegation::Person {
+                            urn: person.urn(),
+                            keys: person
+                                .delegations()
+                                .iter()
+                                .map(|key| PeerId::from(*key))
+                                .collect(),
+                        },
+                    )
+                })
+                .collect();
+            (
+                "project",
+                project.revision.to_string(),
+                project.content_id.to_string(),
+                delegations,
+            )
+        },
+        _ => return Err(Error::UnknownIdentity(urn.clone())),
+    };
+
+    let tracked = tracking::tracked_peers(storage, Some(urn))?.collect::<Result<_, _>>()?;
+    let rad_refs = rad_refs(storage, urn)?;
+
+    Ok(Some(Inspection {
+        urn: urn.clone(),
+        kind,
+        revision,
+        content_id,
+        payload,
+        delegations,
+        tracked,
+        rad_refs,
+    }))
+}
+
+fn rad_refs(storage: &ReadOnly, urn: &Urn) -> Result<BTreeMap<String, String>, Error> {
+    let rad = Reference {
+        remote: None,
+        category: RefsCategory::Rad,
+        name: "*"
+            .parse::<RefspecPattern>()
+            .expect("`*` is a valid pattern"),
+        namespace: Some(Namespace::from(urn)),
+    };
+    let mut refs = BTreeMap::new();
+    for r in storage.references(&rad)? {
+        let r = r?;
+        let name = match r.name() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let target = match (r.target(), r.symbolic_target()) {
+            (Some(oid), _) => oid.to_string(),
+            (None, Some(sym)) => sym.to_string(),
+            (None, None) => continue,
+        };
+        refs.insert(name, target);
+    }
+    Ok(refs)
+}
@@ -0,0 +1,368 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Inspect, export, and manage the key of a [`Profile`].
+
+use std::{
+    error,
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use librad::{
+    crypto::{
+        keystore::{crypto::Crypto, file, FileStorage, Keystore as _},
+        IntoSecretKeyError,
+    },
+    git::storage::{self, read, ReadOnly, Storage},
+    profile::Profile,
+    PeerId,
+    PublicKey,
+    SecretKey,
+};
+use rad_clib::keys::{self, LIBRAD_KEY_FILE};
+
+pub mod pgp;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("malformed key creation time in {0}")]
+    Created(PathBuf),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Keystore(Box<dyn error::Error + Send + Sync + 'static>),
+
+    #[error(transparent)]
+    ReadOnly(#[from] read::error::Init),
+
+    #[error(transparent)]
+    Storage(#[from] storage::error::Init),
+}
+
+impl<C> From<file::Error<C, IntoSecretKeyError>> for Error
+where
+    C: fmt::Debug + fmt::Display + Send + Sync + 'static,
+{
+    fn from(err: file::Error<C, IntoSecretKeyError>) -> Self {
+        Self::Keystore(Box::new(err))
+    }
+}
+
+/// The public parts of the key of a [`Profile`].
+#[derive(Debug, Serialize)]
+pub struct Key {
+    pub peer_id: PeerId,
+    pub public_key: PublicKey,
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "peer id: {}", self.peer_id)?;
+        write!(f, "public key: {}", self.public_key)
+    }
+}
+
+/// The formats the public key can be exported in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// The OpenSSH `authorized_keys` format.
+    Ssh,
+    /// An ASCII armored OpenPGP public key block.
+    Pgp,
+}
+
+impl ExportFormat {
+    pub fn variants() -> &'static [&'static str] {
+        &["ssh", "pgp"]
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ssh => write!(f, "ssh"),
+            Self::Pgp => write!(f, "pgp"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ssh" => Ok(Self::Ssh),
+            "pgp" => Ok(Self::Pgp),
+            _ => Err("unknown export format, expected `ssh` or `pgp`"),
+        }
+    }
+}
+
+/// The outcome of [`rotate`].
+#[derive(Debug, Serialize)]
+pub struct Rotated {
+    pub previous: PeerId,
+    pub peer_id: PeerId,
+    /// Where the previous key was kept.
+    pub backup: PathBuf,
+}
+
+impl fmt::Display for Rotated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "rotated key {} to {}", self.previous, self.peer_id)?;
+        write!(f, "the previous key was kept at {}", self.backup.display())
+    }
+}
+
+/// Get the public parts of the key of `profile`. This does not require
+/// unlocking the key.
+pub fn show(profile: &Profile) -> Result<Key, Error> {
+    let storage = ReadOnly::open(profile.paths())?;
+    let peer_id = *storage.peer_id();
+    Ok(Key {
+        peer_id,
+        public_key: *peer_id.as_public_key(),
+    })
+}
+
+/// Encode `peer_id` as an OpenSSH public key, in the format used by
+/// `authorized_keys`.
+pub fn ssh_public_key(peer_id: &PeerId) -> String {
+    const KEY_TYPE: &str = "ssh-ed25519";
+
+    let key = peer_id.as_public_key().as_ref();
+    let mut blob = Vec::with_capacity(4 + KEY_TYPE.len() + 4 + key.len());
+    blob.extend_from_slice(&(KEY_TYPE.len() as u32).to_be_bytes());
+    blob.extend_from_slice(KEY_TYPE.as_bytes());
+    blob.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    blob.extend_from_slice(key);
+    format!("{} {} rad:{}", KEY_TYPE, base64::encode(blob), peer_id)
+}
+
+/// Encode the key of `profile`, unlocked using `crypto`, as an ASCII armored
+/// OpenPGP public key block, certified for `user_id`.
+///
+/// The creation time of the OpenPGP key is the creation time of the key, see
+/// [`created`].
+pub fn pgp_public_key<C>(profile: &Profile, crypto: C, user_id: &str) -> Result<String, Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    let created = created(profile)?;
+    let key = keys::file_storage(profile, crypto).get_key()?.secret_key;
+    Ok(pgp::armored_public_key(&key, user_id, created))
+}
+
+/// The time the key of `profile` was created, in seconds since the epoch.
+///
+/// The time is recorded next to the key storage when rotating the key. Keys
+/// which were created otherwise, eg. by `rad profile create`, get the
+/// modification time of their key storage recorded when this is first called,
+/// so that the time doesn't change when the key is re-encrypted.
+pub fn created(profile: &Profile) -> Result<u32, Error> {
+    let path = created_file(profile);
+    match fs::read_to_string(&path) {
+        Ok(secs) => secs.trim().parse().map_err(|_| Error::Created(path)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let secs = fs::metadata(key_file(profile))?
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs() as u32)
+                .unwrap_or_default();
+            record_created(profile, secs)?;
+            Ok(secs)
+        },
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Re-encrypt the key of `profile`, unlocking it using `old` and locking it
+/// using `new`.
+pub fn reencrypt<C, D>(profile: &Profile, old: C, new: D) -> Result<PeerId, Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+    D: Crypto,
+    D::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    D::SecretBox: Serialize + DeserializeOwned,
+{
+    let key = keys::file_storage(profile, old).get_key()?.secret_key;
+    let peer_id = PeerId::from(&key);
+    // Make sure the creation time is recorded before the key storage is
+    // rewritten.
+    created(profile)?;
+    replace(&key_file(profile), key, new)?;
+    Ok(peer_id)
+}
+
+/// Replace the key of `profile`, unlocking the current one using `old`, and
+/// locking the newly generated one using `new`.
+///
+/// The current key is kept next to the key storage, suffixed with its peer
+/// id, and the storage of `profile` is re-keyed, see [`Storage::rotate_key`].
+pub fn rotate<C, D>(profile: &Profile, old: C, new: D) -> Result<Rotated, Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+    D: Crypto,
+    D::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    D::SecretBox: Serialize + DeserializeOwned,
+{
+    rotate_to(profile, old, new, SecretKey::new())
+}
+
+/// Like [`rotate`], but replace the current key with the given `key` instead
+/// of a newly generated one.
+pub fn rotate_to<C, D>(profile: &Profile, old: C, new: D, key: SecretKey) -> Result<Rotated, Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+    D: Crypto,
+    D::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    D::SecretBox: Serialize + DeserializeOwned,
+{
+    let staged = stage(profile, key, new)?;
+    rotate_staged(profile, old, staged)
+}
+
+/// A key persisted next to the key storage of a [`Profile`], which is not
+/// in use yet. See [`stage`].
+#[derive(Debug)]
+pub struct Staged {
+    path: PathBuf,
+    key: SecretKey,
+}
+
+impl Staged {
+    pub fn peer_id(&self) -> PeerId {
+        PeerId::from(&self.key)
+    }
+
+    /// Remove the staged key, eg. if rotating to it was aborted.
+    pub fn discard(self) -> Result<(), Error> {
+        Ok(fs::remove_file(&self.path)?)
+    }
+}
+
+/// Persist `key`, locked using `crypto`, next to the key storage of
+/// `profile`, without putting it into use.
+///
+/// This allows to hand out the public part of `key` before rotating to it
+/// using [`rotate_staged`], without risking to lose the secret part if the
+/// rotation does not happen.
+pub fn stage<C>(profile: &Profile, key: SecretKey, crypto: C) -> Result<Staged, Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    let path = key_file(profile).with_file_name(format!("{}.staged", LIBRAD_KEY_FILE));
+    write(&path, key.clone(), crypto)?;
+    Ok(Staged { path, key })
+}
+
+/// Like [`rotate`], but replace the current key with the `staged` one.
+///
+/// If re-keying the storage fails, the previous key is put back in place, so
+/// that the key storage and the storage keep agreeing on the key in use.
+pub fn rotate_staged<C>(profile: &Profile, old: C, staged: Staged) -> Result<Rotated, Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    let path = key_file(profile);
+    let previous = PeerId::from(keys::file_storage(profile, old).get_key()?.secret_key);
+    let backup = path.with_file_name(format!("{}.{}", LIBRAD_KEY_FILE, previous));
+    fs::copy(&path, &backup)?;
+
+    let peer_id = staged.peer_id();
+    fs::rename(&staged.path, &path)?;
+    if let Err(e) = Storage::rotate_key(profile.paths(), staged.key) {
+        let tmp = path.with_file_name(format!("{}.new", LIBRAD_KEY_FILE));
+        if let Err(err) = fs::copy(&backup, &tmp).and_then(|_| fs::rename(&tmp, &path)) {
+            tracing::error!(
+                ?err,
+                backup = %backup.display(),
+                "failed to restore the previous key"
+            );
+        }
+        return Err(e.into());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() as u32)
+        .unwrap_or_default();
+    record_created(profile, now)?;
+
+    Ok(Rotated {
+        previous,
+        peer_id,
+        backup,
+    })
+}
+
+fn key_file(profile: &Profile) -> PathBuf {
+    profile.paths().keys_dir().join(LIBRAD_KEY_FILE)
+}
+
+fn created_file(profile: &Profile) -> PathBuf {
+    key_file(profile).with_file_name(format!("{}.created", LIBRAD_KEY_FILE))
+}
+
+/// Atomically record `secs` as the creation time of the key of `profile`.
+fn record_created(profile: &Profile, secs: u32) -> Result<(), Error> {
+    let path = created_file(profile);
+    let tmp = path.with_extension("created.tmp");
+    fs::write(&tmp, format!("{}\n", secs))?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// Atomically replace the key storage at `path` with one containing `key`,
+/// locked using `crypto`.
+fn replace<C>(path: &Path, key: SecretKey, crypto: C) -> Result<(), Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    let tmp = path.with_file_name(format!("{}.new", LIBRAD_KEY_FILE));
+    write(&tmp, key, crypto)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Write a key storage containing `key`, locked using `crypto`, to `path`,
+/// replacing any existing one.
+fn write<C>(path: &Path, key: SecretKey, crypto: C) -> Result<(), Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    let mut store: FileStorage<C, PublicKey, SecretKey, ()> = FileStorage::new(path, crypto);
+    store.put_key(key)?;
+    Ok(())
+}
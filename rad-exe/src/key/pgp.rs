@@ -0,0 +1,200 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! A minimal OpenPGP encoding of an Ed25519 key, as a transferable public key
+//! consisting of the primary key, a single user id, and its self-signature.
+//!
+//! See [RFC 4880] and, for EdDSA, [draft-ietf-openpgp-rfc4880bis].
+//!
+//! [RFC 4880]: https://datatracker.ietf.org/doc/html/rfc4880
+//! [draft-ietf-openpgp-rfc4880bis]: https://datatracker.ietf.org/doc/html/draft-ietf-openpgp-rfc4880bis-10
+
+use sha1::Sha1;
+use sha2::{Digest as _, Sha256};
+
+use librad::SecretKey;
+
+/// The OID of the Ed25519 curve, as used by OpenPGP.
+const ED25519_OID: [u8; 9] = [0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+const VERSION: u8 = 4;
+const ALGORITHM_EDDSA: u8 = 22;
+const HASH_SHA256: u8 = 8;
+const SIG_POSITIVE_CERTIFICATION: u8 = 0x13;
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+
+const SUBPACKET_CREATION_TIME: u8 = 2;
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_KEY_FLAGS: u8 = 27;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+/// The key may be used to certify other keys, and to sign data.
+const KEY_FLAGS_CERTIFY_SIGN: u8 = 0x01 | 0x02;
+
+/// Encode the public key of `key`, certified for `user_id`, as an ASCII
+/// armored OpenPGP public key block.
+///
+/// The `created` time, in seconds since the Unix epoch, is part of the key
+/// fingerprint, so it should be the same for every export of the same key.
+pub fn armored_public_key(key: &SecretKey, user_id: &str, created: u32) -> String {
+    armor(&public_key(key, user_id, created))
+}
+
+fn public_key(key: &SecretKey, user_id: &str, created: u32) -> Vec<u8> {
+    let public = key_body(key, created);
+    let fingerprint = Sha1::digest(&hashed_key(&public));
+
+    let mut hashed = vec![
+        VERSION,
+        SIG_POSITIVE_CERTIFICATION,
+        ALGORITHM_EDDSA,
+        HASH_SHA256,
+    ];
+    let mut subpackets = vec![];
+    subpacket(
+        &mut subpackets,
+        SUBPACKET_CREATION_TIME,
+        &created.to_be_bytes(),
+    );
+    subpacket(
+        &mut subpackets,
+        SUBPACKET_KEY_FLAGS,
+        &[KEY_FLAGS_CERTIFY_SIGN],
+    );
+    subpacket(&mut subpackets, SUBPACKET_ISSUER_FINGERPRINT, &{
+        let mut fpr = vec![VERSION];
+        fpr.extend_from_slice(&fingerprint);
+        fpr
+    });
+    hashed.extend_from_slice(&(subpackets.len() as u16).to_be_bytes());
+    hashed.extend_from_slice(&subpackets);
+
+    let digest = {
+        let mut hasher = Sha256::new();
+        hasher.update(&hashed_key(&public));
+        hasher.update(&[0xb4]);
+        hasher.update(&(user_id.len() as u32).to_be_bytes());
+        hasher.update(user_id.as_bytes());
+        hasher.update(&hashed);
+        hasher.update(&[VERSION, 0xff]);
+        hasher.update(&(hashed.len() as u32).to_be_bytes());
+        hasher.finalize()
+    };
+    let signature: [u8; 64] = key.sign(&digest).into();
+
+    let mut unhashed = vec![];
+    subpacket(&mut unhashed, SUBPACKET_ISSUER, &fingerprint[12..]);
+
+    let mut sig = hashed;
+    sig.extend_from_slice(&(unhashed.len() as u16).to_be_bytes());
+    sig.extend_from_slice(&unhashed);
+    sig.extend_from_slice(&digest[..2]);
+    sig.extend_from_slice(&mpi(&signature[..32]));
+    sig.extend_from_slice(&mpi(&signature[32..]));
+
+    let mut out = vec![];
+    packet(&mut out, TAG_PUBLIC_KEY, &public);
+    packet(&mut out, TAG_USER_ID, user_id.as_bytes());
+    packet(&mut out, TAG_SIGNATURE, &sig);
+    out
+}
+
+/// The body of the public key packet.
+fn key_body(key: &SecretKey, created: u32) -> Vec<u8> {
+    let mut body = vec![VERSION];
+    body.extend_from_slice(&created.to_be_bytes());
+    body.push(ALGORITHM_EDDSA);
+    body.push(ED25519_OID.len() as u8);
+    body.extend_from_slice(&ED25519_OID);
+    // The key is prefixed by 0x40 to mark it as a native point encoding.
+    let mut point = vec![0x40];
+    point.extend_from_slice(key.public().as_ref());
+    body.extend_from_slice(&mpi(&point));
+    body
+}
+
+/// The public key packet body as it is hashed for fingerprints and
+/// signatures.
+fn hashed_key(body: &[u8]) -> Vec<u8> {
+    let mut hashed = vec![0x99];
+    hashed.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    hashed.extend_from_slice(body);
+    hashed
+}
+
+/// A multiprecision integer: the length in bits, followed by the big-endian
+/// bytes without leading zeros.
+fn mpi(bytes: &[u8]) -> Vec<u8> {
+    let bytes = match bytes.iter().position(|b| *b != 0) {
+        Some(start) => &bytes[start..],
+        None => &[],
+    };
+    let bits = match bytes.first() {
+        Some(first) => (bytes.len() - 1) * 8 + (8 - first.leading_zeros() as usize),
+        None => 0,
+    };
+    let mut out = (bits as u16).to_be_bytes().to_vec();
+    out.extend_from_slice(bytes);
+    out
+}
+
+fn subpacket(out: &mut Vec<u8>, ty: u8, data: &[u8]) {
+    // All subpackets used here are shorter than 192 bytes, so the length fits
+    // into a single octet.
+    out.push(data.len() as u8 + 1);
+    out.push(ty);
+    out.extend_from_slice(data);
+}
+
+/// A new format packet.
+fn packet(out: &mut Vec<u8>, tag: u8, body: &[u8]) {
+    out.push(0xc0 | tag);
+    match body.len() {
+        len if len < 192 => out.push(len as u8),
+        len if len < 8384 => {
+            let len = len - 192;
+            out.push((len >> 8) as u8 + 192);
+            out.push(len as u8);
+        },
+        len => {
+            out.push(0xff);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        },
+    }
+    out.extend_from_slice(body);
+}
+
+fn armor(data: &[u8]) -> String {
+    let mut out = String::from("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n");
+    let encoded = base64::encode(data);
+    for line in encoded.as_bytes().chunks(64) {
+        out.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        out.push('\n');
+    }
+    out.push('=');
+    out.push_str(&base64::encode(&crc24(data).to_be_bytes()[1..]));
+    out.push_str("\n-----END PGP PUBLIC KEY BLOCK-----\n");
+    out
+}
+
+fn crc24(data: &[u8]) -> u32 {
+    const INIT: u32 = 0x00b7_04ce;
+    const POLY: u32 = 0x0186_4cfb;
+
+    let mut crc = INIT;
+    for byte in data {
+        crc ^= (*byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x0100_0000 != 0 {
+                crc ^= POLY;
+            }
+        }
+    }
+    crc & 0x00ff_ffff
+}
@@ -4,3 +4,20 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 pub mod cli;
+pub mod completions;
+pub mod config;
+pub mod daemon;
+pub mod exit;
+pub mod external;
+pub mod graph;
+pub mod hooks;
+pub mod identity;
+pub mod inspect;
+pub mod key;
+pub mod logging;
+pub mod ls;
+pub mod migrate;
+pub mod petname;
+pub mod progress;
+pub mod seed;
+pub mod sync;
@@ -0,0 +1,86 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Log output of the CLI.
+//!
+//! Logs are written to stderr, so they don't interfere with the results
+//! printed to stdout. Which events are logged is controlled by `RUST_LOG`,
+//! defaulting to warnings -- or informational events if `--rad-verbose` is
+//! given.
+
+use std::{fmt, io, str::FromStr};
+
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+
+/// The format of the log output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Multi-line, human readable output, see
+    /// [`tracing_subscriber::fmt::format::Pretty`].
+    Pretty,
+    /// One JSON object per event, see
+    /// [`tracing_subscriber::fmt::format::Json`].
+    Json,
+    /// Single-line, human readable output, see
+    /// [`tracing_subscriber::fmt::format::Compact`].
+    Compact,
+}
+
+impl LogFormat {
+    pub fn variants() -> &'static [&'static str] {
+        &["pretty", "json", "compact"]
+    }
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        Self::Pretty
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pretty => write!(f, "pretty"),
+            Self::Json => write!(f, "json"),
+            Self::Compact => write!(f, "compact"),
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            "compact" => Ok(Self::Compact),
+            _ => Err("unknown log format, expected `pretty`, `json`, or `compact`"),
+        }
+    }
+}
+
+/// Install the global tracing subscriber, logging in the given `format`.
+///
+/// Does nothing if a subscriber is already installed.
+pub fn init(format: LogFormat, verbose: bool) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(if verbose { "info" } else { "warn" }));
+    let builder = FmtSubscriber::builder()
+        .with_env_filter(filter)
+        .with_writer(io::stderr);
+
+    let res = match format {
+        LogFormat::Pretty => tracing::subscriber::set_global_default(builder.pretty().finish()),
+        LogFormat::Json => {
+            tracing::subscriber::set_global_default(builder.json().flatten_event(true).finish())
+        },
+        LogFormat::Compact => tracing::subscriber::set_global_default(builder.compact().finish()),
+    };
+    if res.is_err() {
+        tracing::debug!("tracing subscriber already installed");
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! List the projects found in the local storage.
+
+use std::{convert::TryFrom as _, fmt};
+
+use serde::Serialize;
+use thiserror::Error;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use librad::{
+    git::{
+        identities::{self, Project},
+        storage::{self, ReadOnly, ReadOnlyStorage as _},
+        types::{Namespace, Reference},
+        Urn,
+    },
+    git_ext::{OneLevel, RefLike},
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Storage(#[from] storage::read::Error),
+}
+
+/// A summary of a project in the local storage.
+#[derive(Debug, Serialize)]
+pub struct Listing {
+    pub urn: Urn,
+    /// The name of the project, as found in the identity payload.
+    pub name: String,
+    /// The number of delegates of the project.
+    pub delegates: usize,
+    /// The default branch of the project, as found in the identity payload.
+    pub default_branch: Option<String>,
+    /// The tip of the local default branch, if it exists.
+    pub head: Option<String>,
+    /// The time of the most recent commit of the default branch, or of the
+    /// identity document if there is no default branch, as seconds since the
+    /// Unix epoch.
+    pub updated: Option<i64>,
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let updated = self
+            .updated
+            .and_then(|secs| OffsetDateTime::from_unix_timestamp(secs).ok())
+            .and_then(|time| time.format(&Rfc3339).ok());
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.urn,
+            self.name,
+            self.delegates,
+            self.default_branch.as_deref().unwrap_or("-"),
+            self.head.as_deref().unwrap_or("-"),
+            updated.as_deref().unwrap_or("-"),
+        )
+    }
+}
+
+/// List all projects in the local storage.
+pub fn list<S>(storage: &S) -> Result<Vec<Listing>, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    let storage = storage.as_ref();
+    identities::any::list(storage)?
+        .filter_map(|id| match id {
+            Ok(id) => id.project().map(|project| listing(storage, project)),
+            Err(e) => Some(Err(e.into())),
+        })
+        .collect()
+}
+
+fn listing(storage: &ReadOnly, project: Project) -> Result<Listing, Error> {
+    let urn = project.urn();
+    let payload = project.subject();
+    let default_branch = payload.default_branch.as_ref().map(|b| b.to_string());
+    let head = match default_branch
+        .as_deref()
+        .and_then(|branch| RefLike::try_from(branch).ok())
+    {
+        Some(branch) => {
+            let head = Reference::head(Namespace::from(&urn), None, OneLevel::from(branch));
+            storage
+                .reference(&head)?
+                .map(|r| r.peel_to_commit())
+                .transpose()?
+        },
+        None => None,
+    };
+    let updated = match &head {
+        Some(commit) => Some(commit.time().seconds()),
+        None => storage
+            .tip(&urn, git2::ObjectType::Commit)?
+            .and_then(|obj| obj.into_commit().ok())
+            .map(|commit| commit.time().seconds()),
+    };
+
+    Ok(Listing {
+        name: payload.name.to_string(),
+        delegates: project.delegations().iter().count(),
+        default_branch,
+        head: head.map(|commit| commit.id().to_string()),
+        updated,
+        urn,
+    })
+}
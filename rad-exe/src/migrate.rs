@@ -0,0 +1,497 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Migrate a [`Profile`] to a new passphrase, key, or machine.
+//!
+//! A profile is moved between machines as an archive, see [`archive`] and
+//! [`restore`]. The archive is a tar stream whose first entry is a
+//! `manifest.json` describing the profile, followed by the key storage, the
+//! monorepo, and the configuration of the profile:
+//!
+//! ```text
+//! manifest.json
+//! keys/...
+//! git/...
+//! git-includes/...
+//! config/settings.toml
+//! config/config.toml
+//! ```
+//!
+//! The key storage is archived as is, ie. encrypted. It is thus necessary to
+//! know the passphrase to restore the profile.
+
+use std::{
+    error,
+    fmt,
+    fs,
+    io::{self, Read, Seek as _, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use librad::{
+    crypto::{
+        keystore::{crypto::Crypto, file, Keystore as _},
+        IntoSecretKeyError,
+    },
+    git::{
+        identities,
+        storage::{self, read, ReadOnly, Storage},
+        Urn,
+    },
+    paths::Paths,
+    profile::{self, settings::SETTINGS_FILE, Profile, ProfileId, RadHome},
+    PeerId,
+    SecretKey,
+};
+use rad_clib::keys;
+
+use crate::{config::CONFIG_FILE, key};
+
+/// The version of the archive format written by [`archive`].
+pub const ARCHIVE_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const KEYS: &str = "keys";
+const GIT: &str = "git";
+const GIT_INCLUDES: &str = "git-includes";
+const AUDIT: &str = "audit";
+const CONFIG: &str = "config";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("the profile {0} already exists")]
+    Exists(ProfileId),
+
+    #[error("malformed profile archive: {0}")]
+    Malformed(String),
+
+    #[error("unsupported archive version {found}, expected at most {ARCHIVE_VERSION}")]
+    Version { found: u32 },
+
+    #[error("the storage belongs to {found}, but the key is {expected}")]
+    Mismatch { expected: PeerId, found: PeerId },
+
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Key(#[from] key::Error),
+
+    #[error(transparent)]
+    Keystore(Box<dyn error::Error + Send + Sync + 'static>),
+
+    #[error(transparent)]
+    Manifest(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Profile(#[from] profile::Error),
+
+    #[error(transparent)]
+    ReadOnly(#[from] read::error::Init),
+
+    #[error(transparent)]
+    Storage(#[from] storage::error::Init),
+}
+
+impl<C> From<file::Error<C, IntoSecretKeyError>> for Error
+where
+    C: fmt::Debug + fmt::Display + Send + Sync + 'static,
+{
+    fn from(err: file::Error<C, IntoSecretKeyError>) -> Self {
+        Self::Keystore(Box::new(err))
+    }
+}
+
+/// The first entry of an archive, describing the archived profile.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub profile: String,
+    pub peer_id: PeerId,
+}
+
+/// The outcome of [`migrate`].
+#[derive(Debug, Serialize)]
+pub struct Migrated {
+    pub peer_id: PeerId,
+    /// Set if the key was rotated.
+    pub rotated: Option<key::Rotated>,
+    /// The default identity, if it was updated to delegate to the new key.
+    pub identity: Option<Urn>,
+}
+
+impl fmt::Display for Migrated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.rotated {
+            None => write!(f, "re-encrypted key {}", self.peer_id)?,
+            Some(rotated) => write!(f, "{}", rotated)?,
+        }
+        if let Some(urn) = &self.identity {
+            write!(f, "\nadded {} to the delegations of {}", self.peer_id, urn)?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of [`restore`].
+#[derive(Debug)]
+pub struct Restored {
+    pub profile: Profile,
+    pub peer_id: PeerId,
+}
+
+impl fmt::Display for Restored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "restored profile {} of {}",
+            self.profile.id(),
+            self.peer_id
+        )
+    }
+}
+
+/// Re-encrypt the key of `profile`, unlocking it using `old` and locking it
+/// using `new`.
+///
+/// If `rotate` is set, the key is also replaced with a newly generated one
+/// (see [`key::rotate`]). In that case, the new key is first persisted (see
+/// [`key::stage`]) and added to the delegations of the default identity of
+/// `profile` using the current key, and the updated identity is signed using
+/// the new key afterwards. That is, the identity remains valid, and is
+/// delegated to by both keys.
+pub fn migrate<C, D>(profile: &Profile, old: C, new: D, rotate: bool) -> Result<Migrated, Error>
+where
+    C: Crypto + Clone,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+    D: Crypto,
+    D::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    D::SecretBox: Serialize + DeserializeOwned,
+{
+    if !rotate {
+        let peer_id = key::reencrypt(profile, old, new)?;
+        return Ok(Migrated {
+            peer_id,
+            rotated: None,
+            identity: None,
+        });
+    }
+
+    let current = keys::file_storage(profile, old.clone())
+        .get_key()?
+        .secret_key;
+    let next = SecretKey::new();
+    // Persist the new key before publishing it as a delegate, so it can't get
+    // lost if the rotation fails afterwards.
+    let staged = key::stage(profile, next.clone(), new)?;
+    let identity = match delegate(profile, current, &next) {
+        Ok(identity) => identity,
+        Err(e) => {
+            if let Err(err) = staged.discard() {
+                tracing::warn!(?err, "failed to remove staged key");
+            }
+            return Err(e);
+        },
+    };
+
+    let rotated = key::rotate_staged(profile, old, staged)?;
+    if let Some(urn) = &identity {
+        let storage = Storage::open(profile.paths(), next)?;
+        identities::person::approve(&storage, urn)?;
+    }
+
+    Ok(Migrated {
+        peer_id: rotated.peer_id,
+        rotated: Some(rotated),
+        identity,
+    })
+}
+
+/// Add `next` to the delegations of the default identity of `profile`, if
+/// any, signing the update using `current`.
+fn delegate(profile: &Profile, current: SecretKey, next: &SecretKey) -> Result<Option<Urn>, Error> {
+    let storage = Storage::open(profile.paths(), current)?;
+    match identities::local::default(&storage)? {
+        None => Ok(None),
+        Some(whoami) => {
+            let urn = whoami.urn();
+            let delegations = whoami.delegations().clone().insert(next.public());
+            identities::person::update(&storage, &urn, None, None, delegations)?;
+            Ok(Some(urn))
+        },
+    }
+}
+
+/// Check that the key of `profile`, unlocked using `crypto`, is the one its
+/// storage belongs to.
+pub fn verify<C>(profile: &Profile, crypto: C) -> Result<PeerId, Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    let key = keys::file_storage(profile, crypto).get_key()?.secret_key;
+    let expected = PeerId::from(key);
+    let found = *ReadOnly::open(profile.paths())?.peer_id();
+    if found != expected {
+        return Err(Error::Mismatch { expected, found });
+    }
+
+    Ok(expected)
+}
+
+/// Write `profile` as an archive to `out`.
+///
+/// The profile should not be in use while archiving it, as the archive may
+/// otherwise not be consistent.
+pub fn archive<W>(profile: &Profile, out: W) -> Result<Manifest, Error>
+where
+    W: Write,
+{
+    let paths = profile.paths();
+    let manifest = Manifest {
+        version: ARCHIVE_VERSION,
+        profile: profile.id().to_string(),
+        peer_id: *ReadOnly::open(paths)?.peer_id(),
+    };
+
+    let mut tar = tar::Builder::new(out);
+    let json = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    tar.append_data(&mut header, MANIFEST, json.as_slice())?;
+
+    tar.append_dir_all(KEYS, paths.keys_dir())?;
+    tar.append_dir_all(GIT, paths.git_dir())?;
+    tar.append_dir_all(GIT_INCLUDES, paths.git_includes_dir())?;
+    tar.append_dir_all(AUDIT, paths.audit_dir())?;
+    for name in &[SETTINGS_FILE, CONFIG_FILE] {
+        let path = paths.config_dir().join(name);
+        if path.is_file() {
+            tar.append_path_with_name(&path, Path::new(CONFIG).join(name))?;
+        }
+    }
+    tar.into_inner()?.flush()?;
+
+    Ok(manifest)
+}
+
+/// Restore a profile from an archive created by [`archive`] under `home`.
+///
+/// The profile keeps its [`ProfileId`], and it is an error if a profile with
+/// the same id already exists under `home`. The restored profile is verified
+/// by unlocking its key using `crypto`, and removed again if that fails. Note
+/// that this will not set the active profile, to do that use
+/// [`Profile::set`].
+pub fn restore<R, C>(home: &RadHome, input: R, crypto: C) -> Result<Restored, Error>
+where
+    R: Read,
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    let mut archive = tar::Archive::new(input);
+    let mut entries = archive.entries()?;
+
+    let manifest: Manifest = {
+        let entry = entries
+            .next()
+            .ok_or_else(|| Error::Malformed("empty archive".to_owned()))??;
+        if entry.path()? != Path::new(MANIFEST) {
+            return Err(Error::Malformed(format!("expected {} first", MANIFEST)));
+        }
+        serde_json::from_reader(entry)?
+    };
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(Error::Version {
+            found: manifest.version,
+        });
+    }
+    let id = manifest
+        .profile
+        .parse::<ProfileId>()
+        .map_err(profile::Error::from)?;
+    if Profile::get(home, id.clone())?.is_some() {
+        return Err(Error::Exists(id));
+    }
+
+    let profile = Profile::from_home(home, Some(id.clone()))?;
+    let restored = entries
+        .map(|entry| unpack(profile.paths(), &mut entry?))
+        .collect::<Result<(), Error>>()
+        .and_then(|()| verify(&profile, crypto))
+        .and_then(|peer_id| {
+            if peer_id != manifest.peer_id {
+                return Err(Error::Mismatch {
+                    expected: manifest.peer_id,
+                    found: peer_id,
+                });
+            }
+            Ok(peer_id)
+        });
+
+    match restored {
+        Ok(peer_id) => Ok(Restored { profile, peer_id }),
+        Err(e) => {
+            if let Err(err) = Profile::delete(home, id) {
+                tracing::warn!(?err, "failed to remove partially restored profile");
+            }
+            Err(e)
+        },
+    }
+}
+
+/// Move the profile `id` from `from` to `to`, eg. to relocate the monorepo to
+/// a different disk.
+///
+/// The moved profile is verified using `crypto` before the original is
+/// deleted. If the profile was the active one under `from`, it becomes the
+/// active one under `to`.
+pub fn relocate<C>(from: &RadHome, id: ProfileId, to: &RadHome, crypto: C) -> Result<Profile, Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    let profile = Profile::get(from, id.clone())?
+        .ok_or_else(|| Error::Profile(profile::Error::DoesNotExist(id.clone())))?;
+    let was_active = ProfileId::active(from)
+        .map_err(profile::Error::from)?
+        .as_ref()
+        == Some(&id);
+
+    let mut tmp = tempfile::tempfile()?;
+    archive(&profile, &mut tmp)?;
+    tmp.seek(SeekFrom::Start(0))?;
+    let Restored { profile, .. } = restore(to, tmp, crypto)?;
+
+    Profile::delete(from, id.clone())?;
+    if was_active {
+        Profile::set(to, id)?;
+    }
+
+    Ok(profile)
+}
+
+/// Where an entry of an archive is restored to, see [`destination`].
+enum Destination {
+    /// The entry is unpacked into the directory, which is where the first
+    /// component of its path resides.
+    Tree(PathBuf),
+    /// The entry is a configuration file, restored to the path.
+    Config(PathBuf),
+}
+
+/// Unpack an `entry` of an archive to where it belongs within `paths`.
+///
+/// Only regular files and directories are restored. Any other entry, eg. a
+/// symlink or hardlink, is rejected, as is an entry which would end up
+/// outside of the (canonicalised) profile directories.
+fn unpack<R>(paths: &Paths, entry: &mut tar::Entry<R>) -> Result<(), Error>
+where
+    R: Read,
+{
+    let path = entry.path()?.into_owned();
+    let is_dir = match entry.header().entry_type() {
+        tar::EntryType::Regular => false,
+        tar::EntryType::Directory => true,
+        kind => {
+            return Err(Error::Malformed(format!(
+                "unsupported entry type {:?} of {}",
+                kind,
+                path.display()
+            )))
+        },
+    };
+
+    match destination(paths, &path)? {
+        Destination::Config(dest) => {
+            if is_dir {
+                return Err(Error::Malformed(format!(
+                    "unexpected directory {}",
+                    path.display()
+                )));
+            }
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(dest)?;
+            io::copy(entry, &mut file)?;
+        },
+        Destination::Tree(root) => {
+            let root = fs::canonicalize(root)?;
+            if let Some(parent) = root.join(&path).parent() {
+                fs::create_dir_all(parent)?;
+                if !fs::canonicalize(parent)?.starts_with(&root) {
+                    return Err(Error::Malformed(format!("unsafe entry {}", path.display())));
+                }
+            }
+            if !entry.unpack_in(&root)? {
+                return Err(Error::Malformed(format!("unsafe entry {}", path.display())));
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Map an entry `path` of an archive to where it is restored to within
+/// `paths`.
+fn destination(paths: &Paths, path: &Path) -> Result<Destination, Error> {
+    let unexpected = || Error::Malformed(format!("unexpected entry {}", path.display()));
+
+    let mut components = path.components();
+    let base = match components.next() {
+        Some(Component::Normal(base)) => base,
+        _ => return Err(unexpected()),
+    };
+    let rest = components.as_path();
+    if rest
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(Error::Malformed(format!("unsafe entry {}", path.display())));
+    }
+
+    if base == CONFIG {
+        return match rest.to_str() {
+            Some(name) if name == SETTINGS_FILE || name == CONFIG_FILE => {
+                Ok(Destination::Config(paths.config_dir().join(name)))
+            },
+            _ => Err(unexpected()),
+        };
+    }
+
+    let dir = if base == KEYS {
+        paths.keys_dir()
+    } else if base == GIT {
+        paths.git_dir()
+    } else if base == GIT_INCLUDES {
+        paths.git_includes_dir()
+    } else if base == AUDIT {
+        paths.audit_dir()
+    } else {
+        return Err(unexpected());
+    };
+    // The entry is unpacked relative to the parent of `dir`, so its first
+    // component must name `dir`. This holds for both layouts of `Paths`.
+    match dir.parent() {
+        Some(root) if dir.file_name() == Some(base) => Ok(Destination::Tree(root.to_path_buf())),
+        _ => Err(Error::Malformed(format!(
+            "can't restore {} to {}",
+            path.display(),
+            dir.display()
+        ))),
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Petnames on the command line, see [`librad::profile::petnames`].
+//!
+//! Wherever a command takes a Radicle URN, the petname of one can be given
+//! instead. The name is resolved using the petnames of the profile the command
+//! operates on, so parsing the argument only checks that it is well-formed.
+
+use std::{fmt, str::FromStr};
+
+use librad::{
+    git::Urn,
+    profile::{
+        petnames::{self, Petname},
+        Profile,
+    },
+};
+
+/// A Radicle URN given on the command line, either directly or by its
+/// petname.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UrnOrPetname {
+    Urn(Urn),
+    Petname(Petname),
+}
+
+impl UrnOrPetname {
+    /// Resolve to a [`Urn`], looking up a petname in the petnames of
+    /// `profile`.
+    pub fn resolve(&self, profile: &Profile) -> Result<Urn, petnames::Error> {
+        match self {
+            Self::Urn(urn) => Ok(urn.clone()),
+            Self::Petname(name) => profile.petnames().load()?.resolve_urn(name.as_str()),
+        }
+    }
+}
+
+impl FromStr for UrnOrPetname {
+    type Err = petnames::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(urn) => Ok(Self::Urn(urn)),
+            Err(_) => s
+                .parse()
+                .map(Self::Petname)
+                .map_err(|_| petnames::Error::Unresolvable(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for UrnOrPetname {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Urn(urn) => write!(f, "{}", urn),
+            Self::Petname(name) => write!(f, "{}", name),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Render the progress of long running operations on stderr.
+//!
+//! If stderr is a terminal, progress is drawn as a bar, otherwise each step is
+//! logged as a plain line, so that it remains readable when redirected to a
+//! file. Under `--rad-quiet`, nothing is rendered at all.
+//!
+//! With the `replication-v3` feature, the progress of each replication is
+//! rendered from the events it reports, see [`Progress::reporter`].
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[cfg(feature = "replication-v3")]
+use indicatif::HumanBytes;
+use indicatif::{ProgressBar, ProgressStyle};
+
+#[cfg(feature = "replication-v3")]
+use librad::net::replication::progress::{Event, Reporter};
+
+const TEMPLATE: &str = "{spinner} [{elapsed_precise}] [{bar:30}] {pos}/{len} {wide_msg}";
+
+/// The progress of an operation consisting of a known number of steps.
+pub struct Progress {
+    render: Render,
+}
+
+enum Render {
+    Bar(ProgressBar),
+    Log { pos: AtomicU64, len: AtomicU64 },
+    Hidden,
+}
+
+impl Progress {
+    /// Track the progress of `len` steps, rendering nothing if `quiet` is set.
+    pub fn new(len: u64, quiet: bool) -> Self {
+        let render = if quiet {
+            Render::Hidden
+        } else {
+            let bar = ProgressBar::new(len);
+            if bar.is_hidden() {
+                Render::Log {
+                    pos: AtomicU64::new(0),
+                    len: AtomicU64::new(len),
+                }
+            } else {
+                bar.set_style(
+                    ProgressStyle::default_bar()
+                        .template(TEMPLATE)
+                        .progress_chars("=> "),
+                );
+                bar.enable_steady_tick(100);
+                Render::Bar(bar)
+            }
+        };
+        Self { render }
+    }
+
+    /// A [`Progress`] which never renders anything.
+    pub fn hidden() -> Self {
+        Self {
+            render: Render::Hidden,
+        }
+    }
+
+    /// Change the number of steps, e.g. once it is known.
+    pub fn set_len(&self, steps: u64) {
+        match &self.render {
+            Render::Bar(bar) => bar.set_length(steps),
+            Render::Log { len, .. } => len.store(steps, Ordering::Relaxed),
+            Render::Hidden => {},
+        }
+    }
+
+    /// Describe what is currently being worked on, without advancing.
+    pub fn message(&self, msg: impl fmt::Display) {
+        match &self.render {
+            Render::Bar(bar) => bar.set_message(msg.to_string()),
+            Render::Log { .. } => eprintln!("{}", msg),
+            Render::Hidden => {},
+        }
+    }
+
+    /// Complete a step, reporting its `outcome`.
+    pub fn advance(&self, outcome: impl fmt::Display) {
+        match &self.render {
+            Render::Bar(bar) => {
+                bar.println(outcome.to_string());
+                bar.inc(1);
+            },
+            Render::Log { pos, len } => {
+                let pos = pos.fetch_add(1, Ordering::Relaxed) + 1;
+                let len = len.load(Ordering::Relaxed);
+                eprintln!("[{}/{}] {}", pos, len, outcome)
+            },
+            Render::Hidden => {},
+        }
+    }
+
+    /// A [`Reporter`] describing the replication [`Event`]s reported to it
+    /// as the current message, prefixed by `what`.
+    ///
+    /// The bytes received are only shown on the bar, as they are reported too
+    /// often to be logged.
+    #[cfg(feature = "replication-v3")]
+    pub fn reporter(&self, what: impl fmt::Display) -> Reporter {
+        let what = what.to_string();
+        match &self.render {
+            Render::Bar(bar) => {
+                let bar = bar.clone();
+                Reporter::new(move |event| {
+                    if let Some(msg) = describe(&event) {
+                        bar.set_message(format!("{}: {}", what, msg))
+                    }
+                })
+            },
+            Render::Log { .. } => Reporter::new(move |event| {
+                if matches!(event, Event::Received { .. }) {
+                    return;
+                }
+                if let Some(msg) = describe(&event) {
+                    eprintln!("{}: {}", what, msg)
+                }
+            }),
+            Render::Hidden => Reporter::new(|_| {}),
+        }
+    }
+
+    /// Stop rendering, removing the bar from the terminal.
+    pub fn finish(&self) {
+        if let Render::Bar(bar) = &self.render {
+            bar.finish_and_clear()
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.finish()
+    }
+}
+
+#[cfg(feature = "replication-v3")]
+fn describe(event: &Event) -> Option<String> {
+    match event {
+        Event::LsRefs => Some("asking for refs".to_owned()),
+        Event::WantsHaves { wants, haves } => {
+            Some(format!("wanting {} tips, having {}", wants, haves))
+        },
+        Event::Received { bytes } => Some(format!("received {}", HumanBytes(*bytes))),
+        Event::Validated { warnings } => Some(format!("validated, {} warnings", warnings)),
+        Event::Applied { updated, rejected } => {
+            Some(format!("updated {} refs, rejected {}", updated, rejected))
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Manage the seeds of a [`Profile`], which are consulted by `rad sync` and
+//! `rad daemon` when no seeds are given on the command line.
+//!
+//! The seeds are kept in the [`librad::profile::settings::Settings::seeds`]
+//! of the profile, in the form `<peer id>@<host>:<port>`. The addresses are
+//! only resolved when the seeds are used, so that host names can be configured
+//! while offline. When adding or removing a seed, the peer can also be given by
+//! its petname, which is resolved before the seed is stored.
+
+use thiserror::Error;
+
+use librad::{
+    profile::{petnames::PeerOrPetname, Profile},
+    PeerId,
+};
+
+use crate::sync::Seed;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Settings(#[from] librad::profile::settings::Error),
+
+    #[error("invalid seed `{0}`, expected `<peer id>@<host>:<port>`")]
+    InvalidSeed(String),
+
+    #[error("invalid peer of seed `{0}`")]
+    InvalidPeer(String, #[source] librad::profile::petnames::Error),
+}
+
+/// List the seeds configured for `profile`.
+pub fn list(profile: &Profile) -> Result<Vec<String>, Error> {
+    Ok(profile.settings().load()?.seeds)
+}
+
+/// Add `seed` to the seeds of `profile`, returning `false` if it was already
+/// configured.
+pub fn add(profile: &Profile, seed: &str) -> Result<bool, Error> {
+    let seed = &normalize(profile, seed)?;
+    let store = profile.settings();
+    let mut settings = store.load()?;
+    if settings.seeds.iter().any(|s| s == seed) {
+        return Ok(false);
+    }
+    settings.seeds.push(seed.to_string());
+    store.store(&settings)?;
+    Ok(true)
+}
+
+/// Remove the seeds of `profile` matching `seed`, either by the full `<peer
+/// id>@<host>:<port>` or by only the peer id, returning the removed seeds.
+pub fn remove(profile: &Profile, seed: &str) -> Result<Vec<String>, Error> {
+    let seed = &match seed.parse::<PeerOrPetname>() {
+        Ok(peer) => resolve_peer(profile, seed, &peer)?.to_string(),
+        Err(_) => normalize(profile, seed)?,
+    };
+    let store = profile.settings();
+    let mut settings = store.load()?;
+    let (removed, kept) = settings
+        .seeds
+        .into_iter()
+        .partition::<Vec<_>, _>(|s| s == seed || peer_id_of(s) == Some(seed));
+    if !removed.is_empty() {
+        settings.seeds = kept;
+        store.store(&settings)?;
+    }
+    Ok(removed)
+}
+
+/// Resolve the configured `seeds`, e.g. [`list`], to their addresses.
+///
+/// Seeds which fail to resolve are skipped with a warning, so that a single
+/// unreachable host does not prevent using the remaining ones.
+pub fn resolve(seeds: &[String]) -> Vec<Seed> {
+    seeds
+        .iter()
+        .filter_map(|seed| match seed.parse::<Seed>() {
+            Ok(seed) => Some(seed),
+            Err(err) => {
+                tracing::warn!(%seed, %err, "skipping configured seed");
+                None
+            },
+        })
+        .collect()
+}
+
+/// Validate `seed`, replacing a petname of the peer with its peer id.
+fn normalize(profile: &Profile, seed: &str) -> Result<String, Error> {
+    match seed.split_once('@') {
+        Some((peer, addr)) if addr.rsplit_once(':').is_some() => {
+            let peer = peer
+                .parse::<PeerOrPetname>()
+                .map_err(|e| Error::InvalidPeer(seed.to_string(), e))?;
+            Ok(format!("{}@{}", resolve_peer(profile, seed, &peer)?, addr))
+        },
+        _ => Err(Error::InvalidSeed(seed.to_string())),
+    }
+}
+
+fn resolve_peer(profile: &Profile, seed: &str, peer: &PeerOrPetname) -> Result<PeerId, Error> {
+    let resolved = match peer {
+        PeerOrPetname::Peer(peer) => Ok(*peer),
+        PeerOrPetname::Petname(_) => profile
+            .petnames()
+            .load()
+            .and_then(|petnames| peer.resolve(&petnames)),
+    };
+    resolved.map_err(|e| Error::InvalidPeer(seed.to_string(), e))
+}
+
+fn peer_id_of(seed: &str) -> Option<&str> {
+    seed.split_once('@').map(|(peer_id, _)| peer_id)
+}
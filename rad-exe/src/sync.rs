@@ -0,0 +1,443 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Replicate local projects from, and announce them to, seed nodes.
+
+use std::{
+    fmt,
+    net::{SocketAddr, ToSocketAddrs as _},
+    str::FromStr,
+    time::Duration,
+};
+
+use futures::{stream, StreamExt as _};
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+use librad::{
+    crypto,
+    git::{identities, storage::ReadOnly, Urn},
+    net::{
+        discovery::{self, Discovery as _},
+        peer::{self, Peer},
+        protocol::{self, gossip},
+        replication,
+        Network,
+    },
+    profile::Profile,
+    PeerId,
+    Signer,
+};
+
+use crate::progress::Progress;
+
+/// The amount of time the network endpoint is kept alive after announcing, so
+/// that seeds get a chance to fetch from us.
+pub const PUSH_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Init(#[from] peer::error::Init),
+
+    #[error(transparent)]
+    Bind(#[from] protocol::error::Bootstrap),
+
+    #[error(transparent)]
+    Replicate(#[from] peer::error::Replicate),
+
+    #[error(transparent)]
+    Storage(#[from] peer::error::Storage),
+
+    #[error("no seeds were provided, and no providers were found for `{0}`")]
+    NoSeeds(Urn),
+}
+
+/// A seed node to replicate from, given in the form `<peer id>@<address>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Seed {
+    pub peer_id: PeerId,
+    pub addrs: Vec<SocketAddr>,
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.addrs.first() {
+            Some(addr) => write!(f, "{}@{}", self.peer_id, addr),
+            None => write!(f, "{}", self.peer_id),
+        }
+    }
+}
+
+impl FromStr for Seed {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src.split_once('@') {
+            Some((peer_id, addr)) => {
+                let peer_id = peer_id
+                    .parse()
+                    .map_err(|e: crypto::peer::conversion::Error| e.to_string())?;
+                let addrs = addr
+                    .to_socket_addrs()
+                    .map_err(|e| format!("failed to resolve `{}`: {}", addr, e))?
+                    .collect::<Vec<_>>();
+                if addrs.is_empty() {
+                    return Err(format!(
+                        "the seed `{}` failed to resolve to an address",
+                        src
+                    ));
+                }
+                Ok(Self { peer_id, addrs })
+            },
+            None => Err("missing peer id".to_string()),
+        }
+    }
+}
+
+/// Which direction(s) of synchronisation to perform.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// Only replicate from the seeds.
+    Fetch,
+    /// Only announce the local state to the seeds.
+    Push,
+    /// Replicate from the seeds, and announce the resulting state.
+    Both,
+}
+
+impl Mode {
+    pub fn new(fetch_only: bool, push: bool) -> Self {
+        match (fetch_only, push) {
+            (true, false) => Self::Fetch,
+            (false, true) => Self::Push,
+            _ => Self::Both,
+        }
+    }
+
+    pub fn fetches(&self) -> bool {
+        matches!(self, Self::Fetch | Self::Both)
+    }
+
+    pub fn pushes(&self) -> bool {
+        matches!(self, Self::Push | Self::Both)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub mode: Mode,
+    /// The maximum number of replications running at the same time.
+    pub concurrency: usize,
+    /// If set, providers found on the network are used in addition to the
+    /// given seeds. The value is how long to wait for providers to respond.
+    pub providers: Option<Duration>,
+    pub network: Network,
+}
+
+/// The outcome of replicating a single [`Urn`] from a single seed.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub urn: Urn,
+    pub seed: PeerId,
+    /// The number of refs which were created or updated.
+    pub updated_refs: usize,
+    /// The number of `rad/id` refs which were advanced.
+    pub identities_advanced: usize,
+    /// Whether the local identity document requires confirmation of an update
+    /// proposed by another delegate.
+    pub requires_confirmation: bool,
+    /// Validation errors which did not prevent the replication from
+    /// succeeding.
+    pub warnings: Vec<String>,
+}
+
+impl Summary {
+    #[cfg(feature = "replication-v3")]
+    fn new(urn: Urn, seed: PeerId, success: replication::Success) -> Self {
+        use link_replication::Updated;
+
+        let updated = success.updated_refs();
+        Self {
+            urn,
+            seed,
+            updated_refs: updated.len(),
+            identities_advanced: updated
+                .iter()
+                .filter(|up| match up {
+                    Updated::Direct { name, .. } | Updated::Symbolic { name, .. } => {
+                        name.ends_with(b"rad/id")
+                    },
+                })
+                .count(),
+            requires_confirmation: success.requires_confirmation(),
+            warnings: success
+                .validation_errors()
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+        }
+    }
+
+    #[cfg(not(feature = "replication-v3"))]
+    fn new(urn: Urn, seed: PeerId, success: replication::Success) -> Self {
+        Self {
+            urn,
+            seed,
+            updated_refs: success.updated_tips.len(),
+            identities_advanced: success
+                .updated_tips
+                .keys()
+                .filter(|name| name.as_str().ends_with("rad/id"))
+                .count(),
+            requires_confirmation: matches!(success.identity, replication::IdStatus::Uneven),
+            warnings: vec![],
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} from {}: {} refs updated, {} identities advanced",
+            self.urn, self.seed, self.updated_refs, self.identities_advanced
+        )?;
+        if self.requires_confirmation {
+            write!(f, ", identity requires confirmation")?;
+        }
+        for warning in &self.warnings {
+            write!(f, "\n  warning: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+/// A replication of a single [`Urn`] which did not succeed.
+#[derive(Debug, Serialize)]
+pub struct Failed {
+    pub urn: Urn,
+    /// The seed which was replicated from, if any.
+    pub seed: Option<PeerId>,
+    #[serde(serialize_with = "serialize_display")]
+    pub error: Error,
+}
+
+fn serialize_display<T, S>(val: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: fmt::Display,
+    S: Serializer,
+{
+    serializer.collect_str(val)
+}
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.seed {
+            Some(seed) => write!(f, "{} from {}: {}", self.urn, seed, self.error),
+            None => write!(f, "{}: {}", self.urn, self.error),
+        }
+    }
+}
+
+/// The outcome of a [`sync`] run, per [`Urn`] and seed.
+#[derive(Debug, Default, Serialize)]
+pub struct Synced {
+    pub succeeded: Vec<Summary>,
+    pub failed: Vec<Failed>,
+    pub announced: Vec<Urn>,
+}
+
+/// List the [`Urn`]s of all projects in the local storage.
+pub fn local_projects<S>(storage: &S) -> Result<Vec<Urn>, identities::Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    identities::any::list(storage)?
+        .filter_map(|id| match id {
+            Ok(id) => id.project().map(|p| Ok(p.urn())),
+            Err(e) => Some(Err(e)),
+        })
+        .collect()
+}
+
+/// Replicate `urn` from `seed`, rendering the progress of the replication if
+/// the backend reports it.
+#[cfg(feature = "replication-v3")]
+async fn replicate<S>(
+    peer: &Peer<S>,
+    seed: Seed,
+    urn: Urn,
+    progress: &Progress,
+) -> Result<replication::Success, peer::error::Replicate>
+where
+    S: Signer + Clone,
+{
+    let reporter = progress.reporter(format_args!("{} from {}", urn, seed.peer_id));
+    peer.replicate_with_progress((seed.peer_id, seed.addrs), urn, None, reporter)
+        .await
+}
+
+#[cfg(not(feature = "replication-v3"))]
+async fn replicate<S>(
+    peer: &Peer<S>,
+    seed: Seed,
+    urn: Urn,
+    _progress: &Progress,
+) -> Result<replication::Success, peer::error::Replicate>
+where
+    S: Signer + Clone,
+{
+    peer.replicate((seed.peer_id, seed.addrs), urn, None).await
+}
+
+/// Synchronise the given `urns`, or all local projects if `urns` is empty,
+/// with `seeds`.
+///
+/// An ephemeral network endpoint is bound for the duration of the run, using
+/// the storage of `profile`. Each project counts as one step of `progress`,
+/// either when it is replicated, or announced if only pushing.
+pub async fn sync<S>(
+    profile: &Profile,
+    signer: S,
+    urns: Vec<Urn>,
+    seeds: Vec<Seed>,
+    opts: Options,
+    progress: &Progress,
+) -> Result<Synced, Error>
+where
+    S: Signer + Clone,
+{
+    let peer = Peer::new(peer::Config {
+        signer,
+        protocol: protocol::Config {
+            paths: profile.paths().clone(),
+            listen_addr: ([0, 0, 0, 0], 0).into(),
+            advertised_addrs: None,
+            membership: Default::default(),
+            network: opts.network.clone(),
+            replication: Default::default(),
+            rate_limits: Default::default(),
+        },
+        storage: Default::default(),
+    })?;
+
+    let urns = if urns.is_empty() {
+        peer.using_read_only(|storage| local_projects(storage))
+            .await??
+    } else {
+        urns
+    };
+    progress.set_len(urns.len() as u64);
+
+    let bound = peer.bind().await?;
+    let disco = discovery::Static::resolve(
+        seeds
+            .iter()
+            .map(|seed| (seed.peer_id, seed.addrs.as_slice())),
+    )
+    .expect("seed addresses are already resolved");
+    let (stop, run) = bound.accept(disco.discover());
+    let run = tokio::spawn(run);
+
+    let mut synced = Synced::default();
+    if opts.mode.fetches() {
+        let peer = &peer;
+        let seeds = &seeds;
+        let providers = opts.providers;
+        let mut results = stream::iter(urns.iter().cloned())
+            .map(|urn| async move {
+                let mut from = seeds.clone();
+                if let Some(timeout) = providers {
+                    from.extend(
+                        peer.providers(urn.clone(), timeout)
+                            .map(|info| Seed {
+                                peer_id: info.peer_id,
+                                addrs: info.addrs().copied().collect(),
+                            })
+                            .collect::<Vec<_>>()
+                            .await,
+                    );
+                }
+                if from.is_empty() {
+                    let failed = Failed {
+                        urn: urn.clone(),
+                        seed: None,
+                        error: Error::NoSeeds(urn.clone()),
+                    };
+                    return (urn, vec![Err(failed)]);
+                }
+
+                let mut results = Vec::with_capacity(from.len());
+                for seed in from {
+                    progress.message(format_args!("replicating {} from {}", urn, seed.peer_id));
+                    let seed_id = seed.peer_id;
+                    let res = replicate(peer, seed, urn.clone(), progress).await;
+                    results.push(match res {
+                        Ok(success) => Ok(Summary::new(urn.clone(), seed_id, success)),
+                        Err(e) => Err(Failed {
+                            urn: urn.clone(),
+                            seed: Some(seed_id),
+                            error: e.into(),
+                        }),
+                    });
+                }
+                (urn, results)
+            })
+            .buffer_unordered(opts.concurrency.max(1));
+
+        while let Some((urn, results)) = results.next().await {
+            let failed = results.iter().filter(|res| res.is_err()).count();
+            if failed == 0 {
+                progress.advance(format_args!("replicated {}", urn));
+            } else {
+                progress.advance(format_args!(
+                    "replicated {}, {} of {} replications failed",
+                    urn,
+                    failed,
+                    results.len()
+                ));
+            }
+            for res in results {
+                match res {
+                    Ok(summary) => synced.succeeded.push(summary),
+                    Err(failed) => synced.failed.push(failed),
+                }
+            }
+        }
+    }
+
+    if opts.mode.pushes() {
+        for urn in urns {
+            match peer.announce(gossip::Payload {
+                urn: urn.clone(),
+                rev: None,
+                origin: None,
+            }) {
+                Ok(()) => {
+                    if !opts.mode.fetches() {
+                        progress.advance(format_args!("announced {}", urn));
+                    }
+                    synced.announced.push(urn)
+                },
+                Err(_payload) => tracing::warn!(%urn, "failed to announce URN"),
+            }
+        }
+        progress.message(format_args!(
+            "waiting {}s for the seeds to fetch",
+            PUSH_GRACE.as_secs()
+        ));
+        tokio::time::sleep(PUSH_GRACE).await;
+    }
+
+    stop();
+    let _ = run.await;
+    progress.finish();
+
+    Ok(synced)
+}
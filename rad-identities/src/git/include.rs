@@ -3,37 +3,16 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{convert::TryFrom as _, path::PathBuf};
+use std::path::PathBuf;
 
 use librad::{
-    git::{
-        identities,
-        include::{self, Include},
-        local::url::LocalUrl,
-        storage::ReadOnly,
-    },
-    git_ext,
-    identities::relations,
+    git::{include, storage::ReadOnly},
     paths::Paths,
 };
 
 use crate::field::HasUrn;
 
-#[derive(Debug, thiserror::Error)]
-#[allow(clippy::large_enum_variant)]
-pub enum Error {
-    #[error(transparent)]
-    Identities(#[from] identities::Error),
-
-    #[error(transparent)]
-    Include(#[from] include::Error),
-
-    #[error(transparent)]
-    Ref(#[from] git_ext::name::Error),
-
-    #[error(transparent)]
-    Relations(#[from] identities::relations::Error),
-}
+pub use include::{link, Error};
 
 /// Update the include file for the given `identity`.
 ///
@@ -45,25 +24,7 @@ where
     S: AsRef<ReadOnly>,
     I: HasUrn,
 {
-    let urn = identity.urn();
-    let url = LocalUrl::from(urn.clone());
-    let tracked = identities::relations::tracked(storage, &urn)?;
-    let include = Include::from_tracked_persons(
-        paths.git_includes_dir().to_path_buf(),
-        url,
-        tracked
-            .into_iter()
-            .filter_map(|peer| {
-                relations::Peer::replicated_remote(peer).map(|(p, u)| {
-                    git_ext::RefLike::try_from(u.person().subject().name.to_string())
-                        .map(|r| (r, p))
-                })
-            })
-            .collect::<Result<Vec<_>, _>>()?,
-    );
-    let path = include.file_path();
-    include.save()?;
-
+    let path = include::update(storage, paths, &identity.urn())?;
     tracing::info!("updated include file @ '{}'", path.display());
     Ok(path)
 }
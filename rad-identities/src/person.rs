@@ -201,6 +201,7 @@ where
     };
     let repo = git::checkout::checkout(settings, &person, from)?;
     include::update(&storage, &paths, &person)?;
+    include::link(&repo, &paths, urn)?;
     Ok(repo)
 }
 
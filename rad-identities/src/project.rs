@@ -271,6 +271,7 @@ where
     };
     let repo = git::checkout::checkout(settings, &project, from)?;
     include::update(&storage, &paths, &project)?;
+    include::link(&repo, &paths, urn)?;
     Ok(repo)
 }
 
@@ -3,12 +3,16 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::convert::TryInto as _;
+use std::{convert::TryInto as _, fmt};
 
+use serde_json::json;
 use thrussh_agent::Constraint;
 
 use librad::crypto::keystore::sign;
-use rad_clib::keys::{self, ssh::SshAuthSock};
+use rad_clib::{
+    keys::{passphrase::Passphrases, ssh::SshAuthSock},
+    ser::OutputFormat,
+};
 
 use crate::{
     create,
@@ -26,68 +30,138 @@ use crate::{
 
 use super::args::*;
 
-pub fn main(Args { command }: Args, sock: SshAuthSock) -> anyhow::Result<()> {
-    eval(sock, command)
+pub fn main(
+    Args { command }: Args,
+    sock: SshAuthSock,
+    passphrases: Passphrases,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    eval(sock, passphrases, format, command)
+}
+
+/// Print the `plain` output or the `json` output, depending on the `format`.
+fn output(format: OutputFormat, plain: impl fmt::Display, json: serde_json::Value) {
+    match format {
+        OutputFormat::Plain => println!("{}", plain),
+        OutputFormat::Json => println!("{}", json),
+    }
 }
 
-fn eval(sock: SshAuthSock, command: Command) -> anyhow::Result<()> {
+fn eval(
+    sock: SshAuthSock,
+    passphrases: Passphrases,
+    format: OutputFormat,
+    command: Command,
+) -> anyhow::Result<()> {
     match command {
         Command::Create(Create {}) => {
-            let (profile, peer_id) = create(None, keys::prompt::new())?;
-            println!("profile id: {}", profile.id());
-            println!("peer id: {}", peer_id);
+            let (profile, peer_id) = create(None, passphrases.choose())?;
+            output(
+                format,
+                format_args!("profile id: {}\npeer id: {}", profile.id(), peer_id),
+                json!({ "profile_id": profile.id().to_string(), "peer_id": peer_id }),
+            );
         },
         Command::Get(Get { id }) => {
             let profile = get(None, id)?;
             match profile {
-                Some(profile) => println!("{}", profile.id()),
-                None => println!(
-                    "no active profile found, perhaps you want to run `rad profile create`?"
+                Some(profile) => output(
+                    format,
+                    profile.id(),
+                    json!({ "profile_id": profile.id().to_string() }),
+                ),
+                None => output(
+                    format,
+                    "no active profile found, perhaps you want to run `rad profile create`?",
+                    json!({ "profile_id": null }),
                 ),
             }
         },
         Command::Set(Set { id }) => {
             set(None, id.clone())?;
-            println!("successfully set active profile id to {}", id);
+            output(
+                format,
+                format_args!("successfully set active profile id to {}", id),
+                json!({ "profile_id": id.to_string() }),
+            );
         },
         Command::List(List {}) => {
             let profiles = list(None)?;
-            for profile in profiles {
-                println!("{}", profile.id());
+            match format {
+                OutputFormat::Plain => {
+                    for profile in profiles {
+                        println!("{}", profile.id());
+                    }
+                },
+                OutputFormat::Json => {
+                    let ids = profiles
+                        .iter()
+                        .map(|profile| profile.id().to_string())
+                        .collect::<Vec<_>>();
+                    println!("{}", json!(ids));
+                },
             }
         },
         Command::Peer(GetPeerId { id }) => {
             let peer_id = peer_id(None, id)?;
-            println!("{}", peer_id);
+            output(format, peer_id, json!({ "peer_id": peer_id }));
         },
         Command::Paths(GetPaths { id }) => {
             let paths = paths(None, id)?;
-            println!("git: {}", paths.git_dir().display());
-            println!("git includes: {}", paths.git_includes_dir().display());
-            println!("keys: {}", paths.keys_dir().display());
+            output(
+                format,
+                format_args!(
+                    "git: {}\ngit includes: {}\nkeys: {}",
+                    paths.git_dir().display(),
+                    paths.git_includes_dir().display(),
+                    paths.keys_dir().display()
+                ),
+                json!({
+                    "git": paths.git_dir(),
+                    "git_includes": paths.git_includes_dir(),
+                    "keys": paths.keys_dir(),
+                }),
+            );
         },
         Command::Ssh(Ssh { options }) => match options {
             ssh::Options::Add(ssh::Add { id, time }) => {
                 let constraints =
                     time.map_or(vec![], |seconds| vec![Constraint::KeyLifetime { seconds }]);
-                let id = ssh_add(None, id, sock, keys::prompt::new(), &constraints)?;
-                println!("added key for profile id `{}`", id);
+                let id = ssh_add(None, id, sock, passphrases.unlock(), &constraints)?;
+                output(
+                    format,
+                    format_args!("added key for profile id `{}`", id),
+                    json!({ "profile_id": id.to_string(), "added": true }),
+                );
             },
             ssh::Options::Rm(ssh::Rm { id }) => {
-                let id = ssh_remove(None, id, sock, keys::prompt::new())?;
-                println!("removed key for profile id `{}`", id);
+                let id = ssh_remove(None, id, sock, passphrases.unlock())?;
+                output(
+                    format,
+                    format_args!("removed key for profile id `{}`", id),
+                    json!({ "profile_id": id.to_string(), "removed": true }),
+                );
             },
             ssh::Options::Sign(ssh::Sign { id, payload }) => {
                 let (id, sig) = ssh_sign(None, id, sock, payload)?;
-                println!("`{}` signature for profile id `{}`", sig, id);
+                output(
+                    format,
+                    format_args!("`{}` signature for profile id `{}`", sig, id),
+                    json!({ "profile_id": id.to_string(), "signature": sig.to_string() }),
+                );
             },
             ssh::Options::Ready(ssh::Ready { id }) => {
                 let (id, present) = ssh_ready(None, id, sock)?;
-                if present {
-                    println!("key is on ssh-agent for profile id `{}`", id);
+                let plain = if present {
+                    format!("key is on ssh-agent for profile id `{}`", id)
                 } else {
-                    println!("key is *not* on ssh-agent for profile id `{}`", id);
-                }
+                    format!("key is *not* on ssh-agent for profile id `{}`", id)
+                };
+                output(
+                    format,
+                    plain,
+                    json!({ "profile_id": id.to_string(), "ready": present }),
+                );
             },
             ssh::Options::Verify(ssh::Verify {
                 id,
@@ -97,12 +171,16 @@ fn eval(sock: SshAuthSock, command: Command) -> anyhow::Result<()> {
                 let signature: [u8; 64] = signature.as_bytes().try_into()?;
                 let signature = sign::Signature(signature);
                 let (id, verified) = ssh_verify(None, id, payload, signature.into())?;
-
-                if verified {
-                    println!("payload verified for profile id `{}`", id);
+                let plain = if verified {
+                    format!("payload verified for profile id `{}`", id)
                 } else {
-                    println!("payload *not* verified for profile id `{}`", id);
-                }
+                    format!("payload *not* verified for profile id `{}`", id)
+                };
+                output(
+                    format,
+                    plain,
+                    json!({ "profile_id": id.to_string(), "verified": verified }),
+                );
             },
         },
     }
@@ -82,6 +82,15 @@ where
     Ok((profile, PeerId::from(key)))
 }
 
+/// Delete the profile identified by `id`, including its keys and storage.
+pub fn delete<H>(home: H, id: ProfileId) -> Result<(), Error>
+where
+    H: Into<Option<RadHome>>,
+{
+    let home = home.into().unwrap_or_default();
+    Profile::delete(&home, id).map_err(Error::from)
+}
+
 /// Get the current active `ProfileId`.
 pub fn get<H>(home: H, id: Option<ProfileId>) -> Result<Option<Profile>, Error>
 where
@@ -0,0 +1,68 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Rewriting the refs of a 10k ref advertisement, as done on every negotiation
+//! step.
+//!
+//! Run with `cargo bench -p radicle-link-test --bench link_replication_refs`.
+
+use std::time::{Duration, Instant};
+
+use bstr::{BString, ByteSlice as _};
+use link_crypto::{PeerId, SecretKey};
+use link_replication::refs;
+
+const PEERS: usize = 100;
+const REFS_PER_PEER: usize = 100;
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let local = PeerId::from(SecretKey::new());
+    let advertised = (0..PEERS)
+        .map(|_| PeerId::from(SecretKey::new()))
+        .flat_map(|remote| {
+            (0..REFS_PER_PEER).map(move |i| {
+                (
+                    remote,
+                    BString::from(format!("refs/remotes/{}/heads/branch-{}", remote, i)),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    bench("owned", || {
+        for (_, name) in &advertised {
+            refs::owned(name.as_bstr()).unwrap();
+        }
+    });
+    bench("remote_tracking", || {
+        for (remote, name) in &advertised {
+            let owned = refs::owned(name.as_bstr()).unwrap();
+            refs::remote_tracking(remote, owned);
+        }
+    });
+    bench("scoped", || {
+        for (remote, name) in &advertised {
+            refs::scoped(remote, &local, name.as_bstr());
+        }
+    });
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    // warm up
+    f();
+    let mut total = Duration::default();
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        f();
+        total += start.elapsed();
+    }
+    println!(
+        "{}: {:?}/iter over {} refs",
+        name,
+        total / ITERATIONS,
+        PEERS * REFS_PER_PEER
+    );
+}
@@ -8,7 +8,7 @@ use std::collections::BTreeMap;
 
 use librad::{
     git::{
-        refs::{Refs, Remotes},
+        refs::{Freshness, Refs, Remotes},
         types::RefsCategory,
     },
     PeerId,
@@ -80,6 +80,10 @@ fn gen_unknown() -> impl Strategy<Value = BTreeMap<String, BTreeMap<String, Oid>
     proptest::collection::btree_map(proptest::arbitrary::any::<String>(), submap, 0..5)
 }
 
+fn gen_freshness() -> impl Strategy<Value = Freshness> {
+    (any::<u64>(), any::<u64>()).prop_map(|(seq, timestamp)| Freshness { seq, timestamp })
+}
+
 prop_compose! {
     pub fn gen_refs()(
             heads in gen_references(),
@@ -88,7 +92,8 @@ prop_compose! {
             tags in gen_references(),
             rad in gen_references(),
             unknown_categories in gen_unknown(),
-            remotes in gen_remotes()
+            remotes in gen_remotes(),
+            freshness in proptest::option::of(gen_freshness())
         ) -> Refs {
         let mut with_categories = vec![
             (RefsCategory::Heads, heads),
@@ -112,6 +117,7 @@ prop_compose! {
         Refs {
             categorised_refs: all_categories,
             remotes,
+            freshness,
         }
     }
 }
@@ -7,11 +7,7 @@ use proptest::{collection, prelude::*};
 
 use librad::PeerId;
 
-use crate::librad::keys::gen_secret_key;
-
-pub fn gen_peer_id() -> impl Strategy<Value = PeerId> {
-    gen_secret_key().prop_map(PeerId::from)
-}
+pub use librad::arbitrary::gen_peer_id;
 
 pub fn gen_peers() -> impl Strategy<Value = (PeerId, Vec<PeerId>)> {
     gen_peer_id().prop_flat_map(move |local| {
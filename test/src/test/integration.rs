@@ -7,6 +7,7 @@ mod daemon;
 mod git_helpers;
 mod librad;
 mod link_git;
+mod link_replication;
 mod node_lib;
 #[cfg(unix)]
 mod rad_cli;
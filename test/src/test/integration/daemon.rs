@@ -5,5 +5,6 @@
 
 mod gossip;
 mod internal;
+mod mirror;
 mod replication;
 mod working_copy;
@@ -0,0 +1,50 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use pretty_assertions::assert_eq;
+use radicle_daemon::{mirror, state, RunConfig};
+
+use crate::{
+    daemon::common::{shia_le_pathbuf, Harness},
+    logging,
+};
+
+#[test]
+fn mirrors_delegate_heads() -> Result<(), anyhow::Error> {
+    logging::init();
+
+    let mut harness = Harness::new();
+    let alice = harness.add_peer("alice", RunConfig::default(), &[])?;
+    let bob = harness.add_peer("bob", RunConfig::default(), &[])?;
+    let external = tempfile::tempdir()?;
+    let store = tempfile::tempdir()?;
+    harness.enter(async move {
+        let project = state::init_project(
+            &alice.peer,
+            &alice.owner,
+            shia_le_pathbuf(alice.path.join("radicle")),
+        )
+        .await?;
+        state::clone_project(&bob.peer, project.urn(), alice.peer_id, alice.listen_addrs).await?;
+
+        let external_repo = git2::Repository::init_bare(external.path())?;
+        let store = kv::Store::new(kv::Config::new(store.path().join("store")))?;
+        mirror::add(
+            &store,
+            &project.urn(),
+            mirror::Remote {
+                url: format!("file://{}", external.path().display()),
+            },
+        )?;
+        mirror::mirror(&bob.peer, store, project.urn(), None).await?;
+
+        let default = external_repo.refname_to_id("refs/heads/it")?;
+        let alice_head =
+            external_repo.refname_to_id(&format!("refs/remotes/{}/heads/it", alice.peer_id))?;
+        assert_eq!(default, alice_head);
+
+        Ok(())
+    })
+}
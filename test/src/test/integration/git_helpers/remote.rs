@@ -140,7 +140,13 @@ fn setup_path() -> anyhow::Result<PathBuf> {
 
     if !helper_path.join("git-remote-rad").exists() {
         std::process::Command::new("cargo")
-            .args(&["build", "--bin", "git-remote-rad"])
+            .args(&[
+                "build",
+                "--package",
+                "radicle-git-helpers",
+                "--bin",
+                "git-remote-rad",
+            ])
             .output()?;
     }
     let path = match env::var_os("PATH") {
@@ -7,4 +7,6 @@ mod clone;
 mod fetch_limit;
 mod gossip;
 mod interrogation;
+#[cfg(feature = "replication-v3")]
+mod progress;
 mod regression;
@@ -12,6 +12,7 @@ use librad::{
         storage::ReadOnlyStorage as _,
         types::{Namespace, Reference},
     },
+    identities::payload,
 };
 
 use crate::{
@@ -139,6 +140,63 @@ fn when_disconnected_and_no_addr_hints() {
     ))
 }
 
+#[test]
+fn many() {
+    logging::init();
+
+    let net = testnet::run(default_config()).unwrap();
+    net.enter(async {
+        let host = Host::init(&net.peers()[0]).await;
+        let other = {
+            let owner = host.project.owner.clone();
+            host.peer
+                .using_storage(move |storage| {
+                    TestProject::from_project_payload(
+                        storage,
+                        owner,
+                        payload::Project {
+                            name: "radicle-surf".into(),
+                            description: None,
+                            default_branch: Some("main".into()),
+                        },
+                    )
+                })
+                .await
+                .unwrap()
+                .unwrap()
+        };
+        let urns = vec![host.project.project.urn(), other.project.urn()];
+        let host_peer = host.peer.peer_id();
+        let host_addrs = host.peer.listen_addrs().iter().copied().collect::<Vec<_>>();
+
+        let leecher = &net.peers()[1];
+        let res = leecher
+            .replicate_many((host_peer, host_addrs), urns.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            res.iter().map(|(urn, _)| urn.clone()).collect::<Vec<_>>(),
+            urns
+        );
+        assert!(res.iter().all(|(_, res)| res.is_ok()));
+
+        leecher
+            .using_storage(move |storage| {
+                for urn in &urns {
+                    assert!(
+                        storage
+                            .has_ref(&Reference::rad_self(Namespace::from(urn), host_peer))
+                            .unwrap(),
+                        "`refs/remotes/<host>/rad/self` should exist for {}",
+                        urn
+                    );
+                }
+            })
+            .await
+            .unwrap();
+    })
+}
+
 struct Host<'a> {
     project: TestProject,
     peer: &'a RunningTestPeer,
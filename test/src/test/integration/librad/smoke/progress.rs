@@ -0,0 +1,127 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    ops::Index as _,
+    sync::{Arc, Mutex},
+};
+
+use librad::{
+    git::{refs::Refs, storage::Storage, Urn},
+    net::replication::progress::{Event, Reporter},
+};
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+/// Commit to a new branch of `urn`, and update the signed refs.
+fn commit(storage: &Storage, urn: &Urn) -> anyhow::Result<()> {
+    let repo = git2::Repository::open(storage.path())?;
+    let tree = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+    let sig = git2::Signature::now("alice", "alice@example.com")?;
+    repo.commit(
+        Some(&format!(
+            "refs/namespaces/{}/refs/heads/progress",
+            urn.encode_id()
+        )),
+        &sig,
+        &sig,
+        "progress",
+        &tree,
+        &[],
+    )?;
+    Refs::update(storage, urn)?;
+
+    Ok(())
+}
+
+#[test]
+fn pull_reports_progress() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let host = net.peers().index(0);
+        let leecher = net.peers().index(1);
+
+        let proj = host
+            .using_storage(TestProject::create)
+            .await
+            .unwrap()
+            .unwrap();
+        proj.pull(host, leecher).await.unwrap();
+
+        let urn = proj.project.urn();
+        host.using_storage({
+            let urn = urn.clone();
+            move |storage| commit(storage, &urn)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        leecher
+            .replicate_with_progress(
+                (host.peer_id(), host.listen_addrs().to_vec()),
+                urn,
+                None,
+                Reporter::new({
+                    let events = events.clone();
+                    move |event| events.lock().unwrap().push(event)
+                }),
+            )
+            .await
+            .unwrap();
+        let events = events.lock().unwrap().clone();
+
+        // Every fetch asks for the refs first, and receives the advertisement
+        // and the pack in increments
+        assert_eq!(events.first(), Some(&Event::LsRefs));
+        let mut asked = false;
+        let mut received = 0;
+        let mut wants_haves = 0;
+        for event in &events {
+            match event {
+                Event::LsRefs => asked = true,
+                Event::Received { bytes } => {
+                    assert!(*bytes > 0);
+                    received += 1;
+                },
+                Event::WantsHaves { wants, .. } => {
+                    assert!(asked, "wants computed before asking for refs: {:?}", events);
+                    assert!(*wants > 0);
+                    wants_haves += 1;
+                },
+                _ => {},
+            }
+        }
+        assert!(received > 0, "no bytes received: {:?}", events);
+        assert!(wants_haves > 0, "nothing wanted: {:?}", events);
+
+        // Only then are the refs validated and applied
+        let phases = events
+            .iter()
+            .filter(|event| !matches!(event, Event::Received { .. }))
+            .collect::<Vec<_>>();
+        match phases.as_slice() {
+            [.., Event::Validated { warnings: 0 }, Event::Applied {
+                updated,
+                rejected: 0,
+            }] => assert!(*updated > 0),
+            _ => panic!("unexpected event sequence: {:?}", events),
+        }
+    })
+}
@@ -12,12 +12,26 @@ use std::{
 
 use bstr::ByteSlice as _;
 use futures::{AsyncReadExt as _, TryFutureExt as _};
+use futures_lite::io::AsyncBufRead;
 use git_repository::{
     self as git,
     prelude::*,
     refs::transaction::{Change, PreviousValue, RefEdit},
 };
-use link_git::protocol::{fetch, ls, packwriter, upload_pack, ObjectId, PackWriter, Ref};
+use link_git::protocol::{
+    bundle_uri,
+    fetch,
+    ls,
+    object_info,
+    packwriter,
+    push,
+    receive_pack,
+    upload_pack,
+    ObjectId,
+    PackWriter,
+    Ref,
+    RefExt,
+};
 use tempfile::{tempdir, TempDir};
 
 fn upstream() -> TempDir {
@@ -110,7 +124,7 @@ fn run_ls_refs<R: AsRef<Path>>(remote: R, opt: ls::Options) -> io::Result<Vec<Re
     let (client, server) = futures_ringbuf::Endpoint::pair(256, 256);
     let client = async move {
         let (recv, send) = client.split();
-        ls::ls_refs(opt, recv, send).await
+        ls::ls_refs(opt, recv, send).await.map(|out| out.refs)
     };
     let server = {
         let (recv, send) = server.split();
@@ -150,6 +164,72 @@ where
     Ok(client_out)
 }
 
+/// Migrates the loose objects unpacked by the `inner` [`packwriter::Standard`],
+/// which must have an [`packwriter::Options::unpack_limit`] above the number of
+/// objects received.
+struct Unpacking<F> {
+    git_dir: PathBuf,
+    inner: packwriter::Standard<F>,
+}
+
+impl<F: packwriter::BuildThickener> PackWriter for Unpacking<F> {
+    type Output = u32;
+
+    fn write_pack(
+        &self,
+        pack: impl AsyncBufRead + Unpin,
+        prog: impl git::Progress,
+    ) -> io::Result<Self::Output> {
+        match self.inner.write_pack(pack, prog)? {
+            packwriter::Received::Loose(objects) => {
+                let num_objects = objects.num_objects();
+                objects.migrate(&self.git_dir)?;
+                Ok(num_objects)
+            },
+            packwriter::Received::Pack(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "packfile was not unpacked",
+            )),
+        }
+    }
+}
+
+fn unpacking(git_dir: &Path, stop: Arc<AtomicBool>) -> Unpacking<packwriter::StandardThickener> {
+    Unpacking {
+        git_dir: git_dir.to_owned(),
+        inner: packwriter::Standard::new(
+            git_dir,
+            packwriter::Options {
+                unpack_limit: Some(u32::MAX),
+                ..Default::default()
+            },
+            packwriter::StandardThickener::new(git_dir),
+            stop,
+        ),
+    }
+}
+
+fn run_push<R, L>(remote: R, local: L, opt: push::Options) -> io::Result<push::Outputs>
+where
+    R: AsRef<Path>,
+    L: Into<PathBuf>,
+{
+    let (client, server) = futures_ringbuf::Endpoint::pair(256, 256);
+    let client = async move {
+        let (recv, send) = client.split();
+        push::push(opt, push::PackObjects::new(local), recv, send).await
+    };
+    let server = {
+        let (recv, send) = server.split();
+        receive_pack::receive_pack(&remote, recv, send).and_then(|(_hdr, run)| run)
+    };
+
+    let (client_out, server_out) =
+        futures::executor::block_on(futures::future::try_join(client, server))?;
+    assert!(server_out.success());
+    Ok(client_out)
+}
+
 #[test]
 fn smoke() {
     let remote = upstream();
@@ -182,6 +262,9 @@ fn smoke() {
             haves: vec![],
             wants: vec![],
             want_refs: refs.iter().map(|r| r.unpack().0.clone()).collect(),
+            filter: None,
+            on_progress: None,
+            flow_control: Default::default(),
         },
         |_| packwriter::Discard,
     )
@@ -190,6 +273,53 @@ fn smoke() {
     assert!(out.pack.is_some());
 }
 
+#[test]
+fn symrefs_and_peeled() {
+    let remote = upstream();
+    let (tag, main) = {
+        let repo = git2::Repository::open(&remote).unwrap();
+        let main = repo
+            .refname_to_id("refs/namespaces/foo/refs/heads/main")
+            .unwrap();
+        repo.reference_symbolic(
+            "refs/namespaces/foo/HEAD",
+            "refs/namespaces/foo/refs/heads/main",
+            false,
+            "HEAD",
+        )
+        .unwrap();
+        let tag = repo
+            .tag_annotation_create(
+                "v1",
+                &repo.find_object(main, None).unwrap(),
+                &git2::Signature::now("apollo", "apollo@cree.de").unwrap(),
+                "v1",
+            )
+            .unwrap();
+        repo.reference("refs/namespaces/foo/refs/tags/v1", tag, false, "v1")
+            .unwrap();
+        (tag, main)
+    };
+
+    let refs = run_ls_refs(
+        &remote,
+        ls::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            ref_prefixes: vec!["HEAD".into(), "refs/tags/".into()],
+        },
+    )
+    .unwrap();
+
+    let head = refs.iter().find(|r| r.name() == "HEAD").unwrap();
+    assert_eq!(head.symref_target(), Some("refs/heads/main".into()));
+    assert_eq!(head.object().as_bytes(), main.as_bytes());
+
+    let v1 = refs.iter().find(|r| r.name() == "refs/tags/v1").unwrap();
+    assert_eq!(v1.object().as_bytes(), tag.as_bytes());
+    assert_eq!(v1.peeled().map(|oid| oid.as_bytes()), Some(main.as_bytes()));
+}
+
 #[test]
 fn want_ref() {
     let remote = upstream();
@@ -201,6 +331,9 @@ fn want_ref() {
             haves: vec![],
             wants: vec![],
             want_refs: vec!["refs/heads/main".into(), "refs/pulls/1/head".into()],
+            filter: None,
+            on_progress: None,
+            flow_control: Default::default(),
         },
         |_| packwriter::Discard,
     )
@@ -218,6 +351,139 @@ fn want_ref() {
     )
 }
 
+#[test]
+fn tight_flow_control() {
+    let remote = upstream();
+    let out = run_fetch(
+        &remote,
+        fetch::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            haves: vec![],
+            wants: vec![],
+            want_refs: vec!["refs/heads/next".into()],
+            filter: None,
+            on_progress: None,
+            flow_control: fetch::FlowControl {
+                read_buffer_size: 7,
+                max_in_flight_bytes: 1,
+            },
+        },
+        |_| packwriter::Discard,
+    )
+    .unwrap();
+
+    assert!(out.pack.unwrap() > 0);
+    assert_eq!(out.wanted_refs.len(), 1);
+}
+
+#[test]
+fn filter() {
+    let remote = upstream();
+    let out = run_fetch(
+        &remote,
+        fetch::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            haves: vec![],
+            wants: vec![],
+            want_refs: vec!["refs/heads/main".into()],
+            filter: Some("blob:none".into()),
+            on_progress: None,
+            flow_control: Default::default(),
+        },
+        |_| packwriter::Discard,
+    )
+    .unwrap();
+
+    assert!(out.capabilities.filter);
+    assert!(out.capabilities.ref_in_want);
+    assert!(out.filtered);
+    assert!(out.pack.is_some());
+}
+
+#[test]
+fn filter_then_fetch_missing() {
+    let remote = upstream();
+    let blob = {
+        let repo = git2::Repository::open(&remote).unwrap();
+        let sig = git2::Signature::now("apollo", "apollo@cree.de").unwrap();
+        let blob = repo
+            .blob(b"the world is everything that is the case")
+            .unwrap();
+        let tree = {
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder.insert("README", blob, 0o100644).unwrap();
+            repo.find_tree(builder.write().unwrap()).unwrap()
+        };
+        repo.commit(
+            Some("refs/namespaces/foo/refs/heads/files"),
+            &sig,
+            &sig,
+            "files",
+            &tree,
+            &[],
+        )
+        .unwrap();
+        ObjectId::from_20_bytes(blob.as_bytes())
+    };
+
+    let local = tempdir().unwrap();
+    let local_repo = git2::Repository::init_bare(&local).unwrap();
+    let has_blob = || {
+        local_repo
+            .odb()
+            .unwrap()
+            .exists(git2::Oid::from_bytes(blob.as_bytes()).unwrap())
+    };
+    let pack_writer = |stop: Arc<AtomicBool>| {
+        let git_dir = local.path();
+        packwriter::Standard::new(
+            git_dir,
+            packwriter::Options::default(),
+            packwriter::StandardThickener::new(git_dir),
+            stop,
+        )
+    };
+
+    // Blobless
+    let out = run_fetch(
+        &remote,
+        fetch::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            haves: vec![],
+            wants: vec![],
+            want_refs: vec!["refs/heads/files".into()],
+            filter: Some("blob:none".into()),
+            on_progress: None,
+            flow_control: Default::default(),
+        },
+        pack_writer,
+    )
+    .unwrap();
+    assert!(out.filtered);
+    assert!(!has_blob());
+
+    // Fill in the missing blob, as a promisor remote would
+    run_fetch(
+        &remote,
+        fetch::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            haves: vec![],
+            wants: vec![blob],
+            want_refs: vec![],
+            filter: None,
+            on_progress: None,
+            flow_control: Default::default(),
+        },
+        pack_writer,
+    )
+    .unwrap();
+    assert!(has_blob());
+}
+
 #[test]
 #[should_panic(expected = "`fetch` is empty")]
 fn empty_fetch() {
@@ -230,6 +496,9 @@ fn empty_fetch() {
             haves: vec![],
             wants: vec![],
             want_refs: vec![],
+            filter: None,
+            on_progress: None,
+            flow_control: Default::default(),
         },
         |_| packwriter::Discard,
     )
@@ -262,6 +531,9 @@ where
             haves: vec![],
             wants: vec![],
             want_refs: refs.iter().map(|r| r.unpack().0.clone()).collect(),
+            filter: None,
+            on_progress: None,
+            flow_control: Default::default(),
         },
         build_pack_writer,
     )
@@ -312,6 +584,24 @@ fn clone_gitoxide() {
     })
 }
 
+#[test]
+fn clone_gitoxide_unpacked() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let local_repo = git::init(&local).unwrap();
+    let git_dir = local_repo.path().to_owned();
+
+    clone_with(remote.path(), &local.path(), move |stop| {
+        unpacking(local_repo.path(), stop)
+    });
+
+    let packs = std::fs::read_dir(git_dir.join("objects").join("pack"))
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("pack".as_ref()))
+        .count();
+    assert_eq!(0, packs)
+}
+
 fn thin_pack_with<R, L, B, P>(remote: R, local: L, build_pack_writer: B)
 where
     R: Into<PathBuf>,
@@ -331,6 +621,9 @@ where
                 haves: vec![],
                 wants: vec![],
                 want_refs: vec!["refs/heads/main".into()],
+                filter: None,
+                on_progress: None,
+                flow_control: Default::default(),
             },
             &build_pack_writer,
         )
@@ -357,6 +650,9 @@ where
                 haves: vec![ObjectId::from_20_bytes(head.as_bytes())],
                 wants: vec![],
                 want_refs: vec!["refs/heads/next".into()],
+                filter: None,
+                on_progress: None,
+                flow_control: Default::default(),
             },
             build_pack_writer,
         )
@@ -402,3 +698,350 @@ fn thin_pack_gitoxide() {
         )
     });
 }
+
+#[test]
+fn thin_pack_gitoxide_unpacked() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let local_repo = git::init(&local).unwrap();
+
+    thin_pack_with(remote.path(), local.path(), move |stop| {
+        unpacking(local_repo.path(), stop)
+    });
+}
+
+#[test]
+fn thin_pack_gitoxide_bounded() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let local_repo = git::init(&local).unwrap();
+
+    // A cache this small spills every base object to disk
+    thin_pack_with(remote.path(), local.path(), move |stop| {
+        let git_dir = local_repo.path();
+        packwriter::Standard::new(
+            git_dir,
+            packwriter::Options {
+                base_cache: Some(packwriter::BaseCache {
+                    capacity: 1,
+                    spill: true,
+                }),
+                ..Default::default()
+            },
+            packwriter::StandardThickener::new(git_dir),
+            stop,
+        )
+    });
+}
+
+#[test]
+fn thin_pack_gitoxide_cached() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let local_repo = git::init(&local).unwrap();
+
+    thin_pack_with(remote.path(), local.path(), move |stop| {
+        let git_dir = local_repo.path();
+        packwriter::Standard::new(
+            git_dir,
+            packwriter::Options {
+                base_cache: Some(packwriter::BaseCache {
+                    capacity: 1024 * 1024,
+                    spill: false,
+                }),
+                ..Default::default()
+            },
+            packwriter::StandardThickener::new(git_dir),
+            stop,
+        )
+    });
+}
+
+/// Create a root commit in a new repo at `path`.
+fn local_commit(path: &Path) -> ObjectId {
+    let repo = git2::Repository::init_bare(path).unwrap();
+    let sig = git2::Signature::now("apollo", "apollo@cree.de").unwrap();
+    let tree = repo
+        .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let oid = repo
+        .commit(Some("refs/heads/fresh"), &sig, &sig, "fresh", &tree, &[])
+        .unwrap();
+    ObjectId::from_20_bytes(oid.as_bytes())
+}
+
+fn remote_ref(remote: &Path, name: &str) -> Option<ObjectId> {
+    let repo = git2::Repository::open(remote).unwrap();
+    repo.refname_to_id(&format!("refs/namespaces/foo/{}", name))
+        .ok()
+        .map(|oid| ObjectId::from_20_bytes(oid.as_bytes()))
+}
+
+#[test]
+fn push_create_and_delete() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let head = local_commit(local.path());
+
+    let out = run_push(
+        &remote,
+        local.path(),
+        push::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            updates: vec![
+                push::Update {
+                    name: "refs/heads/fresh".into(),
+                    old: None,
+                    new: Some(head),
+                },
+                push::Update {
+                    name: "refs/pulls/1/head".into(),
+                    old: None,
+                    new: None,
+                },
+            ],
+            atomic: false,
+            push_options: vec![],
+        },
+    )
+    .unwrap();
+
+    assert!(out.refs.iter().any(|r| r.name() == "refs/heads/main"));
+    assert_eq!(out.unpack, Some(Ok(())));
+    assert_eq!(
+        out.statuses
+            .iter()
+            .map(|status| (status.name.clone(), status.result.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("refs/heads/fresh".into(), Ok(())),
+            ("refs/pulls/1/head".into(), Ok(()))
+        ]
+    );
+    assert_eq!(remote_ref(remote.path(), "refs/heads/fresh"), Some(head));
+    assert_eq!(remote_ref(remote.path(), "refs/pulls/1/head"), None);
+}
+
+#[test]
+fn push_stale_is_rejected() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let head = local_commit(local.path());
+
+    let main = remote_ref(remote.path(), "refs/heads/main");
+    let out = run_push(
+        &remote,
+        local.path(),
+        push::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            updates: vec![push::Update {
+                name: "refs/heads/main".into(),
+                old: Some(head),
+                new: Some(head),
+            }],
+            atomic: false,
+            push_options: vec![],
+        },
+    )
+    .unwrap();
+
+    assert_eq!(out.statuses.len(), 1);
+    assert!(out.statuses[0].result.is_err());
+    assert_eq!(remote_ref(remote.path(), "refs/heads/main"), main);
+}
+
+#[test]
+fn push_atomic_with_options() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let head = local_commit(local.path());
+
+    let out = run_push(
+        &remote,
+        local.path(),
+        push::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            updates: vec![
+                push::Update {
+                    name: "refs/heads/fresh".into(),
+                    old: None,
+                    new: Some(head),
+                },
+                push::Update {
+                    name: "refs/heads/main".into(),
+                    old: Some(head),
+                    new: Some(head),
+                },
+            ],
+            atomic: true,
+            push_options: vec!["ci.skip".into()],
+        },
+    )
+    .unwrap();
+
+    assert_eq!(out.statuses.len(), 2);
+    assert!(out.statuses.iter().all(|status| status.result.is_err()));
+    assert_eq!(remote_ref(remote.path(), "refs/heads/fresh"), None);
+}
+
+#[test]
+fn object_info() {
+    let remote = upstream();
+    let (main, elsewhere) = {
+        let repo = git2::Repository::open(&remote).unwrap();
+        let main = repo
+            .refname_to_id("refs/namespaces/foo/refs/heads/main")
+            .unwrap();
+        // Present in the odb, but not reachable from `foo`
+        let elsewhere = {
+            let sig = git2::Signature::now("apollo", "apollo@cree.de").unwrap();
+            let tree = repo
+                .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+                .unwrap();
+            repo.commit(
+                Some("refs/namespaces/bar/refs/heads/main"),
+                &sig,
+                &sig,
+                "elsewhere",
+                &tree,
+                &[],
+            )
+            .unwrap()
+        };
+        (
+            ObjectId::from_20_bytes(main.as_bytes()),
+            ObjectId::from_20_bytes(elsewhere.as_bytes()),
+        )
+    };
+    let missing = ObjectId::from_hex(b"badc0ffee0ddf00dbadc0ffee0ddf00dbadc0ffe").unwrap();
+
+    let (client, server) = futures_ringbuf::Endpoint::pair(256, 256);
+    let client = async move {
+        let (recv, send) = client.split();
+        object_info::object_info(
+            object_info::Options {
+                repo: "foo".into(),
+                extra_params: vec![],
+                oids: vec![main, elsewhere, missing],
+            },
+            recv,
+            send,
+        )
+        .await
+    };
+    let server = {
+        let (recv, send) = server.split();
+        upload_pack::upload_pack(&remote, recv, send).and_then(|(_hdr, run)| run)
+    };
+    let (out, status) =
+        futures::executor::block_on(futures::future::try_join(client, server)).unwrap();
+    assert!(status.success());
+
+    assert_eq!(out.present().collect::<Vec<_>>(), vec![&main]);
+    assert_eq!(out.sizes.get(&elsewhere), Some(&None));
+    assert_eq!(out.sizes.get(&missing), Some(&None));
+}
+
+/// Create a bundle of `refs/namespaces/foo/refs/heads/main` of `remote` in
+/// `dir`, returning its path and the tip.
+fn bundle_main(remote: &Path, dir: &Path) -> (PathBuf, ObjectId) {
+    let bundle_path = dir.join("foo.bundle");
+    let main = {
+        let repo = git2::Repository::open(remote).unwrap();
+        let oid = repo
+            .refname_to_id("refs/namespaces/foo/refs/heads/main")
+            .unwrap();
+        ObjectId::from_20_bytes(oid.as_bytes())
+    };
+    let status = std::process::Command::new("git")
+        .current_dir(remote)
+        .args(&[
+            "bundle",
+            "create",
+            bundle_path.to_str().unwrap(),
+            "refs/namespaces/foo/refs/heads/main",
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    (bundle_path, main)
+}
+
+#[test]
+#[ignore = "requires git >= 2.40 to serve `bundle-uri`"]
+fn bundle_uri_advertised() {
+    let remote = upstream();
+    let bundles = tempdir().unwrap();
+    let (bundle_path, _) = bundle_main(remote.path(), bundles.path());
+    let status = std::process::Command::new("git")
+        .current_dir(&remote)
+        .args(&[
+            "config",
+            "--add",
+            "bundle-uri.foo.uri",
+            bundle_path.to_str().unwrap(),
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let (client, server) = futures_ringbuf::Endpoint::pair(256, 256);
+    let client = async move {
+        let (recv, send) = client.split();
+        bundle_uri::bundle_uri(
+            bundle_uri::Options {
+                repo: "foo".into(),
+                extra_params: vec![],
+            },
+            recv,
+            send,
+        )
+        .await
+    };
+    let server = {
+        let (recv, send) = server.split();
+        upload_pack::upload_pack(&remote, recv, send).and_then(|(_hdr, run)| run)
+    };
+    let (list, status) =
+        futures::executor::block_on(futures::future::try_join(client, server)).unwrap();
+    assert!(status.success());
+    assert_eq!(list.bundles.len(), 1);
+    assert_eq!(list.bundles[0].uri, bundle_path.to_str().unwrap());
+}
+
+#[test]
+fn bundle_unbundle() {
+    let remote = upstream();
+    let bundles = tempdir().unwrap();
+    let (bundle_path, main) = bundle_main(remote.path(), bundles.path());
+
+    let local = tempdir().unwrap();
+    let local_repo = git2::Repository::init_bare(&local).unwrap();
+    let mut reader = io::BufReader::new(std::fs::File::open(&bundle_path).unwrap());
+    let header = bundle_uri::read_header(&mut reader).unwrap();
+    assert!(header.prerequisites.is_empty());
+    assert_eq!(
+        header.refs,
+        vec![("refs/namespaces/foo/refs/heads/main".into(), main)]
+    );
+    let git_dir = local.path();
+    let pack = bundle_uri::unbundle(
+        reader,
+        &packwriter::Standard::new(
+            git_dir,
+            packwriter::Options::default(),
+            packwriter::StandardThickener::new(git_dir),
+            Arc::new(AtomicBool::new(false)),
+        ),
+    )
+    .unwrap();
+    assert!(pack.index_path.is_some());
+    assert!(local_repo
+        .odb()
+        .unwrap()
+        .exists(git2::Oid::from_bytes(main.as_bytes()).unwrap()));
+}
@@ -0,0 +1,13 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+#[cfg(unix)]
+mod faulty;
+#[cfg(unix)]
+mod preopen;
+#[cfg(unix)]
+mod session;
+#[cfg(unix)]
+mod unix;
@@ -0,0 +1,87 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use futures::executor::block_on;
+use link_replication::io::{session::Session, Faults, Faulty, Replay};
+use tempfile::tempdir;
+
+use super::session::{contains, fetch, network, record};
+
+#[test]
+fn disconnect_fails_the_fetch() {
+    let tmp = tempdir().unwrap();
+    let (session, head) = record(tmp.path());
+
+    let local = tmp.path().join("local");
+    let conn = Faulty::new(
+        Replay::load(&session).unwrap(),
+        Faults {
+            disconnect: 1.0,
+            ..Faults::default()
+        },
+    );
+    let err = block_on(fetch(&network(&local, conn))).unwrap_err();
+    // Not taken to be the fault of the remote end, so the replication can be
+    // retried
+    assert_ne!(io::ErrorKind::InvalidData, err.kind());
+    assert!(!contains(&local, head));
+}
+
+#[test]
+fn truncated_pack_is_not_applied_and_fetch_resumes() {
+    let tmp = tempdir().unwrap();
+    let (session, head) = record(tmp.path());
+
+    // Hang up in the middle of the `fetch` response, but not the `ls-refs` one
+    let truncate_after = {
+        let Session { streams } = Session::load(&session).unwrap();
+        let (ls, fetch) = (&streams[0].recv, &streams[1].recv);
+        assert!(ls.len() < fetch.len() / 2);
+        fetch.len() as u64 / 2
+    };
+
+    let local = tmp.path().join("local");
+    let conn = Faulty::new(
+        Replay::load(&session).unwrap(),
+        Faults {
+            truncate_after: Some(truncate_after),
+            ..Faults::default()
+        },
+    );
+    let err = block_on(fetch(&network(&local, conn))).unwrap_err();
+    assert_ne!(io::ErrorKind::InvalidData, err.kind());
+    assert!(!contains(&local, head));
+
+    // Fetching again over a sound connection succeeds
+    let conn = Replay::load(&session).unwrap().strict();
+    assert_eq!(vec![head], block_on(fetch(&network(&local, conn))).unwrap());
+    assert!(contains(&local, head));
+}
+
+#[test]
+fn latency_delays_but_does_not_fail_the_fetch() {
+    let tmp = tempdir().unwrap();
+    let (session, head) = record(tmp.path());
+
+    let latency = Duration::from_millis(20);
+    let local = tmp.path().join("local");
+    let conn = Faulty::new(
+        Replay::load(&session).unwrap().strict(),
+        Faults {
+            latency: Some(latency),
+            ..Faults::default()
+        },
+    );
+    let start = Instant::now();
+    assert_eq!(vec![head], block_on(fetch(&network(&local, conn))).unwrap());
+    // Opening and the first response of both `ls-refs` and `fetch`
+    assert!(start.elapsed() >= 4 * latency);
+    assert!(contains(&local, head));
+}
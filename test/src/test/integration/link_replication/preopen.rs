@@ -0,0 +1,152 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use async_net::unix::{UnixListener, UnixStream};
+use async_trait::async_trait;
+use futures::{executor::block_on, future, TryFutureExt as _};
+use link_crypto::{PeerId, SecretKey};
+use link_git::protocol::{upload_pack, Ref};
+use link_replication::{
+    io::{self as rio, Connection, Unix, UserInfo},
+    namespace,
+    refs,
+    sim,
+    FilteredRef,
+    Negotiation,
+    Net as _,
+    Refdb,
+    SkippedFetch,
+    WantsHaves,
+};
+use tempfile::tempdir;
+
+/// A [`Unix`] connection counting the streams opened on it.
+struct Counting {
+    inner: Unix,
+    preopen: bool,
+    opened: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Connection for Counting {
+    type Read = UnixStream;
+    type Write = UnixStream;
+    type Error = io::Error;
+
+    async fn open_stream(&self) -> Result<(Self::Read, Self::Write), Self::Error> {
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        self.inner.open_stream().await
+    }
+
+    fn preopen_fetch_stream(&self) -> bool {
+        self.preopen
+    }
+}
+
+/// Asks for nothing, so `run_fetch` stops after `ls-refs`.
+struct Nothing;
+
+impl Negotiation for Nothing {
+    fn ref_prefixes(&self) -> Vec<refs::Scoped<'_, '_>> {
+        vec![]
+    }
+
+    fn ref_filter(&self, _: Ref) -> Option<FilteredRef<Self>> {
+        None
+    }
+
+    fn wants_haves<R: Refdb>(
+        &self,
+        _: &R,
+        _: impl IntoIterator<Item = FilteredRef<Self>>,
+    ) -> Result<WantsHaves<Self>, R::FindError> {
+        Ok(WantsHaves {
+            wanted: HashSet::new(),
+            wants: BTreeSet::new(),
+            haves: BTreeSet::new(),
+        })
+    }
+
+    fn fetch_limit(&self) -> u64 {
+        0
+    }
+}
+
+/// Run an `ls-refs` against an empty repository, returning the number of
+/// streams opened.
+fn streams_opened(tmp: &Path, preopen: bool) -> usize {
+    let remote = tmp.join("remote");
+    let local = tmp.join("local");
+    git2::Repository::init_bare(&remote).unwrap();
+    git2::Repository::init_bare(&local).unwrap();
+
+    let sock = tmp.join("link.sock");
+    let listener = UnixListener::bind(&sock).unwrap();
+    let opened = Arc::new(AtomicUsize::new(0));
+    let conn = Counting {
+        inner: Unix::new(&sock),
+        preopen,
+        opened: opened.clone(),
+    };
+    let odb = rio::Odb::open(&local).unwrap();
+    let db = rio::Refdb::new(
+        UserInfo {
+            name: "leecher".to_owned(),
+            peer_id: PeerId::from(SecretKey::new()),
+        },
+        odb,
+        link_git::refs::db::Refdb::open(&local).unwrap(),
+        namespace::expand("foo").unwrap(),
+    )
+    .unwrap();
+    let net = rio::Network::new(db, conn, &local, sim::Urn("foo".to_owned()));
+
+    let expected = if preopen { 2 } else { 1 };
+    let server = async {
+        // Accept all streams before serving any, as the preopened one may
+        // connect first
+        let mut streams = Vec::new();
+        for _ in 0..expected {
+            streams.push(listener.accept().await?.0);
+        }
+        // The preopened stream is dropped without a request, failing its
+        // `upload-pack`
+        future::join_all(streams.into_iter().map(|stream| {
+            upload_pack(&remote, stream.clone(), stream).and_then(|(_hdr, run)| run)
+        }))
+        .await;
+        Ok::<_, io::Error>(())
+    };
+    let client = async {
+        let (_, res) = net.run_fetch(Nothing).await?;
+        assert!(matches!(res, Err(SkippedFetch::NoMatchingRefs)));
+        Ok::<_, io::Error>(())
+    };
+    block_on(future::try_join(server, client)).unwrap();
+
+    opened.load(Ordering::Relaxed)
+}
+
+#[test]
+fn fetch_stream_is_preopened() {
+    let tmp = tempdir().unwrap();
+    assert_eq!(2, streams_opened(tmp.path(), true));
+}
+
+#[test]
+fn fetch_stream_is_opened_on_demand() {
+    let tmp = tempdir().unwrap();
+    assert_eq!(1, streams_opened(tmp.path(), false));
+}
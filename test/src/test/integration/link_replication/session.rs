@@ -0,0 +1,157 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
+
+use async_net::unix::UnixListener;
+use futures::{executor::block_on, future, TryFutureExt as _};
+use link_crypto::{PeerId, SecretKey};
+use link_git::protocol::{upload_pack, ObjectId, Ref};
+use link_replication::{
+    io::{self as rio, Connection, Recorder, Replay, Unix, UserInfo},
+    namespace,
+    refs,
+    sim,
+    FilteredRef,
+    Negotiation,
+    Net as _,
+    Refdb,
+    WantsHaves,
+};
+use tempfile::tempdir;
+
+/// Wants the tips of all advertised branches.
+struct Heads {
+    remote_id: PeerId,
+}
+
+impl Negotiation for Heads {
+    fn ref_prefixes(&self) -> Vec<refs::Scoped<'_, '_>> {
+        vec![]
+    }
+
+    fn ref_filter(&self, r: Ref) -> Option<FilteredRef<Self>> {
+        let (refname, tip) = refs::into_unpacked(r);
+        let parsed = refs::parse::<refs::parsed::Identity>(refname.as_ref())?;
+        Some(FilteredRef::new(refname, tip, &self.remote_id, parsed))
+    }
+
+    fn wants_haves<R: Refdb>(
+        &self,
+        _: &R,
+        refs: impl IntoIterator<Item = FilteredRef<Self>>,
+    ) -> Result<WantsHaves<Self>, R::FindError> {
+        let wanted = refs.into_iter().collect::<HashSet<_>>();
+        Ok(WantsHaves {
+            wants: wanted.iter().map(|r| r.tip).collect(),
+            wanted,
+            haves: BTreeSet::new(),
+        })
+    }
+
+    fn fetch_limit(&self) -> u64 {
+        u64::MAX
+    }
+}
+
+pub(super) type Network<C> = rio::Network<sim::Urn, rio::Refdb<rio::Odb>, rio::Odb, C>;
+
+pub(super) fn network<C>(git_dir: &Path, conn: C) -> Network<C> {
+    git2::Repository::init_bare(git_dir).unwrap();
+    let db = rio::Refdb::new(
+        UserInfo {
+            name: "leecher".to_owned(),
+            peer_id: PeerId::from(SecretKey::new()),
+        },
+        rio::Odb::open(git_dir).unwrap(),
+        link_git::refs::db::Refdb::open(git_dir).unwrap(),
+        namespace::expand("foo").unwrap(),
+    )
+    .unwrap();
+    rio::Network::new(db, conn, git_dir, sim::Urn("foo".to_owned()))
+}
+
+/// Fetch the branches of the namespace `foo` via `net`, returning the fetched
+/// tips.
+pub(super) async fn fetch<C>(net: &Network<C>) -> io::Result<Vec<ObjectId>>
+where
+    C: Connection + Send + Sync,
+    C::Read: Send + 'static,
+    C::Write: Send + 'static,
+{
+    let neg = Heads {
+        remote_id: PeerId::from(SecretKey::from_seed([42; 32])),
+    };
+    let (_, res) = net.run_fetch(neg).await?;
+    let refs = res.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    Ok(refs.into_iter().map(|r| r.tip).collect())
+}
+
+/// Record a fetch of a branch over a unix socket to a session file in `tmp`,
+/// returning the path of the session file and the tip of the branch.
+pub(super) fn record(tmp: &Path) -> (PathBuf, ObjectId) {
+    let remote = tmp.join("remote");
+    let head = {
+        let repo = git2::Repository::init_bare(&remote).unwrap();
+        let sig = git2::Signature::now("apollo", "apollo@cree.de").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        repo.commit(
+            Some("refs/namespaces/foo/refs/heads/main"),
+            &sig,
+            &sig,
+            "initial",
+            &tree,
+            &[],
+        )
+        .unwrap()
+    };
+    let head = ObjectId::from_20_bytes(head.as_bytes());
+    let session = tmp.join("session");
+
+    let sock = tmp.join("link.sock");
+    let listener = UnixListener::bind(&sock).unwrap();
+    let net = network(&tmp.join("recorded"), Unix::new(&sock))
+        .with_recorder(Recorder::create(&session).unwrap());
+    let server = async {
+        // `ls-refs` and `fetch`
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().await?;
+            upload_pack(&remote, stream.clone(), stream)
+                .and_then(|(_hdr, run)| run)
+                .await?;
+        }
+        Ok::<_, io::Error>(())
+    };
+    let (_, tips) = block_on(future::try_join(server, fetch(&net))).unwrap();
+    assert_eq!(vec![head], tips);
+
+    (session, head)
+}
+
+/// Whether the object `oid` is in the repository at `git_dir`.
+pub(super) fn contains(git_dir: &Path, oid: ObjectId) -> bool {
+    git2::Repository::open(git_dir)
+        .unwrap()
+        .find_object(git2::Oid::from_bytes(oid.as_bytes()).unwrap(), None)
+        .is_ok()
+}
+
+#[test]
+fn recorded_fetch_can_be_replayed() {
+    let tmp = tempdir().unwrap();
+    let (session, head) = record(tmp.path());
+
+    // Replay it without the remote end
+    let replayed = tmp.path().join("replayed");
+    let net = network(&replayed, Replay::load(&session).unwrap().strict());
+    assert_eq!(vec![head], block_on(fetch(&net)).unwrap());
+    assert!(contains(&replayed, head));
+}
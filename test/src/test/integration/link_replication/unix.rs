@@ -0,0 +1,62 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use async_net::unix::UnixListener;
+use futures::{executor::block_on, try_join, TryFutureExt as _};
+use link_git::protocol::{ls, upload_pack, RefExt as _};
+use link_replication::io::{Connection as _, Unix};
+use tempfile::tempdir;
+
+#[test]
+fn ls_refs_over_unix_socket() {
+    let tmp = tempdir().unwrap();
+    let git_dir = tmp.path().join("git");
+    let head = {
+        let repo = git2::Repository::init_bare(&git_dir).unwrap();
+        let sig = git2::Signature::now("apollo", "apollo@cree.de").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        repo.commit(
+            Some("refs/namespaces/foo/refs/heads/main"),
+            &sig,
+            &sig,
+            "initial",
+            &tree,
+            &[],
+        )
+        .unwrap()
+    };
+
+    let sock = tmp.path().join("link.sock");
+    let refs = block_on(async {
+        let listener = UnixListener::bind(&sock)?;
+        let server = async {
+            let (stream, _) = listener.accept().await?;
+            upload_pack(&git_dir, stream.clone(), stream)
+                .and_then(|(_hdr, run)| run)
+                .await
+        };
+        let client = async {
+            let (recv, send) = Unix::new(&sock).open_stream().await?;
+            ls::ls_refs(
+                ls::Options {
+                    repo: "foo".into(),
+                    extra_params: vec![],
+                    ref_prefixes: vec!["refs/heads/".into()],
+                },
+                recv,
+                send,
+            )
+            .await
+        };
+        try_join!(server, client).map(|(_status, out)| out.refs)
+    })
+    .unwrap();
+
+    assert_eq!(refs.len(), 1);
+    assert_eq!(refs[0].name(), "refs/heads/main");
+    assert_eq!(refs[0].object().as_bytes(), head.as_bytes());
+}
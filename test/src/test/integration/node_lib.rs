@@ -3,4 +3,10 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod api;
+#[cfg(unix)]
+mod control;
+mod http;
 mod socket_activation;
+#[cfg(target_os = "linux")]
+mod systemd;
@@ -0,0 +1,213 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io::Write as _,
+    net::{SocketAddr, TcpListener},
+};
+
+use anyhow::Result;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::TcpStream,
+};
+
+use librad::git::Urn;
+use node_lib::api;
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+
+const TOKEN: &str = "s3cr3t";
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(1usize),
+        min_connected: 0,
+        bootstrap: testnet::Bootstrap::None,
+    }
+}
+
+/// Start a testnet of one peer which has a project, and serve the api for it.
+fn with_api<F, T>(f: F)
+where
+    F: FnOnce(SocketAddr, Urn) -> T,
+    T: std::future::Future<Output = Result<()>>,
+{
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let peer = &net.peers()[0];
+        let proj = peer
+            .using_storage(TestProject::create)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let token = {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            writeln!(file, "{}", TOKEN).unwrap();
+            api::Token::from_file(file.path()).unwrap()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(api::routine((**peer).clone(), listener, token));
+
+        f(addr, proj.project.urn()).await.unwrap();
+        server.abort();
+    })
+}
+
+/// Issue a `GET` request for `path`, returning the status code and the body.
+async fn get(addr: SocketAddr, path: &str, token: Option<&str>) -> Result<(u16, String)> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let auth = token
+        .map(|token| format!("Authorization: Bearer {}\r\n", token))
+        .unwrap_or_default();
+    stream
+        .write_all(
+            format!(
+                "GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+                path, auth
+            )
+            .as_bytes(),
+        )
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow::anyhow!("malformed response: {}", response))?;
+    let status = head
+        .split(' ')
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("malformed status line: {}", head))?
+        .parse()?;
+    Ok((status, body.to_owned()))
+}
+
+async fn get_json(addr: SocketAddr, path: &str) -> Result<Value> {
+    let (status, body) = get(addr, path, Some(TOKEN)).await?;
+    assert_eq!(status, 200, "{}: {}", path, body);
+    Ok(serde_json::from_str(&body)?)
+}
+
+#[test]
+fn rejects_missing_token() {
+    with_api(|addr, _| async move {
+        let (status, _) = get(addr, "/v1/status", None).await?;
+        assert_eq!(status, 401);
+        Ok(())
+    })
+}
+
+#[test]
+fn rejects_wrong_token() {
+    with_api(|addr, _| async move {
+        let (status, _) = get(addr, "/v1/status", Some("s3cr3u")).await?;
+        assert_eq!(status, 401);
+        let (status, _) = get(addr, "/v1/status", Some("s3cr3t0")).await?;
+        assert_eq!(status, 401);
+        Ok(())
+    })
+}
+
+#[test]
+fn status() {
+    with_api(|addr, _| async move {
+        let status = get_json(addr, "/v1/status").await?;
+        assert!(status["peerId"].is_string());
+        assert_eq!(status["connectedPeers"], 0);
+        Ok(())
+    })
+}
+
+#[test]
+fn peers() {
+    with_api(|addr, _| async move {
+        let peers = get_json(addr, "/v1/peers").await?;
+        assert_eq!(peers, Value::Array(vec![]));
+        Ok(())
+    })
+}
+
+#[test]
+fn projects() {
+    with_api(|addr, urn| async move {
+        let projects = get_json(addr, "/v1/projects").await?;
+        let projects = projects.as_array().unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0]["root"], urn.id.to_string());
+        Ok(())
+    })
+}
+
+#[test]
+fn identity() {
+    with_api(|addr, urn| async move {
+        let path = format!("/v1/identities/{}", urn.encode_id());
+        let project = get_json(addr, &path).await?;
+        assert_eq!(project["root"], urn.id.to_string());
+
+        let (status, _) = get(
+            addr,
+            "/v1/identities/hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo",
+            Some(TOKEN),
+        )
+        .await?;
+        assert_eq!(status, 404);
+        Ok(())
+    })
+}
+
+#[test]
+fn tracking() {
+    with_api(|addr, urn| async move {
+        let all = get_json(addr, "/v1/tracking").await?;
+        let path = format!("/v1/tracking/{}", urn.encode_id());
+        let of_urn = get_json(addr, &path).await?;
+        assert!(all.is_array());
+        assert!(of_urn.is_array());
+        Ok(())
+    })
+}
+
+#[test]
+fn replication() {
+    with_api(|addr, urn| async move {
+        let path = format!("/v1/replication/{}", urn.encode_id());
+        let replicated = get_json(addr, &path).await?;
+        let replicated = replicated.as_array().unwrap();
+        assert_eq!(replicated.len(), 1);
+        assert_eq!(replicated[0]["peerId"], Value::Null);
+        Ok(())
+    })
+}
+
+#[test]
+fn audit() {
+    with_api(|addr, _| async move {
+        // Creating the project is recorded, along with the identity of the peer
+        let records = get_json(addr, "/v1/audit").await?;
+        let records = records.as_array().unwrap();
+        assert!(!records.is_empty());
+        assert!(records.iter().all(|record| record["signature"].is_string()));
+        Ok(())
+    })
+}
+
+#[test]
+fn unknown_route() {
+    with_api(|addr, _| async move {
+        let (status, _) = get(addr, "/v1/nope", Some(TOKEN)).await?;
+        assert_eq!(status, 404);
+        Ok(())
+    })
+}
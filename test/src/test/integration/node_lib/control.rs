@@ -0,0 +1,80 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    fs,
+    io,
+    os::unix::{
+        fs::PermissionsExt as _,
+        net::{UnixListener, UnixStream},
+    },
+};
+
+use anyhow::Result;
+
+use node_lib::control;
+
+#[test]
+fn bind_restricts_access_to_owner() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("control.sock");
+
+    let _listener = control::bind(&path)?;
+    assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+
+    Ok(())
+}
+
+#[test]
+fn bind_removes_stale_socket() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("control.sock");
+    // Dropping the listener leaves the socket file behind, as a crashed node
+    // would
+    drop(UnixListener::bind(&path)?);
+    assert!(path.exists());
+
+    let _listener = control::bind(&path)?;
+    assert!(UnixStream::connect(&path).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn bind_fails_if_socket_is_in_use() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("control.sock");
+    let _listener = UnixListener::bind(&path)?;
+
+    let err = control::bind(&path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+    assert!(UnixStream::connect(&path).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn bind_does_not_remove_other_files() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("control.sock");
+    fs::write(&path, b"not a socket")?;
+
+    assert!(control::bind(&path).is_err());
+    assert_eq!(fs::read(&path)?, b"not a socket");
+
+    Ok(())
+}
+
+#[test]
+fn owner_is_authorized() -> Result<()> {
+    let rt = tokio::runtime::Runtime::new()?;
+    let _guard = rt.enter();
+    let (client, server) = tokio::net::UnixStream::pair()?;
+
+    assert!(control::authorized(&server)?);
+    assert!(control::authorized(&client)?);
+
+    Ok(())
+}
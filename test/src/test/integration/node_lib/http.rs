@@ -0,0 +1,128 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    net::{SocketAddr, TcpListener},
+    path::Path,
+    process::{Command, Output},
+};
+
+use anyhow::Result;
+
+use librad::git::{storage::Storage, Urn};
+use node_lib::http;
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(1usize),
+        min_connected: 0,
+        bootstrap: testnet::Bootstrap::None,
+    }
+}
+
+/// Create a project with a commit on its default branch, `next`.
+fn create(storage: &Storage) -> Result<(Urn, git2::Oid)> {
+    let proj = TestProject::create(storage)?;
+    let urn = proj.project.urn();
+    let repo = git2::Repository::open(storage.path())?;
+    let tree = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+    let author = git2::Signature::now("alice", "alice@example.com")?;
+    let head = repo.commit(
+        Some(&format!(
+            "refs/namespaces/{}/refs/heads/next",
+            urn.encode_id()
+        )),
+        &author,
+        &author,
+        "initial",
+        &tree,
+        &[],
+    )?;
+    Ok((urn, head))
+}
+
+/// Start a testnet of one peer which has a project, and serve it over http.
+fn with_http<F, T>(f: F)
+where
+    F: FnOnce(SocketAddr, Urn, git2::Oid) -> T,
+    T: std::future::Future<Output = Result<()>>,
+{
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let peer = &net.peers()[0];
+        let (urn, head) = peer.using_storage(create).await.unwrap().unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(http::routine((**peer).clone(), listener));
+
+        f(addr, urn, head).await.unwrap();
+        server.abort();
+    })
+}
+
+/// Run `git` with `args` in `dir`, off the async runtime.
+async fn git(dir: &Path, args: &[&str]) -> Result<Output> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .args(args);
+    Ok(tokio::task::spawn_blocking(move || cmd.output()).await??)
+}
+
+#[test]
+fn clone() {
+    with_http(|addr, urn, head| async move {
+        let tmp = tempfile::tempdir()?;
+        let url = format!("http://{}/{}.git", addr, urn.encode_id());
+        let out = git(tmp.path(), &["clone", &url, "cloned"]).await?;
+        assert!(out.status.success(), "{:?}", out);
+
+        let cloned = tmp.path().join("cloned");
+        let out = git(&cloned, &["rev-parse", "refs/remotes/origin/next"]).await?;
+        assert!(out.status.success(), "{:?}", out);
+        assert_eq!(String::from_utf8(out.stdout)?.trim(), head.to_string());
+        Ok(())
+    })
+}
+
+#[test]
+fn push_is_refused() {
+    with_http(|addr, urn, head| async move {
+        let tmp = tempfile::tempdir()?;
+        let url = format!("http://{}/{}.git", addr, urn.encode_id());
+        let out = git(tmp.path(), &["clone", &url, "cloned"]).await?;
+        assert!(out.status.success(), "{:?}", out);
+
+        let cloned = tmp.path().join("cloned");
+        let refspec = format!("{}:refs/heads/pushed", head);
+        let out = git(&cloned, &["push", "origin", &refspec]).await?;
+        assert!(!out.status.success(), "{:?}", out);
+
+        let out = git(tmp.path(), &["ls-remote", &url]).await?;
+        assert!(out.status.success(), "{:?}", out);
+        assert!(!String::from_utf8(out.stdout)?.contains("refs/heads/pushed"));
+        Ok(())
+    })
+}
+
+#[test]
+fn unknown_project_is_not_found() {
+    with_http(|addr, _, _| async move {
+        let tmp = tempfile::tempdir()?;
+        let url = format!("http://{}/hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo.git", addr);
+        let out = git(tmp.path(), &["ls-remote", &url]).await?;
+        assert!(!out.status.success(), "{:?}", out);
+        Ok(())
+    })
+}
@@ -0,0 +1,35 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::os::unix::net::UnixDatagram;
+
+use anyhow::Result;
+
+use node_lib::systemd;
+
+#[test]
+fn send_sends_state() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("notify.sock");
+    let sock = UnixDatagram::bind(&path)?;
+
+    systemd::send(path.to_str().unwrap(), systemd::READY)?;
+
+    let mut buf = [0; 64];
+    let n = sock.recv(&mut buf)?;
+    assert_eq!(&buf[..n], b"READY=1");
+
+    Ok(())
+}
+
+#[test]
+fn send_fails_without_listener() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("notify.sock");
+
+    assert!(systemd::send(path.to_str().unwrap(), systemd::WATCHDOG).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{ops::Index as _, time::Duration};
+
+use futures::{future::FutureExt as _, pin_mut, select, StreamExt as _};
+use tempfile::tempdir;
+
+use librad::{
+    git::{
+        storage::{ReadOnlyStorage as _, Storage},
+        tracking,
+    },
+    net::{
+        protocol::event::{self, upstream::predicate::gossip_from},
+        Network,
+    },
+    profile::{Profile, RadHome},
+    PeerId,
+    SecretKey,
+};
+use rad_exe::{daemon, sync::Seed};
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(1usize),
+        min_connected: 0,
+        bootstrap: testnet::Bootstrap::None,
+    }
+}
+
+#[test]
+fn daemon_announces_and_serves_local_projects() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let seed = net.peers().index(0);
+
+        let home = tempdir().unwrap();
+        let profile = Profile::from_home(&RadHome::Root(home.path().to_path_buf()), None).unwrap();
+        let key = SecretKey::new();
+        let daemon_id = PeerId::from(key.clone());
+        let proj = {
+            let storage = Storage::open(profile.paths(), key.clone()).unwrap();
+            TestProject::create(&storage).unwrap()
+        };
+        let urn = proj.project.urn();
+
+        seed.using_storage({
+            let urn = urn.clone();
+            move |storage| {
+                tracking::track(
+                    storage,
+                    &urn,
+                    None,
+                    tracking::Config::default(),
+                    tracking::policy::Track::MustNotExist,
+                )
+            }
+        })
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+        let seed_events = seed.subscribe();
+        let daemon = daemon::run(
+            &profile,
+            key,
+            daemon::Options {
+                listen_addr: ([127, 0, 0, 1], 0).into(),
+                network: Network::Custom(b"localtestnet".as_ref().into()),
+                seeds: vec![Seed {
+                    peer_id: seed.peer_id(),
+                    addrs: seed.listen_addrs().to_vec(),
+                }],
+                announce: Some(Duration::from_millis(100)),
+                tracker: None,
+            },
+        )
+        .fuse();
+        // The seed replicates the project from the daemon upon receiving its
+        // announcement
+        let replicated = async {
+            event::upstream::expect(
+                seed_events.boxed(),
+                gossip_from(daemon_id),
+                Duration::from_secs(15),
+            )
+            .await
+            .unwrap();
+
+            seed.using_storage({
+                let urn = urn.clone();
+                move |storage| storage.has_urn(&urn)
+            })
+            .await
+            .unwrap()
+            .unwrap()
+        }
+        .fuse();
+        pin_mut!(daemon, replicated);
+
+        select! {
+            res = daemon => panic!("daemon exited early: {:?}", res),
+            has_proj = replicated => assert!(has_proj),
+        }
+    })
+}
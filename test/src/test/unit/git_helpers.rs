@@ -0,0 +1,34 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{local::url::LocalUrl, Urn},
+    PeerId,
+    SecretKey,
+};
+use radicle_git_helpers::remote_helper::url::Url;
+
+use crate::roundtrip::str_roundtrip;
+
+#[test]
+fn url_trip() {
+    let local = LocalUrl::from(Urn::new(git2::Oid::zero().into()));
+    str_roundtrip(Url::from(local.clone()));
+    str_roundtrip(Url {
+        peer: Some(PeerId::from(SecretKey::from_seed([42; 32]))),
+        local,
+    })
+}
+
+#[test]
+fn url_with_peer() {
+    let local = LocalUrl::from(Urn::new(git2::Oid::zero().into()));
+    let peer = PeerId::from(SecretKey::from_seed([42; 32]));
+    let url = format!("rad://{}@{}.git", peer, local.urn.encode_id())
+        .parse::<Url>()
+        .unwrap();
+    assert_eq!(url.peer, Some(peer));
+    assert_eq!(url.local, local);
+}
@@ -3,8 +3,10 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod arbitrary;
 mod canonical;
 mod collaborative_objects;
+mod fixture;
 mod git;
 mod identities;
 mod keys;
@@ -0,0 +1,34 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::str::FromStr as _;
+
+use librad::{
+    arbitrary::{gen_tracking_refname, gen_update, gen_urn},
+    git::{tracking::reference::RefName, Urn},
+    git_ext::Oid,
+};
+use link_replication::Update;
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn urn_roundtrip(urn in gen_urn()) {
+        assert_eq!(urn, Urn::from_str(&urn.to_string()).unwrap())
+    }
+
+    #[test]
+    fn tracking_refname_roundtrip(name in gen_tracking_refname()) {
+        assert_eq!(name, RefName::<Oid>::from_str(&name.to_string()).unwrap())
+    }
+
+    #[test]
+    fn updates_are_remote_tracking(up in gen_update()) {
+        let name = match &up {
+            Update::Direct { name, .. } | Update::Symbolic { name, .. } => name,
+        };
+        assert!(name.starts_with(b"refs/remotes/"))
+    }
+}
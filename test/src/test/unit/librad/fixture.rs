@@ -0,0 +1,142 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{
+        identities,
+        refs::Refs,
+        storage::ReadOnlyStorage as _,
+        tracking::is_tracked,
+        types::{Namespace, Reference},
+    },
+    reflike,
+    testing::fixture::Builder,
+};
+
+#[test]
+fn project_is_replicated() {
+    let fixture = Builder::new(2)
+        .project("radicle-link", 0)
+        .replicate("radicle-link", 0, 1)
+        .build()
+        .unwrap();
+    let urn = fixture.urn("radicle-link");
+    let (peer0, peer1) = (fixture.peer_id(0), fixture.peer_id(1));
+
+    assert!(identities::project::get(fixture.peer(1), urn)
+        .unwrap()
+        .is_some());
+    assert!(is_tracked(fixture.peer(1), urn, Some(peer0)).unwrap());
+    assert!(Refs::load(fixture.peer(1), urn, None)
+        .unwrap()
+        .unwrap()
+        .remotes
+        .flatten()
+        .any(|remote| remote == &peer0));
+    assert!(!is_tracked(fixture.peer(0), urn, Some(peer1)).unwrap());
+}
+
+#[test]
+fn delegations() {
+    let fixture = Builder::new(3)
+        .project("radicle-link", 0)
+        .delegate("radicle-link", 1)
+        .delegate("radicle-link", 2)
+        .build()
+        .unwrap();
+    let urn = fixture.urn("radicle-link");
+
+    let project = identities::project::get(fixture.peer(0), urn)
+        .unwrap()
+        .unwrap();
+    assert_eq!(3, project.delegations().iter().count());
+    for peer in 1..3 {
+        let key = fixture.peer_id(peer);
+        assert!(project
+            .delegations()
+            .iter()
+            .direct()
+            .any(|direct| direct == key.as_public_key()))
+    }
+}
+
+#[test]
+fn commits_are_signed() {
+    let fixture = Builder::new(2)
+        .project("radicle-link", 0)
+        .replicate("radicle-link", 0, 1)
+        .commits("radicle-link", 1, "main", 3)
+        .track("radicle-link", 0, 1)
+        .build()
+        .unwrap();
+    let urn = fixture.urn("radicle-link");
+
+    let head = Reference::head(Namespace::from(urn), None, reflike!("main"));
+    let tip = fixture.peer(1).reference_oid(&head).unwrap();
+    let signed = Refs::load(fixture.peer(1), urn, None).unwrap().unwrap();
+    assert_eq!(
+        Some(tip),
+        signed
+            .heads()
+            .find(|(name, _)| name.as_str() == "main")
+            .map(|(_, oid)| oid)
+    );
+    assert!(is_tracked(fixture.peer(0), urn, Some(fixture.peer_id(1))).unwrap());
+}
+
+#[test]
+fn signed_at_older_tip() {
+    let fixture = Builder::new(1)
+        .project("radicle-link", 0)
+        .commits("radicle-link", 0, "main", 3)
+        .signed_at("radicle-link", 0, "main", 2)
+        .build()
+        .unwrap();
+    let urn = fixture.urn("radicle-link");
+
+    let head = Reference::head(Namespace::from(urn), None, reflike!("main"));
+    let tip = fixture.peer(0).reference_oid(&head).unwrap();
+    let repo = fixture.peer(0).as_raw();
+    let expected = repo
+        .find_commit(git2::Oid::from(tip))
+        .unwrap()
+        .parent(0)
+        .unwrap()
+        .parent(0)
+        .unwrap()
+        .id();
+    let signed = Refs::load(fixture.peer(0), urn, None).unwrap().unwrap();
+    assert_eq!(
+        Some(expected),
+        signed
+            .heads()
+            .find(|(name, _)| name.as_str() == "main")
+            .map(|(_, oid)| git2::Oid::from(oid))
+    );
+}
+
+#[test]
+fn signed_at_beyond_history() {
+    assert!(Builder::new(1)
+        .project("radicle-link", 0)
+        .commits("radicle-link", 0, "main", 1)
+        .signed_at("radicle-link", 0, "main", 2)
+        .build()
+        .is_err())
+}
+
+#[test]
+fn unknown_peer() {
+    assert!(Builder::new(1)
+        .project("radicle-link", 0)
+        .replicate("radicle-link", 1, 0)
+        .build()
+        .is_err());
+    assert!(Builder::new(1)
+        .project("radicle-link", 0)
+        .track("radicle-link", 0, 1)
+        .build()
+        .is_err());
+}
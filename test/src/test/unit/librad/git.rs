@@ -3,6 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod audit;
 #[cfg(not(feature = "replication-v3"))]
 mod fetch;
 mod include;
@@ -0,0 +1,83 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{
+        audit::{self, Delegate, Event},
+        tracking,
+    },
+    PeerId,
+    SecretKey,
+};
+
+use crate::librad::git::{self, storage::storage};
+
+#[test]
+fn records_identity_and_tracking_changes() -> anyhow::Result<()> {
+    let key = SecretKey::new();
+    let storage = storage(key.clone());
+    let whoami = git::dylan(&storage, &key)?;
+    let urn = whoami.urn();
+    let remote_peer = PeerId::from(SecretKey::new());
+
+    assert!(tracking::track(
+        &storage,
+        &urn,
+        Some(remote_peer),
+        tracking::Config::default(),
+        tracking::policy::Track::Any,
+    )?
+    .is_ok());
+    assert!(
+        tracking::untrack(&storage, &urn, remote_peer, tracking::policy::Untrack::Any)?.is_ok()
+    );
+
+    let records = audit::entries(&*storage)?;
+    let events = records
+        .iter()
+        .map(|record| record.entry.event.clone())
+        .collect::<Vec<_>>();
+    assert!(matches!(
+        &events[..],
+        [
+            Event::IdentityRevision { previous: None, .. },
+            Event::Delegations { added, removed, .. },
+            Event::Tracked { peer: Some(tracked), .. },
+            Event::Untracked { peer: Some(untracked), .. },
+        ] if added.contains(&Delegate::Key(PeerId::from(key.clone())))
+            && removed.is_empty()
+            && *tracked == remote_peer
+            && *untracked == remote_peer
+    ));
+    assert!(records
+        .iter()
+        .all(|record| record.entry.peer == PeerId::from(key.clone())));
+
+    let mut exported = Vec::new();
+    assert_eq!(audit::export(&*storage, &mut exported)?, records.len());
+    assert_eq!(
+        exported
+            .split(|b| *b == b'\n')
+            .filter(|l| !l.is_empty())
+            .count(),
+        4
+    );
+
+    Ok(())
+}
+
+#[test]
+fn stored_outside_the_monorepo() -> anyhow::Result<()> {
+    let key = SecretKey::new();
+    let storage = storage(key.clone());
+    assert!(audit::entries(&*storage)?.is_empty());
+
+    git::dylan(&storage, &key)?;
+    assert!(!audit::entries(&*storage)?.is_empty());
+    let monorepo = git2::Repository::open_bare(storage.path())?;
+    assert!(monorepo.find_reference(audit::REF).is_err());
+
+    Ok(())
+}
@@ -8,7 +8,7 @@ use std::collections::{BTreeMap, BTreeSet};
 use pretty_assertions::assert_eq;
 
 use librad::{
-    git::fetch::Fetchspecs,
+    git::{fetch::Fetchspecs, types::Exclusion},
     git_ext as ext,
     identities::{urn::test::FakeId, Urn},
     reflike,
@@ -96,6 +96,7 @@ fn replicate_looks_legit() {
                     "heads" => {"mister" => *ZERO,},
                 },
                 remotes: Remotes::new(),
+                freshness: None,
             },
         ),
         (
@@ -108,6 +109,7 @@ fn replicate_looks_legit() {
                     },
                 },
                 remotes: Remotes::new(),
+                freshness: None,
             },
         ),
     ]
@@ -182,6 +184,7 @@ fn replicate_looks_legit() {
     let specs = Fetchspecs::Replicate {
         tracked_sigrefs,
         delegates,
+        exclude: vec![],
         limit: Default::default(),
     }
     .refspecs(&*PROJECT_URN, TOLA.clone(), &remote_heads);
@@ -308,3 +311,55 @@ fn replicate_looks_legit() {
         .collect::<BTreeSet<String>>()
     )
 }
+
+#[test]
+fn replicate_honours_exclusions() {
+    use crate::make_refs;
+    use librad::git::refs::{Refs, Remotes};
+
+    lazy_static! {
+        static ref ZERO: ext::Oid = ext::Oid::from(git2::Oid::zero());
+    }
+
+    let tracked_sigrefs = [(
+        BOLEK.clone(),
+        Refs {
+            categorised_refs: make_refs! {
+                "heads" => {
+                    "mister" => *ZERO,
+                    "wip/next" => *ZERO,
+                },
+            },
+            remotes: Remotes::new(),
+            freshness: None,
+        },
+    )]
+    .iter()
+    .cloned()
+    .collect::<BTreeMap<_, _>>();
+
+    let remote_heads = [
+        PROJECT_NAMESPACE.join(reflike!("refs/remotes/bolek/heads/mister")),
+        PROJECT_NAMESPACE.join(reflike!("refs/remotes/bolek/heads/wip/next")),
+    ]
+    .iter()
+    .cloned()
+    .map(|name| (name, *ZERO))
+    .collect::<BTreeMap<_, _>>()
+    .into();
+
+    let specs = Fetchspecs::Replicate {
+        tracked_sigrefs,
+        delegates: BTreeSet::new(),
+        exclude: vec![Exclusion::new(refspec_pattern!("refs/heads/wip/*"))],
+        limit: Default::default(),
+    }
+    .refspecs(&*PROJECT_URN, TOLA.clone(), &remote_heads)
+    .into_iter()
+    .map(|spec| spec.to_string())
+    .collect::<Vec<_>>();
+
+    let mister = PROJECT_NAMESPACE.join(reflike!("refs/remotes/bolek/heads/mister"));
+    assert!(specs.contains(&format!("{}:{}", mister, mister)));
+    assert!(!specs.iter().any(|spec| spec.contains("wip/next")));
+}
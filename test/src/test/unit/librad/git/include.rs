@@ -5,11 +5,12 @@
 
 use librad::{
     git::{
-        include::{Error, Include},
+        include::{self, Error, Include},
         local::url::LocalUrl,
         Urn,
     },
     git_ext as ext,
+    paths::Paths,
     reflike,
     PeerId,
     SecretKey,
@@ -136,3 +137,35 @@ fn can_create_and_update() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn link_is_idempotent() -> Result<(), Error> {
+    let tmp_dir = tempfile::tempdir()?;
+    let paths = Paths::from_root(tmp_dir.path())?;
+    let repo = git2::Repository::init(tmp_dir.path().join("working-copy"))?;
+    let urn = Urn::new(git2::Oid::zero().into());
+
+    let other = tmp_dir.path().join("other.inc");
+    include::set_include_path(&repo, other.clone())?;
+
+    let path = include::link(&repo, &paths, &urn)?;
+    assert_eq!(
+        path,
+        paths
+            .git_includes_dir()
+            .join(format!("{}.inc", urn.encode_id()))
+    );
+    assert_eq!(include::link(&repo, &paths, &urn)?, path);
+
+    let config = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+    let mut values = Vec::new();
+    for entry in &config.multivar(include::GIT_CONFIG_PATH_KEY, None)? {
+        values.extend(entry?.value().map(String::from));
+    }
+    assert_eq!(
+        values,
+        vec![other.display().to_string(), path.display().to_string()]
+    );
+
+    Ok(())
+}
@@ -3,5 +3,8 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod hooks;
+mod import;
+mod receive;
 mod transport;
 mod url;
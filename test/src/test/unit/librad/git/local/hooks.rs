@@ -0,0 +1,101 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fs;
+
+use librad::git::local::hooks::{self, Error, Hook};
+
+const COMMAND: &str = "rad hooks run";
+
+#[test]
+fn install_writes_all_hooks() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init(tmp.path())?;
+
+    let installed = hooks::install(&repo, COMMAND, false)?;
+    assert_eq!(installed.len(), Hook::ALL.len());
+    for (hook, path) in Hook::ALL.iter().zip(&installed) {
+        assert_eq!(path, &repo.path().join("hooks").join(hook.file_name()));
+        let script = fs::read_to_string(path)?;
+        assert!(script.ends_with(&format!("exec {} {}\n", COMMAND, hook.as_str())));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            assert_eq!(fs::metadata(path)?.permissions().mode() & 0o111, 0o111);
+        }
+    }
+
+    // Installing again replaces our own hooks
+    hooks::install(&repo, COMMAND, false)?;
+
+    Ok(())
+}
+
+#[test]
+fn install_keeps_foreign_hooks() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init(tmp.path())?;
+    let foreign = repo.path().join("hooks").join("post-commit");
+    fs::create_dir_all(foreign.parent().unwrap())?;
+    fs::write(&foreign, "#!/bin/sh\necho hi\n")?;
+
+    assert!(matches!(
+        hooks::install(&repo, COMMAND, false),
+        Err(Error::Exists(path)) if path == foreign
+    ));
+    assert_eq!(fs::read_to_string(&foreign)?, "#!/bin/sh\necho hi\n");
+
+    hooks::install(&repo, COMMAND, true)?;
+    assert_ne!(fs::read_to_string(&foreign)?, "#!/bin/sh\necho hi\n");
+
+    Ok(())
+}
+
+#[test]
+fn uninstall_removes_only_own_hooks() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init(tmp.path())?;
+    hooks::install(&repo, COMMAND, false)?;
+    let foreign = repo.path().join("hooks").join("pre-commit");
+    fs::write(&foreign, "#!/bin/sh\n")?;
+
+    let removed = hooks::uninstall(&repo)?;
+    assert_eq!(removed.len(), Hook::ALL.len());
+    assert!(removed.iter().all(|path| !path.exists()));
+    assert!(foreign.exists());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn run_runs_installed_hooks_only() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init(tmp.path())?;
+    assert!(hooks::run(&repo, Hook::PostPush)?.is_none());
+
+    // The hook creates a file named after itself in the working copy
+    hooks::install(&repo, "touch", false)?;
+    let status = hooks::run(&repo, Hook::PostPush)?.expect("hook is installed");
+    assert!(status.success());
+    assert!(tmp.path().join("post-push").exists());
+    assert!(!tmp.path().join("post-commit").exists());
+
+    Ok(())
+}
+
+#[test]
+fn post_push_is_not_a_git_hook() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init(tmp.path())?;
+
+    // Only the remote helper runs it, so fetches don't
+    let installed = hooks::install(&repo, COMMAND, false)?;
+    assert!(installed
+        .iter()
+        .all(|path| path.file_name().unwrap() != "reference-transaction"));
+
+    Ok(())
+}
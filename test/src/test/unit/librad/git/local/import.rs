@@ -0,0 +1,83 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{
+        local::import::{import, Error, Options},
+        refs::Refs,
+    },
+    SecretKey,
+};
+
+use crate::librad::git::{self, storage::storage};
+
+fn working_copy(path: &std::path::Path) -> anyhow::Result<git2::Repository> {
+    let repo = git2::Repository::init(path)?;
+    {
+        let sig = git2::Signature::now("dylan", "dylan@example.com")?;
+        let tree = {
+            let mut index = repo.index()?;
+            repo.find_tree(index.write_tree()?)?
+        };
+        let commit = repo.commit(Some("refs/heads/main"), &sig, &sig, "Initial", &tree, &[])?;
+        repo.set_head("refs/heads/main")?;
+        repo.tag_lightweight("v1", &repo.find_object(commit, None)?, false)?;
+    }
+    Ok(repo)
+}
+
+#[test]
+fn imports_existing_repository() -> anyhow::Result<()> {
+    let key = SecretKey::new();
+    let storage = storage(key.clone());
+    git::dylan(&storage, &key)?;
+
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("my-project");
+    let repo = working_copy(&path)?;
+
+    let imported = import(&storage, &path, Options::default())?;
+    let urn = imported.project.urn();
+    assert_eq!(imported.project.subject().name.as_str(), "my-project");
+    assert_eq!(
+        imported
+            .project
+            .subject()
+            .default_branch
+            .as_ref()
+            .map(|b| b.as_str()),
+        Some("main")
+    );
+    assert_eq!(imported.refs, vec!["refs/heads/main", "refs/tags/v1"]);
+
+    let refs = Refs::load(&*storage, &urn, None)?.expect("signed refs exist");
+    let main = refs
+        .heads()
+        .find(|(name, _)| name.as_str() == "main")
+        .map(|(_, oid)| oid);
+    assert_eq!(
+        main.map(git2::Oid::from),
+        repo.refname_to_id("refs/heads/main").ok()
+    );
+    assert!(refs.tags().any(|(name, _)| name.as_str() == "v1"));
+
+    let rad = repo.find_remote("rad")?;
+    assert_eq!(
+        rad.url(),
+        Some(
+            librad::git::local::url::LocalUrl::from(urn)
+                .to_string()
+                .as_str()
+        )
+    );
+    assert_eq!(repo.config()?.get_string("branch.main.remote")?, "rad");
+
+    assert!(matches!(
+        import(&storage, &path, Options::default()),
+        Err(Error::AlreadyLinked { .. })
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,195 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io::Write as _,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+use librad::git::local::receive::{self, Error, Policy};
+
+const ZERO: &str = "0000000000000000000000000000000000000000";
+
+fn commit(repo: &git2::Repository) -> anyhow::Result<git2::Oid> {
+    let tree = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+    let author = git2::Signature::now("dylan", "dylan@example.com")?;
+    Ok(repo.commit(None, &author, &author, "initial", &tree, &[])?)
+}
+
+fn pre_receive(
+    repo: &git2::Repository,
+    hooks: &Path,
+    updates: &[String],
+) -> anyhow::Result<Output> {
+    pre_receive_in(repo, hooks, "", updates)
+}
+
+/// Run the hook as `git receive-pack` does when pushing into `namespace`.
+fn pre_receive_in(
+    repo: &git2::Repository,
+    hooks: &Path,
+    namespace: &str,
+    updates: &[String],
+) -> anyhow::Result<Output> {
+    let mut child = Command::new(hooks.join("pre-receive"))
+        .env("GIT_DIR", repo.path())
+        .env("GIT_NAMESPACE", namespace)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    {
+        let mut stdin = child.stdin.take().unwrap();
+        for update in updates {
+            writeln!(stdin, "{}", update)?;
+        }
+    }
+    Ok(child.wait_with_output()?)
+}
+
+#[test]
+fn only_owned_refs() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init_bare(tmp.path())?;
+    let oid = commit(&repo)?;
+    let hooks = receive::install(repo.path(), &Policy::default())?;
+
+    let ok = pre_receive(
+        &repo,
+        &hooks,
+        &[
+            format!("{} {} refs/heads/main", ZERO, oid),
+            format!("{} {} refs/tags/v1", ZERO, oid),
+        ],
+    )?;
+    assert!(ok.status.success());
+
+    let rejected = pre_receive(
+        &repo,
+        &hooks,
+        &[
+            format!("{} {} refs/heads/main", ZERO, oid),
+            format!("{} {} refs/remotes/hyy5s7ysg96fqa91gbe7h38yddh4mkokft7y4htt8szt9e17sxoe3h/heads/main", ZERO, oid),
+            format!("{} {} refs/rad/id", ZERO, oid),
+        ],
+    )?;
+    assert!(!rejected.status.success());
+    let stderr = String::from_utf8(rejected.stderr)?;
+    assert!(stderr.contains("rejected refs/remotes/"));
+    assert!(stderr.contains("rejected refs/rad/id"));
+    assert!(!stderr.contains("rejected refs/heads/main"));
+
+    Ok(())
+}
+
+#[test]
+fn ref_patterns() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init_bare(tmp.path())?;
+    let oid = commit(&repo)?;
+    let policy = Policy {
+        refs: vec!["refs/heads/feature/*".to_owned()],
+        ..Policy::default()
+    };
+    let hooks = receive::install(repo.path(), &policy)?;
+
+    let ok = pre_receive(
+        &repo,
+        &hooks,
+        &[format!("{} {} refs/heads/feature/x", ZERO, oid)],
+    )?;
+    assert!(ok.status.success());
+    let rejected = pre_receive(
+        &repo,
+        &hooks,
+        &[format!("{} {} refs/heads/main", ZERO, oid)],
+    )?;
+    assert!(!rejected.status.success());
+    assert!(String::from_utf8(rejected.stderr)?.contains("not allowed by the push policy"));
+
+    Ok(())
+}
+
+#[test]
+fn require_signed() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init_bare(tmp.path())?;
+    let oid = commit(&repo)?;
+    let policy = Policy {
+        require_signed: true,
+        ..Policy::default()
+    };
+    let hooks = receive::install(repo.path(), &policy)?;
+
+    let rejected = pre_receive(
+        &repo,
+        &hooks,
+        &[format!("{} {} refs/heads/main", ZERO, oid)],
+    )?;
+    assert!(!rejected.status.success());
+    assert!(String::from_utf8(rejected.stderr)?.contains(&format!("commit {} is not signed", oid)));
+
+    // Deletions don't introduce commits
+    let ok = pre_receive(
+        &repo,
+        &hooks,
+        &[format!("{} {} refs/heads/main", oid, ZERO)],
+    )?;
+    assert!(ok.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn require_signed_in_other_namespaces() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init_bare(tmp.path())?;
+    let oid = commit(&repo)?;
+    repo.reference("refs/namespaces/a/refs/heads/main", oid, false, "")?;
+    let policy = Policy {
+        require_signed: true,
+        ..Policy::default()
+    };
+    let hooks = receive::install(repo.path(), &policy)?;
+
+    // Already in the namespace, so not new
+    let ok = pre_receive_in(
+        &repo,
+        &hooks,
+        "a",
+        &[format!("{} {} refs/heads/next", ZERO, oid)],
+    )?;
+    assert!(ok.status.success());
+
+    // Being reachable from another namespace doesn't make it signed
+    let rejected = pre_receive_in(
+        &repo,
+        &hooks,
+        "b",
+        &[format!("{} {} refs/heads/main", ZERO, oid)],
+    )?;
+    assert!(!rejected.status.success());
+    assert!(String::from_utf8(rejected.stderr)?.contains(&format!("commit {} is not signed", oid)));
+
+    Ok(())
+}
+
+#[test]
+fn invalid_patterns() {
+    let policy = |pattern: &str| Policy {
+        refs: vec![pattern.to_owned()],
+        ..Policy::default()
+    };
+    assert!(matches!(
+        policy("refs/heads/$(rm -rf /)").validate(),
+        Err(Error::Pattern(_))
+    ));
+    assert!(matches!(
+        policy("refs/remotes/*").validate(),
+        Err(Error::Namespace(_))
+    ));
+    assert!(policy("refs/heads/release-*").validate().is_ok());
+}
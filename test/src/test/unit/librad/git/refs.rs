@@ -308,3 +308,156 @@ mod computing_refs {
         assert_eq!(refs.categorised_refs, expected_refs);
     }
 }
+
+mod cache {
+    use librad::{
+        git::{
+            refs::{self, cache::Persisted, Refs},
+            storage::Storage,
+        },
+        paths::Paths,
+        PeerId,
+        SecretKey,
+    };
+
+    use crate::librad::git;
+
+    #[test]
+    fn loads_are_cached() -> anyhow::Result<()> {
+        let key = SecretKey::new();
+        let peer = PeerId::from(key.clone());
+        let tmp = tempfile::tempdir()?;
+        let cache = tmp.path().join("cache");
+        let storage = Storage::open(&Paths::from_root(tmp.path().join("root"))?, key.clone())?;
+        let whoami = git::dylan(&storage, &key)?;
+        let urn = whoami.urn();
+        Refs::update(&storage, &urn)?;
+
+        let storage = storage.with_sigrefs_cache(Persisted::open(&cache)?);
+        refs::cache::clear();
+
+        let uncached = Refs::load(&storage, &urn, None)?;
+        assert!(uncached.is_some());
+        let verified = cache.join("v1").join(peer.to_string());
+        assert_eq!(std::fs::read_dir(&verified)?.count(), 1);
+
+        assert_eq!(Refs::load(&storage, &urn, None)?, uncached);
+        refs::cache::clear();
+        assert_eq!(Refs::load(&storage, &urn, None)?, uncached);
+        assert_eq!(std::fs::read_dir(&verified)?.count(), 1);
+
+        Ok(())
+    }
+}
+
+mod freshness {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use librad::{
+        git::refs::{self, Freshness, Refs, Remotes, Signed, Updated},
+        PeerId,
+        SecretKey,
+    };
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        librad::git::{self, storage::storage},
+        make_refs,
+    };
+
+    fn refs(freshness: Option<Freshness>) -> Refs {
+        Refs {
+            categorised_refs: make_refs! {
+                "heads" => {},
+                "rad" => {},
+                "tags" => {},
+                "notes" => {},
+            },
+            remotes: Remotes::new(),
+            freshness,
+        }
+    }
+
+    #[test]
+    fn signature_covers_freshness() {
+        let key = SecretKey::new();
+        let peer = PeerId::from(key.clone());
+        let fresh = Freshness {
+            seq: 42,
+            timestamp: 1_634_000_000,
+        };
+        let signed = refs(Some(fresh)).sign(&key).unwrap();
+
+        let mut json = serde_json::to_value(&signed).unwrap();
+        assert_eq!(
+            json["refs"]["^freshness"],
+            serde_json::json!({ "version": 1, "seq": 42, "timestamp": 1_634_000_000 })
+        );
+        let verified = Signed::from_json(&serde_json::to_vec(&json).unwrap(), &peer).unwrap();
+        assert_eq!(verified.freshness, Some(fresh));
+
+        json["refs"]["^freshness"]["seq"] = 43.into();
+        assert!(Signed::from_json(&serde_json::to_vec(&json).unwrap(), &peer).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let json = serde_json::json!({
+            "heads": {},
+            "remotes": {},
+            "^freshness": { "version": 2, "seq": 0, "timestamp": 0 }
+        });
+        assert!(serde_json::from_value::<Refs>(json).is_err());
+    }
+
+    #[test]
+    fn age() {
+        let fresh = Freshness {
+            seq: 0,
+            timestamp: 1_000,
+        };
+        assert_eq!(
+            fresh.age_at(UNIX_EPOCH + Duration::from_secs(1_060)),
+            Duration::from_secs(60)
+        );
+        // Clock skew
+        assert_eq!(
+            fresh.age_at(UNIX_EPOCH + Duration::from_secs(10)),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn update_attests_if_enabled() -> anyhow::Result<()> {
+        let key = SecretKey::new();
+        let storage = storage(key.clone());
+        let whoami = git::dylan(&storage, &key)?;
+        let urn = whoami.urn();
+
+        Refs::update(&storage, &urn)?;
+        let unattested = Refs::load(&*storage, &urn, None)?.unwrap();
+        assert_eq!(unattested.freshness, None);
+
+        storage.config()?.set_sigrefs_freshness(true)?;
+        assert!(matches!(
+            Refs::update(&storage, &urn)?,
+            Updated::Updated { .. }
+        ));
+        let attested = Refs::load(&*storage, &urn, None)?.unwrap().freshness;
+        assert_eq!(attested.map(|fresh| fresh.seq), Some(0));
+
+        // Recent attestations of unchanged refs are carried over
+        assert!(matches!(
+            Refs::update(&storage, &urn)?,
+            Updated::Unchanged { .. }
+        ));
+        assert_eq!(
+            Refs::load(&*storage, &urn, None)?.unwrap().freshness,
+            attested
+        );
+
+        assert!(refs::freshness(&*storage, &urn)?.is_empty());
+
+        Ok(())
+    }
+}
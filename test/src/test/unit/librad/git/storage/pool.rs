@@ -0,0 +1,86 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::path::Path;
+
+use tempfile::{tempdir, TempDir};
+
+use librad::{
+    git::storage::{pool, ReadOnly, ReadOnlyStorage as _, Storage},
+    git_ext,
+    paths::Paths,
+    SecretKey,
+};
+
+fn setup() -> (TempDir, Paths) {
+    let tmp = tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    Storage::open(&paths, SecretKey::new()).unwrap();
+    (tmp, paths)
+}
+
+/// Create a commit in a scratch repository, and add it to the monorepo at
+/// `git_dir` as a new packfile.
+fn add_pack(git_dir: &Path) -> git2::Oid {
+    let tmp = tempdir().unwrap();
+    let scratch = git2::Repository::init_bare(tmp.path()).unwrap();
+    let sig = git2::Signature::now("leecher", "leecher@example.com").unwrap();
+    let tree = scratch
+        .find_tree(scratch.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let oid = scratch
+        .commit(None, &sig, &sig, "in a pack", &tree, &[])
+        .unwrap();
+
+    let mut pack = git2::Buf::new();
+    {
+        let mut builder = scratch.packbuilder().unwrap();
+        builder.insert_commit(oid).unwrap();
+        builder.write_buf(&mut pack).unwrap();
+    }
+    let monorepo = git2::Repository::open_bare(git_dir).unwrap();
+    let odb = monorepo.odb().unwrap();
+    let mut writer = odb.packwriter().unwrap();
+    std::io::Write::write_all(&mut writer, &pack).unwrap();
+    writer.commit().unwrap();
+
+    oid
+}
+
+#[test]
+fn refresh_rescans_only_if_packs_were_added() {
+    let (_tmp, paths) = setup();
+    let mut storage = ReadOnly::open(&paths).unwrap();
+    assert!(!storage.refresh().unwrap());
+
+    let oid = add_pack(paths.git_dir());
+    assert!(storage.refresh().unwrap());
+    assert!(!storage.refresh().unwrap());
+    assert!(storage
+        .find_object(git_ext::Oid::from(oid))
+        .unwrap()
+        .is_some());
+}
+
+#[tokio::test]
+async fn recycled_storages_see_new_packs() {
+    let (_tmp, paths) = setup();
+    let pool = pool::Pool::new(pool::ReadConfig::new(paths.clone()), 1);
+
+    {
+        let mut storage = pool.get().await.unwrap();
+        assert!(!storage.refresh().unwrap());
+    }
+    let oid = add_pack(paths.git_dir());
+
+    // The pool has a single entry, so this is the one returned above, which
+    // was refreshed when it was recycled
+    let mut storage = pool.get().await.unwrap();
+    assert!(!storage.refresh().unwrap());
+    assert!(storage
+        .find_object(git_ext::Oid::from(oid))
+        .unwrap()
+        .is_some());
+}
@@ -7,10 +7,13 @@ use std::{collections::BTreeSet, convert::TryFrom as _};
 
 use librad::{
     git::{
-        storage::watch::{EventKind, NamespaceEvent},
+        refs::Refs,
+        storage::watch::{EventKind, NamespaceEvent, SignedRefsEvent},
+        tracking,
         Urn,
     },
     git_ext::RefLike,
+    PeerId,
     SecretKey,
 };
 
@@ -43,3 +46,31 @@ fn namespaces() {
 
     assert_eq!(expected, events)
 }
+
+#[test]
+fn signed_refs() {
+    logging::init();
+
+    let store = storage(SecretKey::new());
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+
+    // Watch only after the reflog directories of the namespace exist, so the
+    // update below doesn't depend on the backend picking up new directories.
+    let (watcher, events) = store.watch().signed_refs().unwrap();
+    assert!(tracking::track(
+        &store,
+        &urn,
+        Some(PeerId::from(SecretKey::new())),
+        tracking::Config::default(),
+        tracking::policy::Track::Any,
+    )
+    .unwrap()
+    .is_ok());
+    Refs::update(&store, &urn).unwrap();
+
+    assert!(events
+        .map(|SignedRefsEvent { urn }| urn)
+        .any(|updated| updated == urn));
+    drop(watcher);
+}
@@ -3,12 +3,12 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::collections::BTreeSet;
+use std::{borrow::Cow, collections::BTreeSet};
 
 use librad::{
     git::{
         storage::Storage,
-        tracking::{is_tracked, policy, track, tracked_peers, untrack, Config},
+        tracking::{batch, is_tracked, policy, track, tracked_peers, untrack, Config},
         Urn,
     },
     paths::Paths,
@@ -97,6 +97,57 @@ fn track_track_is_tracked() {
     }
 }
 
+#[test]
+fn batch_track_track_is_unchanged() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let remote_peer = PeerId::from(SecretKey::new());
+        let urn = Urn::new(git2::Oid::zero().into());
+        let config = Config::default();
+        let action = || batch::Action::Track {
+            urn: Cow::Borrowed(&urn),
+            peer: Some(remote_peer),
+            config: &config,
+            policy: policy::Track::Any,
+        };
+
+        let applied = batch::batch(&storage, Some(action())).unwrap();
+        assert_eq!(applied.updates.len(), 1);
+        assert!(applied.unchanged.is_empty());
+
+        let applied = batch::batch(&storage, Some(action())).unwrap();
+        assert!(applied.updates.is_empty());
+        assert_eq!(applied.unchanged.len(), 1);
+        assert!(is_tracked(&storage, &urn, Some(remote_peer)).unwrap())
+    }
+}
+
+#[test]
+fn untrack_nonexistent_is_unchanged() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let remote_peer = PeerId::from(SecretKey::new());
+        let urn = Urn::new(git2::Oid::zero().into());
+
+        let applied = batch::batch(
+            &storage,
+            Some(batch::Action::Untrack {
+                urn: Cow::Borrowed(&urn),
+                peer: remote_peer,
+                policy: policy::Untrack::Any,
+            }),
+        )
+        .unwrap();
+        assert!(applied.updates.is_empty());
+        assert!(applied.rejections.is_empty());
+        assert_eq!(applied.unchanged.len(), 1);
+    }
+}
+
 #[test]
 fn untrack_nonexistent_is_not_tracked() {
     let tmp = tempfile::tempdir().unwrap();
@@ -5,4 +5,5 @@
 
 mod namespace;
 mod reference;
+mod refspec;
 mod remote;
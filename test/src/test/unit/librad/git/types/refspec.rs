@@ -0,0 +1,44 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::convert::TryFrom;
+
+use librad::{
+    git::types::{Exclusion, Fetchspec},
+    refspec_pattern,
+};
+
+#[test]
+fn exclusion_roundtrip() {
+    let excl = Exclusion::try_from("^refs/heads/wip/*").unwrap();
+    assert_eq!(excl.pattern(), &refspec_pattern!("refs/heads/wip/*"));
+    assert_eq!(excl.to_string(), "^refs/heads/wip/*");
+
+    let spec = Fetchspec::try_from("^refs/heads/wip/*").unwrap();
+    assert_eq!(spec.as_exclusion(), Some(&excl));
+    assert_eq!(spec.to_string(), "^refs/heads/wip/*");
+
+    let spec = Fetchspec::try_from("+refs/heads/*:refs/remotes/origin/*").unwrap();
+    assert!(spec.as_exclusion().is_none());
+
+    assert!(Exclusion::try_from("refs/heads/wip/*").is_err());
+}
+
+#[test]
+fn exclusion_matches() {
+    let wip = Exclusion::new(refspec_pattern!("refs/heads/wip/*"));
+    assert!(wip.matches("refs/heads/wip/x"));
+    assert!(wip.matches("refs/heads/wip/x/y"));
+    assert!(!wip.matches("refs/heads/wip"));
+    assert!(!wip.matches("refs/heads/main"));
+
+    let suffix = Exclusion::new(refspec_pattern!("refs/heads/*-tmp"));
+    assert!(suffix.matches("refs/heads/feature-tmp"));
+    assert!(!suffix.matches("refs/heads/feature"));
+
+    let exact = Exclusion::new(refspec_pattern!("refs/tags/nightly"));
+    assert!(exact.matches("refs/tags/nightly"));
+    assert!(!exact.matches("refs/tags/nightly-1"));
+}
@@ -10,7 +10,14 @@ use pretty_assertions::assert_eq;
 use librad::{
     git::{
         local::url::LocalUrl,
-        types::{remote::Remote, AsNamespace, Force, Namespace, Reference, Refspec},
+        types::{
+            remote::{Remote, SpecChanges, ValidationError},
+            AsNamespace,
+            Force,
+            Namespace,
+            Reference,
+            Refspec,
+        },
         Urn,
     },
     git_ext as ext,
@@ -121,3 +128,153 @@ fn check_remote_fetch_spec() -> Result<(), git2::Error> {
 
     Ok(())
 }
+
+#[test]
+fn builder_rejects_invalid_remotes() {
+    let url = LocalUrl::from(URN.clone());
+
+    let dup = || Refspec {
+        src: refspec_pattern!("refs/heads/*"),
+        dst: refspec_pattern!("refs/remotes/origin/*"),
+        force: Force::True,
+    };
+    let err = Remote::builder(url.clone(), reflike!("origin"))
+        .fetchspecs(vec![dup(), dup()])
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, ValidationError::Duplicate(_)));
+
+    let err = Remote::builder(url.clone(), reflike!("origin"))
+        .fetchspec(Refspec {
+            src: refspec_pattern!("refs/heads/*"),
+            dst: refspec_pattern!("refs/remotes/origin/main"),
+            force: Force::False,
+        })
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, ValidationError::Refspec { .. }));
+
+    let err = Remote::builder(url, reflike!("origin.d"))
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, ValidationError::Name(_)));
+}
+
+#[test]
+fn persist_is_idempotent() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init(tmp.path())?;
+
+    let remote = Remote::builder(LocalUrl::from(URN.clone()), reflike!("rad"))
+        .fetchspecs(vec![
+            Refspec {
+                src: refspec_pattern!("refs/heads/*"),
+                dst: refspec_pattern!("refs/remotes/rad/*"),
+                force: Force::True,
+            },
+            Refspec {
+                src: refspec_pattern!("refs/tags/*"),
+                dst: refspec_pattern!("refs/remotes/rad/tags/*"),
+                force: Force::True,
+            },
+        ])
+        .pushspec(Refspec {
+            src: reflike!("refs/heads/main"),
+            dst: reflike!("refs/heads/main"),
+            force: Force::False,
+        })
+        .build()?;
+
+    assert!(remote.persist(&repo)?);
+    assert!(!remote.persist(&repo)?);
+
+    let git_remote = repo.find_remote("rad")?;
+    assert_eq!(
+        git_remote
+            .fetch_refspecs()?
+            .iter()
+            .flatten()
+            .collect::<Vec<_>>(),
+        vec![
+            "+refs/heads/*:refs/remotes/rad/*",
+            "+refs/tags/*:refs/remotes/rad/tags/*"
+        ]
+    );
+    assert_eq!(
+        git_remote
+            .push_refspecs()?
+            .iter()
+            .flatten()
+            .collect::<Vec<_>>(),
+        vec!["refs/heads/main:refs/heads/main"]
+    );
+
+    let changed = Remote::builder(LocalUrl::from(URN.clone()), reflike!("rad"))
+        .fetchspec(Refspec {
+            src: refspec_pattern!("refs/heads/*"),
+            dst: refspec_pattern!("refs/remotes/rad/*"),
+            force: Force::True,
+        })
+        .build()?;
+    assert!(changed.persist(&repo)?);
+    assert_eq!(repo.find_remote("rad")?.fetch_refspecs()?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn update_and_delete() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init(tmp.path())?;
+    let heads = || Refspec {
+        src: refspec_pattern!("refs/heads/*"),
+        dst: refspec_pattern!("refs/remotes/rad/*"),
+        force: Force::True,
+    };
+    let tags = || Refspec {
+        src: refspec_pattern!("refs/tags/*"),
+        dst: refspec_pattern!("refs/remotes/rad/tags/*"),
+        force: Force::True,
+    };
+
+    let remote = Remote::builder(LocalUrl::from(URN.clone()), reflike!("rad"))
+        .fetchspec(heads())
+        .build()?;
+    let created = remote.update(&repo)?;
+    assert!(created.created);
+    assert_eq!(
+        created.fetch.added,
+        vec!["+refs/heads/*:refs/remotes/rad/*".to_owned()]
+    );
+
+    let remote = Remote::builder(LocalUrl::from(URN.clone()), reflike!("rad"))
+        .fetchspec(tags())
+        .build()?;
+    let updated = remote.update(&repo)?;
+    assert!(!updated.created);
+    assert!(!updated.url);
+    assert_eq!(
+        updated.fetch,
+        SpecChanges {
+            added: vec!["+refs/tags/*:refs/remotes/rad/tags/*".to_owned()],
+            removed: vec!["+refs/heads/*:refs/remotes/rad/*".to_owned()],
+        }
+    );
+    assert!(!remote.update(&repo)?.is_changed());
+
+    let found = Remote::<LocalUrl>::find(&repo, reflike!("rad"))?.expect("should exist");
+    assert_eq!(
+        found
+            .fetchspecs
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec!["+refs/tags/*:refs/remotes/rad/tags/*".to_owned()]
+    );
+
+    assert!(Remote::<LocalUrl>::delete(&repo, reflike!("rad"))?);
+    assert!(!Remote::<LocalUrl>::delete(&repo, reflike!("rad"))?);
+    assert!(Remote::<LocalUrl>::find(&repo, reflike!("rad"))?.is_none());
+
+    Ok(())
+}
@@ -72,3 +72,17 @@ fn test_signature_deserialize_wrong_version() {
     );
     assert!(serde_json::from_str::<Signature>(&ser).is_err())
 }
+
+#[test]
+fn test_secret_key_debug_is_redacted() {
+    let key = SecretKey::from_seed([42; 32]);
+    assert_eq!("SecretKey(<redacted>)", format!("{:?}", key))
+}
+
+#[test]
+fn test_secret_string_is_redacted() {
+    let secret = SecretString::from("hunter2");
+    assert_eq!("<redacted>", format!("{:?}", secret));
+    assert_eq!("<redacted>", secret.to_string());
+    assert_eq!("hunter2", secret.expose())
+}
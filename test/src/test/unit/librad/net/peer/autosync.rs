@@ -0,0 +1,90 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::{Duration, Instant};
+
+use librad::{
+    git::Urn,
+    git_ext as ext,
+    net::peer::autosync::{Config, Schedule},
+    PeerId,
+    SecretKey,
+};
+
+const CONFIG: Config = Config {
+    min_interval: Duration::from_secs(30),
+    backoff: Duration::from_secs(5),
+    max_backoff: Duration::from_secs(60),
+    max_attempts: 3,
+    max_concurrent: 1,
+};
+
+fn urn() -> Urn {
+    Urn::new(ext::Oid::from(git2::Oid::zero()))
+}
+
+#[test]
+fn deduplicates_requests() {
+    let peer = PeerId::from(SecretKey::new());
+    let mut schedule = Schedule::new(CONFIG);
+    let now = Instant::now();
+
+    assert_eq!(schedule.request(peer, urn(), now), Some(now));
+    assert_eq!(schedule.request(peer, urn(), now), None);
+    // Other peers are scheduled independently
+    let other = PeerId::from(SecretKey::new());
+    assert_eq!(schedule.request(other, urn(), now), Some(now));
+
+    // The deduplicated request is served after the minimum interval
+    let done = now + Duration::from_secs(1);
+    assert_eq!(
+        schedule.finished(peer, urn(), true, done),
+        Some(done + CONFIG.min_interval)
+    );
+    assert_eq!(schedule.finished(peer, urn(), true, done), None);
+}
+
+#[test]
+fn rate_limits_requests() {
+    let peer = PeerId::from(SecretKey::new());
+    let mut schedule = Schedule::new(CONFIG);
+    let now = Instant::now();
+
+    schedule.request(peer, urn(), now);
+    assert_eq!(schedule.finished(peer, urn(), true, now), None);
+    assert_eq!(
+        schedule.request(peer, urn(), now + Duration::from_secs(10)),
+        Some(now + CONFIG.min_interval)
+    );
+    schedule.finished(peer, urn(), true, now + CONFIG.min_interval);
+
+    let later = now + CONFIG.min_interval * 3;
+    assert_eq!(schedule.request(peer, urn(), later), Some(later));
+}
+
+#[test]
+fn backs_off_failures() {
+    let peer = PeerId::from(SecretKey::new());
+    let mut schedule = Schedule::new(CONFIG);
+    let now = Instant::now();
+
+    schedule.request(peer, urn(), now);
+    assert_eq!(
+        schedule.finished(peer, urn(), false, now),
+        Some(now + Duration::from_secs(5))
+    );
+    assert_eq!(
+        schedule.finished(peer, urn(), false, now),
+        Some(now + Duration::from_secs(10))
+    );
+    // Gave up after `max_attempts`
+    assert_eq!(schedule.finished(peer, urn(), false, now), None);
+
+    // The next announcement is still subject to the backoff
+    assert_eq!(
+        schedule.request(peer, urn(), now),
+        Some(now + Duration::from_secs(20))
+    );
+}
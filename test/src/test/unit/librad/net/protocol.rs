@@ -5,3 +5,4 @@
 
 mod broadcast;
 mod gossip;
+mod sessions;
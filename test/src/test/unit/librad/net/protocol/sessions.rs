@@ -0,0 +1,116 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use futures::{io::AsyncWriteExt as _, FutureExt as _};
+use librad::{
+    net::protocol::{
+        sessions::{Error, Sessions},
+        FetchQuota,
+    },
+    PeerId,
+    SecretKey,
+};
+
+const QUOTA: FetchQuota = FetchQuota {
+    sessions_per_peer: 2,
+    sessions: 2,
+    queued_per_peer: 2,
+    bytes_per_peer: 0,
+    bytes: 0,
+};
+
+async fn settle() {
+    for _ in 0..10 {
+        tokio::task::yield_now().await
+    }
+}
+
+#[tokio::test]
+async fn queues_are_served_round_robin() {
+    let sessions = Sessions::new(QUOTA);
+    let greedy = PeerId::from(SecretKey::new());
+    let modest = PeerId::from(SecretKey::new());
+
+    let first = sessions.acquire(greedy).await.unwrap();
+    let second = sessions.acquire(greedy).await.unwrap();
+    let third = tokio::spawn({
+        let sessions = sessions.clone();
+        async move { sessions.acquire(greedy).await }
+    });
+    let fourth = tokio::spawn({
+        let sessions = sessions.clone();
+        async move { sessions.acquire(greedy).await }
+    });
+    settle().await;
+    assert_eq!(sessions.queued(&greedy), 2);
+    assert!(matches!(
+        sessions.acquire(greedy).await,
+        Err(Error::QueueFull(_))
+    ));
+
+    let other = tokio::spawn({
+        let sessions = sessions.clone();
+        async move { sessions.acquire(modest).await }
+    });
+    settle().await;
+
+    drop(first);
+    let third = third.await.unwrap().unwrap();
+    drop(second);
+    // The modest peer is served before the greedy peer's next fetch
+    let other = other.await.unwrap().unwrap();
+    assert_eq!(other.peer(), modest);
+    assert_eq!(sessions.active(&modest), 1);
+    assert_eq!(sessions.queued(&greedy), 1);
+
+    drop(third);
+    let fourth = fourth.await.unwrap().unwrap();
+    assert_eq!(sessions.active(&greedy), 1);
+    drop((fourth, other));
+    assert_eq!(sessions.active(&greedy), 0);
+}
+
+#[tokio::test]
+async fn cancelled_waiters_release_their_place() {
+    let sessions = Sessions::new(QUOTA);
+    let peer = PeerId::from(SecretKey::new());
+
+    let first = sessions.acquire(peer).await.unwrap();
+    let _second = sessions.acquire(peer).await.unwrap();
+    assert!(sessions.acquire(peer).now_or_never().is_none());
+    assert!(sessions.acquire(peer).now_or_never().is_none());
+    // The cancelled waiters don't count towards the queue limit
+    let waiting = tokio::spawn({
+        let sessions = sessions.clone();
+        async move { sessions.acquire(peer).await }
+    });
+    settle().await;
+    assert_eq!(sessions.queued(&peer), 1);
+
+    drop(first);
+    waiting.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn throttles_bytes_per_peer() {
+    let sessions = Sessions::new(FetchQuota {
+        bytes_per_peer: 600,
+        ..QUOTA
+    });
+    let peer = PeerId::from(SecretKey::new());
+    let permit = sessions.acquire(peer).await.unwrap();
+
+    let mut send = permit.throttle(futures::io::sink());
+    send.write_all(&[0; 600]).await.unwrap();
+    assert!(send.write(&[0; 10]).now_or_never().is_none());
+
+    // Other peers have their own budget
+    let other = sessions
+        .acquire(PeerId::from(SecretKey::new()))
+        .await
+        .unwrap();
+    let mut send = other.throttle(futures::io::sink());
+    assert_eq!(send.write(&[0; 10]).now_or_never().unwrap().unwrap(), 10);
+}
@@ -5,10 +5,16 @@
 
 use std::{io, sync::Arc};
 
-use rustls::{ClientSession, ServerSession, Session};
+use rustls::{ClientSession, ServerSession, Session, TLSError};
+use time::{Duration, OffsetDateTime};
 
 use librad::{
-    net::tls::{make_client_config, make_server_config},
+    net::tls::{
+        make_client_config,
+        make_client_config_with_clock,
+        make_server_config,
+        make_server_config_with_clock,
+    },
     PeerId,
     SecretKey,
 };
@@ -30,13 +36,58 @@ fn test_can_handshake() {
     do_handshake(&mut client_session, &mut server_session)
 }
 
+#[test]
+fn test_can_handshake_with_skewed_clocks() {
+    assert!(handshake_with_skew(Duration::hours(6), -Duration::hours(6)).is_ok())
+}
+
+#[test]
+fn test_cannot_handshake_with_clocks_skewed_too_far() {
+    assert!(handshake_with_skew(Duration::days(365), -Duration::days(365)).is_err())
+}
+
+#[test]
+fn test_cannot_handshake_with_expired_cert() {
+    // The client's certificate expired about a month ago
+    assert!(handshake_with_skew(-Duration::days(120), Duration::ZERO).is_err())
+}
+
+/// Attempt a handshake between peers whose clocks are off by `client` and
+/// `server` respectively.
+fn handshake_with_skew(client: Duration, server: Duration) -> Result<(), TLSError> {
+    let client_key = SecretKey::new();
+    let server_key = SecretKey::new();
+
+    let server_id = PeerId::from(&server_key).to_string();
+
+    let client_config = Arc::new(
+        make_client_config_with_clock(client_key, move || OffsetDateTime::now_utc() + client)
+            .unwrap(),
+    );
+    let sni = webpki::DNSNameRef::try_from_ascii_str(&server_id).unwrap();
+    let mut client_session = ClientSession::new(&client_config, sni);
+
+    let server_config = Arc::new(
+        make_server_config_with_clock(server_key, move || OffsetDateTime::now_utc() + server)
+            .unwrap(),
+    );
+    let mut server_session = ServerSession::new(&server_config);
+
+    try_handshake(&mut client_session, &mut server_session)
+}
+
 fn do_handshake(client: &mut ClientSession, server: &mut ServerSession) {
+    try_handshake(client, server).unwrap()
+}
+
+fn try_handshake(client: &mut ClientSession, server: &mut ServerSession) -> Result<(), TLSError> {
     while server.is_handshaking() || client.is_handshaking() {
         transfer(client, server);
-        server.process_new_packets().unwrap();
+        server.process_new_packets()?;
         transfer(server, client);
-        client.process_new_packets().unwrap();
+        client.process_new_packets()?;
     }
+    Ok(())
 }
 
 fn transfer(left: &mut dyn Session, right: &mut dyn Session) {
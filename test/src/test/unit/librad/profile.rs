@@ -6,7 +6,18 @@
 use std::{collections::BTreeSet, fs};
 use tempfile::TempDir;
 
-use librad::profile::{id, Error, Profile, ProfileId, RadHome};
+use librad::{
+    crypto::{
+        keystore::{
+            crypto::{Pwhash, KDF_PARAMS_TEST},
+            pinentry::SecUtf8,
+            Keystore as _,
+        },
+        PeerId,
+    },
+    git::storage::ReadOnly,
+    profile::{id, Error, Profile, ProfileId, RadHome},
+};
 
 pub struct TempHome {
     tmp: TempDir,
@@ -154,3 +165,359 @@ fn set_profile() {
     let err = Profile::set(&tmp_home.home, "i-dont-exist".parse().unwrap()).unwrap_err();
     assert!(matches!(err, Error::DoesNotExist { .. }));
 }
+
+#[test]
+fn create_profile() {
+    let tmp_home = temp();
+    let pass = Pwhash::new(SecUtf8::from(b"42".to_vec()), *KDF_PARAMS_TEST);
+
+    let (profile, peer_id) = Profile::create(&tmp_home.home, pass.clone()).unwrap();
+    let key = profile.key_storage(pass).get_key().unwrap();
+    assert_eq!(peer_id, PeerId::from(key.secret_key));
+    assert_eq!(&peer_id, ReadOnly::open(profile.paths()).unwrap().peer_id());
+    assert_eq!(None, ProfileId::active(&tmp_home.home).unwrap());
+}
+
+#[test]
+fn delete_profile() {
+    let tmp_home = temp();
+
+    let keep = Profile::new(&tmp_home.home).unwrap();
+    let p = Profile::new(&tmp_home.home).unwrap();
+    Profile::set(&tmp_home.home, p.id().clone()).unwrap();
+    Profile::delete(&tmp_home.home, p.id().clone()).unwrap();
+
+    assert!(!p.paths().git_dir().exists());
+    assert!(Profile::get(&tmp_home.home, p.id().clone())
+        .unwrap()
+        .is_none());
+    assert_eq!(None, ProfileId::active(&tmp_home.home).unwrap());
+    assert_eq!(
+        vec![keep.id().clone()],
+        Profile::list(&tmp_home.home)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id().clone())
+            .collect::<Vec<_>>()
+    );
+
+    let err = Profile::delete(&tmp_home.home, p.id().clone()).unwrap_err();
+    assert!(matches!(err, Error::DoesNotExist { .. }));
+}
+
+#[test]
+fn list_nonexistent_home() {
+    let tmp_home = temp();
+    let home = RadHome::Root(tmp_home.tmp.path().join("nowhere"));
+    assert!(Profile::list(&home).unwrap().is_empty());
+}
+
+mod settings {
+    use librad::{
+        git::local::receive,
+        net::Network,
+        profile::{
+            settings::{self, Mirror, Store, Tracking},
+            Profile,
+            Settings,
+        },
+    };
+
+    use super::temp;
+
+    #[test]
+    fn defaults_if_missing() {
+        let tmp_home = temp();
+        let profile = Profile::new(&tmp_home.home).unwrap();
+        let settings = profile.settings().load().unwrap();
+        assert_eq!(Settings::default(), settings);
+        assert_eq!(Network::Main, settings.network.network().unwrap());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let tmp_home = temp();
+        let profile = Profile::new(&tmp_home.home).unwrap();
+        let store = profile.settings();
+
+        let settings = Settings {
+            seeds: vec![
+                "hyy5s7ysg96fqa91gbe7h38yddh4mkokft7y4htt8szt9e17sxoe3h@seed.example.com:12345"
+                    .to_owned(),
+            ],
+            tracking: Tracking {
+                data: false,
+                cobs: true,
+            },
+            network: settings::Network {
+                network: Some("testnet".to_owned()),
+                listen_addr: Some(([127, 0, 0, 1], 8776).into()),
+            },
+            mirrors: vec![Mirror {
+                url: "https://github.com/radicle-dev/radicle-link.git".to_owned(),
+                urn: Some(
+                    "rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo"
+                        .parse()
+                        .unwrap(),
+                ),
+            }],
+            push: receive::Policy {
+                refs: vec!["refs/heads/*".to_owned()],
+                require_signed: true,
+            },
+        };
+        store.store(&settings).unwrap();
+        assert_eq!(settings, store.load().unwrap());
+        assert_eq!(
+            settings,
+            Store::at(profile.paths().config_dir().join(settings::SETTINGS_FILE))
+                .load()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn update() {
+        let tmp_home = temp();
+        let store = Profile::new(&tmp_home.home).unwrap().settings();
+
+        store.update(|s| s.seeds.push("seed".to_owned())).unwrap();
+        let updated = store.update(|s| s.tracking.data = false).unwrap();
+        assert_eq!(vec!["seed".to_owned()], updated.seeds);
+        assert_eq!(updated, store.load().unwrap());
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let tmp_home = temp();
+        let store = Profile::new(&tmp_home.home).unwrap().settings();
+        std::fs::write(
+            store.path(),
+            format!("version = {}\n", settings::VERSION + 1),
+        )
+        .unwrap();
+        assert!(matches!(store.load(), Err(settings::Error::Version { .. })))
+    }
+}
+
+mod petnames {
+    use std::fs;
+
+    use librad::{
+        git::Urn,
+        profile::{
+            petnames::{
+                Conflict,
+                Error,
+                OnConflict,
+                PeerOrPetname,
+                Petname,
+                Petnames,
+                Target,
+                PETNAMES_FILE,
+            },
+            Profile,
+        },
+        PeerId,
+        SecretKey,
+    };
+
+    use super::temp;
+
+    fn urn() -> Urn {
+        "rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo"
+            .parse()
+            .unwrap()
+    }
+
+    fn peer() -> PeerId {
+        PeerId::from(SecretKey::new())
+    }
+
+    fn name(s: &str) -> Petname {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn invalid_names() {
+        assert!("".parse::<Petname>().is_err());
+        assert!("-leading".parse::<Petname>().is_err());
+        assert!("has space".parse::<Petname>().is_err());
+        assert!("rad:git:xyz".parse::<Petname>().is_err());
+        assert!(peer().to_string().parse::<Petname>().is_err());
+        assert!("radicle-link_v2.0".parse::<Petname>().is_ok());
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut petnames = Petnames::default();
+        let peer = peer();
+        assert!(petnames.insert(name("link"), urn()).unwrap());
+        assert!(!petnames.insert(name("link"), urn()).unwrap());
+        assert!(matches!(
+            petnames.insert(name("link"), peer),
+            Err(Error::Conflict { .. })
+        ));
+
+        assert_eq!(
+            Some(Target::Urn(urn())),
+            petnames.replace(name("link"), peer)
+        );
+        assert_eq!(Some(Target::Peer(peer)), petnames.get(&name("link")));
+        assert_eq!(
+            vec![&name("link")],
+            petnames.names(&Target::Peer(peer)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn resolve() {
+        let mut petnames = Petnames::default();
+        let peer = peer();
+        petnames.insert(name("link"), urn()).unwrap();
+        petnames.insert(name("seed"), peer).unwrap();
+
+        assert_eq!(urn(), petnames.resolve_urn("link").unwrap());
+        assert_eq!(urn(), petnames.resolve_urn(&urn().to_string()).unwrap());
+        assert_eq!(peer, petnames.resolve_peer("seed").unwrap());
+        assert!(matches!(
+            petnames.resolve_urn("seed"),
+            Err(Error::Kind { .. })
+        ));
+        assert!(matches!(
+            petnames.resolve_peer("nobody"),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn peer_or_petname() {
+        let mut petnames = Petnames::default();
+        let peer = peer();
+        petnames.insert(name("seed"), peer).unwrap();
+        petnames.insert(name("link"), urn()).unwrap();
+
+        let direct = peer.to_string().parse::<PeerOrPetname>().unwrap();
+        assert_eq!(PeerOrPetname::Peer(peer), direct);
+        assert_eq!(peer, direct.resolve(&Petnames::default()).unwrap());
+        assert_eq!(
+            peer,
+            "seed"
+                .parse::<PeerOrPetname>()
+                .unwrap()
+                .resolve(&petnames)
+                .unwrap()
+        );
+        assert!(matches!(
+            "link".parse::<PeerOrPetname>().unwrap().resolve(&petnames),
+            Err(Error::Kind { .. })
+        ));
+        assert!(matches!(
+            "has space".parse::<PeerOrPetname>(),
+            Err(Error::Unresolvable(_))
+        ));
+    }
+
+    #[test]
+    fn stored_in_settings() {
+        let tmp_home = temp();
+        let profile = Profile::new(&tmp_home.home).unwrap();
+        profile
+            .settings()
+            .update(|settings| settings.seeds.push("seed".to_owned()))
+            .unwrap();
+        profile
+            .petnames()
+            .update(|petnames| petnames.insert(name("link"), urn()))
+            .unwrap();
+
+        let settings = profile.settings().load().unwrap();
+        assert_eq!(vec!["seed".to_owned()], settings.seeds);
+        assert_eq!(
+            Some(Target::Urn(urn())),
+            settings.petnames.get(&name("link"))
+        );
+    }
+
+    #[test]
+    fn migrates_legacy_file() {
+        let tmp_home = temp();
+        let profile = Profile::new(&tmp_home.home).unwrap();
+        let (peer, other) = (peer(), self::peer());
+        let legacy = profile.paths().config_dir().join(PETNAMES_FILE);
+        fs::write(
+            &legacy,
+            format!(
+                "version = 1\n\n[urns]\nlink = \"{}\"\n\n[peers]\nseed = \"{}\"\n",
+                urn(),
+                other
+            ),
+        )
+        .unwrap();
+        profile
+            .settings()
+            .update(|settings| {
+                settings.petnames.insert(name("seed"), peer).unwrap();
+            })
+            .unwrap();
+
+        let store = profile.petnames();
+        let loaded = store.load().unwrap();
+        assert_eq!(Some(Target::Urn(urn())), loaded.get(&name("link")));
+        assert_eq!(Some(Target::Peer(peer)), loaded.get(&name("seed")));
+
+        store.store(&loaded).unwrap();
+        assert!(!legacy.exists());
+        assert_eq!(loaded, store.load().unwrap());
+    }
+
+    #[test]
+    fn export_import() {
+        let tmp_home = temp();
+        let profile = Profile::new(&tmp_home.home).unwrap();
+        let store = profile.petnames();
+        let (peer, other) = (peer(), self::peer());
+        store
+            .update(|petnames| {
+                petnames.insert(name("link"), urn())?;
+                petnames.insert(name("seed"), peer)
+            })
+            .unwrap();
+        assert_eq!(2, store.load().unwrap().iter().count());
+
+        let mut exported = Vec::new();
+        store.export(&mut exported).unwrap();
+
+        let theirs = super::temp();
+        let theirs = Profile::new(&theirs.home).unwrap().petnames();
+        theirs
+            .update(|petnames| petnames.insert(name("seed"), other))
+            .unwrap();
+
+        assert!(matches!(
+            theirs.import(exported.as_slice(), OnConflict::Fail),
+            Err(Error::Conflict { .. })
+        ));
+        assert_eq!(1, theirs.load().unwrap().iter().count());
+
+        let conflicts = theirs
+            .import(exported.as_slice(), OnConflict::Keep)
+            .unwrap();
+        assert_eq!(
+            vec![Conflict {
+                name: name("seed"),
+                ours: Target::Peer(other),
+                theirs: Target::Peer(peer),
+            }],
+            conflicts
+        );
+        let merged = theirs.load().unwrap();
+        assert_eq!(Some(Target::Urn(urn())), merged.get(&name("link")));
+        assert_eq!(Some(Target::Peer(other)), merged.get(&name("seed")));
+
+        theirs
+            .import(exported.as_slice(), OnConflict::Replace)
+            .unwrap();
+        assert_eq!(store.load().unwrap(), theirs.load().unwrap());
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::io::Write as _;
+
+use link_git::{
+    hash::ObjectId,
+    odb::index::{self, Index as _},
+};
+
+/// Create a commit with `msg` in a scratch repository, and add it to `repo` as
+/// a new packfile.
+fn add_pack(repo: &git2::Repository, msg: &str) -> ObjectId {
+    let tmp = tempfile::tempdir().unwrap();
+    let scratch = git2::Repository::init_bare(tmp.path()).unwrap();
+    let sig = git2::Signature::now("leecher", "leecher@example.com").unwrap();
+    let tree = scratch
+        .find_tree(scratch.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let oid = scratch.commit(None, &sig, &sig, msg, &tree, &[]).unwrap();
+
+    let mut pack = git2::Buf::new();
+    {
+        let mut builder = scratch.packbuilder().unwrap();
+        builder.insert_commit(oid).unwrap();
+        builder.write_buf(&mut pack).unwrap();
+    }
+    let odb = repo.odb().unwrap();
+    let mut writer = odb.packwriter().unwrap();
+    writer.write_all(&pack).unwrap();
+    writer.commit().unwrap();
+
+    ObjectId::from_20_bytes(oid.as_bytes())
+}
+
+#[test]
+fn new_packs_are_found_after_a_reload() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init_bare(tmp.path()).unwrap();
+    let old = add_pack(&repo, "old");
+
+    let idx = index::Shared::open(repo.path()).unwrap().with_stats();
+    assert!(idx.contains(old));
+    assert_eq!(idx.stats().reloads, 0);
+
+    let new = add_pack(&repo, "new");
+    // Only a rescan finds the new pack
+    assert!(!idx.contains_loaded(new));
+    assert!(idx.contains(new));
+    assert!(idx.contains_loaded(new));
+
+    let stats = idx.stats();
+    assert_eq!(stats.reloads, 1);
+    assert_eq!(stats.indices, 2);
+    // The index of the old pack is still used after the reload
+    assert!(idx.contains_loaded(old));
+}
+
+#[test]
+fn missing_objects_cause_no_more_than_one_reload() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init_bare(tmp.path()).unwrap();
+    let old = add_pack(&repo, "old");
+    let missing = ObjectId::from_20_bytes(
+        git2::Oid::hash_object(git2::ObjectType::Blob, b"missing")
+            .unwrap()
+            .as_bytes(),
+    );
+
+    let idx = index::Shared::open(repo.path()).unwrap().with_stats();
+    assert!(!idx.contains_loaded(missing));
+    assert_eq!(idx.stats().reloads, 0);
+    assert!(!idx.contains(missing));
+    let stats = idx.stats();
+    assert_eq!(stats.reloads, 1);
+    assert_eq!(stats.misses, 1);
+    assert!(idx.contains_loaded(old));
+}
@@ -0,0 +1,66 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use link_git::{
+    object::Kind,
+    odb::Object,
+    protocol::{
+        packwriter::{BaseCache, Cached, Thickener},
+        ObjectId,
+    },
+};
+
+/// Finds every object as a 16 byte blob, counting the lookups.
+#[derive(Default)]
+struct Counting(AtomicUsize);
+
+impl Thickener for &Counting {
+    fn find_object<'a>(&self, _: ObjectId, buf: &'a mut Vec<u8>) -> Option<Object<'a>> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        buf.clear();
+        buf.extend_from_slice(&[0; 16]);
+        Some(Object {
+            kind: Kind::Blob,
+            data: buf,
+            pack_location: None,
+        })
+    }
+}
+
+/// Look up three objects twice, returning the number of lookups which
+/// reached the inner [`Thickener`].
+fn lookups(capacity: usize, spill: bool) -> usize {
+    let inner = Counting::default();
+    let cached = Cached::new(&inner, BaseCache { capacity, spill }).unwrap();
+    let ids = (1..=3u8)
+        .map(|i| ObjectId::from_20_bytes(&[i; 20]))
+        .collect::<Vec<_>>();
+    let mut buf = Vec::new();
+    for id in ids.iter().chain(&ids) {
+        let obj = cached.find_object(*id, &mut buf).unwrap();
+        assert_eq!(Kind::Blob, obj.kind);
+        assert_eq!(&[0; 16], obj.data);
+    }
+    inner.0.load(Ordering::Relaxed)
+}
+
+#[test]
+fn cached_in_memory() {
+    assert_eq!(3, lookups(48, false))
+}
+
+#[test]
+fn evicted_beyond_capacity() {
+    // Only two objects fit, and every lookup evicts the oldest
+    assert_eq!(6, lookups(32, false))
+}
+
+#[test]
+fn spilled_beyond_capacity() {
+    assert_eq!(3, lookups(32, true));
+    assert_eq!(3, lookups(0, true))
+}
@@ -0,0 +1,57 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use futures::{executor::block_on, io::Cursor, AsyncReadExt as _};
+use link_git::protocol::keepalive::SkipKeepalive;
+
+fn read_all(input: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    block_on(SkipKeepalive::new(Cursor::new(input)).read_to_end(&mut buf)).unwrap();
+    buf
+}
+
+#[test]
+fn skips_keepalives() {
+    let input = b"000dpackfile\n0005\x010009\x01PACK0005\x010000";
+    assert_eq!(read_all(input), b"000dpackfile\n0009\x01PACK0000".to_vec())
+}
+
+#[test]
+fn passes_through_packets_of_keepalive_length() {
+    let input = b"0005a0005\x020001000e\x010005\x01abcd0000";
+    assert_eq!(read_all(input), input.to_vec())
+}
+
+#[test]
+fn passes_through_non_pktline() {
+    let input = b"this is not a pkt-line stream: 0005\x01";
+    assert_eq!(read_all(input), input.to_vec())
+}
+
+#[test]
+fn small_reads() {
+    let input = b"0009hello0005\x0100090005\x010000";
+    let output = block_on(async {
+        let mut reader = SkipKeepalive::new(Cursor::new(input));
+        let mut out = Vec::new();
+        let mut buf = [0; 1];
+        loop {
+            match reader.read(&mut buf).await.unwrap() {
+                0 => break,
+                n => out.extend_from_slice(&buf[..n]),
+            }
+        }
+        out
+    });
+    assert_eq!(output, b"0009hello00090005\x010000".to_vec())
+}
+
+#[test]
+fn truncated_header() {
+    let mut buf = Vec::new();
+    let err = block_on(SkipKeepalive::new(Cursor::new(b"0009hello00")).read_to_end(&mut buf))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof)
+}
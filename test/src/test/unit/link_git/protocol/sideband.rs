@@ -0,0 +1,23 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::io;
+
+use link_git::protocol::sideband::RemoteError;
+
+#[test]
+fn remote_error_from_io() {
+    let err = io::Error::new(
+        io::ErrorKind::Other,
+        RemoteError("upload-pack: not our ref".to_owned()),
+    );
+    assert_eq!(
+        RemoteError::from_io(&err).map(|e| e.0.as_str()),
+        Some("upload-pack: not our ref")
+    );
+
+    let err = io::Error::new(io::ErrorKind::UnexpectedEof, "early EOF");
+    assert!(RemoteError::from_io(&err).is_none());
+}
@@ -0,0 +1,120 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::sync::{Arc, Mutex};
+
+use futures::{executor::block_on, io::Cursor, AsyncReadExt as _, AsyncWriteExt as _};
+use link_git::protocol::trace::{Direction, Options, Packet, Record, Traced, Tracer};
+
+fn tracer(opt: Options) -> (Tracer, Arc<Mutex<Vec<Record>>>) {
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let tracer = Tracer::new(opt, {
+        let records = Arc::clone(&records);
+        move |record| records.lock().unwrap().push(record)
+    });
+    (tracer, records)
+}
+
+fn packets(records: &Mutex<Vec<Record>>) -> Vec<Packet> {
+    records
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|record| record.packet.clone())
+        .collect()
+}
+
+fn data(payload: &str) -> Packet {
+    Packet::Data {
+        len: payload.len(),
+        payload: payload.into(),
+        redacted: false,
+    }
+}
+
+#[test]
+fn records_reads() {
+    let (tracer, records) = tracer(Options::default());
+    let input = b"000eversion 2\n00010008done0002";
+    let mut buf = Vec::new();
+    let mut recv = Traced::new(Cursor::new(input), Direction::Recv, Some(&tracer));
+    block_on(recv.read_to_end(&mut buf)).unwrap();
+
+    assert_eq!(buf, input.to_vec());
+    assert_eq!(
+        packets(&records),
+        vec![
+            data("version 2\n"),
+            Packet::Delimiter,
+            data("done"),
+            Packet::ResponseEnd
+        ]
+    );
+    assert!(records
+        .lock()
+        .unwrap()
+        .iter()
+        .all(|record| record.direction == Direction::Recv))
+}
+
+#[test]
+fn records_small_writes() {
+    let (tracer, records) = tracer(Options::default());
+    let (_, mut send) = tracer.wrap(Cursor::new(vec![]), Cursor::new(vec![]));
+    block_on(async {
+        for byte in b"0009hello0000" {
+            send.write_all(&[*byte]).await.unwrap()
+        }
+    });
+
+    assert_eq!(packets(&records), vec![data("hello"), Packet::Flush]);
+}
+
+#[test]
+fn redacts_and_truncates() {
+    let (tracer, records) = tracer(Options {
+        max_payload: 4,
+        ..Options::default()
+    });
+    let input = b"0018server-option=s3cr3t000fhello world0009\x01PACK";
+    let mut recv = Traced::new(Cursor::new(input), Direction::Recv, Some(&tracer));
+    block_on(recv.read_to_end(&mut Vec::new())).unwrap();
+
+    assert_eq!(
+        packets(&records),
+        vec![
+            Packet::Data {
+                len: 20,
+                payload: "server-option=".into(),
+                redacted: true,
+            },
+            Packet::Data {
+                len: 11,
+                payload: "hell".into(),
+                redacted: false,
+            },
+            Packet::Data {
+                len: 5,
+                payload: "\x01".into(),
+                redacted: false,
+            },
+        ]
+    );
+}
+
+#[test]
+fn raw_after_pktlines() {
+    let (tracer, records) = tracer(Options::default());
+    let input = b"0000PACK and then some";
+    {
+        let mut recv = Traced::new(Cursor::new(input), Direction::Recv, Some(&tracer));
+        block_on(recv.read_to_end(&mut Vec::new())).unwrap();
+    }
+
+    assert_eq!(
+        packets(&records),
+        vec![Packet::Flush, Packet::Raw { len: 18 }]
+    );
+}
@@ -0,0 +1,56 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{fs, path::Path, thread, time::Duration};
+
+use link_git::{
+    hash::ObjectId,
+    refs::{db::Refdb, Target},
+};
+
+fn write_packed(path: &Path, oid: git2::Oid) {
+    fs::write(
+        path,
+        format!(
+            "# pack-refs with: peeled fully-peeled sorted \n{} refs/heads/main\n",
+            oid
+        ),
+    )
+    .unwrap()
+}
+
+fn main_of(refdb: &Refdb) -> Option<Target> {
+    refdb
+        .snapshot()
+        .unwrap()
+        .find("refs/heads/main")
+        .unwrap()
+        .map(|r| r.target)
+}
+
+#[test]
+fn snapshot_sees_rewritten_packed_refs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init_bare(tmp.path()).unwrap();
+    let (a, b) = (repo.blob(b"a").unwrap(), repo.blob(b"b").unwrap());
+    let packed = tmp.path().join("packed-refs");
+
+    write_packed(&packed, a);
+    let refdb = Refdb::open(tmp.path()).unwrap();
+    let peeled = |oid: git2::Oid| Some(Target::Peeled(ObjectId::from_20_bytes(oid.as_bytes())));
+    assert_eq!(peeled(a), main_of(&refdb));
+    // Unmodified packed-refs are not re-read
+    assert_eq!(peeled(a), main_of(&refdb));
+
+    // Rewrite until the change is visible in the mtime, whatever its
+    // granularity
+    let mtime = || packed.metadata().unwrap().modified().unwrap();
+    let before = mtime();
+    while mtime() == before {
+        thread::sleep(Duration::from_millis(10));
+        write_packed(&packed, b);
+    }
+    assert_eq!(peeled(b), main_of(&refdb));
+}
@@ -3,4 +3,19 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod bundle;
+mod correlation;
+mod delegations;
+mod error;
+mod faulty;
+mod fetch;
+mod haves;
+mod inflight;
+mod odb;
+mod refdb;
 mod refs;
+mod report;
+mod session;
+mod sim;
+mod throttle;
+mod validation;
@@ -0,0 +1,37 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use link_replication::io::BundlePolicy;
+
+#[test]
+fn nothing_allowed_by_default() {
+    let policy = BundlePolicy::default();
+    assert!(policy.is_empty());
+    assert!(!policy.allows("https://bundles.example.com/foo.bundle"));
+    assert!(!policy.allows("/tmp/foo.bundle"));
+}
+
+#[test]
+fn allowed_hosts() {
+    let policy = BundlePolicy::default().allow_host("Bundles.example.com");
+    assert!(policy.allows("https://bundles.example.com/foo.bundle"));
+    assert!(policy.allows("https://BUNDLES.example.com?foo"));
+
+    assert!(!policy.allows("http://bundles.example.com/foo.bundle"));
+    assert!(!policy.allows("https://bundles.example.com:8443/foo.bundle"));
+    assert!(!policy.allows("https://bundles.example.com@169.254.169.254/"));
+    assert!(!policy.allows("https://localhost/foo.bundle"));
+    assert!(!policy.allows("/tmp/foo.bundle"));
+}
+
+#[test]
+fn allowed_files() {
+    let policy = BundlePolicy::default().allow_file(true);
+    assert!(policy.allows("/tmp/foo.bundle"));
+    assert!(policy.allows("file:///tmp/foo.bundle"));
+
+    assert!(!policy.allows("foo.bundle"));
+    assert!(!policy.allows("https://bundles.example.com/foo.bundle"));
+}
@@ -0,0 +1,23 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use link_replication::CorrelationId;
+
+#[test]
+fn roundtrip() {
+    let id = CorrelationId::new();
+    assert_eq!(id, id.to_string().parse().unwrap())
+}
+
+#[test]
+fn fixed_width() {
+    let id: CorrelationId = "2a".parse().unwrap();
+    assert_eq!("000000000000002a", id.to_string())
+}
+
+#[test]
+fn unique() {
+    assert_ne!(CorrelationId::new(), CorrelationId::new())
+}
@@ -0,0 +1,100 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::BTreeSet, convert::Infallible, iter::FromIterator as _};
+
+use link_crypto::{PeerId, SecretKey};
+use link_replication::{DelegationChanges, ObjectId, Urn, VerifiedIdentity};
+use radicle_data::NonEmpty;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Name(String);
+
+impl Urn for Name {
+    type Error = Infallible;
+
+    fn try_from_id(s: impl AsRef<str>) -> Result<Self, Self::Error> {
+        Ok(Self(s.as_ref().to_owned()))
+    }
+
+    fn encode_id(&self) -> String {
+        self.0.clone()
+    }
+}
+
+struct Identity {
+    ids: BTreeSet<PeerId>,
+    urns: BTreeSet<Name>,
+}
+
+impl Identity {
+    fn new(ids: &[PeerId], urns: &[&str]) -> Self {
+        Self {
+            ids: ids.iter().copied().collect(),
+            urns: urns.iter().map(|urn| Name(urn.to_string())).collect(),
+        }
+    }
+}
+
+impl VerifiedIdentity for Identity {
+    type Rev = ();
+    type Oid = ObjectId;
+    type Urn = Name;
+
+    fn revision(&self) -> Self::Rev {}
+
+    fn content_id(&self) -> Self::Oid {
+        ObjectId::null_sha1()
+    }
+
+    fn urn(&self) -> Self::Urn {
+        Name("project".to_owned())
+    }
+
+    fn delegate_ids(&self) -> NonEmpty<BTreeSet<PeerId>> {
+        NonEmpty::from_maybe_empty(self.ids.clone()).unwrap()
+    }
+
+    fn delegate_urns(&self) -> BTreeSet<Self::Urn> {
+        self.urns.clone()
+    }
+}
+
+fn peer(seed: u8) -> PeerId {
+    PeerId::from(&SecretKey::from_seed([seed; 32]))
+}
+
+#[test]
+fn unchanged() {
+    let (alice, bob) = (peer(1), peer(2));
+    assert_eq!(
+        None,
+        DelegationChanges::between(
+            &Identity::new(&[alice, bob], &["alice"]),
+            &Identity::new(&[bob, alice], &["alice"])
+        )
+    )
+}
+
+#[test]
+fn added_and_removed() {
+    let (alice, bob, carol) = (peer(1), peer(2), peer(3));
+    let changes = DelegationChanges::between(
+        &Identity::new(&[alice, bob], &["alice"]),
+        &Identity::new(&[alice, carol], &["carol"]),
+    )
+    .expect("delegations changed");
+
+    assert_eq!(BTreeSet::from_iter([carol]), changes.added);
+    assert_eq!(BTreeSet::from_iter([bob]), changes.removed);
+    assert_eq!(
+        BTreeSet::from_iter([Name("carol".to_owned())]),
+        changes.added_urns
+    );
+    assert_eq!(
+        BTreeSet::from_iter([Name("alice".to_owned())]),
+        changes.removed_urns
+    );
+}
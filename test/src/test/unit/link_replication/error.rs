@@ -0,0 +1,54 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::io;
+
+use link_replication::{
+    error::{Failure, Layout, Replicate, Validation},
+    CorrelationId,
+};
+
+fn io_error() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionReset, "reset")
+}
+
+#[test]
+fn only_net_is_retryable() {
+    let failures = vec![
+        Failure::SelfReplication,
+        Failure::MissingRadId,
+        Failure::NoVerificationRefs,
+        Failure::Net(Box::new(io_error())),
+        Failure::Layout(Layout::MissingRequiredRefs(vec![])),
+        Failure::Verification(Box::new(io_error())),
+        Failure::Sigrefs(Box::new(io_error())),
+        Failure::Integrity(Box::new(io_error())),
+        Failure::Validation(vec![Validation::Unexpected(
+            "refs/remotes/x/heads/y".into(),
+        )]),
+        Failure::Storage(Box::new(io_error())),
+    ];
+    let retryable = failures
+        .iter()
+        .filter(|f| f.is_retryable())
+        .map(Failure::code)
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["net"], retryable)
+}
+
+#[test]
+fn source_is_retained() {
+    let err = Replicate {
+        id: CorrelationId::new(),
+        failure: Failure::Net(Box::new(io_error())),
+    };
+    assert_eq!("net", err.code());
+
+    let source = std::error::Error::source(&err)
+        .and_then(std::error::Error::source)
+        .and_then(|e| e.downcast_ref::<io::Error>())
+        .expect("io::Error source");
+    assert_eq!(io::ErrorKind::ConnectionReset, source.kind())
+}
@@ -0,0 +1,122 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use futures_lite::{
+    future::block_on,
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+};
+use link_replication::io::{
+    session::{Session, Stream},
+    Connection as _,
+    Faults,
+    Faulty,
+    Replay,
+};
+
+fn conn(recv: &[u8], faults: Faults) -> Faulty<Replay> {
+    Faulty::new(
+        Replay::new(Session {
+            streams: vec![Stream {
+                send: Vec::new(),
+                recv: recv.to_vec(),
+            }],
+        }),
+        faults,
+    )
+}
+
+#[test]
+fn no_faults() {
+    let conn = conn(b"packfile", Faults::default());
+    block_on(async {
+        let (mut recv, _) = conn.open_stream().await.unwrap();
+        let mut buf = Vec::new();
+        recv.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(b"packfile", buf.as_slice())
+    })
+}
+
+#[test]
+fn truncate() {
+    let conn = conn(
+        b"packfile",
+        Faults {
+            truncate_after: Some(4),
+            ..Faults::default()
+        },
+    );
+    block_on(async {
+        let (mut recv, _) = conn.open_stream().await.unwrap();
+        let mut buf = Vec::new();
+        recv.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(b"pack", buf.as_slice())
+    })
+}
+
+#[test]
+fn disconnect() {
+    let conn = conn(
+        b"packfile",
+        Faults {
+            disconnect: 1.0,
+            ..Faults::default()
+        },
+    );
+    block_on(async {
+        let (mut recv, mut send) = conn.open_stream().await.unwrap();
+        let mut buf = Vec::new();
+        let err = recv.read_to_end(&mut buf).await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+        let err = recv.read_to_end(&mut buf).await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+        let err = send.write_all(b"want").await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+    })
+}
+
+#[test]
+fn latency() {
+    let latency = Duration::from_millis(50);
+    let conn = conn(
+        b"packfile",
+        Faults {
+            latency: Some(latency),
+            ..Faults::default()
+        },
+    );
+    block_on(async {
+        let start = Instant::now();
+        let (mut recv, mut send) = conn.open_stream().await.unwrap();
+        send.write_all(b"want").await.unwrap();
+        let mut buf = Vec::new();
+        recv.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(b"packfile", buf.as_slice());
+        assert!(start.elapsed() >= 2 * latency)
+    })
+}
+
+#[test]
+fn bandwidth() {
+    let conn = conn(
+        &[0; 200],
+        Faults {
+            bytes_per_sec: Some(1000),
+            ..Faults::default()
+        },
+    );
+    block_on(async {
+        let start = Instant::now();
+        let (mut recv, _) = conn.open_stream().await.unwrap();
+        let mut buf = Vec::new();
+        recv.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(200, buf.len());
+        assert!(start.elapsed() >= Duration::from_millis(200))
+    })
+}
@@ -0,0 +1,88 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use bstr::BStr;
+use link_replication::{refs::parsed::Cat, FetchSpec};
+
+#[test]
+fn default_categories() {
+    let spec = FetchSpec::default();
+    for cat in [Cat::Heads, Cat::Notes, Cat::Tags, Cat::Cobs] {
+        assert!(spec.allows(&cat), "{} should be fetched by default", cat)
+    }
+    assert!(!spec.allows(&Cat::Unknown("patches".into())))
+}
+
+#[test]
+fn extra_categories() {
+    let spec = FetchSpec::default()
+        .with_category("patches")
+        .with_category("reviews");
+    assert!(spec.allows(&Cat::Unknown("patches".into())));
+    assert!(spec.allows(&Cat::Unknown("reviews".into())));
+    assert!(!spec.allows(&Cat::Unknown("drafts".into())));
+    assert_eq!(
+        vec![BStr::new("patches"), BStr::new("reviews")],
+        spec.categories().collect::<Vec<_>>()
+    )
+}
+
+#[test]
+fn reserved_categories() {
+    let spec = FetchSpec::default()
+        .with_category("")
+        .with_category("heads")
+        .with_category("rad")
+        .with_category("remotes")
+        .with_category("patches/wip");
+    assert_eq!(FetchSpec::default(), spec)
+}
+
+#[test]
+fn exclusions() {
+    let spec = FetchSpec::default()
+        .with_exclusion("refs/heads/wip/*")
+        .with_exclusion("refs/tags/*-rc")
+        .with_exclusion("refs/heads/scratch");
+    assert!(spec.excludes(BStr::new("refs/heads/wip/foo")));
+    assert!(spec.excludes(BStr::new("refs/heads/wip/foo/bar")));
+    assert!(spec.excludes(BStr::new("refs/tags/v1-rc")));
+    assert!(spec.excludes(BStr::new("refs/heads/scratch")));
+    assert!(!spec.excludes(BStr::new("refs/heads/wip")));
+    assert!(!spec.excludes(BStr::new("refs/heads/scratch/foo")));
+    assert!(!spec.excludes(BStr::new("refs/tags/v1")));
+    assert!(!FetchSpec::default().excludes(BStr::new("refs/heads/wip/foo")))
+}
+
+#[test]
+fn tracking_cutoff() {
+    assert_eq!(
+        link_replication::fetch::DEFAULT_TRACKING_CUTOFF,
+        FetchSpec::default().tracking_cutoff()
+    );
+    assert_eq!(
+        5,
+        FetchSpec::default()
+            .with_tracking_cutoff(5)
+            .tracking_cutoff()
+    )
+}
+
+#[test]
+fn concurrent_fetches() {
+    assert_eq!(None, FetchSpec::default().concurrent_fetches());
+    assert_eq!(
+        Some(4),
+        FetchSpec::default()
+            .with_concurrent_fetches(4)
+            .concurrent_fetches()
+    );
+    assert_eq!(
+        Some(1),
+        FetchSpec::default()
+            .with_concurrent_fetches(0)
+            .concurrent_fetches()
+    )
+}
@@ -0,0 +1,51 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use link_crypto::{PeerId, SecretKey};
+use link_git::hash::ObjectId;
+use link_replication::{haves, io, namespace};
+
+/// Commit at `time` seconds since the epoch, and point `name` at the commit.
+fn commit_at(repo: &git2::Repository, name: &str, time: i64) -> ObjectId {
+    let sig =
+        git2::Signature::new("leecher", "leecher@example.com", &git2::Time::new(time, 0)).unwrap();
+    let tree = repo
+        .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let oid = repo
+        .commit(Some(name), &sig, &sig, name, &tree, &[])
+        .unwrap();
+    ObjectId::from_20_bytes(oid.as_bytes())
+}
+
+#[test]
+fn most_recent_commits_first() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init_bare(tmp.path()).unwrap();
+    let old = commit_at(&repo, "refs/namespaces/foo/refs/heads/old", 1_000);
+    let newest = commit_at(&repo, "refs/namespaces/foo/refs/heads/newest", 3_000);
+    let newer = commit_at(&repo, "refs/namespaces/foo/refs/heads/newer", 2_000);
+    // Not a commit, so not offered
+    let blob = repo.blob(b"blob").unwrap();
+    repo.reference("refs/namespaces/foo/refs/heads/blob", blob, false, "blob")
+        .unwrap();
+    // Outside of the namespace
+    commit_at(&repo, "refs/namespaces/bar/refs/heads/newest", 4_000);
+
+    let db = io::Refdb::new(
+        io::UserInfo {
+            name: "leecher".to_owned(),
+            peer_id: PeerId::from(&SecretKey::from_seed([0; 32])),
+        },
+        io::Odb::open(repo.path()).unwrap(),
+        link_git::refs::db::Refdb::open(repo.path()).unwrap(),
+        namespace::expand("foo").unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(vec![newest, newer], haves::recent(&db, 2));
+    assert_eq!(vec![newest, newer, old], haves::recent(&db, 10));
+    assert!(haves::recent(&db, 0).is_empty());
+}
@@ -0,0 +1,50 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use futures_lite::future::block_on;
+use link_git::protocol::ObjectId;
+use link_replication::io::InFlight;
+
+fn oid(byte: u8) -> ObjectId {
+    ObjectId::from_20_bytes(&[byte; 20])
+}
+
+#[test]
+fn claims_are_exclusive() {
+    let in_flight = InFlight::default();
+    let (first, elsewhere) = in_flight.claim(vec![oid(1), oid(2)]);
+    assert_eq!(first.oids(), &[oid(1), oid(2)]);
+    assert!(elsewhere.is_empty());
+
+    let (second, elsewhere) = in_flight.claim(vec![oid(2), oid(3)]);
+    assert_eq!(second.oids(), &[oid(3)]);
+    assert_eq!(elsewhere.len(), 1);
+}
+
+#[test]
+fn delivered_claims_are_not_requested_again() {
+    let in_flight = InFlight::default();
+    let (claimant, _) = in_flight.claim(vec![oid(1)]);
+    let (_, elsewhere) = in_flight.claim(vec![oid(1)]);
+
+    drop(claimant);
+    assert!(block_on(elsewhere.released(|_| true)).is_empty());
+}
+
+#[test]
+fn failed_claims_are_requested_again() {
+    let in_flight = InFlight::default();
+    let (claimant, _) = in_flight.claim(vec![oid(1), oid(2)]);
+    let (_, elsewhere) = in_flight.claim(vec![oid(1), oid(2)]);
+
+    // The claimant fails after delivering only one of its wants
+    drop(claimant);
+    let missing = block_on(elsewhere.released(|id| id == &oid(1)));
+    assert_eq!(missing, vec![oid(2)]);
+
+    let (retry, elsewhere) = in_flight.claim(missing);
+    assert_eq!(retry.oids(), &[oid(2)]);
+    assert!(elsewhere.is_empty());
+}
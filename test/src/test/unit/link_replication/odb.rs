@@ -0,0 +1,62 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use link_git::{
+    hash::{oid, ObjectId},
+    object::Kind,
+    odb::backend::{Loose, Remote, RemoteError},
+};
+use link_replication::{io, Odb as _};
+
+/// A [`Remote`] which claims to have every object, but always returns the
+/// same blob.
+struct Lying;
+
+impl Remote for Lying {
+    fn contains(&self, _: &oid) -> Result<bool, RemoteError> {
+        Ok(true)
+    }
+
+    fn fetch(&self, _: &oid, buf: &mut Vec<u8>) -> Result<Option<Kind>, RemoteError> {
+        buf.clear();
+        buf.extend_from_slice(b"lie");
+        Ok(Some(Kind::Blob))
+    }
+}
+
+#[test]
+fn remote_objects_are_copied_before_they_are_reported() {
+    let tmp = tempfile::tempdir().unwrap();
+    let local = git2::Repository::init_bare(tmp.path().join("local")).unwrap();
+    let remote = git2::Repository::init_bare(tmp.path().join("remote")).unwrap();
+    let cold = remote.blob(b"cold").unwrap();
+    let missing = git2::Oid::hash_object(git2::ObjectType::Blob, b"missing").unwrap();
+
+    let odb = io::Odb::open_with(
+        local.path(),
+        Some(Box::new(Loose::at(remote.path().join("objects")))),
+    )
+    .unwrap();
+    assert!(local.find_blob(cold).is_err());
+    assert!(odb.contains(ObjectId::from_20_bytes(cold.as_bytes())));
+    // libgit2 can read what the odb reports
+    assert_eq!(b"cold", local.find_blob(cold).unwrap().content());
+
+    assert!(!odb.contains(ObjectId::from_20_bytes(missing.as_bytes())));
+}
+
+#[test]
+fn remote_objects_not_matching_their_id_are_rejected() {
+    let tmp = tempfile::tempdir().unwrap();
+    let local = git2::Repository::init_bare(tmp.path()).unwrap();
+    let truth = git2::Oid::hash_object(git2::ObjectType::Blob, b"truth").unwrap();
+    let lie = git2::Oid::hash_object(git2::ObjectType::Blob, b"lie").unwrap();
+
+    let odb = io::Odb::open_with(local.path(), Some(Box::new(Lying))).unwrap();
+    assert!(!odb.contains(ObjectId::from_20_bytes(truth.as_bytes())));
+    // Nothing was cached, under either name
+    assert!(local.find_blob(truth).is_err());
+    assert!(local.find_blob(lie).is_err());
+}
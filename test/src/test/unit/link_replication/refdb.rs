@@ -0,0 +1,176 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use bstr::{BStr, BString};
+use link_crypto::{PeerId, SecretKey};
+use link_git::hash::ObjectId;
+use link_replication::{
+    io::{self, MAX_SYMREF_DEPTH},
+    namespace,
+    refdb,
+    refs,
+    Policy,
+    Refdb as _,
+    SymrefTarget,
+    Update,
+};
+use tempfile::{tempdir, TempDir};
+
+/// A repository with a single commit, and a [`io::Refdb`] of the namespace
+/// `foo` of it.
+fn setup() -> (TempDir, git2::Repository, io::Refdb<io::Odb>, ObjectId) {
+    let tmp = tempdir().unwrap();
+    let repo = git2::Repository::init_bare(tmp.path()).unwrap();
+    let tip = {
+        let sig = git2::Signature::now("leecher", "leecher@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let oid = repo
+            .commit(None, &sig, &sig, "initial", &tree, &[])
+            .unwrap();
+        ObjectId::from_20_bytes(oid.as_bytes())
+    };
+    let db = io::Refdb::new(
+        io::UserInfo {
+            name: "leecher".to_owned(),
+            peer_id: PeerId::from(&SecretKey::from_seed([0; 32])),
+        },
+        io::Odb::open(repo.path()).unwrap(),
+        link_git::refs::db::Refdb::open(repo.path()).unwrap(),
+        namespace::expand("foo").unwrap(),
+    )
+    .unwrap();
+
+    (tmp, repo, db, tip)
+}
+
+fn head(i: usize) -> String {
+    format!("refs/heads/{}", i)
+}
+
+fn namespaced(name: &str) -> String {
+    format!("refs/namespaces/foo/{}", name)
+}
+
+/// Point `refs/heads/<i>` at `refs/heads/<i + 1>`, creating the latter at `tip`
+/// if it doesn't exist.
+fn chain(i: usize, tip: ObjectId) -> Update<'static> {
+    Update::Symbolic {
+        name: BString::from(head(i)).into(),
+        target: SymrefTarget {
+            name: refs::Namespaced {
+                namespace: Some(BString::from("foo").into()),
+                refname: BString::from(head(i + 1)).into(),
+            },
+            target: tip,
+        },
+        type_change: Policy::Allow,
+    }
+}
+
+#[test]
+fn only_symrefs_nested_too_deeply_are_rejected() {
+    let (_tmp, repo, mut db, tip) = setup();
+
+    // Each update creates the target the next one turns into a symref, so
+    // `refs/heads/0` resolves through one symref too many
+    let mut updates = (0..=MAX_SYMREF_DEPTH)
+        .map(|i| chain(i, tip))
+        .collect::<Vec<_>>();
+    updates.push(Update::Direct {
+        name: BString::from("refs/tags/v1").into(),
+        target: tip,
+        no_ff: Policy::Abort,
+    });
+
+    let applied = db.update(updates).unwrap();
+    assert_eq!(1, applied.rejected.len());
+    assert!(matches!(
+        &applied.rejected[0],
+        Update::Symbolic { name, .. } if name.as_ref() == BStr::new(&head(0))
+    ));
+    assert!(repo.find_reference(&namespaced(&head(0))).is_err());
+    assert_eq!(
+        Some(namespaced(&head(2)).as_str()),
+        repo.find_reference(&namespaced(&head(1)))
+            .unwrap()
+            .symbolic_target()
+    );
+    assert_eq!(
+        tip.as_bytes(),
+        repo.refname_to_id(&namespaced("refs/tags/v1"))
+            .unwrap()
+            .as_bytes()
+    );
+}
+
+#[test]
+fn symrefs_within_the_limit_are_applied() {
+    let (_tmp, repo, mut db, tip) = setup();
+
+    let applied = db
+        .update((1..=MAX_SYMREF_DEPTH).map(|i| chain(i, tip)))
+        .unwrap();
+    assert!(applied.rejected.is_empty());
+    for i in 1..=MAX_SYMREF_DEPTH {
+        assert_eq!(
+            Some(namespaced(&head(i + 1)).as_str()),
+            repo.find_reference(&namespaced(&head(i)))
+                .unwrap()
+                .symbolic_target()
+        );
+    }
+}
+
+#[test]
+fn bulk_loaded_remote_tracking_refs_match_the_refdb() {
+    let (_tmp, _repo, mut db, tip) = setup();
+
+    let remotes = [
+        PeerId::from(&SecretKey::from_seed([1; 32])),
+        PeerId::from(&SecretKey::from_seed([2; 32])),
+    ];
+    let loaded = &remotes[..1];
+    let names = remotes
+        .iter()
+        .flat_map(|remote| {
+            vec![
+                format!("refs/remotes/{}/heads/main", remote),
+                format!("refs/remotes/{}/rad/id", remote),
+            ]
+        })
+        .chain(Some(head(0)))
+        .collect::<Vec<_>>();
+    let applied = db
+        .update(names.iter().map(|name| Update::Direct {
+            name: BString::from(name.as_str()).into(),
+            target: tip,
+            no_ff: Policy::Abort,
+        }))
+        .unwrap();
+    assert!(applied.rejected.is_empty());
+
+    let mem =
+        refdb::Mem::load(&db, loaded.iter().map(|id| format!("refs/remotes/{}", id))).unwrap();
+    for name in &names {
+        let expected = if name.starts_with(&format!("refs/remotes/{}/", loaded[0])) {
+            db.refname_to_id(name.as_str()).unwrap()
+        } else {
+            None
+        };
+        assert_eq!(
+            expected,
+            mem.refname_to_id(name.as_str()).unwrap(),
+            "{}",
+            name
+        );
+    }
+    assert_eq!(
+        Some(tip),
+        mem.refname_to_id(format!("refs/remotes/{}/rad/id", loaded[0]).as_str())
+            .unwrap()
+    );
+}
@@ -7,7 +7,8 @@ use std::{fmt::Debug, num::ParseIntError};
 
 use either::Either::*;
 use link_replication::{
-    refs::parsed::{parse, Cat, Identity, Parsed, Rad, Refs},
+    error::InvalidId,
+    refs::parsed::{parse, parse_id, Cat, Identity, Parsed, Rad, Refs, MAX_ID_LEN},
     Urn,
 };
 
@@ -156,6 +157,36 @@ fn rad_ids() {
     );
 }
 
+#[test]
+fn rad_ids_strict() {
+    let too_long = "1".repeat(MAX_ID_LEN + 1);
+    for x in ["042", "4-2", "4_2", "x", too_long.as_str()] {
+        fail::<Usize>(&format!("refs/rad/ids/{}", x));
+    }
+}
+
+#[test]
+fn parse_id_errors() {
+    assert!(matches!(parse_id::<Usize>(b""), Err(InvalidId::Empty)));
+    assert!(matches!(
+        parse_id::<Usize>("1".repeat(MAX_ID_LEN + 1).as_bytes()),
+        Err(InvalidId::TooLong { .. })
+    ));
+    assert!(matches!(
+        parse_id::<Usize>(b"4.2"),
+        Err(InvalidId::Alphabet { byte: b'.', pos: 1 })
+    ));
+    assert!(matches!(
+        parse_id::<Usize>(b"x"),
+        Err(InvalidId::Decode { .. })
+    ));
+    assert!(matches!(
+        parse_id::<Usize>(b"042"),
+        Err(InvalidId::NotCanonical { .. })
+    ));
+    assert_eq!(Usize(42), parse_id::<Usize>(b"42").unwrap());
+}
+
 #[test]
 fn unknown_rad() {
     fail::<Identity>("refs/rad/asdf");
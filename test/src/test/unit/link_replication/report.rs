@@ -0,0 +1,134 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use futures::executor::block_on;
+use link_crypto::{PeerId, SecretKey};
+use link_replication::{
+    report::{Counts, Timings},
+    sim::{self, Network, Peer},
+    FetchLimit,
+    FetchSpec,
+    Rollback,
+    Success,
+    Validate,
+    ValidationPolicy,
+};
+use serde_json::json;
+
+#[test]
+fn timings_are_millis() {
+    let timings = Timings {
+        peek: Duration::from_millis(1500),
+        fetch: Duration::from_secs(3),
+        validate: Duration::from_micros(2500),
+        apply: Duration::from_millis(7),
+        total: Duration::from_secs(5),
+    };
+    assert_eq!(
+        json!({
+            "peek": 1500,
+            "fetch": 3000,
+            "validate": 2,
+            "apply": 7,
+            "total": 5000,
+        }),
+        serde_json::to_value(timings).unwrap()
+    )
+}
+
+/// Pull (or clone) from `remote_id` to `local_id`, validating the whole
+/// namespace.
+fn pull(net: &Network, local_id: PeerId, remote_id: PeerId, clone: bool) -> Success<sim::Urn> {
+    let cx = &mut net.conn(&local_id, &remote_id);
+    let (limit, spec) = (FetchLimit::default(), FetchSpec::default());
+    let (validation, policy) = (Validate::Full, ValidationPolicy::Warn);
+    let rollback = Rollback::default();
+    block_on(async {
+        if clone {
+            link_replication::clone(
+                cx, limit, spec, remote_id, None, validation, policy, rollback,
+            )
+            .await
+        } else {
+            link_replication::pull(
+                cx, limit, spec, remote_id, None, validation, policy, rollback,
+            )
+            .await
+        }
+    })
+    .unwrap()
+}
+
+#[test]
+fn report_attributes_updates_to_peers() {
+    let mut net = Network::default();
+    let maintainer = net
+        .add(Peer::new(PeerId::from(&SecretKey::from_seed([0; 32]))))
+        .id;
+    let seed = net
+        .add(Peer::new(PeerId::from(&SecretKey::from_seed([1; 32]))))
+        .id;
+    let stranger = PeerId::from(&SecretKey::from_seed([2; 32]));
+
+    let peer = net.peer(&maintainer).unwrap();
+    peer.identity("project", Some(maintainer));
+    let tip = peer.odb.commit(&[], "initial commit");
+    peer.set_ref("refs/heads/main", tip);
+    peer.sign_refs();
+
+    let main = format!("refs/remotes/{}/heads/main", maintainer);
+    let report = pull(&net, seed, maintainer, true).report();
+    assert!(report.updated.contains(&main), "{:?}", report.updated);
+    assert!(report.updated.iter().any(|name| name == "refs/rad/id"));
+    assert!(report.rejected.is_empty());
+    // Every update is counted exactly once, either for the peer owning the
+    // remote tracking ref, or as local
+    assert_eq!(
+        report.updated.len(),
+        report.local.updated + report.peers.values().map(|c| c.updated).sum::<usize>()
+    );
+    assert!(report.local.updated > 0);
+    assert!(report.peers[&maintainer].updated > 0);
+    assert!(!report.peers.contains_key(&seed));
+    assert!(report.prunable.is_empty());
+
+    // Advance `main`, and leave refs behind which nobody signed
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.sign_refs();
+    let orphan = format!("refs/remotes/{}/heads/main", stranger);
+    let local = net.peer(&seed).unwrap();
+    local.set_ref(&orphan, tip);
+    local.set_ref("refs/strange/ref", tip);
+
+    let report = pull(&net, seed, maintainer, false).report();
+    assert!(report.updated.contains(&main), "{:?}", report.updated);
+    // Only the maintainer's refs moved, `rad/id` stays where it was
+    let prefix = format!("refs/remotes/{}/", maintainer);
+    assert!(report.updated.iter().all(|name| name.starts_with(&prefix)));
+    assert_eq!(
+        report.peers[&maintainer],
+        Counts {
+            updated: report.updated.len(),
+            ..report.peers[&maintainer]
+        }
+    );
+    assert_eq!(report.local.updated, 0);
+    // Orphans are reported, but not counted
+    assert!(!report.peers.contains_key(&stranger));
+    let mut prunable = report.prunable.clone();
+    prunable.sort();
+    assert_eq!(prunable, vec![orphan, "refs/strange/ref".to_owned()]);
+    let warnings = report
+        .validation_errors
+        .iter()
+        .filter(|w| w.code == "strange-or-prunable")
+        .map(|w| (w.refname.clone(), w.peer))
+        .collect::<Vec<_>>();
+    assert!(warnings.contains(&(Some(prunable[0].clone()), Some(stranger))));
+    assert!(warnings.contains(&(Some(prunable[1].clone()), None)));
+}
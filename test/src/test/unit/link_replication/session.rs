@@ -0,0 +1,128 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use futures_lite::{
+    future::block_on,
+    io::{AsyncReadExt as _, AsyncWriteExt as _, Cursor},
+};
+use link_replication::io::{
+    session::{Session, Stream},
+    Connection as _,
+    Recorder,
+    Replay,
+};
+
+/// A request for `command`, as sent at the start of a stream.
+fn request(command: &str) -> Vec<u8> {
+    let line = format!("command={}\n", command);
+    format!("{:04x}{}0000", line.len() + 4, line).into_bytes()
+}
+
+fn record(path: &std::path::Path) {
+    let recorder = Recorder::create(path).unwrap();
+    block_on(async {
+        // A stream which is opened, but never used
+        let _ = recorder.wrap(Cursor::new(Vec::new()), Vec::new());
+        for (req, resp) in vec![
+            (request("fetch"), &b"pack"[..]),
+            (request("ls-refs"), &b"refs"[..]),
+        ] {
+            let (mut recv, mut send) = recorder.wrap(Cursor::new(resp.to_vec()), Vec::new());
+            send.write_all(&req).await.unwrap();
+            let mut buf = Vec::new();
+            recv.read_to_end(&mut buf).await.unwrap();
+        }
+    })
+}
+
+#[test]
+fn roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("session");
+    record(&path);
+
+    assert_eq!(
+        Session {
+            streams: vec![
+                Stream {
+                    send: request("fetch"),
+                    recv: b"pack".to_vec(),
+                },
+                Stream {
+                    send: request("ls-refs"),
+                    recv: b"refs".to_vec(),
+                },
+            ]
+        },
+        Session::load(&path).unwrap()
+    )
+}
+
+#[test]
+fn chunks_are_flushed_as_they_are_recorded() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("session");
+
+    let recorder = Recorder::create(&path).unwrap();
+    let (_, mut send) = recorder.wrap(Cursor::new(Vec::new()), Vec::new());
+    block_on(send.write_all(&request("ls-refs"))).unwrap();
+
+    // `recorder` is still alive
+    assert_eq!(
+        Session {
+            streams: vec![Stream {
+                send: request("ls-refs"),
+                recv: vec![],
+            }]
+        },
+        Session::load(&path).unwrap()
+    )
+}
+
+#[test]
+fn replay() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("session");
+    record(&path);
+
+    let replay = Replay::load(&path).unwrap().strict();
+    block_on(async {
+        let (mut recv, mut send) = replay.open_stream().await.unwrap();
+        send.write_all(&request("fetch")).await.unwrap();
+        let mut buf = Vec::new();
+        recv.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(b"pack", buf.as_slice());
+
+        let (_, mut send) = replay.open_stream().await.unwrap();
+        assert!(send.write_all(&request("ls-refx")).await.is_err());
+    })
+}
+
+#[test]
+fn replay_is_keyed_by_request() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("session");
+    record(&path);
+
+    let replay = Replay::load(&path).unwrap().strict();
+    block_on(async {
+        // Send the requests in a different order than recorded
+        let (mut fetch_recv, mut fetch_send) = replay.open_stream().await.unwrap();
+        let (mut ls_recv, mut ls_send) = replay.open_stream().await.unwrap();
+
+        ls_send.write_all(&request("ls-refs")).await.unwrap();
+        let mut buf = Vec::new();
+        ls_recv.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(b"refs", buf.as_slice());
+
+        fetch_send.write_all(&request("fetch")).await.unwrap();
+        let mut buf = Vec::new();
+        fetch_recv.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(b"pack", buf.as_slice());
+
+        let (_, mut send) = replay.open_stream().await.unwrap();
+        assert!(send.write_all(&request("fetch")).await.is_err());
+    })
+}
@@ -0,0 +1,1277 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    io,
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use bstr::ByteSlice as _;
+use futures::executor::block_on;
+use link_crypto::{PeerId, SecretKey};
+use link_git::{
+    object::Kind,
+    protocol::{ObjectId, Ref},
+};
+use link_replication::{
+    error,
+    progress,
+    refs::{self, parsed::Identity},
+    sim::{self, Network, Object, Order, Peer},
+    FetchLimit,
+    FetchSpec,
+    FilteredRef,
+    Negotiation,
+    Net,
+    Refdb,
+    Rollback,
+    SkippedFetch,
+    Success,
+    Validate,
+    ValidationPolicy,
+    WantsHaves,
+};
+
+/// Fetch all heads, and all remote tracking heads.
+struct Heads {
+    local_id: PeerId,
+    remote_id: PeerId,
+}
+
+impl Negotiation for Heads {
+    fn ref_prefixes(&self) -> Vec<refs::Scoped<'_, '_>> {
+        vec![]
+    }
+
+    fn ref_filter(&self, r: Ref) -> Option<FilteredRef<Self>> {
+        let (name, tip) = refs::into_unpacked(r);
+        let parsed = refs::parse::<Identity>(name.as_bstr())?;
+        let is_head = matches!(
+            parsed.inner.as_ref().right(),
+            Some(refs::parsed::Refs {
+                cat: refs::parsed::Cat::Heads,
+                ..
+            })
+        );
+        if !is_head || parsed.remote == Some(self.local_id) {
+            return None;
+        }
+        Some(FilteredRef::new(name, tip, &self.remote_id, parsed))
+    }
+
+    fn wants_haves<R: Refdb>(
+        &self,
+        db: &R,
+        refs: impl IntoIterator<Item = FilteredRef<Self>>,
+    ) -> Result<WantsHaves<Self>, R::FindError> {
+        let mut wanted = HashSet::new();
+        let mut wants = BTreeSet::new();
+        let mut haves = BTreeSet::new();
+        for r in refs {
+            let tracking = refs::remote_tracking(&r.remote_id, r.name.as_bstr());
+            match db.refname_to_id(&tracking)?.map(Into::into) {
+                Some(oid) if oid == r.tip => continue,
+                Some(oid) => {
+                    haves.insert(oid);
+                },
+                None => {},
+            }
+            wants.insert(r.tip);
+            wanted.insert(r);
+        }
+
+        Ok(WantsHaves {
+            wanted,
+            wants,
+            haves,
+        })
+    }
+
+    fn fetch_limit(&self) -> u64 {
+        u64::MAX
+    }
+}
+
+fn network(n: u8) -> (Network, Vec<(PeerId, ObjectId)>) {
+    let mut net = Network::default();
+    let mut tips = Vec::new();
+    for i in 0..n {
+        let peer = net.add(Peer::new(PeerId::from(&SecretKey::from_seed([i; 32]))));
+        let tip = peer.odb.commit(&[], &format!("initial commit of {}", i));
+        peer.set_ref("refs/heads/main", tip);
+        tips.push((peer.id, tip));
+    }
+    (net, tips)
+}
+
+fn fetch(net: &Network, local_id: PeerId, remote_id: PeerId) -> Result<bool, SkippedFetch> {
+    let (_, res) = block_on(net.conn(&local_id, &remote_id).run_fetch(Heads {
+        local_id,
+        remote_id,
+    }))
+    .unwrap();
+    let applied = net.peer(&local_id).unwrap().track(res?);
+    Ok(!applied.updated.is_empty())
+}
+
+/// Let all peers fetch from each other in the given `order`, until nothing
+/// changes anymore.
+fn converge(net: &Network, order: Order) {
+    loop {
+        let mut changed = false;
+        for (local_id, remote_id) in net.schedule(order) {
+            changed |= fetch(net, local_id, remote_id).unwrap_or(false);
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+fn assert_converged(net: &Network, tips: &[(PeerId, ObjectId)]) {
+    for peer in net.peers() {
+        for (id, tip) in tips {
+            let name = if id == &peer.id {
+                "refs/heads/main".to_owned()
+            } else {
+                format!("refs/remotes/{}/heads/main", id)
+            };
+            assert_eq!(peer.get_ref(&name), Some(*tip), "{} of {}", name, peer.id);
+            assert!(link_replication::Odb::contains(&peer.odb, tip));
+        }
+    }
+}
+
+#[test]
+fn converges_in_any_order() {
+    let orders = vec![Order::Fifo, Order::Lifo]
+        .into_iter()
+        .chain((1..=8).map(Order::Shuffle));
+    for order in orders {
+        let (net, tips) = network(4);
+        converge(&net, order);
+        assert_converged(&net, &tips);
+    }
+}
+
+#[test]
+fn fetch_is_idempotent() {
+    let (net, _) = network(3);
+    converge(&net, Order::Shuffle(42));
+    for (local_id, remote_id) in net.schedule(Order::Fifo) {
+        assert!(matches!(
+            fetch(&net, local_id, remote_id),
+            Err(SkippedFetch::WantNothing)
+        ));
+    }
+}
+
+#[test]
+fn fast_forwards_propagate() {
+    let (net, mut tips) = network(3);
+    converge(&net, Order::Fifo);
+
+    let (id, base) = tips[0];
+    let peer = net.peer(&id).unwrap();
+    let tip = peer.odb.commit(&[base], "second commit");
+    peer.set_ref("refs/heads/main", tip);
+    tips[0] = (id, tip);
+
+    converge(&net, Order::Shuffle(7));
+    assert_converged(&net, &tips);
+    for peer in net.peers() {
+        assert!(
+            link_replication::Odb::is_in_ancestry_path(&peer.odb, tip, base).unwrap(),
+            "{} is missing history",
+            peer.id
+        );
+    }
+}
+
+type Replicated = Result<Success<sim::Urn>, error::Replicate>;
+
+fn clone(net: &Network, local_id: PeerId, remote_id: PeerId) -> Replicated {
+    block_on(link_replication::clone(
+        &mut net.conn(&local_id, &remote_id),
+        FetchLimit::default(),
+        FetchSpec::default(),
+        remote_id,
+        None,
+        Validate::default(),
+        ValidationPolicy::default(),
+        Rollback::default(),
+    ))
+}
+
+fn pull(net: &Network, local_id: PeerId, remote_id: PeerId) -> Replicated {
+    pull_with(
+        net,
+        local_id,
+        remote_id,
+        FetchSpec::default(),
+        ValidationPolicy::default(),
+        Rollback::default(),
+    )
+}
+
+fn pull_with(
+    net: &Network,
+    local_id: PeerId,
+    remote_id: PeerId,
+    spec: FetchSpec,
+    policy: ValidationPolicy,
+    rollback: Rollback,
+) -> Replicated {
+    block_on(link_replication::pull(
+        &mut net.conn(&local_id, &remote_id),
+        FetchLimit::default(),
+        spec,
+        remote_id,
+        None,
+        Validate::default(),
+        policy,
+        rollback,
+    ))
+}
+
+/// A network of `n` peers, the first of which maintains a project with a
+/// single commit on `main`.
+fn project(n: u8) -> (Network, Vec<PeerId>, ObjectId) {
+    let mut net = Network::default();
+    let ids = (0..n)
+        .map(|i| {
+            net.add(Peer::new(PeerId::from(&SecretKey::from_seed([i; 32]))))
+                .id
+        })
+        .collect::<Vec<_>>();
+    let maintainer = net.peer(&ids[0]).unwrap();
+    maintainer.identity("project", Some(ids[0]));
+    let tip = maintainer.odb.commit(&[], "initial commit");
+    maintainer.set_ref("refs/heads/main", tip);
+    maintainer.sign_refs();
+    (net, ids, tip)
+}
+
+fn main_of(net: &Network, local_id: &PeerId, remote_id: &PeerId) -> Option<ObjectId> {
+    net.peer(local_id)
+        .unwrap()
+        .get_ref(format!("refs/remotes/{}/heads/main", remote_id))
+}
+
+#[test]
+fn clone_and_pull() {
+    let (net, ids, tip) = project(2);
+    let (maintainer, seed) = (ids[0], ids[1]);
+
+    let success = clone(&net, seed, maintainer).unwrap();
+    assert!(success.validation_errors().is_empty());
+    assert_eq!(main_of(&net, &seed, &maintainer), Some(tip));
+    assert!(net.peer(&seed).unwrap().tracked().contains(&maintainer));
+    assert_eq!(
+        net.peer(&seed).unwrap().get_ref("refs/rad/id"),
+        net.peer(&maintainer).unwrap().get_ref("refs/rad/id")
+    );
+
+    let peer = net.peer(&maintainer).unwrap();
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.sign_refs();
+
+    let success = pull(&net, seed, maintainer).unwrap();
+    assert!(success.validation_errors().is_empty());
+    assert!(!success.updated_refs().is_empty());
+    assert_eq!(main_of(&net, &seed, &maintainer), Some(next));
+}
+
+#[test]
+fn pull_via_seed() {
+    let (net, ids, tip) = project(3);
+    let (maintainer, seed, leecher) = (ids[0], ids[1], ids[2]);
+
+    clone(&net, seed, maintainer).unwrap();
+    clone(&net, leecher, seed).unwrap();
+    assert_eq!(main_of(&net, &leecher, &maintainer), Some(tip));
+
+    let peer = net.peer(&maintainer).unwrap();
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.sign_refs();
+
+    pull(&net, seed, maintainer).unwrap();
+    pull(&net, leecher, seed).unwrap();
+    assert_eq!(main_of(&net, &leecher, &maintainer), Some(next));
+}
+
+#[test]
+fn pull_is_idempotent() {
+    let (net, ids, _) = project(2);
+    let (maintainer, seed) = (ids[0], ids[1]);
+
+    clone(&net, seed, maintainer).unwrap();
+    let refs = net.peer(&seed).unwrap().refs();
+    let success = pull(&net, seed, maintainer).unwrap();
+    assert!(success.updated_refs().is_empty());
+    assert_eq!(refs, net.peer(&seed).unwrap().refs());
+}
+
+#[test]
+fn pull_requires_identity() {
+    let (net, ids, _) = project(2);
+    assert!(matches!(
+        pull(&net, ids[1], ids[0]),
+        Err(error::Replicate {
+            failure: error::Failure::MissingRadId,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn pull_configured_category() {
+    let (net, ids, _) = project(3);
+    let (maintainer, contributor, leecher) = (ids[0], ids[1], ids[2]);
+
+    let peer = net.peer(&maintainer).unwrap();
+    peer.track_peer(contributor);
+    peer.sign_refs();
+    clone(&net, contributor, maintainer).unwrap();
+    clone(&net, leecher, maintainer).unwrap();
+
+    // Not signed, but the contributor is tracked by the maintainer
+    let peer = net.peer(&contributor).unwrap();
+    let patch = peer.odb.commit(&[], "patch");
+    peer.set_ref("refs/patches/1", patch);
+    let name = format!("refs/remotes/{}/patches/1", contributor);
+
+    pull(&net, leecher, contributor).unwrap();
+    assert_eq!(net.peer(&leecher).unwrap().get_ref(&name), None);
+
+    let success = pull_with(
+        &net,
+        leecher,
+        contributor,
+        FetchSpec::default().with_category("patches"),
+        ValidationPolicy::Repair,
+        Rollback::default(),
+    )
+    .unwrap();
+    assert!(
+        !success.validation_errors().iter().any(|e| matches!(
+            e,
+            error::Validation::Strange(_) | error::Validation::Unexpected(_)
+        )),
+        "{:?}",
+        success.validation_errors()
+    );
+    assert_eq!(net.peer(&leecher).unwrap().get_ref(&name), Some(patch));
+}
+
+/// A network in which a contributor, tracked by the maintainer, has a note
+/// it did not sign. Returns the ids of the maintainer, the contributor, and a
+/// leecher which cloned from the maintainer, and the note.
+fn unsigned_note() -> (Network, [PeerId; 3], ObjectId) {
+    let (net, ids, _) = project(3);
+    let (maintainer, contributor, leecher) = (ids[0], ids[1], ids[2]);
+
+    let peer = net.peer(&maintainer).unwrap();
+    peer.track_peer(contributor);
+    peer.sign_refs();
+    clone(&net, contributor, maintainer).unwrap();
+    clone(&net, leecher, maintainer).unwrap();
+
+    let peer = net.peer(&contributor).unwrap();
+    let note = peer.odb.commit(&[], "note");
+    peer.set_ref("refs/notes/commits", note);
+
+    (net, [maintainer, contributor, leecher], note)
+}
+
+fn is_unsigned(e: &error::Validation) -> bool {
+    matches!(e, error::Validation::Unsigned { .. })
+}
+
+#[test]
+fn pull_skips_unsigned_notes() {
+    let (net, [_, contributor, leecher], note) = unsigned_note();
+    let name = format!("refs/remotes/{}/notes/commits", contributor);
+
+    // Tracking the contributor is not enough
+    pull(&net, leecher, contributor).unwrap();
+    assert_eq!(net.peer(&leecher).unwrap().get_ref(&name), None);
+
+    net.peer(&contributor).unwrap().sign_refs();
+    let success = pull(&net, leecher, contributor).unwrap();
+    assert!(
+        !success.validation_errors().iter().any(is_unsigned),
+        "{:?}",
+        success.validation_errors()
+    );
+    assert_eq!(net.peer(&leecher).unwrap().get_ref(&name), Some(note));
+}
+
+#[test]
+fn stored_unsigned_notes_are_grandfathered() {
+    let (net, [_, contributor, leecher], note) = unsigned_note();
+    let name = format!("refs/remotes/{}/notes/legacy", contributor);
+    // Replicated before notes had to be signed
+    net.peer(&leecher).unwrap().set_ref(&name, note);
+
+    let success = pull(&net, leecher, contributor).unwrap();
+    assert!(
+        !success.validation_errors().iter().any(is_unsigned),
+        "{:?}",
+        success.validation_errors()
+    );
+    assert_eq!(net.peer(&leecher).unwrap().get_ref(&name), Some(note));
+
+    // Once the contributor publishes signed refs, its notes are expected to be
+    // among them
+    net.peer(&contributor).unwrap().sign_refs();
+    let success = pull(&net, leecher, contributor).unwrap();
+    assert!(
+        success
+            .validation_errors()
+            .iter()
+            .any(|e| is_unsigned(e) && e.refname() == Some(name.as_bytes().as_bstr())),
+        "{:?}",
+        success.validation_errors()
+    );
+}
+
+#[test]
+fn pull_skips_excluded_refs() {
+    let (net, ids, tip) = project(2);
+    let (maintainer, seed) = (ids[0], ids[1]);
+    let peer = net.peer(&maintainer).unwrap();
+    peer.set_ref("refs/heads/wip/scratch", tip);
+    peer.sign_refs();
+    clone(&net, seed, maintainer).unwrap();
+
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.set_ref("refs/heads/wip/scratch", next);
+    peer.sign_refs();
+
+    let success = pull_with(
+        &net,
+        seed,
+        maintainer,
+        FetchSpec::default().with_exclusion("refs/heads/wip/*"),
+        ValidationPolicy::Reject,
+        Rollback::default(),
+    )
+    .unwrap();
+    assert!(success.validation_errors().is_empty());
+    assert_eq!(main_of(&net, &seed, &maintainer), Some(next));
+    assert_eq!(
+        net.peer(&seed)
+            .unwrap()
+            .get_ref(format!("refs/remotes/{}/heads/wip/scratch", maintainer)),
+        Some(tip)
+    );
+}
+
+#[test]
+fn tracking_cutoff_limits_transitive_peers() {
+    let (net, ids, tip) = project(4);
+    let (maintainer, contributor, seed, leecher) = (ids[0], ids[1], ids[2], ids[3]);
+    clone(&net, seed, maintainer).unwrap();
+    clone(&net, contributor, maintainer).unwrap();
+    clone(&net, leecher, seed).unwrap();
+
+    let peer = net.peer(&contributor).unwrap();
+    let patch = peer.odb.commit(&[tip], "contribution");
+    peer.set_ref("refs/heads/main", patch);
+    peer.sign_refs();
+
+    // The seed tracks the contributor, the leecher only the seed
+    net.peer(&seed).unwrap().track_peer(contributor);
+    pull(&net, seed, contributor).unwrap();
+    net.peer(&seed).unwrap().sign_refs();
+    assert_eq!(main_of(&net, &seed, &contributor), Some(patch));
+
+    pull_with(
+        &net,
+        leecher,
+        seed,
+        FetchSpec::default().with_tracking_cutoff(0),
+        ValidationPolicy::default(),
+        Rollback::default(),
+    )
+    .unwrap();
+    assert_eq!(main_of(&net, &leecher, &contributor), None);
+
+    pull(&net, leecher, seed).unwrap();
+    assert_eq!(main_of(&net, &leecher, &contributor), Some(patch));
+}
+
+/// Let the maintainer of a cloned project sign `name` pointing to an object of
+/// `kind`, and pull it.
+fn pull_object(kind: Kind, name: &str, spec: FetchSpec) -> Replicated {
+    let (net, ids, tip) = project(2);
+    let (maintainer, seed) = (ids[0], ids[1]);
+    clone(&net, seed, maintainer).unwrap();
+
+    let peer = net.peer(&maintainer).unwrap();
+    let obj = peer.odb.insert(Object {
+        kind,
+        data: name.as_bytes().to_vec(),
+        parents: vec![tip],
+    });
+    peer.set_ref(name, obj);
+    peer.sign_refs();
+
+    let res = pull_with(
+        &net,
+        seed,
+        maintainer,
+        spec,
+        ValidationPolicy::default(),
+        Rollback::default(),
+    );
+    if res.is_ok() {
+        assert_eq!(
+            net.peer(&seed)
+                .unwrap()
+                .get_ref(format!("refs/remotes/{}/{}", maintainer, &name[5..])),
+            Some(obj)
+        );
+    }
+    res
+}
+
+fn is_object_type_mismatch(res: &Replicated) -> bool {
+    match res {
+        Err(error::Replicate {
+            failure: error::Failure::Integrity(e),
+            ..
+        }) => e.to_string().contains("must point to a commit,"),
+        _ => false,
+    }
+}
+
+#[test]
+fn tag_in_heads_is_rejected() {
+    let res = pull_object(Kind::Tag, "refs/heads/v1", FetchSpec::default());
+    assert!(is_object_type_mismatch(&res), "{:?}", res);
+}
+
+#[test]
+fn blob_in_heads_is_rejected() {
+    let res = pull_object(Kind::Blob, "refs/heads/blob", FetchSpec::default());
+    assert!(is_object_type_mismatch(&res), "{:?}", res);
+}
+
+#[test]
+fn tag_in_tags_is_accepted() {
+    pull_object(Kind::Tag, "refs/tags/v1", FetchSpec::default()).unwrap();
+}
+
+#[test]
+fn unknown_category_is_not_checked() {
+    pull_object(
+        Kind::Blob,
+        "refs/patches/1",
+        FetchSpec::default().with_category("patches"),
+    )
+    .unwrap();
+}
+
+/// A network in which the seed has pulled a second commit of the maintainer,
+/// while the leecher, which published a branch of its own, is still at the
+/// first. Returns the ids of the maintainer, the seed, and the leecher, and
+/// the first and second commit.
+fn rolled_back() -> (Network, [PeerId; 3], ObjectId, ObjectId) {
+    let (net, ids, tip) = project(3);
+    let (maintainer, seed, leecher) = (ids[0], ids[1], ids[2]);
+
+    clone(&net, seed, maintainer).unwrap();
+    clone(&net, leecher, maintainer).unwrap();
+
+    let peer = net.peer(&maintainer).unwrap();
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.sign_refs();
+    pull(&net, seed, maintainer).unwrap();
+
+    let peer = net.peer(&leecher).unwrap();
+    let feature = peer.odb.commit(&[], "feature");
+    peer.set_ref("refs/heads/feature", feature);
+    peer.sign_refs();
+    net.peer(&seed).unwrap().track_peer(leecher);
+
+    (net, [maintainer, seed, leecher], tip, next)
+}
+
+#[test]
+fn rollback_reject_withholds_peer() {
+    let (net, [maintainer, seed, leecher], _, next) = rolled_back();
+    let signed = format!("refs/remotes/{}/rad/signed_refs", maintainer);
+    let stored = net.peer(&seed).unwrap().get_ref(&signed);
+
+    let success = pull_with(
+        &net,
+        seed,
+        leecher,
+        FetchSpec::default(),
+        ValidationPolicy::default(),
+        Rollback::Reject,
+    )
+    .unwrap();
+    let rollbacks = success
+        .rollbacks()
+        .iter()
+        .map(|rollback| rollback.remote)
+        .collect::<Vec<_>>();
+    assert_eq!(rollbacks, vec![maintainer]);
+    assert_eq!(net.peer(&seed).unwrap().get_ref(&signed), stored);
+    assert_eq!(main_of(&net, &seed, &maintainer), Some(next));
+    assert_eq!(
+        net.peer(&seed)
+            .unwrap()
+            .get_ref(format!("refs/remotes/{}/heads/feature", leecher)),
+        net.peer(&leecher).unwrap().get_ref("refs/heads/feature")
+    );
+}
+
+#[test]
+fn rollback_allow_accepts_older_sigrefs() {
+    let (net, [maintainer, seed, leecher], tip, _) = rolled_back();
+    let signed = format!("refs/remotes/{}/rad/signed_refs", maintainer);
+
+    let success = pull_with(
+        &net,
+        seed,
+        leecher,
+        FetchSpec::default(),
+        ValidationPolicy::default(),
+        Rollback::Allow,
+    )
+    .unwrap();
+    assert!(success.rollbacks().is_empty());
+    assert_eq!(
+        net.peer(&seed).unwrap().get_ref(&signed),
+        net.peer(&leecher).unwrap().get_ref(&signed)
+    );
+    assert_eq!(main_of(&net, &seed, &maintainer), Some(tip));
+}
+
+/// A network in which the maintainer signed a second commit, but advertises a
+/// third, unsigned one on `main`. Returns the ids of the maintainer and the
+/// seed, and the signed and unsigned commits.
+fn unsigned_main() -> (Network, [PeerId; 2], ObjectId, ObjectId) {
+    let (net, ids, tip) = project(2);
+    let (maintainer, seed) = (ids[0], ids[1]);
+    clone(&net, seed, maintainer).unwrap();
+
+    let peer = net.peer(&maintainer).unwrap();
+    let signed = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", signed);
+    peer.sign_refs();
+    let unsigned = peer.odb.commit(&[signed], "unsigned commit");
+    peer.set_ref("refs/heads/main", unsigned);
+
+    (net, [maintainer, seed], signed, unsigned)
+}
+
+fn pull_policy(
+    net: &Network,
+    local_id: PeerId,
+    remote_id: PeerId,
+    policy: ValidationPolicy,
+) -> Replicated {
+    pull_with(
+        net,
+        local_id,
+        remote_id,
+        FetchSpec::default(),
+        policy,
+        Rollback::default(),
+    )
+}
+
+fn is_mismatch(e: &error::Validation) -> bool {
+    matches!(e, error::Validation::MismatchedTips { .. })
+}
+
+#[test]
+fn validation_warn_applies_mismatched_tips() {
+    let (net, [maintainer, seed], _, unsigned) = unsigned_main();
+
+    let success = pull_policy(&net, seed, maintainer, ValidationPolicy::Warn).unwrap();
+    assert!(
+        success.validation_errors().iter().any(is_mismatch),
+        "{:?}",
+        success.validation_errors()
+    );
+    assert_eq!(main_of(&net, &seed, &maintainer), Some(unsigned));
+}
+
+#[test]
+fn validation_reject_applies_nothing() {
+    let (net, [maintainer, seed], _, _) = unsigned_main();
+    let refs = net.peer(&seed).unwrap().refs();
+
+    let res = pull_policy(&net, seed, maintainer, ValidationPolicy::Reject);
+    assert!(matches!(
+        &res,
+        Err(error::Replicate {
+            failure: error::Failure::Validation(warnings),
+            ..
+        }) if warnings.iter().any(is_mismatch)
+    ));
+    assert_eq!(refs, net.peer(&seed).unwrap().refs());
+}
+
+#[test]
+fn failed_clone_rolls_back_trackings() {
+    let (net, ids, tip) = project(2);
+    let (maintainer, seed) = (ids[0], ids[1]);
+    let peer = net.peer(&maintainer).unwrap();
+    let unsigned = peer.odb.commit(&[tip], "unsigned commit");
+    peer.set_ref("refs/heads/main", unsigned);
+
+    let res = block_on(link_replication::clone(
+        &mut net.conn(&seed, &maintainer),
+        FetchLimit::default(),
+        FetchSpec::default(),
+        maintainer,
+        None,
+        Validate::default(),
+        ValidationPolicy::Reject,
+        Rollback::default(),
+    ));
+    assert!(matches!(
+        &res,
+        Err(error::Replicate {
+            failure: error::Failure::Validation(_),
+            ..
+        })
+    ));
+    assert!(net.peer(&seed).unwrap().tracked().is_empty());
+    assert_eq!(main_of(&net, &seed, &maintainer), None);
+}
+
+#[test]
+fn validation_repair_retargets_to_signed_tip() {
+    let (net, [maintainer, seed], signed, _) = unsigned_main();
+
+    let success = pull_policy(&net, seed, maintainer, ValidationPolicy::Repair).unwrap();
+    assert!(
+        success.validation_errors().is_empty(),
+        "{:?}",
+        success.validation_errors()
+    );
+    assert_eq!(main_of(&net, &seed, &maintainer), Some(signed));
+}
+
+#[test]
+fn validation_repair_does_not_rewind() {
+    let (net, [maintainer, seed], _, unsigned) = unsigned_main();
+    pull_policy(&net, seed, maintainer, ValidationPolicy::Warn).unwrap();
+
+    let peer = net.peer(&maintainer).unwrap();
+    let newer = peer.odb.commit(&[unsigned], "another unsigned commit");
+    peer.set_ref("refs/heads/main", newer);
+
+    // The signed tip is behind the stored one, so the update is withheld
+    // instead of rewinding `main`
+    pull_policy(&net, seed, maintainer, ValidationPolicy::Repair).unwrap();
+    assert_eq!(main_of(&net, &seed, &maintainer), Some(unsigned));
+}
+
+fn pull_validate(
+    net: &Network,
+    local_id: PeerId,
+    remote_id: PeerId,
+    validation: Validate,
+) -> Replicated {
+    block_on(link_replication::pull(
+        &mut net.conn(&local_id, &remote_id),
+        FetchLimit::default(),
+        FetchSpec::default(),
+        remote_id,
+        None,
+        validation,
+        ValidationPolicy::default(),
+        Rollback::default(),
+    ))
+}
+
+#[test]
+fn orphans_are_only_caught_by_full_validation() {
+    let (net, ids, tip) = project(2);
+    let (maintainer, seed) = (ids[0], ids[1]);
+    clone(&net, seed, maintainer).unwrap();
+
+    // Neither tracked nor signed by anyone
+    let stranger = PeerId::from(&SecretKey::from_seed([42; 32]));
+    let orphan = format!("refs/remotes/{}/heads/main", stranger);
+    net.peer(&seed).unwrap().set_ref(&orphan, tip);
+    let is_orphan = |e: &error::Validation| match e {
+        error::Validation::StrangeOrPrunable(name) => *name == orphan,
+        _ => false,
+    };
+
+    let peer = net.peer(&maintainer).unwrap();
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.sign_refs();
+    let success = pull_validate(&net, seed, maintainer, Validate::Incremental).unwrap();
+    assert!(
+        !success.validation_errors().iter().any(is_orphan),
+        "{:?}",
+        success.validation_errors()
+    );
+
+    let third = peer.odb.commit(&[next], "third commit");
+    peer.set_ref("refs/heads/main", third);
+    peer.sign_refs();
+    let success = pull_validate(&net, seed, maintainer, Validate::Full).unwrap();
+    assert!(
+        success.validation_errors().iter().any(is_orphan),
+        "{:?}",
+        success.validation_errors()
+    );
+}
+
+fn folding(fold_case: bool) -> FetchSpec {
+    FetchSpec::default().with_case_folding(fold_case)
+}
+
+#[test]
+fn case_collisions_are_skipped() {
+    let (net, ids, tip) = project(2);
+    let (maintainer, seed) = (ids[0], ids[1]);
+    clone(&net, seed, maintainer).unwrap();
+
+    let peer = net.peer(&maintainer).unwrap();
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.set_ref("refs/heads/feature", next);
+    peer.set_ref("refs/heads/Feature", next);
+    peer.sign_refs();
+
+    let feature = |name: &str| {
+        net.peer(&seed)
+            .unwrap()
+            .get_ref(format!("refs/remotes/{}/heads/{}", maintainer, name))
+    };
+
+    pull_with(
+        &net,
+        seed,
+        maintainer,
+        folding(true),
+        ValidationPolicy::Warn,
+        Rollback::default(),
+    )
+    .unwrap();
+    assert_eq!(main_of(&net, &seed, &maintainer), Some(next));
+    assert_eq!(feature("feature"), None);
+    assert_eq!(feature("Feature"), None);
+
+    pull_with(
+        &net,
+        seed,
+        maintainer,
+        folding(false),
+        ValidationPolicy::Warn,
+        Rollback::default(),
+    )
+    .unwrap();
+    assert_eq!(feature("feature"), Some(next));
+    assert_eq!(feature("Feature"), Some(next));
+}
+
+#[test]
+fn case_collisions_with_stored_refs_are_skipped() {
+    let (net, ids, tip) = project(2);
+    let (maintainer, seed) = (ids[0], ids[1]);
+    clone(&net, seed, maintainer).unwrap();
+    let stored = format!("refs/remotes/{}/heads/feature", maintainer);
+    net.peer(&seed).unwrap().set_ref(stored.as_str(), tip);
+
+    let peer = net.peer(&maintainer).unwrap();
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.set_ref("refs/heads/FEATURE", next);
+    peer.sign_refs();
+
+    pull_with(
+        &net,
+        seed,
+        maintainer,
+        folding(true),
+        ValidationPolicy::Warn,
+        Rollback::default(),
+    )
+    .unwrap();
+    let seed = net.peer(&seed).unwrap();
+    assert_eq!(main_of(&net, &seed.id, &maintainer), Some(next));
+    assert_eq!(seed.get_ref(&stored), Some(tip));
+    assert_eq!(
+        seed.get_ref(format!("refs/remotes/{}/heads/FEATURE", maintainer)),
+        None
+    );
+}
+
+/// A remote to pull from with [`link_replication::pull_many`], which records
+/// the progress events reported to it.
+struct Remote<'a> {
+    conn: Result<sim::Conn<'a>, io::ErrorKind>,
+    events: Mutex<Vec<progress::Event>>,
+    limits: Mutex<Vec<u64>>,
+    hides_objects: bool,
+}
+
+impl<'a> Remote<'a> {
+    fn up(conn: sim::Conn<'a>) -> Self {
+        Self {
+            conn: Ok(conn),
+            events: Mutex::new(Vec::new()),
+            limits: Mutex::new(Vec::new()),
+            hides_objects: false,
+        }
+    }
+
+    /// A remote which claims to have none of the objects it is asked about.
+    fn hiding(conn: sim::Conn<'a>) -> Self {
+        Self {
+            hides_objects: true,
+            ..Self::up(conn)
+        }
+    }
+
+    /// A remote which fails every fetch with an error of the given `kind`.
+    fn failing(kind: io::ErrorKind) -> Self {
+        Self {
+            conn: Err(kind),
+            events: Mutex::new(Vec::new()),
+            limits: Mutex::new(Vec::new()),
+            hides_objects: false,
+        }
+    }
+
+    fn count(&self, f: impl Fn(&progress::Event) -> bool) -> usize {
+        self.events.lock().unwrap().iter().filter(|e| f(e)).count()
+    }
+}
+
+#[async_trait]
+impl Net for Remote<'_> {
+    type Error = io::Error;
+
+    async fn run_fetch<N, T>(
+        &self,
+        neg: N,
+    ) -> Result<(N, Result<Vec<FilteredRef<T>>, SkippedFetch>), Self::Error>
+    where
+        N: Negotiation<T> + Send + Sync,
+        T: Send + 'static,
+    {
+        self.limits.lock().unwrap().push(neg.fetch_limit());
+        match &self.conn {
+            Ok(conn) => conn.run_fetch(neg).await.map_err(|v| match v {}),
+            Err(kind) => Err(io::Error::new(*kind, "remote failed")),
+        }
+    }
+
+    async fn has_objects(&self, oids: Vec<ObjectId>) -> Result<BTreeSet<ObjectId>, Self::Error> {
+        match &self.conn {
+            Ok(_) if self.hides_objects => Ok(BTreeSet::new()),
+            Ok(conn) => conn.has_objects(oids).await.map_err(|v| match v {}),
+            Err(kind) => Err(io::Error::new(*kind, "remote failed")),
+        }
+    }
+
+    fn progress(&self, event: progress::Event) {
+        self.events.lock().unwrap().push(event)
+    }
+}
+
+fn pull_many(net: &Network, local_id: PeerId, remotes: &mut [(PeerId, Remote<'_>)]) -> Replicated {
+    pull_many_with(net, local_id, remotes, FetchSpec::default())
+}
+
+fn pull_many_with(
+    net: &Network,
+    local_id: PeerId,
+    remotes: &mut [(PeerId, Remote<'_>)],
+    spec: FetchSpec,
+) -> Replicated {
+    // Only the local side of the context is used
+    block_on(link_replication::pull_many(
+        &mut net.conn(&local_id, &local_id),
+        remotes,
+        FetchLimit::default(),
+        spec,
+        None,
+        Validate::default(),
+        ValidationPolicy::default(),
+        Rollback::default(),
+    ))
+}
+
+/// A network in which one seed has pulled a second commit of the maintainer,
+/// and another seed a second commit on a branch of a tracked contributor,
+/// while the leecher has neither. Returns the ids of the maintainer, the
+/// contributor, both seeds, and the leecher, and the second commits of the
+/// maintainer and the contributor.
+fn diverged() -> (Network, [PeerId; 5], ObjectId, ObjectId) {
+    let (net, ids, tip) = project(5);
+    let (maintainer, contributor, first, second, leecher) =
+        (ids[0], ids[1], ids[2], ids[3], ids[4]);
+
+    let peer = net.peer(&maintainer).unwrap();
+    peer.track_peer(contributor);
+    peer.sign_refs();
+    clone(&net, contributor, maintainer).unwrap();
+    let peer = net.peer(&contributor).unwrap();
+    let feature = peer.odb.commit(&[], "feature");
+    peer.set_ref("refs/heads/feature", feature);
+    peer.sign_refs();
+    for id in &[first, second, leecher] {
+        clone(&net, *id, maintainer).unwrap();
+        net.peer(id).unwrap().track_peer(contributor);
+        pull(&net, *id, contributor).unwrap();
+    }
+
+    let peer = net.peer(&maintainer).unwrap();
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.sign_refs();
+    pull(&net, first, maintainer).unwrap();
+
+    let peer = net.peer(&contributor).unwrap();
+    let next_feature = peer.odb.commit(&[feature], "second feature commit");
+    peer.set_ref("refs/heads/feature", next_feature);
+    peer.sign_refs();
+    pull(&net, second, contributor).unwrap();
+
+    (
+        net,
+        [maintainer, contributor, first, second, leecher],
+        next,
+        next_feature,
+    )
+}
+
+#[test]
+fn pull_many_takes_peers_from_the_newest_sigrefs() {
+    for &reverse in &[false, true] {
+        let (net, [maintainer, contributor, first, second, leecher], next, feature) = diverged();
+        let mut remotes = vec![
+            (first, Remote::up(net.conn(&leecher, &first))),
+            (second, Remote::up(net.conn(&leecher, &second))),
+        ];
+        if reverse {
+            remotes.reverse();
+        }
+
+        let success = pull_many(&net, leecher, &mut remotes).unwrap();
+        assert!(
+            success.validation_errors().is_empty(),
+            "{:?}",
+            success.validation_errors()
+        );
+        let peer = net.peer(&leecher).unwrap();
+        assert_eq!(main_of(&net, &leecher, &maintainer), Some(next));
+        assert_eq!(
+            peer.get_ref(format!("refs/remotes/{}/heads/feature", contributor)),
+            Some(feature)
+        );
+        for id in &[maintainer, contributor] {
+            assert_eq!(
+                peer.get_ref(format!("refs/remotes/{}/rad/signed_refs", id)),
+                net.peer(id).unwrap().get_ref("refs/rad/signed_refs"),
+                "signed refs of {}",
+                id
+            );
+        }
+    }
+}
+
+#[test]
+fn pull_many_validates_and_applies_once() {
+    let (net, [_, _, first, second, leecher], _, _) = diverged();
+    let mut remotes = vec![
+        (first, Remote::up(net.conn(&leecher, &first))),
+        (second, Remote::up(net.conn(&leecher, &second))),
+    ];
+
+    let success = pull_many(&net, leecher, &mut remotes).unwrap();
+    let updated = success.updated_refs().len();
+    assert!(updated > 0);
+    for (id, remote) in &remotes {
+        assert_eq!(
+            remote.count(|e| matches!(e, progress::Event::Validated { .. })),
+            1,
+            "validated for {}",
+            id
+        );
+        assert_eq!(
+            remote.count(
+                |e| matches!(e, progress::Event::Applied { updated: n, .. } if *n == updated)
+            ),
+            1,
+            "applied for {}",
+            id
+        );
+    }
+}
+
+#[test]
+fn pull_many_fails_if_a_remote_fails() {
+    let (net, [_, _, first, second, leecher], _, _) = diverged();
+    let refs = net.peer(&leecher).unwrap().refs();
+    let mut remotes = vec![
+        (first, Remote::up(net.conn(&leecher, &first))),
+        (second, Remote::failing(io::ErrorKind::ConnectionReset)),
+    ];
+
+    let err = pull_many(&net, leecher, &mut remotes).unwrap_err();
+    assert!(matches!(err.failure, error::Failure::Net(_)), "{:?}", err);
+    assert!(err.is_retryable());
+    assert_eq!(refs, net.peer(&leecher).unwrap().refs());
+}
+
+#[test]
+fn invalid_data_is_not_retryable() {
+    let (net, [_, _, first, second, leecher], _, _) = diverged();
+    let mut remotes = vec![
+        (first, Remote::up(net.conn(&leecher, &first))),
+        (second, Remote::failing(io::ErrorKind::InvalidData)),
+    ];
+
+    let err = pull_many(&net, leecher, &mut remotes).unwrap_err();
+    assert!(
+        matches!(err.failure, error::Failure::Integrity(_)),
+        "{:?}",
+        err
+    );
+    assert!(!err.is_retryable());
+}
+
+#[test]
+fn concurrent_fetches_match_a_single_fetch() {
+    let (net, ids, tip) = project(5);
+    let (maintainer, contributor, seed, single, concurrent) =
+        (ids[0], ids[1], ids[2], ids[3], ids[4]);
+
+    let peer = net.peer(&maintainer).unwrap();
+    peer.track_peer(contributor);
+    peer.sign_refs();
+    clone(&net, contributor, maintainer).unwrap();
+    for id in &[seed, single, concurrent] {
+        clone(&net, *id, maintainer).unwrap();
+        net.peer(id).unwrap().track_peer(contributor);
+    }
+
+    let peer = net.peer(&contributor).unwrap();
+    let feature = peer.odb.commit(&[], "feature");
+    peer.set_ref("refs/heads/feature", feature);
+    peer.sign_refs();
+    let peer = net.peer(&maintainer).unwrap();
+    let next = peer.odb.commit(&[tip], "second commit");
+    peer.set_ref("refs/heads/main", next);
+    peer.sign_refs();
+    pull(&net, seed, maintainer).unwrap();
+    pull(&net, seed, contributor).unwrap();
+
+    pull(&net, single, seed).unwrap();
+    pull_with(
+        &net,
+        concurrent,
+        seed,
+        FetchSpec::default().with_concurrent_fetches(2),
+        ValidationPolicy::default(),
+        Rollback::default(),
+    )
+    .unwrap();
+
+    let remotes = |id: &PeerId| {
+        net.peer(id)
+            .unwrap()
+            .refs()
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(b"refs/remotes/"))
+            .collect::<BTreeMap<_, _>>()
+    };
+    assert_eq!(main_of(&net, &concurrent, &maintainer), Some(next));
+    assert_eq!(
+        net.peer(&concurrent)
+            .unwrap()
+            .get_ref(format!("refs/remotes/{}/heads/feature", contributor)),
+        Some(feature)
+    );
+    assert_eq!(remotes(&single), remotes(&concurrent));
+}
+
+#[test]
+fn concurrent_fetches_share_the_limit() {
+    let (net, [_, _, first, _, leecher], _, _) = diverged();
+    let mut remotes = vec![(first, Remote::up(net.conn(&leecher, &first)))];
+
+    pull_many_with(
+        &net,
+        leecher,
+        &mut remotes,
+        FetchSpec::default().with_concurrent_fetches(2),
+    )
+    .unwrap();
+    let limits = remotes[0].1.limits.lock().unwrap();
+    let limit = FetchLimit::default();
+    // The verification refs, and the refs of each peer separately
+    assert!(limits.len() > 2, "{:?}", limits);
+    assert!(
+        limits.iter().sum::<u64>() <= limit.peek + limit.data,
+        "{:?}",
+        limits
+    );
+}
+
+#[test]
+fn pull_many_skips_remotes_lacking_the_signed_tips() {
+    for &reverse in &[false, true] {
+        let (net, ids, tip) = project(4);
+        let (maintainer, first, second, leecher) = (ids[0], ids[1], ids[2], ids[3]);
+        let peer = net.peer(&maintainer).unwrap();
+        let next = peer.odb.commit(&[tip], "second commit");
+        peer.set_ref("refs/heads/main", next);
+        peer.sign_refs();
+        for id in &[first, second] {
+            pull(&net, *id, maintainer).unwrap();
+        }
+
+        let mut remotes = vec![
+            (first, Remote::hiding(net.conn(&leecher, &first))),
+            (second, Remote::up(net.conn(&leecher, &second))),
+        ];
+        if reverse {
+            remotes.reverse();
+        }
+        pull_many_with(
+            &net,
+            leecher,
+            &mut remotes,
+            FetchSpec::default().with_concurrent_fetches(2),
+        )
+        .unwrap();
+
+        assert_eq!(main_of(&net, &leecher, &maintainer), Some(next));
+        for (id, remote) in &remotes {
+            let fetches = remote.limits.lock().unwrap().len();
+            if *id == first {
+                // Only the verification refs
+                assert_eq!(fetches, 1, "fetches from {}", id);
+            } else {
+                assert!(fetches > 1, "fetches from {}", id);
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_lite::{
+    future::{self, block_on},
+    io::{AsyncRead, AsyncReadExt as _, AsyncWriteExt as _, Cursor},
+};
+use link_replication::io::{Bandwidth, Throttle};
+
+#[test]
+fn unlimited() {
+    let throttle = Throttle::default();
+    let (mut recv, mut send) = throttle.wrap(Cursor::new(b"packfile".to_vec()), Vec::new());
+    block_on(async {
+        let mut buf = Vec::new();
+        recv.read_to_end(&mut buf).await.unwrap();
+        send.write_all(b"want").await.unwrap();
+        assert_eq!(b"packfile", buf.as_slice());
+        assert_eq!(b"want", send.into_inner().as_slice());
+    });
+    assert_eq!(8, throttle.traffic().received());
+    assert_eq!(4, throttle.traffic().sent());
+}
+
+#[test]
+fn limit_down() {
+    let throttle = Throttle::new(Bandwidth {
+        up: None,
+        down: Some(1000),
+    });
+    let (mut recv, _) = throttle.wrap(Cursor::new(vec![0; 300]), Vec::new());
+    let start = Instant::now();
+    block_on(async {
+        let mut buf = Vec::new();
+        recv.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(300, buf.len())
+    });
+    // 100 bytes are available right away, the rest arrives at 1000 bytes per
+    // second
+    assert!(start.elapsed() >= Duration::from_millis(150));
+    assert_eq!(300, throttle.traffic().received());
+}
+
+#[test]
+fn limit_up_shared() {
+    let throttle = Throttle::new(Bandwidth {
+        up: Some(1000),
+        down: None,
+    });
+    let (_, mut a) = throttle.wrap(Cursor::new(vec![]), Vec::new());
+    let (_, mut b) = throttle.clone().wrap(Cursor::new(vec![]), Vec::new());
+    let start = Instant::now();
+    block_on(async {
+        a.write_all(&[0; 150]).await.unwrap();
+        b.write_all(&[0; 150]).await.unwrap();
+    });
+    assert!(start.elapsed() >= Duration::from_millis(150));
+    assert_eq!(300, throttle.traffic().sent());
+    assert_eq!(0, throttle.traffic().received());
+}
+
+/// A reader which is pending every other time it is polled.
+struct Yielding<R> {
+    inner: R,
+    ready: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Yielding<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.ready = !self.ready;
+        if self.ready {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[test]
+fn limit_down_concurrent() {
+    let throttle = Throttle::new(Bandwidth {
+        up: None,
+        down: Some(1000),
+    });
+    let yielding = || Yielding {
+        inner: Cursor::new(vec![0; 200]),
+        ready: false,
+    };
+    let (mut a, _) = throttle.wrap(yielding(), Vec::new());
+    let (mut b, _) = throttle.wrap(yielding(), Vec::new());
+    let start = Instant::now();
+    block_on(async {
+        let (mut x, mut y) = (Vec::new(), Vec::new());
+        let (r, s) = future::zip(a.read_to_end(&mut x), b.read_to_end(&mut y)).await;
+        assert_eq!(200, r.unwrap());
+        assert_eq!(200, s.unwrap());
+    });
+    // Both streams are pending after being granted bytes, which must not be
+    // granted to the other stream as well: only 100 bytes are available right
+    // away in total
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert_eq!(400, throttle.traffic().received());
+}
@@ -0,0 +1,49 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use bstr::{BString, ByteSlice as _};
+use link_crypto::PeerId;
+use link_git::protocol::ObjectId;
+use link_replication::error::Validation;
+use once_cell::sync::Lazy;
+
+static PEER: Lazy<PeerId> = Lazy::new(|| {
+    "hyn3aar1qghrnjrdi161oks1w3z9s173mxti88ci6qthps8brmp6yo"
+        .parse()
+        .unwrap()
+});
+
+#[test]
+fn peer_of_remote_tracking_refname() {
+    let name = BString::from(format!("refs/remotes/{}/heads/main", *PEER));
+    let fail = Validation::MismatchedTips {
+        signed: ObjectId::null_sha1(),
+        actual: ObjectId::null_sha1(),
+        name: name.clone(),
+    };
+    assert_eq!(fail.code(), "mismatched-tips");
+    assert_eq!(fail.peer(), Some(*PEER));
+    assert_eq!(fail.refname(), Some(name.as_bstr()));
+    assert_eq!(
+        fail.tips(),
+        Some((ObjectId::null_sha1(), ObjectId::null_sha1()))
+    );
+}
+
+#[test]
+fn no_peer_of_owned_refname() {
+    let fail = Validation::StrangeOrPrunable(BString::from("refs/heads/main"));
+    assert_eq!(fail.code(), "strange-or-prunable");
+    assert_eq!(fail.peer(), None);
+    assert_eq!(fail.tips(), None);
+}
+
+#[test]
+fn peer_without_refname() {
+    let fail = Validation::MissingSigRefs(*PEER);
+    assert_eq!(fail.code(), "missing-sigrefs");
+    assert_eq!(fail.peer(), Some(*PEER));
+    assert_eq!(fail.refname(), None);
+}
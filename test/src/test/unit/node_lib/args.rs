@@ -14,13 +14,23 @@ use structopt::StructOpt as _;
 
 use librad::{
     net::Network,
-    profile::{ProfileId, RadHome},
+    profile::{
+        petnames::{PeerOrPetname, Petnames},
+        ProfileId,
+        RadHome,
+    },
+    PeerId,
 };
 
 use node_lib::args::{
     self,
+    ApiArgs,
     Args,
+    AutosyncArgs,
     Bootstrap,
+    ControlArgs,
+    FeedArgs,
+    HttpArgs,
     KeyArgs,
     MetricsArgs,
     MetricsProvider,
@@ -30,6 +40,7 @@ use node_lib::args::{
     TrackingArgs,
     TrackingMode,
 };
+use node_lib::tracking::Tracker;
 
 #[test]
 fn defaults() -> Result<()> {
@@ -90,6 +101,166 @@ fn bootstraps() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn api_listen() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--api-listen", "127.0.0.1:8777",
+            "--api-token-file", "/run/linkd/api.token",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            api: ApiArgs {
+                enable: false,
+                listen: Some(SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::new(127, 0, 0, 1),
+                    8777
+                ))),
+                token_file: Some(PathBuf::from("/run/linkd/api.token")),
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn api_loopback() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--api",
+            "--api-token-file", "/run/linkd/api.token",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            api: ApiArgs {
+                enable: true,
+                listen: None,
+                token_file: Some(PathBuf::from("/run/linkd/api.token")),
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn autosync() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--autosync",
+            "--autosync-max-concurrent", "2",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            autosync: AutosyncArgs {
+                enable: true,
+                max_concurrent: Some(2),
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn control_socket() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--control-socket", "/run/linkd/control.sock",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            control: ControlArgs {
+                socket: Some(PathBuf::from("/run/linkd/control.sock")),
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn feed_listen() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--feed-listen", "127.0.0.1:8778",
+            "--api-token-file", "/run/linkd/api.token",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            api: ApiArgs {
+                token_file: Some(PathBuf::from("/run/linkd/api.token")),
+                ..Default::default()
+            },
+            feed: FeedArgs {
+                listen: Some(SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::new(127, 0, 0, 1),
+                    8778
+                ))),
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn http_listen() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--http-listen", "0.0.0.0:8080",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            http: HttpArgs {
+                listen: Some(SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::new(0, 0, 0, 0),
+                    8080
+                ))),
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
 #[test]
 fn metrics_graphite() -> Result<()> {
     #[rustfmt::skip]
@@ -187,6 +358,30 @@ fn protocol_network() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn protocol_tracking_cutoff() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--protocol-tracking-cutoff", "3",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                tracking_cutoff: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
 #[test]
 fn rad_home() -> Result<()> {
     #[rustfmt::skip]
@@ -408,9 +603,71 @@ fn tracking() -> Result<()> {
                 mode: Some(TrackingMode::Selected),
                 peer_ids: vec!["hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc".parse()?,],
                 urns: vec!["rad:git:hnrkb39fr6f4jj59nfiq7tfd9aznirdu7b59o".parse()?],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    #[rustfmt::skip]
+    let parsed = Args::from_iter_safe(vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--track", "open",
+            "--track-max-urns", "100",
+            "--track-max-peers", "5",
+            "--block-peer-id", "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc",
+            "--block-urn", "rad:git:hnrkb39fr6f4jj59nfiq7tfd9aznirdu7b59o",
+    ])?;
+    assert_eq!(
+        parsed,
+        Args {
+            tracking: TrackingArgs {
+                mode: Some(TrackingMode::Open),
+                max_urns: Some(100),
+                max_peers: Some(5),
+                blocked_peer_ids: vec!["hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc".parse()?],
+                blocked_urns: vec!["rad:git:hnrkb39fr6f4jj59nfiq7tfd9aznirdu7b59o".parse()?],
+                ..Default::default()
             },
             ..Default::default()
         }
     );
     Ok(())
 }
+
+#[test]
+fn tracking_petnames() -> Result<()> {
+    let peer_id: PeerId = "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc".parse()?;
+    let mut petnames = Petnames::default();
+    petnames.insert("seed".parse()?, peer_id)?;
+
+    #[rustfmt::skip]
+    let parsed = Args::from_iter_safe(vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--track", "selected",
+            "--track-peer-id", "seed",
+    ])?;
+    assert_eq!(parsed.tracking.peer_ids, vec!["seed".parse::<PeerOrPetname>()?]);
+    match Tracker::from_args(&parsed.tracking, &petnames)? {
+        Some(Tracker::Selected { peer_ids, .. }) => {
+            assert_eq!(peer_ids.into_iter().collect::<Vec<_>>(), vec![peer_id])
+        },
+        _ => panic!("expected selected tracking"),
+    }
+    assert!(Tracker::from_args(&parsed.tracking, &Petnames::default()).is_err());
+
+    #[rustfmt::skip]
+    let parsed = Args::from_iter_safe(vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--track", "open",
+            "--block-peer-id", "seed",
+    ])?;
+    match Tracker::from_args(&parsed.tracking, &petnames)? {
+        Some(Tracker::Open { blocked, .. }) => assert!(blocked.peer_ids.contains(&peer_id)),
+        _ => panic!("expected open tracking"),
+    }
+    Ok(())
+}
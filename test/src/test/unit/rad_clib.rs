@@ -5,3 +5,5 @@
 
 #[cfg(unix)]
 mod keys;
+#[cfg(unix)]
+mod passphrase;
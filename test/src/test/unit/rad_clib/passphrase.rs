@@ -0,0 +1,53 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{fs::File, io::Write as _, os::unix::io::FromRawFd as _};
+
+use tempfile::tempdir;
+
+use librad::{
+    crypto::{keystore::Keystore as _, SecretKey},
+    profile::{Profile, ProfileId, RadHome},
+    PeerId,
+};
+use rad_clib::keys::{file_storage, passphrase::Passphrases};
+
+/// Write `lines` to a pipe, returning the read end of it.
+fn pipe(lines: &str) -> anyhow::Result<i32> {
+    let (read, write) = nix::unistd::pipe()?;
+    let mut write = unsafe { File::from_raw_fd(write) };
+    write.write_all(lines.as_bytes())?;
+    Ok(read)
+}
+
+#[test]
+fn passphrases_from_fd() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let home = RadHome::Root(temp.path().to_path_buf());
+    let profile = Profile::from_home(&home, Some(ProfileId::new()))?;
+    let key = SecretKey::new();
+
+    let passphrases = Passphrases::new(Some(pipe("42\n42\n")?))?;
+    file_storage(&profile, passphrases.choose()).put_key(key.clone())?;
+    let unlocked = file_storage(&profile, passphrases.unlock()).get_key()?;
+    assert_eq!(PeerId::from(unlocked.secret_key), PeerId::from(key));
+
+    Ok(())
+}
+
+#[test]
+fn passphrases_from_fd_mismatch() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let home = RadHome::Root(temp.path().to_path_buf());
+    let profile = Profile::from_home(&home, Some(ProfileId::new()))?;
+
+    let passphrases = Passphrases::new(Some(pipe("42\n43\n")?))?;
+    file_storage(&profile, passphrases.choose()).put_key(SecretKey::new())?;
+    assert!(file_storage(&profile, passphrases.unlock())
+        .get_key()
+        .is_err());
+
+    Ok(())
+}
@@ -4,3 +4,14 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 mod args;
+mod config;
+mod exit;
+mod external;
+mod graph;
+mod identity;
+mod inspect;
+mod key;
+mod ls;
+mod migrate;
+mod petname;
+mod seed;
@@ -7,7 +7,8 @@ use std::env;
 
 use rusty_fork::rusty_fork_test;
 
-use rad_exe::cli::args::*;
+use rad_clib::{keys::ssh::SshAuthSock, ser::OutputFormat};
+use rad_exe::{cli::args::*, logging::LogFormat};
 
 #[test]
 fn rad_profile_first_precedence() {
@@ -22,6 +23,9 @@ fn rad_profile_first_precedence() {
             rad_ssh_auth_sock: Default::default(),
             rad_quiet: false,
             rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
         },
         command: Command::External(external),
     };
@@ -48,6 +52,9 @@ fn rad_profile_first_precedence_multiple_externals() {
             rad_ssh_auth_sock: Default::default(),
             rad_quiet: false,
             rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
         },
         command: Command::External(external),
     };
@@ -76,6 +83,9 @@ fn rad_profile_second_precedence() {
         rad_ssh_auth_sock: Default::default(),
             rad_quiet: false,
             rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
         },
         command: Command::External(external),
     };
@@ -103,6 +113,9 @@ fn rad_profile_second_precedence_multiple() {
         rad_ssh_auth_sock: Default::default(),
             rad_quiet: false,
             rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
         },
         command: Command::External(external),
     };
@@ -124,6 +137,9 @@ fn rad_profile_env_var() {
         rad_ssh_auth_sock: Default::default(),
             rad_quiet: false,
             rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
         },
         command: Command::External(external),
     };
@@ -145,6 +161,9 @@ fn rad_verbose_second_precedence() {
         rad_ssh_auth_sock: Default::default(),
             rad_quiet: false,
             rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
         },
         command: Command::External(external),
     };
@@ -166,6 +185,9 @@ fn rad_verbose_env_var() {
         rad_ssh_auth_sock: Default::default(),
             rad_quiet: false,
             rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
         },
         command: Command::External(external),
     };
@@ -176,6 +198,119 @@ fn rad_verbose_env_var() {
         assert!(index.is_some());
     }
 }
+
+#[test]
+fn rad_format_env_var() {
+    env::set_var("RAD_FORMAT", "json");
+    let external = vec!["xxx".to_string()];
+    let args = Args {
+        global: Global {
+            rad_profile: None,
+            rad_ssh_auth_sock: Default::default(),
+            rad_quiet: false,
+            rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
+        },
+        command: Command::External(external),
+    };
+
+    assert_eq!(args.global.format().unwrap(), OutputFormat::Json);
+    let args = sanitise_globals(args);
+    if let Command::External(external) = args.command {
+        let index = find_arg(RAD_FORMAT_ARG, &external);
+        assert_eq!("json", external[index.unwrap() + 1]);
+    }
+}
+
+#[test]
+fn rad_format_invalid_env_var() {
+    env::set_var("RAD_FORMAT", "yaml");
+    let global = Global {
+        rad_profile: None,
+        rad_ssh_auth_sock: Default::default(),
+        rad_quiet: false,
+        rad_verbose: false,
+        rad_format: None,
+        rad_passphrase_fd: None,
+        rad_log: None,
+    };
+
+    let err = global.format().unwrap_err();
+    assert_eq!(err.var, "RAD_FORMAT");
+    assert_eq!(err.value, "yaml");
+}
+
+#[test]
+fn rad_ssh_auth_sock_env_var() {
+    env::set_var("RAD_SSH_AUTH_SOCK", "/tmp/agent.sock");
+    let external = vec!["xxx".to_string()];
+    let args = Args {
+        global: Global {
+            rad_profile: None,
+            rad_ssh_auth_sock: None,
+            rad_quiet: false,
+            rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
+        },
+        command: Command::External(external),
+    };
+
+    assert_eq!(
+        args.global.ssh_auth_sock(),
+        SshAuthSock::Uds("/tmp/agent.sock".into())
+    );
+    let args = sanitise_globals(args);
+    if let Command::External(external) = args.command {
+        let index = find_arg(RAD_SSH_AUTH_SOCK_ARG, &external);
+        assert_eq!("/tmp/agent.sock", external[index.unwrap() + 1]);
+    }
+}
+#[test]
+fn rad_log_env_var() {
+    env::set_var("RAD_LOG", "json");
+    let external = vec!["xxx".to_string()];
+    let args = Args {
+        global: Global {
+            rad_profile: None,
+            rad_ssh_auth_sock: Default::default(),
+            rad_quiet: false,
+            rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
+        },
+        command: Command::External(external),
+    };
+
+    assert_eq!(args.global.log().unwrap(), LogFormat::Json);
+    let args = sanitise_globals(args);
+    if let Command::External(external) = args.command {
+        let index = find_arg(RAD_LOG_ARG, &external);
+        assert_eq!("json", external[index.unwrap() + 1]);
+    }
+}
+
+#[test]
+fn rad_log_invalid_env_var() {
+    env::set_var("RAD_LOG", "verbose");
+    let global = Global {
+        rad_profile: None,
+        rad_ssh_auth_sock: Default::default(),
+        rad_quiet: false,
+        rad_verbose: false,
+        rad_format: None,
+        rad_passphrase_fd: None,
+        rad_log: None,
+    };
+
+    let err = global.log().unwrap_err();
+    assert_eq!(err.var, "RAD_LOG");
+    assert_eq!(err.value, "verbose");
+}
 /* end rusty_fork! */
 }
 
@@ -188,6 +323,9 @@ fn rad_verbose_first_precedence() {
             rad_ssh_auth_sock: Default::default(),
             rad_quiet: false,
             rad_verbose: true,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
         },
         command: Command::External(external),
     };
@@ -198,3 +336,84 @@ fn rad_verbose_first_precedence() {
         assert!(index.is_some());
     }
 }
+
+#[test]
+fn rad_format_first_precedence() {
+    let external = vec![
+        "xxx".to_string(),
+        RAD_FORMAT_ARG.to_string(),
+        "plain".to_string(),
+    ];
+    let args = Args {
+        global: Global {
+            rad_profile: None,
+            rad_ssh_auth_sock: Default::default(),
+            rad_quiet: false,
+            rad_verbose: false,
+            rad_format: Some(OutputFormat::Json),
+            rad_passphrase_fd: None,
+            rad_log: None,
+        },
+        command: Command::External(external),
+    };
+
+    let args = sanitise_globals(args);
+    if let Command::External(external) = args.command {
+        let index = find_arg(RAD_FORMAT_ARG, &external);
+        assert_eq!("json", external[index.unwrap() + 1]);
+    }
+}
+
+#[test]
+fn rad_log_first_precedence() {
+    let external = vec![
+        "xxx".to_string(),
+        RAD_LOG_ARG.to_string(),
+        "pretty".to_string(),
+    ];
+    let args = Args {
+        global: Global {
+            rad_profile: None,
+            rad_ssh_auth_sock: Default::default(),
+            rad_quiet: false,
+            rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: Some(LogFormat::Compact),
+        },
+        command: Command::External(external),
+    };
+
+    let args = sanitise_globals(args);
+    if let Command::External(external) = args.command {
+        let index = find_arg(RAD_LOG_ARG, &external);
+        assert_eq!("compact", external[index.unwrap() + 1]);
+    }
+}
+
+#[test]
+fn rad_ssh_auth_sock_first_precedence() {
+    let external = vec![
+        "xxx".to_string(),
+        RAD_SSH_AUTH_SOCK_ARG.to_string(),
+        "/tmp/other.sock".to_string(),
+    ];
+    let args = Args {
+        global: Global {
+            rad_profile: None,
+            rad_ssh_auth_sock: Some(SshAuthSock::Uds("/tmp/agent.sock".into())),
+            rad_quiet: false,
+            rad_verbose: false,
+            rad_format: None,
+            rad_passphrase_fd: None,
+            rad_log: None,
+        },
+        command: Command::External(external),
+    };
+
+    let args = sanitise_globals(args);
+    if let Command::External(external) = args.command {
+        let index = find_arg(RAD_SSH_AUTH_SOCK_ARG, &external);
+        assert_eq!("/tmp/agent.sock", external[index.unwrap() + 1]);
+    }
+}
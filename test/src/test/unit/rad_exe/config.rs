@@ -0,0 +1,90 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fs;
+
+use tempfile::tempdir;
+
+use librad::profile::{Profile, ProfileId, RadHome};
+use rad_clib::ser::OutputFormat;
+use rad_exe::config::{self, Config};
+
+#[test]
+fn missing_files_are_empty() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let home = RadHome::Root(temp.path().to_path_buf());
+    let config = Config::load(&home, Some(&ProfileId::new()))?;
+
+    assert_eq!(config.profile, None);
+    assert_eq!(config.format, None);
+    assert_eq!(config.log, None);
+    Ok(())
+}
+
+#[test]
+fn profile_overrides_user() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let home = RadHome::Root(temp.path().to_path_buf());
+    let profile = Profile::from_home(&home, Some(ProfileId::new()))?;
+
+    fs::write(
+        config::user_file(&home)?,
+        "format = \"json\"\nverbose = true\nquiet = true\n",
+    )?;
+    fs::write(
+        config::profile_file(&profile),
+        "format = \"plain\"\nquiet = false\n",
+    )?;
+
+    let config = Config::load(&home, Some(profile.id()))?;
+    assert_eq!(config.format, Some(OutputFormat::Plain));
+    assert_eq!(config.verbose, Some(true));
+    assert_eq!(config.quiet, Some(false));
+    Ok(())
+}
+
+#[test]
+fn profile_from_user_config() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let home = RadHome::Root(temp.path().to_path_buf());
+    let profile = Profile::from_home(&home, Some(ProfileId::new()))?;
+
+    fs::write(
+        config::user_file(&home)?,
+        format!("profile = \"{}\"\n", profile.id()),
+    )?;
+    fs::write(config::profile_file(&profile), "format = \"json\"\n")?;
+
+    let config = Config::load(&home, None)?;
+    assert_eq!(config.profile.as_ref(), Some(profile.id()));
+    assert_eq!(config.format, Some(OutputFormat::Json));
+    Ok(())
+}
+
+#[test]
+fn invalid_values_are_errors() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let home = RadHome::Root(temp.path().to_path_buf());
+    fs::write(config::user_file(&home)?, "format = \"yaml\"\n")?;
+
+    assert!(Config::load(&home, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn malformed_degrades_to_defaults() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let home = RadHome::Root(temp.path().to_path_buf());
+
+    fs::write(config::user_file(&home)?, "format = \"yaml\"\nverbose = true\n")?;
+    let config = Config::load_or_default(&home, None);
+    assert_eq!(config.format, None);
+    assert_eq!(config.verbose, None);
+
+    fs::write(config::user_file(&home)?, "format = ")?;
+    let config = Config::load_or_default(&home, None);
+    assert_eq!(config.format, None);
+    Ok(())
+}
@@ -0,0 +1,32 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{net::peer, SecretKey};
+use rad_exe::{exit::Code, sync};
+
+#[test]
+fn no_connection_is_retryable() {
+    let peer = SecretKey::new().into();
+    let err = anyhow::Error::from(peer::error::Replicate::NoConnection(peer));
+    assert_eq!(Code::of(&err), Code::Retryable);
+    assert_eq!(i32::from(Code::Retryable), 8);
+}
+
+#[test]
+fn replicate_during_sync_is_classified() {
+    let peer = SecretKey::new().into();
+    let err = anyhow::Error::from(sync::Error::from(peer::error::Replicate::NoConnection(
+        peer,
+    )));
+    assert_eq!(Code::of(&err), Code::Retryable);
+}
+
+#[test]
+fn context_does_not_hide_the_class() {
+    let peer = SecretKey::new().into();
+    let err = anyhow::Error::from(peer::error::Replicate::NoConnection(peer))
+        .context("failed to sync");
+    assert_eq!(Code::of(&err), Code::Retryable);
+}
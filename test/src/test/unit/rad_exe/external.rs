@@ -0,0 +1,116 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{env, fs, os::unix::fs::PermissionsExt as _, path::Path};
+
+use rusty_fork::rusty_fork_test;
+use tempfile::tempdir;
+
+use librad::{
+    git::storage::Storage,
+    profile::{Profile, ProfileId, RadHome, RAD_HOME},
+    PeerId,
+    SecretKey,
+};
+use rad_exe::{
+    cli::args::{RAD_FORMAT_ARG, RAD_PROFILE_ARG, RAD_QUIET_ARG},
+    external::{self, RAD_GIT_DIR, RAD_PEER_ID},
+};
+
+fn touch(path: &Path, mode: u32) {
+    fs::write(path, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+fn lookup<'a>(env: &'a [(&'static str, String)], key: &str) -> Option<&'a str> {
+    env.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str())
+}
+
+rusty_fork_test! {
+#[test]
+fn commands_are_found_on_the_path() {
+    let first = tempdir().unwrap();
+    let second = tempdir().unwrap();
+    touch(&first.path().join("rad-foo"), 0o755);
+    touch(&first.path().join("rad-bar"), 0o644);
+    touch(&first.path().join("radicle"), 0o755);
+    touch(&second.path().join("rad-foo"), 0o755);
+    touch(&second.path().join("rad-baz"), 0o755);
+    touch(&second.path().join("rad-"), 0o755);
+    env::set_var(
+        "PATH",
+        env::join_paths(&[first.path(), Path::new("/does/not/exist"), second.path()]).unwrap(),
+    );
+
+    let found = external::commands()
+        .into_iter()
+        .map(|ext| (ext.name, ext.path))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        vec![
+            ("baz".to_owned(), second.path().join("rad-baz")),
+            // The first one on the `PATH` is the one which gets executed
+            ("foo".to_owned(), first.path().join("rad-foo")),
+        ]
+    );
+}
+}
+
+rusty_fork_test! {
+#[test]
+fn globals_are_exported() {
+    let home = tempdir().unwrap();
+    env::set_var(RAD_HOME, home.path());
+
+    let args = [RAD_QUIET_ARG, RAD_FORMAT_ARG, "json", "sub", "--flag"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>();
+    let env = external::environment(&args);
+    assert_eq!(lookup(&env, "RAD_QUIET"), Some("1"));
+    assert_eq!(lookup(&env, "RAD_FORMAT"), Some("json"));
+    assert_eq!(lookup(&env, "RAD_VERBOSE"), None);
+    // There is no active profile to export
+    assert_eq!(lookup(&env, "RAD_PROFILE"), None);
+}
+}
+
+rusty_fork_test! {
+#[test]
+fn profile_is_exported() {
+    let home = tempdir().unwrap();
+    env::set_var(RAD_HOME, home.path());
+    let id = ProfileId::new();
+    let profile = Profile::from_home(&RadHome::Root(home.path().to_path_buf()), Some(id.clone()))
+        .unwrap();
+    let key = SecretKey::new();
+    Storage::open(profile.paths(), key.clone()).unwrap();
+
+    let env = external::environment(&[RAD_PROFILE_ARG.to_owned(), id.to_string()]);
+    assert_eq!(lookup(&env, "RAD_PROFILE"), Some(id.to_string().as_str()));
+    assert_eq!(
+        lookup(&env, RAD_GIT_DIR),
+        Some(profile.paths().git_dir().display().to_string().as_str())
+    );
+    assert_eq!(
+        lookup(&env, RAD_PEER_ID),
+        Some(PeerId::from(key).to_string().as_str())
+    );
+}
+}
+
+rusty_fork_test! {
+#[test]
+fn unknown_profile_is_passed_on() {
+    let home = tempdir().unwrap();
+    env::set_var(RAD_HOME, home.path());
+    let id = ProfileId::new();
+
+    let env = external::environment(&[RAD_PROFILE_ARG.to_owned(), id.to_string()]);
+    assert_eq!(lookup(&env, "RAD_PROFILE"), Some(id.to_string().as_str()));
+    assert_eq!(lookup(&env, RAD_GIT_DIR), None);
+}
+}
@@ -0,0 +1,71 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{git::tracking, PeerId, SecretKey};
+use rad_exe::graph::{self, Edge, Label, Node};
+
+use crate::librad::git::{self, storage::storage};
+
+#[test]
+fn tracking_and_delegations() -> anyhow::Result<()> {
+    let key = SecretKey::new();
+    let local_peer = PeerId::from(key.clone());
+    let storage = storage(key.clone());
+    let whoami = git::dylan(&storage, &key)?;
+    let urn = whoami.urn();
+    let remote_peer = PeerId::from(SecretKey::new());
+
+    assert!(tracking::track(
+        &*storage,
+        &urn,
+        Some(remote_peer),
+        tracking::Config::default(),
+        tracking::policy::Track::Any,
+    )?
+    .is_ok());
+
+    let graph = graph::graph(&*storage, None)?;
+    assert_eq!(
+        graph.nodes.get(&local_peer.to_string()),
+        Some(&Node::Peer {
+            id: local_peer,
+            local: true
+        })
+    );
+    assert_eq!(
+        graph.nodes.get(&remote_peer.to_string()),
+        Some(&Node::Peer {
+            id: remote_peer,
+            local: false
+        })
+    );
+    assert_eq!(
+        graph.nodes.get(&urn.to_string()),
+        Some(&Node::Identity {
+            urn: urn.clone(),
+            kind: Some("person"),
+            name: Some("dylan".to_owned())
+        })
+    );
+    assert!(graph.edges.contains(&Edge {
+        from: local_peer.to_string(),
+        to: urn.to_string(),
+        label: Label::Delegates,
+    }));
+    assert!(graph.edges.contains(&Edge {
+        from: local_peer.to_string(),
+        to: remote_peer.to_string(),
+        label: Label::Tracks { urn: urn.clone() },
+    }));
+
+    let dot = graph.to_string();
+    assert!(dot.starts_with("digraph radicle {"));
+    assert!(dot.contains(&format!(
+        "\"{}\" -> \"{}\" [label=\"delegates\"];",
+        local_peer, urn
+    )));
+
+    Ok(())
+}
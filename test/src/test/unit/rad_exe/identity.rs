@@ -0,0 +1,24 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{git::Urn, PeerId, SecretKey};
+use rad_exe::identity::Delegate;
+
+#[test]
+fn parse_delegate() {
+    let peer_id = PeerId::from(SecretKey::from_seed([42; 32]));
+    assert_eq!(
+        peer_id.to_string().parse::<Delegate>(),
+        Ok(Delegate::Key(peer_id))
+    );
+
+    let urn = Urn::new(git_ext::Oid::from(git2::Oid::zero()));
+    assert_eq!(
+        urn.to_string().parse::<Delegate>(),
+        Ok(Delegate::Person(urn))
+    );
+
+    assert!("neither".parse::<Delegate>().is_err());
+}
@@ -0,0 +1,84 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{git::tracking, PeerId, SecretKey};
+use rad_exe::inspect::{self, Delegation};
+
+use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+#[test]
+fn project_is_inspected() -> anyhow::Result<()> {
+    let key = SecretKey::new();
+    let local_peer = PeerId::from(key.clone());
+    let storage = storage(key);
+    let proj = TestProject::create(&storage)?;
+    let urn = proj.project.urn();
+    let remote_peer = PeerId::from(SecretKey::new());
+    assert!(tracking::track(
+        &*storage,
+        &urn,
+        Some(remote_peer),
+        tracking::Config::default(),
+        tracking::policy::Track::Any,
+    )?
+    .is_ok());
+
+    let inspection = inspect::inspect(&*storage, &urn)?.expect("project exists");
+    assert_eq!(inspection.kind, "project");
+    assert_eq!(inspection.content_id, proj.project.content_id.to_string());
+    assert!(matches!(
+        inspection.delegations.as_slice(),
+        [Delegation::Person { urn, keys }] if *urn == proj.owner.urn() && keys == &[local_peer]
+    ));
+    assert_eq!(inspection.tracked, vec![remote_peer]);
+    assert_eq!(
+        inspection
+            .rad_refs
+            .get(&format!("refs/namespaces/{}/refs/rad/id", urn.encode_id())),
+        Some(&inspection.content_id)
+    );
+
+    let plain = inspection.to_string();
+    assert!(plain.starts_with(&format!("urn: {}\nkind: project\n", urn)));
+    assert!(plain.contains(&format!(
+        "delegations:\n  {}\n    {}\n",
+        proj.owner.urn(),
+        local_peer
+    )));
+    assert!(plain.contains(&format!("tracked:\n  {}\nrefs:\n", remote_peer)));
+
+    let json = serde_json::to_value(&inspection)?;
+    assert_eq!(json["urn"], urn.to_string());
+    assert_eq!(json["kind"], "project");
+    assert_eq!(
+        json["tracked"],
+        serde_json::json!([remote_peer.to_string()])
+    );
+    assert_eq!(
+        json["delegations"],
+        serde_json::json!([{
+            "person": {
+                "urn": proj.owner.urn().to_string(),
+                "keys": [local_peer.to_string()],
+            }
+        }])
+    );
+    assert!(json["payload"]
+        .to_string()
+        .contains("\"name\":\"radicle-link\""));
+
+    Ok(())
+}
+
+#[test]
+fn unknown_urn_is_none() -> anyhow::Result<()> {
+    let theirs = storage(SecretKey::new());
+    let proj = TestProject::create(&theirs)?;
+    let ours = storage(SecretKey::new());
+
+    assert!(inspect::inspect(&*ours, &proj.project.urn())?.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fs;
+
+use librad::{
+    crypto::keystore::{
+        crypto::{Pwhash, KDF_PARAMS_TEST},
+        pinentry::SecUtf8,
+        Keystore as _,
+    },
+    profile::{Profile, RadHome},
+    PeerId,
+    SecretKey,
+};
+use rad_exe::key::{self, pgp, ssh_public_key};
+
+fn pass(phrase: &str) -> Pwhash<SecUtf8> {
+    Pwhash::new(SecUtf8::from(phrase.as_bytes().to_vec()), *KDF_PARAMS_TEST)
+}
+
+#[test]
+fn ssh_public_key_format() {
+    let peer_id = PeerId::from(SecretKey::from_seed([42; 32]));
+    let encoded = ssh_public_key(&peer_id);
+    let parts = encoded.split(' ').collect::<Vec<_>>();
+
+    assert_eq!(parts.len(), 3);
+    assert_eq!(parts[0], "ssh-ed25519");
+    // The base64 encoding of the blob always starts with the encoded key type.
+    assert!(parts[1].starts_with("AAAAC3NzaC1lZDI1NTE5AAAAI"));
+    assert_eq!(parts[2], format!("rad:{}", peer_id));
+}
+
+#[test]
+fn pgp_public_key_is_deterministic() {
+    let key = SecretKey::from_seed([42; 32]);
+    let armored = pgp::armored_public_key(&key, "rad:test", 1_600_000_000);
+
+    assert!(armored.starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n"));
+    assert!(armored.ends_with("-----END PGP PUBLIC KEY BLOCK-----\n"));
+    assert!(armored.lines().all(|line| line.len() <= 64));
+    assert_eq!(
+        armored,
+        pgp::armored_public_key(&key, "rad:test", 1_600_000_000)
+    );
+}
+
+#[test]
+fn created_survives_reencrypt() {
+    let home = tempfile::tempdir().unwrap();
+    let home = RadHome::Root(home.path().to_path_buf());
+    let (profile, _) = Profile::create(&home, pass("42")).unwrap();
+
+    let created = key::created(&profile).unwrap();
+    let exported = key::pgp_public_key(&profile, pass("42"), "rad:test").unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    key::reencrypt(&profile, pass("42"), pass("43")).unwrap();
+
+    assert_eq!(created, key::created(&profile).unwrap());
+    assert_eq!(
+        exported,
+        key::pgp_public_key(&profile, pass("43"), "rad:test").unwrap()
+    );
+}
+
+#[test]
+fn rotate_records_created() {
+    let home = tempfile::tempdir().unwrap();
+    let home = RadHome::Root(home.path().to_path_buf());
+    let (profile, _) = Profile::create(&home, pass("42")).unwrap();
+
+    let fake = 1_600_000_000;
+    fs::write(
+        profile.paths().keys_dir().join("librad.key.created"),
+        fake.to_string(),
+    )
+    .unwrap();
+    key::rotate(&profile, pass("42"), pass("43")).unwrap();
+    assert!(key::created(&profile).unwrap() > fake);
+}
+
+#[test]
+fn failed_rotate_keeps_previous_key() {
+    let home = tempfile::tempdir().unwrap();
+    let home = RadHome::Root(home.path().to_path_buf());
+    let (profile, previous) = Profile::create(&home, pass("42")).unwrap();
+
+    // Re-keying the storage fails if there is no storage.
+    fs::remove_dir_all(profile.paths().git_dir()).unwrap();
+    assert!(key::rotate(&profile, pass("42"), pass("43")).is_err());
+
+    let key = profile
+        .key_storage(pass("42"))
+        .get_key()
+        .unwrap()
+        .secret_key;
+    assert_eq!(previous, PeerId::from(key));
+}
@@ -0,0 +1,64 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{git::storage::Storage, SecretKey};
+use rad_exe::ls;
+
+use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+/// Commit to the default branch, `next`, of `proj` at a fixed time.
+fn commit(storage: &Storage, proj: &TestProject) -> anyhow::Result<git2::Oid> {
+    let repo = git2::Repository::open(storage.path())?;
+    let tree = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+    let author = git2::Signature::new(
+        "alice",
+        "alice@example.com",
+        &git2::Time::new(1_600_000_000, 0),
+    )?;
+    let head = format!(
+        "refs/namespaces/{}/refs/heads/next",
+        proj.project.urn().encode_id()
+    );
+    Ok(repo.commit(Some(&head), &author, &author, "initial", &tree, &[])?)
+}
+
+#[test]
+fn projects_are_listed() -> anyhow::Result<()> {
+    let storage = storage(SecretKey::new());
+    let proj = TestProject::create(&storage)?;
+    let urn = proj.project.urn();
+
+    // Only the project is listed, not its owner
+    let listings = ls::list(&*storage)?;
+    assert_eq!(listings.len(), 1);
+    assert_eq!(listings[0].urn, urn);
+    assert_eq!(listings[0].head, None);
+    assert!(listings[0]
+        .to_string()
+        .starts_with(&format!("{}\tradicle-link\t1\tnext\t-\t", urn)));
+
+    let head = commit(&storage, &proj)?;
+    let listings = ls::list(&*storage)?;
+    assert_eq!(
+        listings[0].to_string(),
+        format!(
+            "{}\tradicle-link\t1\tnext\t{}\t2020-09-13T12:26:40Z",
+            urn, head
+        )
+    );
+    assert_eq!(
+        serde_json::to_value(&listings)?,
+        serde_json::json!([{
+            "urn": urn.to_string(),
+            "name": "radicle-link",
+            "delegates": 1,
+            "default_branch": "next",
+            "head": head.to_string(),
+            "updated": 1_600_000_000,
+        }])
+    );
+
+    Ok(())
+}
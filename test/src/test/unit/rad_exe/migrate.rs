@@ -0,0 +1,177 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::io;
+
+use librad::{
+    crypto::keystore::{
+        crypto::{Pwhash, KDF_PARAMS_TEST},
+        pinentry::SecUtf8,
+        Keystore as _,
+    },
+    git::{
+        identities::{self, local},
+        storage::Storage,
+    },
+    identities::{delegation, payload},
+    profile::{Profile, RadHome},
+};
+use rad_exe::migrate::{self, archive, migrate, restore};
+
+fn pass(phrase: &str) -> Pwhash<SecUtf8> {
+    Pwhash::new(SecUtf8::from(phrase.as_bytes().to_vec()), *KDF_PARAMS_TEST)
+}
+
+#[test]
+fn archive_and_restore() {
+    let from = tempfile::tempdir().unwrap();
+    let from = RadHome::Root(from.path().to_path_buf());
+    let to = tempfile::tempdir().unwrap();
+    let to = RadHome::Root(to.path().to_path_buf());
+
+    let (profile, peer_id) = Profile::create(&from, pass("42")).unwrap();
+    profile
+        .settings()
+        .update(|settings| settings.seeds.push("seed".to_owned()))
+        .unwrap();
+
+    let mut buf = Vec::new();
+    let manifest = archive(&profile, &mut buf).unwrap();
+    assert_eq!(peer_id, manifest.peer_id);
+
+    let restored = restore(&to, buf.as_slice(), pass("42")).unwrap();
+    assert_eq!(peer_id, restored.peer_id);
+    assert_eq!(profile.id(), restored.profile.id());
+    assert_eq!(
+        vec!["seed".to_owned()],
+        restored.profile.settings().load().unwrap().seeds
+    );
+
+    let err = restore(&to, buf.as_slice(), pass("42")).unwrap_err();
+    assert!(matches!(err, migrate::Error::Exists(_)));
+}
+
+#[test]
+fn restore_with_wrong_passphrase() {
+    let from = tempfile::tempdir().unwrap();
+    let from = RadHome::Root(from.path().to_path_buf());
+    let to = tempfile::tempdir().unwrap();
+    let to = RadHome::Root(to.path().to_path_buf());
+
+    let (profile, _) = Profile::create(&from, pass("42")).unwrap();
+    let mut buf = Vec::new();
+    archive(&profile, &mut buf).unwrap();
+
+    assert!(restore(&to, buf.as_slice(), pass("43")).is_err());
+    assert!(Profile::get(&to, profile.id().clone()).unwrap().is_none());
+}
+
+/// Archive a fresh profile, appending an entry of `kind` at `path` to it.
+fn archive_with(kind: tar::EntryType, path: &str, link: Option<&str>) -> (RadHome, Vec<u8>) {
+    let from = tempfile::tempdir().unwrap();
+    let from = RadHome::Root(from.path().to_path_buf());
+    let (profile, _) = Profile::create(&from, pass("42")).unwrap();
+    let mut good = Vec::new();
+    archive(&profile, &mut good).unwrap();
+
+    let mut tar = tar::Builder::new(Vec::new());
+    for entry in tar::Archive::new(good.as_slice()).entries().unwrap() {
+        let entry = entry.unwrap();
+        let mut header = entry.header().clone();
+        tar.append(&mut header, entry).unwrap();
+    }
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(kind);
+    header.set_size(0);
+    header.set_mode(0o644);
+    if let Some(link) = link {
+        header.set_link_name(link).unwrap();
+    }
+    header.set_cksum();
+    tar.append_data(&mut header, path, io::empty()).unwrap();
+
+    let to = tempfile::tempdir().unwrap();
+    (RadHome::Root(to.into_path()), tar.into_inner().unwrap())
+}
+
+#[test]
+fn restore_rejects_symlinks() {
+    let (to, buf) = archive_with(tar::EntryType::Symlink, "git/evil", Some("/tmp"));
+    let err = restore(&to, buf.as_slice(), pass("42")).unwrap_err();
+    assert!(matches!(err, migrate::Error::Malformed(_)));
+    assert!(Profile::list(&to).unwrap().is_empty());
+}
+
+#[test]
+fn restore_rejects_hardlinks() {
+    let (to, buf) = archive_with(tar::EntryType::Link, "keys/evil", Some("/etc/passwd"));
+    let err = restore(&to, buf.as_slice(), pass("42")).unwrap_err();
+    assert!(matches!(err, migrate::Error::Malformed(_)));
+}
+
+#[test]
+fn restore_rejects_unknown_config() {
+    let (to, buf) = archive_with(tar::EntryType::Regular, "config/active_profile", None);
+    let err = restore(&to, buf.as_slice(), pass("42")).unwrap_err();
+    assert!(matches!(err, migrate::Error::Malformed(_)));
+}
+
+#[test]
+fn rotate_keeps_identity_valid() {
+    let home = tempfile::tempdir().unwrap();
+    let home = RadHome::Root(home.path().to_path_buf());
+    let (profile, previous) = Profile::create(&home, pass("42")).unwrap();
+
+    let urn = {
+        let key = profile
+            .key_storage(pass("42"))
+            .get_key()
+            .unwrap()
+            .secret_key;
+        let storage = Storage::open(profile.paths(), key).unwrap();
+        let person = identities::person::create(
+            &storage,
+            payload::Person {
+                name: "dylan".into(),
+            },
+            delegation::Direct::new(*previous.as_public_key()),
+        )
+        .unwrap();
+        let whoami = local::load(&storage, person.urn()).unwrap().unwrap();
+        storage.config().unwrap().set_user(whoami).unwrap();
+        person.urn()
+    };
+
+    let migrated = migrate(&profile, pass("42"), pass("43"), true).unwrap();
+    assert_ne!(previous, migrated.peer_id);
+    assert_eq!(Some(&urn), migrated.identity.as_ref());
+
+    let key = profile
+        .key_storage(pass("43"))
+        .get_key()
+        .unwrap()
+        .secret_key;
+    let storage = Storage::open(profile.paths(), key).unwrap();
+    let whoami = local::default(&storage).unwrap().unwrap();
+    assert_eq!(urn, whoami.urn());
+    assert!(whoami
+        .delegations()
+        .contains(migrated.peer_id.as_public_key()));
+    assert!(whoami.delegations().contains(previous.as_public_key()));
+    assert!(!profile.paths().keys_dir().join("librad.key.staged").exists());
+}
+
+#[test]
+fn rotate_without_identity() {
+    let home = tempfile::tempdir().unwrap();
+    let home = RadHome::Root(home.path().to_path_buf());
+    let (profile, previous) = Profile::create(&home, pass("42")).unwrap();
+
+    let migrated = migrate(&profile, pass("42"), pass("43"), true).unwrap();
+    assert_ne!(previous, migrated.peer_id);
+    assert_eq!(None, migrated.identity);
+    assert!(!profile.paths().keys_dir().join("librad.key.staged").exists());
+    assert!(migrate::verify(&profile, pass("43")).is_ok());
+}
@@ -0,0 +1,38 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::Urn,
+    profile::{Profile, RadHome},
+};
+use rad_exe::petname::UrnOrPetname;
+
+#[test]
+fn urn_or_petname() {
+    let tmp = tempfile::tempdir().unwrap();
+    let profile = Profile::new(&RadHome::Root(tmp.path().to_path_buf())).unwrap();
+    let urn: Urn = "rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo"
+        .parse()
+        .unwrap();
+    profile
+        .petnames()
+        .update(|petnames| petnames.insert("link".parse()?, urn.clone()))
+        .unwrap();
+
+    let arg = urn.to_string().parse::<UrnOrPetname>().unwrap();
+    assert_eq!(UrnOrPetname::Urn(urn.clone()), arg);
+    assert_eq!(urn, arg.resolve(&profile).unwrap());
+
+    let arg = "link".parse::<UrnOrPetname>().unwrap();
+    assert!(matches!(arg, UrnOrPetname::Petname(_)));
+    assert_eq!(urn, arg.resolve(&profile).unwrap());
+
+    assert!("unknown"
+        .parse::<UrnOrPetname>()
+        .unwrap()
+        .resolve(&profile)
+        .is_err());
+    assert!("not a name".parse::<UrnOrPetname>().is_err());
+}
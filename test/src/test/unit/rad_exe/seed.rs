@@ -0,0 +1,92 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fs;
+
+use tempfile::tempdir;
+
+use librad::{
+    profile::{Profile, ProfileId},
+    PeerId,
+    SecretKey,
+};
+use rad_exe::{config, seed};
+
+#[test]
+fn add_list_remove() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let profile = Profile::from_root(temp.path(), Some(ProfileId::new()))?;
+    let peer_id = PeerId::from(SecretKey::from_seed([42; 32]));
+    let seed = format!("{}@seed.example.com:12345", peer_id);
+
+    assert!(seed::list(&profile)?.is_empty());
+    assert!(seed::add(&profile, &seed)?);
+    assert!(!seed::add(&profile, &seed)?);
+    assert_eq!(seed::list(&profile)?, vec![seed.clone()]);
+
+    assert_eq!(seed::remove(&profile, &peer_id.to_string())?, vec![seed]);
+    assert!(seed::list(&profile)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn preserves_other_settings() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let profile = Profile::from_root(temp.path(), Some(ProfileId::new()))?;
+    profile
+        .settings()
+        .update(|settings| settings.network.network = Some("devnet".to_owned()))?;
+
+    let peer_id = PeerId::from(SecretKey::from_seed([42; 32]));
+    seed::add(&profile, &format!("{}@127.0.0.1:12345", peer_id))?;
+
+    let settings = profile.settings().load()?;
+    assert_eq!(settings.network.network.as_deref(), Some("devnet"));
+    assert_eq!(settings.seeds.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn ignores_config_file() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let profile = Profile::from_root(temp.path(), Some(ProfileId::new()))?;
+    let peer_id = PeerId::from(SecretKey::from_seed([42; 32]));
+    fs::write(
+        profile.paths().config_dir().join(config::CONFIG_FILE),
+        format!("seeds = [\"{}@127.0.0.1:12345\"]\n", peer_id),
+    )?;
+
+    assert!(seed::list(&profile)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn rejects_invalid_seeds() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let profile = Profile::from_root(temp.path(), Some(ProfileId::new()))?;
+
+    assert!(seed::add(&profile, "seed.example.com:12345").is_err());
+    assert!(seed::add(&profile, "notapeer@seed.example.com:12345").is_err());
+    Ok(())
+}
+
+#[test]
+fn resolves_petnames() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let profile = Profile::from_root(temp.path(), Some(ProfileId::new()))?;
+    let peer_id = PeerId::from(SecretKey::from_seed([42; 32]));
+    profile
+        .petnames()
+        .update(|petnames| petnames.insert("seed".parse()?, peer_id))?;
+
+    assert!(seed::add(&profile, "seed@seed.example.com:12345")?);
+    let seed = format!("{}@seed.example.com:12345", peer_id);
+    assert_eq!(seed::list(&profile)?, vec![seed.clone()]);
+
+    assert_eq!(seed::remove(&profile, "seed")?, vec![seed]);
+    assert!(seed::list(&profile)?.is_empty());
+    Ok(())
+}